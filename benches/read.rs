@@ -2,6 +2,9 @@
 
 extern crate test;
 
+use gpx::parser::extensions::{EmptyExtensions, GarminTrackPointExtensions, RawExtensions};
+use gpx::{ExtensionsMode, ParseOptions};
+
 const NITER: usize = 100;
 
 #[bench]
@@ -14,3 +17,174 @@ fn bench_read(bencher: &mut test::Bencher) {
         }
     });
 }
+
+/// geocaches gives a document of waypoints with extensions the size of
+/// those of geocaching pocket queries, with a cache description and logs.
+fn geocaches() -> Vec<u8> {
+    let mut gpx = String::from(
+        r#"<gpx version="1.1" creator="bench" xmlns:groundspeak="http://www.groundspeak.com/cache/1/0/1">"#,
+    );
+    for cache in 0..200 {
+        gpx.push_str(&format!(
+            r#"<wpt lat="47.{0:04}" lon="8.{0:04}"><name>GC{0}</name><extensions><groundspeak:cache id="{0}">"#,
+            cache
+        ));
+        gpx.push_str("<groundspeak:long_description html=\"True\">");
+        gpx.push_str(&"&lt;p&gt;A walk along the river to the old mill.&lt;/p&gt;".repeat(40));
+        gpx.push_str("</groundspeak:long_description><groundspeak:logs>");
+        for log in 0..10 {
+            gpx.push_str(&format!(
+                "<groundspeak:log id=\"{}\"><groundspeak:type>Found it</groundspeak:type><groundspeak:text>{}</groundspeak:text></groundspeak:log>",
+                log,
+                "Thanks for the cache! ".repeat(10)
+            ));
+        }
+        gpx.push_str("</groundspeak:logs></groundspeak:cache></extensions></wpt>");
+    }
+    gpx.push_str("</gpx>");
+    gpx.into_bytes()
+}
+
+#[bench]
+fn bench_read_extensions_raw(bencher: &mut test::Bencher) {
+    let gpx_bytes = geocaches();
+
+    bencher.iter(|| {
+        let gpx: gpx::Gpx<RawExtensions> = gpx::read_with_extensions(&gpx_bytes[..]).unwrap();
+        test::black_box(gpx);
+    });
+}
+
+#[bench]
+fn bench_read_extensions_skipped(bencher: &mut test::Bencher) {
+    let gpx_bytes = geocaches();
+    let options = ParseOptions::strict().with_extensions(ExtensionsMode::Skip);
+
+    bencher.iter(|| {
+        let gpx: gpx::Gpx<RawExtensions> = gpx::read_with_options(&gpx_bytes[..], options.clone()).unwrap();
+        test::black_box(gpx);
+    });
+}
+
+#[bench]
+fn bench_read_extensions_empty(bencher: &mut test::Bencher) {
+    let gpx_bytes = geocaches();
+
+    bencher.iter(|| {
+        test::black_box(gpx::read(&gpx_bytes[..]).unwrap());
+    });
+}
+
+/// track_points gives a document of a million track points with the usual
+/// leaf elements.
+fn track_points() -> Vec<u8> {
+    let mut gpx = String::from(r#"<gpx version="1.1" creator="bench"><trk><trkseg>"#);
+    for point in 0..1_000_000 {
+        gpx.push_str(&format!(
+            r#"<trkpt lat="47.{0:06}" lon="8.{0:06}"><ele>{1}.5</ele><time>2020-01-01T00:00:00Z</time><hdop>1.2</hdop></trkpt>"#,
+            point,
+            point % 1000
+        ));
+    }
+    gpx.push_str("</trkseg></trk></gpx>");
+    gpx.into_bytes()
+}
+
+#[bench]
+fn bench_read_track_points(bencher: &mut test::Bencher) {
+    let gpx_bytes = track_points();
+
+    bencher.iter(|| {
+        test::black_box(gpx::read(&gpx_bytes[..]).unwrap());
+    });
+}
+
+#[bench]
+fn bench_read_track_points_with_capacity_hint(bencher: &mut test::Bencher) {
+    let gpx_bytes = track_points();
+    let options = ParseOptions::strict().with_points_capacity_hint(1_000_000);
+
+    bencher.iter(|| {
+        let gpx: gpx::Gpx<EmptyExtensions> = gpx::read_with_options(&gpx_bytes[..], options.clone()).unwrap();
+        test::black_box(gpx);
+    });
+}
+
+/// garmin_repeated gives the Garmin activity fixture with its track repeated
+/// a thousand times, some 370 MB. Run with the `quick-xml` feature to
+/// compare the XML parsers; every iteration takes seconds, so run it alone
+/// with `cargo bench --features quick-xml garmin_repeated`.
+fn garmin_repeated() -> Vec<u8> {
+    let fixture = include_str!("../tests/fixtures/garmin-activity.gpx");
+    let start = fixture.find("<trk>").unwrap();
+    let end = fixture.rfind("</gpx>").unwrap();
+    let mut gpx = String::with_capacity(fixture.len() * 1000);
+    gpx.push_str(&fixture[..start]);
+    for _ in 0..1000 {
+        gpx.push_str(&fixture[start..end]);
+    }
+    gpx.push_str(&fixture[end..]);
+    gpx.into_bytes()
+}
+
+#[bench]
+fn bench_read_garmin_repeated(bencher: &mut test::Bencher) {
+    let gpx_bytes = garmin_repeated();
+    bencher.bytes = gpx_bytes.len() as u64;
+
+    bencher.iter(|| {
+        let gpx: gpx::Gpx<GarminTrackPointExtensions> = gpx::read_with_extensions(&gpx_bytes[..]).unwrap();
+        test::black_box(gpx);
+    });
+}
+
+#[cfg(feature = "quick-xml")]
+#[bench]
+fn bench_read_garmin_repeated_quick_xml(bencher: &mut test::Bencher) {
+    let gpx_bytes = garmin_repeated();
+    bencher.bytes = gpx_bytes.len() as u64;
+    let options = ParseOptions::strict().with_xml_parser(gpx::XmlParser::QuickXml);
+
+    bencher.iter(|| {
+        let gpx: gpx::Gpx<GarminTrackPointExtensions> = gpx::read_with_options(&gpx_bytes[..], options.clone()).unwrap();
+        test::black_box(gpx);
+    });
+}
+
+/// segments gives a document of 500 track segments of 500 points, as
+/// devices starting a segment at every pause write.
+fn segments() -> Vec<u8> {
+    let mut gpx = String::from(r#"<gpx version="1.1" creator="bench"><trk>"#);
+    for segment in 0..500 {
+        gpx.push_str("<trkseg>");
+        for point in 0..500 {
+            gpx.push_str(&format!(
+                r#"<trkpt lat="47.{0:03}{1:03}" lon="8.{0:03}{1:03}"><ele>{1}.5</ele><time>2020-01-01T00:00:00Z</time></trkpt>"#,
+                segment, point
+            ));
+        }
+        gpx.push_str("</trkseg>");
+    }
+    gpx.push_str("</trk></gpx>");
+    gpx.into_bytes()
+}
+
+#[bench]
+fn bench_read_segments(bencher: &mut test::Bencher) {
+    let gpx_bytes = segments();
+
+    bencher.iter(|| {
+        test::black_box(gpx::read(&gpx_bytes[..]).unwrap());
+    });
+}
+
+#[cfg(feature = "rayon")]
+#[bench]
+fn bench_read_segments_parallel(bencher: &mut test::Bencher) {
+    let gpx_bytes = segments();
+
+    bencher.iter(|| {
+        let gpx: gpx::Gpx<EmptyExtensions> = gpx::read_parallel(&gpx_bytes, ParseOptions::strict()).unwrap();
+        test::black_box(gpx);
+    });
+}
@@ -0,0 +1,28 @@
+//! read feeds arbitrary bytes to the reader, strictly and leniently, which
+//! must give a document or an error but never panic.
+
+#![no_main]
+
+use gpx::parser::extensions::{GarminExtensions, RawExtensions};
+use gpx::{read, read_with_options, ParseLimits, ParseOptions, XmlParser};
+use libfuzzer_sys::fuzz_target;
+
+#[cfg(not(feature = "quick-xml"))]
+const XML_PARSER: XmlParser = XmlParser::XmlRs;
+#[cfg(feature = "quick-xml")]
+const XML_PARSER: XmlParser = XmlParser::QuickXml;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = read(data);
+    #[cfg(feature = "quick-xml")]
+    let _ = read_with_options::<_, gpx::parser::extensions::EmptyExtensions>(data, ParseOptions::strict().with_xml_parser(XML_PARSER));
+
+    // Bound the points so that memory stays within what libFuzzer allows.
+    let limits = ParseLimits {
+        max_points: Some(100_000),
+        ..ParseLimits::default()
+    };
+    let lenient = ParseOptions::lenient().with_limits(limits).with_xml_parser(XML_PARSER);
+    let _ = read_with_options::<_, GarminExtensions>(data, lenient.clone());
+    let _ = read_with_options::<_, RawExtensions>(data, lenient.with_entity_repair());
+});
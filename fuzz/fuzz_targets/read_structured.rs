@@ -0,0 +1,112 @@
+//! read_structured mutates valid documents with fragments of GPX, such as
+//! numbers out of range and times that are not, so that the fuzzer gets past
+//! the XML parser to the GPX one sooner than from arbitrary bytes.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use gpx::parser::extensions::GarminExtensions;
+use gpx::{read, read_with_options, ParseOptions, XmlParser};
+use libfuzzer_sys::fuzz_target;
+
+#[cfg(not(feature = "quick-xml"))]
+const XML_PARSER: XmlParser = XmlParser::XmlRs;
+#[cfg(feature = "quick-xml")]
+const XML_PARSER: XmlParser = XmlParser::QuickXml;
+
+/// Small documents between them holding most of what GPX has.
+const SEEDS: [&str; 6] = [
+    include_str!("../../tests/fixtures/wikipedia_example.gpx"),
+    include_str!("../../tests/fixtures/gpx10_example.gpx"),
+    include_str!("../../tests/fixtures/with_personal_data.gpx"),
+    include_str!("../../tests/fixtures/with_accuracy.gpx"),
+    include_str!("../../tests/fixtures/styled_tracks.gpx"),
+    include_str!("../../tests/fixtures/viking_with_route_extensions.gpx"),
+];
+
+const FRAGMENTS: [&str; 26] = [
+    "<trkpt lat=\"",
+    "\" lon=\"",
+    "<time>",
+    "</time>",
+    "<ele>",
+    "<sat>",
+    "<year>",
+    "<extensions>",
+    "</trkseg>",
+    "<![CDATA[",
+    "]]>",
+    "&",
+    "&#x;",
+    "&#99999999;",
+    "-",
+    "NaN",
+    "inf",
+    "1e309",
+    "18446744073709551616",
+    "-9223372036854775809",
+    "2020-13-45T99:99:99Z",
+    "+99999-01-01T00:00:00Z",
+    "9999-12-31T23:59:59",
+    "-23:59",
+    "1565653500",
+    "\u{1F5FA}",
+];
+
+#[derive(Arbitrary, Debug)]
+enum Mutation {
+    Insert { at: u16, fragment: u8 },
+    Remove { at: u16, len: u8 },
+    Repeat { at: u16, len: u8, times: u8 },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    seed: u8,
+    lenient: bool,
+    mutations: Vec<Mutation>,
+}
+
+/// char_boundary moves `at` into `text` and back to the start of the
+/// character it is in.
+fn char_boundary(text: &str, at: usize) -> usize {
+    let mut at = at.min(text.len());
+    while !text.is_char_boundary(at) {
+        at -= 1;
+    }
+    at
+}
+
+fuzz_target!(|input: Input| {
+    let mut text = SEEDS[usize::from(input.seed) % SEEDS.len()].to_string();
+    for mutation in input.mutations {
+        match mutation {
+            Mutation::Insert { at, fragment } => {
+                let at = char_boundary(&text, usize::from(at));
+                text.insert_str(at, FRAGMENTS[usize::from(fragment) % FRAGMENTS.len()]);
+            }
+            Mutation::Remove { at, len } => {
+                let start = char_boundary(&text, usize::from(at));
+                let end = char_boundary(&text, start + usize::from(len));
+                if start < end {
+                    text.replace_range(start..end, "");
+                }
+            }
+            Mutation::Repeat { at, len, times } => {
+                let start = char_boundary(&text, usize::from(at));
+                let end = char_boundary(&text, start + usize::from(len));
+                if start < end && text.len() < 1 << 20 {
+                    let repeated = text[start..end].repeat(usize::from(times % 16));
+                    text.insert_str(end, &repeated);
+                }
+            }
+        }
+    }
+
+    let _ = read(text.as_bytes());
+    #[cfg(feature = "quick-xml")]
+    let _ = read_with_options::<_, gpx::parser::extensions::EmptyExtensions>(text.as_bytes(), ParseOptions::strict().with_xml_parser(XML_PARSER));
+    if input.lenient {
+        let _ = read_with_options::<_, GarminExtensions>(text.as_bytes(), ParseOptions::lenient().with_xml_parser(XML_PARSER));
+    }
+});
@@ -0,0 +1,275 @@
+//! activity recognizes the activity of a track from the vocabularies apps
+//! use for its type.
+
+use std::fmt;
+
+use crate::parser::extensions::WaypointExtensions;
+use crate::Track;
+
+/// A common activity, as recorded in the [`type_`](Track::type_) of tracks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Activity {
+    Running,
+    TrailRunning,
+    Walking,
+    Hiking,
+    Mountaineering,
+    Cycling,
+    MountainBiking,
+    GravelCycling,
+    EBiking,
+    Swimming,
+    Rowing,
+    Kayaking,
+    Canoeing,
+    StandUpPaddling,
+    Sailing,
+    Skiing,
+    CrossCountrySkiing,
+    BackcountrySkiing,
+    Snowshoeing,
+    InlineSkating,
+    Driving,
+    Motorcycling,
+    Flying,
+}
+
+/// Every activity, in declaration order.
+const ACTIVITIES: [Activity; 23] = [
+    Activity::Running,
+    Activity::TrailRunning,
+    Activity::Walking,
+    Activity::Hiking,
+    Activity::Mountaineering,
+    Activity::Cycling,
+    Activity::MountainBiking,
+    Activity::GravelCycling,
+    Activity::EBiking,
+    Activity::Swimming,
+    Activity::Rowing,
+    Activity::Kayaking,
+    Activity::Canoeing,
+    Activity::StandUpPaddling,
+    Activity::Sailing,
+    Activity::Skiing,
+    Activity::CrossCountrySkiing,
+    Activity::BackcountrySkiing,
+    Activity::Snowshoeing,
+    Activity::InlineSkating,
+    Activity::Driving,
+    Activity::Motorcycling,
+    Activity::Flying,
+];
+
+/// Other spellings apps use, normalized as by [`normalize`].
+const SYNONYMS: [(&str, Activity); 52] = [
+    ("run", Activity::Running),
+    ("jog", Activity::Running),
+    ("jogging", Activity::Running),
+    ("trailrun", Activity::TrailRunning),
+    ("walk", Activity::Walking),
+    ("hike", Activity::Hiking),
+    ("alpinetour", Activity::Mountaineering),
+    ("alpineclimbing", Activity::Mountaineering),
+    ("bike", Activity::Cycling),
+    ("biking", Activity::Cycling),
+    ("bicycle", Activity::Cycling),
+    ("cycle", Activity::Cycling),
+    ("ride", Activity::Cycling),
+    ("roadbiking", Activity::Cycling),
+    ("roadcycling", Activity::Cycling),
+    ("racebike", Activity::Cycling),
+    ("touringbicycle", Activity::Cycling),
+    ("mtb", Activity::MountainBiking),
+    ("mtbeasy", Activity::MountainBiking),
+    ("mountainbike", Activity::MountainBiking),
+    ("mountainbikeride", Activity::MountainBiking),
+    ("gravelride", Activity::GravelCycling),
+    ("gravelbiking", Activity::GravelCycling),
+    ("ebike", Activity::EBiking),
+    ("ebikeride", Activity::EBiking),
+    ("etouringbicycle", Activity::EBiking),
+    ("swim", Activity::Swimming),
+    ("openwaterswimming", Activity::Swimming),
+    ("row", Activity::Rowing),
+    ("kayak", Activity::Kayaking),
+    ("canoe", Activity::Canoeing),
+    ("sup", Activity::StandUpPaddling),
+    ("standuppaddle", Activity::StandUpPaddling),
+    ("standuppaddleboarding", Activity::StandUpPaddling),
+    ("sail", Activity::Sailing),
+    ("ski", Activity::Skiing),
+    ("alpineski", Activity::Skiing),
+    ("alpineskiing", Activity::Skiing),
+    ("downhillskiing", Activity::Skiing),
+    ("resortskiing", Activity::Skiing),
+    ("nordicski", Activity::CrossCountrySkiing),
+    ("nordicskiing", Activity::CrossCountrySkiing),
+    ("xcski", Activity::CrossCountrySkiing),
+    ("backcountryski", Activity::BackcountrySkiing),
+    ("skitouring", Activity::BackcountrySkiing),
+    ("snowshoe", Activity::Snowshoeing),
+    ("inlineskate", Activity::InlineSkating),
+    ("drive", Activity::Driving),
+    ("car", Activity::Driving),
+    ("motorcycle", Activity::Motorcycling),
+    ("fly", Activity::Flying),
+    ("flight", Activity::Flying),
+];
+
+/// normalize lowercases `value` and removes its underscores, hyphens and
+/// spaces, so that "Trail_Running" and "trail-running" match.
+fn normalize(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !matches!(c, '_' | '-' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl Activity {
+    /// Gives the canonical spelling of this activity, in snake case as Garmin
+    /// writes it: "running", "trail_running" and so on.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Activity::Running => "running",
+            Activity::TrailRunning => "trail_running",
+            Activity::Walking => "walking",
+            Activity::Hiking => "hiking",
+            Activity::Mountaineering => "mountaineering",
+            Activity::Cycling => "cycling",
+            Activity::MountainBiking => "mountain_biking",
+            Activity::GravelCycling => "gravel_cycling",
+            Activity::EBiking => "e_biking",
+            Activity::Swimming => "swimming",
+            Activity::Rowing => "rowing",
+            Activity::Kayaking => "kayaking",
+            Activity::Canoeing => "canoeing",
+            Activity::StandUpPaddling => "stand_up_paddling",
+            Activity::Sailing => "sailing",
+            Activity::Skiing => "skiing",
+            Activity::CrossCountrySkiing => "cross_country_skiing",
+            Activity::BackcountrySkiing => "backcountry_skiing",
+            Activity::Snowshoeing => "snowshoeing",
+            Activity::InlineSkating => "inline_skating",
+            Activity::Driving => "driving",
+            Activity::Motorcycling => "motorcycling",
+            Activity::Flying => "flying",
+        }
+    }
+
+    /// Recognizes the activity spelled `value`, ignoring case, underscores,
+    /// hyphens and spaces, and knowing the spellings of Strava, Garmin,
+    /// Komoot and others: "Run", "trail_running", "MountainBikeRide", "mtb".
+    pub fn parse(value: &str) -> Option<Activity> {
+        let value = normalize(value);
+        ACTIVITIES
+            .iter()
+            .copied()
+            .find(|activity| normalize(activity.as_str()) == value)
+            .or_else(|| {
+                SYNONYMS
+                    .iter()
+                    .find(|(synonym, _)| *synonym == value)
+                    .map(|&(_, activity)| activity)
+            })
+    }
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Gives the activity the [`type_`](Track::type_) of this track spells,
+    /// see [`Activity::parse`], or `None` when it has no type or one that is
+    /// not a known activity.
+    ///
+    /// ```
+    /// use gpx::{Activity, Track};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut track: Track<EmptyExtensions> = Track::new();
+    /// track.type_ = Some(String::from("MountainBikeRide"));
+    /// assert_eq!(track.activity(), Some(Activity::MountainBiking));
+    ///
+    /// track.set_activity(Activity::TrailRunning);
+    /// assert_eq!(track.type_.as_deref(), Some("trail_running"));
+    /// ```
+    pub fn activity(&self) -> Option<Activity> {
+        self.type_.as_deref().and_then(Activity::parse)
+    }
+
+    /// Sets the [`type_`](Track::type_) of this track to the canonical
+    /// spelling of `activity`.
+    pub fn set_activity(&mut self, activity: Activity) {
+        self.type_ = Some(String::from(activity.as_str()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize, Activity, ACTIVITIES, SYNONYMS};
+
+    #[test]
+    fn parse_vendor_spellings() {
+        let spellings = [
+            // Garmin
+            ("running", Activity::Running),
+            ("trail_running", Activity::TrailRunning),
+            ("hiking", Activity::Hiking),
+            ("cycling", Activity::Cycling),
+            ("mountain_biking", Activity::MountainBiking),
+            ("open_water_swimming", Activity::Swimming),
+            ("resort_skiing", Activity::Skiing),
+            // Strava
+            ("Run", Activity::Running),
+            ("TrailRun", Activity::TrailRunning),
+            ("Ride", Activity::Cycling),
+            ("MountainBikeRide", Activity::MountainBiking),
+            ("GravelRide", Activity::GravelCycling),
+            ("EBikeRide", Activity::EBiking),
+            ("AlpineSki", Activity::Skiing),
+            ("NordicSki", Activity::CrossCountrySkiing),
+            ("BackcountrySki", Activity::BackcountrySkiing),
+            ("StandUpPaddling", Activity::StandUpPaddling),
+            ("InlineSkate", Activity::InlineSkating),
+            // Komoot
+            ("hike", Activity::Hiking),
+            ("jogging", Activity::Running),
+            ("mtb", Activity::MountainBiking),
+            ("mtb_easy", Activity::MountainBiking),
+            ("racebike", Activity::Cycling),
+            ("touringbicycle", Activity::Cycling),
+            ("e_touringbicycle", Activity::EBiking),
+            // Outdooractive
+            ("alpineTour", Activity::Mountaineering),
+            // Others
+            ("Biking", Activity::Cycling),
+            ("bike", Activity::Cycling),
+            ("Trail-Running", Activity::TrailRunning),
+            ("cross country skiing", Activity::CrossCountrySkiing),
+        ];
+        for (spelling, activity) in spellings {
+            assert_eq!(Activity::parse(spelling), Some(activity), "{}", spelling);
+        }
+
+        assert_eq!(Activity::parse("trackOnWeb"), None);
+        assert_eq!(Activity::parse(""), None);
+    }
+
+    #[test]
+    fn canonical_spellings_round_trip() {
+        for activity in ACTIVITIES {
+            assert_eq!(Activity::parse(activity.as_str()), Some(activity));
+            assert_eq!(activity.to_string(), activity.as_str());
+        }
+        // Synonyms are normalized, and do not shadow a canonical spelling.
+        for (synonym, _) in SYNONYMS {
+            assert_eq!(normalize(synonym), synonym);
+            assert!(ACTIVITIES.iter().all(|activity| normalize(activity.as_str()) != synonym));
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! canonical puts the children of a document in a canonical order, so that
+//! diffs between exports of the same data are meaningful.
+
+use std::cmp::Ordering;
+
+use time::OffsetDateTime;
+
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Gpx, Link, Track};
+
+/// by_option orders `Some` values as `order` does, before `None` ones.
+fn by_option<T>(a: &Option<T>, b: &Option<T>, order: impl Fn(&T, &T) -> Ordering) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => order(a, b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// start gives the earliest time of the points of `track`.
+fn start<E: WaypointExtensions + Default>(track: &Track<E>) -> Option<OffsetDateTime> {
+    track.segments.iter().filter_map(|segment| segment.time_span()).map(|(start, _)| start).min()
+}
+
+/// canonical_links removes the duplicates from `links`, keeping the first,
+/// and sorts the rest by href.
+fn canonical_links(links: &mut Vec<Link>) {
+    let mut kept: Vec<Link> = Vec::with_capacity(links.len());
+    for link in links.drain(..) {
+        if !kept.contains(&link) {
+            kept.push(link);
+        }
+    }
+    kept.sort_by(|a, b| a.href.cmp(&b.href));
+    *links = kept;
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Puts the children of this document in a canonical order, which together
+    /// with the deterministic writer makes for reproducible output:
+    ///
+    /// - waypoints by name,
+    /// - routes by number, then by name,
+    /// - tracks by the time of their earliest point,
+    /// - the links of the metadata, waypoints, routes, tracks and their points
+    ///   by href, with duplicates removed.
+    ///
+    /// Names and hrefs compare byte-wise, not by locale, and children lacking
+    /// what they are ordered by come last. Children that tie keep their
+    /// order, and the points of routes and track segments are left as they
+    /// are. This order is part of the API: it will not change between
+    /// versions.
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{Gpx, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut gpx: Gpx<EmptyExtensions> = Gpx::default();
+    /// for name in [Some("Summit"), None, Some("Camp")] {
+    ///     let mut waypoint = Waypoint::new(Point::new(0.0, 0.0));
+    ///     waypoint.name = name.map(String::from);
+    ///     gpx.waypoints.push(waypoint);
+    /// }
+    ///
+    /// gpx.canonical_order();
+    /// let names: Vec<_> = gpx.waypoints.iter().map(|waypoint| waypoint.name.as_deref()).collect();
+    /// assert_eq!(names, [Some("Camp"), Some("Summit"), None]);
+    /// ```
+    pub fn canonical_order(&mut self) {
+        self.waypoints.sort_by(|a, b| by_option(&a.name, &b.name, Ord::cmp));
+        self.routes.sort_by(|a, b| {
+            by_option(&a.number, &b.number, Ord::cmp).then_with(|| by_option(&a.name, &b.name, Ord::cmp))
+        });
+        // Find the start times once, rather than in every comparison.
+        let mut tracks: Vec<_> = self.tracks.drain(..).map(|track| (start(&track), track)).collect();
+        tracks.sort_by(|a, b| by_option(&a.0, &b.0, Ord::cmp));
+        self.tracks = tracks.into_iter().map(|(_, track)| track).collect();
+
+        if let Some(metadata) = &mut self.metadata {
+            canonical_links(&mut metadata.links);
+        }
+        for waypoint in &mut self.waypoints {
+            canonical_links(&mut waypoint.links);
+        }
+        for route in &mut self.routes {
+            canonical_links(&mut route.links);
+            for point in &mut route.points {
+                canonical_links(&mut point.links);
+            }
+        }
+        for track in &mut self.tracks {
+            canonical_links(&mut track.links);
+            for point in track.segments.iter_mut().flat_map(|segment| &mut segment.points) {
+                canonical_links(&mut point.links);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Link;
+
+    use super::canonical_links;
+
+    fn link(href: &str, text: Option<&str>) -> Link {
+        Link {
+            href: String::from(href),
+            text: text.map(String::from),
+            type_: None,
+        }
+    }
+
+    #[test]
+    fn canonical_links_dedup_and_sort() {
+        let mut links = vec![
+            link("https://b.example", None),
+            link("https://a.example", Some("second")),
+            link("https://b.example", None),
+            link("https://a.example", Some("first")),
+        ];
+        canonical_links(&mut links);
+        assert_eq!(
+            links,
+            [
+                link("https://a.example", Some("second")),
+                link("https://a.example", Some("first")),
+                link("https://b.example", None),
+            ]
+        );
+    }
+}
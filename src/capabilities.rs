@@ -0,0 +1,121 @@
+//! capabilities describes what this build of the crate supports, for
+//! programs that report it to their own users.
+
+#[cfg(feature = "use-serde")]
+use serde::Serialize;
+
+use crate::extensions::KnownSchema;
+use crate::GpxVersion;
+
+/// The optional cargo features of the crate, with whether this build has
+/// them.
+const FEATURES: [(&str, bool); 10] = [
+    ("use-serde", cfg!(feature = "use-serde")),
+    ("geodesic", cfg!(feature = "geodesic")),
+    ("geojson", cfg!(feature = "geojson")),
+    ("encoding", cfg!(feature = "encoding")),
+    ("nmea", cfg!(feature = "nmea")),
+    ("tcx", cfg!(feature = "tcx")),
+    ("quick-xml", cfg!(feature = "quick-xml")),
+    ("tracing", cfg!(feature = "tracing")),
+    ("rayon", cfg!(feature = "rayon")),
+    ("wasm", cfg!(feature = "wasm")),
+];
+
+/// An extension schema that the crate reads, see
+/// [`KnownSchema::consumer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "use-serde", derive(Serialize))]
+pub struct ExtensionSchema {
+    /// The element of the extension, inside `<extensions>`.
+    pub element: &'static str,
+    /// The namespace of the element.
+    pub namespace: &'static str,
+    /// The type reading it, such as `GarminExtensions` in
+    /// `gpx::parser::extensions`, or `TrackStyle` for the styles of tracks.
+    pub consumer: &'static str,
+}
+
+/// What this build of the crate supports, see [`capabilities`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "use-serde", derive(Serialize))]
+pub struct Capabilities {
+    /// The version of the crate.
+    pub version: &'static str,
+    /// The optional cargo features this build has, such as `geojson`.
+    pub features: Vec<&'static str>,
+    /// The GPX versions read and written.
+    pub gpx_versions: Vec<GpxVersion>,
+    /// The extension schemas with built-in support. Any other extension can
+    /// be kept as it is with `RawExtensions`.
+    pub extensions: Vec<ExtensionSchema>,
+}
+
+/// Describes what this build of the crate supports: its version, optional
+/// features, GPX versions and extension schemas. Derived from the build
+/// itself, it stays accurate as features are turned on and off.
+///
+/// ```
+/// use gpx::{capabilities, GpxVersion};
+///
+/// let capabilities = capabilities();
+/// assert_eq!(capabilities.gpx_versions, [GpxVersion::Gpx10, GpxVersion::Gpx11]);
+/// assert_eq!(capabilities.features.contains(&"geojson"), cfg!(feature = "geojson"));
+/// ```
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        features: FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(feature, _)| *feature)
+            .collect(),
+        gpx_versions: vec![GpxVersion::Gpx10, GpxVersion::Gpx11],
+        extensions: KnownSchema::ALL.iter().filter_map(|schema| schema.consumer()).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extensions::KnownSchema;
+
+    use super::capabilities;
+
+    #[test]
+    fn capabilities_of_test_build() {
+        let capabilities = capabilities();
+        assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+
+        #[cfg(feature = "geojson")]
+        assert!(capabilities.features.contains(&"geojson"));
+        #[cfg(not(feature = "geojson"))]
+        assert!(!capabilities.features.contains(&"geojson"));
+        #[cfg(feature = "tcx")]
+        assert!(capabilities.features.contains(&"tcx"));
+        #[cfg(not(feature = "tcx"))]
+        assert!(!capabilities.features.contains(&"tcx"));
+        #[cfg(not(any(
+            feature = "use-serde",
+            feature = "geodesic",
+            feature = "geojson",
+            feature = "encoding",
+            feature = "nmea",
+            feature = "tcx",
+            feature = "quick-xml",
+            feature = "tracing",
+            feature = "rayon",
+            feature = "wasm"
+        )))]
+        assert!(capabilities.features.is_empty());
+
+        // Every schema with a consumer is listed, in the order of the registry.
+        let schemas: Vec<_> = capabilities
+            .extensions
+            .iter()
+            .map(|extension| KnownSchema::from_namespace(extension.namespace).unwrap())
+            .collect();
+        let expected: Vec<_> = KnownSchema::ALL.iter().copied().filter(|schema| schema.has_consumer()).collect();
+        assert_eq!(schemas, expected);
+        assert!(capabilities.extensions.iter().any(|extension| extension.consumer == "TrackStyle"));
+    }
+}
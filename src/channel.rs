@@ -0,0 +1,425 @@
+//! channel computes series of values derived from the points of a track,
+//! such as speed or grade, keyed by the points they belong to so they can be
+//! kept aligned with the track as points are removed.
+
+use std::collections::BTreeMap;
+
+use time::{Duration, OffsetDateTime};
+
+use crate::distance::haversine_distance;
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Track, Waypoint};
+
+/// Refers to a point of a track by the index of its segment and its index in
+/// that segment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TrackPointRef {
+    pub segment: usize,
+    pub index: usize,
+}
+
+/// What [`Track::compute_channel`] gives its function for every point.
+#[derive(Clone, Copy, Debug)]
+pub struct ChannelCtx<'a, E: WaypointExtensions + Default> {
+    /// Where the point is in the track.
+    pub at: TrackPointRef,
+    pub point: &'a Waypoint<E>,
+    /// The point before it in its segment, if any.
+    pub previous: Option<&'a Waypoint<E>>,
+    /// The point after it in its segment, if any.
+    pub next: Option<&'a Waypoint<E>>,
+    /// The haversine distance in meters from the first point of the track,
+    /// not counting the gaps between segments.
+    pub distance: f64,
+    /// The time since the earliest time of the points of the track, `None`
+    /// when the point has no time.
+    pub elapsed: Option<Duration>,
+}
+
+/// The edits made to the points of a track, for [`Channel::remap`] to follow.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EditLog {
+    /// The points removed, in the order they were, each referring to the
+    /// track as it was when it was removed.
+    removals: Vec<TrackPointRef>,
+}
+
+impl EditLog {
+    pub fn new() -> EditLog {
+        Default::default()
+    }
+
+    /// Records the removal of the point `at`, for edits made other than by
+    /// [`Track::remove_point`].
+    pub fn record_removal(&mut self, at: TrackPointRef) {
+        self.removals.push(at);
+    }
+
+    /// The points removed, in the order they were.
+    pub fn removals(&self) -> &[TrackPointRef] {
+        &self.removals
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.removals.is_empty()
+    }
+
+    /// Forgets the edits, once every channel has been remapped.
+    pub fn clear(&mut self) {
+        self.removals.clear();
+    }
+}
+
+/// A named series of values derived from the points of a track, each keyed
+/// by the [`TrackPointRef`] of its point.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Channel {
+    name: String,
+    /// The values, ordered by point.
+    values: Vec<(TrackPointRef, Option<f64>)>,
+}
+
+impl Channel {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gives the value of the point `at`, `None` when it has none or there is
+    /// no such point.
+    pub fn get(&self, at: TrackPointRef) -> Option<f64> {
+        let index = self.values.binary_search_by_key(&at, |&(at, _)| at).ok()?;
+        self.values[index].1
+    }
+
+    /// Gives the points and their values, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (TrackPointRef, Option<f64>)> + '_ {
+        self.values.iter().copied()
+    }
+
+    /// How many points the channel has values for, `None` or not.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Follows the edits of `log`, which must be those made since the
+    /// channel was computed or last remapped: the values of removed points
+    /// are dropped, and those of the points after them in their segments are
+    /// keyed by their new places. Gives how many values were dropped.
+    ///
+    /// The values of the points left are kept as they were, even those that
+    /// depended on a removed neighbor.
+    pub fn remap(&mut self, log: &EditLog) -> usize {
+        let mut removals: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for removal in log.removals() {
+            removals.entry(removal.segment).or_default().push(removal.index);
+        }
+        // The points of each segment the channel has values for, which bound
+        // the removals that can refer to them.
+        let mut lens: BTreeMap<usize, usize> = BTreeMap::new();
+        for (at, _) in &self.values {
+            let len = lens.entry(at.segment).or_default();
+            *len = (*len).max(at.index + 1);
+        }
+        let removed: BTreeMap<_, _> = removals
+            .into_iter()
+            .filter_map(|(segment, indices)| Some((segment, removed_points(*lens.get(&segment)?, &indices))))
+            .collect();
+
+        // The values are ordered by point, so the removed points before each
+        // are counted once for all the values of its segment.
+        let before = self.values.len();
+        let mut segment = None;
+        let mut flags: &[bool] = &[];
+        let (mut counted, mut shift) = (0, 0);
+        self.values.retain_mut(|(at, _)| {
+            if segment != Some(at.segment) {
+                segment = Some(at.segment);
+                flags = removed.get(&at.segment).map_or(&[], Vec::as_slice);
+                counted = 0;
+                shift = 0;
+            }
+            let end = at.index.min(flags.len());
+            shift += flags[counted..end].iter().filter(|&&removed| removed).count();
+            counted = end;
+            if flags.get(at.index) == Some(&true) {
+                return false;
+            }
+            at.index -= shift;
+            true
+        });
+        before - self.values.len()
+    }
+}
+
+/// removed_points replays `removals`, each the index of a point in a segment
+/// of `len` points once the points of the removals before it were gone, and
+/// gives whether each point of the segment as it was at first was removed.
+/// Removals past the points left remove nothing.
+fn removed_points(len: usize, removals: &[usize]) -> Vec<bool> {
+    // A Fenwick tree counting the points left, so finding the point of a
+    // removal does not go through every point before it.
+    let mut left = vec![0; len + 1];
+    for i in 1..=len {
+        left[i] += 1;
+        let parent = i + (i & i.wrapping_neg());
+        if parent <= len {
+            left[parent] += left[i];
+        }
+    }
+    let top = match len {
+        0 => 0,
+        len => 1 << (usize::BITS - 1 - len.leading_zeros()),
+    };
+    let mut removed = vec![false; len];
+    let mut remaining = len;
+    for &index in removals {
+        if index >= remaining {
+            continue;
+        }
+        remaining -= 1;
+        // Find the point with `index` points left before it.
+        let (mut position, mut rank) = (0, index + 1);
+        let mut step = top;
+        while step > 0 {
+            if position + step <= len && left[position + step] < rank {
+                position += step;
+                rank -= left[position];
+            }
+            step >>= 1;
+        }
+        removed[position] = true;
+        let mut i = position + 1;
+        while i <= len {
+            left[i] -= 1;
+            i += i & i.wrapping_neg();
+        }
+    }
+    removed
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Computes the channel `name` with `f`, called for every point in order.
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{Track, TrackPointRef, TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut track: Track<EmptyExtensions> = Track::new();
+    /// track.segments.push(TrackSegment {
+    ///     points: vec![Waypoint::new(Point::new(0.0, 0.0)), Waypoint::new(Point::new(0.0, 0.001))],
+    /// });
+    ///
+    /// let distance = track.compute_channel("distance", |ctx| Some(ctx.distance));
+    /// let second = distance.get(TrackPointRef { segment: 0, index: 1 }).unwrap();
+    /// assert!((second - 111.2).abs() < 0.1);
+    /// ```
+    pub fn compute_channel<F>(&self, name: impl Into<String>, mut f: F) -> Channel
+    where
+        F: FnMut(ChannelCtx<'_, E>) -> Option<f64>,
+    {
+        let start = self
+            .segments
+            .iter()
+            .flat_map(|segment| &segment.points)
+            .filter_map(|point| point.time)
+            .map(OffsetDateTime::from)
+            .min();
+        let mut values = Vec::with_capacity(self.points_count());
+        let mut distance = 0.0;
+        for (segment_index, segment) in self.segments.iter().enumerate() {
+            for (index, point) in segment.points.iter().enumerate() {
+                let previous = index.checked_sub(1).map(|index| &segment.points[index]);
+                if let Some(previous) = previous {
+                    distance += haversine_distance(previous.point(), point.point());
+                }
+                let at = TrackPointRef {
+                    segment: segment_index,
+                    index,
+                };
+                let elapsed = match (point.time, start) {
+                    (Some(time), Some(start)) => Some(OffsetDateTime::from(time) - start),
+                    _ => None,
+                };
+                let value = f(ChannelCtx {
+                    at,
+                    point,
+                    previous,
+                    next: segment.points.get(index + 1),
+                    distance,
+                    elapsed,
+                });
+                values.push((at, value));
+            }
+        }
+        Channel {
+            name: name.into(),
+            values,
+        }
+    }
+
+    /// Removes the point `at`, recording it in `log`, and gives it, or `None`
+    /// when there is no such point.
+    pub fn remove_point(&mut self, at: TrackPointRef, log: &mut EditLog) -> Option<Waypoint<E>> {
+        let points = &mut self.segments.get_mut(at.segment)?.points;
+        if at.index >= points.len() {
+            return None;
+        }
+        log.record_removal(at);
+        Some(points.remove(at.index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+    use time::{Duration, OffsetDateTime};
+
+    use crate::distance::haversine_distance;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{Track, TrackSegment, Waypoint};
+
+    use super::{ChannelCtx, EditLog, TrackPointRef};
+
+    /// A track with a segment for every list of `seconds`, of points 0.001
+    /// degrees of latitude apart at those times.
+    fn track(seconds: &[&[i64]]) -> Track<EmptyExtensions> {
+        let mut track = Track::new();
+        for (segment, seconds) in seconds.iter().enumerate() {
+            let points = seconds
+                .iter()
+                .enumerate()
+                .map(|(index, &seconds)| {
+                    let mut point = Waypoint::new(Point::new(segment as f64, index as f64 * 0.001));
+                    point.time = Some((OffsetDateTime::UNIX_EPOCH + Duration::seconds(seconds)).into());
+                    point
+                })
+                .collect();
+            track.segments.push(TrackSegment { points });
+        }
+        track
+    }
+
+    /// speed gives the speed from the previous point in meters per second.
+    fn speed(ctx: ChannelCtx<'_, EmptyExtensions>) -> Option<f64> {
+        let previous = ctx.previous?;
+        let seconds = (OffsetDateTime::from(ctx.point.time?) - OffsetDateTime::from(previous.time?)).as_seconds_f64();
+        Some(haversine_distance(previous.point(), ctx.point.point()) / seconds)
+    }
+
+    #[test]
+    fn compute_channel_context() {
+        let track = track(&[&[10, 20, 30], &[100, 110]]);
+        let mut seen = Vec::new();
+        track.compute_channel("context", |ctx| {
+            seen.push((ctx.at, ctx.previous.is_some(), ctx.next.is_some(), ctx.elapsed));
+            Some(ctx.distance)
+        });
+        assert_eq!(
+            seen,
+            [
+                (TrackPointRef { segment: 0, index: 0 }, false, true, Some(Duration::ZERO)),
+                (TrackPointRef { segment: 0, index: 1 }, true, true, Some(Duration::seconds(10))),
+                (TrackPointRef { segment: 0, index: 2 }, true, false, Some(Duration::seconds(20))),
+                (TrackPointRef { segment: 1, index: 0 }, false, true, Some(Duration::seconds(90))),
+                (TrackPointRef { segment: 1, index: 1 }, true, false, Some(Duration::seconds(100))),
+            ]
+        );
+
+        // The gap between the segments does not count.
+        let distance = track.compute_channel("distance", |ctx| Some(ctx.distance));
+        let last = distance.get(TrackPointRef { segment: 1, index: 1 }).unwrap();
+        assert!((last - 3.0 * 111.195).abs() < 0.1, "{}", last);
+    }
+
+    #[test]
+    fn channel_remapped_after_removal() {
+        let mut track = track(&[&[0, 10, 30, 40], &[100, 105]]);
+        let mut channel = track.compute_channel("speed", speed);
+        assert_eq!(channel.name(), "speed");
+        assert_eq!(channel.len(), 6);
+        let before: Vec<_> = channel.iter().collect();
+        assert_eq!(before[0].1, None);
+
+        let mut log = EditLog::new();
+        let removed = track.remove_point(TrackPointRef { segment: 0, index: 1 }, &mut log).unwrap();
+        assert_eq!(removed.point(), Point::new(0.0, 0.001));
+        assert!(track.remove_point(TrackPointRef { segment: 2, index: 0 }, &mut log).is_none());
+        assert!(track.remove_point(TrackPointRef { segment: 1, index: 2 }, &mut log).is_none());
+        assert_eq!(log.removals(), [TrackPointRef { segment: 0, index: 1 }]);
+
+        assert_eq!(channel.remap(&log), 1);
+        log.clear();
+        assert_eq!(channel.len(), track.points_count());
+
+        // Every value is still that of its point, now at the place given.
+        let expected = [before[0], before[2], before[3], before[4], before[5]];
+        let places: Vec<_> = track
+            .segments
+            .iter()
+            .enumerate()
+            .flat_map(|(segment, points)| (0..points.points.len()).map(move |index| TrackPointRef { segment, index }))
+            .collect();
+        assert_eq!(places.len(), expected.len());
+        for (at, (was, value)) in places.into_iter().zip(expected) {
+            assert_eq!(channel.get(at), value, "{:?} was {:?}", at, was);
+        }
+        assert_eq!(channel.get(TrackPointRef { segment: 0, index: 3 }), None);
+        assert_eq!(channel.remap(&log), 0);
+    }
+
+    #[test]
+    fn remap_follows_many_removals() {
+        let mut track = track(&[&[0; 40], &[0; 7], &[0; 25]]);
+        let mut channel = track.compute_channel("order", |ctx| Some((ctx.at.segment * 100 + ctx.at.index) as f64));
+        let mut log = EditLog::new();
+        for step in 0..30 {
+            let segment = step % 3;
+            let len = track.segments[segment].points.len();
+            if len > 0 {
+                track.remove_point(TrackPointRef { segment, index: step * 7 % len }, &mut log).unwrap();
+            }
+        }
+        // Removals of points that are not there are ignored.
+        log.record_removal(TrackPointRef { segment: 1, index: 50 });
+        log.record_removal(TrackPointRef { segment: 3, index: 0 });
+
+        // Replaying the removals one at a time gives the same channel.
+        let mut expected: Vec<_> = channel.iter().collect();
+        for &removed in log.removals() {
+            expected.retain(|&(at, _)| at != removed);
+            for (at, _) in &mut expected {
+                if at.segment == removed.segment && at.index > removed.index {
+                    at.index -= 1;
+                }
+            }
+        }
+        assert_eq!(channel.remap(&log), 72 - expected.len());
+        assert_eq!(channel.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(channel.len(), track.points_count());
+    }
+
+    #[test]
+    fn remap_ignores_removals_out_of_range() {
+        let track = track(&[&[0, 10, 20]]);
+        let mut channel = track.compute_channel("order", |ctx| Some(ctx.at.index as f64));
+        let mut log = EditLog::new();
+        log.record_removal(TrackPointRef { segment: 0, index: usize::MAX });
+        log.record_removal(TrackPointRef { segment: 0, index: 1 });
+        log.record_removal(TrackPointRef { segment: 0, index: 2 });
+        log.record_removal(TrackPointRef { segment: usize::MAX, index: usize::MAX });
+
+        assert_eq!(channel.remap(&log), 1);
+        assert_eq!(
+            channel.iter().collect::<Vec<_>>(),
+            [
+                (TrackPointRef { segment: 0, index: 0 }, Some(0.0)),
+                (TrackPointRef { segment: 0, index: 1 }, Some(2.0)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! clean removes points of track segments that are artifacts of recording,
+//! rather than of moving.
+
+use geo_types::Point;
+
+use crate::distance::haversine_distance;
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Time, TrackSegment};
+
+/// speed gives the speed in meters per second from `a` to `b`, which is
+/// infinite when they are apart at the same time.
+fn speed(a: (Point<f64>, Time), b: (Point<f64>, Time)) -> f64 {
+    haversine_distance(a.0, b.0) / (b.1 - a.1).as_seconds_f64().abs()
+}
+
+impl<E: WaypointExtensions + Default> TrackSegment<E> {
+    /// Removes the points closer than `min_distance_m` meters to the point
+    /// kept before them, such as the runs of points recorded while standing
+    /// still, giving how many points were removed.
+    ///
+    /// The first and last points are always kept.
+    pub fn dedup(&mut self, min_distance_m: f64) -> usize {
+        let len = self.points.len();
+        let mut index = 0;
+        let mut last_kept: Option<Point<f64>> = None;
+        self.points.retain(|point| {
+            index += 1;
+            let keep = index == len
+                || last_kept.map_or(true, |last| haversine_distance(last, point.point()) >= min_distance_m);
+            if keep {
+                last_kept = Some(point.point());
+            }
+            keep
+        });
+        len - self.points.len()
+    }
+
+    /// Removes the points reached from the point before them, and left for
+    /// the point after them, faster than `max_speed_mps` meters per second,
+    /// such as the wild outliers of a poor fix, giving how many points were
+    /// removed.
+    ///
+    /// Only points with a time are considered, and compared to the points
+    /// with a time around them, so points without one are never removed, and
+    /// neither are the first and last points with one. A point is compared to
+    /// the last point kept before it, so a run of outliers is removed too.
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    /// use time::{Duration, OffsetDateTime};
+    ///
+    /// let start = OffsetDateTime::from_unix_timestamp(1_600_000_000).unwrap();
+    /// let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+    /// for (i, y) in [0.0, 0.0001, 0.5, 0.0003].iter().enumerate() {
+    ///     let mut point = Waypoint::new(Point::new(0.0, *y));
+    ///     point.time = Some((start + Duration::seconds(10 * i as i64)).into());
+    ///     segment.points.push(point);
+    /// }
+    ///
+    /// assert_eq!(segment.remove_speed_outliers(50.0), 1);
+    /// assert_eq!(segment.points[2].point().y(), 0.0003);
+    /// ```
+    pub fn remove_speed_outliers(&mut self, max_speed_mps: f64) -> usize {
+        let timed: Vec<_> = self
+            .points
+            .iter()
+            .enumerate()
+            .filter_map(|(index, point)| Some((index, (point.point(), point.time?))))
+            .collect();
+        let mut remove = vec![false; self.points.len()];
+        if let Some(&(_, first)) = timed.first() {
+            let mut previous = first;
+            for pair in timed[1..].windows(2) {
+                let ((index, current), (_, next)) = (pair[0], pair[1]);
+                if speed(previous, current) > max_speed_mps && speed(current, next) > max_speed_mps {
+                    remove[index] = true;
+                } else {
+                    previous = current;
+                }
+            }
+        }
+
+        let len = self.points.len();
+        let mut index = 0;
+        self.points.retain(|_| {
+            index += 1;
+            !remove[index - 1]
+        });
+        len - self.points.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+    use time::{Duration, OffsetDateTime};
+
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{TrackSegment, Waypoint};
+
+    /// A walk north along the meridian, a point every 10 seconds, standing
+    /// still for three points and with one point teleported 50 km east.
+    fn trace() -> TrackSegment<EmptyExtensions> {
+        let start = OffsetDateTime::from_unix_timestamp(1_600_000_000).unwrap();
+        let positions = [
+            (0.0, 0.0),
+            (0.0, 0.0001),
+            (0.0, 0.0002),
+            (0.0, 0.0002),
+            (0.00001, 0.0002),
+            (0.0, 0.0003),
+            (0.45, 0.0004),
+            (0.0, 0.0005),
+            (0.0, 0.0006),
+        ];
+        let points = positions
+            .iter()
+            .enumerate()
+            .map(|(i, &(x, y))| {
+                let mut point = Waypoint::new(Point::new(x, y));
+                point.time = Some((start + Duration::seconds(10 * i as i64)).into());
+                point
+            })
+            .collect();
+        TrackSegment { points }
+    }
+
+    fn ys(segment: &TrackSegment<EmptyExtensions>) -> Vec<f64> {
+        segment.points.iter().map(|point| point.point().y()).collect()
+    }
+
+    #[test]
+    fn dedup_stationary_cluster() {
+        let mut segment = trace();
+        assert_eq!(segment.dedup(5.0), 2);
+        assert_eq!(ys(&segment), [0.0, 0.0001, 0.0002, 0.0003, 0.0004, 0.0005, 0.0006]);
+
+        // The last point is kept even when close to the one before.
+        let mut segment = trace();
+        segment.points.push(Waypoint::new(Point::new(0.0, 0.0006)));
+        assert_eq!(segment.dedup(5.0), 2);
+        assert_eq!(segment.points.len(), 8);
+
+        assert_eq!(TrackSegment::<EmptyExtensions>::new().dedup(5.0), 0);
+    }
+
+    #[test]
+    fn remove_teleporting_outlier() {
+        let mut segment = trace();
+        assert_eq!(segment.remove_speed_outliers(20.0), 1);
+        assert_eq!(segment.points.len(), 8);
+        assert!(segment.points.iter().all(|point| point.point().x() < 0.1));
+
+        // An outlier without a time is never removed, and the points around
+        // it are compared to each other instead.
+        let mut segment = trace();
+        segment.points[6].time = None;
+        assert_eq!(segment.remove_speed_outliers(20.0), 0);
+
+        // Nor are the first and last points, with a single neighbor.
+        let mut segment = trace();
+        let time = segment.points[8].time;
+        segment.points[8] = Waypoint::new(Point::new(0.45, 0.0006));
+        segment.points[8].time = time;
+        assert_eq!(segment.remove_speed_outliers(20.0), 1);
+        assert_eq!(segment.points.len(), 8);
+    }
+}
@@ -0,0 +1,176 @@
+//! compact stores the coordinates of track segments as fixed point numbers,
+//! to hold many points in little memory.
+//!
+//! [`TrackSegment`] itself keeps its points as [`Waypoint`](crate::Waypoint)s
+//! in a public `Vec`, which callers index, push to and borrow from, so it
+//! cannot hold them in another form without breaking every one of them.
+//! [`CompactTrackSegment`] is the compact form instead, with the accessors of
+//! [`TrackSegment`] for the coordinates, converting them as they are read.
+
+use geo_types::{LineString, Point};
+
+use crate::distance::haversine_distance;
+use crate::parser::extensions::WaypointExtensions;
+use crate::TrackSegment;
+
+/// How many fixed point units make a degree. Like OpenStreetMap, coordinates
+/// are stored to 1e-7 degrees, about a centimeter.
+const UNITS_PER_DEGREE: f64 = 1e7;
+
+/// CompactTrackSegment holds the coordinates of a track segment as 32-bit
+/// fixed point numbers of 1e-7 degrees, taking half the memory of the `f64`
+/// coordinates of a [`LineString`], and a fiftieth of that of the
+/// [`Waypoint`]s of a [`TrackSegment`].
+///
+/// Every coordinate given back is within 0.5e-7 degrees of the one stored.
+/// Only the coordinates are kept, not the elevation, time or anything else.
+///
+/// Together with [`read_with_waypoint_callback`], it reads the coordinates
+/// of documents too large to hold as [`Gpx`] values:
+///
+/// ```
+/// use gpx::{read_with_waypoint_callback, CompactTrackSegment, Gpx};
+/// use gpx::parser::extensions::EmptyExtensions;
+///
+/// let data = "<gpx version=\"1.1\"><trk><trkseg>
+///     <trkpt lat=\"51.4778412\" lon=\"-0.0014863\"/>
+///     <trkpt lat=\"51.4780120\" lon=\"-0.0015234\"/>
+/// </trkseg></trk></gpx>";
+///
+/// let mut segment = CompactTrackSegment::new();
+/// let _: Gpx<EmptyExtensions> = read_with_waypoint_callback(data.as_bytes(), |point| {
+///     segment.push(point.point());
+/// })
+/// .unwrap();
+/// assert_eq!(segment.len(), 2);
+/// assert!((segment.point(1).unwrap().y() - 51.4780120).abs() <= 0.5e-7);
+/// ```
+///
+/// [`Waypoint`]: crate::Waypoint
+/// [`Gpx`]: crate::Gpx
+/// [`read_with_waypoint_callback`]: crate::read_with_waypoint_callback
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompactTrackSegment {
+    /// Longitude and latitude, in units of 1e-7 degrees.
+    coordinates: Vec<[i32; 2]>,
+}
+
+/// to_fixed converts `degrees` to fixed point, saturating beyond the range
+/// of an `i32`, which valid coordinates do not reach.
+fn to_fixed(degrees: f64) -> i32 {
+    (degrees * UNITS_PER_DEGREE).round() as i32
+}
+
+fn from_fixed(units: i32) -> f64 {
+    f64::from(units) / UNITS_PER_DEGREE
+}
+
+impl CompactTrackSegment {
+    /// Gives an empty segment.
+    pub fn new() -> CompactTrackSegment {
+        Default::default()
+    }
+
+    /// Adds `point` to the end of the segment.
+    pub fn push(&mut self, point: Point<f64>) {
+        self.coordinates.push([to_fixed(point.x()), to_fixed(point.y())]);
+    }
+
+    pub fn len(&self) -> usize {
+        self.coordinates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coordinates.is_empty()
+    }
+
+    /// Gives the point at `index`, or `None` when there is none.
+    pub fn point(&self, index: usize) -> Option<Point<f64>> {
+        self.coordinates
+            .get(index)
+            .map(|&[x, y]| Point::new(from_fixed(x), from_fixed(y)))
+    }
+
+    /// Gives the points in order.
+    pub fn points(&self) -> impl Iterator<Item = Point<f64>> + '_ {
+        self.coordinates
+            .iter()
+            .map(|&[x, y]| Point::new(from_fixed(x), from_fixed(y)))
+    }
+
+    /// Gives a geo-types LineString of the points, like
+    /// [`TrackSegment::linestring`].
+    pub fn linestring(&self) -> LineString<f64> {
+        self.points().collect()
+    }
+
+    /// Gives the length of the segment in meters, like
+    /// [`TrackSegment::length_meters`].
+    pub fn length_meters(&self) -> f64 {
+        self.points()
+            .zip(self.points().skip(1))
+            .map(|(a, b)| haversine_distance(a, b))
+            .sum()
+    }
+}
+
+impl<E: WaypointExtensions + Default> TrackSegment<E> {
+    /// Gives the coordinates of this segment as a [`CompactTrackSegment`].
+    pub fn compact(&self) -> CompactTrackSegment {
+        CompactTrackSegment {
+            coordinates: self
+                .points
+                .iter()
+                .map(|point| [to_fixed(point.point().x()), to_fixed(point.point().y())])
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{TrackSegment, Waypoint};
+
+    use super::CompactTrackSegment;
+
+    #[test]
+    fn compact_within_quantization_error() {
+        // A spread of coordinates over the whole globe, from a simple linear
+        // congruential generator.
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = || {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+        for _ in 0..10_000 {
+            let point = Point::new(next() * 360.0 - 180.0, next() * 180.0 - 90.0);
+            segment.points.push(Waypoint::new(point));
+        }
+        segment.points.push(Waypoint::new(Point::new(-180.0, 90.0)));
+
+        let compact = segment.compact();
+        assert_eq!(compact.len(), segment.points.len());
+        for (original, point) in segment.points.iter().zip(compact.points()) {
+            let original = original.point();
+            assert!((original.x() - point.x()).abs() <= 0.5e-7 + 1e-12);
+            assert!((original.y() - point.y()).abs() <= 0.5e-7 + 1e-12);
+        }
+        assert_eq!(compact.point(10_000), Some(Point::new(-180.0, 90.0)));
+        assert_eq!(compact.point(10_001), None);
+        assert_eq!(compact.linestring().0.len(), compact.len());
+        let length = segment.length_meters();
+        assert!((compact.length_meters() - length).abs() <= length * 1e-9);
+    }
+
+    #[test]
+    fn compact_push() {
+        let mut compact = CompactTrackSegment::new();
+        assert!(compact.is_empty());
+        compact.push(Point::new(8.54169249, 47.37689));
+        assert_eq!(compact.point(0), Some(Point::new(8.5416925, 47.37689)));
+    }
+}
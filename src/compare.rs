@@ -0,0 +1,390 @@
+//! compare compares documents while ignoring the fields that vary between
+//! exports of the same data, for regression tests against a baseline.
+
+use std::ops::{BitOr, BitOrAssign};
+
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Gpx, GpxCopyright, Link, Metadata, Person, Route, Track, TrackSegment, Waypoint};
+
+/// Fields that [`Gpx::structural_eq`] ignores, combined with `|`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct IgnoreFields(u8);
+
+impl IgnoreFields {
+    /// Ignores nothing.
+    pub const NONE: IgnoreFields = IgnoreFields(0);
+    /// The creator of the document, which exporters write with their version.
+    pub const CREATOR: IgnoreFields = IgnoreFields(1);
+    /// The time of the metadata, which exporters set to when they ran.
+    pub const METADATA_TIME: IgnoreFields = IgnoreFields(1 << 1);
+    /// The order of the links of anything that has several.
+    pub const LINK_ORDER: IgnoreFields = IgnoreFields(1 << 2);
+    /// The extension values of points, and the styles of tracks, which are
+    /// read from extensions too.
+    pub const EXTENSIONS: IgnoreFields = IgnoreFields(1 << 3);
+    /// Whitespace at the ends of strings, and how much of it separates their
+    /// words.
+    pub const WHITESPACE: IgnoreFields = IgnoreFields(1 << 4);
+    /// Ignores all of the above.
+    pub const ALL: IgnoreFields = IgnoreFields((1 << 5) - 1);
+
+    /// Whether all the fields of `other` are ignored too.
+    pub fn contains(self, other: IgnoreFields) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for IgnoreFields {
+    type Output = IgnoreFields;
+
+    fn bitor(self, other: IgnoreFields) -> IgnoreFields {
+        IgnoreFields(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for IgnoreFields {
+    fn bitor_assign(&mut self, other: IgnoreFields) {
+        self.0 |= other.0;
+    }
+}
+
+/// Compare compares the parts of two documents, skipping the fields it
+/// ignores, without copying either.
+struct Compare {
+    ignore: IgnoreFields,
+}
+
+impl Compare {
+    fn ignores(&self, fields: IgnoreFields) -> bool {
+        self.ignore.contains(fields)
+    }
+
+    fn string(&self, a: &str, b: &str) -> bool {
+        if self.ignores(IgnoreFields::WHITESPACE) {
+            a.split_whitespace().eq(b.split_whitespace())
+        } else {
+            a == b
+        }
+    }
+
+    fn optional_string(&self, a: &Option<String>, b: &Option<String>) -> bool {
+        Compare::optional(a, b, |a, b| self.string(a, b))
+    }
+
+    fn optional<T>(a: &Option<T>, b: &Option<T>, eq: impl Fn(&T, &T) -> bool) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+
+    fn all<T>(a: &[T], b: &[T], eq: impl Fn(&T, &T) -> bool) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| eq(a, b))
+    }
+
+    // Each struct is destructured whole, so that a field added to it cannot
+    // be left out of the comparison.
+
+    fn link(&self, a: &Link, b: &Link) -> bool {
+        let Link { href, text, type_ } = a;
+        self.string(href, &b.href) && self.optional_string(text, &b.text) && self.optional_string(type_, &b.type_)
+    }
+
+    /// links compares two lists of links, in any order when
+    /// [`IgnoreFields::LINK_ORDER`] is ignored, by matching each link of `a`
+    /// with a link of `b` not matched yet.
+    fn links(&self, a: &[Link], b: &[Link]) -> bool {
+        if a.len() != b.len() {
+            return false;
+        }
+        if !self.ignores(IgnoreFields::LINK_ORDER) {
+            return a.iter().zip(b).all(|(a, b)| self.link(a, b));
+        }
+        let mut matched = vec![false; b.len()];
+        a.iter().all(|a| {
+            let found = b
+                .iter()
+                .zip(&matched)
+                .position(|(b, matched)| !*matched && self.link(a, b));
+            found.map(|index| matched[index] = true).is_some()
+        })
+    }
+
+    fn person(&self, a: &Person, b: &Person) -> bool {
+        let Person { name, email, link } = a;
+        self.optional_string(name, &b.name)
+            && self.optional_string(email, &b.email)
+            && Compare::optional(link, &b.link, |a, b| self.link(a, b))
+    }
+
+    fn copyright(&self, a: &GpxCopyright, b: &GpxCopyright) -> bool {
+        let GpxCopyright { author, year, license } = a;
+        self.optional_string(author, &b.author) && *year == b.year && self.optional_string(license, &b.license)
+    }
+
+    fn metadata(&self, a: &Metadata, b: &Metadata) -> bool {
+        let Metadata {
+            name,
+            description,
+            author,
+            links,
+            time,
+            keywords,
+            copyright,
+            bounds,
+        } = a;
+        self.optional_string(name, &b.name)
+            && self.optional_string(description, &b.description)
+            && Compare::optional(author, &b.author, |a, b| self.person(a, b))
+            && self.links(links, &b.links)
+            && (self.ignores(IgnoreFields::METADATA_TIME) || *time == b.time)
+            && self.optional_string(keywords, &b.keywords)
+            && Compare::optional(copyright, &b.copyright, |a, b| self.copyright(a, b))
+            && *bounds == b.bounds
+    }
+
+    #[allow(deprecated)]
+    fn waypoint<E: WaypointExtensions + Default>(&self, a: &Waypoint<E>, b: &Waypoint<E>) -> bool {
+        let Waypoint {
+            point,
+            elevation,
+            speed,
+            course,
+            time,
+            name,
+            comment,
+            description,
+            source,
+            links,
+            symbol,
+            type_,
+            magvar,
+            geoidheight,
+            fix,
+            sat,
+            hdop,
+            vdop,
+            pdop,
+            age,
+            dgps_age,
+            dgpsid,
+            extensions,
+        } = a;
+        *point == b.point
+            && *elevation == b.elevation
+            && *speed == b.speed
+            && *course == b.course
+            && *time == b.time
+            && self.optional_string(name, &b.name)
+            && self.optional_string(comment, &b.comment)
+            && self.optional_string(description, &b.description)
+            && self.optional_string(source, &b.source)
+            && self.links(links, &b.links)
+            && self.optional_string(symbol, &b.symbol)
+            && self.optional_string(type_, &b.type_)
+            && *magvar == b.magvar
+            && *geoidheight == b.geoidheight
+            && *fix == b.fix
+            && *sat == b.sat
+            && *hdop == b.hdop
+            && *vdop == b.vdop
+            && *pdop == b.pdop
+            && *age == b.age
+            && *dgps_age == b.dgps_age
+            && *dgpsid == b.dgpsid
+            && (self.ignores(IgnoreFields::EXTENSIONS) || *extensions == b.extensions)
+    }
+
+    fn waypoints<E: WaypointExtensions + Default>(&self, a: &[Waypoint<E>], b: &[Waypoint<E>]) -> bool {
+        Compare::all(a, b, |a, b| self.waypoint(a, b))
+    }
+
+    fn route<E: WaypointExtensions + Default>(&self, a: &Route<E>, b: &Route<E>) -> bool {
+        let Route {
+            name,
+            comment,
+            description,
+            source,
+            links,
+            number,
+            type_,
+            points,
+        } = a;
+        self.optional_string(name, &b.name)
+            && self.optional_string(comment, &b.comment)
+            && self.optional_string(description, &b.description)
+            && self.optional_string(source, &b.source)
+            && self.links(links, &b.links)
+            && *number == b.number
+            && self.optional_string(type_, &b.type_)
+            && self.waypoints(points, &b.points)
+    }
+
+    fn segment<E: WaypointExtensions + Default>(&self, a: &TrackSegment<E>, b: &TrackSegment<E>) -> bool {
+        let TrackSegment { points } = a;
+        self.waypoints(points, &b.points)
+    }
+
+    fn track<E: WaypointExtensions + Default>(&self, a: &Track<E>, b: &Track<E>) -> bool {
+        let Track {
+            name,
+            comment,
+            description,
+            source,
+            links,
+            type_,
+            number,
+            style,
+            segments,
+        } = a;
+        self.optional_string(name, &b.name)
+            && self.optional_string(comment, &b.comment)
+            && self.optional_string(description, &b.description)
+            && self.optional_string(source, &b.source)
+            && self.links(links, &b.links)
+            && self.optional_string(type_, &b.type_)
+            && *number == b.number
+            && (self.ignores(IgnoreFields::EXTENSIONS) || *style == b.style)
+            && Compare::all(segments, &b.segments, |a, b| self.segment(a, b))
+    }
+
+    fn gpx<E: WaypointExtensions + Default>(&self, a: &Gpx<E>, b: &Gpx<E>) -> bool {
+        let Gpx {
+            version,
+            creator,
+            metadata,
+            waypoints,
+            tracks,
+            routes,
+            comments,
+            namespaces,
+            schema_locations,
+        } = a;
+        *version == b.version
+            && (self.ignores(IgnoreFields::CREATOR) || self.optional_string(creator, &b.creator))
+            && Compare::optional(metadata, &b.metadata, |a, b| self.metadata(a, b))
+            && self.waypoints(waypoints, &b.waypoints)
+            && Compare::all(tracks, &b.tracks, |a, b| self.track(a, b))
+            && Compare::all(routes, &b.routes, |a, b| self.route(a, b))
+            && Compare::all(comments, &b.comments, |a, b| self.string(a, b))
+            && *namespaces == b.namespaces
+            && *schema_locations == b.schema_locations
+    }
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Compares this document with `other` but for the `ignore`d fields, for
+    /// regression tests comparing a fresh export against a stored baseline.
+    ///
+    /// ```
+    /// use gpx::{Gpx, IgnoreFields};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let baseline: Gpx<EmptyExtensions> = Gpx {
+    ///     creator: Some(String::from("exporter 1.2")),
+    ///     ..Default::default()
+    /// };
+    /// let export = Gpx {
+    ///     creator: Some(String::from("exporter 1.3")),
+    ///     ..baseline.clone()
+    /// };
+    ///
+    /// assert!(!export.structural_eq(&baseline, IgnoreFields::NONE));
+    /// assert!(export.structural_eq(&baseline, IgnoreFields::CREATOR | IgnoreFields::METADATA_TIME));
+    /// ```
+    pub fn structural_eq(&self, other: &Gpx<E>, ignore: IgnoreFields) -> bool {
+        Compare { ignore }.gpx(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::BufReader;
+
+    use crate::parser::extensions::GarminExtensions;
+    use crate::{read_with_extensions, write, Gpx, Link};
+
+    use super::IgnoreFields;
+
+    const FLAGS: [IgnoreFields; 5] = [
+        IgnoreFields::CREATOR,
+        IgnoreFields::METADATA_TIME,
+        IgnoreFields::LINK_ORDER,
+        IgnoreFields::EXTENSIONS,
+        IgnoreFields::WHITESPACE,
+    ];
+
+    fn link(href: &str) -> Link {
+        Link {
+            href: String::from(href),
+            ..Default::default()
+        }
+    }
+
+    /// reexport writes `gpx` and reads it back after applying `edit` to the
+    /// XML.
+    fn reexport(gpx: &Gpx<GarminExtensions>, edit: impl Fn(String) -> String) -> Gpx<GarminExtensions> {
+        let mut written = Vec::new();
+        write(gpx, &mut written).unwrap();
+        let xml = edit(String::from_utf8(written).unwrap());
+        read_with_extensions(xml.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn structural_eq_ignores_volatile_fields() {
+        let file = File::open("tests/fixtures/marine_waypoint.gpx").unwrap();
+        let mut baseline: Gpx<GarminExtensions> = read_with_extensions(BufReader::new(file)).unwrap();
+        baseline.waypoints[0].links = vec![link("https://a.example"), link("https://b.example")];
+
+        let mut export = reexport(&baseline, |xml| xml);
+        assert!(export.structural_eq(&baseline, IgnoreFields::NONE));
+        export.creator = Some(String::from("GPSMAP 8612 v2"));
+        export.metadata.as_mut().unwrap().time = None;
+        export.waypoints[0].links.reverse();
+        export.waypoints[0].extensions.depth = Some(9.0);
+        export.waypoints[1].name = Some(String::from("  Harbour\n  entrance "));
+
+        assert!(export.structural_eq(&baseline, IgnoreFields::ALL));
+        assert!(!export.structural_eq(&baseline, IgnoreFields::NONE));
+        for flag in FLAGS {
+            let others = FLAGS
+                .iter()
+                .filter(|other| **other != flag)
+                .fold(IgnoreFields::NONE, |all, other| all | *other);
+            assert!(!export.structural_eq(&baseline, others), "{:?} is needed", flag);
+        }
+    }
+
+    #[test]
+    fn structural_eq_sees_moved_point() {
+        let file = File::open("tests/fixtures/marine_waypoint.gpx").unwrap();
+        let baseline: Gpx<GarminExtensions> = read_with_extensions(BufReader::new(file)).unwrap();
+        let moved = reexport(&baseline, |xml| xml.replace("43.684512", "43.684612"));
+
+        assert!(!moved.structural_eq(&baseline, IgnoreFields::ALL));
+    }
+
+    #[test]
+    fn structural_eq_matches_each_link_once() {
+        let mut baseline: Gpx<GarminExtensions> = Gpx::default();
+        baseline.waypoints.push(Default::default());
+        baseline.waypoints[0].links = vec![link("https://a.example"), link("https://a.example"), link("https://b.example")];
+        let mut export = baseline.clone();
+        export.waypoints[0].links = vec![link("https://b.example"), link(" https://a.example"), link("https://a.example")];
+
+        assert!(export.structural_eq(&baseline, IgnoreFields::LINK_ORDER | IgnoreFields::WHITESPACE));
+        assert!(!export.structural_eq(&baseline, IgnoreFields::LINK_ORDER));
+        export.waypoints[0].links[0] = link("https://a.example");
+        assert!(!export.structural_eq(&baseline, IgnoreFields::ALL));
+    }
+
+    #[test]
+    fn ignore_fields_contains() {
+        let ignore = IgnoreFields::CREATOR | IgnoreFields::WHITESPACE;
+        assert!(ignore.contains(IgnoreFields::CREATOR));
+        assert!(!ignore.contains(IgnoreFields::CREATOR | IgnoreFields::LINK_ORDER));
+        assert!(IgnoreFields::ALL.contains(ignore));
+        assert!(ignore.contains(IgnoreFields::NONE));
+    }
+}
@@ -0,0 +1,244 @@
+//! crop keeps the parts of tracks and routes within a time range or a
+//! bounding box.
+
+use geo_types::Rect;
+
+use crate::parser::extensions::WaypointExtensions;
+use crate::{DeriveOptions, Gpx, Route, Time, Track, TrackSegment, Waypoint};
+
+/// Whether `point` has a time within `start..=end`, `None` when it has no
+/// time.
+fn within_time<E: WaypointExtensions + Default>(point: &Waypoint<E>, start: Time, end: Time) -> Option<bool> {
+    point.time.map(|time| start <= time && time <= end)
+}
+
+/// Whether `point` lies within `bounds`, edges included.
+fn within_bounds<E: WaypointExtensions + Default>(point: &Waypoint<E>, bounds: &Rect<f64>) -> bool {
+    let (point, min, max) = (point.point(), bounds.min(), bounds.max());
+    min.x <= point.x() && point.x() <= max.x && min.y <= point.y() && point.y() <= max.y
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Gives a copy of this track keeping only the points with a time within
+    /// `start..=end`.
+    ///
+    /// Like [`Track::crop_by_bounds`], segments are split where points outside
+    /// the range are dropped and empty segments removed. Points without a time
+    /// are dropped without splitting their segment.
+    pub fn crop_by_time(&self, start: Time, end: Time) -> Track<E>
+    where
+        E: Clone,
+    {
+        self.crop(|point| within_time(point, start, end))
+    }
+
+    /// Gives a copy of this track keeping only the points within `bounds`.
+    ///
+    /// A segment leaving and re-entering the box is split in two, rather than
+    /// joining the points on both sides of the gap with a straight line.
+    /// Segments entirely outside the box are removed.
+    ///
+    /// ```
+    /// use geo_types::{coord, Point, Rect};
+    /// use gpx::{Track, TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+    /// for x in [0.0, 1.0, 5.0, 2.0, 3.0] {
+    ///     segment.points.push(Waypoint::new(Point::new(x, 0.0)));
+    /// }
+    /// let mut track = Track::new();
+    /// track.segments.push(segment);
+    ///
+    /// let bounds = Rect::new(coord! { x: 0.0, y: -1.0 }, coord! { x: 4.0, y: 1.0 });
+    /// let cropped = track.crop_by_bounds(&bounds);
+    /// assert_eq!(cropped.segments.len(), 2);
+    /// ```
+    pub fn crop_by_bounds(&self, bounds: &Rect<f64>) -> Track<E>
+    where
+        E: Clone,
+    {
+        self.crop(|point| Some(within_bounds(point, bounds)))
+    }
+
+    /// crop keeps the runs of points `keep` gives `Some(true)` as segments.
+    /// Points it gives `None` are dropped without ending the run.
+    fn crop<F>(&self, keep: F) -> Track<E>
+    where
+        E: Clone,
+        F: Fn(&Waypoint<E>) -> Option<bool>,
+    {
+        let mut segments = Vec::new();
+        for segment in &self.segments {
+            let mut run = TrackSegment::new();
+            for point in &segment.points {
+                match keep(point) {
+                    Some(true) => run.points.push(point.clone()),
+                    Some(false) if !run.points.is_empty() => segments.push(std::mem::take(&mut run)),
+                    _ => {}
+                }
+            }
+            if !run.points.is_empty() {
+                segments.push(run);
+            }
+        }
+        Track {
+            segments,
+            ..self.without_segments()
+        }
+    }
+}
+
+impl<E: WaypointExtensions + Default> Route<E> {
+    /// Gives a copy of this route keeping only the points with a time within
+    /// `start..=end`; points without a time are dropped.
+    pub fn crop_by_time(&self, start: Time, end: Time) -> Route<E>
+    where
+        E: Clone,
+    {
+        self.crop(|point| within_time(point, start, end))
+    }
+
+    /// Gives a copy of this route keeping only the points within `bounds`.
+    ///
+    /// Routes have a single list of points, so unlike tracks they are not
+    /// split where they leave and re-enter the box.
+    pub fn crop_by_bounds(&self, bounds: &Rect<f64>) -> Route<E>
+    where
+        E: Clone,
+    {
+        self.crop(|point| Some(within_bounds(point, bounds)))
+    }
+
+    fn crop<F>(&self, keep: F) -> Route<E>
+    where
+        E: Clone,
+        F: Fn(&Waypoint<E>) -> Option<bool>,
+    {
+        Route {
+            points: self.points.iter().filter(|point| keep(point) == Some(true)).cloned().collect(),
+            ..self.without_points()
+        }
+    }
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Gives a copy of this document with every track and route cropped by
+    /// [`Track::crop_by_time`] and [`Route::crop_by_time`]. Waypoints are
+    /// kept as they are.
+    pub fn crop_by_time(&self, start: Time, end: Time) -> Gpx<E>
+    where
+        E: Clone,
+    {
+        self.crop_by_time_with(start, end, &Default::default())
+    }
+
+    /// Crops the document like [`Gpx::crop_by_time`], using the given
+    /// [`DeriveOptions`].
+    pub fn crop_by_time_with(&self, start: Time, end: Time, options: &DeriveOptions) -> Gpx<E>
+    where
+        E: Clone,
+    {
+        let note = || match (start.format(), end.format()) {
+            (Ok(start), Ok(end)) => format!("cropped to {} - {}", start, end),
+            _ => String::from("cropped by time"),
+        };
+        Gpx {
+            waypoints: self.waypoints.clone(),
+            tracks: self.tracks.iter().map(|track| track.crop_by_time(start, end)).collect(),
+            routes: self.routes.iter().map(|route| route.crop_by_time(start, end)).collect(),
+            ..self.derive_document(options, note)
+        }
+    }
+
+    /// Gives a copy of this document with every track and route cropped by
+    /// [`Track::crop_by_bounds`] and [`Route::crop_by_bounds`]. Waypoints are
+    /// kept as they are.
+    pub fn crop_by_bounds(&self, bounds: &Rect<f64>) -> Gpx<E>
+    where
+        E: Clone,
+    {
+        self.crop_by_bounds_with(bounds, &Default::default())
+    }
+
+    /// Crops the document like [`Gpx::crop_by_bounds`], using the given
+    /// [`DeriveOptions`].
+    pub fn crop_by_bounds_with(&self, bounds: &Rect<f64>, options: &DeriveOptions) -> Gpx<E>
+    where
+        E: Clone,
+    {
+        let note = || {
+            let (min, max) = (bounds.min(), bounds.max());
+            format!("cropped to bounds ({}, {}) - ({}, {})", min.x, min.y, max.x, max.y)
+        };
+        Gpx {
+            waypoints: self.waypoints.clone(),
+            tracks: self.tracks.iter().map(|track| track.crop_by_bounds(bounds)).collect(),
+            routes: self.routes.iter().map(|route| route.crop_by_bounds(bounds)).collect(),
+            ..self.derive_document(options, note)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{coord, Rect};
+
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::testing::{time, waypoint, xs};
+    use crate::{Gpx, Route, Track, TrackSegment};
+
+    #[test]
+    fn crop_by_bounds_splits_on_reentry() {
+        let mut track: Track<EmptyExtensions> = Track::new();
+        track.name = Some(String::from("ride"));
+        track.segments.push(TrackSegment {
+            // leaves the box after 1.0 and comes back at 2.0
+            points: [0.0, 0.5, 1.0, 6.0, 7.0, 2.0, 3.0].iter().map(|&x| waypoint(x, None)).collect(),
+        });
+        track.segments.push(TrackSegment {
+            points: vec![waypoint(9.0, None)],
+        });
+
+        let bounds = Rect::new(coord! { x: 0.0, y: -1.0 }, coord! { x: 4.0, y: 1.0 });
+        let cropped = track.crop_by_bounds(&bounds);
+
+        assert_eq!(cropped.name.as_deref(), Some("ride"));
+        assert_eq!(cropped.segments.len(), 2);
+        assert_eq!(xs(&cropped.segments[0]), [0.0, 0.5, 1.0]);
+        assert_eq!(xs(&cropped.segments[1]), [2.0, 3.0]);
+    }
+
+    #[test]
+    fn crop_by_time_drops_points_without_time() {
+        let mut track: Track<EmptyExtensions> = Track::new();
+        track.segments.push(TrackSegment {
+            points: vec![
+                waypoint(0.0, Some(0)),
+                waypoint(1.0, Some(10)),
+                waypoint(1.5, None),
+                waypoint(2.0, Some(20)),
+                waypoint(3.0, Some(30)),
+            ],
+        });
+        track.segments.push(TrackSegment {
+            points: vec![waypoint(4.0, None), waypoint(5.0, Some(50))],
+        });
+
+        let cropped = track.crop_by_time(time(10), time(20));
+        assert_eq!(cropped.segments.len(), 1);
+        assert_eq!(xs(&cropped.segments[0]), [1.0, 2.0]);
+
+        let mut gpx: Gpx<EmptyExtensions> = Gpx::default();
+        gpx.tracks.push(track);
+        let mut route = Route::new();
+        route.points = vec![waypoint(0.0, Some(0)), waypoint(1.0, None), waypoint(2.0, Some(50))];
+        gpx.routes.push(route);
+        gpx.waypoints.push(waypoint(0.0, None));
+
+        let cropped = gpx.crop_by_time(time(40), time(60));
+        assert_eq!(xs(&cropped.tracks[0].segments[0]), [5.0]);
+        assert_eq!(cropped.routes[0].points.len(), 1);
+        assert_eq!(cropped.waypoints.len(), 1);
+    }
+}
@@ -0,0 +1,324 @@
+//! csv writes the points of tracks as CSV, and reads track segments back from
+//! it, for analysis in spreadsheets and data frames.
+
+use std::io::{Read, Write};
+
+use geo_types::Point;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::errors::{GpxError, GpxResult};
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Gpx, Track, TrackSegment, Waypoint};
+
+const HEADER: &str = "track,segment,index,lat,lon,ele,time";
+
+/// Options for [`TrackSegment::from_csv_with`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// Skips the rows that cannot be read, instead of failing on the first.
+    pub skip_malformed: bool,
+}
+
+/// quote gives `field` as a CSV field, quoted when it holds a separator, a
+/// quote or a line break.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        String::from(field)
+    }
+}
+
+/// write_rows writes a row for each point of `track`, the `index`th track.
+fn write_rows<W: Write, E: WaypointExtensions + Default>(
+    track: &Track<E>,
+    index: usize,
+    writer: &mut W,
+) -> GpxResult<()> {
+    for (segment_index, segment) in track.segments.iter().enumerate() {
+        for (point_index, point) in segment.points.iter().enumerate() {
+            let elevation = point.elevation.map(|elevation| elevation.to_string()).unwrap_or_default();
+            let time = match point.time {
+                Some(time) => OffsetDateTime::from(time).format(&Rfc3339)?,
+                None => String::new(),
+            };
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                index,
+                segment_index,
+                point_index,
+                point.point().y(),
+                point.point().x(),
+                quote(&elevation),
+                quote(&time)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Writes the points of this track as CSV, a row per point under a
+    /// header of `track,segment,index,lat,lon,ele,time`: the track, always 0,
+    /// the segment and the point by their indices, the coordinates, and the
+    /// elevation and RFC 3339 time, left empty when the point has none.
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{Track, TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+    /// segment.points.push(Waypoint::new(Point::new(8.54, 47.37)));
+    /// let mut track = Track::new();
+    /// track.segments.push(segment);
+    ///
+    /// let mut csv = Vec::new();
+    /// track.to_csv(&mut csv).unwrap();
+    /// assert_eq!(String::from_utf8(csv).unwrap(), "track,segment,index,lat,lon,ele,time\n0,0,0,47.37,8.54,,\n");
+    /// ```
+    pub fn to_csv<W: Write>(&self, mut writer: W) -> GpxResult<()> {
+        writeln!(writer, "{}", HEADER)?;
+        write_rows(self, 0, &mut writer)
+    }
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Writes the points of every track as CSV, like [`Track::to_csv`], with
+    /// the index of their track in the `track` column.
+    pub fn to_csv<W: Write>(&self, mut writer: W) -> GpxResult<()> {
+        writeln!(writer, "{}", HEADER)?;
+        for (index, track) in self.tracks.iter().enumerate() {
+            write_rows(track, index, &mut writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// records splits `csv` into records of fields, each with the line it starts
+/// on, unquoting quoted fields. Blank lines are left out.
+fn records(csv: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = Vec::new();
+    let mut fields: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = csv.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                fields.push(std::mem::take(&mut field));
+                let record = std::mem::take(&mut fields);
+                if !is_blank(&record) {
+                    records.push((start, record));
+                }
+                line += 1;
+                start = line;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    fields.push(field);
+    if !is_blank(&fields) {
+        records.push((start, fields));
+    }
+    records
+}
+
+/// is_blank gives whether `record` is a blank line.
+fn is_blank(record: &[String]) -> bool {
+    matches!(record, [field] if field.is_empty())
+}
+
+/// parse_row gives the point of `fields`, a row of the columns at `columns`.
+fn parse_row<E: WaypointExtensions + Default>(
+    fields: &[String],
+    columns: &[Option<usize>; 4],
+) -> Result<Waypoint<E>, String> {
+    let field = |column: Option<usize>| -> Option<&str> {
+        column
+            .and_then(|column| fields.get(column))
+            .map(|field| field.trim())
+            .filter(|field| !field.is_empty())
+    };
+    let coordinate = |column: Option<usize>, name: &str| -> Result<f64, String> {
+        let value = field(column).ok_or_else(|| format!("missing `{}`", name))?;
+        value
+            .parse()
+            .map_err(|_| format!("`{}` is not a number for `{}`", value, name))
+    };
+    let mut point = Waypoint::new(Point::new(coordinate(columns[1], "lon")?, coordinate(columns[0], "lat")?));
+    if let Some(value) = field(columns[2]) {
+        point.elevation = Some(
+            value
+                .parse()
+                .map_err(|_| format!("`{}` is not a number for `ele`", value))?,
+        );
+    }
+    if let Some(value) = field(columns[3]) {
+        let time = OffsetDateTime::parse(value, &Rfc3339)
+            .map_err(|_| format!("`{}` is not an RFC 3339 time", value))?;
+        point.time = Some(time.into());
+    }
+    Ok(point)
+}
+
+impl<E: WaypointExtensions + Default> TrackSegment<E> {
+    /// Reads a segment from CSV, a point per row, finding the `lat`, `lon`,
+    /// `ele` and `time` columns by the header. The coordinates are required,
+    /// while the elevation and RFC 3339 time may be left out or empty. Other
+    /// columns are ignored, so the output of [`Track::to_csv`] reads back.
+    ///
+    /// A row that cannot be read fails with a
+    /// [`GpxError::InvalidCsvRow`] giving its line.
+    ///
+    /// ```
+    /// use gpx::TrackSegment;
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let csv = "lat,lon,ele\n47.37,8.54,408.2\n47.38,8.55,\n";
+    /// let segment: TrackSegment<EmptyExtensions> = TrackSegment::from_csv(csv.as_bytes()).unwrap();
+    /// assert_eq!(segment.points.len(), 2);
+    /// assert_eq!(segment.points[0].elevation, Some(408.2));
+    /// assert_eq!(segment.points[1].elevation, None);
+    /// ```
+    pub fn from_csv<R: Read>(reader: R) -> GpxResult<TrackSegment<E>> {
+        TrackSegment::from_csv_with(reader, &Default::default()).map(|(segment, _)| segment)
+    }
+
+    /// Reads a segment from CSV like [`TrackSegment::from_csv`], using the
+    /// given [`CsvOptions`]. Gives the segment along with the lines of the
+    /// rows skipped.
+    pub fn from_csv_with<R: Read>(mut reader: R, options: &CsvOptions) -> GpxResult<(TrackSegment<E>, Vec<usize>)> {
+        let mut csv = String::new();
+        reader.read_to_string(&mut csv)?;
+        let mut records = records(&csv).into_iter();
+
+        let header = match records.next() {
+            Some((_, header)) => header,
+            None => return Err(GpxError::InvalidCsvRow(1, String::from("missing header"))),
+        };
+        let column = |name: &str| header.iter().position(|field| field.trim().eq_ignore_ascii_case(name));
+        let columns = [column("lat"), column("lon"), column("ele"), column("time")];
+        for (column, name) in columns[..2].iter().zip(["lat", "lon"]) {
+            if column.is_none() {
+                return Err(GpxError::InvalidCsvRow(1, format!("no `{}` column", name)));
+            }
+        }
+
+        let mut segment = TrackSegment::new();
+        let mut skipped = Vec::new();
+        for (line, fields) in records {
+            match parse_row(&fields, &columns) {
+                Ok(point) => segment.points.push(point),
+                Err(_) if options.skip_malformed => skipped.push(line),
+                Err(message) => return Err(GpxError::InvalidCsvRow(line, message)),
+            }
+        }
+        Ok((segment, skipped))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+    use time::OffsetDateTime;
+
+    use crate::errors::GpxError;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{Track, TrackSegment, Waypoint};
+
+    use super::{quote, records, CsvOptions};
+
+    fn round_trip(segment: TrackSegment<EmptyExtensions>) {
+        let mut track = Track::new();
+        track.segments.push(segment.clone());
+        let mut csv = Vec::new();
+        track.to_csv(&mut csv).unwrap();
+        let read: TrackSegment<EmptyExtensions> = TrackSegment::from_csv(csv.as_slice()).unwrap();
+        assert_eq!(read, segment);
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let mut segment = TrackSegment::new();
+        for i in 0..3 {
+            let mut point = Waypoint::new(Point::new(8.5417 + f64::from(i) / 3.0, -47.376_912_345));
+            point.elevation = Some(408.25 + f64::from(i));
+            point.time = Some(OffsetDateTime::from_unix_timestamp_nanos(1_714_545_000_500_000_000).unwrap().into());
+            segment.points.push(point);
+        }
+        round_trip(segment.clone());
+
+        for point in &mut segment.points {
+            point.time = None;
+        }
+        round_trip(segment.clone());
+
+        for point in &mut segment.points {
+            point.elevation = None;
+        }
+        round_trip(segment);
+    }
+
+    #[test]
+    fn csv_columns_by_header() {
+        let csv = "\"time\",LON,lat\r\n2024-05-01T06:30:00+02:00,8.5,47.5\r\n\r\n";
+        let segment: TrackSegment<EmptyExtensions> = TrackSegment::from_csv(csv.as_bytes()).unwrap();
+        assert_eq!(segment.points.len(), 1);
+        assert_eq!(segment.points[0].point(), Point::new(8.5, 47.5));
+        assert_eq!(
+            segment.points[0].time.map(OffsetDateTime::from),
+            Some(OffsetDateTime::from_unix_timestamp(1_714_537_800).unwrap())
+        );
+    }
+
+    #[test]
+    fn csv_malformed_rows() {
+        let csv = "lat,lon,ele\n47.5,8.5,1\n47.6,east,2\n47.7,8.7,3\n,8.8,\n";
+        let result: Result<TrackSegment<EmptyExtensions>, _> = TrackSegment::from_csv(csv.as_bytes());
+        match result {
+            Err(GpxError::InvalidCsvRow(line, message)) => {
+                assert_eq!(line, 3);
+                assert_eq!(message, "`east` is not a number for `lon`");
+            }
+            other => panic!("expected an invalid row, got {:?}", other),
+        }
+
+        let options = CsvOptions { skip_malformed: true };
+        let (segment, skipped) = TrackSegment::<EmptyExtensions>::from_csv_with(csv.as_bytes(), &options).unwrap();
+        assert_eq!(segment.points.len(), 2);
+        assert_eq!(skipped, [3, 5]);
+
+        let result: Result<TrackSegment<EmptyExtensions>, _> = TrackSegment::from_csv("lat,ele\n".as_bytes());
+        assert!(matches!(result, Err(GpxError::InvalidCsvRow(1, _))));
+    }
+
+    #[test]
+    fn csv_quoting() {
+        assert_eq!(quote("1.5"), "1.5");
+        assert_eq!(quote("a,\"b\""), "\"a,\"\"b\"\"\"");
+        assert_eq!(
+            records("a,\"b,\"\"c\"\"\nd\"\n\ne,f"),
+            [
+                (1, vec![String::from("a"), String::from("b,\"c\"\nd")]),
+                (4, vec![String::from("e"), String::from("f")]),
+            ]
+        );
+    }
+}
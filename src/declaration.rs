@@ -0,0 +1,144 @@
+//! declaration reads what the start of a document tells of its encoding: its
+//! byte order mark, the first characters, and the XML declaration.
+
+use std::io::{self, ErrorKind, Read};
+use std::ops::Range;
+
+/// How many bytes are read at most looking for the end of the XML
+/// declaration.
+pub(crate) const DECLARATION_LIMIT: usize = 1024;
+
+/// The UTF-8 encoding of the byte order mark, U+FEFF.
+pub(crate) const UTF8_BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+
+/// How documents in UTF-16 and UTF-32 start, with a byte order mark or with
+/// the `<` of their first markup, after appendix F of the XML specification.
+/// The UTF-32LE byte order mark starts as the UTF-16LE one does.
+const WIDE_STARTS: [&[u8]; 5] = [b"\xFF\xFE", b"\xFE\xFF", b"\x00\x00", b"<\x00", b"\x00<"];
+
+/// How the labels of the encodings with characters of more than one byte
+/// start, such as `UTF-16LE` and `ucs-2`, in lowercase. A label of just
+/// `unicode` is UTF-16 too, while `unicode-1-1-utf-8` is not.
+const WIDE_LABELS: [&str; 11] = [
+    "utf-16",
+    "utf16",
+    "utf-32",
+    "utf32",
+    "ucs-2",
+    "ucs2",
+    "ucs-4",
+    "ucs4",
+    "iso-10646-ucs",
+    "unicodef",
+    "csunicode",
+];
+
+/// read_prefix reads the start of `reader` up to the end of its first markup,
+/// which is the XML declaration when there is one, and at most
+/// [`DECLARATION_LIMIT`] bytes.
+pub(crate) fn read_prefix<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut prefix = Vec::new();
+    let mut byte = [0];
+    while prefix.len() < DECLARATION_LIMIT && !prefix.ends_with(b">") {
+        match reader.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) => prefix.push(byte[0]),
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(prefix)
+}
+
+/// encoding_value gives where the value of the `encoding` attribute is in the
+/// XML declaration `text` starts with, if there is one.
+pub(crate) fn encoding_value(text: &str) -> Option<Range<usize>> {
+    let declaration = &text[..text.find("?>")?];
+    if !declaration.starts_with("<?xml") {
+        return None;
+    }
+    let name_end = declaration.find("encoding")? + "encoding".len();
+    let value = declaration[name_end..].trim_start().strip_prefix('=')?.trim_start();
+    let quote = value.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    let start = declaration.len() - value.len() + 1;
+    let end = start + declaration[start..].find(quote)?;
+    Some(start..end)
+}
+
+/// is_ascii_compatible tells whether the document starting with `prefix` is
+/// in an encoding writing ASCII characters as single bytes, as UTF-8 and
+/// Windows-1252 do and UTF-16 and UTF-32 do not, going by its byte order
+/// mark, its first characters and its declared encoding.
+pub(crate) fn is_ascii_compatible(prefix: &[u8]) -> bool {
+    if WIDE_STARTS.iter().any(|start| prefix.starts_with(start)) {
+        return false;
+    }
+    let prefix = prefix.strip_prefix(&UTF8_BOM[..]).unwrap_or(prefix);
+    let text = String::from_utf8_lossy(&prefix[..prefix.len().min(DECLARATION_LIMIT)]);
+    match encoding_value(&text) {
+        Some(range) => {
+            let label = text[range].trim().to_ascii_lowercase();
+            label != "unicode" && !WIDE_LABELS.iter().any(|wide| label.starts_with(wide))
+        }
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encoding_value, is_ascii_compatible, read_prefix};
+
+    /// utf16le encodes `text` in UTF-16LE, with a byte order mark.
+    fn utf16le(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        bytes
+    }
+
+    #[test]
+    fn encoding_value_in_declaration() {
+        let text = "<?xml version=\"1.0\" encoding = 'ISO-8859-1'?><gpx/>";
+        assert_eq!(encoding_value(text).map(|range| &text[range]), Some("ISO-8859-1"));
+
+        assert_eq!(encoding_value("<?xml version=\"1.0\"?><gpx/>"), None);
+        assert_eq!(encoding_value("<gpx encoding=\"latin1\"/>"), None);
+    }
+
+    #[test]
+    fn ascii_compatible_encodings() {
+        for prefix in [
+            &b"<gpx version=\"1.1\">"[..],
+            b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"UTF-8\"?>",
+            b"<?xml version=\"1.0\" encoding=\"windows-1252\"?>",
+            b"<?xml version=\"1.0\" encoding=\"ISO-8859-16\"?>",
+            b"<?xml version=\"1.0\" encoding=\"unicode-1-1-utf-8\"?>",
+            b"",
+        ] {
+            assert!(is_ascii_compatible(prefix), "{}", String::from_utf8_lossy(prefix));
+        }
+
+        let utf16 = utf16le("<?xml version=\"1.0\"?>");
+        let mut utf16be_without_bom: Vec<u8> = "<gpx>".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        utf16be_without_bom.truncate(4);
+        for prefix in [
+            &utf16[..],
+            &utf16be_without_bom[..],
+            &b"\x00\x00\xFE\xFF"[..],
+            &b"<?xml version=\"1.0\" encoding=\"UTF-16\"?>"[..],
+            &b"<?xml version=\"1.0\" encoding='ucs-2'?>"[..],
+            &b"<?xml version=\"1.0\" encoding=\"Unicode\"?>"[..],
+        ] {
+            assert!(!is_ascii_compatible(prefix), "{:?}", prefix);
+        }
+    }
+
+    #[test]
+    fn prefix_ends_with_first_markup() {
+        let mut input = &b"<?xml version=\"1.0\"?><gpx/>"[..];
+        assert_eq!(read_prefix(&mut input).unwrap(), b"<?xml version=\"1.0\"?>");
+        assert_eq!(input, b"<gpx/>");
+
+        let long = vec![b' '; 2000];
+        assert_eq!(read_prefix(&mut &long[..]).unwrap().len(), 1024);
+    }
+}
@@ -0,0 +1,80 @@
+//! derive starts documents derived from another one by a transformation,
+//! such as cropping or simplifying.
+
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Gpx, Metadata};
+
+/// Options for transformations giving a derived document, such as
+/// [`Gpx::crop_by_bounds_with`] or [`Gpx::simplified_with`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeriveOptions {
+    /// Append a note on the transformation, such as "simplified with
+    /// epsilon=5 m", to the description of the metadata.
+    pub provenance: bool,
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Gives a document with the version, creator and metadata of this one,
+    /// and nothing else, for a transformation to fill in. With
+    /// [`DeriveOptions::provenance`], the `note` on the transformation is
+    /// appended to the description.
+    pub(crate) fn derive_document<F>(&self, options: &DeriveOptions, note: F) -> Gpx<E>
+    where
+        F: FnOnce() -> String,
+    {
+        let mut metadata = self.metadata.clone();
+        if options.provenance {
+            let metadata = metadata.get_or_insert_with(Metadata::default);
+            let note = note();
+            metadata.description = Some(match metadata.description.take() {
+                Some(description) if !description.is_empty() => format!("{}\n{}", description, note),
+                _ => note,
+            });
+        }
+        Gpx {
+            version: self.version,
+            creator: self.creator.clone(),
+            metadata,
+            waypoints: Vec::new(),
+            tracks: Vec::new(),
+            routes: Vec::new(),
+            comments: self.comments.clone(),
+            namespaces: self.namespaces.clone(),
+            schema_locations: self.schema_locations.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{Gpx, GpxVersion, Metadata};
+
+    use super::DeriveOptions;
+
+    #[test]
+    fn derive_document_appends_note() {
+        let gpx: Gpx<EmptyExtensions> = Gpx {
+            version: GpxVersion::Gpx11,
+            creator: Some(String::from("device")),
+            metadata: Some(Metadata {
+                description: Some(String::from("Morning ride")),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let derived = gpx.derive_document(&DeriveOptions::default(), || unreachable!());
+        assert_eq!(derived.version, GpxVersion::Gpx11);
+        assert_eq!(derived.creator, gpx.creator);
+        assert_eq!(derived.metadata, gpx.metadata);
+
+        let options = DeriveOptions { provenance: true };
+        let derived = gpx.derive_document(&options, || String::from("cropped"));
+        let description = derived.metadata.unwrap().description;
+        assert_eq!(description.as_deref(), Some("Morning ride\ncropped"));
+
+        let derived = Gpx::<EmptyExtensions>::default().derive_document(&options, || String::from("cropped"));
+        assert_eq!(derived.metadata.unwrap().description.as_deref(), Some("cropped"));
+    }
+}
@@ -0,0 +1,217 @@
+//! distance provides great-circle distances between geographical points and
+//! the lengths of tracks and routes.
+//!
+//! Lengths use the haversine formula, which treats the earth as a sphere with
+//! the mean earth radius. Compared to distances on the WGS 84 ellipsoid, it is
+//! off by at most about 0.5%, usually much less. Enable the `geodesic` feature
+//! for the `length_geodesic_meters` methods, which use Karney's geodesic
+//! algorithm (through the `geo` crate) and are accurate to a few nanometers.
+
+#[cfg(feature = "geodesic")]
+use geo::GeodesicLength;
+use geo_types::Point;
+
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Gpx, Route, Track, TrackSegment, Waypoint};
+
+/// Mean earth radius in meters, as recommended by the IUGG.
+pub(crate) const MEAN_EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Gives the haversine distance in meters between two points given as
+/// (longitude, latitude) in degrees.
+pub(crate) fn haversine_distance(a: Point<f64>, b: Point<f64>) -> f64 {
+    let (lat_a, lat_b) = (a.y().to_radians(), b.y().to_radians());
+    let delta_lat = lat_b - lat_a;
+    let delta_lon = (b.x() - a.x()).to_radians();
+    let h = (delta_lat / 2.0).sin().powi(2)
+        + lat_a.cos() * lat_b.cos() * (delta_lon / 2.0).sin().powi(2);
+    2.0 * MEAN_EARTH_RADIUS * h.sqrt().asin()
+}
+
+/// Gives the great-circle distance in meters between two waypoints, using the
+/// haversine formula.
+///
+/// ```
+/// use geo_types::Point;
+/// use gpx::{distance_between, Waypoint};
+/// use gpx::parser::extensions::EmptyExtensions;
+///
+/// let equator: Waypoint<EmptyExtensions> = Waypoint::new(Point::new(0.0, 0.0));
+/// let north = Waypoint::new(Point::new(0.0, 1.0));
+/// assert_eq!(distance_between(&equator, &north).round(), 111_195.0);
+/// ```
+pub fn distance_between<E: WaypointExtensions + Default>(a: &Waypoint<E>, b: &Waypoint<E>) -> f64 {
+    haversine_distance(a.point(), b.point())
+}
+
+/// Gives the distance in meters between two waypoints like
+/// [`distance_between`], including the difference in elevation. Without the
+/// elevation of either waypoint, it is the same as [`distance_between`].
+pub fn distance_between_3d<E: WaypointExtensions + Default>(a: &Waypoint<E>, b: &Waypoint<E>) -> f64 {
+    let climb = match (a.elevation, b.elevation) {
+        (Some(a), Some(b)) => b - a,
+        _ => 0.0,
+    };
+    distance_between(a, b).hypot(climb)
+}
+
+/// Gives the haversine length in meters of the path through `points`.
+fn path_length(points: &[Waypoint<impl WaypointExtensions + Default>]) -> f64 {
+    points
+        .windows(2)
+        .map(|pair| haversine_distance(pair[0].point(), pair[1].point()))
+        .sum()
+}
+
+/// Gives the length in meters of the path through `points` like
+/// [`path_length`], including the climbs and descents, see
+/// [`distance_between_3d`].
+fn path_length_3d(points: &[Waypoint<impl WaypointExtensions + Default>]) -> f64 {
+    points.windows(2).map(|pair| distance_between_3d(&pair[0], &pair[1])).sum()
+}
+
+impl<E: WaypointExtensions + Default> TrackSegment<E> {
+    /// Gives the length of the segment in meters, using the haversine formula.
+    ///
+    /// Segments with less than two points have a length of 0.
+    pub fn length_meters(&self) -> f64 {
+        path_length(&self.points)
+    }
+
+    /// Gives the length of the segment in meters like [`length_meters`](Self::length_meters),
+    /// including the differences in elevation between points. The legs to or
+    /// from a point without an elevation count as flat.
+    pub fn length_3d_meters(&self) -> f64 {
+        path_length_3d(&self.points)
+    }
+
+    /// Gives the length of the segment in meters on the WGS 84 ellipsoid.
+    #[cfg(feature = "geodesic")]
+    pub fn length_geodesic_meters(&self) -> f64 {
+        self.linestring().geodesic_length()
+    }
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Gives the summed length of all segments in meters, using the haversine
+    /// formula. The gaps between segments are not included.
+    pub fn length_meters(&self) -> f64 {
+        self.segments.iter().map(TrackSegment::length_meters).sum()
+    }
+
+    /// Gives the summed length of all segments in meters, including the
+    /// differences in elevation, see [`TrackSegment::length_3d_meters`].
+    pub fn length_3d_meters(&self) -> f64 {
+        self.segments.iter().map(TrackSegment::length_3d_meters).sum()
+    }
+
+    /// Gives the summed length of all segments in meters on the WGS 84
+    /// ellipsoid.
+    #[cfg(feature = "geodesic")]
+    pub fn length_geodesic_meters(&self) -> f64 {
+        self.segments.iter().map(TrackSegment::length_geodesic_meters).sum()
+    }
+}
+
+impl<E: WaypointExtensions + Default> Route<E> {
+    /// Gives the length of the route in meters, using the haversine formula.
+    pub fn length_meters(&self) -> f64 {
+        path_length(&self.points)
+    }
+
+    /// Gives the length of the route in meters, including the differences in
+    /// elevation, see [`TrackSegment::length_3d_meters`].
+    pub fn length_3d_meters(&self) -> f64 {
+        path_length_3d(&self.points)
+    }
+
+    /// Gives the length of the route in meters on the WGS 84 ellipsoid.
+    #[cfg(feature = "geodesic")]
+    pub fn length_geodesic_meters(&self) -> f64 {
+        self.linestring().geodesic_length()
+    }
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Gives the summed length of all tracks and routes in meters, using the
+    /// haversine formula.
+    pub fn total_length_meters(&self) -> f64 {
+        let tracks: f64 = self.tracks.iter().map(Track::length_meters).sum();
+        let routes: f64 = self.routes.iter().map(Route::length_meters).sum();
+        tracks + routes
+    }
+
+    /// Gives the summed length of all tracks and routes in meters on the
+    /// WGS 84 ellipsoid.
+    #[cfg(feature = "geodesic")]
+    pub fn total_length_geodesic_meters(&self) -> f64 {
+        let tracks: f64 = self.tracks.iter().map(Track::length_geodesic_meters).sum();
+        let routes: f64 = self.routes.iter().map(Route::length_geodesic_meters).sum();
+        tracks + routes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+    use geo_types::Point;
+
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{TrackSegment, Waypoint};
+
+    use super::{distance_between, distance_between_3d, haversine_distance};
+
+    #[test]
+    fn haversine_distance_one_degree() {
+        let distance = haversine_distance(Point::new(0.0, 0.0), Point::new(1.0, 0.0));
+        assert_approx_eq!(distance, 111_195.08, 0.01);
+        assert_eq!(haversine_distance(Point::new(5.0, 5.0), Point::new(5.0, 5.0)), 0.0);
+    }
+
+    #[test]
+    fn distance_between_3d_adds_climb() {
+        let mut a: Waypoint<EmptyExtensions> = Waypoint::new(Point::new(0.0, 0.0));
+        let mut b = Waypoint::new(Point::new(0.0, 0.0036));
+        let flat = distance_between(&a, &b);
+        assert_eq!(distance_between_3d(&a, &b), flat);
+
+        a.elevation = Some(100.0);
+        assert_eq!(distance_between_3d(&a, &b), flat);
+
+        b.elevation = Some(400.0);
+        assert_approx_eq!(distance_between_3d(&a, &b), (flat * flat + 300.0 * 300.0).sqrt());
+        assert_approx_eq!(distance_between_3d(&b, &a), distance_between_3d(&a, &b));
+    }
+
+    #[test]
+    fn length_of_short_segments() {
+        let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+        assert_eq!(segment.length_meters(), 0.0);
+
+        segment.points.push(Waypoint::new(Point::new(1.0, 2.0)));
+        assert_eq!(segment.length_meters(), 0.0);
+
+        segment.points.push(Waypoint::new(Point::new(2.0, 2.0)));
+        let expect = haversine_distance(Point::new(1.0, 2.0), Point::new(2.0, 2.0));
+        assert_eq!(segment.length_meters(), expect);
+    }
+
+    #[test]
+    fn length_3d_of_elevated_segment() {
+        // Legs of about 400 m, climbing 300 m and descending 300 m, then one
+        // to a point without an elevation.
+        let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+        for (index, &elevation) in [Some(100.0), Some(400.0), Some(100.0), None].iter().enumerate() {
+            let mut point = Waypoint::new(Point::new(0.0, index as f64 * 0.0036));
+            point.elevation = elevation;
+            segment.points.push(point);
+        }
+        let leg = distance_between(&segment.points[0], &segment.points[1]);
+        assert_approx_eq!(segment.length_meters(), 3.0 * leg, 1e-6);
+        assert_approx_eq!(segment.length_3d_meters(), 2.0 * leg.hypot(300.0) + leg, 1e-6);
+        assert!(segment.length_3d_meters() - segment.length_meters() > 150.0);
+
+        segment.points.truncate(1);
+        assert_eq!(segment.length_3d_meters(), 0.0);
+    }
+}
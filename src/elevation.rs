@@ -0,0 +1,455 @@
+//! elevation computes climbing statistics and profiles from point
+//! elevations, and cleans them up.
+
+use crate::distance::haversine_distance;
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Gpx, Route, Track, TrackSegment, Waypoint};
+
+/// Sums two (gain, loss) pairs.
+fn add((gain_a, loss_a): (f64, f64), (gain_b, loss_b): (f64, f64)) -> (f64, f64) {
+    (gain_a + gain_b, loss_a + loss_b)
+}
+
+/// extend_profile adds the `(distance, elevation)` samples of `points` to
+/// `profile`, counting distances from `start`, and gives the distance at the
+/// last point.
+///
+/// Points without an elevation give no sample, unless `bridge_gaps` is set
+/// and they lie between two points with one, when they are given one
+/// interpolated by distance.
+fn extend_profile<E: WaypointExtensions + Default>(
+    points: &[Waypoint<E>],
+    start: f64,
+    bridge_gaps: bool,
+    profile: &mut Vec<(f64, f64)>,
+) -> f64 {
+    let mut distance = start;
+    // The distance and elevation of the last point with one, and the
+    // distances of the points without one since.
+    let mut previous: Option<(f64, f64)> = None;
+    let mut gap: Vec<f64> = Vec::new();
+    for (index, point) in points.iter().enumerate() {
+        if index > 0 {
+            distance += haversine_distance(points[index - 1].point(), point.point());
+        }
+        let elevation = match point.elevation {
+            Some(elevation) => elevation,
+            None => {
+                gap.push(distance);
+                continue;
+            }
+        };
+        if let Some((from_distance, from)) = previous.filter(|_| bridge_gaps) {
+            let span = distance - from_distance;
+            for &at in &gap {
+                let fraction = if span > 0.0 { (at - from_distance) / span } else { 0.0 };
+                profile.push((at, from + (elevation - from) * fraction));
+            }
+        }
+        gap.clear();
+        profile.push((distance, elevation));
+        previous = Some((distance, elevation));
+    }
+    distance
+}
+
+/// downsample keeps at most `max_samples` samples of `profile`, at least 2,
+/// splitting it into buckets of consecutive samples and keeping the lowest
+/// and highest of each, so no spike is lost.
+fn downsample(profile: Vec<(f64, f64)>, max_samples: Option<usize>) -> Vec<(f64, f64)> {
+    let max_samples = match max_samples {
+        Some(max_samples) if profile.len() > max_samples.max(2) => max_samples.max(2),
+        _ => return profile,
+    };
+    let buckets = max_samples / 2;
+    let mut kept = Vec::with_capacity(max_samples);
+    for bucket in 0..buckets {
+        let samples = &profile[bucket * profile.len() / buckets..(bucket + 1) * profile.len() / buckets];
+        let (mut low, mut high) = (0, 0);
+        for (index, &(_, elevation)) in samples.iter().enumerate() {
+            if elevation < samples[low].1 {
+                low = index;
+            }
+            if elevation > samples[high].1 {
+                high = index;
+            }
+        }
+        kept.push(samples[low.min(high)]);
+        if low != high {
+            kept.push(samples[low.max(high)]);
+        }
+    }
+    kept
+}
+
+impl<E: WaypointExtensions + Default> TrackSegment<E> {
+    /// Gives the total ascent and descent of the segment in meters, as
+    /// `(gain, loss)`, both positive.
+    ///
+    /// To filter out elevation noise, a change is only counted once it exceeds
+    /// `threshold` meters from the last counted elevation. With a threshold of
+    /// 0, every rise and fall between consecutive points counts. Points
+    /// without elevation are skipped.
+    pub fn elevation_gain_loss(&self, threshold: f64) -> (f64, f64) {
+        let mut elevations = self.points.iter().filter_map(|point| point.elevation);
+        let mut reference = match elevations.next() {
+            Some(elevation) => elevation,
+            None => return (0.0, 0.0),
+        };
+        let (mut gain, mut loss) = (0.0, 0.0);
+        for elevation in elevations {
+            let delta = elevation - reference;
+            if delta.abs() > threshold {
+                if delta > 0.0 {
+                    gain += delta;
+                } else {
+                    loss -= delta;
+                }
+                reference = elevation;
+            }
+        }
+        (gain, loss)
+    }
+
+    /// Smooths the elevations with a centered moving average, replacing each
+    /// with the mean of itself and up to `window / 2` elevations on either
+    /// side, fewer near the ends.
+    ///
+    /// Only points with an elevation take part: points without one are
+    /// neither given one nor counted in the window. Coordinates, times and
+    /// all else are left as they are.
+    pub fn smooth_elevation(&mut self, window: usize) {
+        let elevations: Vec<f64> = self.points.iter().filter_map(|point| point.elevation).collect();
+        let half = window / 2;
+        let smoothed = (0..elevations.len()).map(|index| {
+            let around = &elevations[index.saturating_sub(half)..(index + half + 1).min(elevations.len())];
+            around.iter().sum::<f64>() / around.len() as f64
+        });
+        let points = self.points.iter_mut().filter(|point| point.elevation.is_some());
+        for (point, elevation) in points.zip(smoothed) {
+            point.elevation = Some(elevation);
+        }
+    }
+
+    /// Gives the points without an elevation between two points with one an
+    /// elevation interpolated linearly between them, by distance along the
+    /// segment, giving how many points were given one.
+    ///
+    /// Points before the first elevation or after the last one are left
+    /// without one, as there is nothing to interpolate from.
+    pub fn fill_elevation_gaps(&mut self) -> usize {
+        let mut filled = 0;
+        let mut previous: Option<usize> = None;
+        for index in 0..self.points.len() {
+            if self.points[index].elevation.is_none() {
+                continue;
+            }
+            if let Some(start) = previous.filter(|&start| index > start + 1) {
+                filled += self.interpolate_elevations(start, index);
+            }
+            previous = Some(index);
+        }
+        filled
+    }
+
+    /// interpolate_elevations gives the points strictly between `start` and
+    /// `end`, which both have an elevation, elevations interpolated between
+    /// theirs, giving how many points were given one.
+    fn interpolate_elevations(&mut self, start: usize, end: usize) -> usize {
+        let points = &mut self.points[start..=end];
+        let mut distances = vec![0.0];
+        for pair in points.windows(2) {
+            let distance = haversine_distance(pair[0].point(), pair[1].point());
+            distances.push(distances[distances.len() - 1] + distance);
+        }
+        let total = distances[distances.len() - 1];
+        let last = points.len() - 1;
+        let (from, to) = (points[0].elevation.unwrap_or(0.0), points[last].elevation.unwrap_or(0.0));
+        for (index, point) in points.iter_mut().enumerate().take(last).skip(1) {
+            // Points all in one place are spread evenly instead.
+            let fraction = if total > 0.0 {
+                distances[index] / total
+            } else {
+                index as f64 / last as f64
+            };
+            point.elevation = Some(from + (to - from) * fraction);
+        }
+        last - 1
+    }
+
+    /// Gives the elevation profile of the segment for charting, as
+    /// `(distance, elevation)` pairs in meters, the distance being along the
+    /// segment from its first point.
+    ///
+    /// Points without an elevation are skipped. With `max_samples`, the
+    /// profile is downsampled to at most that many pairs, at least 2, keeping
+    /// the lowest and highest elevation of every stretch so spikes are not
+    /// lost.
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+    /// for (x, elevation) in [(0.0, Some(100.0)), (0.001, None), (0.002, Some(120.0))] {
+    ///     let mut point = Waypoint::new(Point::new(x, 0.0));
+    ///     point.elevation = elevation;
+    ///     segment.points.push(point);
+    /// }
+    ///
+    /// let profile = segment.elevation_profile(None);
+    /// assert_eq!(profile.len(), 2);
+    /// assert_eq!(profile[0], (0.0, 100.0));
+    /// assert_eq!(profile[1].1, 120.0);
+    ///
+    /// let bridged = segment.elevation_profile_with(None, true);
+    /// assert!((bridged[1].1 - 110.0).abs() < 1e-6);
+    /// ```
+    pub fn elevation_profile(&self, max_samples: Option<usize>) -> Vec<(f64, f64)> {
+        self.elevation_profile_with(max_samples, false)
+    }
+
+    /// Gives the elevation profile of the segment like
+    /// [`TrackSegment::elevation_profile`], except that with `bridge_gaps`,
+    /// points without an elevation between two points with one are given one
+    /// interpolated by distance rather than skipped.
+    pub fn elevation_profile_with(&self, max_samples: Option<usize>, bridge_gaps: bool) -> Vec<(f64, f64)> {
+        let mut profile = Vec::new();
+        extend_profile(&self.points, 0.0, bridge_gaps, &mut profile);
+        downsample(profile, max_samples)
+    }
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Gives the total ascent and descent over all segments, as computed by
+    /// [`TrackSegment::elevation_gain_loss`].
+    pub fn elevation_gain_loss(&self, threshold: f64) -> (f64, f64) {
+        self.segments
+            .iter()
+            .map(|segment| segment.elevation_gain_loss(threshold))
+            .fold((0.0, 0.0), add)
+    }
+
+    /// Gives the elevation profile of the track like
+    /// [`TrackSegment::elevation_profile`], the distance running on from one
+    /// segment to the next without the gaps between them, as in
+    /// [`Track::length_meters`].
+    pub fn elevation_profile(&self, max_samples: Option<usize>) -> Vec<(f64, f64)> {
+        self.elevation_profile_with(max_samples, false)
+    }
+
+    /// Gives the elevation profile of the track like
+    /// [`Track::elevation_profile`], bridging gaps in the elevations of each
+    /// segment as in [`TrackSegment::elevation_profile_with`].
+    pub fn elevation_profile_with(&self, max_samples: Option<usize>, bridge_gaps: bool) -> Vec<(f64, f64)> {
+        let mut profile = Vec::new();
+        let mut distance = 0.0;
+        for segment in &self.segments {
+            distance = extend_profile(&segment.points, distance, bridge_gaps, &mut profile);
+        }
+        downsample(profile, max_samples)
+    }
+}
+
+impl<E: WaypointExtensions + Default> Route<E> {
+    /// Gives the elevation profile of the route like
+    /// [`TrackSegment::elevation_profile`].
+    pub fn elevation_profile(&self, max_samples: Option<usize>) -> Vec<(f64, f64)> {
+        self.elevation_profile_with(max_samples, false)
+    }
+
+    /// Gives the elevation profile of the route like
+    /// [`TrackSegment::elevation_profile_with`].
+    pub fn elevation_profile_with(&self, max_samples: Option<usize>, bridge_gaps: bool) -> Vec<(f64, f64)> {
+        let mut profile = Vec::new();
+        extend_profile(&self.points, 0.0, bridge_gaps, &mut profile);
+        downsample(profile, max_samples)
+    }
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Gives the total ascent and descent over all tracks, as computed by
+    /// [`TrackSegment::elevation_gain_loss`].
+    pub fn elevation_gain_loss(&self, threshold: f64) -> (f64, f64) {
+        self.tracks
+            .iter()
+            .map(|track| track.elevation_gain_loss(threshold))
+            .fold((0.0, 0.0), add)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+    use geo_types::Point;
+
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::testing;
+    use crate::{read, Gpx, Route, Track, TrackSegment, Waypoint};
+
+    /// with_elevations gives the points of `segment` the given elevations.
+    fn with_elevations(mut segment: TrackSegment<EmptyExtensions>, elevations: &[Option<f64>]) -> TrackSegment<EmptyExtensions> {
+        for (point, &elevation) in segment.points.iter_mut().zip(elevations) {
+            point.elevation = elevation;
+        }
+        segment
+    }
+
+    fn segment(elevations: &[Option<f64>]) -> TrackSegment<EmptyExtensions> {
+        with_elevations(testing::segment(&vec![(0.0, 0.0); elevations.len()]), elevations)
+    }
+
+    #[test]
+    fn sawtooth_gain_loss() {
+        // Small 2-3 m teeth riding on two large 10 m climbs.
+        let segment = segment(&[
+            Some(0.0),
+            Some(3.0),
+            Some(1.0),
+            Some(4.0),
+            None,
+            Some(2.0),
+            Some(12.0),
+            Some(10.0),
+            Some(22.0),
+            None,
+            Some(20.0),
+        ]);
+
+        assert_eq!(segment.elevation_gain_loss(0.0), (28.0, 8.0));
+        assert_eq!(segment.elevation_gain_loss(5.0), (22.0, 0.0));
+    }
+
+    #[test]
+    fn gain_loss_without_elevation() {
+        assert_eq!(segment(&[]).elevation_gain_loss(0.0), (0.0, 0.0));
+        assert_eq!(segment(&[None, Some(5.0), None]).elevation_gain_loss(0.0), (0.0, 0.0));
+    }
+
+    fn elevations(segment: &TrackSegment<EmptyExtensions>) -> Vec<Option<f64>> {
+        segment.points.iter().map(|point| point.elevation).collect()
+    }
+
+    #[test]
+    fn smooth_elevation_profile() {
+        let mut segment = segment(&[Some(10.0), Some(16.0), None, Some(10.0), Some(16.0), Some(10.0), Some(4.0)]);
+        segment.smooth_elevation(3);
+        assert_eq!(
+            elevations(&segment),
+            [Some(13.0), Some(12.0), None, Some(14.0), Some(12.0), Some(10.0), Some(7.0)]
+        );
+
+        // A window of one, or less, leaves the elevations as they are.
+        let mut unchanged = segment.clone();
+        unchanged.smooth_elevation(1);
+        assert_eq!(unchanged, segment);
+    }
+
+    #[test]
+    fn fill_elevation_gaps_by_distance() {
+        let mut segment = segment(&[None, Some(100.0), None, None, None, Some(140.0), None]);
+        for (index, point) in segment.points.iter_mut().enumerate() {
+            // Equally spaced but for the third point, a quarter of the way
+            // from the second.
+            let x = if index == 2 { 1.25 } else { index as f64 };
+            let elevation = point.elevation;
+            *point = Waypoint::new(Point::new(x * 0.001, 0.0));
+            point.elevation = elevation;
+        }
+
+        assert_eq!(segment.fill_elevation_gaps(), 3);
+        let filled = elevations(&segment);
+        assert_eq!(filled[0], None);
+        assert_approx_eq!(filled[2].unwrap(), 102.5, 1e-6);
+        assert_approx_eq!(filled[3].unwrap(), 120.0, 1e-6);
+        assert_approx_eq!(filled[4].unwrap(), 130.0, 1e-6);
+        assert_eq!(filled[6], None);
+
+        // Points all in one place are filled evenly.
+        let mut stacked = self::segment(&[Some(0.0), None, None, Some(30.0)]);
+        assert_eq!(stacked.fill_elevation_gaps(), 2);
+        assert_eq!(elevations(&stacked), [Some(0.0), Some(10.0), Some(20.0), Some(30.0)]);
+    }
+
+    #[test]
+    fn gain_loss_rolls_up() {
+        let mut track: Track<EmptyExtensions> = Track::new();
+        track.segments.push(segment(&[Some(0.0), Some(10.0)]));
+        track.segments.push(segment(&[Some(10.0), Some(4.0)]));
+        assert_eq!(track.elevation_gain_loss(0.0), (10.0, 6.0));
+
+        let mut gpx: Gpx<EmptyExtensions> = Default::default();
+        gpx.tracks.push(track.clone());
+        gpx.tracks.push(track);
+        assert_eq!(gpx.elevation_gain_loss(0.0), (20.0, 12.0));
+    }
+
+    /// A segment of points 0.001° apart going east, with the given
+    /// elevations.
+    fn spaced(elevations: &[Option<f64>]) -> TrackSegment<EmptyExtensions> {
+        let coordinates: Vec<_> = (0..elevations.len()).map(|index| (index as f64 * 0.001, 0.0)).collect();
+        with_elevations(testing::segment(&coordinates), elevations)
+    }
+
+    #[test]
+    fn elevation_profile_skips_or_bridges() {
+        let segment = spaced(&[None, Some(100.0), None, None, Some(130.0), None]);
+        let step = segment.cumulative_distances()[1];
+
+        let skipped = segment.elevation_profile(None);
+        assert_eq!(skipped.len(), 2);
+        assert_approx_eq!(skipped[0].0, step);
+        assert_approx_eq!(skipped[1].0, 4.0 * step);
+
+        let bridged = segment.elevation_profile_with(None, true);
+        let elevations: Vec<f64> = bridged.iter().map(|&(_, elevation)| elevation).collect();
+        assert_eq!(bridged.len(), 4);
+        assert_approx_eq!(elevations[1], 110.0, 1e-6);
+        assert_approx_eq!(elevations[2], 120.0, 1e-6);
+        assert_approx_eq!(bridged[2].0, 3.0 * step);
+
+        let route = Route {
+            points: segment.points.clone(),
+            ..Default::default()
+        };
+        assert_eq!(route.elevation_profile_with(None, true), bridged);
+        assert!(TrackSegment::<EmptyExtensions>::new().elevation_profile(Some(10)).is_empty());
+    }
+
+    #[test]
+    fn track_elevation_profile_runs_on() {
+        let mut track: Track<EmptyExtensions> = Track::new();
+        track.segments.push(spaced(&[Some(1.0), Some(2.0), Some(3.0)]));
+        track.segments.push(spaced(&[Some(4.0), None, Some(6.0)]));
+        let length = track.segments[0].length_meters();
+
+        let profile = track.elevation_profile(None);
+        assert_eq!(profile.len(), 5);
+        assert!(profile.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert_approx_eq!(profile[3].0, length);
+        assert_approx_eq!(profile[4].0, 2.0 * length);
+    }
+
+    #[test]
+    fn elevation_profile_downsampling_keeps_extremes() {
+        let gpx = read(std::fs::File::open("tests/fixtures/garmin-activity.gpx").unwrap()).unwrap();
+        let track = &gpx.tracks[0];
+        let full = track.elevation_profile(None);
+        assert!(full.len() > 100);
+        assert!(full.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+
+        let extremes = |profile: &[(f64, f64)]| {
+            profile.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), &(_, elevation)| {
+                (low.min(elevation), high.max(elevation))
+            })
+        };
+        for max_samples in [2, 3, 10, 51] {
+            let downsampled = track.elevation_profile(Some(max_samples));
+            assert!(downsampled.len() <= max_samples, "{} samples", max_samples);
+            assert!(downsampled.windows(2).all(|pair| pair[0].0 < pair[1].0));
+            assert_eq!(extremes(&downsampled), extremes(&full));
+        }
+        assert_eq!(track.elevation_profile(Some(full.len())), full);
+    }
+}
@@ -0,0 +1,151 @@
+//! encoding converts documents in other encodings than UTF-8, as declared in
+//! their XML declaration, to UTF-8 before they are parsed.
+
+use std::io::{self, Read};
+
+use encoding_rs::{CoderResult, Decoder, Encoding, REPLACEMENT, UTF_8};
+
+use crate::declaration::{encoding_value, read_prefix};
+
+/// How many bytes are decoded at once.
+const CHUNK_SIZE: usize = 8192;
+
+/// TranscodingReader reads a document in the encoding its XML declaration
+/// names, or its byte order mark gives, and gives it in UTF-8, with the
+/// declaration changed to match.
+///
+/// Wrapping the input of any of the read functions with it lets them read
+/// documents the XML parser cannot read itself, such as the Windows-1252 and
+/// Latin-1 documents some older software exports. Documents in UTF-8, or in
+/// an encoding not known, are given as they are.
+///
+/// ```
+/// use gpx::{read, TranscodingReader};
+///
+/// let data: &[u8] = b"<?xml version=\"1.0\" encoding=\"windows-1252\"?>\
+///     <gpx version=\"1.1\"><wpt lat=\"48.85\" lon=\"2.35\"><name>Caf\xe9</name></wpt></gpx>";
+///
+/// let gpx = read(TranscodingReader::new(data)).unwrap();
+/// assert_eq!(gpx.waypoints[0].name.as_deref(), Some("Café"));
+/// ```
+pub struct TranscodingReader<R: Read> {
+    inner: R,
+    started: bool,
+    /// Decodes the rest of the input, until it ends; `None` when the input
+    /// is given as it is.
+    decoder: Option<Decoder>,
+    /// UTF-8 not given yet, from `position` on.
+    output: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> TranscodingReader<R> {
+    pub fn new(inner: R) -> TranscodingReader<R> {
+        TranscodingReader {
+            inner,
+            started: false,
+            decoder: None,
+            output: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// start reads the XML declaration to find the encoding of the input,
+    /// and sets up decoding it when it is not UTF-8.
+    fn start(&mut self) -> io::Result<()> {
+        self.started = true;
+        let prefix = read_prefix(&mut self.inner)?;
+
+        let encoding = match Encoding::for_bom(&prefix) {
+            Some((encoding, _)) => Some(encoding),
+            None => {
+                let text = String::from_utf8_lossy(&prefix);
+                encoding_value(&text).and_then(|range| Encoding::for_label(text[range].as_bytes()))
+            }
+        };
+        match encoding {
+            Some(encoding) if encoding != UTF_8 && encoding != REPLACEMENT => {
+                let mut decoder = encoding.new_decoder_with_bom_removal();
+                let mut text = decode(&mut decoder, &prefix, false);
+                if let Some(range) = encoding_value(&text) {
+                    text.replace_range(range, "UTF-8");
+                }
+                self.decoder = Some(decoder);
+                self.output = text.into_bytes();
+            }
+            _ => self.output = prefix,
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for TranscodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.started {
+            self.start()?;
+        }
+        loop {
+            if self.position < self.output.len() {
+                let read = (&self.output[self.position..]).read(buf)?;
+                self.position += read;
+                return Ok(read);
+            }
+            let decoder = match &mut self.decoder {
+                Some(decoder) => decoder,
+                None => return self.inner.read(buf),
+            };
+            let mut chunk = [0; CHUNK_SIZE];
+            let read = self.inner.read(&mut chunk)?;
+            self.output = decode(decoder, &chunk[..read], read == 0).into_bytes();
+            self.position = 0;
+            if read == 0 {
+                self.decoder = None;
+            }
+        }
+    }
+}
+
+/// decode decodes all of `input` to UTF-8, replacing malformed sequences.
+fn decode(decoder: &mut Decoder, mut input: &[u8], last: bool) -> String {
+    let capacity = decoder.max_utf8_buffer_length(input.len()).unwrap_or(input.len());
+    let mut text = String::with_capacity(capacity);
+    loop {
+        let (result, read, _) = decoder.decode_to_string(input, &mut text, last);
+        input = &input[read..];
+        match result {
+            CoderResult::InputEmpty => return text,
+            CoderResult::OutputFull => text.reserve(input.len() + 16),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::TranscodingReader;
+
+    fn transcode(input: &[u8]) -> String {
+        let mut output = String::new();
+        TranscodingReader::new(input).read_to_string(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn transcode_latin1() {
+        let input = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<name>Z\xfcrich</name>";
+        assert_eq!(
+            transcode(input),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<name>Zürich</name>"
+        );
+    }
+
+    #[test]
+    fn transcode_utf8_unchanged() {
+        let input = "<?xml version=\"1.0\" encoding=\"utf-8\"?><name>Zürich</name>";
+        assert_eq!(transcode(input.as_bytes()), input);
+
+        let input = "<gpx><name>Zürich</name></gpx>";
+        assert_eq!(transcode(input.as_bytes()), input);
+    }
+}
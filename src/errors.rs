@@ -4,6 +4,8 @@ use std::num::{ParseFloatError, ParseIntError};
 
 use thiserror::Error;
 
+use crate::parser::time::EpochUnit;
+
 pub type GpxResult<T> = Result<T, GpxError>;
 
 #[derive(Error, Debug)]
@@ -12,6 +14,8 @@ pub type GpxResult<T> = Result<T, GpxError>;
 pub enum GpxError {
     #[error("error while casting to f64")]
     ParseFloatError(#[from] ParseFloatError),
+    #[error("`{1}` holds `{0}`, which is not a number")]
+    NotANumber(String, &'static str),
     #[error("Could not convert value to u32")]
     ParseIntegerError(#[from] ParseIntError),
     #[error("invalid child element `{0}` in `{1}`")]
@@ -27,11 +31,13 @@ pub enum GpxError {
     #[error("minimum `{0}` larger than maximum `{0}`")]
     OutOfBounds(&'static str),
     #[error("error while parsing XML")]
-    XmlParseError(#[from] xml::reader::Error),
+    XmlParseError(#[source] xml::reader::Error),
     #[error("unknown GPX version: `{0}`")]
     UnknownVersionError(crate::types::GpxVersion),
     #[error("tag opened twice: `{0}`")]
     TagOpenedTwice(&'static str),
+    #[error("`{0}` repeated in `{1}`, which allows only one")]
+    DuplicateElement(&'static str, &'static str),
     #[error("error while parsing 'track' segment")]
     TrackSegmentError(),
     #[error("no string content")]
@@ -42,8 +48,8 @@ pub enum GpxError {
     MissingEmailPartError(&'static str),
     #[error("email contains multiple `@` symbols")]
     TooManyAtsError,
-    #[error("error while parsing `{0}`")]
-    EventParsingError(&'static str),
+    #[error("error while parsing `{0}`: {1}")]
+    EventParsingError(&'static str, #[source] xml::reader::Error),
     #[error("error while parsing metadata")]
     MetadataParsingError(),
     #[error("invalid `{0}`: must be between `{1}`. Actual value: `{2}`")]
@@ -52,4 +58,247 @@ pub enum GpxError {
     Iso8601Error(#[from] time::error::Parse),
     #[error("error trying to write ISO8601 formatted date")]
     Iso8601ErrorWriting(#[from] time::error::Format),
+    #[error("`{1}` is missing its `{0}` coordinate")]
+    MissingCoordinate(&'static str, &'static str),
+    #[error("unix epoch timestamp `{0}` is not an ISO8601 formatted date")]
+    EpochTimestamp(String),
+    #[error("unix time `{0}` is beyond the years -9999 to 9999")]
+    UnixTimeOutOfRange(i64),
+    #[error("time of point `{0}` is beyond the years -9999 to 9999")]
+    TimeOutOfRange(usize),
+    #[error("speed `{0}` is not a positive number of meters per second")]
+    InvalidSpeed(f64),
+    #[error("interval `{0}` is not positive, or too small to resample with")]
+    InvalidInterval(f64),
+    #[error("input differs from the one the checkpoint was taken from")]
+    CheckpointMismatch,
+    #[error("output violates the GPX schema in {} places", .0.len())]
+    SchemaViolations(Vec<crate::schema::SchemaViolation>),
+    #[error("no schema rules for GPX version `{0}`")]
+    NoSchemaRules(crate::types::GpxVersion),
+    #[error("input exceeds the `{0}` limit")]
+    LimitExceeded(&'static str),
+    #[error("feature {0} has a `{1}` geometry, which has no GPX equivalent")]
+    UnsupportedGeometry(usize, &'static str),
+    #[error("the `{0}` option is not supported on this target")]
+    UnsupportedOption(&'static str),
+    #[error("{1} at `{0}`")]
+    InvariantViolation(String, crate::invariants::Invariant),
+    #[error("line {0} of the CSV: {1}")]
+    InvalidCsvRow(usize, String),
+    #[error("line {0} of the NMEA log: {1}")]
+    InvalidNmeaSentence(usize, &'static str),
+    #[error("invalid polyline at byte {0}: {1}")]
+    InvalidPolyline(usize, &'static str),
+    #[error("error while reading or writing")]
+    IoError(#[from] std::io::Error),
+    #[error("parsing took longer than allowed, after {points} points")]
+    DeadlineExceeded {
+        /// How many bytes of the input had been read, unknown for contexts
+        /// made with [`Context::new`](crate::parser::Context::new).
+        bytes: Option<u64>,
+        /// How many waypoints, route points and track points had been parsed.
+        points: u64,
+    },
+}
+
+/// What kind of failure a [`GpxError`] is, a stable set to branch on where
+/// the variants of [`GpxError`] keep growing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// Reading or writing failed, such as a file that could not be read.
+    Io,
+    /// The input is not well-formed XML, or XML could not be written.
+    Xml,
+    /// Elements are missing, misplaced, repeated or unclosed, or lack a
+    /// required attribute.
+    InvalidStructure,
+    /// A value could not be read, or is outside its range.
+    InvalidValue,
+    /// The input exceeded a [`ParseLimits`](crate::ParseLimits) limit, or
+    /// the [`ParseOptions::timeout`](crate::ParseOptions::timeout).
+    Limit,
+    /// The input is valid, yet beyond what this crate handles, such as an
+    /// unknown GPX version, or an option is not supported on the target,
+    /// such as a timeout on `wasm32-unknown-unknown`.
+    Unsupported,
+}
+
+impl GpxError {
+    /// Gives what kind of failure this is.
+    ///
+    /// ```
+    /// use gpx::errors::ErrorKind;
+    ///
+    /// let err = gpx::read("<gpx version=\"1.1\"><wpt lat=\"91\" lon=\"0\"/></gpx>".as_bytes()).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::InvalidValue);
+    /// ```
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            GpxError::IoError(_) => ErrorKind::Io,
+            GpxError::XmlParseError(err) | GpxError::EventParsingError(_, err)
+                if matches!(err.kind(), xml::reader::ErrorKind::Io(_)) =>
+            {
+                ErrorKind::Io
+            }
+            GpxError::XmlParseError(_) | GpxError::XmlWriteError(_) | GpxError::EventParsingError(..) => ErrorKind::Xml,
+            GpxError::InvalidChildElement(..)
+            | GpxError::InvalidClosingTag(..)
+            | GpxError::MissingClosingTag(_)
+            | GpxError::MissingOpeningTag(_)
+            | GpxError::InvalidElementLacksAttribute(..)
+            | GpxError::TagOpenedTwice(_)
+            | GpxError::DuplicateElement(..)
+            | GpxError::TrackSegmentError()
+            | GpxError::NoStringContent
+            | GpxError::MetadataParsingError()
+            | GpxError::MissingCoordinate(..)
+            | GpxError::CheckpointMismatch
+            | GpxError::SchemaViolations(_) => ErrorKind::InvalidStructure,
+            GpxError::ParseFloatError(_)
+            | GpxError::NotANumber(..)
+            | GpxError::ParseIntegerError(_)
+            | GpxError::OutOfBounds(_)
+            | GpxError::MissingEmailPartError(_)
+            | GpxError::TooManyAtsError
+            | GpxError::LonLatOutOfBoundsError(..)
+            | GpxError::Iso8601Error(_)
+            | GpxError::Iso8601ErrorWriting(_)
+            | GpxError::EpochTimestamp(_)
+            | GpxError::UnixTimeOutOfRange(_)
+            | GpxError::TimeOutOfRange(_)
+            | GpxError::InvalidSpeed(_)
+            | GpxError::InvalidInterval(_)
+            | GpxError::InvariantViolation(..)
+            | GpxError::InvalidCsvRow(..)
+            | GpxError::InvalidNmeaSentence(..)
+            | GpxError::InvalidPolyline(..) => ErrorKind::InvalidValue,
+            GpxError::LimitExceeded(_) | GpxError::DeadlineExceeded { .. } => ErrorKind::Limit,
+            GpxError::UnknownVersionError(_) | GpxError::NoSchemaRules(_) | GpxError::UnsupportedGeometry(..)
+            | GpxError::UnsupportedOption(_) => {
+                ErrorKind::Unsupported
+            }
+        }
+    }
+
+    /// Gives an error of the XML parser met while parsing `element`, keeping
+    /// it as the source, or the limit it is when it is one.
+    pub(crate) fn in_element(element: &'static str, err: xml::reader::Error) -> GpxError {
+        match GpxError::from(err) {
+            GpxError::XmlParseError(err) => GpxError::EventParsingError(element, err),
+            err => err,
+        }
+    }
+}
+
+impl From<xml::reader::Error> for GpxError {
+    fn from(err: xml::reader::Error) -> GpxError {
+        // xml-rs only tells its limits apart from other syntax errors by
+        // their messages.
+        match err.kind() {
+            xml::reader::ErrorKind::Syntax(message) if message == "Entity too big" => {
+                GpxError::LimitExceeded("entity expansion")
+            }
+            xml::reader::ErrorKind::Syntax(message) if message.contains("allowed by the parser's configuration") => {
+                GpxError::LimitExceeded("document size")
+            }
+            _ => GpxError::XmlParseError(err),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+/// Issues the parser recovered from while reading in lenient mode
+pub enum GpxWarning {
+    /// A `<time>` held a unix epoch timestamp instead of an ISO8601 date.
+    EpochTimestamp { value: String, unit: EpochUnit },
+    /// A `<time>` held only a date, taken as midnight UTC.
+    DateOnly { value: String },
+    /// A point lacking its `missing` coordinate was left out.
+    SkippedPoint {
+        element: &'static str,
+        missing: &'static str,
+    },
+    /// An `element` unknown to its `parent` was left out.
+    UnknownElement {
+        element: String,
+        parent: &'static str,
+    },
+    /// An `element` repeated in a `parent` that allows only one was merged
+    /// into the first, such as the `<extensions>` of a point, merged with
+    /// [`WaypointExtensions::merge`](crate::parser::extensions::WaypointExtensions::merge).
+    DuplicateElement {
+        element: &'static str,
+        parent: &'static str,
+    },
+    /// A `coordinate` of an `element` outside its range was replaced: a
+    /// latitude is clamped to [-90, 90] and a longitude wrapped into
+    /// [-180, 180).
+    OutOfRange {
+        element: &'static str,
+        coordinate: &'static str,
+        value: f64,
+        replacement: f64,
+    },
+    /// A `<time>` that is not a date was left out.
+    MalformedTime { value: String },
+    /// An integer `element` written with a fraction was truncated.
+    FractionalNumber {
+        element: &'static str,
+        value: String,
+        replacement: u64,
+    },
+    /// A decimal `element` written with a comma for its decimal point, as
+    /// some exporters do in locales writing numbers so, was read as if it
+    /// had a point.
+    DecimalComma {
+        element: &'static str,
+        value: String,
+        replacement: f64,
+    },
+    /// A value of an `element` that is not a number, or a negative one where
+    /// it cannot be, was left out.
+    InvalidNumber { element: &'static str, value: String },
+    /// A track style `value` of an `element`, such as a color that is not
+    /// six hex digits or an empty width, was left out.
+    InvalidStyle { element: &'static str, value: String },
+    /// A value of an `element` above [`ParseOptions::sat_sentinel`], taken
+    /// for a firmware's way of saying "unknown", was left out.
+    ///
+    /// [`ParseOptions::sat_sentinel`]: crate::ParseOptions::sat_sentinel
+    UnknownSentinel { element: &'static str, value: u64 },
+    /// A control character that XML does not allow, such as a NUL, was
+    /// stripped from the input. Only tab, line feed and carriage return are
+    /// allowed.
+    ControlCharacter { byte: u8 },
+    /// An ampersand starting no reference, such as the one of a query string
+    /// in a link, was escaped as `&amp;`, following
+    /// [`ParseOptions::repair_entities`](crate::ParseOptions::repair_entities).
+    /// The `offset` is that of the ampersand in the input, in bytes.
+    RawAmpersand { offset: usize },
+    /// An NMEA `sentence` that could not be read, for the `reason` given, was
+    /// left out.
+    SkippedSentence { sentence: String, reason: &'static str },
+    /// The document was normalized after parsing, following
+    /// [`ParseOptions::post_normalize`](crate::ParseOptions::post_normalize).
+    Normalized(crate::invariants::Fixup),
+}
+
+/// A position in the input, as one-based line and column, counting columns
+/// in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: u64,
+    pub column: u64,
+}
+
+/// A [`GpxWarning`] with the position the parser had read up to when it
+/// recovered, which is just past the tag at fault.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReadWarning {
+    pub warning: GpxWarning,
+    /// The position, unknown for contexts made with
+    /// [`Context::new`](crate::parser::Context::new).
+    pub position: Option<Position>,
 }
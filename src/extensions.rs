@@ -0,0 +1,308 @@
+//! extensions tells which extension schemas a document uses, and reads it
+//! with the built-in consumers of those it finds.
+
+use std::io::Read;
+
+use xml::reader::XmlEvent;
+
+use crate::errors::{GpxError, GpxResult};
+use crate::parser::create_context_with_options;
+pub use crate::parser::extensions::DetectedExtensions;
+use crate::parser::extensions::{
+    EmptyExtensions, GARMIN_NAMESPACE, GARMIN_TRACK_POINT_NAMESPACE, GARMIN_TRACK_POINT_V2_NAMESPACE,
+};
+use crate::parser::style::GPX_STYLE_NAMESPACE;
+use crate::{read_with_extensions, ExtensionSchema, Gpx, GpxVersion, ParseOptions};
+
+/// An extension schema that [`detect`] knows of. The schemas are ordered as
+/// `detect` reports them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum KnownSchema {
+    /// Garmin's `TrackPointExtension/v1`, with heart rate, cadence and
+    /// temperatures, read by
+    /// [`GarminTrackPointExtensions`](crate::parser::extensions::GarminTrackPointExtensions).
+    GarminTrackPointV1,
+    /// Garmin's `TrackPointExtension/v2`, which adds speed and course to
+    /// version 1.
+    GarminTrackPointV2,
+    /// Garmin's `GpxExtensions/v3`, the `gpxx` extensions of waypoints, routes
+    /// and tracks, read by
+    /// [`GarminExtensions`](crate::parser::extensions::GarminExtensions).
+    GarminGpxx,
+    /// Cluetrust's `gpxdata`, written by older training software.
+    Cluetrust,
+    /// The extensions of the OsmAnd app.
+    OsmAnd,
+    /// Groundspeak's geocache descriptions.
+    Groundspeak,
+    /// Topografix's `gpx_style`, for how tracks and routes are drawn.
+    GpxStyle,
+}
+
+impl KnownSchema {
+    /// Every known schema, in the order [`detect`] reports them.
+    pub const ALL: [KnownSchema; 7] = [
+        KnownSchema::GarminTrackPointV1,
+        KnownSchema::GarminTrackPointV2,
+        KnownSchema::GarminGpxx,
+        KnownSchema::Cluetrust,
+        KnownSchema::OsmAnd,
+        KnownSchema::Groundspeak,
+        KnownSchema::GpxStyle,
+    ];
+
+    /// Gives the schema with the namespace `uri`, if it is known. Groundspeak
+    /// schemas are known in all their versions.
+    pub fn from_namespace(uri: &str) -> Option<KnownSchema> {
+        match uri.trim_end_matches('/') {
+            GARMIN_TRACK_POINT_NAMESPACE => Some(KnownSchema::GarminTrackPointV1),
+            GARMIN_TRACK_POINT_V2_NAMESPACE => Some(KnownSchema::GarminTrackPointV2),
+            GARMIN_NAMESPACE => Some(KnownSchema::GarminGpxx),
+            "http://www.cluetrust.com/XML/GPXDATA/1/0" => Some(KnownSchema::Cluetrust),
+            "https://osmand.net" | "http://osmand.net" => Some(KnownSchema::OsmAnd),
+            GPX_STYLE_NAMESPACE => Some(KnownSchema::GpxStyle),
+            uri if uri.starts_with("http://www.groundspeak.com/cache/1/") => Some(KnownSchema::Groundspeak),
+            _ => None,
+        }
+    }
+
+    /// Gives the element of this schema that the crate reads and the type
+    /// reading it, or `None` when its extensions are only kept as they are
+    /// by [`RawExtensions`](crate::parser::extensions::RawExtensions).
+    /// [`capabilities`](crate::capabilities) lists these.
+    pub fn consumer(self) -> Option<ExtensionSchema> {
+        let (element, namespace, consumer) = match self {
+            KnownSchema::GarminTrackPointV1 => (
+                "TrackPointExtension",
+                GARMIN_TRACK_POINT_NAMESPACE,
+                "GarminTrackPointExtensions",
+            ),
+            KnownSchema::GarminTrackPointV2 => (
+                "TrackPointExtension",
+                GARMIN_TRACK_POINT_V2_NAMESPACE,
+                "GarminTrackPointExtensions",
+            ),
+            KnownSchema::GarminGpxx => ("WaypointExtension", GARMIN_NAMESPACE, "GarminExtensions"),
+            KnownSchema::GpxStyle => ("line", GPX_STYLE_NAMESPACE, "TrackStyle"),
+            KnownSchema::Cluetrust | KnownSchema::OsmAnd | KnownSchema::Groundspeak => return None,
+        };
+        Some(ExtensionSchema {
+            element,
+            namespace,
+            consumer,
+        })
+    }
+
+    /// Whether the crate reads the extensions of this schema, see
+    /// [`KnownSchema::consumer`].
+    pub fn has_consumer(self) -> bool {
+        self.consumer().is_some()
+    }
+}
+
+/// Options for [`detect_with`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct DetectOptions {
+    /// Also looks at the first `<extensions>` element, for the schemas that
+    /// are declared there rather than on the root element. This reads the
+    /// document up to the end of that element, or of the first waypoint,
+    /// route or track when none comes before it.
+    pub scan_extensions: bool,
+    /// How the document is parsed: its [`ParseLimits`](crate::ParseLimits),
+    /// [`XmlParser`](crate::XmlParser) and timeout apply as they do when
+    /// reading it.
+    pub parse: ParseOptions,
+}
+
+impl Default for DetectOptions {
+    fn default() -> DetectOptions {
+        DetectOptions {
+            scan_extensions: true,
+            parse: Default::default(),
+        }
+    }
+}
+
+/// Tells which known extension schemas a document uses, from the namespaces
+/// declared on its root element and in its first `<extensions>` element.
+///
+/// ```
+/// use gpx::extensions::{detect, KnownSchema};
+///
+/// let data = r#"<gpx version="1.1" xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+///     <trk><trkseg><trkpt lat="1" lon="2"><extensions>
+///         <s:line xmlns:s="http://www.topografix.com/GPX/gpx_style/0/2"/>
+///     </extensions></trkpt></trkseg></trk>
+/// </gpx>"#;
+///
+/// let schemas = detect(data.as_bytes()).unwrap();
+/// assert_eq!(schemas, [KnownSchema::GarminTrackPointV1, KnownSchema::GpxStyle]);
+/// ```
+pub fn detect<R: Read>(reader: R) -> GpxResult<Vec<KnownSchema>> {
+    detect_with(reader, &Default::default())
+}
+
+/// Tells which known extension schemas a document uses like [`detect`],
+/// looking as far as `options` allow.
+pub fn detect_with<R: Read>(reader: R, options: &DetectOptions) -> GpxResult<Vec<KnownSchema>> {
+    let mut context =
+        create_context_with_options::<R, EmptyExtensions>(reader, GpxVersion::Unknown, options.parse.clone());
+    let mut schemas = Vec::new();
+    let mut depth = 0;
+    let mut extensions = None;
+    while let Some(event) = context.reader().next() {
+        context.check_deadline()?;
+        match event.map_err(|err| GpxError::in_element("gpx", err))? {
+            XmlEvent::StartElement { name, namespace, .. } => {
+                depth += 1;
+                context.check_depth(depth)?;
+                for (_, uri) in &namespace {
+                    schemas.extend(KnownSchema::from_namespace(uri));
+                }
+                if depth == 1 && !options.scan_extensions {
+                    break;
+                }
+                if extensions.is_none() && name.local_name == "extensions" {
+                    extensions = Some(depth);
+                }
+            }
+            XmlEvent::EndElement { name } => {
+                // The first point or line without extensions makes those of
+                // the ones after it unlikely.
+                let first_child = depth == 2 && matches!(name.local_name.as_str(), "wpt" | "rte" | "trk");
+                if extensions == Some(depth) || first_child {
+                    break;
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+    schemas.sort();
+    schemas.dedup();
+    Ok(schemas)
+}
+
+/// Reads a document, reading the extensions of every point with the
+/// built-in consumer of their schema: Garmin `gpxx:WaypointExtension` as
+/// [`GarminExtensions`](crate::parser::extensions::GarminExtensions) does,
+/// Garmin `gpxtpx:TrackPointExtension` as
+/// [`GarminTrackPointExtensions`](crate::parser::extensions::GarminTrackPointExtensions)
+/// does, and both together for points that have both. This spares knowing
+/// beforehand which of them a file uses; [`detect`] tells it without reading
+/// the document.
+///
+/// ```
+/// use gpx::extensions::{read_with_detected_extensions, DetectedExtensions};
+///
+/// let data = r#"<gpx version="1.1" xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+///     <trk><trkseg><trkpt lat="1" lon="2"><extensions>
+///         <gpxtpx:TrackPointExtension><gpxtpx:hr>132</gpxtpx:hr></gpxtpx:TrackPointExtension>
+///     </extensions></trkpt></trkseg></trk>
+/// </gpx>"#;
+///
+/// let gpx = read_with_detected_extensions(data.as_bytes()).unwrap();
+/// match &gpx.tracks[0].segments[0].points[0].extensions {
+///     DetectedExtensions::GarminTrackPoint(point) => assert_eq!(point.heart_rate, Some(132)),
+///     other => panic!("unexpected extensions {:?}", other),
+/// }
+/// ```
+pub fn read_with_detected_extensions<R: Read>(reader: R) -> GpxResult<Gpx<DetectedExtensions>> {
+    read_with_extensions(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::GpxError;
+    use crate::{ParseLimits, ParseOptions};
+
+    use super::{detect, detect_with, DetectOptions, KnownSchema};
+
+    #[test]
+    fn from_namespace() {
+        assert_eq!(
+            KnownSchema::from_namespace("http://www.groundspeak.com/cache/1/0/1"),
+            Some(KnownSchema::Groundspeak)
+        );
+        assert_eq!(KnownSchema::from_namespace("https://osmand.net/"), Some(KnownSchema::OsmAnd));
+        assert_eq!(KnownSchema::from_namespace("http://www.topografix.com/GPX/1/1"), None);
+    }
+
+    #[test]
+    fn consumers_read_their_namespaces() {
+        let mut sorted = KnownSchema::ALL;
+        sorted.sort();
+        assert_eq!(sorted, KnownSchema::ALL);
+        for schema in KnownSchema::ALL {
+            if let Some(consumer) = schema.consumer() {
+                assert_eq!(KnownSchema::from_namespace(consumer.namespace), Some(schema));
+            }
+        }
+    }
+
+    #[test]
+    fn detect_in_first_extensions() {
+        let data = r#"<gpx version="1.1" xmlns:osmand="https://osmand.net">
+            <wpt lat="1" lon="2"><extensions>
+                <gpxdata:hr xmlns:gpxdata="http://www.cluetrust.com/XML/GPXDATA/1/0">120</gpxdata:hr>
+            </extensions></wpt>
+            <wpt lat="1" lon="2"><extensions>
+                <groundspeak:cache xmlns:groundspeak="http://www.groundspeak.com/cache/1/0"/>
+            </extensions></wpt>
+        </gpx>"#;
+
+        assert_eq!(
+            detect(data.as_bytes()).unwrap(),
+            [KnownSchema::Cluetrust, KnownSchema::OsmAnd]
+        );
+        let options = DetectOptions {
+            scan_extensions: false,
+            ..Default::default()
+        };
+        assert_eq!(detect_with(data.as_bytes(), &options).unwrap(), [KnownSchema::OsmAnd]);
+
+        #[cfg(feature = "quick-xml")]
+        {
+            let options = DetectOptions {
+                parse: ParseOptions::strict().with_xml_parser(crate::XmlParser::QuickXml),
+                ..Default::default()
+            };
+            assert_eq!(
+                detect_with(data.as_bytes(), &options).unwrap(),
+                [KnownSchema::Cluetrust, KnownSchema::OsmAnd]
+            );
+        }
+    }
+
+    #[test]
+    fn detect_stops_at_first_point_without_extensions() {
+        let data = r#"<gpx version="1.1">
+            <wpt lat="1" lon="2"/>
+            <wpt lat="1" lon="2"><extensions>
+                <gpxdata:hr xmlns:gpxdata="http://www.cluetrust.com/XML/GPXDATA/1/0">120</gpxdata:hr>
+            </extensions></wpt>
+            <unclosed>
+        "#;
+
+        assert_eq!(detect(data.as_bytes()).unwrap(), []);
+    }
+
+    #[test]
+    fn detect_with_limits() {
+        let data = r#"<gpx version="1.1"><metadata><extensions><a><b/></a></extensions></metadata></gpx>"#;
+        let limits = ParseLimits {
+            max_element_depth: 3,
+            ..Default::default()
+        };
+        let options = DetectOptions {
+            parse: ParseOptions::strict().with_limits(limits),
+            ..Default::default()
+        };
+        assert!(matches!(
+            detect_with(data.as_bytes(), &options),
+            Err(GpxError::LimitExceeded("element depth"))
+        ));
+        assert_eq!(detect(data.as_bytes()).unwrap(), []);
+    }
+}
@@ -0,0 +1,356 @@
+//! geojson converts GPX documents to GeoJSON, and GeoJSON to GPX documents.
+
+use geo_types::Point;
+use geojson::{Feature, FeatureCollection, GeoJson, Geometry, JsonObject, Position, Value};
+
+use crate::errors::{GpxError, GpxResult};
+use crate::parser::extensions::{EmptyExtensions, WaypointExtensions};
+use crate::{Gpx, GpxVersion, Route, Track, TrackSegment, TrackStyle, Waypoint};
+
+/// What [`Gpx::from_geojson_with`] makes of LineString features.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineStringsAs {
+    /// A route per LineString.
+    Routes,
+    /// A track of a single segment per LineString.
+    Tracks,
+}
+
+/// Options for [`Gpx::from_geojson_with`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeoJsonImportOptions {
+    pub linestrings: LineStringsAs,
+}
+
+impl Default for GeoJsonImportOptions {
+    fn default() -> GeoJsonImportOptions {
+        GeoJsonImportOptions {
+            linestrings: LineStringsAs::Routes,
+        }
+    }
+}
+
+/// properties gives the GeoJSON properties of a feature with the given name
+/// and description.
+fn properties(name: &Option<String>, description: &Option<String>) -> JsonObject {
+    let mut properties = JsonObject::new();
+    if let Some(name) = name {
+        properties.insert("name".into(), name.clone().into());
+    }
+    if let Some(description) = description {
+        properties.insert("desc".into(), description.clone().into());
+    }
+    properties
+}
+
+/// add_style adds the
+/// [simplestyle](https://github.com/mapbox/simplestyle-spec) properties of a
+/// line drawn with `style`.
+fn add_style(properties: &mut JsonObject, style: &TrackStyle) {
+    if let Some(color) = &style.color {
+        properties.insert("stroke".into(), color.clone().into());
+    }
+    if let Some(width) = style.width {
+        properties.insert("stroke-width".into(), width.into());
+    }
+    if let Some(opacity) = style.opacity {
+        properties.insert("stroke-opacity".into(), opacity.into());
+    }
+}
+
+fn feature(value: Value, properties: JsonObject) -> Feature {
+    Feature {
+        bbox: None,
+        geometry: Some(Geometry::new(value)),
+        id: None,
+        properties: Some(properties),
+        foreign_members: None,
+    }
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Converts the document to a GeoJSON feature collection: a
+    /// MultiLineString per track, then a LineString per route, then a Point
+    /// per waypoint.
+    ///
+    /// Features carry `name` and `desc` properties, and tracks also carry
+    /// their [`style`](crate::Track::style) as simplestyle `stroke`,
+    /// `stroke-width` and `stroke-opacity` properties.
+    pub fn to_geojson(&self) -> FeatureCollection {
+        let tracks = self.tracks.iter().map(|track| {
+            let mut properties = properties(&track.name, &track.description);
+            if let Some(style) = track.style() {
+                add_style(&mut properties, style);
+            }
+            feature(Value::from(&track.multilinestring()), properties)
+        });
+        let routes = self.routes.iter().map(|route| {
+            let properties = properties(&route.name, &route.description);
+            feature(Value::from(&route.linestring()), properties)
+        });
+        let waypoints = self.waypoints.iter().map(|waypoint| {
+            let properties = properties(&waypoint.name, &waypoint.description);
+            feature(Value::from(&waypoint.point()), properties)
+        });
+
+        FeatureCollection {
+            bbox: None,
+            features: tracks.chain(routes).chain(waypoints).collect(),
+            foreign_members: None,
+        }
+    }
+}
+
+/// property gives the string property `key` of `properties`, if any.
+fn property(properties: Option<&JsonObject>, key: &str) -> Option<String> {
+    properties?.get(key)?.as_str().map(String::from)
+}
+
+/// waypoint gives the waypoint at `position`, which holds longitude, latitude
+/// and optionally elevation.
+fn waypoint(position: &Position) -> GpxResult<Waypoint<EmptyExtensions>> {
+    let (longitude, latitude) = match position[..] {
+        [longitude, latitude, ..] => (longitude, latitude),
+        [_] => return Err(GpxError::MissingCoordinate("latitude", "position")),
+        [] => return Err(GpxError::MissingCoordinate("longitude", "position")),
+    };
+    let mut waypoint = Waypoint::new(Point::new(longitude, latitude));
+    waypoint.elevation = position.get(2).copied();
+    Ok(waypoint)
+}
+
+fn segment(positions: &[Position]) -> GpxResult<TrackSegment<EmptyExtensions>> {
+    Ok(TrackSegment {
+        points: positions.iter().map(waypoint).collect::<GpxResult<_>>()?,
+    })
+}
+
+/// add_geometry adds the `index`th feature, of geometry `value` and the given
+/// properties, to `gpx`.
+fn add_geometry(
+    gpx: &mut Gpx<EmptyExtensions>,
+    index: usize,
+    value: &Value,
+    properties: Option<&JsonObject>,
+    options: &GeoJsonImportOptions,
+) -> GpxResult<()> {
+    let name = property(properties, "name");
+    let description = property(properties, "desc");
+    let track = |segments: Vec<TrackSegment<EmptyExtensions>>| {
+        let mut track = Track::new();
+        track.name = name.clone();
+        track.description = description.clone();
+        track.segments = segments;
+        track
+    };
+    match value {
+        Value::Point(position) => {
+            let mut waypoint = waypoint(position)?;
+            waypoint.name = name;
+            waypoint.description = description;
+            gpx.waypoints.push(waypoint);
+        }
+        Value::LineString(positions) => match options.linestrings {
+            LineStringsAs::Routes => {
+                let mut route = Route::new();
+                route.name = name;
+                route.description = description;
+                route.points = segment(positions)?.points;
+                gpx.routes.push(route);
+            }
+            LineStringsAs::Tracks => gpx.tracks.push(track(vec![segment(positions)?])),
+        },
+        Value::MultiLineString(lines) => {
+            let segments = lines.iter().map(|positions| segment(positions)).collect::<GpxResult<_>>()?;
+            gpx.tracks.push(track(segments));
+        }
+        other => return Err(GpxError::UnsupportedGeometry(index, other.type_name())),
+    }
+    Ok(())
+}
+
+/// add_feature adds the `index`th feature to `gpx`, unless it has no
+/// geometry.
+fn add_feature(
+    gpx: &mut Gpx<EmptyExtensions>,
+    index: usize,
+    feature: &Feature,
+    options: &GeoJsonImportOptions,
+) -> GpxResult<()> {
+    match &feature.geometry {
+        Some(geometry) => add_geometry(gpx, index, &geometry.value, feature.properties.as_ref(), options),
+        None => Ok(()),
+    }
+}
+
+impl Gpx<EmptyExtensions> {
+    /// Converts GeoJSON to a GPX 1.1 document: a waypoint per Point, a route
+    /// per LineString and a track per MultiLineString, with a segment per
+    /// LineString. This is the reverse of [`Gpx::to_geojson`].
+    ///
+    /// The `name` and `desc` properties of features become names and
+    /// descriptions, and a third coordinate becomes the elevation. Features
+    /// without a geometry are left out, and other geometries are a
+    /// [`GpxError::UnsupportedGeometry`] naming the index of their feature.
+    ///
+    /// ```
+    /// use geojson::GeoJson;
+    /// use gpx::Gpx;
+    ///
+    /// let geojson: GeoJson = r#"{
+    ///     "type": "Feature",
+    ///     "properties": { "name": "Summit" },
+    ///     "geometry": { "type": "Point", "coordinates": [8.6345, 46.5597, 3024.0] }
+    /// }"#.parse().unwrap();
+    ///
+    /// let gpx = Gpx::from_geojson(&geojson).unwrap();
+    /// assert_eq!(gpx.waypoints[0].name.as_deref(), Some("Summit"));
+    /// assert_eq!(gpx.waypoints[0].elevation, Some(3024.0));
+    /// ```
+    pub fn from_geojson(geojson: &GeoJson) -> GpxResult<Gpx<EmptyExtensions>> {
+        Gpx::from_geojson_with(geojson, &Default::default())
+    }
+
+    /// Converts GeoJSON to a GPX 1.1 document like [`Gpx::from_geojson`],
+    /// making routes or tracks of LineStrings according to `options`.
+    pub fn from_geojson_with(geojson: &GeoJson, options: &GeoJsonImportOptions) -> GpxResult<Gpx<EmptyExtensions>> {
+        let mut gpx = Gpx {
+            version: GpxVersion::Gpx11,
+            ..Default::default()
+        };
+        match geojson {
+            GeoJson::Geometry(geometry) => add_geometry(&mut gpx, 0, &geometry.value, None, options)?,
+            GeoJson::Feature(feature) => add_feature(&mut gpx, 0, feature, options)?,
+            GeoJson::FeatureCollection(collection) => {
+                for (index, feature) in collection.features.iter().enumerate() {
+                    add_feature(&mut gpx, index, feature, options)?;
+                }
+            }
+        }
+        Ok(gpx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::BufReader;
+
+    use geojson::{GeoJson, JsonValue, Value};
+
+    use crate::errors::GpxError;
+    use crate::{read, Gpx};
+
+    use super::{GeoJsonImportOptions, LineStringsAs};
+
+    #[test]
+    fn styled_tracks_to_geojson() {
+        let file = File::open("tests/fixtures/styled_tracks.gpx").unwrap();
+        let gpx = read(BufReader::new(file)).unwrap();
+        let collection = gpx.to_geojson();
+        assert_eq!(collection.features.len(), 4);
+
+        let gpx_style = &collection.features[0];
+        assert!(matches!(
+            gpx_style.geometry.as_ref().unwrap().value,
+            Value::MultiLineString(_)
+        ));
+        assert_eq!(gpx_style.property("name"), Some(&JsonValue::from("Evening ride")));
+        assert_eq!(gpx_style.property("stroke"), Some(&JsonValue::from("#1E90FF")));
+        assert_eq!(gpx_style.property("stroke-width"), Some(&JsonValue::from(3.0)));
+        assert_eq!(gpx_style.property("stroke-opacity"), Some(&JsonValue::from(0.75)));
+
+        let garmin = &collection.features[1];
+        assert_eq!(garmin.property("stroke"), Some(&JsonValue::from("#800000")));
+        assert!(!garmin.contains_property("stroke-width"));
+
+        let unstyled = &collection.features[2];
+        assert!(!unstyled.contains_property("stroke"));
+
+        let waypoint = &collection.features[3];
+        assert_eq!(
+            waypoint.geometry.as_ref().unwrap().value,
+            Value::Point(vec![-122.4194, 37.7749])
+        );
+        assert_eq!(waypoint.property("desc"), Some(&JsonValue::from("Start")));
+    }
+
+    #[test]
+    fn geojson_round_trip() {
+        for fixture in ["styled_tracks", "ecology-trail-and-lovers-lane-loop", "marine_waypoint"] {
+            let file = File::open(format!("tests/fixtures/{}.gpx", fixture)).unwrap();
+            let gpx = read(BufReader::new(file)).unwrap();
+            let collection = gpx.to_geojson();
+
+            let imported = Gpx::from_geojson(&GeoJson::FeatureCollection(collection.clone())).unwrap();
+            assert_eq!(imported.tracks.len(), gpx.tracks.len());
+            assert_eq!(imported.routes.len(), gpx.routes.len());
+            assert_eq!(imported.waypoints.len(), gpx.waypoints.len());
+            for (imported, track) in imported.tracks.iter().zip(&gpx.tracks) {
+                assert_eq!(imported.multilinestring(), track.multilinestring());
+                assert_eq!(imported.name, track.name);
+            }
+            for (imported, route) in imported.routes.iter().zip(&gpx.routes) {
+                assert_eq!(imported.linestring(), route.linestring());
+            }
+            for (imported, waypoint) in imported.waypoints.iter().zip(&gpx.waypoints) {
+                assert_eq!(imported.point(), waypoint.point());
+                assert_eq!(imported.description, waypoint.description);
+            }
+
+            // Converting back gives the same geometries.
+            let again = imported.to_geojson();
+            for (feature, original) in again.features.iter().zip(&collection.features) {
+                assert_eq!(feature.geometry, original.geometry);
+            }
+        }
+    }
+
+    #[test]
+    fn geojson_import_options_and_errors() {
+        let geojson: GeoJson = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "properties": { "name": "Ridge", "desc": "Planned" },
+                    "geometry": { "type": "LineString", "coordinates": [[8.0, 46.0, 1500.5], [8.1, 46.1]] }
+                },
+                { "type": "Feature", "properties": null, "geometry": null },
+                {
+                    "type": "Feature",
+                    "properties": {},
+                    "geometry": { "type": "Polygon", "coordinates": [[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [0.0, 0.0]]] }
+                }
+            ]
+        }"#
+        .parse()
+        .unwrap();
+
+        let result = Gpx::from_geojson(&geojson);
+        assert!(matches!(result, Err(GpxError::UnsupportedGeometry(2, "Polygon"))));
+
+        let mut collection = match geojson {
+            GeoJson::FeatureCollection(collection) => collection,
+            _ => unreachable!(),
+        };
+        collection.features.pop();
+        let geojson = GeoJson::FeatureCollection(collection);
+
+        let gpx = Gpx::from_geojson(&geojson).unwrap();
+        assert!(gpx.tracks.is_empty());
+        let route = &gpx.routes[0];
+        assert_eq!(route.name.as_deref(), Some("Ridge"));
+        assert_eq!(route.description.as_deref(), Some("Planned"));
+        assert_eq!(route.points[0].elevation, Some(1500.5));
+        assert_eq!(route.points[1].elevation, None);
+
+        let options = GeoJsonImportOptions {
+            linestrings: LineStringsAs::Tracks,
+        };
+        let gpx = Gpx::from_geojson_with(&geojson, &options).unwrap();
+        assert!(gpx.routes.is_empty());
+        assert_eq!(gpx.tracks[0].name.as_deref(), Some("Ridge"));
+        assert_eq!(gpx.tracks[0].segments[0].points.len(), 2);
+    }
+}
@@ -0,0 +1,336 @@
+//! invariants finds and fixes the parts of a document that are allowed by
+//! the parser but trouble the code using it, such as empty track segments.
+
+use std::fmt;
+
+#[cfg(feature = "use-serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{GpxError, GpxResult};
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Gpx, Link, Waypoint};
+
+/// What [`Gpx::ensure_invariants`] does with what violates an [`Invariant`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
+pub enum InvariantPolicy {
+    /// Removes empty track segments, routes without points and links without
+    /// an href, and repairs the rest.
+    DropEmpty,
+    /// Keeps everything, only repairing what can be repaired.
+    KeepAll,
+    /// Fails on the first violation, leaving the document as it is.
+    Error,
+}
+
+/// A property of documents that [`Gpx::ensure_invariants`] ensures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Invariant {
+    /// Track segments have points.
+    NonEmptySegment,
+    /// Routes have points.
+    NonEmptyRoute,
+    /// Links have an href.
+    LinkHref,
+    /// Elevations are finite numbers, not NaN or infinite.
+    FiniteElevation,
+}
+
+impl fmt::Display for Invariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Invariant::NonEmptySegment => "empty track segment",
+            Invariant::NonEmptyRoute => "route without points",
+            Invariant::LinkHref => "link without href",
+            Invariant::FiniteElevation => "elevation that is not a finite number",
+        })
+    }
+}
+
+/// What [`Gpx::ensure_invariants`] did about a violation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FixupAction {
+    /// Removed what violated the invariant.
+    Removed,
+    /// Repaired it: a non-finite elevation is cleared.
+    Repaired,
+    /// Kept it as it is, following [`InvariantPolicy::KeepAll`].
+    Kept,
+}
+
+/// A violation of an [`Invariant`] found by [`Gpx::ensure_invariants`], and
+/// what was done about it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fixup {
+    /// Where the violation was, as a path of element names with indices such
+    /// as `trk[0]/trkseg[2]`, counting in the document as it was before.
+    pub path: String,
+    pub invariant: Invariant,
+    pub action: FixupAction,
+}
+
+/// link_violations finds the violations among `links`, under `path`.
+fn link_violations(links: &[Link], path: &str, violations: &mut Vec<(String, Invariant)>) {
+    for (index, link) in links.iter().enumerate() {
+        if link.href.is_empty() {
+            violations.push((format!("{}link[{}]", path, index), Invariant::LinkHref));
+        }
+    }
+}
+
+/// point_violations finds the violations of the point at `path`.
+fn point_violations<E: WaypointExtensions + Default>(
+    point: &Waypoint<E>,
+    path: String,
+    violations: &mut Vec<(String, Invariant)>,
+) {
+    link_violations(&point.links, &format!("{}/", path), violations);
+    if point.elevation.map_or(false, |elevation| !elevation.is_finite()) {
+        violations.push((path, Invariant::FiniteElevation));
+    }
+}
+
+/// fix_links fixes the links of an element according to `policy`.
+fn fix_links(links: &mut Vec<Link>, policy: InvariantPolicy) {
+    if policy == InvariantPolicy::DropEmpty {
+        links.retain(|link| !link.href.is_empty());
+    }
+}
+
+/// fix_point fixes a point according to `policy`.
+fn fix_point<E: WaypointExtensions + Default>(point: &mut Waypoint<E>, policy: InvariantPolicy) {
+    fix_links(&mut point.links, policy);
+    if point.elevation.map_or(false, |elevation| !elevation.is_finite()) {
+        point.elevation = None;
+    }
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Finds the violations of every [`Invariant`], in document order, as
+    /// their path and the invariant violated.
+    fn violations(&self) -> Vec<(String, Invariant)> {
+        let mut violations = Vec::new();
+        if let Some(metadata) = &self.metadata {
+            link_violations(&metadata.links, "metadata/", &mut violations);
+        }
+        for (index, waypoint) in self.waypoints.iter().enumerate() {
+            point_violations(waypoint, format!("wpt[{}]", index), &mut violations);
+        }
+        for (index, route) in self.routes.iter().enumerate() {
+            let path = format!("rte[{}]", index);
+            if route.points.is_empty() {
+                violations.push((path.clone(), Invariant::NonEmptyRoute));
+            }
+            link_violations(&route.links, &format!("{}/", path), &mut violations);
+            for (point, waypoint) in route.points.iter().enumerate() {
+                point_violations(waypoint, format!("{}/rtept[{}]", path, point), &mut violations);
+            }
+        }
+        for (index, track) in self.tracks.iter().enumerate() {
+            let path = format!("trk[{}]", index);
+            link_violations(&track.links, &format!("{}/", path), &mut violations);
+            for (segment_index, segment) in track.segments.iter().enumerate() {
+                let path = format!("{}/trkseg[{}]", path, segment_index);
+                if segment.points.is_empty() {
+                    violations.push((path.clone(), Invariant::NonEmptySegment));
+                }
+                for (point, waypoint) in segment.points.iter().enumerate() {
+                    point_violations(waypoint, format!("{}/trkpt[{}]", path, point), &mut violations);
+                }
+            }
+        }
+        violations
+    }
+
+    /// Ensures every [`Invariant`] holds, removing or repairing what violates
+    /// one according to `policy`, and gives what was found and done, in
+    /// document order.
+    ///
+    /// Non-finite elevations are cleared, unless the policy is
+    /// [`InvariantPolicy::Error`]: then the first violation is a
+    /// [`GpxError::InvariantViolation`], and nothing is changed.
+    ///
+    /// ```
+    /// use gpx::{FixupAction, Gpx, Invariant, InvariantPolicy, Route};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut gpx: Gpx<EmptyExtensions> = Gpx::default();
+    /// gpx.routes.push(Route::new());
+    ///
+    /// let fixups = gpx.ensure_invariants(InvariantPolicy::DropEmpty).unwrap();
+    /// assert_eq!(fixups[0].path, "rte[0]");
+    /// assert_eq!(fixups[0].invariant, Invariant::NonEmptyRoute);
+    /// assert_eq!(fixups[0].action, FixupAction::Removed);
+    /// assert!(gpx.routes.is_empty());
+    /// ```
+    pub fn ensure_invariants(&mut self, policy: InvariantPolicy) -> GpxResult<Vec<Fixup>> {
+        let violations = self.violations();
+        if policy == InvariantPolicy::Error {
+            if let Some((path, invariant)) = violations.into_iter().next() {
+                return Err(GpxError::InvariantViolation(path, invariant));
+            }
+            return Ok(Vec::new());
+        }
+
+        if let Some(metadata) = &mut self.metadata {
+            fix_links(&mut metadata.links, policy);
+        }
+        for waypoint in &mut self.waypoints {
+            fix_point(waypoint, policy);
+        }
+        for route in &mut self.routes {
+            fix_links(&mut route.links, policy);
+            for point in &mut route.points {
+                fix_point(point, policy);
+            }
+        }
+        for track in &mut self.tracks {
+            fix_links(&mut track.links, policy);
+            for point in track.segments.iter_mut().flat_map(|segment| &mut segment.points) {
+                fix_point(point, policy);
+            }
+        }
+        if policy == InvariantPolicy::DropEmpty {
+            self.routes.retain(|route| !route.points.is_empty());
+            for track in &mut self.tracks {
+                track.segments.retain(|segment| !segment.points.is_empty());
+            }
+        }
+
+        Ok(violations
+            .into_iter()
+            .map(|(path, invariant)| {
+                let action = match (invariant, policy) {
+                    (Invariant::FiniteElevation, _) => FixupAction::Repaired,
+                    (_, InvariantPolicy::DropEmpty) => FixupAction::Removed,
+                    _ => FixupAction::Kept,
+                };
+                Fixup {
+                    path,
+                    invariant,
+                    action,
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+
+    use crate::errors::GpxError;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{Gpx, Link, Metadata, Route, Track, TrackSegment, Waypoint};
+
+    use super::{Fixup, FixupAction, Invariant, InvariantPolicy};
+
+    fn link(href: &str) -> Link {
+        Link {
+            href: String::from(href),
+            ..Default::default()
+        }
+    }
+
+    fn point(elevation: Option<f64>) -> Waypoint<EmptyExtensions> {
+        let mut point = Waypoint::new(Point::new(1.0, 2.0));
+        point.elevation = elevation;
+        point
+    }
+
+    /// document violates every invariant.
+    fn document() -> Gpx<EmptyExtensions> {
+        let mut gpx = Gpx {
+            metadata: Some(Metadata {
+                links: vec![link(""), link("https://example.com")],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        gpx.waypoints.push(point(Some(f64::NAN)));
+        gpx.routes.push(Route::new());
+        let mut route = Route::new();
+        route.points.push(point(Some(12.0)));
+        gpx.routes.push(route);
+        let mut track = Track::new();
+        track.segments.push(TrackSegment::new());
+        track.segments.push(TrackSegment {
+            points: vec![point(Some(f64::INFINITY)), point(None)],
+        });
+        track.segments[1].points[1].links.push(link(""));
+        gpx.tracks.push(track);
+        gpx
+    }
+
+    fn fixup(path: &str, invariant: Invariant, action: FixupAction) -> Fixup {
+        Fixup {
+            path: String::from(path),
+            invariant,
+            action,
+        }
+    }
+
+    #[test]
+    fn ensure_invariants_drop_empty() {
+        let mut gpx = document();
+        let fixups = gpx.ensure_invariants(InvariantPolicy::DropEmpty).unwrap();
+        assert_eq!(
+            fixups,
+            [
+                fixup("metadata/link[0]", Invariant::LinkHref, FixupAction::Removed),
+                fixup("wpt[0]", Invariant::FiniteElevation, FixupAction::Repaired),
+                fixup("rte[0]", Invariant::NonEmptyRoute, FixupAction::Removed),
+                fixup("trk[0]/trkseg[0]", Invariant::NonEmptySegment, FixupAction::Removed),
+                fixup("trk[0]/trkseg[1]/trkpt[0]", Invariant::FiniteElevation, FixupAction::Repaired),
+                fixup("trk[0]/trkseg[1]/trkpt[1]/link[0]", Invariant::LinkHref, FixupAction::Removed),
+            ]
+        );
+
+        assert_eq!(gpx.metadata.as_ref().unwrap().links, [link("https://example.com")]);
+        assert_eq!(gpx.waypoints[0].elevation, None);
+        assert_eq!(gpx.routes.len(), 1);
+        assert_eq!(gpx.routes[0].points[0].elevation, Some(12.0));
+        let segments = &gpx.tracks[0].segments;
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].points[0].elevation, None);
+        assert!(segments[0].points[1].links.is_empty());
+
+        assert!(gpx.ensure_invariants(InvariantPolicy::DropEmpty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn ensure_invariants_keep_all() {
+        let mut gpx = document();
+        let fixups = gpx.ensure_invariants(InvariantPolicy::KeepAll).unwrap();
+        assert_eq!(fixups.len(), 6);
+        assert_eq!(fixups[0].action, FixupAction::Kept);
+        assert_eq!(fixups[1].action, FixupAction::Repaired);
+
+        assert_eq!(gpx.metadata.as_ref().unwrap().links.len(), 2);
+        assert_eq!(gpx.waypoints[0].elevation, None);
+        assert_eq!(gpx.routes.len(), 2);
+        assert_eq!(gpx.tracks[0].segments.len(), 2);
+        assert_eq!(gpx.tracks[0].segments[1].points[0].elevation, None);
+
+        // Only what was kept is left to report.
+        let fixups = gpx.ensure_invariants(InvariantPolicy::KeepAll).unwrap();
+        assert_eq!(fixups.len(), 4);
+        assert!(fixups.iter().all(|fixup| fixup.action == FixupAction::Kept));
+    }
+
+    #[test]
+    fn ensure_invariants_error() {
+        let mut gpx = document();
+        let result = gpx.ensure_invariants(InvariantPolicy::Error);
+        match result {
+            Err(GpxError::InvariantViolation(path, Invariant::LinkHref)) => assert_eq!(path, "metadata/link[0]"),
+            other => panic!("expected a violation, got {:?}", other),
+        }
+        assert!(gpx.waypoints[0].elevation.unwrap().is_nan());
+        assert_eq!(gpx.routes.len(), 2);
+
+        let mut gpx: Gpx<EmptyExtensions> = Gpx::default();
+        gpx.waypoints.push(point(Some(3.0)));
+        assert!(gpx.ensure_invariants(InvariantPolicy::Error).unwrap().is_empty());
+    }
+}
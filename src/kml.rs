@@ -0,0 +1,288 @@
+//! kml converts GPX documents to KML, for Google Earth.
+
+use std::io::Write;
+
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+
+use crate::errors::GpxResult;
+use crate::parser::extensions::WaypointExtensions;
+use crate::writer::{write_string, write_string_if_exists, write_xml_event};
+use crate::{Gpx, TrackSegment, Waypoint};
+
+const KML_NAMESPACE: &str = "http://www.opengis.net/kml/2.2";
+const GX_NAMESPACE: &str = "http://www.google.com/kml/ext/2.2";
+
+/// Options for [`Gpx::to_kml_string_with`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KmlOptions {
+    /// Writes the timed points of tracks as `gx:Track`, and the times of
+    /// waypoints as `TimeStamp`, so the time slider of Google Earth animates
+    /// them. Points without a time are left out of a `gx:Track`.
+    pub timestamps: bool,
+}
+
+/// altitude_mode gives the KML altitude mode of `points`: on the ground,
+/// unless they all have an elevation.
+fn altitude_mode<'a, E: WaypointExtensions + Default + 'a>(
+    mut points: impl Iterator<Item = &'a Waypoint<E>>,
+) -> &'static str {
+    if points.all(|point| point.elevation.is_some()) {
+        "absolute"
+    } else {
+        "clampToGround"
+    }
+}
+
+/// coordinates gives the KML coordinates of `points`: longitude, latitude and
+/// elevation, when there is one, separated by commas.
+fn coordinates<E: WaypointExtensions + Default>(points: &[Waypoint<E>]) -> String {
+    points
+        .iter()
+        .map(|point| match point.elevation {
+            Some(elevation) => format!("{},{},{}", point.point().x(), point.point().y(), elevation),
+            None => format!("{},{}", point.point().x(), point.point().y()),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn write_line_string<W: Write, E: WaypointExtensions + Default>(
+    points: &[Waypoint<E>],
+    writer: &mut EventWriter<W>,
+) -> GpxResult<()> {
+    write_xml_event(XmlEvent::start_element("LineString"), writer)?;
+    write_string("altitudeMode", altitude_mode(points.iter()), writer)?;
+    write_string("coordinates", &coordinates(points), writer)?;
+    write_xml_event(XmlEvent::end_element(), writer)
+}
+
+/// write_gx_track writes the timed points of `segment` as a `gx:Track`, the
+/// times first and then the coordinates, as KML wants them.
+fn write_gx_track<W: Write, E: WaypointExtensions + Default>(
+    segment: &TrackSegment<E>,
+    writer: &mut EventWriter<W>,
+) -> GpxResult<()> {
+    let timed: Vec<_> = segment.points.iter().filter(|point| point.time.is_some()).collect();
+    write_xml_event(XmlEvent::start_element("gx:Track"), writer)?;
+    write_string("altitudeMode", altitude_mode(timed.iter().copied()), writer)?;
+    for point in &timed {
+        write_string("when", &point.time.unwrap().format()?, writer)?;
+    }
+    for point in &timed {
+        let coord = point.coord_3d(0.0);
+        write_string("gx:coord", &format!("{} {} {}", coord[0], coord[1], coord[2]), writer)?;
+    }
+    write_xml_event(XmlEvent::end_element(), writer)
+}
+
+fn write_placemark<W: Write>(
+    name: &Option<String>,
+    description: &Option<String>,
+    writer: &mut EventWriter<W>,
+) -> GpxResult<()> {
+    write_xml_event(XmlEvent::start_element("Placemark"), writer)?;
+    write_string_if_exists("name", name, writer)?;
+    write_string_if_exists("description", description, writer)
+}
+
+fn write_kml<W: Write, E: WaypointExtensions + Default>(
+    gpx: &Gpx<E>,
+    options: &KmlOptions,
+    writer: &mut EventWriter<W>,
+) -> GpxResult<()> {
+    write_xml_event(
+        XmlEvent::start_element("kml")
+            .default_ns(KML_NAMESPACE)
+            .ns("gx", GX_NAMESPACE),
+        writer,
+    )?;
+    write_xml_event(XmlEvent::start_element("Document"), writer)?;
+    if let Some(metadata) = &gpx.metadata {
+        write_string_if_exists("name", &metadata.name, writer)?;
+        write_string_if_exists("description", &metadata.description, writer)?;
+    }
+
+    for track in &gpx.tracks {
+        write_placemark(&track.name, &track.description, writer)?;
+        let timed: Vec<_> = track
+            .segments
+            .iter()
+            .filter(|segment| segment.points.iter().any(|point| point.time.is_some()))
+            .collect();
+        if options.timestamps && !timed.is_empty() {
+            write_xml_event(XmlEvent::start_element("gx:MultiTrack"), writer)?;
+            for segment in timed {
+                write_gx_track(segment, writer)?;
+            }
+        } else {
+            write_xml_event(XmlEvent::start_element("MultiGeometry"), writer)?;
+            for segment in &track.segments {
+                write_line_string(&segment.points, writer)?;
+            }
+        }
+        write_xml_event(XmlEvent::end_element(), writer)?;
+        write_xml_event(XmlEvent::end_element(), writer)?;
+    }
+
+    for route in &gpx.routes {
+        write_placemark(&route.name, &route.description, writer)?;
+        write_line_string(&route.points, writer)?;
+        write_xml_event(XmlEvent::end_element(), writer)?;
+    }
+
+    for waypoint in &gpx.waypoints {
+        write_placemark(&waypoint.name, &waypoint.description, writer)?;
+        if let (true, Some(time)) = (options.timestamps, waypoint.time) {
+            write_xml_event(XmlEvent::start_element("TimeStamp"), writer)?;
+            write_string("when", &time.format()?, writer)?;
+            write_xml_event(XmlEvent::end_element(), writer)?;
+        }
+        write_xml_event(XmlEvent::start_element("Point"), writer)?;
+        write_string("altitudeMode", altitude_mode(std::iter::once(waypoint)), writer)?;
+        write_string("coordinates", &coordinates(std::slice::from_ref(waypoint)), writer)?;
+        write_xml_event(XmlEvent::end_element(), writer)?;
+        write_xml_event(XmlEvent::end_element(), writer)?;
+    }
+
+    write_xml_event(XmlEvent::end_element(), writer)?;
+    write_xml_event(XmlEvent::end_element(), writer)
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Converts the document to KML, for Google Earth: a Document named as
+    /// the metadata, holding a Placemark per track, then per route, then per
+    /// waypoint, with their names and descriptions.
+    ///
+    /// Tracks are a MultiGeometry of a LineString per segment, routes a
+    /// LineString and waypoints a Point. Coordinates are written longitude
+    /// first, with the elevation when there is one; geometries whose points
+    /// all have an elevation are placed at it, the others on the ground.
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{Gpx, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut gpx: Gpx<EmptyExtensions> = Gpx::default();
+    /// let mut summit = Waypoint::new(Point::new(8.6345, 46.5597));
+    /// summit.name = Some(String::from("Summit"));
+    /// gpx.waypoints.push(summit);
+    ///
+    /// let kml = gpx.to_kml_string().unwrap();
+    /// assert!(kml.contains("<coordinates>8.6345,46.5597</coordinates>"));
+    /// ```
+    pub fn to_kml_string(&self) -> GpxResult<String> {
+        self.to_kml_string_with(&Default::default())
+    }
+
+    /// Converts the document to KML like [`Gpx::to_kml_string`], writing
+    /// times according to `options`.
+    pub fn to_kml_string_with(&self, options: &KmlOptions) -> GpxResult<String> {
+        let mut output = Vec::new();
+        let mut writer = EmitterConfig::new()
+            .perform_indent(true)
+            .create_writer(&mut output);
+        write_kml(self, options, &mut writer)?;
+        Ok(String::from_utf8(output).expect("the XML writer writes UTF-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::BufReader;
+
+    use xml::reader::{EventReader, XmlEvent};
+
+    use crate::read;
+
+    use super::{KmlOptions, GX_NAMESPACE, KML_NAMESPACE};
+
+    /// elements gives the local names of the elements of `kml` with their
+    /// text, in document order.
+    fn elements(kml: &str) -> Vec<(String, String)> {
+        let mut elements: Vec<(String, String)> = Vec::new();
+        for event in EventReader::new(kml.as_bytes()) {
+            match event.unwrap() {
+                XmlEvent::StartElement { name, .. } => {
+                    let namespace = name.namespace.as_deref().unwrap();
+                    assert!(namespace == KML_NAMESPACE || namespace == GX_NAMESPACE);
+                    elements.push((name.local_name, String::new()));
+                }
+                XmlEvent::Characters(text) => elements.last_mut().unwrap().1.push_str(&text),
+                _ => {}
+            }
+        }
+        elements
+    }
+
+    fn texts<'a>(elements: &'a [(String, String)], name: &str) -> Vec<&'a str> {
+        elements
+            .iter()
+            .filter(|(element, _)| element == name)
+            .map(|(_, text)| text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn to_kml_structure_and_coordinates() {
+        let file = File::open("tests/fixtures/wikipedia_example.gpx").unwrap();
+        let gpx = read(BufReader::new(file)).unwrap();
+        let kml = gpx.to_kml_string().unwrap();
+        let elements = elements(&kml);
+
+        assert_eq!(elements[0].0, "kml");
+        assert_eq!(elements[1].0, "Document");
+        assert_eq!(texts(&elements, "Placemark").len(), 1);
+        assert_eq!(texts(&elements, "name"), ["Example GPX Document"]);
+        assert_eq!(texts(&elements, "altitudeMode"), ["absolute"]);
+        assert!(texts(&elements, "when").is_empty());
+
+        let coordinates = texts(&elements, "coordinates");
+        let parsed: Vec<Vec<f64>> = coordinates[0]
+            .split(' ')
+            .map(|coord| coord.split(',').map(|value| value.parse().unwrap()).collect())
+            .collect();
+        let expected: Vec<Vec<f64>> = gpx.tracks[0].segments[0]
+            .points
+            .iter()
+            .map(|point| point.coord_3d(0.0).to_vec())
+            .collect();
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn to_kml_with_timestamps() {
+        let file = File::open("tests/fixtures/wikipedia_example.gpx").unwrap();
+        let gpx = read(BufReader::new(file)).unwrap();
+        let options = KmlOptions { timestamps: true };
+        let elements = elements(&gpx.to_kml_string_with(&options).unwrap());
+
+        assert_eq!(texts(&elements, "MultiTrack").len(), 1);
+        assert_eq!(texts(&elements, "Track").len(), 1);
+        let points = &gpx.tracks[0].segments[0].points;
+        let whens = texts(&elements, "when");
+        assert_eq!(whens.len(), points.len());
+        assert_eq!(whens[0], points[0].time.unwrap().format().unwrap());
+        let coord = points[2].coord_3d(0.0);
+        assert_eq!(
+            texts(&elements, "coord")[2],
+            format!("{} {} {}", coord[0], coord[1], coord[2])
+        );
+    }
+
+    #[test]
+    fn to_kml_routes_and_waypoints() {
+        let file = File::open("tests/fixtures/viking_with_route_extensions.gpx").unwrap();
+        let gpx = read(BufReader::new(file)).unwrap();
+        let elements = elements(&gpx.to_kml_string().unwrap());
+
+        let placemarks = gpx.tracks.len() + gpx.routes.len() + gpx.waypoints.len();
+        assert_eq!(texts(&elements, "Placemark").len(), placemarks);
+        assert_eq!(texts(&elements, "Point").len(), gpx.waypoints.len());
+        assert_eq!(
+            texts(&elements, "LineString").len(),
+            gpx.routes.len() + gpx.tracks.iter().map(|track| track.segments.len()).sum::<usize>()
+        );
+    }
+}
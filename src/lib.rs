@@ -9,21 +9,22 @@
 //!
 //! use gpx::read;
 //! use gpx::{Gpx, Track, TrackSegment};
+//! use gpx::parser::extensions::EmptyExtensions;
 //!
 //! // This XML file actually exists — try it for yourself!
 //! let file = File::open("tests/fixtures/wikipedia_example.gpx").unwrap();
 //! let reader = BufReader::new(file);
 //!
 //! // read takes any io::Read and gives a Result<Gpx, Error>.
-//! let gpx: Gpx = read(reader).unwrap();
+//! let gpx: Gpx<EmptyExtensions> = read(reader).unwrap();
 //!
 //! // Each GPX file has multiple "tracks", this takes the first one.
-//! let track: &Track = &gpx.tracks[0];
+//! let track: &Track<EmptyExtensions> = &gpx.tracks[0];
 //! assert_eq!(track.name, Some(String::from("Example GPX Document")));
 //!
 //! // Each track will have different segments full of waypoints, where a
 //! // waypoint contains info like latitude, longitude, and elevation.
-//! let segment: &TrackSegment = &track.segments[0];
+//! let segment: &TrackSegment<EmptyExtensions> = &track.segments[0];
 //!
 //! // This is an example of retrieving the elevation (in meters) at certain points.
 //! assert_eq!(segment.points[0].elevation, Some(4.46));
@@ -32,13 +33,93 @@
 //! ```
 
 // Export our type structs in the root, along with the read and write functions.
-pub use crate::reader::{read, read_with_extensions};
+pub use crate::reader::{
+    read, read_file, read_from_slice, read_with_extensions, read_with_options, read_with_root_handler, read_with_root_handler_with_options, read_with_warnings, read_with_waypoint_callback,
+    ExtensionsMode, ParseLimits, ParseOptions, ReadOutcome, XmlParser,
+};
+pub use crate::activity::Activity;
+pub use crate::channel::{Channel, ChannelCtx, EditLog, TrackPointRef};
+pub use crate::capabilities::{capabilities, Capabilities, ExtensionSchema};
+pub use crate::compact::CompactTrackSegment;
+pub use crate::compare::IgnoreFields;
+pub use crate::csv::CsvOptions;
+pub use crate::invariants::{Fixup, FixupAction, Invariant, InvariantPolicy};
+pub use crate::kml::KmlOptions;
+pub use crate::links::{LinkDedupCounts, LinkScope};
+pub use crate::derive::DeriveOptions;
+pub use crate::distance::{distance_between, distance_between_3d};
+#[cfg(feature = "encoding")]
+pub use crate::encoding::TranscodingReader;
+#[cfg(feature = "geojson")]
+pub use crate::geojson::{GeoJsonImportOptions, LineStringsAs};
+pub use crate::names::{DisambiguationOptions, DisambiguationStyle};
+#[cfg(feature = "rayon")]
+pub use crate::parallel::{read_dir_parallel, read_dir_parallel_map, read_parallel, ReadDirOptions};
+pub use crate::privacy::{PiiPolicy, PiiReport};
+pub use crate::reverse::ReverseTimes;
+pub use crate::sanity::{SanityFinding, SanityOptions};
+pub use crate::schema::{validate, SchemaViolation, ViolationKind};
+pub use crate::stats::{StatsOptions, TrackStats};
+pub use crate::stream::{Checkpoint, TrackPointIter};
+pub use crate::timing::TimingStrategy;
+pub use crate::tracks::{tracks, GpxItem, GpxTrackIter};
 pub use crate::types::*;
-pub use crate::writer::{write, write_with_event_writer};
+#[cfg(feature = "wasm")]
+pub use crate::wasm::{parse_gpx, LineManifest, Manifest, PlaceManifest};
+pub use crate::wkt::WktOptions;
+pub use crate::writer::{write, write_with_config, write_with_event_writer, TimePrecision, WriterConfig};
 
+mod activity;
+mod canonical;
+mod capabilities;
+mod channel;
+mod clean;
+mod compact;
+mod compare;
+mod crop;
+mod csv;
+mod declaration;
+mod derive;
+mod distance;
+mod elevation;
+#[cfg(feature = "encoding")]
+mod encoding;
+pub mod extensions;
+#[cfg(feature = "geojson")]
+mod geojson;
+mod invariants;
+mod kml;
+mod links;
+mod names;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "nmea")]
+pub mod nmea;
 pub mod parser;
+pub mod pipeline;
+mod polyline;
+mod privacy;
+mod query;
 mod reader;
+mod repair;
+mod resample;
+mod reverse;
+mod sanity;
+mod schema;
+mod simplify;
+mod stats;
+mod style;
+mod stream;
+#[cfg(feature = "tcx")]
+pub mod tcx;
+#[cfg(test)]
+mod testing;
+mod timing;
+mod tracks;
 mod types;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod wkt;
 mod writer;
 
 // Errors should be namespaced away.
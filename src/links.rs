@@ -0,0 +1,278 @@
+//! links removes the links a document repeats, within the links of one
+//! element or across the document.
+
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Gpx, Link};
+
+/// Where [`Gpx::dedup_links`] looks for repeated links.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkScope {
+    /// Within the links of each element on its own: the metadata, every
+    /// waypoint, track, route and point.
+    Entity,
+    /// Within each element as for [`LinkScope::Entity`], and across the
+    /// document: waypoints, tracks, routes and points lose the links the
+    /// metadata has, keeping the copy in the metadata.
+    Document,
+}
+
+/// How many links [`Gpx::dedup_links`] removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LinkDedupCounts {
+    /// Links the same as another of the same element.
+    pub entity: usize,
+
+    /// Links the same as one of the metadata, removed with
+    /// [`LinkScope::Document`] only.
+    pub document: usize,
+}
+
+/// loose_text gives the text of `link` as it is compared: trimmed, with
+/// runs of whitespace as one space and lowercased. Text only repeating the
+/// href counts as none, as that is what readers show without text.
+fn loose_text(link: &Link) -> String {
+    let text = match &link.text {
+        Some(text) => text.split_whitespace().collect::<Vec<_>>().join(" "),
+        None => return String::new(),
+    };
+    if text == link.href {
+        return String::new();
+    }
+    text.to_lowercase()
+}
+
+/// same_link tells whether `a` and `b` are the same link, with the same href
+/// and type, and text that is loosely the same.
+fn same_link(a: &Link, b: &Link) -> bool {
+    a.href == b.href && a.type_ == b.type_ && loose_text(a) == loose_text(b)
+}
+
+/// keep_text gives `kept` the text of `removed`, the same link, when it has
+/// none of its own.
+fn keep_text(kept: &mut Link, removed: Link) {
+    if kept.text.is_none() {
+        kept.text = removed.text;
+    }
+}
+
+/// dedup removes the links the same as an earlier one, giving how many.
+fn dedup(links: &mut Vec<Link>) -> usize {
+    if links.len() < 2 {
+        return 0;
+    }
+    let before = links.len();
+    let mut kept: Vec<Link> = Vec::with_capacity(before);
+    for link in links.drain(..) {
+        match kept.iter_mut().find(|other| same_link(other, &link)) {
+            Some(other) => keep_text(other, link),
+            None => kept.push(link),
+        }
+    }
+    *links = kept;
+    before - links.len()
+}
+
+/// remove_known removes the links the same as one of `known`, giving how
+/// many.
+fn remove_known(links: &mut Vec<Link>, known: &mut [Link]) -> usize {
+    if links.is_empty() || known.is_empty() {
+        return 0;
+    }
+    let before = links.len();
+    let mut kept = Vec::with_capacity(before);
+    for link in links.drain(..) {
+        match known.iter_mut().find(|other| same_link(other, &link)) {
+            Some(other) => keep_text(other, link),
+            None => kept.push(link),
+        }
+    }
+    *links = kept;
+    before - links.len()
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Removes repeated links within `scope`, giving how many were removed.
+    ///
+    /// Links are the same when their hrefs are, compared case-sensitively,
+    /// their types are, and their texts are but for case and whitespace. A
+    /// link without text is the same as one whose text is its href. The
+    /// first of the same links is kept, taking the text of a removed one when
+    /// it has none.
+    ///
+    /// ```
+    /// use gpx::{Gpx, Link, LinkDedupCounts, LinkScope, Metadata, Track};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let garmin = Link {
+    ///     href: "connect.garmin.com".into(),
+    ///     text: Some("Garmin Connect".into()),
+    ///     ..Default::default()
+    /// };
+    /// let mut gpx: Gpx<EmptyExtensions> = Gpx::default();
+    /// gpx.metadata = Some(Metadata {
+    ///     links: vec![garmin.clone()],
+    ///     ..Default::default()
+    /// });
+    /// let mut track: Track<EmptyExtensions> = Track::new();
+    /// track.links = vec![garmin.clone(), garmin.clone()];
+    /// gpx.tracks.push(track);
+    ///
+    /// assert_eq!(
+    ///     gpx.dedup_links(LinkScope::Document),
+    ///     LinkDedupCounts { entity: 1, document: 1 }
+    /// );
+    /// assert!(gpx.tracks[0].links.is_empty());
+    /// assert_eq!(gpx.metadata.unwrap().links, [garmin]);
+    /// ```
+    pub fn dedup_links(&mut self, scope: LinkScope) -> LinkDedupCounts {
+        let mut counts = LinkDedupCounts::default();
+        let mut known = match self.metadata.as_mut() {
+            Some(metadata) => {
+                counts.entity += dedup(&mut metadata.links);
+                std::mem::take(&mut metadata.links)
+            }
+            None => Vec::new(),
+        };
+        self.for_each_links(|links| {
+            counts.entity += dedup(links);
+            if scope == LinkScope::Document {
+                counts.document += remove_known(links, &mut known);
+            }
+        });
+        if let Some(metadata) = self.metadata.as_mut() {
+            metadata.links = known;
+        }
+        counts
+    }
+
+    /// for_each_links calls `f` with the links of every waypoint, track,
+    /// route and point, in document order.
+    fn for_each_links(&mut self, mut f: impl FnMut(&mut Vec<Link>)) {
+        for waypoint in &mut self.waypoints {
+            f(&mut waypoint.links);
+        }
+        for track in &mut self.tracks {
+            f(&mut track.links);
+            for segment in &mut track.segments {
+                for point in &mut segment.points {
+                    f(&mut point.links);
+                }
+            }
+        }
+        for route in &mut self.routes {
+            f(&mut route.links);
+            for point in &mut route.points {
+                f(&mut point.links);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{Gpx, Link, Metadata, Route, Track, TrackSegment, Waypoint};
+
+    use super::{loose_text, LinkDedupCounts, LinkScope};
+
+    fn link(href: &str, text: Option<&str>) -> Link {
+        Link {
+            href: href.into(),
+            text: text.map(String::from),
+            type_: None,
+        }
+    }
+
+    fn point(links: Vec<Link>) -> Waypoint<EmptyExtensions> {
+        let mut point = Waypoint::new(Point::new(0.0, 0.0));
+        point.links = links;
+        point
+    }
+
+    /// garmin gives a document as Garmin Connect exports them, with the same
+    /// link in the metadata, the track and every waypoint, and links of
+    /// their own besides.
+    fn garmin() -> Gpx<EmptyExtensions> {
+        let garmin = || link("connect.garmin.com", Some("Garmin Connect"));
+        let mut track = Track::new();
+        track.links = vec![garmin(), link("connect.garmin.com", Some("  garmin   CONNECT "))];
+        track.segments.push(TrackSegment {
+            points: vec![point(vec![]), point(vec![link("https://example.com/photo.jpg", None)])],
+        });
+        let mut route = Route::new();
+        route.links = vec![link("connect.garmin.com", None), link("connect.garmin.com", Some("connect.garmin.com"))];
+        route.points.push(point(vec![garmin()]));
+        Gpx {
+            metadata: Some(Metadata {
+                links: vec![garmin(), garmin()],
+                ..Default::default()
+            }),
+            waypoints: vec![
+                point(vec![garmin(), garmin()]),
+                point(vec![garmin(), link("CONNECT.garmin.com", Some("Garmin Connect"))]),
+                point(vec![link("connect.garmin.com", Some("My activity"))]),
+            ],
+            tracks: vec![track],
+            routes: vec![route],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn loose_text_of_links() {
+        assert_eq!(loose_text(&link("a", Some(" Garmin \t Connect\n"))), "garmin connect");
+        assert_eq!(loose_text(&link("a", Some(" a "))), "");
+        assert_eq!(loose_text(&link("a", Some("A"))), "a");
+        assert_eq!(loose_text(&link("a", None)), "");
+    }
+
+    #[test]
+    fn dedup_links_entity() {
+        let mut gpx = garmin();
+        let counts = gpx.dedup_links(LinkScope::Entity);
+        assert_eq!(counts, LinkDedupCounts { entity: 4, document: 0 });
+
+        let metadata = gpx.metadata.as_ref().unwrap();
+        assert_eq!(metadata.links, [link("connect.garmin.com", Some("Garmin Connect"))]);
+        assert_eq!(gpx.waypoints[0].links.len(), 1);
+        assert_eq!(gpx.waypoints[1].links.len(), 2);
+        assert_eq!(gpx.tracks[0].links, [link("connect.garmin.com", Some("Garmin Connect"))]);
+        // The copy without text takes the text of the removed one.
+        assert_eq!(gpx.routes[0].links, [link("connect.garmin.com", Some("connect.garmin.com"))]);
+
+        assert_eq!(gpx.dedup_links(LinkScope::Entity), LinkDedupCounts::default());
+    }
+
+    #[test]
+    fn dedup_links_document() {
+        let mut gpx = garmin();
+        let counts = gpx.dedup_links(LinkScope::Document);
+        assert_eq!(counts, LinkDedupCounts { entity: 4, document: 4 });
+
+        let metadata = gpx.metadata.as_ref().unwrap();
+        assert_eq!(metadata.links, [link("connect.garmin.com", Some("Garmin Connect"))]);
+        assert!(gpx.waypoints[0].links.is_empty());
+        assert!(gpx.tracks[0].links.is_empty());
+        assert!(gpx.routes[0].points[0].links.is_empty());
+
+        // Links that differ in the case of the href, or in their text, stay.
+        assert_eq!(gpx.routes[0].links, [link("connect.garmin.com", Some("connect.garmin.com"))]);
+        assert_eq!(gpx.waypoints[1].links, [link("CONNECT.garmin.com", Some("Garmin Connect"))]);
+        assert_eq!(gpx.waypoints[2].links, [link("connect.garmin.com", Some("My activity"))]);
+        assert_eq!(gpx.tracks[0].segments[0].points[1].links.len(), 1);
+
+        assert_eq!(gpx.dedup_links(LinkScope::Document), LinkDedupCounts::default());
+    }
+
+    #[test]
+    fn dedup_links_without_metadata() {
+        let mut gpx = garmin();
+        gpx.metadata = None;
+        let counts = gpx.dedup_links(LinkScope::Document);
+        assert_eq!(counts, LinkDedupCounts { entity: 3, document: 0 });
+        assert!(gpx.metadata.is_none());
+        assert_eq!(gpx.waypoints[0].links.len(), 1);
+    }
+}
@@ -0,0 +1,258 @@
+//! names finds waypoints sharing a name, and gives them distinct ones.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Gpx, Waypoint};
+
+/// How [`Gpx::disambiguate_waypoint_names`] tells waypoints sharing a name
+/// apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisambiguationStyle {
+    /// Appends a sequence number in document order: "Water 1", "Water 2".
+    /// Numbers giving the name of another waypoint are skipped.
+    Sequence,
+    /// Appends the distance along the nearest track to its point closest to
+    /// the waypoint: "Water km 12.3". Without tracks, falls back to
+    /// [`DisambiguationStyle::Sequence`].
+    ///
+    /// Waypoints close to the same point of a track get the same marker, and
+    /// so still share a name, as may a waypoint already named with a marker.
+    TrackDistance,
+}
+
+/// Options for [`Gpx::disambiguate_waypoint_names_with`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisambiguationOptions {
+    /// How waypoints sharing a name are told apart.
+    pub style: DisambiguationStyle,
+    /// Names waypoints without a name this, followed by a sequence number or
+    /// distance marker as for a shared name.
+    pub unnamed: Option<String>,
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Gives every name shared by several waypoints, with the indices of
+    /// those waypoints, in order of first use.
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{Gpx, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut gpx: Gpx<EmptyExtensions> = Gpx::default();
+    /// for name in ["Water", "Summit", "Water"] {
+    ///     let mut waypoint = Waypoint::new(Point::new(0.0, 0.0));
+    ///     waypoint.name = Some(name.into());
+    ///     gpx.waypoints.push(waypoint);
+    /// }
+    ///
+    /// assert_eq!(gpx.waypoint_name_collisions(), [(String::from("Water"), vec![0, 2])]);
+    /// ```
+    pub fn waypoint_name_collisions(&self) -> Vec<(String, Vec<usize>)> {
+        let mut names: Vec<(String, Vec<usize>)> = Vec::new();
+        // Where each name is in `names`.
+        let mut found: HashMap<&str, usize> = HashMap::new();
+        for (index, waypoint) in self.waypoints.iter().enumerate() {
+            let name = match &waypoint.name {
+                Some(name) => name,
+                None => continue,
+            };
+            match found.get(name.as_str()) {
+                Some(&at) => names[at].1.push(index),
+                None => {
+                    found.insert(name, names.len());
+                    names.push((name.clone(), vec![index]));
+                }
+            }
+        }
+        names.retain(|(_, indices)| indices.len() > 1);
+        names
+    }
+
+    /// Renames the waypoints sharing a name to tell them apart in the given
+    /// `style`, giving how many were renamed.
+    pub fn disambiguate_waypoint_names(&mut self, style: DisambiguationStyle) -> usize {
+        self.disambiguate_waypoint_names_with(&DisambiguationOptions { style, unnamed: None })
+    }
+
+    /// Renames the waypoints sharing a name like
+    /// [`Gpx::disambiguate_waypoint_names`], and names the unnamed ones when
+    /// [`DisambiguationOptions::unnamed`] is set, giving how many were
+    /// renamed or named.
+    pub fn disambiguate_waypoint_names_with(&mut self, options: &DisambiguationOptions) -> usize {
+        let mut groups = self.waypoint_name_collisions();
+        if let Some(unnamed) = &options.unnamed {
+            let indices: Vec<_> = (0..self.waypoints.len())
+                .filter(|&index| self.waypoints[index].name.is_none())
+                .collect();
+            if !indices.is_empty() {
+                groups.push((unnamed.clone(), indices));
+            }
+        }
+
+        // The names kept, and those given, which sequence numbers skip.
+        let renaming: HashSet<usize> = groups.iter().flat_map(|(_, indices)| indices.iter().copied()).collect();
+        let mut taken: HashSet<String> = self
+            .waypoints
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !renaming.contains(index))
+            .filter_map(|(_, waypoint)| waypoint.name.clone())
+            .collect();
+
+        let mut renamed = 0;
+        for (name, indices) in groups {
+            let mut number = 0;
+            for index in indices {
+                let marker = match options.style {
+                    DisambiguationStyle::TrackDistance => self.track_distance(&self.waypoints[index]),
+                    DisambiguationStyle::Sequence => None,
+                };
+                let name = match marker {
+                    Some(meters) => format!("{} km {:.1}", name, meters / 1000.0),
+                    None => loop {
+                        number += 1;
+                        let numbered = format!("{} {}", name, number);
+                        if !taken.contains(&numbered) {
+                            break numbered;
+                        }
+                    },
+                };
+                taken.insert(name.clone());
+                self.waypoints[index].name = Some(name);
+                renamed += 1;
+            }
+        }
+        renamed
+    }
+
+    /// track_distance gives the distance in meters along the track nearest to
+    /// `waypoint`, up to its point closest to it, or `None` without tracks.
+    fn track_distance(&self, waypoint: &Waypoint<E>) -> Option<f64> {
+        let (track, (segment, point, _)) = self
+            .tracks
+            .iter()
+            .filter_map(|track| Some((track, track.closest_point(waypoint.point())?)))
+            .min_by(|a, b| (a.1).2.total_cmp(&(b.1).2))?;
+        let before: f64 = track.segments[..segment]
+            .iter()
+            .map(|segment| segment.length_meters())
+            .sum();
+        Some(before + track.segments[segment].cumulative_distances()[point])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{Gpx, Track, TrackSegment, Waypoint};
+
+    use super::{DisambiguationOptions, DisambiguationStyle};
+
+    fn document(names: &[(Option<&str>, f64)]) -> Gpx<EmptyExtensions> {
+        let mut gpx = Gpx::default();
+        for &(name, x) in names {
+            let mut waypoint = Waypoint::new(Point::new(x, 0.001));
+            waypoint.name = name.map(String::from);
+            gpx.waypoints.push(waypoint);
+        }
+        gpx
+    }
+
+    fn names(gpx: &Gpx<EmptyExtensions>) -> Vec<Option<&str>> {
+        gpx.waypoints.iter().map(|waypoint| waypoint.name.as_deref()).collect()
+    }
+
+    #[test]
+    fn waypoint_name_collisions() {
+        let gpx = document(&[
+            (Some("Water"), 0.0),
+            (Some("Camp"), 0.0),
+            (None, 0.0),
+            (Some("Water"), 0.0),
+            (Some("Camp"), 0.0),
+            (Some("Summit"), 0.0),
+            (None, 0.0),
+            (Some("Water"), 0.0),
+        ]);
+        assert_eq!(
+            gpx.waypoint_name_collisions(),
+            [
+                (String::from("Water"), vec![0, 3, 7]),
+                (String::from("Camp"), vec![1, 4])
+            ]
+        );
+        assert!(document(&[(Some("Water"), 0.0)]).waypoint_name_collisions().is_empty());
+    }
+
+    #[test]
+    fn disambiguate_by_sequence() {
+        let mut gpx = document(&[(Some("Water"), 0.0), (None, 0.0), (Some("Summit"), 0.0), (Some("Water"), 0.0)]);
+        assert_eq!(gpx.disambiguate_waypoint_names(DisambiguationStyle::Sequence), 2);
+        assert_eq!(names(&gpx), [Some("Water 1"), None, Some("Summit"), Some("Water 2")]);
+
+        let options = DisambiguationOptions {
+            style: DisambiguationStyle::Sequence,
+            unnamed: Some(String::from("Waypoint")),
+        };
+        assert_eq!(gpx.disambiguate_waypoint_names_with(&options), 1);
+        assert_eq!(names(&gpx)[1], Some("Waypoint 1"));
+
+        // Without tracks, distance markers fall back to sequence numbers.
+        let mut gpx = document(&[(Some("Water"), 0.0), (Some("Water"), 0.0)]);
+        gpx.disambiguate_waypoint_names(DisambiguationStyle::TrackDistance);
+        assert_eq!(names(&gpx), [Some("Water 1"), Some("Water 2")]);
+    }
+
+    #[test]
+    fn disambiguate_skips_taken_names() {
+        let mut gpx = document(&[
+            (Some("Water"), 0.0),
+            (Some("Water"), 0.0),
+            (Some("Water 2"), 0.0),
+            (Some("Water 1"), 0.0),
+            (Some("Water 1"), 0.0),
+        ]);
+        assert_eq!(gpx.disambiguate_waypoint_names(DisambiguationStyle::Sequence), 4);
+        assert_eq!(
+            names(&gpx),
+            [
+                Some("Water 1"),
+                Some("Water 3"),
+                Some("Water 2"),
+                Some("Water 1 1"),
+                Some("Water 1 2")
+            ]
+        );
+        assert!(gpx.waypoint_name_collisions().is_empty());
+    }
+
+    #[test]
+    fn disambiguate_by_track_distance() {
+        let mut gpx = document(&[(Some("Water"), 0.05), (Some("Water"), 0.123), (None, 0.2)]);
+        // Along the equator, a point every 0.01 degrees of about 1.11 km,
+        // over two segments.
+        let segment = |from: usize, to: usize| TrackSegment {
+            points: (from..=to)
+                .map(|i| Waypoint::new(Point::new(i as f64 * 0.01, 0.0)))
+                .collect(),
+        };
+        let mut track = Track::new();
+        track.segments.push(segment(0, 10));
+        track.segments.push(segment(10, 20));
+        gpx.tracks.push(track);
+
+        let options = DisambiguationOptions {
+            style: DisambiguationStyle::TrackDistance,
+            unnamed: Some(String::from("Waypoint")),
+        };
+        assert_eq!(gpx.disambiguate_waypoint_names_with(&options), 3);
+        assert_eq!(
+            names(&gpx),
+            [Some("Water km 5.6"), Some("Water km 13.3"), Some("Waypoint km 22.2")]
+        );
+    }
+}
@@ -0,0 +1,425 @@
+//! nmea reads NMEA 0183 logs, as raw GPS loggers write them, into a track.
+//!
+//! A log is read into one track of one segment, with a point per fix. The
+//! `RMC` sentence of a fix gives its position, date, speed and course, and
+//! the `GGA` sentence of the same time of day its elevation, fix quality,
+//! satellite count and HDOP. Other sentences are ignored, as are lines that
+//! are not sentences. Fixes without a position, as loggers write before they
+//! have one, are left out with a [`GpxWarning::SkippedPoint`].
+
+use std::convert::TryFrom;
+use std::io::BufRead;
+
+use geo_types::Point;
+use time::{Date, Month, PrimitiveDateTime};
+
+use crate::errors::{GpxError, GpxResult, GpxWarning, Position, ReadWarning};
+use crate::parser::extensions::EmptyExtensions;
+use crate::{Fix, Gpx, GpxVersion, ParseOptions, ReadOutcome, Track, TrackSegment, Waypoint};
+
+/// Meters per second in a knot, the unit of NMEA speeds.
+const KNOT: f64 = 1852.0 / 3600.0;
+
+/// Reads the fixes of an NMEA 0183 log.
+///
+/// ```
+/// let log = "$GPGGA,063000,4722.200,N,00832.400,E,1,08,0.9,410.5,M,48.0,M,,*46
+/// $GPRMC,063000,A,4722.200,N,00832.400,E,5.0,90.0,010524,,*2A";
+///
+/// let gpx = gpx::nmea::read(log.as_bytes()).unwrap();
+/// let point = &gpx.tracks[0].segments[0].points[0];
+/// assert_eq!(point.point().y(), 47.37);
+/// assert_eq!(point.elevation, Some(410.5));
+/// ```
+pub fn read<R: BufRead>(reader: R) -> GpxResult<Gpx<EmptyExtensions>> {
+    read_with_warnings(reader, Default::default()).map(|outcome| outcome.gpx)
+}
+
+/// Reads the fixes of an NMEA 0183 log like [`read`], and gives them along
+/// with the fixes left out and the sentences skipped.
+///
+/// A sentence whose checksum does not match, or whose fields cannot be read,
+/// fails the read, unless [`ParseOptions::lenient`] is set: then it is
+/// skipped with a [`GpxWarning::SkippedSentence`]. The positions of the
+/// warnings are the lines of the log.
+pub fn read_with_warnings<R: BufRead>(reader: R, options: ParseOptions) -> GpxResult<ReadOutcome<EmptyExtensions>> {
+    let mut log = Log {
+        lenient: options.lenient,
+        ..Default::default()
+    };
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let sentence = line.trim();
+        if sentence.starts_with('$') {
+            log.read_sentence(index + 1, sentence)?;
+        }
+    }
+    log.flush();
+
+    let mut track = Track::new();
+    track.segments.push(TrackSegment { points: log.points });
+    Ok(ReadOutcome {
+        gpx: Gpx {
+            version: GpxVersion::Gpx11,
+            tracks: vec![track],
+            ..Default::default()
+        },
+        warnings: log.warnings,
+    })
+}
+
+/// The fields of an `RMC` sentence that go into a point.
+#[derive(Debug, Default, PartialEq)]
+struct Rmc {
+    /// The position as longitude and latitude, unless the fix is void.
+    position: Option<(f64, f64)>,
+    date: Option<Date>,
+    speed: Option<f64>,
+    course: Option<f64>,
+}
+
+/// The fields of a `GGA` sentence that go into a point.
+#[derive(Debug, Default, PartialEq)]
+struct Gga {
+    position: Option<(f64, f64)>,
+    elevation: Option<f64>,
+    geoidheight: Option<f64>,
+    fix: Option<Fix>,
+    sat: Option<u64>,
+    hdop: Option<f64>,
+}
+
+/// The sentences of a fix, which share a time of day.
+#[derive(Debug, Default)]
+struct Epoch {
+    /// The line of the first sentence.
+    line: usize,
+    /// The time field of the sentences, as written.
+    key: String,
+    time: Option<time::Time>,
+    rmc: Option<Rmc>,
+    gga: Option<Gga>,
+}
+
+/// The state of a log being read.
+#[derive(Debug, Default)]
+struct Log {
+    lenient: bool,
+    points: Vec<Waypoint<EmptyExtensions>>,
+    warnings: Vec<ReadWarning>,
+    /// The date of the last `RMC` sentence, which dates the `GGA` sentences
+    /// that lack one.
+    date: Option<Date>,
+    epoch: Option<Epoch>,
+}
+
+impl Log {
+    fn warn(&mut self, line: usize, warning: GpxWarning) {
+        self.warnings.push(ReadWarning {
+            warning,
+            position: Some(Position {
+                line: line as u64,
+                column: 1,
+            }),
+        });
+    }
+
+    /// read_sentence adds the sentence on `line` to the current fix, or
+    /// starts a new one when its time of day differs.
+    fn read_sentence(&mut self, line: usize, sentence: &str) -> GpxResult<()> {
+        let parsed = fields(sentence).and_then(|fields| {
+            let sentence = match fields[0].get(2..) {
+                Some("RMC") => Sentence::Rmc(rmc(&fields)?),
+                Some("GGA") => Sentence::Gga(gga(&fields)?),
+                _ => return Ok(None),
+            };
+            let time = field(&fields, 1).map(time_of_day).transpose()?;
+            Ok(Some((fields[1], time, sentence)))
+        });
+        let (key, time, sentence) = match parsed {
+            Ok(Some(parsed)) => parsed,
+            Ok(None) => return Ok(()),
+            Err(reason) if self.lenient => {
+                self.warn(
+                    line,
+                    GpxWarning::SkippedSentence {
+                        sentence: sentence.to_string(),
+                        reason,
+                    },
+                );
+                return Ok(());
+            }
+            Err(reason) => return Err(GpxError::InvalidNmeaSentence(line, reason)),
+        };
+
+        let same_fix = self.epoch.as_ref().map_or(false, |epoch| {
+            epoch.key == key
+                && match sentence {
+                    Sentence::Rmc(_) => epoch.rmc.is_none(),
+                    Sentence::Gga(_) => epoch.gga.is_none(),
+                }
+        });
+        if !same_fix {
+            self.flush();
+            self.epoch = Some(Epoch {
+                line,
+                key: key.to_string(),
+                time,
+                ..Default::default()
+            });
+        }
+        let epoch = self.epoch.as_mut().expect("a fix was started");
+        match sentence {
+            Sentence::Rmc(rmc) => epoch.rmc = Some(rmc),
+            Sentence::Gga(gga) => epoch.gga = Some(gga),
+        }
+        Ok(())
+    }
+
+    /// flush turns the current fix into a point, or leaves it out when it has
+    /// no position.
+    fn flush(&mut self) {
+        let epoch = match self.epoch.take() {
+            Some(epoch) => epoch,
+            None => return,
+        };
+        let element = if epoch.rmc.is_some() { "RMC" } else { "GGA" };
+        let rmc = epoch.rmc.unwrap_or_default();
+        let gga = epoch.gga.unwrap_or_default();
+        self.date = rmc.date.or(self.date);
+
+        let (longitude, latitude) = match rmc.position.or(gga.position) {
+            Some(position) => position,
+            None => {
+                self.warn(
+                    epoch.line,
+                    GpxWarning::SkippedPoint {
+                        element,
+                        missing: "latitude",
+                    },
+                );
+                return;
+            }
+        };
+        let mut point = Waypoint::new(Point::new(longitude, latitude));
+        point.time = match (self.date, epoch.time) {
+            (Some(date), Some(time)) => Some(PrimitiveDateTime::new(date, time).assume_utc().into()),
+            _ => None,
+        };
+        point.speed = rmc.speed;
+        point.course = rmc.course;
+        point.elevation = gga.elevation;
+        point.geoidheight = gga.geoidheight;
+        point.fix = gga.fix;
+        point.sat = gga.sat;
+        point.hdop = gga.hdop;
+        self.points.push(point);
+    }
+}
+
+/// A sentence that goes into a point.
+enum Sentence {
+    Rmc(Rmc),
+    Gga(Gga),
+}
+
+/// fields checks the checksum of `sentence`, when it has one, and gives its
+/// fields, the first being its address, such as `GPRMC`.
+fn fields(sentence: &str) -> Result<Vec<&str>, &'static str> {
+    let body = &sentence[1..];
+    let data = match body.split_once('*') {
+        Some((data, checksum)) => {
+            let expected = u8::from_str_radix(checksum, 16).map_err(|_| "malformed checksum")?;
+            if data.bytes().fold(0, |sum, byte| sum ^ byte) != expected {
+                return Err("checksum mismatch");
+            }
+            data
+        }
+        None => body,
+    };
+    Ok(data.split(',').collect())
+}
+
+/// field gives the field at `index` of `fields`, unless it is missing or
+/// empty.
+fn field<'a>(fields: &[&'a str], index: usize) -> Option<&'a str> {
+    fields.get(index).copied().filter(|field| !field.is_empty())
+}
+
+fn number(fields: &[&str], index: usize, reason: &'static str) -> Result<Option<f64>, &'static str> {
+    field(fields, index).map(|value| value.parse().map_err(|_| reason)).transpose()
+}
+
+/// position gives the longitude and latitude written from `index` on, as
+/// `ddmm.mmmm,N,dddmm.mmmm,E`.
+fn position(fields: &[&str], index: usize) -> Result<Option<(f64, f64)>, &'static str> {
+    let latitude = coordinate(fields, index, ('N', 'S'), "malformed latitude")?;
+    let longitude = coordinate(fields, index + 2, ('E', 'W'), "malformed longitude")?;
+    Ok(longitude.zip(latitude))
+}
+
+/// coordinate gives the degrees and minutes at `index` as degrees, negative
+/// in the second of the `hemispheres`, which follow at `index + 1`.
+fn coordinate(
+    fields: &[&str],
+    index: usize,
+    hemispheres: (char, char),
+    reason: &'static str,
+) -> Result<Option<f64>, &'static str> {
+    let value = match number(fields, index, reason)? {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let degrees = (value / 100.0).trunc();
+    let degrees = degrees + (value - degrees * 100.0) / 60.0;
+    match field(fields, index + 1).and_then(|hemisphere| hemisphere.parse::<char>().ok()) {
+        Some(hemisphere) if hemisphere == hemispheres.0 => Ok(Some(degrees)),
+        Some(hemisphere) if hemisphere == hemispheres.1 => Ok(Some(-degrees)),
+        _ => Err(reason),
+    }
+}
+
+/// time_of_day reads a time written `hhmmss` or `hhmmss.sss`.
+fn time_of_day(value: &str) -> Result<time::Time, &'static str> {
+    let reason = "malformed time";
+    let part = |range: std::ops::Range<usize>| value.get(range).and_then(|part| part.parse::<u8>().ok()).ok_or(reason);
+    let (hours, minutes) = (part(0..2)?, part(2..4)?);
+    let seconds: f64 = value.get(4..).and_then(|seconds| seconds.parse().ok()).ok_or(reason)?;
+    let nanoseconds = ((seconds.fract() * 1e9).round() as u32).min(999_999_999);
+    time::Time::from_hms_nano(hours, minutes, seconds.trunc() as u8, nanoseconds).map_err(|_| reason)
+}
+
+/// date reads a date written `ddmmyy`, in the years from 1980, when GPS
+/// started, to 2079.
+fn date(value: &str) -> Result<Date, &'static str> {
+    let reason = "malformed date";
+    let part = |range: std::ops::Range<usize>| value.get(range).and_then(|part| part.parse::<u8>().ok()).ok_or(reason);
+    if value.len() != 6 {
+        return Err(reason);
+    }
+    let year = match part(4..6)? {
+        year if year < 80 => 2000 + year as i32,
+        year => 1900 + year as i32,
+    };
+    let month = Month::try_from(part(2..4)?).map_err(|_| reason)?;
+    Date::from_calendar_date(year, month, part(0..2)?).map_err(|_| reason)
+}
+
+/// rmc reads `$--RMC,time,status,lat,N,lon,E,knots,course,ddmmyy,...`.
+fn rmc(fields: &[&str]) -> Result<Rmc, &'static str> {
+    let position = position(fields, 3)?;
+    Ok(Rmc {
+        position: position.filter(|_| field(fields, 2) == Some("A")),
+        date: field(fields, 9).map(date).transpose()?,
+        speed: number(fields, 7, "malformed speed")?.map(|knots| knots * KNOT),
+        course: number(fields, 8, "malformed course")?,
+    })
+}
+
+/// gga reads `$--GGA,time,lat,N,lon,E,quality,satellites,hdop,altitude,M,separation,M,...`.
+fn gga(fields: &[&str]) -> Result<Gga, &'static str> {
+    let elevation = number(fields, 9, "malformed altitude")?;
+    Ok(Gga {
+        position: position(fields, 2)?,
+        elevation,
+        geoidheight: number(fields, 11, "malformed geoid separation")?,
+        fix: field(fields, 6).map(|quality| fix(quality, elevation.is_some())),
+        sat: field(fields, 7)
+            .map(|sat| sat.parse().map_err(|_| "malformed satellite count"))
+            .transpose()?,
+        hdop: number(fields, 8, "malformed HDOP")?,
+    })
+}
+
+/// fix maps a GGA fix quality to a [`Fix`]. GGA does not tell a 2D GPS fix
+/// from a 3D one, so it is taken for 3D when the sentence has an altitude.
+fn fix(quality: &str, has_altitude: bool) -> Fix {
+    match quality {
+        "0" => Fix::None,
+        "1" if has_altitude => Fix::ThreeDimensional,
+        "1" => Fix::TwoDimensional,
+        "2" => Fix::DGPS,
+        "3" => Fix::PPS,
+        other => Fix::Other(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::{GpxError, GpxWarning};
+    use crate::{Fix, ParseOptions};
+
+    use super::{fields, position, read, read_with_warnings, time_of_day, KNOT};
+
+    #[test]
+    fn fields_checks_checksum() {
+        let sentence = "$GPGSA,A,3,04,05,,09,12,,,24,,,,,2.5,1.3,2.1*39";
+        assert_eq!(fields(sentence).unwrap()[..3], ["GPGSA", "A", "3"]);
+        assert_eq!(fields(&sentence.replace("*39", "*38")), Err("checksum mismatch"));
+        assert_eq!(fields(&sentence.replace("*39", "*3G")), Err("malformed checksum"));
+        assert_eq!(fields("$GPGSA,A,1").unwrap(), ["GPGSA", "A", "1"]);
+    }
+
+    #[test]
+    fn position_by_hemisphere() {
+        let fields = ["GPGGA", "", "3345.678", "S", "07030.000", "W"];
+        let (longitude, latitude) = position(&fields, 2).unwrap().unwrap();
+        assert!((latitude + 33.7613).abs() < 1e-9);
+        assert!((longitude + 70.5).abs() < 1e-9);
+
+        assert_eq!(position(&["GPGGA", "", "", "", "", ""], 2), Ok(None));
+        assert_eq!(position(&fields[..5], 2), Err("malformed longitude"));
+    }
+
+    #[test]
+    fn time_of_day_with_fraction() {
+        let time = time_of_day("235959.25").unwrap();
+        assert_eq!(time.as_hms_milli(), (23, 59, 59, 250));
+        assert_eq!(time_of_day("2359").map(|_| ()), Err("malformed time"));
+        assert_eq!(time_of_day("245959").map(|_| ()), Err("malformed time"));
+    }
+
+    #[test]
+    fn read_merges_by_time_of_day() {
+        let log = "$GPRMC,120000,A,0100.000,N,00200.000,E,10.0,45.0,311299,,*1C
+$GPGGA,120000,0100.000,N,00200.000,E,1,03,2.0,,,,,,*73
+$GPGGA,120001,0100.100,N,00200.000,E,6,05,1.0,12.0,M,,,,*21";
+        let points = &read(log.as_bytes()).unwrap().tracks[0].segments[0].points;
+
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].speed, Some(10.0 * KNOT));
+        assert_eq!(points[0].course, Some(45.0));
+        assert_eq!(points[0].fix, Some(Fix::TwoDimensional));
+        assert_eq!(points[0].sat, Some(3));
+        assert_eq!(points[0].time.unwrap().format().unwrap(), "1999-12-31T12:00:00.000000000Z");
+        assert_eq!(points[1].fix, Some(Fix::Other(String::from("6"))));
+        assert_eq!(points[1].elevation, Some(12.0));
+        assert_eq!(points[1].time.unwrap().format().unwrap(), "1999-12-31T12:00:01.000000000Z");
+    }
+
+    #[test]
+    fn read_checksum_mismatch() {
+        let log = "$GPGGA,120000,0100.000,N,00200.000,E,1,03,2.0,,,,,,*76
+$GPGGA,120001,0100.100,N,00200.000,E,6,05,1.0,12.0,M,,,,*21";
+        assert!(matches!(
+            read(log.as_bytes()),
+            Err(GpxError::InvalidNmeaSentence(1, "checksum mismatch"))
+        ));
+
+        let options = ParseOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let outcome = read_with_warnings(log.as_bytes(), options).unwrap();
+        assert_eq!(outcome.gpx.tracks[0].segments[0].points.len(), 1);
+        assert_eq!(outcome.warnings.len(), 1);
+        assert_eq!(outcome.warnings[0].position.unwrap().line, 1);
+        assert_eq!(
+            outcome.warnings[0].warning,
+            GpxWarning::SkippedSentence {
+                sentence: log.lines().next().unwrap().to_string(),
+                reason: "checksum mismatch",
+            }
+        );
+    }
+}
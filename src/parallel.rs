@@ -0,0 +1,548 @@
+//! parallel reads on a pool of threads: every GPX file of a directory at
+//! once, for importers of many files, or the track segments of a document at
+//! once, for documents of many segments.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+
+use crate::declaration::encoding_value;
+use crate::errors::GpxResult;
+use crate::parser::extensions::{EmptyExtensions, WaypointExtensions};
+use crate::parser::{create_context_with_options, tracksegment, verify_starting_tag};
+use crate::{read_with_options, Gpx, ParseOptions, TrackSegment};
+
+/// Options for [`read_dir_parallel`] and [`read_dir_parallel_map`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReadDirOptions {
+    /// Reads the files of subdirectories too, but not of symbolic links to
+    /// directories, which could loop.
+    pub recursive: bool,
+    /// How many files are read at once, by default as many as there are
+    /// CPUs.
+    pub workers: Option<usize>,
+    /// How every file is parsed.
+    pub parse: ParseOptions,
+}
+
+/// Reads the `.gpx` and gzipped `.gpx.gz` files of the directory at `path`
+/// in parallel, giving every one with its path, in the order of the paths.
+/// A file that cannot be read or parsed has its error, leaving the others
+/// read; failing to list the directory is an error of its own.
+///
+/// ```
+/// use gpx::{read_dir_parallel, ReadDirOptions};
+///
+/// let documents = read_dir_parallel("tests/fixtures", &ReadDirOptions::default()).unwrap();
+/// let (path, gpx) = &documents[0];
+/// assert!(path.ends_with("billion-laughs.gpx"));
+/// assert!(gpx.is_err());
+/// ```
+pub fn read_dir_parallel<P: AsRef<Path>>(
+    path: P,
+    options: &ReadDirOptions,
+) -> GpxResult<Vec<(PathBuf, GpxResult<Gpx<EmptyExtensions>>)>> {
+    read_dir_parallel_map(path, options, |_, gpx| gpx)
+}
+
+/// Reads the files of the directory at `path` as [`read_dir_parallel`] does,
+/// giving what `map` makes of every document rather than the document, so
+/// that only what is needed of them is held at once.
+///
+/// ```
+/// use gpx::{read_dir_parallel_map, ReadDirOptions};
+///
+/// let points = read_dir_parallel_map("tests/fixtures", &ReadDirOptions::default(), |_, gpx| {
+///     gpx.map(|gpx| gpx.points_count()).unwrap_or(0)
+/// })
+/// .unwrap();
+/// assert!(points.iter().any(|(_, points)| *points > 0));
+/// ```
+pub fn read_dir_parallel_map<P, T, F>(path: P, options: &ReadDirOptions, map: F) -> GpxResult<Vec<(PathBuf, T)>>
+where
+    P: AsRef<Path>,
+    T: Send,
+    F: Fn(&Path, GpxResult<Gpx<EmptyExtensions>>) -> T + Sync,
+{
+    let mut paths = Vec::new();
+    find_files(path.as_ref(), options.recursive, &mut paths)?;
+    paths.sort();
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(options.workers.unwrap_or(0))
+        .build()
+        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+    Ok(pool.install(|| {
+        paths
+            .into_par_iter()
+            .map(|path| {
+                let value = map(&path, read_path(&path, &options.parse));
+                (path, value)
+            })
+            .collect()
+    }))
+}
+
+/// is_gpx tells whether `path` names a `.gpx` or `.gpx.gz` file, whatever
+/// the case.
+fn is_gpx(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name.to_ascii_lowercase(),
+        None => return false,
+    };
+    name.ends_with(".gpx") || name.ends_with(".gpx.gz")
+}
+
+/// find_files adds the GPX files of the directory `dir` to `paths`, and
+/// those of its subdirectories when `recursive`.
+fn find_files(dir: &Path, recursive: bool, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if recursive {
+                find_files(&path, recursive, paths)?;
+            }
+        } else if is_gpx(&path) && path.is_file() {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// read_path reads the GPX file at `path`, unzipping it when it ends with
+/// `.gz`.
+fn read_path(path: &Path, options: &ParseOptions) -> GpxResult<Gpx<EmptyExtensions>> {
+    let file = BufReader::new(File::open(path)?);
+    if path.extension().map_or(false, |extension| extension.eq_ignore_ascii_case("gz")) {
+        read_with_options(MultiGzDecoder::new(file), options.clone())
+    } else {
+        read_with_options(file, options.clone())
+    }
+}
+
+/// Reads an activity in GPX format from `bytes` like [`read_with_options`],
+/// parsing its track segments in parallel, for documents of many segments,
+/// such as those of devices starting a segment at every pause.
+///
+/// The document is first scanned for its segments, then read without them,
+/// and its segments are read at once and put back in order. The result is
+/// the same as reading it serially: a document that fails to read this way,
+/// or that cannot be split, such as one with a DTD, is read serially, which
+/// gives the first error of the document. A timeout counts for each part of
+/// the document on its own. A document is read serially too under
+/// [`ParseLimits::max_points`](crate::ParseLimits::max_points), which counts
+/// the points of the whole document.
+///
+/// ```
+/// use gpx::{read_parallel, Gpx, ParseOptions};
+/// use gpx::parser::extensions::EmptyExtensions;
+///
+/// let data = br#"<gpx version="1.1"><trk>
+///     <trkseg><trkpt lat="1" lon="2"/></trkseg>
+///     <trkseg><trkpt lat="3" lon="4"/><trkpt lat="5" lon="6"/></trkseg>
+/// </trk></gpx>"#;
+///
+/// let gpx: Gpx<EmptyExtensions> = read_parallel(data, ParseOptions::strict()).unwrap();
+/// assert_eq!(gpx.tracks[0].segments[1].points.len(), 2);
+/// ```
+pub fn read_parallel<E>(bytes: &[u8], options: ParseOptions) -> GpxResult<Gpx<E>>
+where
+    E: WaypointExtensions + Default + Send,
+    E::ExtensionsValue: Send,
+{
+    match Segments::find(bytes) {
+        Some(segments) if segments.segments.len() > 1 && options.limits.max_points.is_none() => match segments.read(bytes, &options) {
+            Some(gpx) => Ok(gpx),
+            None => read_with_options(bytes, options),
+        },
+        _ => read_with_options(bytes, options),
+    }
+}
+
+/// A segment of a document, by the byte ranges of its element and of the
+/// starting tag of its track.
+struct Segment {
+    track: Range<usize>,
+    element: Range<usize>,
+}
+
+/// Segments are the track segments of a document, with the starting tag of
+/// its `gpx` element, which declares the namespaces they may use.
+struct Segments {
+    root: Range<usize>,
+    segments: Vec<Segment>,
+}
+
+/// local_name gives the name of the element of a tag, without its prefix,
+/// from `tag`, the bytes after `<` or `</`.
+fn local_name(tag: &[u8]) -> &[u8] {
+    let end = tag
+        .iter()
+        .position(|&b| b.is_ascii_whitespace() || b == b'/' || b == b'>')
+        .unwrap_or(tag.len());
+    let name = &tag[..end];
+    match name.iter().rposition(|&b| b == b':') {
+        Some(colon) => &name[colon + 1..],
+        None => name,
+    }
+}
+
+/// find gives the index in `bytes` after the first `pattern` from `start`.
+fn find(bytes: &[u8], start: usize, pattern: &[u8]) -> Option<usize> {
+    bytes[start..]
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .map(|position| start + position + pattern.len())
+}
+
+/// tag_end gives the index in `bytes` after the `>` ending the tag starting
+/// at `start`, passing over quoted attribute values.
+fn tag_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (index, &byte) in bytes.iter().enumerate().skip(start) {
+        match (quote, byte) {
+            (None, b'"' | b'\'') => quote = Some(byte),
+            (Some(open), _) if byte == open => quote = None,
+            (None, b'>') => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+impl Segments {
+    /// find scans `bytes` for the segments of the tracks of its root, giving
+    /// `None` for documents it cannot split: those not in UTF-8, or declared
+    /// in another encoding, which the segments read without the declaration
+    /// would not be read in, those with a DTD, which can define entities
+    /// expanding to markup, and malformed ones, left to the serial parse to
+    /// fail on.
+    fn find(bytes: &[u8]) -> Option<Segments> {
+        let text = std::str::from_utf8(bytes).ok()?;
+        let mut root = None;
+        let mut track = None;
+        let mut segment = None;
+        let mut segments = Vec::new();
+        let mut open: Vec<&[u8]> = Vec::new();
+        let mut index = 0;
+        while let Some(offset) = bytes[index..].iter().position(|&b| b == b'<') {
+            let start = index + offset;
+            let rest = &bytes[start..];
+            index = if rest.starts_with(b"<!--") {
+                find(bytes, start, b"-->")?
+            } else if rest.starts_with(b"<![CDATA[") {
+                find(bytes, start, b"]]>")?
+            } else if rest.starts_with(b"<?") {
+                if let Some(value) = encoding_value(&text[start..]) {
+                    let encoding = &text[start..][value];
+                    if !encoding.eq_ignore_ascii_case("utf-8") && !encoding.eq_ignore_ascii_case("utf8") {
+                        return None;
+                    }
+                }
+                find(bytes, start, b"?>")?
+            } else if rest.starts_with(b"<!") {
+                return None;
+            } else if rest.starts_with(b"</") {
+                let end = tag_end(bytes, start)?;
+                let name = local_name(&bytes[start + 2..]);
+                if open.pop()? != name {
+                    return None;
+                }
+                match (open.len(), name) {
+                    (0, _) => break,
+                    (2, b"trkseg") => segments.push(Segment {
+                        track: track.clone()?,
+                        element: segment.take()?..end,
+                    }),
+                    _ => {}
+                }
+                end
+            } else {
+                let end = tag_end(bytes, start)?;
+                let name = local_name(&bytes[start + 1..]);
+                let empty = bytes[end - 2] == b'/';
+                match (open.as_slice(), name) {
+                    ([], b"gpx") => root = Some(start..end),
+                    ([b"gpx"], b"trk") => track = Some(start..end),
+                    ([b"gpx", b"trk"], b"trkseg") if empty => segments.push(Segment {
+                        track: track.clone()?,
+                        element: start..end,
+                    }),
+                    ([b"gpx", b"trk"], b"trkseg") => segment = Some(start),
+                    _ => {}
+                }
+                if !empty {
+                    open.push(name);
+                } else if open.is_empty() {
+                    break;
+                }
+                end
+            };
+        }
+        Some(Segments { root: root?, segments })
+    }
+
+    /// read reads the document of `bytes` without its segments, then its
+    /// segments in parallel, putting them back in order. Any error gives
+    /// `None`, as it may not be the first of the document.
+    fn read<E>(&self, bytes: &[u8], options: &ParseOptions) -> Option<Gpx<E>>
+    where
+        E: WaypointExtensions + Default + Send,
+        E::ExtensionsValue: Send,
+    {
+        let mut skeleton = Vec::with_capacity(bytes.len());
+        let mut copied = 0;
+        for segment in &self.segments {
+            skeleton.extend_from_slice(&bytes[copied..segment.element.start]);
+            skeleton.extend_from_slice(b"<trkseg/>");
+            copied = segment.element.end;
+        }
+        skeleton.extend_from_slice(&bytes[copied..]);
+        let skeleton_options = ParseOptions {
+            post_normalize: None,
+            ..options.clone()
+        };
+        let mut gpx: Gpx<E> = read_with_options(&skeleton[..], skeleton_options).ok()?;
+
+        let version = gpx.version;
+        let parsed: Vec<GpxResult<TrackSegment<E>>> = self
+            .segments
+            .par_iter()
+            .map(|segment| {
+                let input = bytes[self.root.clone()]
+                    .chain(&bytes[segment.track.clone()])
+                    .chain(&bytes[segment.element.clone()]);
+                // Every segment reserves its share of the points, by size.
+                let options = ParseOptions {
+                    points_capacity_hint: options
+                        .points_capacity_hint
+                        .map(|hint| (hint as u128 * segment.element.len() as u128 / bytes.len() as u128) as usize),
+                    ..options.clone()
+                };
+                let mut context = create_context_with_options::<_, E>(input, version, options);
+                verify_starting_tag(&mut context, "gpx")?;
+                verify_starting_tag(&mut context, "trk")?;
+                tracksegment::consume(&mut context)
+            })
+            .collect();
+
+        let mut parsed = parsed.into_iter();
+        for track in &mut gpx.tracks {
+            for segment in &mut track.segments {
+                *segment = parsed.next()?.ok()?;
+            }
+        }
+        if parsed.next().is_some() {
+            return None;
+        }
+        if let Some(policy) = options.post_normalize {
+            gpx.ensure_invariants(policy).ok()?;
+        }
+        Some(gpx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use crate::parser::extensions::{
+        DetectedExtensions, EmptyExtensions, GarminTrackPointExtensions, RawExtensions, WaypointExtensions,
+    };
+    use crate::errors::GpxError;
+    use crate::{read_with_options, InvariantPolicy, ParseLimits, ParseOptions};
+
+    use super::{read_dir_parallel, read_dir_parallel_map, read_parallel, ReadDirOptions, Segments};
+
+    #[test]
+    fn read_fixtures() {
+        let documents = read_dir_parallel("tests/fixtures", &ReadDirOptions::default()).unwrap();
+        let mut fixtures: Vec<PathBuf> = fs::read_dir("tests/fixtures")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().map_or(false, |extension| extension == "gpx"))
+            .collect();
+        fixtures.sort();
+        let paths: Vec<_> = documents.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(paths, fixtures);
+
+        let failing = ["billion-laughs.gpx", "messy.gpx", "windows1252.gpx"];
+        for (path, gpx) in &documents {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            assert_eq!(gpx.is_err(), failing.contains(&name), "{}", name);
+        }
+
+        // Lenient parsing reads the messy one.
+        let options = ReadDirOptions {
+            workers: Some(2),
+            parse: ParseOptions::lenient(),
+            ..Default::default()
+        };
+        let read = read_dir_parallel_map("tests/fixtures", &options, |_, gpx| gpx.is_ok()).unwrap();
+        assert_eq!(read.iter().filter(|(_, read)| !read).count(), 2);
+    }
+
+    #[test]
+    fn read_recursive_and_gzipped() {
+        let dir = std::env::temp_dir().join(format!("gpx-read-dir-{}", std::process::id()));
+        let nested = dir.join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        let data = fs::read("tests/fixtures/wikipedia_example.gpx").unwrap();
+        fs::write(dir.join("plain.GPX"), &data).unwrap();
+        fs::write(dir.join("notes.txt"), "not a track").unwrap();
+        let mut encoder = GzEncoder::new(File::create(nested.join("zipped.gpx.gz")).unwrap(), Compression::default());
+        encoder.write_all(&data).unwrap();
+        encoder.finish().unwrap();
+
+        let points = |recursive| {
+            let options = ReadDirOptions {
+                recursive,
+                ..Default::default()
+            };
+            read_dir_parallel_map(&dir, &options, |_, gpx| gpx.unwrap().points_count()).unwrap()
+        };
+        let flat = points(false);
+        let recursive = points(true);
+        assert!(read_dir_parallel(dir.join("missing"), &ReadDirOptions::default()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(flat, [(dir.join("plain.GPX"), 3)]);
+        assert_eq!(
+            recursive,
+            [(dir.join("nested/zipped.gpx.gz"), 3), (dir.join("plain.GPX"), 3)]
+        );
+    }
+
+    /// assert_same_as_serial asserts that reading `data` in parallel gives
+    /// what reading it serially does.
+    fn assert_same_as_serial<E>(data: &[u8], options: &ParseOptions)
+    where
+        E: WaypointExtensions + Default + Send + PartialEq + Debug,
+        E::ExtensionsValue: Send,
+    {
+        let parallel = read_parallel::<E>(data, options.clone());
+        let serial = read_with_options::<_, E>(data, options.clone());
+        match (parallel, serial) {
+            (Ok(parallel), Ok(serial)) => assert_eq!(parallel, serial),
+            (Err(parallel), Err(serial)) => assert_eq!(parallel.to_string(), serial.to_string()),
+            (parallel, serial) => panic!("read {:?} in parallel but {:?} serially", parallel.err(), serial.err()),
+        }
+    }
+
+    fn assert_same_as_serial_with_all(data: &[u8]) {
+        let normalized = ParseOptions {
+            post_normalize: Some(InvariantPolicy::DropEmpty),
+            ..ParseOptions::lenient()
+        };
+        for options in [ParseOptions::strict(), ParseOptions::lenient(), normalized] {
+            assert_same_as_serial::<EmptyExtensions>(data, &options);
+            assert_same_as_serial::<RawExtensions>(data, &options);
+            assert_same_as_serial::<GarminTrackPointExtensions>(data, &options);
+            assert_same_as_serial::<DetectedExtensions>(data, &options);
+        }
+    }
+
+    /// segments gives a document of `count` track segments of a few points,
+    /// with markup that looks like segments in comments, character data
+    /// and attributes, and an empty segment.
+    fn segments(count: usize, broken: Option<usize>) -> String {
+        let mut gpx = String::from(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- <trkseg> in a comment -->
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1" xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+  <wpt lat="1" lon="2"><name><![CDATA[</trkseg>]]></name></wpt>
+  <trk><name>Ride</name><link href="https://example.com/?a=1&amp;b=>"><text>a > b</text></link>
+    <trkseg/>
+"#,
+        );
+        for segment in 0..count {
+            gpx.push_str("    <trkseg>\n");
+            for point in 0..3 {
+                let lat = if broken == Some(segment) { String::from("north") } else { format!("47.{}{}", segment, point) };
+                gpx.push_str(&format!(
+                    r#"      <trkpt lat="{}" lon="8.{}"><ele>{}</ele><time>2020-01-01T00:{:02}:{:02}Z</time><desc><![CDATA[<trkseg>]]></desc><extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>{}</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions></trkpt>
+"#,
+                    lat,
+                    point,
+                    segment,
+                    segment % 60,
+                    point,
+                    100 + point
+                ));
+            }
+            gpx.push_str("    </trkseg>\n");
+        }
+        gpx.push_str("  </trk>\n  <trk><trkseg><trkpt lat=\"3\" lon=\"4\"/></trkseg></trk>\n</gpx>\n<!-- after -->\n");
+        gpx
+    }
+
+    #[test]
+    fn read_parallel_as_serial() {
+        let data = segments(20, None);
+        let split = Segments::find(data.as_bytes()).unwrap();
+        assert_eq!(split.segments.len(), 22);
+        // It is read split, not falling back to reading serially.
+        for options in [ParseOptions::strict(), ParseOptions::lenient()] {
+            assert!(split.read::<RawExtensions>(data.as_bytes(), &options).is_some());
+        }
+        assert_same_as_serial_with_all(data.as_bytes());
+        let gpx = read_parallel::<GarminTrackPointExtensions>(data.as_bytes(), ParseOptions::strict()).unwrap();
+        assert_eq!(gpx.tracks[0].segments.len(), 21);
+        assert_eq!(gpx.tracks[0].segments[20].points[2].extensions.heart_rate, Some(102));
+
+        // The first error of the document wins, whichever part it is in.
+        let broken = segments(20, Some(7)).replace("</gpx>", "<wpt/></gpx>");
+        assert_same_as_serial_with_all(broken.as_bytes());
+        let broken = segments(20, Some(7)).replace("<name>Ride</name>", "<name>Ride</nam>");
+        assert_same_as_serial_with_all(broken.as_bytes());
+
+        // Declared in another encoding, the bytes of UTF-8 read as that one.
+        let latin1 = segments(2, None)
+            .replace("encoding=\"UTF-8\"", "encoding=\"ISO-8859-1\"")
+            .replace("</time>", "</time><name>caf\u{e9}</name>");
+        assert!(Segments::find(latin1.as_bytes()).is_none());
+        assert_same_as_serial_with_all(latin1.as_bytes());
+        let gpx = read_parallel::<EmptyExtensions>(latin1.as_bytes(), ParseOptions::strict()).unwrap();
+        assert_eq!(gpx.tracks[0].segments[1].points[0].name.as_deref(), Some("caf\u{c3}\u{a9}"));
+
+        // Every segment is under the limit on points, the document is not.
+        let limits = ParseLimits {
+            max_points: Some(60),
+            ..Default::default()
+        };
+        let result = read_parallel::<EmptyExtensions>(data.as_bytes(), ParseOptions::strict().with_limits(limits));
+        assert!(matches!(result, Err(GpxError::LimitExceeded("points"))));
+    }
+
+    #[test]
+    fn read_parallel_fixtures_as_serial() {
+        for entry in fs::read_dir("tests/fixtures").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(false, |extension| extension == "gpx") {
+                assert_same_as_serial_with_all(&fs::read(&path).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn split_only_what_can_be() {
+        let dtd = format!("<!DOCTYPE gpx [<!ENTITY e \"x\">]>{}", segments(2, None));
+        assert!(Segments::find(dtd.as_bytes()).is_none());
+        assert!(Segments::find(b"<gpx><trk><trkseg></trk></gpx>").is_none());
+        assert!(Segments::find(b"<gpx version=\"1.1\"><trk><trkseg>\xff</trkseg></trk></gpx>").is_none());
+        let other_roots = b"<gpx/><gpx><trk><trkseg/></trk></gpx>";
+        assert_eq!(Segments::find(other_roots).unwrap().segments.len(), 0);
+    }
+}
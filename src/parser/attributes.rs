@@ -0,0 +1,110 @@
+//! attributes gives the attributes of a starting tag by name.
+
+use xml::attribute::OwnedAttribute;
+
+use crate::errors::{GpxError, GpxResult};
+use crate::parser::number;
+
+/// The attributes of a starting tag, as [`verify_starting_tag`](super::verify_starting_tag)
+/// gives them, looked up by local name without copying their values.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Attributes {
+    element: &'static str,
+    attributes: Vec<OwnedAttribute>,
+}
+
+impl Attributes {
+    pub(crate) fn new(element: &'static str, attributes: Vec<OwnedAttribute>) -> Attributes {
+        Attributes { element, attributes }
+    }
+
+    /// The value of the attribute named `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|attr| attr.name.local_name == name)
+            .map(|attr| attr.value.as_str())
+    }
+
+    /// The values of the attributes named `names`, found in one pass over
+    /// the attributes.
+    pub fn get_all<const N: usize>(&self, names: [&str; N]) -> [Option<&str>; N] {
+        let mut values = [None; N];
+        for attr in &self.attributes {
+            if let Some(index) = names.iter().position(|name| attr.name.local_name == *name) {
+                values[index].get_or_insert(attr.value.as_str());
+            }
+        }
+        values
+    }
+
+    /// The value of the attribute named `name`, which the element requires.
+    pub fn require(&self, name: &'static str) -> GpxResult<&str> {
+        self.get(name)
+            .ok_or(GpxError::InvalidElementLacksAttribute(name, self.element))
+    }
+
+    /// The value of the attribute named `name`, which the element requires,
+    /// parsed as a number.
+    pub fn get_f64(&self, name: &'static str) -> GpxResult<f64> {
+        number::parse(self.require(name)?, name)
+    }
+
+    /// Takes the value of the attribute named `name` out, if there is one.
+    pub fn take(&mut self, name: &str) -> Option<String> {
+        self.attributes
+            .iter_mut()
+            .find(|attr| attr.name.local_name == name)
+            .map(|attr| std::mem::take(&mut attr.value))
+    }
+
+    /// The attributes as the XML parser gave them.
+    pub fn into_inner(self) -> Vec<OwnedAttribute> {
+        self.attributes
+    }
+}
+
+impl AsRef<[OwnedAttribute]> for Attributes {
+    fn as_ref(&self) -> &[OwnedAttribute] {
+        &self.attributes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xml::attribute::OwnedAttribute;
+    use xml::name::OwnedName;
+
+    use crate::errors::GpxError;
+
+    use super::Attributes;
+
+    fn attributes(pairs: &[(&str, &str)]) -> Attributes {
+        let attributes = pairs
+            .iter()
+            .map(|(name, value)| OwnedAttribute::new(OwnedName::local(*name), *value))
+            .collect();
+        Attributes::new("bounds", attributes)
+    }
+
+    #[test]
+    fn get_by_local_name() {
+        let mut attributes = attributes(&[("minlat", "1.5"), ("maxlat", "x"), ("minlat", "2")]);
+        assert_eq!(attributes.get("minlat"), Some("1.5"));
+        assert_eq!(attributes.get("minlon"), None);
+        assert_eq!(
+            attributes.get_all(["maxlat", "minlon", "minlat"]),
+            [Some("x"), None, Some("1.5")]
+        );
+
+        assert_eq!(attributes.get_f64("minlat").unwrap(), 1.5);
+        assert!(matches!(attributes.get_f64("maxlat"), Err(GpxError::NotANumber(value, "maxlat")) if value == "x"));
+        assert!(matches!(
+            attributes.get_f64("minlon"),
+            Err(GpxError::InvalidElementLacksAttribute("minlon", "bounds"))
+        ));
+
+        assert_eq!(attributes.take("maxlat"), Some(String::from("x")));
+        assert_eq!(attributes.get("maxlat"), Some(""));
+    }
+}
@@ -4,36 +4,24 @@ use geo_types::{Coord, Rect};
 use xml::reader::XmlEvent;
 
 use crate::errors::{GpxError, GpxResult};
-use crate::parser::{Context, verify_starting_tag};
+use crate::parser::{number, Context, verify_starting_tag};
 use crate::parser::extensions::WaypointExtensions;
 
 /// consume consumes a bounds element until it ends.
 pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<Rect<f64>> {
     let attributes = verify_starting_tag(context, "bounds")?;
     // get required bounds
-    let minlat = attributes
-        .iter()
-        .find(|attr| attr.name.local_name == "minlat")
-        .ok_or(GpxError::InvalidElementLacksAttribute("minlat", "bounds"))?;
-    let maxlat = attributes
-        .iter()
-        .find(|attr| attr.name.local_name == "maxlat")
-        .ok_or(GpxError::InvalidElementLacksAttribute("maxlat", "bounds"))?;
-
-    let minlat: f64 = minlat.value.parse()?;
-    let maxlat: f64 = maxlat.value.parse()?;
-
-    let minlon = attributes
-        .iter()
-        .find(|attr| attr.name.local_name == "minlon")
-        .ok_or(GpxError::InvalidElementLacksAttribute("minlon", "bounds"))?;
-    let maxlon = attributes
-        .iter()
-        .find(|attr| attr.name.local_name == "maxlon")
-        .ok_or(GpxError::InvalidElementLacksAttribute("maxlon", "bounds"))?;
-
-    let minlon: f64 = minlon.value.parse()?;
-    let maxlon: f64 = maxlon.value.parse()?;
+    let minlat = attributes.require("minlat")?;
+    let maxlat = attributes.require("maxlat")?;
+
+    let minlat: f64 = number::parse(minlat, "minlat")?;
+    let maxlat: f64 = number::parse(maxlat, "maxlat")?;
+
+    let minlon = attributes.require("minlon")?;
+    let maxlon = attributes.require("maxlon")?;
+
+    let minlon: f64 = number::parse(minlon, "minlon")?;
+    let maxlon: f64 = number::parse(maxlon, "maxlon")?;
 
     // Verify bounding box first, since Rect::new will panic if these are wrong.
     if minlon > maxlon {
@@ -4,38 +4,23 @@ use std::io::Read;
 
 use xml::reader::XmlEvent;
 
-use crate::errors::{GpxError, GpxResult};
+use crate::errors::{GpxError, GpxResult, GpxWarning};
 use crate::GpxCopyright;
-use crate::parser::{Context, string, verify_starting_tag};
+use crate::parser::{Context, number, string, verify_starting_tag};
 use crate::parser::extensions::WaypointExtensions;
 
 /// consume consumes a GPX copyright from the `reader` until it ends.
 /// When it returns, the reader will be at the element after the end GPX copyright tag.
 pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<GpxCopyright> {
     let mut copyright: GpxCopyright = Default::default();
-    let attributes = verify_starting_tag(context, "copyright")?;
-    let attr = attributes
-        .into_iter()
-        .find(|attr| attr.name.local_name == "author");
-
-    copyright.author = attr.map(|a| a.value);
-
-    loop {
-        let next_event = {
-            if let Some(next) = context.reader.peek() {
-                match next {
-                    Ok(n) => n,
-                    Err(_) => return Err(GpxError::EventParsingError("copyright")),
-                }
-            } else {
-                break;
-            }
-        };
+    let mut attributes = verify_starting_tag(context, "copyright")?;
+    copyright.author = attributes.take("author");
 
+    while let Some(next_event) = context.peek_ok("copyright")? {
         match next_event {
             XmlEvent::StartElement { ref name, .. } => match name.local_name.as_ref() {
                 "license" => copyright.license = Some(string::consume(context, "license", false)?),
-                "year" => copyright.year = string::consume(context, "year", false)?.parse().ok(),
+                "year" => copyright.year = year(context)?,
                 child => {
                     return Err(GpxError::InvalidChildElement(
                         String::from(child),
@@ -62,6 +47,19 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
     Err(GpxError::MissingClosingTag("copyright"))
 }
 
+/// year consumes the year of a copyright. In lenient mode, one that is not a
+/// number is left out with a warning.
+fn year<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<Option<i32>> {
+    match number::consume(context, "year") {
+        Ok(year) => Ok(Some(year)),
+        Err(GpxError::NotANumber(value, element)) if context.options.lenient => {
+            context.warn(GpxWarning::InvalidNumber { element, value });
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::GpxVersion;
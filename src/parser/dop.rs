@@ -0,0 +1,97 @@
+//! dop handles parsing of GPX-spec dilutions of precision, as written by
+//! exporters that format numbers for their locale too.
+
+use std::io::Read;
+
+use crate::errors::{GpxError, GpxResult, GpxWarning};
+use crate::parser::extensions::WaypointExtensions;
+use crate::parser::{string, Context};
+
+/// consume consumes a dilution of precision, such as `<hdop>`.
+///
+/// Integers such as `5` and decimals without a fraction such as `5.` are
+/// numbers like any other. In lenient mode, a decimal comma such as `5,0` is
+/// read as a point with a warning. Anything else is an error.
+pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>, tagname: &'static str) -> GpxResult<f64> {
+    let content = string::consume(context, tagname, false)?;
+    let text = content.trim();
+    if let Ok(value) = text.parse() {
+        return Ok(value);
+    }
+    if context.options.lenient && text.matches(',').count() == 1 && !text.contains('.') {
+        if let Ok(replacement) = text.replace(',', ".").parse() {
+            context.warn(GpxWarning::DecimalComma {
+                element: tagname,
+                value: content,
+                replacement,
+            });
+            return Ok(replacement);
+        }
+    }
+    Err(GpxError::NotANumber(content, tagname))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::errors::{GpxError, GpxResult, GpxWarning};
+    use crate::parser::create_context_with_options;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{GpxVersion, ParseOptions};
+
+    use super::consume;
+
+    /// Consumes `<hdop>value</hdop>`, giving the warnings too.
+    fn hdop(value: &str, options: ParseOptions) -> (GpxResult<f64>, Vec<GpxWarning>) {
+        let xml = format!("<hdop>{}</hdop>", value);
+        let mut context = create_context_with_options::<_, EmptyExtensions>(
+            BufReader::new(xml.as_bytes()),
+            GpxVersion::Gpx11,
+            options,
+        );
+        let result = consume(&mut context, "hdop");
+        let warnings = context.warnings().iter().map(|warning| warning.warning.clone()).collect();
+        (result, warnings)
+    }
+
+    #[test]
+    fn consume_numbers() {
+        for options in [ParseOptions::strict(), ParseOptions::lenient()] {
+            for (value, expected) in [("5", 5.0), ("5.", 5.0), ("5.0", 5.0), (" 728. ", 728.0), ("0.9", 0.9)] {
+                let (result, warnings) = hdop(value, options.clone());
+                assert_eq!(result.unwrap(), expected, "{}", value);
+                assert!(warnings.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn consume_decimal_comma() {
+        let (result, _) = hdop("5,0", ParseOptions::strict());
+        assert!(matches!(result, Err(GpxError::NotANumber(value, "hdop")) if value == "5,0"));
+
+        let (result, warnings) = hdop("5,0", ParseOptions::lenient());
+        assert_eq!(result.unwrap(), 5.0);
+        assert_eq!(
+            warnings,
+            [GpxWarning::DecimalComma {
+                element: "hdop",
+                value: String::from("5,0"),
+                replacement: 5.0
+            }]
+        );
+        assert_eq!(hdop("1,25", ParseOptions::lenient()).0.unwrap(), 1.25);
+    }
+
+    #[test]
+    fn consume_not_a_number() {
+        for value in ["good", "1,000.5", "1,2,3", "5.0.1"] {
+            let (result, warnings) = hdop(value, ParseOptions::lenient());
+            assert!(matches!(result, Err(GpxError::NotANumber(_, "hdop"))), "{}", value);
+            assert!(warnings.is_empty());
+        }
+        let error = hdop("good", ParseOptions::strict()).0.unwrap_err();
+        assert_eq!(error.to_string(), "`hdop` holds `good`, which is not a number");
+    }
+}
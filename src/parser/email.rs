@@ -14,17 +14,10 @@ use crate::parser::extensions::WaypointExtensions;
 pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<String> {
     let attributes = verify_starting_tag(context, "email")?;
     // get required id and domain attributes
-    let id = attributes
-        .iter()
-        .find(|attr| attr.name.local_name == "id")
-        .ok_or(GpxError::InvalidElementLacksAttribute("id", "email"))?;
+    let id = attributes.require("id")?;
+    let domain = attributes.require("domain")?;
 
-    let domain = attributes
-        .iter()
-        .find(|attr| attr.name.local_name == "domain")
-        .ok_or(GpxError::InvalidElementLacksAttribute("domain", "email"))?;
-
-    let email = format!("{id}@{domain}", id = &id.value, domain = &domain.value);
+    let email = format!("{id}@{domain}", id = id, domain = domain);
 
     for event in &mut context.reader {
         match event? {
@@ -0,0 +1,227 @@
+//! events gives the XML events the parser reads, from xml-rs, or from
+//! quick-xml when [`ParseOptions::xml_parser`] says so.
+//!
+//! Both give the events of xml-rs, which consumers of extensions match on,
+//! so that parsing reads the same whichever parses the XML.
+
+use std::io::Read;
+use std::rc::Rc;
+
+use xml::reader::{Result, XmlEvent};
+use xml::{EventReader, ParserConfig};
+
+#[cfg(feature = "quick-xml")]
+use crate::parser::quick::QuickXmlEvents;
+use crate::parser::tracking::{Tracker, TrackingReader};
+use crate::reader::{ParseOptions, XmlParser};
+
+/// The XML events of a document, as [`Context::reader`](crate::parser::Context::reader)
+/// gives them.
+pub struct Events<R: Read>(Source<R>);
+
+// There is one per document, which boxing would only make slower to reach.
+#[allow(clippy::large_enum_variant)]
+enum Source<R: Read> {
+    XmlRs(xml::reader::Events<R>),
+    #[cfg(feature = "quick-xml")]
+    QuickXml(QuickXmlEvents<R>),
+}
+
+impl<R: Read> Events<TrackingReader<R>> {
+    /// parse gives the events of `reader` with the XML parser of `options`.
+    pub(crate) fn parse(reader: R, options: &ParseOptions, tracker: Option<Rc<Tracker>>) -> Events<TrackingReader<R>> {
+        match options.xml_parser {
+            XmlParser::XmlRs => Events::xml_rs(reader, options, tracker),
+            #[cfg(feature = "quick-xml")]
+            XmlParser::QuickXml => Events::quick_xml(reader, options, tracker),
+        }
+    }
+
+    /// xml_rs gives the events of `reader` parsed by xml-rs.
+    pub(crate) fn xml_rs(reader: R, options: &ParseOptions, tracker: Option<Rc<Tracker>>) -> Events<TrackingReader<R>> {
+        let parser_config = ParserConfig {
+            whitespace_to_characters: true, //convert Whitespace event to Characters
+            cdata_to_characters: true,      //convert CData event to Characters
+            ignore_comments: !options.keep_comments,
+            ..ParserConfig::new()
+        }
+        .max_entity_expansion_depth(options.limits.max_entity_expansion_depth)
+        .max_entity_expansion_length(options.limits.max_entity_expansion_length);
+        let reader = TrackingReader::new(reader, tracker);
+        EventReader::new_with_config(reader, parser_config).into_iter().into()
+    }
+
+    /// quick_xml gives the events of `reader` parsed by quick-xml, which
+    /// follows the position for its syntax errors with or without `tracker`.
+    #[cfg(feature = "quick-xml")]
+    pub(crate) fn quick_xml(reader: R, options: &ParseOptions, tracker: Option<Rc<Tracker>>) -> Events<TrackingReader<R>> {
+        let tracker = tracker.unwrap_or_default();
+        let reader = TrackingReader::buffered(reader, tracker.clone());
+        Events(Source::QuickXml(QuickXmlEvents::new(reader, options, tracker)))
+    }
+}
+
+impl<R: Read> From<xml::reader::Events<R>> for Events<R> {
+    fn from(events: xml::reader::Events<R>) -> Events<R> {
+        Events(Source::XmlRs(events))
+    }
+}
+
+impl<R: Read> Iterator for Events<R> {
+    type Item = Result<XmlEvent>;
+
+    fn next(&mut self) -> Option<Result<XmlEvent>> {
+        match &mut self.0 {
+            Source::XmlRs(events) => events.next(),
+            #[cfg(feature = "quick-xml")]
+            Source::QuickXml(events) => events.next(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "quick-xml"))]
+mod tests {
+    use std::fs;
+    use std::io::Read;
+    use std::rc::Rc;
+
+    use crate::parser::tracking::{Tracker, TrackingReader};
+    use crate::reader::ParseOptions;
+
+    use super::Events;
+
+    type Parse<'a> = fn(&'a [u8], &ParseOptions, Option<Rc<Tracker>>) -> Events<TrackingReader<&'a [u8]>>;
+
+    /// events gives the events of `data` read with `parse`, and the message
+    /// of the error ending them if any. Its position is left out, as the two
+    /// parsers fail at different places of the same markup.
+    fn events<'a>(data: &'a [u8], options: &ParseOptions, parse: Parse<'a>) -> Vec<Result<String, String>> {
+        parse(data, options, Some(Rc::new(Tracker::default())))
+            .map(|event| {
+                event
+                    .map(|event| format!("{:?}", event))
+                    .map_err(|error| error.msg().to_string())
+            })
+            .collect()
+    }
+
+    fn assert_same_events(data: &[u8], options: &ParseOptions) {
+        assert_eq!(
+            events(data, options, Events::quick_xml),
+            events(data, options, Events::xml_rs),
+            "in {}",
+            String::from_utf8_lossy(data)
+        );
+    }
+
+    #[test]
+    fn quick_xml_reads_fixtures_as_xml_rs() {
+        let mut fixtures: Vec<_> = fs::read_dir("tests/fixtures").unwrap().map(|entry| entry.unwrap().path()).collect();
+        fixtures.sort();
+        for path in fixtures {
+            if path.is_dir() || path.extension().unwrap() == "nmea" {
+                continue;
+            }
+            let mut data = Vec::new();
+            fs::File::open(&path).unwrap().read_to_end(&mut data).unwrap();
+            for keep_comments in [false, true] {
+                let options = ParseOptions {
+                    keep_comments,
+                    ..Default::default()
+                };
+                assert_same_events(&data, &options);
+            }
+        }
+    }
+
+    #[test]
+    fn quick_xml_fails_as_xml_rs() {
+        let documents = [
+            "<a>x\u{1}y</a>",
+            "<a b=\"\u{1}\"/>",
+            "<a>&#0;</a>",
+            "<a>&#xD800;</a>",
+            "<a>&#zz;</a>",
+            "<a>&;</a>",
+            "<a>&foo;</a>",
+            "<a><!-- \u{1} --></a>",
+            "<a></b>",
+            "<a>",
+            "",
+            "  ",
+            "<a/>x",
+            "<a b='1' b='2'/>",
+            "<p:a/>",
+            "<a p:b='1'/>",
+            "<a xmlns:xmlns='u'/>",
+            "<a xmlns:p=''/>",
+            "<?xml version='1.0'?><?xml version='1.0'?><a/>",
+            "<?xml version='2.0'?><a/>",
+            "<?xml version='1.0' encoding='windows-1252'?><a/>",
+            "<?xml version='1.0' encoding='UTF-16'?><a/>",
+            "<?xml version='1.0' encoding='US-ASCII'?><a>\u{e9}</a>",
+        ];
+        for document in documents {
+            assert_same_events(document.as_bytes(), &Default::default());
+        }
+    }
+
+    #[test]
+    fn quick_xml_reads_markup_as_xml_rs() {
+        let documents = [
+            "<?xml version='1.1' encoding='utf-8' standalone='yes'?>\n<a/>",
+            "<a>x\r\ny<!--c-->z<![CDATA[<w>]]>&amp;&lt;&#65;&#x42;</a>",
+            "<a b='\tx\n' c='&quot;&#x41;'/>",
+            "<a xmlns='u' xmlns:p='v'><p:b p:c='1' d='2'/><b xmlns=''/></a>",
+            "<a><?pi data here?><?empty?></a>",
+            "<!DOCTYPE a [<!ENTITY e 'x&#13;y'><!ENTITY f '&e;&e;'>]><a b='&e;'>&f;</a>",
+            "<!DOCTYPE a SYSTEM 'a.dtd'><a/>",
+            "\n<a/>\n<!-- after -->\n",
+        ];
+        for document in documents {
+            assert_same_events(document.as_bytes(), &Default::default());
+            let keep_comments = ParseOptions {
+                keep_comments: true,
+                ..Default::default()
+            };
+            assert_same_events(document.as_bytes(), &keep_comments);
+        }
+    }
+
+    /// utf16 encodes `text` in UTF-16, with a byte order mark.
+    fn utf16(text: &str, to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        std::iter::once('\u{FEFF}')
+            .chain(text.chars())
+            .collect::<String>()
+            .encode_utf16()
+            .flat_map(to_bytes)
+            .collect()
+    }
+
+    #[test]
+    fn quick_xml_reads_encodings_as_xml_rs() {
+        let documents = [
+            "<a>Z\u{fc}rich \u{1F5FA}</a>",
+            "<?xml version='1.0'?><a b='\u{e9}'/>",
+            "<?xml version='1.0' encoding='UTF-16'?><a/>",
+            "<?xml version='1.0' encoding='ISO-8859-1'?><a/>",
+        ];
+        for document in documents {
+            assert_same_events(&utf16(document, u16::to_le_bytes), &Default::default());
+            assert_same_events(&utf16(document, u16::to_be_bytes), &Default::default());
+        }
+
+        let latin1 = b"<?xml version='1.0' encoding='latin1'?><a b='\xE9'>Z\xFCrich</a>";
+        assert_same_events(latin1, &Default::default());
+        let ascii = b"<?xml version='1.0' encoding='us-ascii'?><a>Zurich</a>";
+        assert_same_events(ascii, &Default::default());
+
+        let mut odd = utf16("<a/>", u16::to_le_bytes);
+        odd.push(b' ');
+        let lone_surrogate = [&utf16("<a>", u16::to_le_bytes)[..], b"\x00\xD8\x3C\x00/a>"].concat();
+        for data in [odd, lone_surrogate] {
+            assert!(events(&data, &Default::default(), Events::quick_xml).last().unwrap().is_err());
+            assert!(events(&data, &Default::default(), Events::xml_rs).last().unwrap().is_err());
+        }
+    }
+}
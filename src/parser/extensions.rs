@@ -1,23 +1,57 @@
 //! extensions handles parsing of GPX-spec extensions.
 
-// TODO: extensions are not implemented
-
+use std::collections::BTreeMap;
 use std::fmt::Debug;
-use std::io::Read;
+use std::io::{Read, Write};
 
+use xml::escape::{escape_str_attribute, escape_str_pcdata};
+use xml::name::OwnedName;
+use xml::namespace::Namespace;
 use xml::reader::XmlEvent;
+use xml::writer::{self, EventWriter};
 
 use crate::errors::{GpxError, GpxResult};
-use crate::parser::Context;
+use crate::parser::{number, skip_element, string, Context};
+use crate::privacy::{remove_elements, PiiPolicy};
+use crate::reader::ExtensionsMode;
 
 use super::verify_starting_tag;
 
 pub trait WaypointExtensions {
     type ExtensionsValue: Clone + Debug + PartialEq + Default;
+
+    /// Whether `consume` keeps the bytes it reads with
+    /// [`Context::start_recording`], which the readers that give no warnings
+    /// only make possible for extensions types setting this. `false` by
+    /// default.
+    const RECORDS: bool = false;
+
     fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<Self::ExtensionsValue>;
+
+    /// Merges `other`, read from a repeated `<extensions>` element of the
+    /// same point in lenient mode, into `value`, as some merged files repeat
+    /// the element. By default `value` is kept as it is, leaving `other` out;
+    /// the repetition is warned of with
+    /// [`GpxWarning::DuplicateElement`](crate::errors::GpxWarning::DuplicateElement)
+    /// either way.
+    fn merge(_value: &mut Self::ExtensionsValue, _other: Self::ExtensionsValue) {}
+
+    /// Removes personally identifying fields, such as device serial numbers,
+    /// from `value` according to `policy`, giving the number of removed
+    /// fields. Used by [`Gpx::scrub_pii`](crate::Gpx::scrub_pii); removes
+    /// nothing by default.
+    fn scrub_pii(_value: &mut Self::ExtensionsValue, _policy: &PiiPolicy) -> usize {
+        0
+    }
+
+    /// Writes `value` as the `<extensions>` element of a waypoint. Writes
+    /// nothing by default.
+    fn write<W: Write>(_value: &Self::ExtensionsValue, _writer: &mut EventWriter<W>) -> GpxResult<()> {
+        Ok(())
+    }
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct EmptyExtensions;
 
 impl WaypointExtensions for EmptyExtensions {
@@ -26,40 +60,798 @@ impl WaypointExtensions for EmptyExtensions {
     /// consume consumes a single string as tag content.
     fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<Self::ExtensionsValue> {
         verify_starting_tag(context, "extensions")?;
+        skip_element(context, "extensions")
+    }
+}
+
+/// The content of an `<extensions>` element, kept as written.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RawXml {
+    /// The XML between the `<extensions>` tags, byte for byte as it was read:
+    /// entity references, CDATA sections, attribute quoting and whitespace,
+    /// including line endings, are left untouched.
+    pub xml: String,
+
+    /// The namespaces, by prefix, the XML uses but does not declare itself.
+    pub namespaces: BTreeMap<String, String>,
+
+    /// Whether the XML was cut short at the cap of
+    /// [`ExtensionsMode::RawCapped`](crate::ExtensionsMode::RawCapped). It is
+    /// then not well-formed, and is not written back.
+    pub truncated: bool,
+}
+
+/// RawExtensions keeps the extensions of waypoints as raw XML, and writes them
+/// back unchanged.
+///
+/// The XML is captured from the bytes read, except for contexts created
+/// with [`Context::new`] or input that is not UTF-8. Then it is rebuilt from
+/// the parsed events, which gives the same XML document but not the same
+/// bytes: entities and CDATA sections come back escaped and attributes
+/// double-quoted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RawExtensions;
 
-        let mut depth = 1;
-        for event in &mut context.reader {
-            match event? {
-                XmlEvent::StartElement { name, .. } => {
-                    // I think its bad to hardcode the check on name == "extensions", because it is not a generic approach
-                    // and treats inner tags that are called "extensions" differently from any other inner tags, like "a", "foo", "bar"
-                    // It is correct, but feels wrong, maybe only a personal feeling
-                    if name.local_name == "extensions" {
-                        depth += 1;
+impl WaypointExtensions for RawExtensions {
+    type ExtensionsValue = RawXml;
+    const RECORDS: bool = true;
+
+    fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<Self::ExtensionsValue> {
+        let outer = match context.reader.peek() {
+            Some(Ok(XmlEvent::StartElement { namespace, .. })) => namespace.clone(),
+            _ => Namespace::empty(),
+        };
+        let mut scopes = vec![outer.clone()];
+        verify_starting_tag(context, "extensions")?;
+        let recording = context.start_recording();
+
+        let cap = match context.options.extensions {
+            ExtensionsMode::RawCapped(cap) => Some(cap),
+            _ => None,
+        };
+
+        // Rebuild the XML from the events too, in case the bytes are unusable.
+        let mut rebuilt = String::new();
+        let mut prefixes = Vec::new();
+        let mut truncated = false;
+        let recorded = loop {
+            context.check_deadline()?;
+            let event = match context.reader.next() {
+                Some(event) => event?,
+                None => return Err(GpxError::MissingClosingTag("extensions")),
+            };
+            match event {
+                XmlEvent::StartElement {
+                    name,
+                    attributes,
+                    namespace,
+                } => {
+                    rebuilt.push('<');
+                    push_name(&mut rebuilt, &name, &mut prefixes);
+                    for (prefix, uri) in &namespace {
+                        if scopes.last().and_then(|scope| scope.get(prefix)) != Some(uri) {
+                            let attribute = match prefix {
+                                "" => String::from("xmlns"),
+                                prefix => format!("xmlns:{}", prefix),
+                            };
+                            rebuilt.push_str(&format!(" {}=\"{}\"", attribute, escape_str_attribute(uri)));
+                        }
+                    }
+                    for attribute in &attributes {
+                        rebuilt.push(' ');
+                        push_name(&mut rebuilt, &attribute.name, &mut prefixes);
+                        rebuilt.push_str(&format!("=\"{}\"", escape_str_attribute(&attribute.value)));
                     }
+                    rebuilt.push('>');
+                    scopes.push(namespace);
+                    context.check_depth(scopes.len() - 1)?;
                 }
                 XmlEvent::EndElement { name } => {
-                    if name.local_name == "extensions" {
-                        // pop one
-                        depth -= 1;
-                        if depth == 0 {
-                            return Ok(());
-                        }
+                    scopes.pop();
+                    if scopes.is_empty() {
+                        break context.stop_recording();
                     }
+                    rebuilt.push_str("</");
+                    push_name(&mut rebuilt, &name, &mut prefixes);
+                    rebuilt.push('>');
                 }
+                XmlEvent::Characters(chars)
+                | XmlEvent::CData(chars)
+                | XmlEvent::Whitespace(chars) => rebuilt.push_str(&escape_str_pcdata(&chars)),
                 _ => {}
             }
+            if let Some(cap) = cap {
+                let kept = match context.recorded().filter(|_| recording) {
+                    Some(recorded) => recorded,
+                    None => rebuilt.len(),
+                };
+                if kept > cap {
+                    // Nothing more is kept, so the rest is skipped unrecorded.
+                    truncated = true;
+                    let recorded = context.stop_recording();
+                    skip_subtree(context, scopes.len() - 1)?;
+                    break recorded;
+                }
+            }
+        };
+
+        let raw = recorded
+            .filter(|_| recording)
+            .and_then(|mut bytes| {
+                // Unless cut short, the recording ends with the closing tag of
+                // the extensions.
+                if !truncated {
+                    let end = bytes.iter().rposition(|&b| b == b'<').unwrap_or(0);
+                    bytes.truncate(end);
+                }
+                String::from_utf8(bytes).ok()
+            });
+
+        let namespaces = prefixes
+            .into_iter()
+            .filter_map(|prefix| {
+                let uri = outer.get(&prefix)?;
+                Some((prefix, uri.to_owned()))
+            })
+            .collect();
+        let mut xml = raw.unwrap_or(rebuilt);
+        if let Some(cap) = cap.filter(|&cap| xml.len() > cap) {
+            let end = (0..=cap).rev().find(|&end| xml.is_char_boundary(end)).unwrap_or(0);
+            xml.truncate(end);
+        }
+        Ok(RawXml {
+            xml,
+            namespaces,
+            truncated,
+        })
+    }
+
+    /// Appends the XML of `other` to that of `value`, so that they are
+    /// written back as one `<extensions>` element.
+    fn merge(value: &mut Self::ExtensionsValue, other: Self::ExtensionsValue) {
+        value.xml.push_str(&other.xml);
+        for (prefix, uri) in other.namespaces {
+            value.namespaces.entry(prefix).or_insert(uri);
+        }
+        value.truncated |= other.truncated;
+    }
+
+    /// Removes the elements named in
+    /// [`PiiPolicy::serial_elements`](crate::PiiPolicy::serial_elements),
+    /// with their content, from the XML.
+    fn scrub_pii(value: &mut Self::ExtensionsValue, policy: &PiiPolicy) -> usize {
+        let (xml, removed) = remove_elements(&value.xml, &policy.serial_elements);
+        value.xml = xml;
+        removed
+    }
+
+    fn write<W: Write>(value: &Self::ExtensionsValue, writer: &mut EventWriter<W>) -> GpxResult<()> {
+        if value.truncated {
+            return Ok(());
+        }
+        let mut start = writer::XmlEvent::start_element("extensions");
+        for (prefix, uri) in &value.namespaces {
+            start = start.ns(prefix.as_str(), uri.as_str());
+        }
+        writer.write(start)?;
+        if !value.xml.is_empty() {
+            // Finish the starting tag, then write the XML as is.
+            writer.write(writer::XmlEvent::characters(""))?;
+            writer
+                .inner_mut()
+                .write_all(value.xml.as_bytes())
+                .map_err(writer::Error::from)?;
+        }
+        writer.write(writer::XmlEvent::end_element())?;
+        Ok(())
+    }
+}
+
+/// skip_subtree consumes and discards everything up to and including the end
+/// of the `<extensions>` element, from inside elements nested `depth` deep in
+/// it.
+fn skip_subtree<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>, depth: usize) -> GpxResult<()> {
+    for _ in 0..=depth {
+        skip_element(context, "extensions")?;
+    }
+    Ok(())
+}
+
+/// The namespace of Garmin's GPX extensions.
+pub(crate) const GARMIN_NAMESPACE: &str = "http://www.garmin.com/xmlschemas/GpxExtensions/v3";
+
+/// The fields of a Garmin `WaypointExtension`, as written by Garmin devices
+/// and marine chartplotters.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GarminWaypoint {
+    /// Distance in meters at which the device alarms when approaching.
+    pub proximity: Option<f64>,
+
+    /// Water depth in meters.
+    pub depth: Option<f64>,
+
+    /// How the waypoint is shown on the map, such as `SymbolAndName`.
+    pub display_mode: Option<String>,
+}
+
+/// GarminExtensions reads the Garmin `gpxx:WaypointExtension` of waypoints,
+/// ignoring other extensions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GarminExtensions;
+
+impl WaypointExtensions for GarminExtensions {
+    type ExtensionsValue = GarminWaypoint;
+
+    fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<Self::ExtensionsValue> {
+        verify_starting_tag(context, "extensions")?;
+        let mut waypoint = GarminWaypoint::default();
+
+        while let Some(next_event) = context.peek_ok("extensions")? {
+            match next_event {
+                XmlEvent::StartElement { ref name, .. } if name.local_name == "WaypointExtension" => {
+                    consume_garmin_waypoint(context, &mut waypoint)?
+                }
+                XmlEvent::StartElement { .. } => {
+                    context.reader.next();
+                    skip_element(context, "extensions")?;
+                }
+                XmlEvent::EndElement { .. } => {
+                    context.reader.next(); //consume the end tag
+                    return Ok(waypoint);
+                }
+                _ => {
+                    context.reader.next(); //consume and ignore this event
+                }
+            }
+        }
+
+        Err(GpxError::MissingClosingTag("extensions"))
+    }
+
+    fn merge(value: &mut Self::ExtensionsValue, other: Self::ExtensionsValue) {
+        merge_garmin_waypoint(value, other)
+    }
+
+    fn write<W: Write>(value: &Self::ExtensionsValue, writer: &mut EventWriter<W>) -> GpxResult<()> {
+        if *value == GarminWaypoint::default() {
+            return Ok(());
+        }
+        writer.write(writer::XmlEvent::start_element("extensions"))?;
+        write_garmin_waypoint(value, writer)?;
+        writer.write(writer::XmlEvent::end_element())?;
+        Ok(())
+    }
+}
+
+/// write_garmin_waypoint writes `value` as a Garmin `WaypointExtension`
+/// element.
+fn write_garmin_waypoint<W: Write>(value: &GarminWaypoint, writer: &mut EventWriter<W>) -> GpxResult<()> {
+    writer.write(writer::XmlEvent::start_element("gpxx:WaypointExtension").ns("gpxx", GARMIN_NAMESPACE))?;
+    let fields = [
+        ("gpxx:Proximity", value.proximity.map(|v| v.to_string())),
+        ("gpxx:Depth", value.depth.map(|v| v.to_string())),
+        ("gpxx:DisplayMode", value.display_mode.clone()),
+    ];
+    for (name, field) in fields.iter() {
+        if let Some(field) = field {
+            writer.write(writer::XmlEvent::start_element(*name))?;
+            writer.write(writer::XmlEvent::characters(field))?;
+            writer.write(writer::XmlEvent::end_element())?;
+        }
+    }
+    writer.write(writer::XmlEvent::end_element())?;
+    Ok(())
+}
+
+/// merge_garmin_waypoint sets the fields of `value` that `other` has, as a
+/// later element of the same point.
+fn merge_garmin_waypoint(value: &mut GarminWaypoint, other: GarminWaypoint) {
+    let GarminWaypoint {
+        proximity,
+        depth,
+        display_mode,
+    } = other;
+    value.proximity = proximity.or(value.proximity);
+    value.depth = depth.or(value.depth);
+    value.display_mode = display_mode.or(value.display_mode.take());
+}
+
+/// consume_garmin_waypoint consumes a Garmin `WaypointExtension` element
+/// into `waypoint`.
+fn consume_garmin_waypoint<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+    waypoint: &mut GarminWaypoint,
+) -> GpxResult<()> {
+    verify_starting_tag(context, "WaypointExtension")?;
+
+    while let Some(next_event) = context.peek_ok("WaypointExtension")? {
+        match next_event {
+            XmlEvent::StartElement { ref name, .. } => match name.local_name.as_ref() {
+                "Proximity" => {
+                    waypoint.proximity = Some(number::consume(context, "Proximity")?)
+                }
+                "Depth" => waypoint.depth = Some(number::consume(context, "Depth")?),
+                "DisplayMode" => {
+                    waypoint.display_mode = Some(string::consume(context, "DisplayMode", false)?)
+                }
+                _ => {
+                    context.reader.next();
+                    skip_element(context, "WaypointExtension")?;
+                }
+            },
+            XmlEvent::EndElement { .. } => {
+                context.reader.next(); //consume the end tag
+                return Ok(());
+            }
+            _ => {
+                context.reader.next(); //consume and ignore this event
+            }
+        }
+    }
+
+    Err(GpxError::MissingClosingTag("WaypointExtension"))
+}
+
+/// The namespace of Garmin's track point extensions.
+pub(crate) const GARMIN_TRACK_POINT_NAMESPACE: &str = "http://www.garmin.com/xmlschemas/TrackPointExtension/v1";
+
+/// The fields of a Garmin `TrackPointExtension`, as written by Garmin fitness
+/// devices and the many apps taking after them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GarminTrackPoint {
+    /// Air temperature in degrees Celsius.
+    pub air_temperature: Option<f64>,
+
+    /// Water temperature in degrees Celsius.
+    pub water_temperature: Option<f64>,
+
+    /// Water depth in meters.
+    pub depth: Option<f64>,
+
+    /// Heart rate in beats per minute.
+    pub heart_rate: Option<u32>,
+
+    /// Cadence in revolutions, or strides, per minute.
+    pub cadence: Option<u32>,
+}
+
+/// GarminTrackPointExtensions reads the Garmin `gpxtpx:TrackPointExtension`
+/// of points, ignoring other extensions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GarminTrackPointExtensions;
+
+impl WaypointExtensions for GarminTrackPointExtensions {
+    type ExtensionsValue = GarminTrackPoint;
+
+    fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<Self::ExtensionsValue> {
+        verify_starting_tag(context, "extensions")?;
+        let mut point = GarminTrackPoint::default();
+
+        while let Some(next_event) = context.peek_ok("extensions")? {
+            match next_event {
+                XmlEvent::StartElement { ref name, .. } if name.local_name == "TrackPointExtension" => {
+                    consume_garmin_track_point(context, &mut point)?
+                }
+                XmlEvent::StartElement { .. } => {
+                    context.reader.next();
+                    skip_element(context, "extensions")?;
+                }
+                XmlEvent::EndElement { .. } => {
+                    context.reader.next(); //consume the end tag
+                    return Ok(point);
+                }
+                _ => {
+                    context.reader.next(); //consume and ignore this event
+                }
+            }
         }
 
         Err(GpxError::MissingClosingTag("extensions"))
     }
+
+    fn merge(value: &mut Self::ExtensionsValue, other: Self::ExtensionsValue) {
+        merge_garmin_track_point(value, other)
+    }
+
+    fn write<W: Write>(value: &Self::ExtensionsValue, writer: &mut EventWriter<W>) -> GpxResult<()> {
+        if *value == GarminTrackPoint::default() {
+            return Ok(());
+        }
+        writer.write(writer::XmlEvent::start_element("extensions"))?;
+        write_garmin_track_point(value, writer)?;
+        writer.write(writer::XmlEvent::end_element())?;
+        Ok(())
+    }
+}
+
+/// write_garmin_track_point writes `value` as a Garmin `TrackPointExtension`
+/// element, of version 1.
+fn write_garmin_track_point<W: Write>(value: &GarminTrackPoint, writer: &mut EventWriter<W>) -> GpxResult<()> {
+    writer.write(
+        writer::XmlEvent::start_element("gpxtpx:TrackPointExtension").ns("gpxtpx", GARMIN_TRACK_POINT_NAMESPACE),
+    )?;
+    let fields = [
+        ("gpxtpx:atemp", value.air_temperature.map(|v| v.to_string())),
+        ("gpxtpx:wtemp", value.water_temperature.map(|v| v.to_string())),
+        ("gpxtpx:depth", value.depth.map(|v| v.to_string())),
+        ("gpxtpx:hr", value.heart_rate.map(|v| v.to_string())),
+        ("gpxtpx:cad", value.cadence.map(|v| v.to_string())),
+    ];
+    for (name, field) in fields.iter() {
+        if let Some(field) = field {
+            writer.write(writer::XmlEvent::start_element(*name))?;
+            writer.write(writer::XmlEvent::characters(field))?;
+            writer.write(writer::XmlEvent::end_element())?;
+        }
+    }
+    writer.write(writer::XmlEvent::end_element())?;
+    Ok(())
+}
+
+/// merge_garmin_track_point sets the fields of `value` that `other` has, as
+/// a later element of the same point.
+fn merge_garmin_track_point(value: &mut GarminTrackPoint, other: GarminTrackPoint) {
+    let GarminTrackPoint {
+        air_temperature,
+        water_temperature,
+        depth,
+        heart_rate,
+        cadence,
+    } = other;
+    value.air_temperature = air_temperature.or(value.air_temperature);
+    value.water_temperature = water_temperature.or(value.water_temperature);
+    value.depth = depth.or(value.depth);
+    value.heart_rate = heart_rate.or(value.heart_rate);
+    value.cadence = cadence.or(value.cadence);
+}
+
+/// consume_garmin_track_point consumes a Garmin `TrackPointExtension` element
+/// into `point`.
+fn consume_garmin_track_point<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+    point: &mut GarminTrackPoint,
+) -> GpxResult<()> {
+    verify_starting_tag(context, "TrackPointExtension")?;
+
+    while let Some(next_event) = context.peek_ok("TrackPointExtension")? {
+        match next_event {
+            XmlEvent::StartElement { ref name, .. } => match name.local_name.as_ref() {
+                "atemp" => point.air_temperature = Some(number::consume(context, "atemp")?),
+                "wtemp" => point.water_temperature = Some(number::consume(context, "wtemp")?),
+                "depth" => point.depth = Some(number::consume(context, "depth")?),
+                "hr" => point.heart_rate = Some(number::consume(context, "hr")?),
+                "cad" => point.cadence = Some(number::consume(context, "cad")?),
+                _ => {
+                    context.reader.next();
+                    skip_element(context, "TrackPointExtension")?;
+                }
+            },
+            XmlEvent::EndElement { .. } => {
+                context.reader.next(); //consume the end tag
+                return Ok(());
+            }
+            _ => {
+                context.reader.next(); //consume and ignore this event
+            }
+        }
+    }
+
+    Err(GpxError::MissingClosingTag("TrackPointExtension"))
+}
+
+/// The namespace of version 2 of Garmin's track point extensions, which
+/// adds speed and course to version 1.
+pub(crate) const GARMIN_TRACK_POINT_V2_NAMESPACE: &str = "http://www.garmin.com/xmlschemas/TrackPointExtension/v2";
+
+/// DetectedExtensions reads the extensions of a point with the built-in
+/// consumer of their schema, telling the schemas apart by namespace, and
+/// ignores extensions of other schemas. See
+/// [`read_with_detected_extensions`](crate::extensions::read_with_detected_extensions).
+///
+/// It is its own value: the extensions of a point are the variant of the
+/// schemas they were found to have.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum DetectedExtensions {
+    /// No extension of a schema with a built-in consumer.
+    #[default]
+    None,
+    /// A Garmin `gpxx:WaypointExtension`, read as by [`GarminExtensions`].
+    GarminWaypoint(GarminWaypoint),
+    /// A Garmin `gpxtpx:TrackPointExtension`, of version 1 or 2, read as by
+    /// [`GarminTrackPointExtensions`]. It is written back as version 1.
+    GarminTrackPoint(GarminTrackPoint),
+    /// Both Garmin extensions, as some devices write them together.
+    Composite(GarminWaypoint, GarminTrackPoint),
+}
+
+impl DetectedExtensions {
+    /// The variant holding the extensions of each schema found.
+    fn from_parts(waypoint: Option<GarminWaypoint>, point: Option<GarminTrackPoint>) -> DetectedExtensions {
+        match (waypoint, point) {
+            (None, None) => DetectedExtensions::None,
+            (Some(waypoint), None) => DetectedExtensions::GarminWaypoint(waypoint),
+            (None, Some(point)) => DetectedExtensions::GarminTrackPoint(point),
+            (Some(waypoint), Some(point)) => DetectedExtensions::Composite(waypoint, point),
+        }
+    }
+
+    /// The extensions of each schema, the other way around from
+    /// [`DetectedExtensions::from_parts`].
+    fn into_parts(self) -> (Option<GarminWaypoint>, Option<GarminTrackPoint>) {
+        match self {
+            DetectedExtensions::None => (None, None),
+            DetectedExtensions::GarminWaypoint(waypoint) => (Some(waypoint), None),
+            DetectedExtensions::GarminTrackPoint(point) => (None, Some(point)),
+            DetectedExtensions::Composite(waypoint, point) => (Some(waypoint), Some(point)),
+        }
+    }
+}
+
+impl WaypointExtensions for DetectedExtensions {
+    type ExtensionsValue = DetectedExtensions;
+
+    fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<Self::ExtensionsValue> {
+        verify_starting_tag(context, "extensions")?;
+        let mut waypoint = None;
+        let mut point = None;
+
+        while let Some(next_event) = context.peek_ok("extensions")? {
+            match next_event {
+                XmlEvent::StartElement { ref name, .. } => {
+                    match (name.namespace.as_deref(), name.local_name.as_ref()) {
+                        (Some(GARMIN_NAMESPACE), "WaypointExtension") => {
+                            consume_garmin_waypoint(context, waypoint.get_or_insert_with(GarminWaypoint::default))?
+                        }
+                        (
+                            Some(GARMIN_TRACK_POINT_NAMESPACE | GARMIN_TRACK_POINT_V2_NAMESPACE),
+                            "TrackPointExtension",
+                        ) => consume_garmin_track_point(context, point.get_or_insert_with(GarminTrackPoint::default))?,
+                        _ => {
+                            context.reader.next();
+                            skip_element(context, "extensions")?;
+                        }
+                    }
+                }
+                XmlEvent::EndElement { .. } => {
+                    context.reader.next(); //consume the end tag
+                    return Ok(DetectedExtensions::from_parts(waypoint, point));
+                }
+                _ => {
+                    context.reader.next(); //consume and ignore this event
+                }
+            }
+        }
+
+        Err(GpxError::MissingClosingTag("extensions"))
+    }
+
+    fn merge(value: &mut Self::ExtensionsValue, other: Self::ExtensionsValue) {
+        let (mut waypoint, mut point) = std::mem::take(value).into_parts();
+        let (other_waypoint, other_point) = other.into_parts();
+        if let Some(other) = other_waypoint {
+            merge_garmin_waypoint(waypoint.get_or_insert_with(GarminWaypoint::default), other);
+        }
+        if let Some(other) = other_point {
+            merge_garmin_track_point(point.get_or_insert_with(GarminTrackPoint::default), other);
+        }
+        *value = DetectedExtensions::from_parts(waypoint, point);
+    }
+
+    fn write<W: Write>(value: &Self::ExtensionsValue, writer: &mut EventWriter<W>) -> GpxResult<()> {
+        let (waypoint, point) = match value {
+            DetectedExtensions::None => return Ok(()),
+            DetectedExtensions::GarminWaypoint(waypoint) => (Some(waypoint), None),
+            DetectedExtensions::GarminTrackPoint(point) => (None, Some(point)),
+            DetectedExtensions::Composite(waypoint, point) => (Some(waypoint), Some(point)),
+        };
+        writer.write(writer::XmlEvent::start_element("extensions"))?;
+        if let Some(waypoint) = waypoint {
+            write_garmin_waypoint(waypoint, writer)?;
+        }
+        if let Some(point) = point {
+            write_garmin_track_point(point, writer)?;
+        }
+        writer.write(writer::XmlEvent::end_element())?;
+        Ok(())
+    }
+}
+
+/// push_name appends `name` as written in the document to `xml`, noting its
+/// prefix in `prefixes`.
+fn push_name(xml: &mut String, name: &OwnedName, prefixes: &mut Vec<String>) {
+    if let Some(prefix) = &name.prefix {
+        if !prefixes.contains(prefix) {
+            prefixes.push(prefix.clone());
+        }
+        xml.push_str(prefix);
+        xml.push(':');
+    }
+    xml.push_str(&name.local_name);
 }
 
 #[cfg(test)]
 mod tests {
     use core::panic;
+    use std::io::Read;
+
+    use crate::{errors::GpxError, ExtensionsMode, GpxVersion, ParseOptions};
+    use crate::errors::GpxResult;
+    use xml::writer::EventWriter;
+    use xml::EventReader;
+
+    use crate::parser::{create_context, create_context_with_options, verify_starting_tag, Context, Events};
+
+    use super::{
+        DetectedExtensions, EmptyExtensions, GarminExtensions, GarminTrackPoint, GarminTrackPointExtensions,
+        GarminWaypoint, RawExtensions, WaypointExtensions,
+    };
+
+    fn consume<R: Read>(context: &mut Context<R, EmptyExtensions>) -> GpxResult<()> {
+        EmptyExtensions::consume(context)
+    }
+
+    const RAW: &str = "<extensions><a:b xmlns:a=\"urn:a\" c='d'>caf&#xE9;\r\n<![CDATA[<x>]]></a:b><e:f/></extensions>";
+
+    #[test]
+    fn consume_raw_extensions() {
+        let xml = format!("<gpx xmlns:e=\"urn:e\">{}</gpx>", RAW);
+        let mut context = create_context::<_, RawExtensions>(xml.as_bytes(), GpxVersion::Gpx11);
+        verify_starting_tag(&mut context, "gpx").unwrap();
+        let raw = RawExtensions::consume(&mut context).unwrap();
+
+        assert_eq!(raw.xml, &RAW["<extensions>".len()..RAW.len() - "</extensions>".len()]);
+        assert_eq!(raw.namespaces.into_iter().collect::<Vec<_>>(), [("e".into(), "urn:e".into())]);
+    }
+
+    #[test]
+    fn consume_raw_extensions_capped() {
+        let xml = "<extensions><name>Café</name></extensions>";
+        let options = ParseOptions::strict().with_extensions(ExtensionsMode::RawCapped(10));
+        let mut context = create_context_with_options::<_, RawExtensions>(xml.as_bytes(), GpxVersion::Gpx11, options);
+        let raw = RawExtensions::consume(&mut context).unwrap();
+
+        // The cap falls inside the é, which is left out whole.
+        assert_eq!(raw.xml, "<name>Caf");
+        assert!(raw.truncated);
+
+        let mut written = Vec::new();
+        RawExtensions::write(&raw, &mut EventWriter::new(&mut written)).unwrap();
+        assert!(written.is_empty());
+
+        let options = ParseOptions::strict().with_extensions(ExtensionsMode::RawCapped(18));
+        let mut context = create_context_with_options::<_, RawExtensions>(xml.as_bytes(), GpxVersion::Gpx11, options);
+        let raw = RawExtensions::consume(&mut context).unwrap();
+        assert_eq!(raw.xml, "<name>Café</name>");
+        assert!(!raw.truncated);
+    }
+
+    #[test]
+    fn consume_raw_extensions_capped_skips_rest() {
+        let xml = "<gpx><extensions><a><b>long text</b><c/></a><d/></extensions><after/></gpx>";
+        let options = ParseOptions::strict().with_extensions(ExtensionsMode::RawCapped(8));
+        let mut context = create_context_with_options::<_, EmptyExtensions>(xml.as_bytes(), GpxVersion::Gpx11, options);
+        verify_starting_tag(&mut context, "gpx").unwrap();
+        let raw = RawExtensions::consume(&mut context).unwrap();
+
+        assert_eq!(raw.xml, "<a><b>lo");
+        assert!(raw.truncated);
+        verify_starting_tag(&mut context, "after").unwrap();
+
+        // Without a recording, the cap applies to the rebuilt XML.
+        let events = Events::from(EventReader::new(xml.as_bytes()).into_iter()).peekable();
+        let options = ParseOptions::strict().with_extensions(ExtensionsMode::RawCapped(8));
+        let mut context: Context<_, EmptyExtensions> = Context::with_options(events, GpxVersion::Gpx11, options);
+        verify_starting_tag(&mut context, "gpx").unwrap();
+        let raw = RawExtensions::consume(&mut context).unwrap();
+
+        assert_eq!(raw.xml, "<a><b>lo");
+        assert!(raw.truncated);
+        verify_starting_tag(&mut context, "after").unwrap();
+    }
+
+    #[test]
+    fn consume_raw_extensions_rebuilt() {
+        // Without a recording, the XML is rebuilt from the events.
+        let xml = format!("<gpx xmlns:e=\"urn:e\">{}</gpx>", RAW);
+        let events = Events::from(EventReader::new(xml.as_bytes()).into_iter()).peekable();
+        let mut context: Context<_, EmptyExtensions> = Context::new(events, GpxVersion::Gpx11);
+        verify_starting_tag(&mut context, "gpx").unwrap();
+        let raw = RawExtensions::consume(&mut context).unwrap();
+
+        assert_eq!(
+            raw.xml,
+            "<a:b xmlns:a=\"urn:a\" c=\"d\">caf\u{e9}\r\n&lt;x&gt;</a:b><e:f></e:f>"
+        );
+    }
+
+    #[test]
+    fn consume_garmin_extensions() {
+        let xml = "<extensions>
+            <gpxx:WaypointExtension xmlns:gpxx=\"http://www.garmin.com/xmlschemas/GpxExtensions/v3\">
+                <gpxx:Proximity>50.5</gpxx:Proximity>
+                <gpxx:Temperature>12</gpxx:Temperature>
+                <gpxx:Depth>7.25</gpxx:Depth>
+            </gpxx:WaypointExtension>
+            <other:Thing xmlns:other=\"urn:other\"><Depth>1</Depth></other:Thing>
+        </extensions>";
+        let mut context = create_context::<_, EmptyExtensions>(xml.as_bytes(), GpxVersion::Gpx11);
+        let value = GarminExtensions::consume(&mut context).unwrap();
+
+        assert_eq!(
+            value,
+            GarminWaypoint {
+                proximity: Some(50.5),
+                depth: Some(7.25),
+                display_mode: None,
+            }
+        );
+    }
+
+    #[test]
+    fn consume_garmin_track_point_extensions() {
+        let xml = "<extensions>
+            <gpxtpx:TrackPointExtension xmlns:gpxtpx=\"http://www.garmin.com/xmlschemas/TrackPointExtension/v2\">
+                <gpxtpx:atemp>21.5</gpxtpx:atemp>
+                <gpxtpx:hr>142</gpxtpx:hr>
+                <gpxtpx:cad>88</gpxtpx:cad>
+                <gpxtpx:speed>3.1</gpxtpx:speed>
+            </gpxtpx:TrackPointExtension>
+        </extensions>";
+        let mut context = create_context::<_, EmptyExtensions>(xml.as_bytes(), GpxVersion::Gpx11);
+        let value = GarminTrackPointExtensions::consume(&mut context).unwrap();
 
-    use crate::{errors::GpxError, GpxVersion};
+        assert_eq!(
+            value,
+            GarminTrackPoint {
+                air_temperature: Some(21.5),
+                heart_rate: Some(142),
+                cadence: Some(88),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn consume_detected_extensions() {
+        let consume = |xml: &str| {
+            let mut context = create_context::<_, EmptyExtensions>(xml.as_bytes(), GpxVersion::Gpx11);
+            DetectedExtensions::consume(&mut context).unwrap()
+        };
+        let waypoint = "<gpxx:WaypointExtension xmlns:gpxx=\"http://www.garmin.com/xmlschemas/GpxExtensions/v3\">
+            <gpxx:Depth>7.25</gpxx:Depth>
+        </gpxx:WaypointExtension>";
+        let point = "<tpx:TrackPointExtension xmlns:tpx=\"http://www.garmin.com/xmlschemas/TrackPointExtension/v1\">
+            <tpx:hr>142</tpx:hr>
+        </tpx:TrackPointExtension>";
+        // Elements are told apart by namespace, not by name.
+        let other = "<TrackPointExtension xmlns=\"urn:other\"><hr>1</hr></TrackPointExtension>";
+        let depth = GarminWaypoint {
+            depth: Some(7.25),
+            ..Default::default()
+        };
+        let heart_rate = GarminTrackPoint {
+            heart_rate: Some(142),
+            ..Default::default()
+        };
+
+        assert_eq!(consume("<extensions/>"), DetectedExtensions::None);
+        assert_eq!(
+            consume(&format!("<extensions>{}</extensions>", other)),
+            DetectedExtensions::None
+        );
+        assert_eq!(
+            consume(&format!("<extensions>{}{}</extensions>", other, waypoint)),
+            DetectedExtensions::GarminWaypoint(depth.clone())
+        );
+        assert_eq!(
+            consume(&format!("<extensions>{}</extensions>", point)),
+            DetectedExtensions::GarminTrackPoint(heart_rate.clone())
+        );
+        assert_eq!(
+            consume(&format!("<extensions>{}{}</extensions>", point, waypoint)),
+            DetectedExtensions::Composite(depth, heart_rate)
+        );
+    }
 
     #[test]
     fn consume_arbitrary_extensions() {
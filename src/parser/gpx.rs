@@ -3,70 +3,54 @@
 use std::io::Read;
 
 use geo_types::Rect;
+use xml::namespace::{Namespace, NS_EMPTY_URI, NS_NO_PREFIX, NS_XMLNS_PREFIX, NS_XMLNS_URI, NS_XML_PREFIX, NS_XML_URI};
 use xml::reader::XmlEvent;
 
 use crate::{Gpx, GpxVersion, Link, Metadata, Person};
-use crate::errors::{GpxError, GpxResult};
+use crate::errors::{GpxError, GpxResult, GpxWarning};
 use crate::parser::{
-    bounds, Context, metadata, route, string, time, track, verify_starting_tag, waypoint,
+    bounds, Context, metadata, route, string, time, track, unknown_element, verify_starting_tag, waypoint,
 };
 use crate::parser::extensions::WaypointExtensions;
 use crate::parser::time::Time;
 
 use super::extensions;
 
-/// Convert the version string to the version enum
-fn version_string_to_version(version_str: &str) -> GpxResult<GpxVersion> {
-    match version_str {
-        "1.0" => Ok(GpxVersion::Gpx10),
-        "1.1" => Ok(GpxVersion::Gpx11),
-        _ => Err(GpxError::UnknownVersionError(GpxVersion::Unknown)),
-    }
-}
+/// A handler offered the unknown children of the `gpx` element, giving
+/// whether it consumed them.
+pub(crate) type RootHandler<'h, R, E> = dyn FnMut(&str, &mut Context<R, E>) -> GpxResult<bool> + 'h;
 
 /// consume consumes an entire GPX element.
 pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> Result<Gpx<E>, GpxError> {
+    consume_with_root_handler(context, &mut |_, _| Ok(false))
+}
+
+/// consume_with_root_handler consumes an entire GPX element like [`consume`],
+/// offering every unknown child of it to `handler` first, which gives whether
+/// it consumed the child.
+pub(crate) fn consume_with_root_handler<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+    handler: &mut RootHandler<'_, R, E>,
+) -> Result<Gpx<E>, GpxError> {
     let mut gpx: Gpx<E> = Default::default();
+    let mut gpx10_metadata = Gpx10Metadata::default();
 
-    let mut author: Option<String> = None;
-    let mut url: Option<String> = None;
-    let mut urlname: Option<String> = None;
-    let mut email: Option<String> = None;
-    let mut time: Option<Time> = None;
-    let mut bounds: Option<Rect<f64>> = None;
-    let mut gpx_name: Option<String> = None;
-    let mut description: Option<String> = None;
-    let mut keywords: Option<String> = None;
-
-    // First we consume the gpx tag and its attributes
-    let attributes = verify_starting_tag(context, "gpx")?;
-    let version = attributes
-        .iter()
-        .find(|attr| attr.name.local_name == "version")
-        .ok_or(GpxError::InvalidElementLacksAttribute("version", "gpx"))?;
-    gpx.version = version_string_to_version(&version.value)?;
-    context.version = gpx.version;
-
-    let creator = attributes
-        .iter()
-        .find(|attr| attr.name.local_name == "creator");
-    gpx.creator = creator.map(|c| c.value.to_owned());
+    let start = consume_start(context, &mut gpx.comments)?;
+    gpx.version = start.version;
+    gpx.creator = start.creator;
+    gpx.namespaces = start.namespaces;
+    gpx.schema_locations = start.schema_locations;
 
     loop {
-        let next_event = {
-            if let Some(next) = context.reader.peek() {
-                match next {
-                    Ok(n) => n,
-                    Err(_) => return Err(GpxError::EventParsingError("Expecting an event")),
-                }
-            } else {
-                break;
-            }
+        context.check_deadline()?;
+        let next_event = match context.peek_ok("gpx")? {
+            Some(event) => event,
+            None => break,
         };
 
         match next_event {
             XmlEvent::StartElement { ref name, .. } => match name.local_name.as_ref() {
-                "metadata" if context.version != GpxVersion::Gpx10 => {
+                "metadata" if gpx.version != GpxVersion::Gpx10 => {
                     gpx.metadata = Some(metadata::consume(context)?);
                 }
                 "trk" => {
@@ -76,40 +60,19 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
                     gpx.routes.push(route::consume(context)?);
                 }
                 "wpt" => {
-                    gpx.waypoints.push(waypoint::consume(context, "wpt")?);
-                }
-                "time" if context.version == GpxVersion::Gpx10 => {
-                    time = Some(time::consume(context)?);
-                }
-                "bounds" if context.version == GpxVersion::Gpx10 => {
-                    bounds = Some(bounds::consume(context)?);
-                }
-                "author" if context.version == GpxVersion::Gpx10 => {
-                    author = Some(string::consume(context, "author", false)?);
-                }
-                "email" if context.version == GpxVersion::Gpx10 => {
-                    email = Some(string::consume(context, "email", false)?);
-                }
-                "url" if context.version == GpxVersion::Gpx10 => {
-                    url = Some(string::consume(context, "url", false)?);
-                }
-                "urlname" if context.version == GpxVersion::Gpx10 => {
-                    urlname = Some(string::consume(context, "urlname", false)?);
-                }
-                "name" if context.version == GpxVersion::Gpx10 => {
-                    gpx_name = Some(string::consume(context, "name", false)?);
-                }
-                "desc" if context.version == GpxVersion::Gpx10 => {
-                    description = Some(string::consume(context, "desc", true)?);
-                }
-                "keywords" if context.version == GpxVersion::Gpx10 => {
-                    keywords = Some(string::consume(context, "keywords", true)?);
+                    if let Some(point) = waypoint::consume_or_skip(context, "wpt")? {
+                        context.count_point()?;
+                        gpx.waypoints.push(point);
+                    }
                 }
                 "extensions" => {
                     extensions::EmptyExtensions::consume(context)?;
                 }
                 child => {
-                    return Err(GpxError::InvalidChildElement(String::from(child), "gpx"));
+                    let child = String::from(child);
+                    if !gpx10_metadata.consume(context, &child)? && !handler(&child, context)? {
+                        unknown_element(context, child, "gpx")?;
+                    }
                 }
             },
             XmlEvent::EndElement { name } => {
@@ -117,39 +80,22 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
                     return Err(GpxError::InvalidClosingTag(name.local_name.clone(), "gpx"));
                 }
                 if gpx.version == GpxVersion::Gpx10 {
-                    let link = url.map(|url| Link {
-                        href: url,
-                        text: urlname,
-                        ..Default::default()
-                    });
-                    let person: Person = Person {
-                        name: author,
-                        email,
-                        link,
-                    };
-                    let author = if person != Default::default() {
-                        Some(person)
-                    } else {
-                        None
-                    };
-                    let metadata: Metadata = Metadata {
-                        name: gpx_name,
-                        time,
-                        bounds,
-                        keywords,
-                        description,
-                        author,
-                        ..Default::default()
-                    };
-
-                    if metadata != Default::default() {
-                        gpx.metadata = Some(metadata);
-                    }
+                    gpx.metadata = gpx10_metadata.into_metadata();
                 }
                 context.reader.next();
 
+                if let Some(policy) = context.options.post_normalize {
+                    for fixup in gpx.ensure_invariants(policy)? {
+                        context.warn(GpxWarning::Normalized(fixup));
+                    }
+                }
                 return Ok(gpx);
             }
+            XmlEvent::Comment(_) => {
+                if let Some(Ok(XmlEvent::Comment(comment))) = context.reader.next() {
+                    gpx.comments.push(comment);
+                }
+            }
             _ => {
                 context.reader.next(); //consume and ignore this event
             }
@@ -159,13 +105,157 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
     Err(GpxError::MissingClosingTag("gpx"))
 }
 
+/// What the `gpx` starting tag tells of a document.
+pub(crate) struct Start {
+    pub(crate) version: GpxVersion,
+    pub(crate) creator: Option<String>,
+    pub(crate) namespaces: Vec<(String, String)>,
+    pub(crate) schema_locations: Vec<(String, String)>,
+}
+
+/// declared_namespaces gives the namespaces of `namespace` as prefix and URI
+/// pairs, without those every element has: the `xml` and `xmlns` prefixes
+/// and the empty default namespace.
+fn declared_namespaces(namespace: &Namespace) -> Vec<(String, String)> {
+    namespace
+        .into_iter()
+        .filter(|&(prefix, uri)| match prefix {
+            NS_XML_PREFIX => uri != NS_XML_URI,
+            NS_XMLNS_PREFIX => uri != NS_XMLNS_URI,
+            NS_NO_PREFIX => uri != NS_EMPTY_URI,
+            _ => true,
+        })
+        .map(|(prefix, uri)| (prefix.to_string(), uri.to_string()))
+        .collect()
+}
+
+/// schema_locations splits an `xsi:schemaLocation` into namespace and
+/// location pairs, leaving out a namespace without a location.
+fn schema_locations(value: &str) -> Vec<(String, String)> {
+    let mut parts = value.split_whitespace();
+    let mut locations = Vec::new();
+    while let (Some(namespace), Some(location)) = (parts.next(), parts.next()) {
+        locations.push((namespace.to_string(), location.to_string()));
+    }
+    locations
+}
+
+/// consume_start consumes the `gpx` starting tag and the comments before it,
+/// which it keeps in `comments`, giving what the tag tells of the document.
+pub(crate) fn consume_start<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+    comments: &mut Vec<String>,
+) -> GpxResult<Start> {
+    // Comments before the gpx tag would be skipped looking for it
+    while let Some(Ok(XmlEvent::Comment(_) | XmlEvent::StartDocument { .. } | XmlEvent::ProcessingInstruction { .. })) =
+        context.reader.peek()
+    {
+        if let Some(Ok(XmlEvent::Comment(comment))) = context.reader.next() {
+            comments.push(comment);
+        }
+    }
+
+    let namespaces = match context.reader.peek() {
+        Some(Ok(XmlEvent::StartElement { namespace, .. })) => declared_namespaces(namespace),
+        _ => Vec::new(),
+    };
+    let mut attributes = verify_starting_tag(context, "gpx")?;
+    let version: GpxVersion = attributes.require("version")?.parse()?;
+    context.version = version;
+
+    Ok(Start {
+        version,
+        creator: attributes.take("creator"),
+        namespaces,
+        schema_locations: attributes.get("schemaLocation").map(schema_locations).unwrap_or_default(),
+    })
+}
+
+/// The children of a GPX 1.0 `gpx` element that GPX 1.1 moved into
+/// `metadata`.
+#[derive(Default)]
+pub(crate) struct Gpx10Metadata {
+    author: Option<String>,
+    url: Option<String>,
+    urlname: Option<String>,
+    email: Option<String>,
+    time: Option<Time>,
+    bounds: Option<Rect<f64>>,
+    name: Option<String>,
+    description: Option<String>,
+    keywords: Option<String>,
+}
+
+impl Gpx10Metadata {
+    /// consume consumes the child `child` of a GPX 1.0 `gpx` element when it
+    /// is one of these, giving whether it was.
+    pub(crate) fn consume<R: Read, E: WaypointExtensions + Default>(
+        &mut self,
+        context: &mut Context<R, E>,
+        child: &str,
+    ) -> GpxResult<bool> {
+        if context.version != GpxVersion::Gpx10 {
+            return Ok(false);
+        }
+        match child {
+            "time" => self.time = time::consume_or_drop(context)?,
+            "bounds" => self.bounds = Some(bounds::consume(context)?),
+            "author" => self.author = Some(string::consume(context, "author", false)?),
+            "email" => self.email = Some(string::consume(context, "email", false)?),
+            "url" => self.url = Some(string::consume(context, "url", false)?),
+            "urlname" => self.urlname = Some(string::consume(context, "urlname", false)?),
+            "name" => self.name = Some(string::consume(context, "name", false)?),
+            "desc" => self.description = Some(string::consume(context, "desc", true)?),
+            "keywords" => self.keywords = Some(string::consume(context, "keywords", true)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// into_metadata gives the metadata read, if there was any.
+    pub(crate) fn into_metadata(self) -> Option<Metadata> {
+        let urlname = self.urlname;
+        let link = self.url.map(|url| Link {
+            href: url,
+            text: urlname,
+            ..Default::default()
+        });
+        let person: Person = Person {
+            name: self.author,
+            email: self.email,
+            link,
+        };
+        let author = if person != Default::default() {
+            Some(person)
+        } else {
+            None
+        };
+        let metadata: Metadata = Metadata {
+            name: self.name,
+            time: self.time,
+            bounds: self.bounds,
+            keywords: self.keywords,
+            description: self.description,
+            author,
+            ..Default::default()
+        };
+
+        if metadata != Default::default() {
+            Some(metadata)
+        } else {
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use geo_types::Point;
 
-    use crate::{errors::GpxError, GpxVersion};
+    use crate::errors::{ErrorKind, GpxError};
+    use crate::GpxVersion;
 
-    use super::consume;
+    use super::{consume, schema_locations};
 
     #[test]
     fn consume_gpx() {
@@ -292,12 +382,12 @@ mod tests {
         );
 
         assert!(gpx.is_err());
-        // the track parser gets an internal "invalid closing tag"-error, and gives back an "EventParsingError("track event")
-        if let GpxError::EventParsingError(err) = gpx.unwrap_err() {
-            assert_eq!(err, "track event");
-        } else {
-            panic!("Expected different error.")
-        }
+        // the track parser gets the "invalid closing tag" error of the XML
+        // parser, and gives it back with the element as its context.
+        let err = gpx.unwrap_err();
+        assert!(matches!(err, GpxError::EventParsingError("track", _)), "{:?}", err);
+        assert_eq!(err.kind(), ErrorKind::Xml);
+        assert!(std::error::Error::source(&err).is_some());
     }
 
     #[test]
@@ -340,11 +430,19 @@ mod tests {
         );
 
         assert!(gpx.is_err());
-        // the track parser gets an internal "invalid closing tag"-error, and gives back an "EventParsingError("track event")
-        if let GpxError::EventParsingError(err) = gpx.unwrap_err() {
-            assert_eq!(err, "track event");
-        } else {
-            panic!("Expected different error.")
-        }
+        // the track parser gets the "invalid closing tag" error of the XML
+        // parser, and gives it back with the element as its context.
+        let err = gpx.unwrap_err();
+        assert!(matches!(err, GpxError::EventParsingError("track", _)), "{:?}", err);
+        assert_eq!(err.kind(), ErrorKind::Xml);
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn split_schema_locations() {
+        let pairs = schema_locations("  urn:a  a.xsd\n\turn:b b.xsd urn:c ");
+        let pairs: Vec<_> = pairs.iter().map(|(namespace, location)| (namespace.as_str(), location.as_str())).collect();
+        assert_eq!(pairs, [("urn:a", "a.xsd"), ("urn:b", "b.xsd")]);
+        assert!(schema_locations("").is_empty());
     }
 }
@@ -0,0 +1,156 @@
+//! integer handles parsing of GPX-spec non-negative integers, such as
+//! satellite counts and DGPS station ids, as written by buggy firmwares too.
+
+use std::convert::TryFrom;
+use std::io::Read;
+use std::str::FromStr;
+
+use crate::errors::{GpxError, GpxResult, GpxWarning};
+use crate::parser::extensions::WaypointExtensions;
+use crate::parser::{string, Context};
+
+/// consume consumes a non-negative integer.
+///
+/// A value above `sentinel`, such as a satellite count of 255, is taken for a
+/// firmware's way of saying "unknown", and left out with a warning. Fields
+/// where large values are meaningful, such as DGPS station ids, pass `None`.
+///
+/// In lenient mode, a value written with a fraction, such as `9.0`, is
+/// truncated, and a negative value left out, both with a warning. In strict
+/// mode they are errors.
+pub fn consume<R, E, T>(context: &mut Context<R, E>, tagname: &'static str, sentinel: Option<u64>) -> GpxResult<Option<T>>
+where
+    R: Read,
+    E: WaypointExtensions + Default,
+    T: FromStr + TryFrom<u64> + Into<u64> + Copy,
+{
+    let content = string::consume(context, tagname, false)?;
+    let text = content.trim();
+    let value: T = match text.parse() {
+        Ok(value) => value,
+        Err(_) => {
+            let number = match text.parse::<f64>() {
+                Ok(number) if context.options.lenient && number.is_finite() => number,
+                _ => return Err(GpxError::NotANumber(content, tagname)),
+            };
+            if number < 0.0 {
+                context.warn(GpxWarning::InvalidNumber {
+                    element: tagname,
+                    value: content,
+                });
+                return Ok(None);
+            }
+            let value = match T::try_from(number as u64) {
+                Ok(value) => value,
+                Err(_) => return Err(GpxError::NotANumber(content, tagname)),
+            };
+            context.warn(GpxWarning::FractionalNumber {
+                element: tagname,
+                value: content,
+                replacement: value.into(),
+            });
+            value
+        }
+    };
+    match sentinel {
+        Some(sentinel) if value.into() > sentinel => {
+            context.warn(GpxWarning::UnknownSentinel {
+                element: tagname,
+                value: value.into(),
+            });
+            Ok(None)
+        }
+        _ => Ok(Some(value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::errors::{GpxError, GpxResult, GpxWarning};
+    use crate::parser::create_context_with_options;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{GpxVersion, ParseOptions};
+
+    use super::consume;
+
+    /// Consumes `<sat>value</sat>` with the usual sentinel of 99, giving the
+    /// warnings too.
+    fn sat(value: &str, options: ParseOptions) -> (GpxResult<Option<u64>>, Vec<GpxWarning>) {
+        let xml = format!("<sat>{}</sat>", value);
+        let mut context = create_context_with_options::<_, EmptyExtensions>(
+            BufReader::new(xml.as_bytes()),
+            GpxVersion::Gpx11,
+            options,
+        );
+        let result = consume(&mut context, "sat", Some(99));
+        let warnings = context.warnings().iter().map(|warning| warning.warning.clone()).collect();
+        (result, warnings)
+    }
+
+    #[test]
+    fn consume_plain() {
+        for options in [ParseOptions::strict(), ParseOptions::lenient()] {
+            for (value, expected) in [("0", 0), (" 12 ", 12), ("99", 99)] {
+                let (result, warnings) = sat(value, options.clone());
+                assert_eq!(result.unwrap(), Some(expected));
+                assert!(warnings.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn consume_sentinel() {
+        for options in [ParseOptions::strict(), ParseOptions::lenient()] {
+            let (result, warnings) = sat("255", options);
+            assert_eq!(result.unwrap(), None);
+            assert_eq!(warnings, [GpxWarning::UnknownSentinel { element: "sat", value: 255 }]);
+        }
+
+        // Fields without a sentinel keep large values.
+        let xml = "<dgpsid>1023</dgpsid>";
+        let mut context = create_context_with_options::<_, EmptyExtensions>(
+            BufReader::new(xml.as_bytes()),
+            GpxVersion::Gpx11,
+            ParseOptions::lenient(),
+        );
+        assert_eq!(consume::<_, _, u16>(&mut context, "dgpsid", None).unwrap(), Some(1023));
+    }
+
+    #[test]
+    fn consume_fractional() {
+        let (result, _) = sat("9.0", ParseOptions::strict());
+        assert!(matches!(result, Err(GpxError::NotANumber(_, "sat"))));
+
+        let (result, warnings) = sat("9.0", ParseOptions::lenient());
+        assert_eq!(result.unwrap(), Some(9));
+        assert_eq!(
+            warnings,
+            [GpxWarning::FractionalNumber {
+                element: "sat",
+                value: String::from("9.0"),
+                replacement: 9
+            }]
+        );
+    }
+
+    #[test]
+    fn consume_negative() {
+        let (result, _) = sat("-3", ParseOptions::strict());
+        assert!(matches!(result, Err(GpxError::NotANumber(_, "sat"))));
+
+        let (result, warnings) = sat("-3", ParseOptions::lenient());
+        assert_eq!(result.unwrap(), None);
+        assert_eq!(
+            warnings,
+            [GpxWarning::InvalidNumber {
+                element: "sat",
+                value: String::from("-3")
+            }]
+        );
+
+        let (result, _) = sat("many", ParseOptions::lenient());
+        assert!(matches!(result, Err(GpxError::NotANumber(_, "sat"))));
+    }
+}
@@ -14,27 +14,12 @@ use crate::parser::extensions::WaypointExtensions;
 /// tag.
 pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<Link> {
     let mut link: Link = Default::default();
-    let attributes = verify_starting_tag(context, "link")?;
-    let attr = attributes
-        .into_iter()
-        .find(|attr| attr.name.local_name == "href");
-
-    let attr = attr.ok_or(GpxError::InvalidElementLacksAttribute("href", "link"))?;
-
-    link.href = attr.value;
-
-    loop {
-        let next_event = {
-            if let Some(next) = context.reader.peek() {
-                match next {
-                    Ok(n) => n,
-                    Err(_) => return Err(GpxError::EventParsingError("link event")),
-                }
-            } else {
-                break;
-            }
-        };
+    let mut attributes = verify_starting_tag(context, "link")?;
+    link.href = attributes
+        .take("href")
+        .ok_or(GpxError::InvalidElementLacksAttribute("href", "link"))?;
 
+    while let Some(next_event) = context.peek_ok("link")? {
         match next_event {
             XmlEvent::StartElement { ref name, .. } => match name.local_name.as_ref() {
                 "text" => link.text = Some(string::consume(context, "text", false)?),
@@ -59,11 +44,40 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
     Err(GpxError::MissingClosingTag("link"))
 }
 
+/// consume_gpx10 consumes a GPX 1.0 `<url>` or `<urlname>` element into the
+/// last of `links`, starting a new link if that one already has the value.
+pub fn consume_gpx10<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+    tagname: &'static str,
+    links: &mut Vec<Link>,
+) -> GpxResult<()> {
+    let value = string::consume(context, tagname, true)?;
+    let is_url = tagname == "url";
+    let needs_new = match links.last() {
+        Some(link) if is_url => !link.href.is_empty(),
+        Some(link) => link.text.is_some(),
+        None => true,
+    };
+    if needs_new {
+        links.push(Default::default());
+    }
+    let link = links.last_mut().unwrap();
+    if is_url {
+        link.href = value;
+    } else {
+        link.text = Some(value);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::parser::create_context;
+    use crate::parser::extensions::EmptyExtensions;
     use crate::GpxVersion;
+    use crate::Link;
 
-    use super::consume;
+    use super::{consume, consume_gpx10};
 
     #[test]
     fn consume_simple_link() {
@@ -108,4 +122,19 @@ mod tests {
 
         assert!(link.is_err());
     }
+
+    #[test]
+    fn consume_gpx10_url() {
+        let mut links: Vec<Link> = Vec::new();
+        let mut context = create_context::<_, EmptyExtensions>(
+            "<url>http://example.com</url><urlname>example</urlname>".as_bytes(),
+            GpxVersion::Gpx10,
+        );
+        consume_gpx10(&mut context, "url", &mut links).unwrap();
+        consume_gpx10(&mut context, "urlname", &mut links).unwrap();
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].href, "http://example.com");
+        assert_eq!(links[0].text.as_deref(), Some("example"));
+    }
 }
@@ -7,7 +7,7 @@ use xml::reader::XmlEvent;
 use crate::errors::{GpxError, GpxResult};
 use crate::Metadata;
 use crate::parser::{
-    bounds, Context, copyright, extensions, link, person, string, time, verify_starting_tag,
+    bounds, Context, copyright, extensions, link, person, string, time, unknown_element, verify_starting_tag,
 };
 use crate::parser::extensions::WaypointExtensions;
 
@@ -15,18 +15,7 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
     let mut metadata: Metadata = Default::default();
     verify_starting_tag(context, "metadata")?;
 
-    loop {
-        let next_event = {
-            if let Some(next) = context.reader.peek() {
-                match next {
-                    Ok(n) => n,
-                    Err(_) => return Err(GpxError::MetadataParsingError()),
-                }
-            } else {
-                break;
-            }
-        };
-
+    while let Some(next_event) = context.peek_ok("metadata")? {
         match next_event {
             XmlEvent::StartElement { ref name, .. } => match name.local_name.as_ref() {
                 "name" => {
@@ -42,7 +31,7 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
                     metadata.keywords = Some(string::consume(context, "keywords", true)?);
                 }
                 "time" => {
-                    metadata.time = Some(time::consume(context)?);
+                    metadata.time = time::consume_or_drop(context)?;
                 }
                 "link" => {
                     metadata.links.push(link::consume(context)?);
@@ -57,10 +46,8 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
                     extensions::EmptyExtensions::consume(context)?;
                 }
                 child => {
-                    return Err(GpxError::InvalidChildElement(
-                        String::from(child),
-                        "metadata",
-                    ));
+                    let child = String::from(child);
+                    unknown_element(context, child, "metadata")?;
                 }
             },
             XmlEvent::EndElement { ref name } => {
@@ -3,14 +3,19 @@
 use std::io::Read;
 use std::iter::Peekable;
 use std::marker::PhantomData;
+use std::rc::Rc;
+use std::time::Instant;
 
-use xml::{EventReader, ParserConfig};
-use xml::attribute::OwnedAttribute;
-use xml::reader::{Events, XmlEvent};
+use xml::reader::XmlEvent;
 
-use crate::errors::{GpxError, GpxResult};
-use crate::parser::extensions::WaypointExtensions;
-use crate::types::GpxVersion;
+use crate::errors::{GpxError, GpxResult, GpxWarning, Position, ReadWarning};
+pub use crate::parser::attributes::Attributes;
+pub use crate::parser::events::Events;
+use crate::parser::extensions::{EmptyExtensions, WaypointExtensions};
+pub use crate::parser::tracking::TrackingReader;
+use crate::parser::tracking::Tracker;
+use crate::reader::{ExtensionsMode, ParseOptions};
+use crate::types::{GpxVersion, Waypoint};
 
 // Just a shared macro for testing 'consume'.
 #[cfg(test)]
@@ -19,62 +24,327 @@ macro_rules! consume {
     ($xml:expr, $version:expr) => {{
         use std::io::BufReader;
         use $crate::parser::create_context;
-        consume(&mut create_context(
+        use $crate::parser::extensions::EmptyExtensions;
+        consume(&mut create_context::<_, EmptyExtensions>(
             BufReader::new($xml.as_bytes()),
             $version,
         ))
     }};
     ($xml:expr, $version:expr, $tagname:expr) => {{
-        use crate::parser::create_context;
+        use $crate::parser::create_context;
+        use $crate::parser::extensions::EmptyExtensions;
         use std::io::BufReader;
         consume(
-            &mut create_context(BufReader::new($xml.as_bytes()), $version),
+            &mut create_context::<_, EmptyExtensions>(BufReader::new($xml.as_bytes()), $version),
             $tagname,
         )
     }};
     ($xml:expr, $version:expr, $tagname:expr, $allow_empty:expr) => {{
-        use crate::parser::create_context;
+        use $crate::parser::create_context;
+        use $crate::parser::extensions::EmptyExtensions;
         use std::io::BufReader;
         consume(
-            &mut create_context(BufReader::new($xml.as_bytes()), $version),
+            &mut create_context::<_, EmptyExtensions>(BufReader::new($xml.as_bytes()), $version),
             $tagname,
             $allow_empty,
         )
     }};
 }
 
+mod attributes;
 pub mod bounds;
 pub mod copyright;
+pub mod dop;
 pub mod email;
+mod events;
 pub mod extensions;
 pub mod fix;
 pub mod gpx;
+pub mod integer;
 pub mod link;
 pub mod metadata;
+pub mod number;
 pub mod person;
+#[cfg(feature = "quick-xml")]
+mod quick;
+mod tracking;
 pub mod route;
 pub mod string;
+pub mod style;
 pub mod time;
 pub mod track;
 pub mod tracksegment;
 pub mod waypoint;
 
-pub struct Context<R: Read, E: WaypointExtensions + Default> {
+/// How many calls of [`Context::check_deadline`] look at the clock once.
+const DEADLINE_INTERVAL: u64 = 256;
+
+/// now gives the time, on targets with a clock.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn now() -> Option<Instant> {
+    Some(Instant::now())
+}
+
+/// now gives nothing, as `wasm32-unknown-unknown` has no clock: asking
+/// `Instant` for the time panics there.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn now() -> Option<Instant> {
+    None
+}
+
+/// How many points [`ParseOptions::points_capacity_hint`] reserves at most,
+/// some 400 MB of points without extensions.
+pub(crate) const MAX_RESERVED_POINTS: usize = 1 << 20;
+
+/// A callback taking every parsed track and route point.
+type OnPoint<'a, E> = &'a mut dyn FnMut(&Waypoint<E>);
+
+pub struct Context<'a, R: Read, E: WaypointExtensions + Default> {
     reader: Peekable<Events<R>>,
     version: GpxVersion,
+    options: ParseOptions,
+    warnings: Vec<ReadWarning>,
+    on_point: Option<OnPoint<'a, E>>,
+    tracker: Option<Rc<Tracker>>,
+    // Only looked at with a timeout, and never set on targets without a clock
+    started: Option<Instant>,
+    checks: u64,
+    points: u64,
+    /// Whether a track segment or route reserved room for its points.
+    reserved: bool,
     phantom: PhantomData<E>,
 }
 
-impl<R: Read, E: WaypointExtensions + Default> Context<R, E> {
-    pub fn new(reader: Peekable<Events<R>>, version: GpxVersion) -> Context<R, E> {
-        Context { reader, version, phantom: Default::default() }
+impl<'a, R: Read, E: WaypointExtensions + Default> Context<'a, R, E> {
+    pub fn new(reader: Peekable<Events<R>>, version: GpxVersion) -> Context<'a, R, E> {
+        Context::with_options(reader, version, Default::default())
+    }
+
+    pub fn with_options(
+        reader: Peekable<Events<R>>,
+        version: GpxVersion,
+        options: ParseOptions,
+    ) -> Context<'a, R, E> {
+        let started = options.timeout.and_then(|_| now());
+        Context {
+            reader,
+            version,
+            options,
+            warnings: Vec::new(),
+            on_point: None,
+            tracker: None,
+            started,
+            checks: 0,
+            points: 0,
+            reserved: false,
+            phantom: Default::default(),
+        }
     }
 
     pub fn reader(&mut self) -> &mut Peekable<Events<R>> {
         &mut self.reader
     }
 
+    /// Peeks at the next event while parsing `element`, giving `None` at the
+    /// end of the document. An error of the XML parser is taken out of the
+    /// reader and given with `element` as its context.
+    pub(crate) fn peek_ok(&mut self, element: &'static str) -> GpxResult<Option<&XmlEvent>> {
+        if let Some(Err(_)) = self.reader.peek() {
+            return Err(self.take_error(element));
+        }
+        Ok(self.reader.peek().and_then(|event| event.as_ref().ok()))
+    }
+
+    /// Takes the error out of the reader after peeking at an event that is
+    /// one, giving it with `element` as its context.
+    fn take_error(&mut self, element: &'static str) -> GpxError {
+        match self.reader.next() {
+            Some(Err(err)) => GpxError::in_element(element, err),
+            // Peeking at an error guarantees the next event is that error.
+            _ => GpxError::MissingClosingTag(element),
+        }
+    }
+
+    pub fn version(&self) -> GpxVersion {
+        self.version
+    }
+
+    pub(crate) fn set_version(&mut self, version: GpxVersion) {
+        self.version = version;
+    }
+
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// Gives the position in the input the parser has read up to, which is
+    /// unknown for contexts made with [`Context::new`], and for the readers
+    /// that give no warnings when nothing in their options needs it.
+    pub fn position(&self) -> Option<Position> {
+        self.tracker.as_ref().map(|tracker| tracker.position())
+    }
+
+    /// Records an issue the parser recovered from in lenient mode, at the
+    /// current position.
+    pub fn warn(&mut self, warning: GpxWarning) {
+        self.take_stripped();
+        let position = self.position();
+        self.warnings.push(ReadWarning { warning, position });
+    }
+
+    /// Records an issue recovered from at `position`, found before parsing.
+    pub(crate) fn warn_at(&mut self, warning: GpxWarning, position: Position) {
+        self.warnings.push(ReadWarning {
+            warning,
+            position: Some(position),
+        });
+    }
+
+    /// take_stripped records the control characters stripped from the input
+    /// so far as warnings. They were read before the current position, so
+    /// they come before the warnings of anything parsed from here.
+    fn take_stripped(&mut self) {
+        let stripped = match &self.tracker {
+            Some(tracker) => tracker.take_stripped(),
+            None => return,
+        };
+        for (position, byte) in stripped {
+            self.warnings.push(ReadWarning {
+                warning: GpxWarning::ControlCharacter { byte },
+                position: Some(position),
+            });
+        }
+    }
+
+    /// Gives the warnings recorded so far.
+    pub fn warnings(&self) -> &[ReadWarning] {
+        &self.warnings
+    }
+
+    /// Takes the warnings recorded so far.
+    pub(crate) fn take_warnings(&mut self) -> Vec<ReadWarning> {
+        self.take_stripped();
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Hands every track and route point to `on_point` instead of keeping it.
+    pub(crate) fn set_on_point(&mut self, on_point: OnPoint<'a, E>) {
+        self.on_point = Some(on_point);
+    }
+
+    /// reserve_points reserves room in `points` for the points of
+    /// [`ParseOptions::points_capacity_hint`] not read yet, at most
+    /// [`MAX_RESERVED_POINTS`], the first time it is called only, so that a
+    /// document of many segments does not reserve the whole hint for each.
+    /// Nothing is reserved for points given to a callback rather than kept,
+    /// nor when the memory is not there, in which case `points` grows as it
+    /// would without a hint.
+    pub(crate) fn reserve_points(&mut self, points: &mut Vec<Waypoint<E>>) {
+        if self.reserved || self.on_point.is_some() {
+            return;
+        }
+        self.reserved = true;
+        if let Some(hint) = self.options.points_capacity_hint {
+            let capacity = hint.saturating_sub(self.points as usize).min(MAX_RESERVED_POINTS);
+            let _ = points.try_reserve_exact(capacity);
+        }
+    }
+
+    /// fit_points shrinks `points` to fit when
+    /// [`ParseOptions::shrink_points`] says so.
+    pub(crate) fn fit_points(&self, points: &mut Vec<Waypoint<E>>) {
+        if self.options.shrink_points {
+            points.shrink_to_fit();
+        }
+    }
+
+    /// Keeps a parsed track or route point in `points`, unless a callback
+    /// takes it, counting it with [`Context::count_point`].
+    pub(crate) fn keep_point(&mut self, point: Waypoint<E>, points: &mut Vec<Waypoint<E>>) -> GpxResult<()> {
+        self.count_point()?;
+        match self.on_point.as_mut() {
+            Some(on_point) => on_point(&point),
+            None => points.push(point),
+        }
+        Ok(())
+    }
+
+    /// Starts recording the raw bytes of the events consumed from now on,
+    /// giving whether that is possible. It is not for contexts made with
+    /// [`Context::new`], nor for the readers that give no warnings unless the
+    /// extensions type sets [`WaypointExtensions::RECORDS`] or the options
+    /// need the position otherwise, nor after peeking at an event that is
+    /// not consumed yet, as its bytes have already been read.
+    pub fn start_recording(&mut self) -> bool {
+        match &self.tracker {
+            Some(tracker) => {
+                tracker.start();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stops recording, giving the raw bytes of the events consumed since
+    /// [`Context::start_recording`].
+    pub fn stop_recording(&mut self) -> Option<Vec<u8>> {
+        self.tracker.as_ref().map(|tracker| tracker.stop())
+    }
+
+    /// How many bytes have been recorded since [`Context::start_recording`],
+    /// if recording is possible.
+    pub(crate) fn recorded(&self) -> Option<usize> {
+        self.tracker.as_ref().map(|tracker| tracker.recorded())
+    }
+
+    /// Fails with [`GpxError::LimitExceeded`] when `depth`, how deeply
+    /// elements nest inside one that is skipped or kept as it is, exceeds
+    /// [`ParseLimits::max_element_depth`](crate::ParseLimits::max_element_depth).
+    pub fn check_depth(&self, depth: usize) -> GpxResult<()> {
+        if depth > self.options.limits.max_element_depth {
+            return Err(GpxError::LimitExceeded("element depth"));
+        }
+        Ok(())
+    }
+
+    /// Counts a waypoint, route point or track point, failing with
+    /// [`GpxError::LimitExceeded`] once there are more than
+    /// [`ParseLimits::max_points`](crate::ParseLimits::max_points).
+    pub(crate) fn count_point(&mut self) -> GpxResult<()> {
+        self.points += 1;
+        match self.options.limits.max_points {
+            Some(max) if self.points > max as u64 => Err(GpxError::LimitExceeded("points")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails with [`GpxError::DeadlineExceeded`] once parsing has taken longer
+    /// than [`ParseOptions::timeout`]. Only every few calls look at the clock,
+    /// so it is cheap enough to call for every event of a loop. On targets
+    /// without a clock, a timeout fails with [`GpxError::UnsupportedOption`].
+    pub fn check_deadline(&mut self) -> GpxResult<()> {
+        let (timeout, started) = match (self.options.timeout, self.started) {
+            (Some(timeout), Some(started)) => (timeout, started),
+            (Some(_), None) => return Err(GpxError::UnsupportedOption("timeout")),
+            (None, _) => return Ok(()),
+        };
+        self.checks += 1;
+        if self.checks % DEADLINE_INTERVAL == 0 && started.elapsed() > timeout {
+            return Err(GpxError::DeadlineExceeded {
+                bytes: self.tracker.as_ref().map(|tracker| tracker.offset()),
+                points: self.points,
+            });
+        }
+        Ok(())
+    }
+
+    /// Reads the extensions of a point with `E`, or skips them when the
+    /// options say so.
     pub fn consume_waypoint_extensions(&mut self) -> GpxResult<E::ExtensionsValue> {
+        if self.options.extensions == ExtensionsMode::Skip {
+            EmptyExtensions::consume(self)?;
+            return Ok(Default::default());
+        }
         E::consume(self)
     }
 }
@@ -82,7 +352,7 @@ impl<R: Read, E: WaypointExtensions + Default> Context<R, E> {
 pub fn verify_starting_tag<R: Read, E: WaypointExtensions + Default>(
     context: &mut Context<R, E>,
     local_name: &'static str,
-) -> Result<Vec<OwnedAttribute>, GpxError> {
+) -> Result<Attributes, GpxError> {
     //makes sure the specified starting tag is the next tag on the stream
     //we ignore and skip all xmlevents except StartElement, Characters and EndElement
     loop {
@@ -94,7 +364,7 @@ pub fn verify_starting_tag<R: Read, E: WaypointExtensions + Default>(
                 if name.local_name != local_name {
                     return Err(GpxError::InvalidChildElement(name.local_name, local_name));
                 } else {
-                    return Ok(attributes);
+                    return Ok(Attributes::new(local_name, attributes));
                 }
             }
             Some(Ok(XmlEvent::EndElement { name, .. })) => {
@@ -103,19 +373,155 @@ pub fn verify_starting_tag<R: Read, E: WaypointExtensions + Default>(
             Some(Ok(XmlEvent::Characters(chars))) => {
                 return Err(GpxError::InvalidChildElement(chars, local_name));
             }
+            Some(Err(err)) => return Err(err.into()),
             Some(_) => {} //ignore other elements
             None => return Err(GpxError::MissingOpeningTag(local_name)),
         }
     }
 }
 
-pub(crate) fn create_context<R: Read, E: WaypointExtensions + Default>(reader: R, version: GpxVersion) -> Context<R, E> {
-    let parser_config = ParserConfig {
-        whitespace_to_characters: true, //convert Whitespace event to Characters
-        cdata_to_characters: true,      //convert CData event to Characters
-        ..ParserConfig::new()
-    };
-    let parser = EventReader::new_with_config(reader, parser_config);
-    let events = parser.into_iter().peekable();
-    Context::new(events, version)
+/// skip_element consumes and discards everything up to and including the end
+/// of the `local_name` element whose starting tag has been consumed.
+pub fn skip_element<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+    local_name: &'static str,
+) -> GpxResult<()> {
+    let mut depth = 1;
+    while let Some(event) = context.reader.next() {
+        context.check_deadline()?;
+        match event? {
+            XmlEvent::StartElement { .. } => depth += 1,
+            XmlEvent::EndElement { .. } => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+        context.check_depth(depth)?;
+    }
+    Err(GpxError::MissingClosingTag(local_name))
+}
+
+/// unknown_element handles an unexpected `child` of `parent`, whose starting
+/// tag is peeked at. In lenient mode it is skipped with a warning, otherwise
+/// it is an error.
+pub fn unknown_element<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+    child: String,
+    parent: &'static str,
+) -> GpxResult<()> {
+    if !context.options.lenient {
+        return Err(GpxError::InvalidChildElement(child, parent));
+    }
+    context.reader.next();
+    context.warn(GpxWarning::UnknownElement {
+        element: child,
+        parent,
+    });
+    skip_element(context, parent)
+}
+
+pub(crate) fn create_context<'a, R: Read, E: WaypointExtensions + Default>(reader: R, version: GpxVersion) -> Context<'a, TrackingReader<R>, E> {
+    create_context_with_options(reader, version, Default::default())
+}
+
+/// create_context_with_options makes a context following the position of
+/// its reader only when something needs it: the warnings and stripping of
+/// lenient mode, the recordings of `E`, a timeout or a point limit. Otherwise
+/// the input is read without looking at every byte, and the warnings have no
+/// position.
+pub(crate) fn create_context_with_options<'a, R: Read, E: WaypointExtensions + Default>(
+    reader: R,
+    version: GpxVersion,
+    options: ParseOptions,
+) -> Context<'a, TrackingReader<R>, E> {
+    let tracked = options.lenient || E::RECORDS || options.timeout.is_some() || options.limits.max_points.is_some();
+    create_context_with_events(reader, version, options, tracked, Events::parse)
+}
+
+/// create_tracked_context_with_options makes a context following the
+/// position of its reader whatever the options, for callers giving its
+/// warnings or handing it to other code.
+pub(crate) fn create_tracked_context_with_options<'a, R: Read, E: WaypointExtensions + Default>(
+    reader: R,
+    version: GpxVersion,
+    options: ParseOptions,
+) -> Context<'a, TrackingReader<R>, E> {
+    create_context_with_events(reader, version, options, true, Events::parse)
+}
+
+/// create_xml_rs_context_with_options makes a tracked context parsing
+/// `reader` with xml-rs whatever the features, which reads no further into
+/// `reader` than the events parsed, for callers following what is read from
+/// it.
+pub(crate) fn create_xml_rs_context_with_options<'a, R: Read, E: WaypointExtensions + Default>(
+    reader: R,
+    version: GpxVersion,
+    options: ParseOptions,
+) -> Context<'a, TrackingReader<R>, E> {
+    create_context_with_events(reader, version, options, true, Events::xml_rs)
+}
+
+/// Makes the events of a reader, following its position with the tracker if
+/// any.
+type MakeEvents<R> = fn(R, &ParseOptions, Option<Rc<Tracker>>) -> Events<TrackingReader<R>>;
+
+fn create_context_with_events<'a, R: Read, E: WaypointExtensions + Default>(
+    reader: R,
+    version: GpxVersion,
+    options: ParseOptions,
+    tracked: bool,
+    events: MakeEvents<R>,
+) -> Context<'a, TrackingReader<R>, E> {
+    let tracker = tracked.then(|| {
+        Rc::new(if options.lenient {
+            Tracker::stripping()
+        } else {
+            Tracker::default()
+        })
+    });
+    let events = events(reader, &options, tracker.clone()).peekable();
+    let mut context = Context::with_options(events, version, options);
+    context.tracker = tracker;
+    context
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::errors::{ErrorKind, GpxError};
+    use crate::parser::extensions::{EmptyExtensions, RawExtensions};
+    use crate::{GpxVersion, ParseOptions};
+
+    use super::{create_context_with_options, create_tracked_context_with_options};
+
+    #[test]
+    fn check_deadline_without_clock() {
+        let options = ParseOptions::strict().with_timeout(Duration::from_secs(60));
+        let mut context = create_context_with_options::<_, EmptyExtensions>(&b"<gpx/>"[..], GpxVersion::Gpx11, options);
+        assert!(context.check_deadline().is_ok());
+
+        // As on targets without a clock.
+        context.started = None;
+        let err = context.check_deadline().unwrap_err();
+        assert!(matches!(err, GpxError::UnsupportedOption("timeout")));
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn track_only_when_needed() {
+        let data = &b"<gpx/>"[..];
+        let context = create_context_with_options::<_, EmptyExtensions>(data, GpxVersion::Gpx11, ParseOptions::strict());
+        assert!(context.position().is_none());
+
+        let context = create_context_with_options::<_, RawExtensions>(data, GpxVersion::Gpx11, ParseOptions::strict());
+        assert!(context.position().is_some());
+        let context = create_context_with_options::<_, EmptyExtensions>(data, GpxVersion::Gpx11, ParseOptions::lenient());
+        assert!(context.position().is_some());
+        let context = create_tracked_context_with_options::<_, EmptyExtensions>(data, GpxVersion::Gpx11, ParseOptions::strict());
+        assert!(context.position().is_some());
+    }
 }
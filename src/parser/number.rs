@@ -0,0 +1,51 @@
+//! number handles parsing of GPX-spec decimals and other numbers, failing
+//! with the text at fault and where it was.
+
+use std::io::Read;
+use std::str::FromStr;
+
+use crate::errors::{GpxError, GpxResult};
+use crate::parser::extensions::WaypointExtensions;
+use crate::parser::{string, Context};
+
+/// parse parses `value`, the content of `element` or the value of the
+/// attribute of that name, as a number, ignoring the whitespace around it as
+/// XML schema numbers do.
+pub fn parse<T: FromStr>(value: &str, element: &'static str) -> GpxResult<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| GpxError::NotANumber(value.to_string(), element))
+}
+
+/// consume consumes a single number as tag content.
+pub fn consume<R, E, T>(context: &mut Context<R, E>, tagname: &'static str) -> GpxResult<T>
+where
+    R: Read,
+    E: WaypointExtensions + Default,
+    T: FromStr,
+{
+    let content = string::consume(context, tagname, false)?;
+    match content.trim().parse() {
+        Ok(value) => Ok(value),
+        Err(_) => Err(GpxError::NotANumber(content, tagname)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::GpxError;
+
+    use super::parse;
+
+    #[test]
+    fn parse_numbers() {
+        assert_eq!(parse::<f64>(" 12.5\n", "ele").unwrap(), 12.5);
+        assert_eq!(parse::<u8>("7", "sat").unwrap(), 7);
+
+        let err = parse::<f64>("12O.5", "ele").unwrap_err();
+        assert!(matches!(&err, GpxError::NotANumber(value, "ele") if value == "12O.5"));
+        assert_eq!(err.to_string(), "`ele` holds `12O.5`, which is not a number");
+        assert!(matches!(parse::<u8>("300", "hr"), Err(GpxError::NotANumber(_, "hr"))));
+    }
+}
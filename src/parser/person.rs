@@ -13,18 +13,7 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
     let mut person: Person = Default::default();
     verify_starting_tag(context, tagname)?;
 
-    loop {
-        let next_event = {
-            if let Some(next) = context.reader.peek() {
-                match next {
-                    Ok(n) => n,
-                    Err(_) => return Err(GpxError::EventParsingError("person")),
-                }
-            } else {
-                break;
-            }
-        };
-
+    while let Some(next_event) = context.peek_ok("person")? {
         match next_event {
             XmlEvent::StartElement { ref name, .. } => match name.local_name.as_ref() {
                 "name" => person.name = Some(string::consume(context, "name", false)?),
@@ -0,0 +1,715 @@
+//! quick parses documents with quick-xml, giving the events xml-rs gives
+//! with the configuration the parser uses: character data, CDATA and entity
+//! references coalesced into one `Characters` event, comments given only
+//! when kept, and the same errors for the same mistakes.
+//!
+//! Entities declared in the document type expand as text, where xml-rs
+//! parses them as markup; the elements of an entity are unlikely enough in
+//! a GPX document not to be worth parsing again.
+//!
+//! quick-xml reads UTF-8 only, so input in the other encodings xml-rs reads,
+//! UTF-16 with a byte order mark, ISO-8859-1 and US-ASCII, is transcoded to
+//! UTF-8 first. A document declaring any other encoding fails as with xml-rs.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use quick_xml::events::{BytesDecl, BytesStart, Event};
+use xml::attribute::OwnedAttribute;
+use xml::common::{is_name_char, is_name_start_char, is_whitespace_char, is_xml10_char, TextPosition, XmlVersion};
+use xml::name::OwnedName;
+use xml::namespace::{NamespaceStack, NS_XMLNS_PREFIX, NS_XMLNS_URI, NS_XML_PREFIX, NS_XML_URI};
+use xml::reader::{Error, Result, XmlEvent};
+use xml::Encoding;
+
+use crate::declaration::{encoding_value, read_prefix};
+use crate::parser::tracking::{Tracker, TrackingBufReader};
+use crate::reader::{ParseLimits, ParseOptions};
+
+/// How many bytes [`Transcoder`] reads from the input at once.
+const TRANSCODE_SIZE: usize = 4 * 1024;
+
+/// Transcoder reads input in the encodings xml-rs reads as UTF-8.
+struct Transcoder<R: Read> {
+    inner: R,
+    /// The encoding of the input as xml-rs reads it up to the XML
+    /// declaration, from the byte order mark, or `Default` without one,
+    /// and the encoding it was declared in when that is the one read.
+    encoding: Encoding,
+    started: bool,
+    /// The bytes read and not transcoded yet.
+    pending: Vec<u8>,
+    /// The UTF-8 transcoded and not read yet, from `at`.
+    transcoded: Vec<u8>,
+    at: usize,
+    /// The error met transcoding, to give once the characters before it
+    /// are read.
+    error: Option<io::Error>,
+}
+
+/// invalid_data gives the error of input that is not in its encoding.
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl<R: Read> Transcoder<R> {
+    fn new(inner: R) -> Transcoder<R> {
+        Transcoder {
+            inner,
+            encoding: Encoding::Default,
+            started: false,
+            pending: Vec::new(),
+            transcoded: Vec::new(),
+            at: 0,
+            error: None,
+        }
+    }
+
+    /// start reads the start of the input for its encoding, from its byte
+    /// order mark, then from its XML declaration, as xml-rs does.
+    fn start(&mut self) -> io::Result<()> {
+        let prefix = read_prefix(&mut self.inner)?;
+        let (encoding, bom) = match prefix.get(..2) {
+            Some(b"\xFF\xFE") => (Encoding::Utf16Le, 2),
+            Some(b"\xFE\xFF") => (Encoding::Utf16Be, 2),
+            _ => {
+                let text = String::from_utf8_lossy(&prefix);
+                let declared = encoding_value(&text).and_then(|range| text[range].parse().ok());
+                match declared {
+                    Some(encoding @ (Encoding::Latin1 | Encoding::Ascii)) => (encoding, 0),
+                    _ => (Encoding::Default, 0),
+                }
+            }
+        };
+        self.encoding = encoding;
+        self.pending = prefix[bom..].to_vec();
+        Ok(())
+    }
+
+    /// transcode transcodes the pending bytes that make whole characters, up
+    /// to the first that is not in the encoding of the input.
+    fn transcode(&mut self) {
+        self.transcoded.clear();
+        self.at = 0;
+        let mut index = 0;
+        let mut buffer = [0; 4];
+        while index < self.pending.len() {
+            let (c, len) = match self.encoding {
+                Encoding::Latin1 => (char::from(self.pending[index]), 1),
+                Encoding::Ascii if self.pending[index].is_ascii() => (char::from(self.pending[index]), 1),
+                Encoding::Ascii => {
+                    self.error = Some(invalid_data("char is not ASCII"));
+                    break;
+                }
+                _ => {
+                    let unit = |at: usize| {
+                        let bytes = [self.pending[at], self.pending[at + 1]];
+                        match self.encoding {
+                            Encoding::Utf16Be => u16::from_be_bytes(bytes),
+                            _ => u16::from_le_bytes(bytes),
+                        }
+                    };
+                    if index + 2 > self.pending.len() {
+                        break;
+                    }
+                    let first = unit(index);
+                    let len = if (0xD800..0xDC00).contains(&first) { 4 } else { 2 };
+                    if index + len > self.pending.len() {
+                        break;
+                    }
+                    let c = match len {
+                        4 => char::decode_utf16([first, unit(index + 2)]).next(),
+                        _ => char::decode_utf16([first]).next(),
+                    };
+                    match c {
+                        Some(Ok(c)) => (c, len),
+                        _ => {
+                            self.error = Some(invalid_data("invalid UTF-16"));
+                            break;
+                        }
+                    }
+                }
+            };
+            self.transcoded.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            index += len;
+        }
+        self.pending.drain(..index);
+    }
+}
+
+impl<R: Read> Read for Transcoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.started {
+            self.start()?;
+            self.started = true;
+        }
+        if matches!(self.encoding, Encoding::Default) {
+            if self.pending.is_empty() {
+                return self.inner.read(buf);
+            }
+            let len = self.pending.len().min(buf.len());
+            buf[..len].copy_from_slice(&self.pending[..len]);
+            self.pending.drain(..len);
+            return Ok(len);
+        }
+        while self.at == self.transcoded.len() {
+            if let Some(error) = self.error.take() {
+                return Err(error);
+            }
+            let start = self.pending.len();
+            self.pending.resize(start + TRANSCODE_SIZE, 0);
+            let read = match self.inner.read(&mut self.pending[start..]) {
+                Ok(read) => read,
+                Err(error) => {
+                    self.pending.truncate(start);
+                    return Err(error);
+                }
+            };
+            self.pending.truncate(start + read);
+            if read == 0 && self.pending.is_empty() {
+                return Ok(0);
+            } else if read == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.transcode();
+        }
+        let len = (self.transcoded.len() - self.at).min(buf.len());
+        buf[..len].copy_from_slice(&self.transcoded[self.at..self.at + len]);
+        self.at += len;
+        Ok(len)
+    }
+}
+
+/// QuickXmlEvents iterates the events of a document parsed by quick-xml.
+pub(crate) struct QuickXmlEvents<R: Read> {
+    reader: quick_xml::Reader<TrackingBufReader<Transcoder<R>>>,
+    tracker: Rc<Tracker>,
+    buffer: Vec<u8>,
+    keep_comments: bool,
+    limits: ParseLimits,
+    namespaces: NamespaceStack,
+    /// The names of the elements open, innermost last.
+    open: Vec<OwnedName>,
+    /// The entities declared in the document type, by name.
+    entities: HashMap<String, String>,
+    /// The character data read and not given yet.
+    text: String,
+    /// The events read and not given yet.
+    queued: VecDeque<XmlEvent>,
+    started: bool,
+    /// Whether an element was read.
+    rooted: bool,
+    finished: bool,
+    /// The error to give once the events before it are.
+    error: Option<Error>,
+}
+
+/// name splits `qualified` into its prefix and local name.
+fn name(qualified: &str) -> OwnedName {
+    match qualified.split_once(':') {
+        Some((prefix, local_name)) => OwnedName {
+            local_name: local_name.to_string(),
+            namespace: None,
+            prefix: Some(prefix.to_string()),
+        },
+        None => OwnedName {
+            local_name: qualified.to_string(),
+            namespace: None,
+            prefix: None,
+        },
+    }
+}
+
+impl<R: Read> QuickXmlEvents<R> {
+    pub(crate) fn new(reader: R, options: &ParseOptions, tracker: Rc<Tracker>) -> QuickXmlEvents<R> {
+        let reader = TrackingBufReader::new(Transcoder::new(reader), tracker.clone());
+        let mut reader = quick_xml::Reader::from_reader(reader);
+        let config = reader.config_mut();
+        config.expand_empty_elements = true;
+        // Closing tags are checked here, to fail as xml-rs does.
+        config.check_end_names = false;
+        config.allow_unmatched_ends = true;
+        QuickXmlEvents {
+            reader,
+            tracker,
+            buffer: Vec::new(),
+            keep_comments: options.keep_comments,
+            limits: options.limits,
+            namespaces: NamespaceStack::default(),
+            open: Vec::new(),
+            entities: HashMap::new(),
+            text: String::new(),
+            queued: VecDeque::new(),
+            started: false,
+            rooted: false,
+            finished: false,
+            error: None,
+        }
+    }
+
+    /// error gives a syntax error at the position read up to.
+    fn error(&self, message: impl Into<Cow<'static, str>>) -> Error {
+        let position = self.tracker.position();
+        let position = TextPosition {
+            row: position.line - 1,
+            column: position.column - 1,
+        };
+        Error::from((&position, message))
+    }
+
+    fn quick_error(&self, error: quick_xml::Error) -> Error {
+        match error {
+            quick_xml::Error::Io(error) => match Arc::try_unwrap(error) {
+                Ok(error) => error.into(),
+                Err(error) => io::Error::new(error.kind(), error.to_string()).into(),
+            },
+            error => self.error(error.to_string()),
+        }
+    }
+
+    fn utf8<'b>(&self, bytes: &'b [u8]) -> Result<&'b str> {
+        std::str::from_utf8(bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error).into())
+    }
+
+    /// check_name fails on the first character of `name` that XML does not
+    /// allow in names.
+    fn check_name(&self, name: &str) -> Result<()> {
+        let mut chars = name.chars();
+        let invalid = match chars.next() {
+            Some(c) if !is_name_start_char(c) => Some(c),
+            _ => chars.find(|c| !is_name_char(*c)),
+        };
+        match invalid {
+            Some(c) => Err(self.error(format!("Unexpected token inside qualified name: {}", c))),
+            None => Ok(()),
+        }
+    }
+
+    /// check_characters fails on the first character of `text` that XML
+    /// does not allow.
+    fn check_characters(&self, text: &str) -> Result<()> {
+        match text.chars().find(|c| !is_xml10_char(*c)) {
+            Some(c) => Err(self.error(format!("Invalid character U+{:04X}", c as u32))),
+            None => Ok(()),
+        }
+    }
+
+    /// read reads the next event of quick-xml, queuing the events it makes.
+    fn read(&mut self) -> Result<()> {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.clear();
+        let read = match self.reader.read_event_into(&mut buffer) {
+            Ok(event) => self.handle(event),
+            Err(error) => Err(self.quick_error(error)),
+        };
+        self.buffer = buffer;
+        read
+    }
+
+    fn handle(&mut self, event: Event) -> Result<()> {
+        // As with xml-rs, an empty document does not even start.
+        if !self.started && !matches!(event, Event::Eof) {
+            self.started = true;
+            if let Event::Decl(decl) = &event {
+                let start = self.start_document(decl)?;
+                self.queued.push_back(start);
+                return Ok(());
+            }
+            self.queued.push_back(XmlEvent::StartDocument {
+                version: XmlVersion::Version10,
+                encoding: self.read_encoding().to_string(),
+                standalone: None,
+            });
+        }
+        match event {
+            Event::Decl(_) => Err(self.misplaced_declaration()),
+            Event::Start(start) => {
+                self.flush_text()?;
+                self.start_element(&start)
+            }
+            Event::Empty(start) => {
+                self.flush_text()?;
+                self.start_element(&start)?;
+                self.end_element(start.name().as_ref())
+            }
+            Event::End(end) => {
+                self.flush_text()?;
+                self.end_element(end.name().as_ref())
+            }
+            Event::Text(text) => {
+                let raw = self.utf8(&text)?;
+                self.check_characters(raw)?;
+                let mut text = std::mem::take(&mut self.text);
+                let unescaped = self.unescape(raw, &mut text, false);
+                self.text = text;
+                unescaped
+            }
+            Event::CData(data) => {
+                let data = self.utf8(&data)?;
+                self.check_characters(data)?;
+                self.text.push_str(data);
+                Ok(())
+            }
+            Event::Comment(comment) => {
+                let comment = self.utf8(&comment)?;
+                self.check_characters(comment)?;
+                if self.keep_comments {
+                    self.flush_text()?;
+                    self.queued.push_back(XmlEvent::Comment(comment.to_string()));
+                }
+                Ok(())
+            }
+            Event::PI(instruction) => {
+                self.flush_text()?;
+                let name = self.utf8(instruction.target())?;
+                if name.eq_ignore_ascii_case("xml") {
+                    return Err(self.misplaced_declaration());
+                }
+                let data = self.utf8(instruction.content())?.trim_start();
+                self.queued.push_back(XmlEvent::ProcessingInstruction {
+                    name: name.to_string(),
+                    data: Some(data.to_string()).filter(|data| !data.is_empty()),
+                });
+                Ok(())
+            }
+            Event::DocType(doctype) => {
+                let doctype = self.utf8(&doctype)?;
+                self.declare_entities(doctype)
+            }
+            Event::Eof => {
+                self.flush_text()?;
+                if !self.open.is_empty() {
+                    return Err(self.error("Unexpected end of stream: still inside the root element"));
+                }
+                if !self.rooted {
+                    return Err(self.error("Unexpected end of stream: no root element found"));
+                }
+                self.queued.push_back(XmlEvent::EndDocument);
+                self.finished = true;
+                Ok(())
+            }
+        }
+    }
+
+    fn misplaced_declaration(&self) -> Error {
+        self.error(
+            "Invalid processing instruction: <?xml\nThe XML spec only allows \"<?xml\" at the very beginning of the \
+             file, with no whitespace, comments, or any elements before it",
+        )
+    }
+
+    fn start_document(&self, decl: &BytesDecl) -> Result<XmlEvent> {
+        let version = match &*decl.version().map_err(|error| self.quick_error(error))? {
+            b"1.0" => XmlVersion::Version10,
+            b"1.1" => XmlVersion::Version11,
+            other => return Err(self.error(format!("Invalid XML version: {}", String::from_utf8_lossy(other)))),
+        };
+        let encoding = match decl.encoding() {
+            Some(encoding) => {
+                let encoding = encoding.map_err(|error| self.quick_error(error.into()))?;
+                let encoding = self.utf8(&encoding)?.to_string();
+                self.check_encoding(&encoding)?;
+                encoding
+            }
+            None => self.read_encoding().to_string(),
+        };
+        let standalone = match decl.standalone() {
+            Some(standalone) => match &*standalone.map_err(|error| self.quick_error(error.into()))? {
+                b"yes" => Some(true),
+                b"no" => Some(false),
+                other => {
+                    return Err(self.error(format!(
+                        "Invalid standalone declaration value: {}",
+                        String::from_utf8_lossy(other)
+                    )))
+                }
+            },
+            None => None,
+        };
+        Ok(XmlEvent::StartDocument {
+            version,
+            encoding,
+            standalone,
+        })
+    }
+
+    /// check_encoding fails on a declared `encoding` that xml-rs does not
+    /// read, or that is not the one of the byte order mark.
+    fn check_encoding(&self, encoding: &str) -> Result<()> {
+        let declared: Encoding = encoding
+            .parse()
+            .map_err(|_| self.error(format!("Unsupported encoding: {}", encoding)))?;
+        let read = self.read_encoding();
+        let conflicting = match read {
+            Encoding::Utf16Le | Encoding::Utf16Be => declared != Encoding::Utf16,
+            _ => declared == Encoding::Utf16,
+        };
+        if conflicting {
+            return Err(self.error(format!("Declared encoding {}, but uses {}", declared, read)));
+        }
+        Ok(())
+    }
+
+    /// read_encoding gives the encoding xml-rs would read the document in
+    /// before its XML declaration.
+    fn read_encoding(&self) -> Encoding {
+        match self.reader.get_ref().get_ref().encoding {
+            Encoding::Utf16Le => Encoding::Utf16Le,
+            Encoding::Utf16Be => Encoding::Utf16Be,
+            _ => Encoding::Default,
+        }
+    }
+
+    /// flush_text queues the character data read, which outside of the root
+    /// element may only be whitespace, and is dropped.
+    fn flush_text(&mut self) -> Result<()> {
+        if self.text.is_empty() {
+            return Ok(());
+        }
+        let text = std::mem::take(&mut self.text);
+        if !self.open.is_empty() {
+            self.queued.push_back(XmlEvent::Characters(text));
+            return Ok(());
+        }
+        match text.chars().find(|c| !is_whitespace_char(*c)) {
+            Some(c) => Err(self.error(format!("Unexpected characters outside the root element: {}", c))),
+            None => Ok(()),
+        }
+    }
+
+    /// resolve gives the namespace of the prefix of `name`, a `kind` of
+    /// name for the error when it is not declared.
+    fn resolve(&self, name: &OwnedName, kind: &str) -> Result<Option<String>> {
+        match self.namespaces.get(name.prefix.as_deref().unwrap_or("")) {
+            Some("") => Ok(None),
+            Some(namespace) => Ok(Some(namespace.to_string())),
+            None => Err(self.error(format!("{} {} prefix is unbound", kind, name))),
+        }
+    }
+
+    fn start_element(&mut self, start: &BytesStart) -> Result<()> {
+        let qualified = start.name();
+        let qualified = self.utf8(qualified.as_ref())?;
+        self.check_name(qualified)?;
+        let mut element = name(qualified);
+        self.namespaces.push_empty();
+        let mut attributes: Vec<OwnedAttribute> = Vec::new();
+        for attribute in start.attributes().with_checks(false) {
+            let attribute = attribute.map_err(|error| self.quick_error(error.into()))?;
+            let key = self.utf8(attribute.key.as_ref())?;
+            self.check_name(key)?;
+            let value = self.attribute_value(&attribute.value)?;
+            if key == NS_XMLNS_PREFIX {
+                if [NS_XMLNS_PREFIX, NS_XML_PREFIX, NS_XML_URI, NS_XMLNS_URI].contains(&value.as_str()) {
+                    return Err(self.error(format!("Namespace '{}' cannot be default", value)));
+                }
+                self.namespaces.put("", value);
+            } else if let Some(prefix) = key.strip_prefix("xmlns:") {
+                if prefix == NS_XMLNS_PREFIX {
+                    return Err(self.error("Cannot redefine XMLNS prefix"));
+                } else if prefix == NS_XML_PREFIX && value != NS_XML_URI {
+                    return Err(self.error("Default XMLNS prefix cannot be rebound to another value"));
+                } else if value.is_empty() {
+                    return Err(self.error(format!("Cannot undefine prefix '{}'", prefix)));
+                }
+                self.namespaces.put(prefix, value);
+            } else {
+                let name = name(key);
+                if attributes.iter().any(|attribute| attribute.name == name) {
+                    return Err(self.error(format!("Attribute '{}' is redefined", name)));
+                }
+                attributes.push(OwnedAttribute { name, value });
+            }
+        }
+        element.namespace = self.resolve(&element, "Element")?;
+        for attribute in &mut attributes {
+            if attribute.name.prefix.is_some() {
+                attribute.name.namespace = self.resolve(&attribute.name, "Attribute")?;
+            }
+        }
+        self.open.push(element.clone());
+        self.rooted = true;
+        self.queued.push_back(XmlEvent::StartElement {
+            name: element,
+            attributes,
+            namespace: self.namespaces.squash(),
+        });
+        Ok(())
+    }
+
+    fn end_element(&mut self, qualified: &[u8]) -> Result<()> {
+        let qualified = self.utf8(qualified)?;
+        self.check_name(qualified)?;
+        let mut element = name(qualified);
+        element.namespace = self.resolve(&element, "Element")?;
+        match self.open.pop() {
+            Some(open) if open == element => {
+                self.namespaces.pop();
+                self.queued.push_back(XmlEvent::EndElement { name: element });
+                Ok(())
+            }
+            Some(open) => Err(self.error(format!("Unexpected closing tag: {} != {}", element, open))),
+            None => Err(self.error("Unexpected token: </")),
+        }
+    }
+
+    fn attribute_value(&self, raw: &[u8]) -> Result<String> {
+        let raw = self.utf8(raw)?;
+        self.check_characters(raw)?;
+        if !raw.contains('&') {
+            return Ok(raw.to_string());
+        }
+        let mut value = String::with_capacity(raw.len());
+        self.unescape(raw, &mut value, true)?;
+        Ok(value)
+    }
+
+    /// unescape appends `raw` to `out`, replacing its references. Entities
+    /// declared in the document type expand into references of their own in
+    /// character data, but not in `attribute` values.
+    fn unescape(&self, raw: &str, out: &mut String, attribute: bool) -> Result<()> {
+        if !raw.contains('&') {
+            out.push_str(raw);
+            return Ok(());
+        }
+        let mut rest = raw;
+        while let Some(at) = rest.find('&') {
+            out.push_str(&rest[..at]);
+            let reference = &rest[at + 1..];
+            let end = reference
+                .find(|c: char| c != '#' && !is_name_char(c))
+                .unwrap_or(reference.len());
+            match reference[end..].chars().next() {
+                Some(';') => {}
+                other => {
+                    return Err(self.error(format!(
+                        "Unexpected token inside entity: {}",
+                        other.unwrap_or('<')
+                    )))
+                }
+            }
+            let mut expansions = 0;
+            self.reference(&reference[..end], out, attribute, &mut expansions, out.len())?;
+            rest = &reference[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(())
+    }
+
+    /// reference appends the value of the reference to `name` to `out`,
+    /// counting the `expansions` of entities since `start` of `out`, which
+    /// are limited like xml-rs limits them.
+    fn reference(&self, name: &str, out: &mut String, attribute: bool, expansions: &mut u8, start: usize) -> Result<()> {
+        let c = match name {
+            "" => return Err(self.error("Encountered empty entity")),
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "apos" => '\'',
+            "quot" => '"',
+            _ if name.starts_with('#') => self.character(&name[1..])?,
+            _ => {
+                let value = match self.entities.get(name) {
+                    Some(value) => value,
+                    None => return Err(self.error(format!("Unexpected entity: {}", name))),
+                };
+                if attribute {
+                    out.push_str(value);
+                    return Ok(());
+                }
+                *expansions = expansions.saturating_add(1);
+                if *expansions > self.limits.max_entity_expansion_depth
+                    || out.len() - start + value.len() > self.limits.max_entity_expansion_length
+                {
+                    return Err(self.error("Entity too big"));
+                }
+                let mut rest = value.as_str();
+                while let Some(at) = rest.find('&') {
+                    out.push_str(&rest[..at]);
+                    let reference = &rest[at + 1..];
+                    let end = reference.find(';').unwrap_or(reference.len());
+                    self.reference(&reference[..end], out, attribute, expansions, start)?;
+                    rest = reference.get(end + 1..).unwrap_or("");
+                }
+                out.push_str(rest);
+                return Ok(());
+            }
+        };
+        out.push(c);
+        Ok(())
+    }
+
+    /// character gives the character of a numeric reference.
+    fn character(&self, number: &str) -> Result<char> {
+        let value = match number.strip_prefix('x') {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => number.parse(),
+        }
+        .map_err(|_| self.error(format!("Invalid numeric entity: {}", number)))?;
+        match char::from_u32(value) {
+            Some(c) if is_xml10_char(c) => Ok(c),
+            _ => Err(self.error(format!("Invalid character U+{:04X}", value))),
+        }
+    }
+
+    /// declare_entities keeps the general entities declared in the internal
+    /// subset of `doctype`, with their character references replaced, the
+    /// first declaration of a name winning. Entities declared outside of the
+    /// document expand to nothing, as with xml-rs.
+    fn declare_entities(&mut self, doctype: &str) -> Result<()> {
+        let mut rest = doctype;
+        while let Some(at) = rest.find("<!ENTITY") {
+            rest = rest[at + "<!ENTITY".len()..].trim_start();
+            if rest.starts_with('%') {
+                continue;
+            }
+            let end = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+            let name = &rest[..end];
+            rest = rest[end..].trim_start();
+            let mut value = String::new();
+            if let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') {
+                let close = match rest[1..].find(quote) {
+                    Some(close) => close + 1,
+                    None => return Err(self.error("Unexpected end of stream")),
+                };
+                let mut raw = &rest[1..close];
+                self.check_characters(raw)?;
+                while let Some(at) = raw.find("&#") {
+                    value.push_str(&raw[..at]);
+                    let reference = &raw[at + 2..];
+                    let end = reference.find(';').unwrap_or(reference.len());
+                    value.push(self.character(&reference[..end])?);
+                    raw = reference.get(end + 1..).unwrap_or("");
+                }
+                value.push_str(raw);
+                rest = &rest[close + 1..];
+            }
+            self.entities.entry(name.to_string()).or_insert(value);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for QuickXmlEvents<R> {
+    type Item = Result<XmlEvent>;
+
+    fn next(&mut self) -> Option<Result<XmlEvent>> {
+        while self.queued.is_empty() && !self.finished {
+            if let Err(error) = self.read() {
+                // The character data before the error is given first.
+                if !self.open.is_empty() && !self.text.is_empty() {
+                    self.queued.push_back(XmlEvent::Characters(std::mem::take(&mut self.text)));
+                }
+                self.error = Some(error);
+                self.finished = true;
+            }
+        }
+        match self.queued.pop_front() {
+            Some(event) => Some(Ok(event)),
+            None => self.error.take().map(Err),
+        }
+    }
+}
@@ -5,25 +5,22 @@ use std::io::Read;
 use xml::reader::XmlEvent;
 
 use crate::errors::{GpxError, GpxResult};
-use crate::parser::{Context, extensions, link, string, verify_starting_tag, waypoint};
+use crate::parser::{Context, extensions, integer, link, string, unknown_element, verify_starting_tag, waypoint};
 use crate::parser::extensions::WaypointExtensions;
-use crate::Route;
+use crate::{GpxVersion, Route};
 
 /// consume consumes a GPX route from the `reader` until it ends.
 pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<Route<E>> {
     let mut route: Route<E> = Default::default();
     verify_starting_tag(context, "rte")?;
+    let version = context.version();
+    context.reserve_points(&mut route.points);
 
     loop {
-        let next_event = {
-            if let Some(next) = context.reader.peek() {
-                match next {
-                    Ok(n) => n,
-                    Err(_) => return Err(GpxError::EventParsingError("route event")),
-                }
-            } else {
-                break;
-            }
+        context.check_deadline()?;
+        let next_event = match context.peek_ok("route")? {
+            Some(event) => event,
+            None => break,
         };
 
         match next_event {
@@ -41,22 +38,31 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
                     route.source = Some(string::consume(context, "src", true)?);
                 }
                 "number" => {
-                    route.number = Some(string::consume(context, "number", false)?.parse()?)
+                    route.number = integer::consume(context, "number", None)?
                 }
                 "type" => {
                     route.type_ = Some(string::consume(context, "type", false)?);
                 }
                 "rtept" => {
-                    route.points.push(waypoint::consume(context, "rtept")?);
+                    if let Some(point) = waypoint::consume_or_skip(context, "rtept")? {
+                        context.keep_point(point, &mut route.points)?;
+                    }
                 }
                 "link" => {
                     route.links.push(link::consume(context)?);
                 }
+                "url" if version == GpxVersion::Gpx10 => {
+                    link::consume_gpx10(context, "url", &mut route.links)?;
+                }
+                "urlname" if version == GpxVersion::Gpx10 => {
+                    link::consume_gpx10(context, "urlname", &mut route.links)?;
+                }
                 "extensions" => {
                     extensions::EmptyExtensions::consume(context)?;
                 }
                 child => {
-                    return Err(GpxError::InvalidChildElement(String::from(child), "route"));
+                    let child = String::from(child);
+                    unknown_element(context, child, "route")?;
                 }
             },
             XmlEvent::EndElement { ref name } => {
@@ -67,6 +73,7 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
                     ));
                 }
                 context.reader.next(); //consume the end tag
+                context.fit_points(&mut route.points);
                 return Ok(route);
             }
             _ => {
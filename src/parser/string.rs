@@ -18,14 +18,22 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(
     let mut string = String::new();
 
     for event in context.reader() {
-        match event? {
+        match event.map_err(|err| GpxError::in_element(tagname, err))? {
             XmlEvent::StartElement { ref name, .. } => {
                 return Err(GpxError::InvalidChildElement(
                     name.local_name.clone(),
                     tagname,
                 ));
             }
-            XmlEvent::Characters(content) => string = content,
+            // Text can come in several events, split by comments for one.
+            // The first is taken as it is, and the others appended to it.
+            XmlEvent::Characters(content) => {
+                if string.is_empty() {
+                    string = content;
+                } else {
+                    string.push_str(&content);
+                }
+            }
             XmlEvent::EndElement { ref name } => {
                 if name.local_name != tagname {
                     return Err(GpxError::InvalidClosingTag(
@@ -63,6 +71,20 @@ mod tests {
         assert_eq!(result.unwrap(), "hello world");
     }
 
+    #[test]
+    fn consume_split_string() {
+        let result = consume!("<string>a&amp;b</string>", GpxVersion::Gpx11, "string", false);
+        assert_eq!(result.unwrap(), "a&b");
+
+        let result = consume!(
+            "<string>before <!-- note --> after &lt;3</string>",
+            GpxVersion::Gpx11,
+            "string",
+            false
+        );
+        assert_eq!(result.unwrap(), "before  after <3");
+    }
+
     #[test]
     fn consume_new_tag() {
         // cannot start new tag inside string
@@ -0,0 +1,256 @@
+//! style handles parsing of the track style extensions.
+
+use std::io::Read;
+
+use xml::reader::XmlEvent;
+
+use crate::errors::{GpxError, GpxResult, GpxWarning};
+use crate::parser::extensions::{WaypointExtensions, GARMIN_NAMESPACE};
+use crate::parser::{skip_element, string, verify_starting_tag, Context};
+use crate::TrackStyle;
+
+/// The namespace of the gpx_style extensions.
+pub(crate) const GPX_STYLE_NAMESPACE: &str = "http://www.topografix.com/GPX/gpx_style/0/2";
+
+/// consume consumes the extensions of a track, giving its style from the
+/// gpx_style `line` or Garmin `TrackExtension` elements, if any. Other
+/// extensions, and elements of those names in other namespaces, are ignored.
+/// A style value that cannot be read is left out, with a warning in lenient
+/// mode.
+pub fn consume<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+) -> GpxResult<Option<TrackStyle>> {
+    verify_starting_tag(context, "extensions")?;
+    let mut style = TrackStyle::default();
+    let mut garmin_color = None;
+
+    while let Some(next_event) = context.peek_ok("extensions")? {
+        match next_event {
+            XmlEvent::StartElement { ref name, .. } => match (
+                name.namespace.as_deref().map(|uri| uri.trim_end_matches('/')),
+                name.local_name.as_ref(),
+            ) {
+                (Some(GPX_STYLE_NAMESPACE), "line") => consume_line(context, &mut style)?,
+                (Some(GARMIN_NAMESPACE), "TrackExtension") => garmin_color = consume_track_extension(context)?,
+                _ => {
+                    context.reader.next();
+                    skip_element(context, "extensions")?;
+                }
+            },
+            XmlEvent::EndElement { .. } => {
+                context.reader.next(); //consume the end tag
+                if style.color.is_none() {
+                    style.color = garmin_color;
+                }
+                return Ok(Some(style).filter(|style| *style != TrackStyle::default()));
+            }
+            _ => {
+                context.reader.next(); //consume and ignore this event
+            }
+        }
+    }
+
+    Err(GpxError::MissingClosingTag("extensions"))
+}
+
+/// consume_line consumes a gpx_style `line` element into `style`.
+fn consume_line<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+    style: &mut TrackStyle,
+) -> GpxResult<()> {
+    verify_starting_tag(context, "line")?;
+
+    while let Some(next_event) = context.peek_ok("line")? {
+        match next_event {
+            XmlEvent::StartElement { ref name, .. } => match name.local_name.as_ref() {
+                "color" => style.color = value(context, "color", TrackStyle::hex_color)?,
+                "opacity" => style.opacity = value(context, "opacity", |value| value.trim().parse().ok())?,
+                "width" => style.width = value(context, "width", |value| value.trim().parse().ok())?,
+                _ => {
+                    context.reader.next();
+                    skip_element(context, "line")?;
+                }
+            },
+            XmlEvent::EndElement { .. } => {
+                context.reader.next(); //consume the end tag
+                return Ok(());
+            }
+            _ => {
+                context.reader.next(); //consume and ignore this event
+            }
+        }
+    }
+
+    Err(GpxError::MissingClosingTag("line"))
+}
+
+/// value consumes the style value of `element`, read with `parse`. One that
+/// is empty or cannot be read is left out, with a warning in lenient mode.
+fn value<R, E, T>(
+    context: &mut Context<R, E>,
+    element: &'static str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> GpxResult<Option<T>>
+where
+    R: Read,
+    E: WaypointExtensions + Default,
+{
+    let value = string::consume(context, element, true)?;
+    let parsed = parse(&value);
+    if parsed.is_none() && context.options.lenient {
+        context.warn(GpxWarning::InvalidStyle { element, value });
+    }
+    Ok(parsed)
+}
+
+/// consume_track_extension consumes a Garmin `TrackExtension` element,
+/// giving the color of its `DisplayColor`.
+fn consume_track_extension<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+) -> GpxResult<Option<String>> {
+    verify_starting_tag(context, "TrackExtension")?;
+    let mut color = None;
+
+    while let Some(next_event) = context.peek_ok("TrackExtension")? {
+        match next_event {
+            XmlEvent::StartElement { ref name, .. } if name.local_name == "DisplayColor" => {
+                let name = string::consume(context, "DisplayColor", true)?;
+                color = TrackStyle::garmin_color(name.trim()).map(String::from);
+            }
+            XmlEvent::StartElement { .. } => {
+                context.reader.next();
+                skip_element(context, "TrackExtension")?;
+            }
+            XmlEvent::EndElement { .. } => {
+                context.reader.next(); //consume the end tag
+                return Ok(color);
+            }
+            _ => {
+                context.reader.next(); //consume and ignore this event
+            }
+        }
+    }
+
+    Err(GpxError::MissingClosingTag("TrackExtension"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::errors::{GpxResult, GpxWarning};
+    use crate::parser::create_context_with_options;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{GpxVersion, ParseOptions, TrackStyle};
+
+    use super::consume;
+
+    /// Consumes the track extensions `xml` with `options`, giving the
+    /// warnings too.
+    fn style(xml: &str, options: ParseOptions) -> (GpxResult<Option<TrackStyle>>, Vec<GpxWarning>) {
+        let mut context =
+            create_context_with_options::<_, EmptyExtensions>(BufReader::new(xml.as_bytes()), GpxVersion::Gpx11, options);
+        let result = consume(&mut context);
+        let warnings = context.warnings().iter().map(|warning| warning.warning.clone()).collect();
+        (result, warnings)
+    }
+
+    #[test]
+    fn consume_gpx_style() {
+        let style = consume!(
+            "<extensions>
+                <gpx_style:line xmlns:gpx_style=\"http://www.topografix.com/GPX/gpx_style/0/2\">
+                    <gpx_style:color>ff0000</gpx_style:color>
+                    <gpx_style:opacity>0.5</gpx_style:opacity>
+                    <gpx_style:width>2.5</gpx_style:width>
+                    <gpx_style:pattern>dash</gpx_style:pattern>
+                </gpx_style:line>
+            </extensions>",
+            GpxVersion::Gpx11
+        );
+
+        assert_eq!(
+            style.unwrap(),
+            Some(TrackStyle {
+                color: Some("#FF0000".into()),
+                opacity: Some(0.5),
+                width: Some(2.5),
+            })
+        );
+    }
+
+    #[test]
+    fn consume_garmin_display_color() {
+        let style = consume!(
+            "<extensions>
+                <other><DisplayColor>Red</DisplayColor></other>
+                <gpxx:TrackExtension xmlns:gpxx=\"http://www.garmin.com/xmlschemas/GpxExtensions/v3\">
+                    <gpxx:DisplayColor>DarkCyan</gpxx:DisplayColor>
+                </gpxx:TrackExtension>
+            </extensions>",
+            GpxVersion::Gpx11
+        );
+
+        assert_eq!(
+            style.unwrap().unwrap().color,
+            Some(String::from("#008080"))
+        );
+    }
+
+    #[test]
+    fn consume_without_style() {
+        let style = consume!(
+            "<extensions><a><b>no style</b></a></extensions>",
+            GpxVersion::Gpx11
+        );
+
+        assert_eq!(style.unwrap(), None);
+    }
+
+    #[test]
+    fn consume_line_of_other_namespace() {
+        let xml = "<extensions>
+            <other:line xmlns:other=\"urn:x\"><width>thick</width></other:line>
+        </extensions>";
+        for options in [ParseOptions::strict(), ParseOptions::lenient()] {
+            let (result, warnings) = style(xml, options);
+            assert_eq!(result.unwrap(), None);
+            assert!(warnings.is_empty());
+        }
+    }
+
+    #[test]
+    fn consume_unreadable_style_values() {
+        let xml = "<extensions>
+            <line xmlns=\"http://www.topografix.com/GPX/gpx_style/0/2\">
+                <color></color>
+                <opacity>0.5</opacity>
+                <width>thick</width>
+            </line>
+        </extensions>";
+        let expected = TrackStyle {
+            opacity: Some(0.5),
+            ..Default::default()
+        };
+
+        let (result, warnings) = style(xml, ParseOptions::strict());
+        assert_eq!(result.unwrap(), Some(expected.clone()));
+        assert!(warnings.is_empty());
+
+        let (result, warnings) = style(xml, ParseOptions::lenient());
+        assert_eq!(result.unwrap(), Some(expected));
+        assert_eq!(
+            warnings,
+            [
+                GpxWarning::InvalidStyle {
+                    element: "color",
+                    value: String::new(),
+                },
+                GpxWarning::InvalidStyle {
+                    element: "width",
+                    value: String::from("thick"),
+                },
+            ]
+        );
+    }
+}
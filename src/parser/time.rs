@@ -5,12 +5,26 @@ use std::io::Read;
 /// format: [-]CCYY-MM-DDThh:mm:ss[Z|(+|-)hh:mm]
 #[cfg(feature = "use-serde")]
 use serde::{Deserialize, Serialize};
-use time::{format_description::well_known::Iso8601, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use time::{format_description::well_known::Iso8601, Date, Duration, OffsetDateTime, PrimitiveDateTime};
 
-use crate::errors::GpxResult;
+use crate::errors::{GpxError, GpxResult, GpxWarning};
 use crate::parser::{Context, string};
 use crate::parser::extensions::WaypointExtensions;
 
+/// A point in time, between the years -9999 and 9999.
+///
+/// It converts from and to `time::OffsetDateTime`, and from and to unix
+/// times as plain integers, for code that keeps the `time` crate out of its
+/// API.
+///
+/// ```
+/// use gpx::Time;
+///
+/// let time = Time::from_unix_millis(1_255_820_323_250).unwrap();
+/// assert_eq!(time.unix(), 1_255_820_323);
+/// assert_eq!(time.unix_millis(), 1_255_820_323_250);
+/// assert_eq!(time.format().unwrap(), "2009-10-17T22:58:43.250000000Z");
+/// ```
 #[derive(Debug, Clone, Copy, Eq, Ord, PartialOrd, PartialEq, Hash)]
 #[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
 pub struct Time(OffsetDateTime);
@@ -20,6 +34,42 @@ impl Time {
     pub fn format(&self) -> GpxResult<String> {
         self.0.format(&Iso8601::DEFAULT).map_err(From::from)
     }
+
+    /// Gives the time `seconds` after the unix epoch, 1970-01-01T00:00:00Z,
+    /// or before it when negative. Beyond the years -9999 to 9999 it fails
+    /// with [`GpxError::UnixTimeOutOfRange`].
+    pub fn from_unix(seconds: i64) -> GpxResult<Time> {
+        OffsetDateTime::from_unix_timestamp(seconds)
+            .map(Time)
+            .map_err(|_| GpxError::UnixTimeOutOfRange(seconds))
+    }
+
+    /// Gives the time `millis` milliseconds after the unix epoch, like
+    /// [`Time::from_unix`].
+    pub fn from_unix_millis(millis: i64) -> GpxResult<Time> {
+        OffsetDateTime::from_unix_timestamp_nanos(i128::from(millis) * 1_000_000)
+            .map(Time)
+            .map_err(|_| GpxError::UnixTimeOutOfRange(millis))
+    }
+
+    /// Gives the seconds since the unix epoch, rounded down.
+    pub fn unix(&self) -> i64 {
+        self.0.unix_timestamp()
+    }
+
+    /// Gives the milliseconds since the unix epoch, rounded down.
+    pub fn unix_millis(&self) -> i64 {
+        // Within the years -9999 to 9999, milliseconds fit an i64.
+        self.0.unix_timestamp_nanos().div_euclid(1_000_000) as i64
+    }
+}
+
+impl std::ops::Sub for Time {
+    type Output = Duration;
+
+    fn sub(self, rhs: Time) -> Duration {
+        self.0 - rhs.0
+    }
 }
 
 impl From<OffsetDateTime> for Time {
@@ -34,24 +84,127 @@ impl From<Time> for OffsetDateTime {
     }
 }
 
+/// Unit assumed for a numeric unix epoch timestamp.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
+pub enum EpochUnit {
+    Seconds,
+    Milliseconds,
+}
+
+/// Seconds since the epoch of 1990-01-01T00:00:00Z and 2100-01-01T00:00:00Z,
+/// the range in which a numeric timestamp is considered plausible.
+const PLAUSIBLE_EPOCH_SECONDS: std::ops::Range<i64> = 631_152_000..4_102_444_800;
+
+/// Interprets an all-digits string as a unix epoch timestamp, in milliseconds
+/// when it has 13 digits and in seconds otherwise.
+fn parse_epoch(time_str: &str) -> Option<(OffsetDateTime, EpochUnit)> {
+    let value: i64 = time_str.parse().ok()?;
+    let (seconds, nanos, unit) = if time_str.len() == 13 {
+        (value / 1000, (value % 1000) * 1_000_000, EpochUnit::Milliseconds)
+    } else {
+        (value, 0, EpochUnit::Seconds)
+    };
+    if !PLAUSIBLE_EPOCH_SECONDS.contains(&seconds) {
+        return None;
+    }
+    let time = OffsetDateTime::from_unix_timestamp_nanos(i128::from(seconds * 1_000_000_000 + nanos)).ok()?;
+    Some((time, unit))
+}
+
 /// consume consumes an element as a time.
 pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<Time> {
     let time_str = string::consume(context, "time", false)?;
+    parse(context, &time_str)
+}
+
+/// consume_or_drop consumes an element as a time like [`consume`], except
+/// that in lenient mode a time that cannot be parsed is dropped with a
+/// warning, giving `None`.
+pub fn consume_or_drop<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+) -> GpxResult<Option<Time>> {
+    let time_str = string::consume(context, "time", false)?;
+    match parse(context, &time_str) {
+        Ok(time) => Ok(Some(time)),
+        Err(GpxError::Iso8601Error(_)) | Err(GpxError::EpochTimestamp(_)) if context.options.lenient => {
+            context.warn(GpxWarning::MalformedTime { value: time_str });
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
 
+/// parse parses the content of a time element, copying it only into errors
+/// and warnings.
+pub(crate) fn parse<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>, time_str: &str) -> GpxResult<Time> {
     // Try parsing as ISO 8601 with offset
-    let time = OffsetDateTime::parse(&time_str, &Iso8601::PARSING).or_else(|_| {
+    let time = OffsetDateTime::parse(time_str, &Iso8601::PARSING).or_else(|_| {
         // Try parsing as ISO 8601 without offset, assuming UTC
-        PrimitiveDateTime::parse(&time_str, &Iso8601::PARSING).map(PrimitiveDateTime::assume_utc)
-    })?;
+        PrimitiveDateTime::parse(time_str, &Iso8601::PARSING).map(PrimitiveDateTime::assume_utc)
+    });
+
+    let time = match time {
+        Ok(time) => time,
+        // Some trackers write unix epoch timestamps, accept them only when lenient
+        Err(_) if !time_str.is_empty() && time_str.bytes().all(|b| b.is_ascii_digit()) => {
+            if !context.options.lenient {
+                return Err(GpxError::EpochTimestamp(time_str.to_owned()));
+            }
+            let (time, unit) = match parse_epoch(time_str) {
+                Some(parsed) => parsed,
+                None => return Err(GpxError::EpochTimestamp(time_str.to_owned())),
+            };
+            context.warn(GpxWarning::EpochTimestamp {
+                value: time_str.to_owned(),
+                unit,
+            });
+            time
+        }
+        // Some generators leave out the time of day, take it as midnight UTC when lenient
+        Err(err) => match Date::parse(time_str, &Iso8601::PARSING) {
+            Ok(date) if context.options.lenient => {
+                context.warn(GpxWarning::DateOnly {
+                    value: time_str.to_owned(),
+                });
+                date.midnight().assume_utc()
+            }
+            _ => return Err(err.into()),
+        },
+    };
 
-    Ok(time.to_offset(UtcOffset::UTC).into())
+    // A time in range at its own offset can be out of it in UTC, such as
+    // 9999-12-31T23:00:00-05:00, which to_offset would panic on.
+    match OffsetDateTime::from_unix_timestamp_nanos(time.unix_timestamp_nanos()) {
+        Ok(time) => Ok(time.into()),
+        Err(range) => Err(time::error::Parse::from(time::error::TryFromParsed::from(range)).into()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::GpxVersion;
+    use std::io::BufReader;
+
+    use time::format_description::well_known::Iso8601;
+    use time::OffsetDateTime;
+
+    use crate::errors::{GpxError, GpxResult, GpxWarning};
+    use crate::parser::create_context_with_options;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{GpxVersion, ParseOptions};
 
-    use super::consume;
+    use super::{consume, EpochUnit, Time};
+
+    fn consume_with_options(xml: &str, options: ParseOptions) -> (GpxResult<Time>, Vec<GpxWarning>) {
+        let mut context = create_context_with_options::<_, EmptyExtensions>(
+            BufReader::new(xml.as_bytes()),
+            GpxVersion::Gpx11,
+            options,
+        );
+        let result = consume(&mut context);
+        let warnings = context.warnings().iter().map(|w| w.warning.clone()).collect();
+        (result, warnings)
+    }
 
     #[test]
     fn consume_time() {
@@ -96,4 +249,123 @@ mod tests {
         let result = consume!("<time>2021-10-10T09:55:20.952</time>", GpxVersion::Gpx11);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn consume_date_only() {
+        let (result, warnings) = consume_with_options("<time>2009-10-17</time>", ParseOptions::lenient());
+        let expect: Time = OffsetDateTime::parse("2009-10-17T00:00:00Z", &Iso8601::PARSING)
+            .unwrap()
+            .into();
+        assert_eq!(result.unwrap(), expect);
+        assert_eq!(
+            warnings,
+            vec![GpxWarning::DateOnly {
+                value: "2009-10-17".into()
+            }]
+        );
+
+        let (result, _) = consume_with_options("<time>2009-10-17</time>", ParseOptions::strict());
+        assert!(matches!(result, Err(GpxError::Iso8601Error(_))));
+
+        // Full dates with a time of day are unaffected, and give no warning.
+        let (result, warnings) = consume_with_options("<time>2009-10-17T18:37:26Z</time>", ParseOptions::lenient());
+        let expect: Time = OffsetDateTime::parse("2009-10-17T18:37:26Z", &Iso8601::PARSING)
+            .unwrap()
+            .into();
+        assert_eq!(result.unwrap(), expect);
+        assert!(warnings.is_empty());
+
+        // An invalid date is still an error.
+        let (result, _) = consume_with_options("<time>2009-13-17</time>", ParseOptions::lenient());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn consume_epoch_seconds() {
+        let (result, warnings) = consume_with_options("<time>1565653500</time>", ParseOptions::lenient());
+        let expect: Time = OffsetDateTime::from_unix_timestamp(1565653500).unwrap().into();
+        assert_eq!(result.unwrap(), expect);
+        assert_eq!(
+            warnings,
+            vec![GpxWarning::EpochTimestamp {
+                value: "1565653500".into(),
+                unit: EpochUnit::Seconds
+            }]
+        );
+
+        let (result, _) = consume_with_options("<time>1565653500</time>", ParseOptions::strict());
+        match result {
+            Err(GpxError::EpochTimestamp(raw)) => assert_eq!(raw, "1565653500"),
+            other => panic!("expected epoch timestamp error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn consume_epoch_milliseconds() {
+        let (result, warnings) = consume_with_options("<time>1565653500250</time>", ParseOptions::lenient());
+        let expect: Time = OffsetDateTime::from_unix_timestamp_nanos(1_565_653_500_250_000_000)
+            .unwrap()
+            .into();
+        assert_eq!(result.unwrap(), expect);
+        assert_eq!(
+            warnings,
+            vec![GpxWarning::EpochTimestamp {
+                value: "1565653500250".into(),
+                unit: EpochUnit::Milliseconds
+            }]
+        );
+
+        let (result, _) = consume_with_options("<time>1565653500250</time>", ParseOptions::strict());
+        assert!(matches!(result, Err(GpxError::EpochTimestamp(_))));
+    }
+
+    #[test]
+    fn consume_epoch_too_small() {
+        // 1989-12-31T23:59:59Z
+        for options in [ParseOptions::lenient(), ParseOptions::strict()] {
+            let (result, warnings) = consume_with_options("<time>631151999</time>", options);
+            assert!(matches!(result, Err(GpxError::EpochTimestamp(ref raw)) if raw == "631151999"));
+            assert!(warnings.is_empty());
+        }
+    }
+
+    #[test]
+    fn consume_epoch_too_large() {
+        // 2100-01-01T00:00:00Z, in seconds and in milliseconds
+        for value in ["4102444800", "4102444800000"] {
+            for options in [ParseOptions::lenient(), ParseOptions::strict()] {
+                let xml = format!("<time>{}</time>", value);
+                let (result, warnings) = consume_with_options(&xml, options);
+                assert!(matches!(result, Err(GpxError::EpochTimestamp(ref raw)) if raw == value));
+                assert!(warnings.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn unix_time() {
+        let time = Time::from_unix(0).unwrap();
+        assert_eq!(OffsetDateTime::from(time), OffsetDateTime::UNIX_EPOCH);
+
+        // Before the epoch, both round down.
+        let time = Time::from_unix_millis(-1_500).unwrap();
+        assert_eq!((time.unix(), time.unix_millis()), (-2, -1_500));
+
+        // The last second of 9999 and the first of -9999.
+        for seconds in [253_402_300_799, -377_705_116_800] {
+            let time = Time::from_unix(seconds).unwrap();
+            assert_eq!(time.unix(), seconds);
+            assert_eq!(time.unix_millis(), seconds * 1000);
+            assert!(matches!(
+                Time::from_unix(seconds + seconds.signum()),
+                Err(GpxError::UnixTimeOutOfRange(value)) if value == seconds + seconds.signum()
+            ));
+        }
+        // Milliseconds given as seconds.
+        assert!(matches!(
+            Time::from_unix(1_255_820_323_250),
+            Err(GpxError::UnixTimeOutOfRange(1_255_820_323_250))
+        ));
+        assert!(Time::from_unix_millis(i64::MAX).is_err());
+    }
 }
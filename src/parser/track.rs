@@ -5,25 +5,21 @@ use std::io::Read;
 use xml::reader::XmlEvent;
 
 use crate::errors::{GpxError, GpxResult};
-use crate::parser::{Context, extensions, link, string, tracksegment, verify_starting_tag};
+use crate::parser::{Context, integer, link, string, style, tracksegment, unknown_element, verify_starting_tag};
 use crate::parser::extensions::WaypointExtensions;
-use crate::Track;
+use crate::{GpxVersion, Track};
 
 /// consume consumes a GPX track from the `reader` until it ends.
 pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<Track<E>> {
     let mut track: Track<E> = Default::default();
     verify_starting_tag(context, "trk")?;
+    let version = context.version();
 
     loop {
-        let next_event = {
-            if let Some(next) = context.reader.peek() {
-                match next {
-                    Ok(n) => n,
-                    Err(_) => return Err(GpxError::EventParsingError("track event")),
-                }
-            } else {
-                break;
-            }
+        context.check_deadline()?;
+        let next_event = match context.peek_ok("track")? {
+            Some(event) => event,
+            None => break,
         };
 
         match next_event {
@@ -49,14 +45,21 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
                 "link" => {
                     track.links.push(link::consume(context)?);
                 }
+                "url" if version == GpxVersion::Gpx10 => {
+                    link::consume_gpx10(context, "url", &mut track.links)?;
+                }
+                "urlname" if version == GpxVersion::Gpx10 => {
+                    link::consume_gpx10(context, "urlname", &mut track.links)?;
+                }
                 "number" => {
-                    track.number = Some(string::consume(context, "number", false)?.parse()?)
+                    track.number = integer::consume(context, "number", None)?
                 }
                 "extensions" => {
-                    extensions::EmptyExtensions::consume(context)?;
+                    track.style = style::consume(context)?;
                 }
                 child => {
-                    return Err(GpxError::InvalidChildElement(String::from(child), "track"));
+                    let child = String::from(child);
+                    unknown_element(context, child, "track")?;
                 }
             },
             XmlEvent::EndElement { ref name } => {
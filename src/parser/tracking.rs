@@ -0,0 +1,383 @@
+//! tracking follows the bytes the XML parser reads: their position in the
+//! input, and their exact content while recording.
+//!
+//! It also drops a UTF-8 byte order mark at the start of the input, which
+//! some editors on Windows write and which the XML parser does not take
+//! everywhere.
+//!
+//! Stripping control characters works on bytes, so it is only done for input
+//! in an encoding that is ASCII compatible. In UTF-16, for one, every ASCII
+//! character has a zero byte.
+
+#[cfg(feature = "quick-xml")]
+use std::io::{BufRead, ErrorKind};
+use std::cell::{Cell, RefCell};
+use std::io::Read;
+use std::rc::Rc;
+
+use crate::declaration::{is_ascii_compatible, read_prefix, UTF8_BOM};
+use crate::errors::Position;
+
+/// Tracker holds the position of the reader, and the bytes read while
+/// recording. It is shared by the reader and its context, which parse on one
+/// thread, so plain cells do.
+#[derive(Debug)]
+pub(crate) struct Tracker {
+    line: Cell<u64>,
+    column: Cell<u64>,
+    offset: Cell<u64>,
+    active: Cell<bool>,
+    bytes: RefCell<Vec<u8>>,
+    /// Whether the reader strips the control characters XML disallows.
+    stripping: Cell<bool>,
+    /// The control characters stripped and not yet taken, with their
+    /// positions.
+    stripped: RefCell<Vec<(Position, u8)>>,
+}
+
+impl Default for Tracker {
+    fn default() -> Tracker {
+        Tracker {
+            line: Cell::new(1),
+            column: Cell::new(1),
+            offset: Cell::new(0),
+            active: Cell::new(false),
+            bytes: RefCell::new(Vec::new()),
+            stripping: Cell::new(false),
+            stripped: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+/// is_disallowed_control tells whether `byte` is a control character that
+/// XML 1.0 does not allow anywhere in a document, which is all but tab, line
+/// feed and carriage return. Being ASCII, it is never part of a multi-byte
+/// UTF-8 sequence.
+fn is_disallowed_control(byte: u8) -> bool {
+    matches!(byte, 0x00..=0x08 | 0x0B | 0x0C | 0x0E..=0x1F)
+}
+
+impl Tracker {
+    /// A tracker whose reader strips the control characters XML disallows,
+    /// keeping them for [`Tracker::take_stripped`].
+    pub(crate) fn stripping() -> Tracker {
+        Tracker {
+            stripping: Cell::new(true),
+            ..Default::default()
+        }
+    }
+
+    fn is_stripping(&self) -> bool {
+        self.stripping.get()
+    }
+
+    /// Takes the control characters stripped since last called.
+    pub(crate) fn take_stripped(&self) -> Vec<(Position, u8)> {
+        std::mem::take(&mut *self.stripped.borrow_mut())
+    }
+
+    pub(crate) fn start(&self) {
+        self.bytes.borrow_mut().clear();
+        self.active.set(true);
+    }
+
+    pub(crate) fn stop(&self) -> Vec<u8> {
+        self.active.set(false);
+        std::mem::take(&mut *self.bytes.borrow_mut())
+    }
+
+    /// How many bytes have been recorded since [`Tracker::start`].
+    pub(crate) fn recorded(&self) -> usize {
+        self.bytes.borrow().len()
+    }
+
+    /// The position of the next byte to be read.
+    pub(crate) fn position(&self) -> Position {
+        Position {
+            line: self.line.get(),
+            column: self.column.get(),
+        }
+    }
+
+    /// How many bytes have been read.
+    pub(crate) fn offset(&self) -> u64 {
+        self.offset.get()
+    }
+
+    /// skip moves the offset past `count` bytes dropped from the input,
+    /// leaving the line and column as they are.
+    fn skip(&self, count: usize) {
+        self.offset.set(self.offset.get() + count as u64);
+    }
+
+    /// record keeps `bytes` when recording.
+    fn record(&self, bytes: &[u8]) {
+        if self.active.get() {
+            self.bytes.borrow_mut().extend_from_slice(bytes);
+        }
+    }
+
+    /// advance moves the position past `bytes`, and when stripping, removes
+    /// the disallowed control characters from them, giving how many bytes
+    /// are left at their start. The position is stored once for all of
+    /// them.
+    fn advance(&self, bytes: &mut [u8]) -> usize {
+        self.offset.set(self.offset.get() + bytes.len() as u64);
+        let stripping = self.is_stripping();
+        let mut line = self.line.get();
+        let mut column = self.column.get();
+        let mut kept = 0;
+        for index in 0..bytes.len() {
+            let byte = bytes[index];
+            if stripping && is_disallowed_control(byte) {
+                self.stripped.borrow_mut().push((Position { line, column }, byte));
+            } else {
+                bytes[kept] = byte;
+                kept += 1;
+            }
+            if byte == b'\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        self.line.set(line);
+        self.column.set(column);
+        kept
+    }
+
+    /// pass moves the position past `bytes`, which start at `start` in a
+    /// buffer, and past the control characters stripped from it before them,
+    /// given with their index in that buffer.
+    #[cfg(feature = "quick-xml")]
+    fn pass(&self, bytes: &[u8], start: usize, stripped: &[(usize, u8)]) {
+        self.offset
+            .set(self.offset.get() + (bytes.len() + stripped.len()) as u64);
+        let mut line = self.line.get();
+        let mut column = self.column.get();
+        let mut stripped = stripped.iter().peekable();
+        for index in start..=start + bytes.len() {
+            while let Some((_, control)) = stripped.next_if(|(at, _)| *at == index) {
+                self.stripped.borrow_mut().push((Position { line, column }, *control));
+                column += 1;
+            }
+            match bytes.get(index - start) {
+                Some(b'\n') => {
+                    line += 1;
+                    column = 1;
+                }
+                Some(_) => column += 1,
+                None => {}
+            }
+        }
+        self.line.set(line);
+        self.column.set(column);
+        self.record(bytes);
+    }
+}
+
+/// TrackingReader is the reader the parser reads the input through, as
+/// the contexts of [`read_with_root_handler`](crate::read_with_root_handler)
+/// and the other readers name it. It drops a byte order mark, and when given
+/// a tracker, follows the position of the bytes read through it.
+///
+/// The XML parser reads one byte at a time, so the tracker is never ahead of
+/// the events parsed, and a recording holds exactly the bytes of the events
+/// parsed while it is active. Without a tracker the bytes are passed on as
+/// they are read.
+pub struct TrackingReader<R: Read> {
+    inner: R,
+    tracker: Option<Rc<Tracker>>,
+    /// Whether the bytes read are reported, rather than left to a
+    /// [`TrackingBufReader`] reading through this one.
+    reporting: bool,
+    /// Whether the start of the input was read for its encoding.
+    started: bool,
+    /// The bytes read at the start of the input but the byte order mark, to
+    /// be read before the rest of it.
+    pending: Vec<u8>,
+}
+
+impl<R: Read> TrackingReader<R> {
+    pub(crate) fn new(inner: R, tracker: Option<Rc<Tracker>>) -> TrackingReader<R> {
+        TrackingReader {
+            inner,
+            tracker,
+            reporting: true,
+            started: false,
+            pending: Vec::new(),
+        }
+    }
+
+    /// A reader for a [`TrackingBufReader`] to read through, which only drops
+    /// the byte order mark, leaving the rest to it.
+    #[cfg(feature = "quick-xml")]
+    pub(crate) fn buffered(inner: R, tracker: Rc<Tracker>) -> TrackingReader<R> {
+        TrackingReader {
+            reporting: false,
+            ..TrackingReader::new(inner, Some(tracker))
+        }
+    }
+
+    /// start reads the start of the input, dropping a UTF-8 byte order mark
+    /// and keeping the rest to be read. When the input is in an encoding that
+    /// is not ASCII compatible, the tracker stops stripping.
+    fn start(&mut self) -> std::io::Result<()> {
+        let prefix = read_prefix(&mut self.inner)?;
+        if let Some(tracker) = &self.tracker {
+            if !is_ascii_compatible(&prefix) {
+                tracker.stripping.set(false);
+            }
+        }
+        match prefix.strip_prefix(&UTF8_BOM[..]) {
+            Some(rest) => {
+                if let Some(tracker) = &self.tracker {
+                    tracker.skip(UTF8_BOM.len());
+                }
+                self.pending = rest.to_vec();
+            }
+            None => self.pending = prefix,
+        }
+        Ok(())
+    }
+}
+
+/// read_inner reads the `pending` bytes first, then `inner`.
+fn read_inner<R: Read>(inner: &mut R, pending: &mut Vec<u8>, buf: &mut [u8]) -> std::io::Result<usize> {
+    if pending.is_empty() {
+        return inner.read(buf);
+    }
+    let len = pending.len().min(buf.len());
+    buf[..len].copy_from_slice(&pending[..len]);
+    pending.drain(..len);
+    Ok(len)
+}
+
+impl<R: Read> Read for TrackingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if !self.started {
+            self.start()?;
+            self.started = true;
+        }
+        let tracker = match &self.tracker {
+            Some(tracker) if self.reporting => tracker,
+            _ => return read_inner(&mut self.inner, &mut self.pending, buf),
+        };
+        loop {
+            let read = read_inner(&mut self.inner, &mut self.pending, buf)?;
+            let kept = tracker.advance(&mut buf[..read]);
+            // Reading nothing would mean the end of the input, so read on
+            // when everything read was stripped.
+            if kept == 0 && read > 0 {
+                continue;
+            }
+            tracker.record(&buf[..kept]);
+            return Ok(kept);
+        }
+    }
+}
+
+/// How many bytes [`TrackingBufReader`] reads at once.
+#[cfg(feature = "quick-xml")]
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// TrackingBufReader buffers the input for XML parsers that read ahead,
+/// reporting bytes to a [`Tracker`] as they are consumed rather than read.
+///
+/// Parsers consume the bytes of an event as they give it, so as with
+/// [`TrackingReader`], the tracker is never ahead of the events parsed. The
+/// control characters XML disallows are stripped from the buffer as it is
+/// filled, and reported as the byte after them is consumed.
+#[cfg(feature = "quick-xml")]
+pub(crate) struct TrackingBufReader<R: Read> {
+    inner: R,
+    tracker: Rc<Tracker>,
+    buffer: Box<[u8]>,
+    /// The bytes of `buffer` not consumed yet.
+    start: usize,
+    end: usize,
+    /// The control characters stripped from `buffer`, with the index of the
+    /// byte they were before.
+    stripped: Vec<(usize, u8)>,
+}
+
+#[cfg(feature = "quick-xml")]
+impl<R: Read> TrackingBufReader<R> {
+    pub(crate) fn new(inner: R, tracker: Rc<Tracker>) -> TrackingBufReader<R> {
+        TrackingBufReader {
+            inner,
+            tracker,
+            buffer: vec![0; BUFFER_SIZE].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            stripped: Vec::new(),
+        }
+    }
+
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// fill reads more of the input into the empty buffer, stripping it
+    /// when the tracker does.
+    fn fill(&mut self) -> std::io::Result<()> {
+        // The control characters at the end of what was consumed.
+        self.tracker.pass(&[], self.end, &self.stripped);
+        self.stripped.clear();
+        loop {
+            let read = match self.inner.read(&mut self.buffer) {
+                Ok(read) => read,
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            };
+            let mut kept = 0;
+            for index in 0..read {
+                let byte = self.buffer[index];
+                if self.tracker.is_stripping() && is_disallowed_control(byte) {
+                    self.stripped.push((kept, byte));
+                } else {
+                    self.buffer[kept] = byte;
+                    kept += 1;
+                }
+            }
+            self.start = 0;
+            self.end = kept;
+            // Control characters stripped from a read of nothing else stay
+            // before the first byte of the next one.
+            if kept > 0 || read == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(feature = "quick-xml")]
+impl<R: Read> Read for TrackingBufReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+#[cfg(feature = "quick-xml")]
+impl<R: Read> BufRead for TrackingBufReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.start == self.end {
+            self.fill()?;
+        }
+        Ok(&self.buffer[self.start..self.end])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        let end = self.start + amount;
+        let stripped = self.stripped.iter().take_while(|(at, _)| *at < end).count();
+        self.tracker
+            .pass(&self.buffer[self.start..end], self.start, &self.stripped[..stripped]);
+        self.stripped.drain(..stripped);
+        self.start = end;
+    }
+}
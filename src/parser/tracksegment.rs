@@ -5,7 +5,7 @@ use std::io::Read;
 use xml::reader::XmlEvent;
 
 use crate::errors::{GpxError, GpxResult};
-use crate::parser::{Context, verify_starting_tag, waypoint};
+use crate::parser::{Context, unknown_element, verify_starting_tag, waypoint};
 use crate::parser::extensions::WaypointExtensions;
 use crate::TrackSegment;
 
@@ -13,27 +13,25 @@ use crate::TrackSegment;
 pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<TrackSegment<E>> {
     let mut segment: TrackSegment<E> = Default::default();
     verify_starting_tag(context, "trkseg")?;
+    context.reserve_points(&mut segment.points);
 
     loop {
-        let next_event = {
-            if let Some(next) = context.reader.peek() {
-                match next {
-                    Ok(n) => n,
-                    Err(_) => return Err(GpxError::TrackSegmentError()),
-                }
-            } else {
-                break;
-            }
+        context.check_deadline()?;
+        let next_event = match context.peek_ok("track segment")? {
+            Some(event) => event,
+            None => break,
         };
 
         match next_event {
             XmlEvent::StartElement { ref name, .. } => match name.local_name.as_ref() {
-                "trkpt" => segment.points.push(waypoint::consume(context, "trkpt")?),
+                "trkpt" => {
+                    if let Some(point) = waypoint::consume_or_skip(context, "trkpt")? {
+                        context.keep_point(point, &mut segment.points)?;
+                    }
+                }
                 child => {
-                    return Err(GpxError::InvalidChildElement(
-                        String::from(child),
-                        "tracksegment",
-                    ));
+                    let child = String::from(child);
+                    unknown_element(context, child, "tracksegment")?;
                 }
             },
             XmlEvent::EndElement { ref name } => {
@@ -44,6 +42,7 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
                     ));
                 }
                 context.reader.next(); //consume the end tag
+                context.fit_points(&mut segment.points);
                 return Ok(segment);
             }
             _ => {
@@ -60,7 +59,7 @@ mod tests {
     use assert_approx_eq::assert_approx_eq;
     use geo::euclidean_length::EuclideanLength;
 
-    use crate::GpxVersion;
+    use crate::{distance_between, GpxVersion};
 
     use super::consume;
 
@@ -89,6 +88,10 @@ mod tests {
 
         let linestring = segment.linestring();
         assert_approx_eq!(linestring.euclidean_length(), 9.2377437);
+
+        // The White House to Boston, Massachusetts.
+        let distance = distance_between(&segment.points[0], &segment.points[1]);
+        assert_approx_eq!(distance, 633_866.46, 0.01);
     }
 
     #[test]
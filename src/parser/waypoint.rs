@@ -6,80 +6,161 @@ use geo_types::Point;
 use xml::reader::XmlEvent;
 
 use crate::{GpxVersion, Waypoint};
-use crate::errors::{GpxError, GpxResult};
-use crate::parser::{Context, fix, link, string, time, verify_starting_tag};
+use crate::errors::{GpxError, GpxResult, GpxWarning};
+use crate::parser::{Attributes, Context, dop, fix, integer, link, number, skip_element, string, time, unknown_element, verify_starting_tag};
 use crate::parser::extensions::WaypointExtensions;
 
-/// consume consumes a GPX waypoint from the `reader` until it ends.
-pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>, tagname: &'static str) -> GpxResult<Waypoint<E>> {
-    let attributes = verify_starting_tag(context, tagname)?;
+/// Checks the value of a required coordinate attribute, which must be present
+/// and non-empty.
+fn coordinate<'a>(value: Option<&'a str>, coordinate: &'static str, tagname: &'static str) -> GpxResult<&'a str> {
+    value
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .ok_or(GpxError::MissingCoordinate(coordinate, tagname))
+}
 
+/// point gives the geographical point from the `lat` and `lon` attributes.
+/// In lenient mode a coordinate out of its range is replaced with a warning.
+fn point<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+    attributes: &Attributes,
+    tagname: &'static str,
+) -> GpxResult<Point<f64>> {
     // get required latitude and longitude
-    let latitude = attributes
-        .iter()
-        .find(|attr| attr.name.local_name == "lat")
-        .ok_or(GpxError::InvalidElementLacksAttribute(
-            "latitude", "waypoint",
-        ))?;
+    let [latitude, longitude] = attributes.get_all(["lat", "lon"]);
+    let latitude = coordinate(latitude, "latitude", tagname)?;
+    let longitude = coordinate(longitude, "longitude", tagname)?;
 
-    let latitude: f64 = latitude.value.parse()?;
+    let mut latitude: f64 = number::parse(latitude, "lat")?;
 
     if !(-90.0..=90.0).contains(&latitude) {
-        return Err(GpxError::LonLatOutOfBoundsError(
-            "latitude",
-            "[-90.0, 90.0]",
-            latitude,
-        ));
+        if !context.options.lenient || latitude.is_nan() {
+            return Err(GpxError::LonLatOutOfBoundsError(
+                "latitude",
+                "[-90.0, 90.0]",
+                latitude,
+            ));
+        }
+        let replacement = latitude.clamp(-90.0, 90.0);
+        context.warn(GpxWarning::OutOfRange {
+            element: tagname,
+            coordinate: "latitude",
+            value: latitude,
+            replacement,
+        });
+        latitude = replacement;
     };
 
-    let longitude = attributes
-        .iter()
-        .find(|attr| attr.name.local_name == "lon")
-        .ok_or(GpxError::InvalidElementLacksAttribute(
-            "longitude",
-            "waypoint",
-        ))?;
-
-    let longitude: f64 = longitude.value.parse()?;
+    let mut longitude: f64 = number::parse(longitude, "lon")?;
 
     if !(-180.0..180.0).contains(&longitude) {
-        return Err(GpxError::LonLatOutOfBoundsError(
-            "Longitude",
-            "[-180.0, 180.0)",
-            longitude,
-        ));
+        if !context.options.lenient || !longitude.is_finite() {
+            return Err(GpxError::LonLatOutOfBoundsError(
+                "Longitude",
+                "[-180.0, 180.0)",
+                longitude,
+            ));
+        }
+        let replacement = (longitude + 180.0).rem_euclid(360.0) - 180.0;
+        context.warn(GpxWarning::OutOfRange {
+            element: tagname,
+            coordinate: "longitude",
+            value: longitude,
+            replacement,
+        });
+        longitude = replacement;
     };
 
-    let mut waypoint: Waypoint<E> = Waypoint::new(Point::new(longitude, latitude));
+    if let Some(decimals) = context.options.coordinate_decimals {
+        latitude = quantize(latitude, decimals);
+        longitude = quantize(longitude, decimals);
+    }
 
-    loop {
-        let next_event = {
-            if let Some(next) = context.reader.peek() {
-                match next {
-                    Ok(n) => n,
-                    Err(_) => return Err(GpxError::EventParsingError("waypoint event")),
-                }
-            } else {
-                break;
-            }
-        };
+    Ok(Point::new(longitude, latitude))
+}
+
+/// quantize rounds `value` to `decimals` decimal places, leaving it as it is
+/// when there are too many places to round to.
+fn quantize(value: f64, decimals: u32) -> f64 {
+    let scale = 10f64.powi(decimals.min(i32::MAX as u32) as i32);
+    let quantized = (value * scale).round() / scale;
+    if quantized.is_finite() {
+        quantized
+    } else {
+        value
+    }
+}
 
+/// consume consumes a GPX waypoint from the `reader` until it ends.
+pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>, tagname: &'static str) -> GpxResult<Waypoint<E>> {
+    let attributes = verify_starting_tag(context, tagname)?;
+    let point = point(context, &attributes, tagname)?;
+    consume_content(context, tagname, point)
+}
+
+/// consume_or_skip consumes a GPX waypoint like [`consume`], except that in
+/// lenient mode a point lacking a coordinate is skipped with a warning,
+/// giving `None`.
+pub fn consume_or_skip<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+    tagname: &'static str,
+) -> GpxResult<Option<Waypoint<E>>> {
+    let attributes = verify_starting_tag(context, tagname)?;
+    match point(context, &attributes, tagname) {
+        Ok(point) => consume_content(context, tagname, point).map(Some),
+        Err(GpxError::MissingCoordinate(coordinate, _)) if context.options.lenient => {
+            context.warn(GpxWarning::SkippedPoint {
+                element: tagname,
+                missing: coordinate,
+            });
+            skip_element(context, tagname)?;
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// consume_content consumes the children of a GPX waypoint whose starting
+/// tag has been consumed.
+fn consume_content<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+    tagname: &'static str,
+    point: Point<f64>,
+) -> GpxResult<Waypoint<E>> {
+    let mut waypoint: Waypoint<E> = Waypoint::new(point);
+    let version = context.version();
+    let mut has_extensions = false;
+
+    while let Some(next_event) = context.peek_ok(tagname)? {
         match next_event {
             XmlEvent::StartElement { ref name, .. } => {
                 match name.local_name.as_ref() {
                     "ele" => {
                         // Cast the elevation to an f64, from a string.
-                        waypoint.elevation = match string::consume(context, "ele", false) {
-                            Ok(v) => Some(v.parse()?),
+                        waypoint.elevation = match number::consume(context, "ele") {
+                            Ok(v) => Some(v),
                             Err(GpxError::NoStringContent) => None,
                             Err(other_err) => return Err(other_err),
                         }
                     }
-                    "speed" if context.version == GpxVersion::Gpx10 => {
+                    "speed" if version == GpxVersion::Gpx10 => {
                         // Speed is from GPX 1.0
-                        waypoint.speed = Some(string::consume(context, "speed", false)?.parse()?);
+                        waypoint.speed = Some(number::consume(context, "speed")?);
+                    }
+                    "course" if version == GpxVersion::Gpx10 => {
+                        // Course is from GPX 1.0
+                        waypoint.course = Some(number::consume(context, "course")?);
+                    }
+                    "url" if version == GpxVersion::Gpx10 => {
+                        link::consume_gpx10(context, "url", &mut waypoint.links)?
+                    }
+                    "urlname" if version == GpxVersion::Gpx10 => {
+                        link::consume_gpx10(context, "urlname", &mut waypoint.links)?
+                    }
+                    "time" => waypoint.time = time::consume_or_drop(context)?,
+                    "magvar" => {
+                        waypoint.magvar = Some(number::consume(context, "magvar")?)
                     }
-                    "time" => waypoint.time = Some(time::consume(context)?),
                     "name" => waypoint.name = Some(string::consume(context, "name", true)?),
                     "cmt" => waypoint.comment = Some(string::consume(context, "cmt", true)?),
                     "desc" => waypoint.description = Some(string::consume(context, "desc", true)?),
@@ -92,35 +173,43 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
                     "fix" => waypoint.fix = Some(fix::consume(context)?),
                     "geoidheight" => {
                         waypoint.geoidheight =
-                            Some(string::consume(context, "geoidheight", false)?.parse()?)
+                            Some(number::consume(context, "geoidheight")?)
                     }
-                    "sat" => waypoint.sat = Some(string::consume(context, "sat", false)?.parse()?),
-                    "hdop" => {
-                        waypoint.hdop = Some(string::consume(context, "hdop", false)?.parse()?)
-                    }
-                    "vdop" => {
-                        waypoint.vdop = Some(string::consume(context, "vdop", false)?.parse()?)
-                    }
-                    "pdop" => {
-                        waypoint.pdop = Some(string::consume(context, "pdop", false)?.parse()?)
+                    "sat" => {
+                        let sentinel = context.options.sat_sentinel;
+                        waypoint.sat = integer::consume(context, "sat", sentinel)?
                     }
+                    "hdop" => waypoint.hdop = Some(dop::consume(context, "hdop")?),
+                    "vdop" => waypoint.vdop = Some(dop::consume(context, "vdop")?),
+                    "pdop" => waypoint.pdop = Some(dop::consume(context, "pdop")?),
                     "ageofdgpsdata" => {
                         waypoint.dgps_age =
-                            Some(string::consume(context, "ageofdgpsdata", false)?.parse()?)
+                            Some(number::consume(context, "ageofdgpsdata")?)
                     }
                     "dgpsid" => {
-                        waypoint.dgpsid = Some(string::consume(context, "dgpsid", false)?.parse()?)
+                        waypoint.dgpsid = integer::consume(context, "dgpsid", None)?
                     }
 
                     // Finally the GPX 1.1 extensions
+                    "extensions" if !has_extensions => {
+                        waypoint.extensions = context.consume_waypoint_extensions()?;
+                        has_extensions = true;
+                    }
                     "extensions" => {
-                        waypoint.extensions = context.consume_waypoint_extensions()?
+                        // Some merged files repeat the element.
+                        if !context.options.lenient {
+                            return Err(GpxError::DuplicateElement("extensions", tagname));
+                        }
+                        context.warn(GpxWarning::DuplicateElement {
+                            element: "extensions",
+                            parent: tagname,
+                        });
+                        let extensions = context.consume_waypoint_extensions()?;
+                        E::merge(&mut waypoint.extensions, extensions);
                     }
                     child => {
-                        return Err(GpxError::InvalidChildElement(
-                            String::from(child),
-                            "waypoint",
-                        ));
+                        let child = String::from(child);
+                        unknown_element(context, child, "waypoint")?;
                     }
                 }
             }
@@ -147,9 +236,14 @@ pub fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R
 mod tests {
     use geo_types::Point;
 
-    use crate::{Fix, GpxVersion};
+    use std::io::BufReader;
 
-    use super::consume;
+    use crate::errors::{GpxError, GpxWarning};
+    use crate::parser::create_context_with_options;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{Fix, GpxVersion, ParseOptions};
+
+    use super::{consume, consume_or_skip};
 
     #[test]
     fn consume_waypoint() {
@@ -282,4 +376,58 @@ mod tests {
 
         assert!(waypoint.is_err());
     }
+
+    #[test]
+    fn consume_missing_latitude() {
+        let waypoint = consume!("<trkpt lon=\"1.234\"></trkpt>", GpxVersion::Gpx11, "trkpt");
+        assert!(matches!(waypoint, Err(GpxError::MissingCoordinate("latitude", "trkpt"))));
+
+        let waypoint = consume!("<trkpt lat=\"\" lon=\"1.234\"></trkpt>", GpxVersion::Gpx11, "trkpt");
+        assert!(matches!(waypoint, Err(GpxError::MissingCoordinate("latitude", "trkpt"))));
+    }
+
+    #[test]
+    fn skip_missing_latitude() {
+        let xml = "<trkpt lon=\"1.234\"><name>skipped</name></trkpt><trkpt lat=\"2.345\" lon=\"1.234\"/>";
+
+        let mut context = create_context_with_options::<_, EmptyExtensions>(
+            BufReader::new(xml.as_bytes()),
+            GpxVersion::Gpx11,
+            ParseOptions::strict(),
+        );
+        let waypoint = consume_or_skip(&mut context, "trkpt");
+        assert!(matches!(waypoint, Err(GpxError::MissingCoordinate("latitude", "trkpt"))));
+
+        let mut context = create_context_with_options::<_, EmptyExtensions>(
+            BufReader::new(xml.as_bytes()),
+            GpxVersion::Gpx11,
+            ParseOptions::lenient(),
+        );
+        assert!(consume_or_skip(&mut context, "trkpt").unwrap().is_none());
+        assert_eq!(
+            context.warnings()[0].warning,
+            GpxWarning::SkippedPoint {
+                element: "trkpt",
+                missing: "latitude"
+            }
+        );
+        // the next point is read as usual
+        let waypoint = consume_or_skip(&mut context, "trkpt").unwrap().unwrap();
+        assert_eq!(waypoint.point(), Point::new(1.234, 2.345));
+    }
+
+    #[test]
+    fn consume_quantized() {
+        let xml = "<trkpt lat=\"-33.8567844\" lon=\"151.2152967\"></trkpt>";
+        let mut context = create_context_with_options::<_, EmptyExtensions>(
+            BufReader::new(xml.as_bytes()),
+            GpxVersion::Gpx11,
+            ParseOptions::strict().quantize_coordinates(3),
+        );
+        let waypoint = consume(&mut context, "trkpt").unwrap();
+        assert_eq!(waypoint.point(), Point::new(151.215, -33.857));
+
+        assert_eq!(super::quantize(1.5, 0), 2.0);
+        assert_eq!(super::quantize(0.123456789, 400), 0.123456789);
+    }
 }
@@ -0,0 +1,179 @@
+//! pipeline parses a GPX document on a thread of its own, handing its items
+//! over a channel, so parsing overlaps with processing them.
+
+use std::io::Read;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use crate::errors::GpxResult;
+use crate::parser::extensions::{EmptyExtensions, WaypointExtensions};
+use crate::reader::ParseOptions;
+use crate::tracks::{GpxItem, GpxTrackIter};
+
+/// How many items the channel holds before the parsing thread waits for them
+/// to be received, which keeps the memory used flat however fast parsing is.
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Parses `reader` on a new thread, sending its items in the order of the
+/// document, the [`GpxItem::Header`] first.
+///
+/// The thread gives any error it meets when joined, after sending the items
+/// before it. Dropping the receiver stops the thread cleanly, which then
+/// gives `Ok(())`.
+///
+/// ```
+/// use gpx::pipeline::spawn_reader;
+/// use gpx::{GpxItem, ParseOptions};
+///
+/// let data = "<gpx version=\"1.1\"><trk><trkseg><trkpt lat=\"1.0\" lon=\"2.0\"/></trkseg></trk></gpx>";
+///
+/// let (parsing, items) = spawn_reader(data.as_bytes(), ParseOptions::default());
+/// let points: usize = items
+///     .iter()
+///     .map(|item| match item {
+///         GpxItem::Track(track) => track.points_count(),
+///         _ => 0,
+///     })
+///     .sum();
+/// parsing.join().unwrap().unwrap();
+/// assert_eq!(points, 1);
+/// ```
+pub fn spawn_reader<R: Read + Send + 'static>(
+    reader: R,
+    options: ParseOptions,
+) -> (JoinHandle<GpxResult<()>>, Receiver<GpxItem<EmptyExtensions>>) {
+    spawn_reader_with_extensions(reader, options)
+}
+
+/// Parses `reader` on a new thread like [`spawn_reader`], reading the
+/// extensions of points with `E`.
+pub fn spawn_reader_with_extensions<R, E>(
+    reader: R,
+    options: ParseOptions,
+) -> (JoinHandle<GpxResult<()>>, Receiver<GpxItem<E>>)
+where
+    R: Read + Send + 'static,
+    E: WaypointExtensions + Default + Send + 'static,
+    E::ExtensionsValue: Send,
+{
+    let (sender, receiver) = sync_channel(CHANNEL_CAPACITY);
+    let parsing = thread::spawn(move || send_items(reader, options, sender));
+    (parsing, receiver)
+}
+
+/// send_items parses `reader`, sending its items until there are no more or
+/// nothing receives them.
+fn send_items<R: Read, E: WaypointExtensions + Default + 'static>(
+    reader: R,
+    options: ParseOptions,
+    sender: SyncSender<GpxItem<E>>,
+) -> GpxResult<()> {
+    let mut items = GpxTrackIter::<R, E>::with_options(reader, options)?;
+    for item in items.take_start() {
+        if sender.send(item).is_err() {
+            return Ok(());
+        }
+    }
+    while let Some(item) = items.next_item()? {
+        if sender.send(item).is_err() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Read;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{read, Gpx, GpxItem, ParseOptions};
+
+    use super::spawn_reader;
+
+    /// CountingReader counts the bytes read through it.
+    struct CountingReader {
+        inner: std::io::Cursor<Vec<u8>>,
+        read: Arc<AtomicUsize>,
+    }
+
+    impl Read for CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let read = self.inner.read(buf)?;
+            self.read.fetch_add(read, Ordering::Relaxed);
+            Ok(read)
+        }
+    }
+
+    /// reassemble puts the items of a document back together.
+    fn reassemble(items: impl Iterator<Item = GpxItem<EmptyExtensions>>) -> Gpx<EmptyExtensions> {
+        let mut gpx: Gpx<EmptyExtensions> = Default::default();
+        for item in items {
+            match item {
+                GpxItem::Header {
+                    version,
+                    creator,
+                    metadata,
+                    namespaces,
+                    schema_locations,
+                } => {
+                    gpx.version = version;
+                    gpx.creator = creator;
+                    gpx.metadata = metadata;
+                    gpx.namespaces = namespaces;
+                    gpx.schema_locations = schema_locations;
+                }
+                GpxItem::Waypoint(point) => gpx.waypoints.push(point),
+                GpxItem::Route(route) => gpx.routes.push(route),
+                GpxItem::Track(track) => gpx.tracks.push(track),
+            }
+        }
+        gpx
+    }
+
+    #[test]
+    fn pipeline_as_read() {
+        for fixture in ["caltopo-export.gpx", "wikipedia_example.gpx", "gpx10_example.gpx", "garmin-activity.gpx"] {
+            let data = fs::read(format!("tests/fixtures/{}", fixture)).unwrap();
+            let mut expected = read(data.as_slice()).unwrap();
+            expected.comments.clear();
+
+            let (parsing, items) = spawn_reader(std::io::Cursor::new(data), ParseOptions::default());
+            let gpx = reassemble(items.into_iter());
+            parsing.join().unwrap().unwrap();
+            assert_eq!(gpx, expected, "{}", fixture);
+        }
+    }
+
+    #[test]
+    fn pipeline_gives_errors_when_joined() {
+        let data = "<gpx version=\"1.1\"><wpt lat=\"1\" lon=\"2\"/><trk><trkseg></trk></gpx>";
+        let (parsing, items) = spawn_reader(data.as_bytes(), ParseOptions::default());
+        let items: Vec<_> = items.into_iter().collect();
+        assert!(matches!(items[..], [GpxItem::Header { .. }, GpxItem::Waypoint(_)]));
+        assert!(parsing.join().unwrap().is_err());
+    }
+
+    #[test]
+    fn pipeline_stops_when_dropped() {
+        let track = "<trk><trkseg><trkpt lat=\"1\" lon=\"2\"/><trkpt lat=\"3\" lon=\"4\"/></trkseg></trk>";
+        let data = format!("<gpx version=\"1.1\">{}</gpx>", track.repeat(5000));
+        let read = Arc::new(AtomicUsize::new(0));
+        let reader = CountingReader {
+            inner: std::io::Cursor::new(data.clone().into_bytes()),
+            read: read.clone(),
+        };
+
+        let (parsing, items) = spawn_reader(reader, ParseOptions::default());
+        let first_track = items.iter().find(|item| matches!(item, GpxItem::Track(_)));
+        assert!(first_track.is_some());
+        drop(items);
+
+        parsing.join().unwrap().unwrap();
+        let read = read.load(Ordering::Relaxed);
+        assert!(read < data.len() / 10, "read {} of {}", read, data.len());
+    }
+}
@@ -0,0 +1,257 @@
+//! polyline converts track segments and routes to and from the encoded
+//! polylines of Google, Strava and the routing engines.
+
+use geo_types::Point;
+
+use crate::errors::{GpxError, GpxResult};
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Route, TrackSegment, Waypoint};
+
+/// The most decimal places a polyline is encoded or decoded with, well beyond
+/// the 5 or 6 encoders use. Coordinates stay far within an `i64` with them.
+const MAX_PRECISION: u32 = 10;
+
+/// encode_value appends the polyline encoding of `value` to `encoded`.
+fn encode_value(value: i64, encoded: &mut String) {
+    let mut value = ((value << 1) ^ (value >> 63)) as u64;
+    while value >= 0x20 {
+        encoded.push(char::from((0x20 | (value & 0x1f)) as u8 + 63));
+        value >>= 5;
+    }
+    encoded.push(char::from(value as u8 + 63));
+}
+
+/// encode gives the encoded polyline of `points`, with at most
+/// [`MAX_PRECISION`] decimal places. Coordinates out of range fail, as they
+/// would not fit an `i64` once scaled.
+fn encode<E: WaypointExtensions + Default>(points: &[Waypoint<E>], precision: u32) -> GpxResult<String> {
+    if precision > MAX_PRECISION {
+        return Err(GpxError::InvalidPolyline(0, "more than 10 decimal places"));
+    }
+    let factor = 10f64.powi(precision as i32);
+    let mut encoded = String::new();
+    let mut previous = (0, 0);
+    for point in points {
+        if !(-90.0..=90.0).contains(&point.point().y()) {
+            return Err(GpxError::LonLatOutOfBoundsError(
+                "latitude",
+                "[-90.0, 90.0]",
+                point.point().y(),
+            ));
+        }
+        if !(-180.0..=180.0).contains(&point.point().x()) {
+            return Err(GpxError::LonLatOutOfBoundsError(
+                "longitude",
+                "[-180.0, 180.0]",
+                point.point().x(),
+            ));
+        }
+        let current = (
+            (point.point().y() * factor).round() as i64,
+            (point.point().x() * factor).round() as i64,
+        );
+        encode_value(current.0 - previous.0, &mut encoded);
+        encode_value(current.1 - previous.1, &mut encoded);
+        previous = current;
+    }
+    Ok(encoded)
+}
+
+/// decode_value decodes the value starting at byte `start` of `bytes`,
+/// giving it with the index of the byte after it.
+fn decode_value(bytes: &[u8], start: usize) -> GpxResult<(i64, usize)> {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+    let mut index = start;
+    loop {
+        let byte = match bytes.get(index) {
+            Some(&byte) => byte,
+            None => return Err(GpxError::InvalidPolyline(index, "the last value is cut off")),
+        };
+        let chunk = match byte.checked_sub(63) {
+            Some(chunk) if chunk < 0x40 => u64::from(chunk),
+            _ => return Err(GpxError::InvalidPolyline(index, "not a polyline character")),
+        };
+        if shift >= 60 {
+            return Err(GpxError::InvalidPolyline(index, "value too long"));
+        }
+        value |= (chunk & 0x1f) << shift;
+        shift += 5;
+        index += 1;
+        if chunk < 0x20 {
+            let value = ((value >> 1) as i64) ^ -((value & 1) as i64);
+            return Ok((value, index));
+        }
+    }
+}
+
+/// decode gives the points of the encoded polyline `encoded`.
+fn decode<E: WaypointExtensions + Default>(encoded: &str, precision: u32) -> GpxResult<Vec<Waypoint<E>>> {
+    if precision > MAX_PRECISION {
+        return Err(GpxError::InvalidPolyline(0, "more than 10 decimal places"));
+    }
+    let factor = 10f64.powi(precision as i32);
+    let bytes = encoded.as_bytes();
+    let mut points = Vec::new();
+    let (mut latitude, mut longitude) = (0i64, 0i64);
+    let mut index = 0;
+    while index < bytes.len() {
+        let start = index;
+        let (delta, next) = decode_value(bytes, index)?;
+        latitude = latitude
+            .checked_add(delta)
+            .ok_or(GpxError::InvalidPolyline(start, "coordinates out of range"))?;
+        if next == bytes.len() {
+            return Err(GpxError::InvalidPolyline(next, "the last longitude is missing"));
+        }
+        let (delta, after) = decode_value(bytes, next)?;
+        longitude = longitude
+            .checked_add(delta)
+            .ok_or(GpxError::InvalidPolyline(next, "coordinates out of range"))?;
+        index = after;
+
+        let point = Point::new(longitude as f64 / factor, latitude as f64 / factor);
+        if !(-90.0..=90.0).contains(&point.y()) || !(-180.0..=180.0).contains(&point.x()) {
+            return Err(GpxError::InvalidPolyline(start, "coordinates out of range"));
+        }
+        points.push(Waypoint::new(point));
+    }
+    Ok(points)
+}
+
+impl<E: WaypointExtensions + Default> TrackSegment<E> {
+    /// Encodes the points of this segment as a polyline, with `precision`
+    /// decimal places: 5 for Google and Strava, 6 for OSRM and Valhalla.
+    /// A precision above 10 fails with [`GpxError::InvalidPolyline`] at byte 0,
+    /// as decoding does. Elevation, time and the other fields of points are
+    /// left out. A coordinate out of range, or not a number, fails with
+    /// [`GpxError::LonLatOutOfBoundsError`].
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+    /// for (lon, lat) in [(-120.2, 38.5), (-120.95, 40.7), (-126.453, 43.252)] {
+    ///     segment.points.push(Waypoint::new(Point::new(lon, lat)));
+    /// }
+    /// assert_eq!(segment.to_polyline(5).unwrap(), "_p~iF~ps|U_ulLnnqC_mqNvxq`@");
+    /// ```
+    pub fn to_polyline(&self, precision: u32) -> GpxResult<String> {
+        encode(&self.points, precision)
+    }
+
+    /// Decodes a segment from a polyline with `precision` decimal places, see
+    /// [`TrackSegment::to_polyline`]. A polyline that is corrupt, or decodes
+    /// to coordinates out of range, fails with a
+    /// [`GpxError::InvalidPolyline`] giving the byte at fault, and so does a
+    /// precision above 10, at byte 0.
+    pub fn from_polyline(encoded: &str, precision: u32) -> GpxResult<TrackSegment<E>> {
+        Ok(TrackSegment {
+            points: decode(encoded, precision)?,
+        })
+    }
+}
+
+impl<E: WaypointExtensions + Default> Route<E> {
+    /// Encodes the points of this route as a polyline, like
+    /// [`TrackSegment::to_polyline`].
+    pub fn to_polyline(&self, precision: u32) -> GpxResult<String> {
+        encode(&self.points, precision)
+    }
+
+    /// Decodes a route from a polyline, like
+    /// [`TrackSegment::from_polyline`].
+    pub fn from_polyline(encoded: &str, precision: u32) -> GpxResult<Route<E>> {
+        let mut route = Route::new();
+        route.points = decode(encoded, precision)?;
+        Ok(route)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::GpxError;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::testing::segment;
+    use crate::{Route, TrackSegment};
+
+    /// The example of Google's documentation of the format.
+    const GOOGLE: &str = "_p~iF~ps|U_ulLnnqC_mqNvxq`@";
+
+    #[test]
+    fn polyline_google_example() {
+        let expected = segment(&[(-120.2, 38.5), (-120.95, 40.7), (-126.453, 43.252)]);
+        assert_eq!(expected.to_polyline(5).unwrap(), GOOGLE);
+        assert_eq!(TrackSegment::from_polyline(GOOGLE, 5).unwrap(), expected);
+    }
+
+    #[test]
+    fn polyline_round_trip() {
+        let coordinates = [
+            (8.541_694, 47.376_887),
+            (8.541_702, 47.376_901),
+            (-179.999_999, -89.999_999),
+            (180.0, 90.0),
+            (0.0, 0.0),
+        ];
+        for precision in [5, 6] {
+            let rounded: Vec<_> = coordinates
+                .iter()
+                .map(|&(x, y): &(f64, f64)| {
+                    let factor = 10f64.powi(precision as i32);
+                    ((x * factor).round() / factor, (y * factor).round() / factor)
+                })
+                .collect();
+            let expected = segment(&rounded);
+            let encoded = segment(&coordinates).to_polyline(precision).unwrap();
+            assert_eq!(TrackSegment::from_polyline(&encoded, precision).unwrap(), expected);
+
+            let mut route: Route<EmptyExtensions> = Route::new();
+            route.points = expected.points.clone();
+            assert_eq!(Route::from_polyline(&route.to_polyline(precision).unwrap(), precision).unwrap(), route);
+        }
+        assert_eq!(TrackSegment::<EmptyExtensions>::from_polyline("", 5).unwrap().points.len(), 0);
+    }
+
+    #[test]
+    fn polyline_corrupt() {
+        let corrupt = |encoded: &str| match TrackSegment::<EmptyExtensions>::from_polyline(encoded, 5) {
+            Err(GpxError::InvalidPolyline(index, _)) => index,
+            other => panic!("expected an invalid polyline, got {:?}", other),
+        };
+        // A character outside of the alphabet.
+        assert_eq!(corrupt("_p~iF~ps|U_ul LnnqC"), 13);
+        // Cut off in the middle of a value, and after a latitude.
+        assert_eq!(corrupt(&GOOGLE[..GOOGLE.len() - 1]), GOOGLE.len() - 1);
+        assert_eq!(corrupt("_p~iF"), 5);
+        // Out of range, and too long for any coordinate.
+        assert_eq!(corrupt("_p~iF~ps|U_p~iF~ps|U"), 10);
+        assert_eq!(corrupt("~~~~~~~~~~~~~~~"), 12);
+    }
+
+    #[test]
+    fn polyline_precision_bounded() {
+        let huge = TrackSegment::<EmptyExtensions>::from_polyline(GOOGLE, u32::MAX);
+        assert!(matches!(huge, Err(GpxError::InvalidPolyline(0, _))));
+        assert!(TrackSegment::<EmptyExtensions>::from_polyline(GOOGLE, 10).is_ok());
+
+        let expected = segment(&[(-120.2, 38.5), (-120.95, 40.7)]);
+        assert!(matches!(expected.to_polyline(11), Err(GpxError::InvalidPolyline(0, _))));
+        assert!(matches!(expected.to_polyline(u32::MAX), Err(GpxError::InvalidPolyline(0, _))));
+        let encoded = expected.to_polyline(10).unwrap();
+        assert_eq!(TrackSegment::from_polyline(&encoded, 10).unwrap(), expected);
+    }
+
+    #[test]
+    fn polyline_encode_out_of_range() {
+        for (x, y) in [(0.0, 1e300), (-1e300, 0.0), (0.0, f64::NAN), (f64::INFINITY, 0.0), (180.5, 0.0)] {
+            let encoded = segment(&[(0.0, 0.0), (x, y)]).to_polyline(10);
+            assert!(matches!(encoded, Err(GpxError::LonLatOutOfBoundsError(..))), "{} {}", x, y);
+        }
+        let mut route: Route<EmptyExtensions> = Route::new();
+        route.points = segment(&[(1e300, -1e300)]).points;
+        assert!(route.to_polyline(5).is_err());
+    }
+}
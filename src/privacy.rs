@@ -0,0 +1,358 @@
+//! privacy removes personally identifying information from GPX documents.
+
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Gpx, Link, Waypoint};
+
+/// Hosts of activity platforms whose links usually point at user profiles or
+/// activities.
+const DEFAULT_DENIED_HOSTS: [&str; 5] = [
+    "strava.com",
+    "connect.garmin.com",
+    "komoot.com",
+    "alltrails.com",
+    "ridewithgps.com",
+];
+
+/// Names of the extension elements that usually hold a device serial number.
+const DEFAULT_SERIAL_ELEMENTS: [&str; 4] = ["SerialNumber", "Serial", "DeviceSerial", "UnitId"];
+
+/// What [`Gpx::scrub_pii`] removes.
+///
+/// The copyright holder and license are always kept, so that attribution
+/// survives scrubbing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PiiPolicy {
+    /// Remove the metadata author, including their email address and link.
+    pub author: bool,
+
+    /// Remove the creator, which often names the recording device.
+    pub creator: bool,
+
+    /// Remove all links of the metadata.
+    pub metadata_links: bool,
+
+    /// Remove links of waypoints, tracks and routes whose host is one of
+    /// these or a subdomain of one of these.
+    pub denied_link_hosts: Vec<String>,
+
+    /// Let the extensions remove identifying fields, such as device serial
+    /// numbers, through [`WaypointExtensions::scrub_pii`].
+    pub extensions: bool,
+
+    /// Local names of the extension elements holding device serial numbers,
+    /// matched ignoring ASCII case and whatever their namespace.
+    /// [`RawExtensions`](crate::parser::extensions::RawExtensions) removes
+    /// them with their content.
+    pub serial_elements: Vec<String>,
+}
+
+impl Default for PiiPolicy {
+    fn default() -> PiiPolicy {
+        PiiPolicy {
+            author: true,
+            creator: true,
+            metadata_links: true,
+            denied_link_hosts: DEFAULT_DENIED_HOSTS.iter().map(|&host| host.into()).collect(),
+            extensions: true,
+            serial_elements: DEFAULT_SERIAL_ELEMENTS.iter().map(|&name| name.into()).collect(),
+        }
+    }
+}
+
+/// What [`Gpx::scrub_pii`] removed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PiiReport {
+    /// Whether the metadata author was removed.
+    pub author: bool,
+
+    /// Whether the creator was removed.
+    pub creator: bool,
+
+    /// The removed links, from the metadata, waypoints, tracks and routes.
+    pub links: Vec<Link>,
+
+    /// Number of fields removed from waypoint extensions.
+    pub extension_fields: usize,
+}
+
+impl PiiReport {
+    /// Whether nothing was removed.
+    pub fn is_empty(&self) -> bool {
+        self == &PiiReport::default()
+    }
+}
+
+/// Gives the host of `href`, without scheme, user info and port.
+fn host(href: &str) -> &str {
+    let rest = href.split_once("://").map_or(href, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = authority.rsplit('@').next().unwrap_or("");
+    host.split(':').next().unwrap_or("")
+}
+
+/// remove_elements removes the elements with one of the local `names` from
+/// the XML fragment `xml`, with their content, giving the XML left and how
+/// many elements were removed. Comments, CDATA sections and processing
+/// instructions are passed over as they are. An element left open at the end
+/// of `xml`, as in XML cut short, is removed up to the end.
+pub(crate) fn remove_elements(xml: &str, names: &[String]) -> (String, usize) {
+    let mut kept = String::with_capacity(xml.len());
+    let mut removed = 0;
+    // The start of the element being removed, and how deep inside it the
+    // scan is.
+    let mut removing: Option<(usize, usize)> = None;
+    let mut copied = 0;
+    let mut index = 0;
+    while let Some(offset) = xml[index..].find('<') {
+        let start = index + offset;
+        let rest = &xml[start..];
+        let skip_to = |end: &str| rest.find(end).map(|at| start + at + end.len());
+        let end = if rest.starts_with("<!--") {
+            skip_to("-->")
+        } else if rest.starts_with("<![CDATA[") {
+            skip_to("]]>")
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            skip_to(">")
+        } else {
+            let end = tag_end(rest).map(|at| start + at);
+            let closing = rest.starts_with("</");
+            let empty = !closing && end.map_or(false, |end| xml[..end].ends_with("/>"));
+            let name = rest[if closing { 2 } else { 1 }..]
+                .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+                .next()
+                .unwrap_or("");
+            let local_name = name.rsplit(':').next().unwrap_or(name);
+            match (&mut removing, closing) {
+                (Some((_, depth)), true) => *depth -= 1,
+                (Some((_, depth)), false) if !empty => *depth += 1,
+                (Some(_), false) => {}
+                (None, false) if names.iter().any(|name| name.eq_ignore_ascii_case(local_name)) => {
+                    removing = Some((start, if empty { 0 } else { 1 }))
+                }
+                (None, _) => {}
+            }
+            end
+        };
+        let end = match end {
+            Some(end) => end,
+            None => break,
+        };
+        if let Some((from, 0)) = removing {
+            kept.push_str(&xml[copied..from]);
+            copied = end;
+            removed += 1;
+            removing = None;
+        }
+        index = end;
+    }
+    match removing {
+        Some((from, _)) => {
+            kept.push_str(&xml[copied..from]);
+            removed += 1;
+        }
+        None => kept.push_str(&xml[copied..]),
+    }
+    (kept, removed)
+}
+
+/// tag_end gives the index after the `>` ending the tag `tag` starts with,
+/// passing over the ones in quoted attribute values.
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '>') => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+impl PiiPolicy {
+    fn denies(&self, link: &Link) -> bool {
+        let host = host(&link.href).to_ascii_lowercase();
+        self.denied_link_hosts.iter().any(|denied| {
+            let denied = denied.to_ascii_lowercase();
+            host == denied || host.ends_with(&format!(".{}", denied))
+        })
+    }
+
+    fn scrub_links(&self, links: &mut Vec<Link>, report: &mut PiiReport) {
+        let (denied, kept): (Vec<Link>, Vec<Link>) = links.drain(..).partition(|link| self.denies(link));
+        *links = kept;
+        report.links.extend(denied);
+    }
+
+    fn scrub_waypoint<E: WaypointExtensions + Default>(&self, waypoint: &mut Waypoint<E>, report: &mut PiiReport) {
+        self.scrub_links(&mut waypoint.links, report);
+        if self.extensions {
+            report.extension_fields += E::scrub_pii(&mut waypoint.extensions, self);
+        }
+    }
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Removes personally identifying information according to `policy`,
+    /// giving a report of what was removed.
+    ///
+    /// Scrubbing is idempotent: scrubbing again with the same policy removes
+    /// nothing more.
+    ///
+    /// ```
+    /// use gpx::{Gpx, Metadata, Person, PiiPolicy};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut gpx: Gpx<EmptyExtensions> = Default::default();
+    /// gpx.metadata = Some(Metadata {
+    ///     author: Some(Person { name: Some("Jane Doe".into()), ..Default::default() }),
+    ///     ..Default::default()
+    /// });
+    ///
+    /// let report = gpx.scrub_pii(&PiiPolicy::default());
+    /// assert!(report.author);
+    /// assert!(gpx.metadata.unwrap().author.is_none());
+    /// ```
+    pub fn scrub_pii(&mut self, policy: &PiiPolicy) -> PiiReport {
+        let mut report = PiiReport::default();
+        if policy.creator {
+            report.creator = self.creator.take().is_some();
+        }
+        if let Some(metadata) = self.metadata.as_mut() {
+            if policy.author {
+                report.author = metadata.author.take().is_some();
+            }
+            if policy.metadata_links {
+                report.links.append(&mut metadata.links);
+            }
+        }
+        for waypoint in &mut self.waypoints {
+            policy.scrub_waypoint(waypoint, &mut report);
+        }
+        for track in &mut self.tracks {
+            policy.scrub_links(&mut track.links, &mut report);
+            for segment in &mut track.segments {
+                for point in &mut segment.points {
+                    policy.scrub_waypoint(point, &mut report);
+                }
+            }
+        }
+        for route in &mut self.routes {
+            policy.scrub_links(&mut route.links, &mut report);
+            for point in &mut route.points {
+                policy.scrub_waypoint(point, &mut report);
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::BufReader;
+
+    use crate::parser::extensions::RawExtensions;
+    use crate::{read, read_with_extensions, Gpx};
+
+    use super::{host, remove_elements, PiiPolicy};
+
+    #[test]
+    fn host_of_href() {
+        assert_eq!(host("https://www.strava.com/athletes/1"), "www.strava.com");
+        assert_eq!(host("http://user@example.com:8080?q"), "example.com");
+        assert_eq!(host("connect.garmin.com"), "connect.garmin.com");
+    }
+
+    #[test]
+    fn remove_serial_elements() {
+        let names = PiiPolicy::default().serial_elements;
+        let remove = |xml: &str| remove_elements(xml, &names);
+
+        assert_eq!(
+            remove("<d:Device><d:SerialNumber>123</d:SerialNumber><d:Model>Edge</d:Model></d:Device>"),
+            (String::from("<d:Device><d:Model>Edge</d:Model></d:Device>"), 1)
+        );
+        assert_eq!(
+            remove("<a x='>'/><unitid v=\"1\"/><Serial><Serial>1</Serial><b/></Serial><c/>"),
+            (String::from("<a x='>'/><c/>"), 2)
+        );
+        // Names in comments, CDATA sections and text are left as they are.
+        let untouched = "<!-- <Serial>1</Serial> --><a><![CDATA[<Serial>]]>Serial</a>";
+        assert_eq!(remove(untouched), (String::from(untouched), 0));
+        // An element left open, as in XML cut short, is removed to the end.
+        assert_eq!(remove("<a/><SerialNumber>12"), (String::from("<a/>"), 1));
+        assert_eq!(remove_elements("<Serial>1</Serial>", &[]), (String::from("<Serial>1</Serial>"), 0));
+    }
+
+    #[test]
+    fn scrub_raw_extensions() {
+        let xml = "<gpx version=\"1.1\" creator=\"test\" xmlns:d=\"urn:device\">
+            <wpt lat=\"1\" lon=\"2\"><extensions>
+                <d:Device><d:SerialNumber>3996123456</d:SerialNumber><d:Model>Edge 530</d:Model></d:Device>
+            </extensions></wpt>
+        </gpx>";
+        let mut gpx: Gpx<RawExtensions> = read_with_extensions(xml.as_bytes()).unwrap();
+
+        let report = gpx.scrub_pii(&PiiPolicy::default());
+        assert_eq!(report.extension_fields, 1);
+        let extensions = &gpx.waypoints[0].extensions.xml;
+        assert!(!extensions.contains("3996123456"));
+        assert!(extensions.contains("<d:Model>Edge 530</d:Model>"));
+
+        // Scrubbing again changes nothing.
+        let scrubbed = gpx.clone();
+        assert!(gpx.scrub_pii(&PiiPolicy::default()).is_empty());
+        assert_eq!(gpx, scrubbed);
+
+        // Extensions are left alone when the policy says so.
+        let mut gpx: Gpx<RawExtensions> = read_with_extensions(xml.as_bytes()).unwrap();
+        let policy = PiiPolicy {
+            extensions: false,
+            ..Default::default()
+        };
+        assert_eq!(gpx.scrub_pii(&policy).extension_fields, 0);
+        assert!(gpx.waypoints[0].extensions.xml.contains("3996123456"));
+    }
+
+    #[test]
+    fn scrub_fixture() {
+        let file = File::open("tests/fixtures/with_personal_data.gpx").unwrap();
+        let mut gpx = read(BufReader::new(file)).unwrap();
+        let original = gpx.clone();
+
+        let report = gpx.scrub_pii(&PiiPolicy::default());
+        assert!(report.author);
+        assert!(report.creator);
+        let removed: Vec<&str> = report.links.iter().map(|link| link.href.as_str()).collect();
+        assert_eq!(
+            removed,
+            [
+                "https://connect.garmin.com/modern/profile/janedoe",
+                "https://www.strava.com/athletes/1234567",
+                "https://www.strava.com/activities/987654321",
+            ]
+        );
+
+        // Attribution and geometry are kept.
+        let metadata = gpx.metadata.as_ref().unwrap();
+        assert!(metadata.author.is_none());
+        assert!(metadata.links.is_empty());
+        assert_eq!(metadata.copyright, original.metadata.as_ref().unwrap().copyright);
+        assert_eq!(
+            metadata.copyright.as_ref().unwrap().license.as_deref(),
+            Some("https://creativecommons.org/licenses/by/4.0/")
+        );
+        assert!(gpx.creator.is_none());
+        assert_eq!(gpx.waypoints[0].links.len(), 1);
+        assert_eq!(gpx.tracks[0].links[0].href, "https://example.org/route-description");
+        assert_eq!(gpx.tracks[0].multilinestring(), original.tracks[0].multilinestring());
+        assert_eq!(gpx.waypoints[0].point(), original.waypoints[0].point());
+
+        // Scrubbing again changes nothing.
+        let scrubbed = gpx.clone();
+        assert!(gpx.scrub_pii(&PiiPolicy::default()).is_empty());
+        assert_eq!(gpx, scrubbed);
+    }
+}
@@ -0,0 +1,362 @@
+//! query finds points on tracks, by proximity, by distance along them or by
+//! time.
+
+use geo_types::Point;
+use time::Duration;
+
+use crate::distance::haversine_distance;
+use crate::parser::extensions::WaypointExtensions;
+use crate::resample::interpolate;
+use crate::{Time, Track, TrackSegment, Waypoint};
+
+impl<E: WaypointExtensions + Default> TrackSegment<E> {
+    /// Gives the haversine distance in meters from the first point to each
+    /// point of the segment, so the first is 0 and the last is the
+    /// [`length_meters`](TrackSegment::length_meters).
+    pub fn cumulative_distances(&self) -> Vec<f64> {
+        let mut total = 0.0;
+        let mut distances = Vec::with_capacity(self.points.len());
+        for (i, point) in self.points.iter().enumerate() {
+            if i > 0 {
+                total += haversine_distance(self.points[i - 1].point(), point.point());
+            }
+            distances.push(total);
+        }
+        distances
+    }
+
+    /// Gives the point `meters` along the segment from its first point,
+    /// interpolated between the points on either side, or `None` when the
+    /// segment is shorter or `meters` negative.
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+    /// segment.points.push(Waypoint::new(Point::new(0.0, 0.0)));
+    /// segment.points.push(Waypoint::new(Point::new(0.0, 0.01)));
+    ///
+    /// let halfway = segment.point_at_distance(segment.length_meters() / 2.0).unwrap();
+    /// assert!((halfway.y() - 0.005).abs() < 1e-9);
+    /// assert_eq!(segment.point_at_distance(2000.0), None);
+    /// ```
+    pub fn point_at_distance(&self, meters: f64) -> Option<Point<f64>> {
+        if meters < 0.0 || meters.is_nan() {
+            return None;
+        }
+        let distances = self.cumulative_distances();
+        // The first point at or beyond the distance.
+        let end = distances.iter().position(|&distance| distance >= meters)?;
+        let end_point = self.points[end].point();
+        if end == 0 || distances[end] == meters {
+            return Some(end_point);
+        }
+        let start_point = self.points[end - 1].point();
+        let fraction = (meters - distances[end - 1]) / (distances[end] - distances[end - 1]);
+        Some(Point::new(
+            start_point.x() + (end_point.x() - start_point.x()) * fraction,
+            start_point.y() + (end_point.y() - start_point.y()) * fraction,
+        ))
+    }
+
+    /// Gives the point of the segment closest to `target`, as its index and
+    /// its haversine distance in meters to `target`, or `None` for a segment
+    /// without points. Of points as close, the first is given.
+    pub fn closest_point(&self, target: Point<f64>) -> Option<(usize, f64)> {
+        let mut closest: Option<(usize, f64)> = None;
+        for (index, point) in self.points.iter().enumerate() {
+            let distance = haversine_distance(point.point(), target);
+            if closest.map_or(true, |(_, best)| distance < best) {
+                closest = Some((index, distance));
+            }
+        }
+        closest
+    }
+
+    /// Splits the segment at its point closest to `p`, which ends the first
+    /// segment and starts the second, so the two still meet. A segment
+    /// without points gives two segments without points.
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+    /// for x in [0.0, 0.001, 0.002, 0.003] {
+    ///     segment.points.push(Waypoint::new(Point::new(x, 0.0)));
+    /// }
+    ///
+    /// let (before, after) = segment.split_at_nearest(&Point::new(0.0021, 0.0001));
+    /// assert_eq!(before.points.len(), 3);
+    /// assert_eq!(after.points.len(), 2);
+    /// assert_eq!(before.points.last(), after.points.first());
+    /// ```
+    pub fn split_at_nearest(&self, p: &Point<f64>) -> (TrackSegment<E>, TrackSegment<E>)
+    where
+        E: Clone,
+    {
+        match self.closest_point(*p) {
+            Some((index, _)) => (
+                TrackSegment {
+                    points: self.points[..=index].to_vec(),
+                },
+                TrackSegment {
+                    points: self.points[index..].to_vec(),
+                },
+            ),
+            None => (TrackSegment::new(), TrackSegment::new()),
+        }
+    }
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Gives the point of the track closest to `target`, as the index of its
+    /// segment, its index within the segment and its haversine distance in
+    /// meters to `target`, or `None` for a track without points.
+    ///
+    /// Only the points themselves are considered, not the lines between
+    /// them. The points are scanned one by one, which is fine for single
+    /// queries on a track of any length.
+    pub fn closest_point(&self, target: Point<f64>) -> Option<(usize, usize, f64)> {
+        let mut closest: Option<(usize, usize, f64)> = None;
+        for (segment_index, segment) in self.segments.iter().enumerate() {
+            if let Some((point_index, distance)) = segment.closest_point(target) {
+                if closest.map_or(true, |(_, _, best)| distance < best) {
+                    closest = Some((segment_index, point_index, distance));
+                }
+            }
+        }
+        closest
+    }
+
+    /// Gives the position of the track at `time`, interpolated between the
+    /// points timed just before and just after it, or `None` when `time` is
+    /// outside the times of the points.
+    ///
+    /// The point given is new: its coordinates are interpolated, as is its
+    /// elevation when the points around it both have one, its time is `time`
+    /// and all else is left at its default. Points without a time are
+    /// skipped, and the last point of a segment is followed by the first of
+    /// the next one, so positions in a gap between segments are interpolated
+    /// too.
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{Track, TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    /// use time::{Duration, OffsetDateTime};
+    ///
+    /// let start = OffsetDateTime::from_unix_timestamp(1_600_000_000).unwrap();
+    /// let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+    /// for (i, x) in [0.0, 0.002].iter().enumerate() {
+    ///     let mut point = Waypoint::new(Point::new(*x, 0.0));
+    ///     point.time = Some((start + Duration::minutes(i as i64)).into());
+    ///     segment.points.push(point);
+    /// }
+    /// let mut track = Track::new();
+    /// track.segments.push(segment);
+    ///
+    /// let photo = (start + Duration::seconds(15)).into();
+    /// let position = track.position_at_time(photo).unwrap();
+    /// assert!((position.point().x() - 0.0005).abs() < 1e-12);
+    /// assert_eq!(track.position_at_time((start - Duration::seconds(1)).into()), None);
+    /// ```
+    pub fn position_at_time(&self, time: Time) -> Option<Waypoint<E>> {
+        self.position_at_time_with(time, Duration::ZERO)
+    }
+
+    /// Gives the position of the track at `time` like
+    /// [`Track::position_at_time`], except that a `time` up to `snap` before
+    /// the first point or after the last one gives the position of that
+    /// point.
+    pub fn position_at_time_with(&self, time: Time, snap: Duration) -> Option<Waypoint<E>> {
+        let points: Vec<_> = self
+            .segments
+            .iter()
+            .flat_map(|segment| &segment.points)
+            .filter_map(|point| Some((point, point.time?)))
+            .collect();
+        let (first, last) = (points.first()?, points.last()?);
+
+        let (a, b, fraction) = if time <= first.1 || time >= last.1 {
+            let (edge, edge_time) = if time <= first.1 { first } else { last };
+            if (time - *edge_time).abs() > snap {
+                return None;
+            }
+            (*edge, *edge, 0.0)
+        } else {
+            let pair = points
+                .windows(2)
+                .find(|pair| pair[0].1 <= time && time <= pair[1].1)?;
+            let ((a, a_time), (b, b_time)) = (pair[0], pair[1]);
+            let span = (b_time - a_time).as_seconds_f64();
+            let fraction = if span > 0.0 {
+                (time - a_time).as_seconds_f64() / span
+            } else {
+                0.0
+            };
+            (a, b, fraction)
+        };
+        let mut point = interpolate(a, b, fraction);
+        point.time = Some(time);
+        Some(point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+    use geo_types::Point;
+    use time::{Duration, OffsetDateTime};
+
+    use crate::distance::haversine_distance;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{Time, Track, TrackSegment, Waypoint};
+
+    /// An L-shaped segment, going north and then east from the origin, with
+    /// its corner at (0, 0.002).
+    fn l_shape() -> TrackSegment<EmptyExtensions> {
+        let points = [(0.0, 0.0), (0.0, 0.001), (0.0, 0.002), (0.001, 0.002), (0.002, 0.002)];
+        TrackSegment {
+            points: points
+                .iter()
+                .map(|&(x, y)| Waypoint::new(Point::new(x, y)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn cumulative_distances_along_l_shape() {
+        let segment = l_shape();
+        let distances = segment.cumulative_distances();
+        assert_eq!(distances.len(), 5);
+        assert_eq!(distances[0], 0.0);
+        assert_approx_eq!(distances[2], 222.39, 0.01);
+        assert_approx_eq!(distances[4], segment.length_meters());
+        assert!(TrackSegment::<EmptyExtensions>::new().cumulative_distances().is_empty());
+    }
+
+    #[test]
+    fn point_at_distance_around_corner() {
+        let segment = l_shape();
+        let distances = segment.cumulative_distances();
+
+        assert_eq!(segment.point_at_distance(0.0), Some(Point::new(0.0, 0.0)));
+        assert_eq!(segment.point_at_distance(distances[2]), Some(Point::new(0.0, 0.002)));
+
+        // Half way along the first leg after the corner.
+        let point = segment.point_at_distance((distances[2] + distances[3]) / 2.0).unwrap();
+        assert_approx_eq!(point.x(), 0.0005, 1e-9);
+        assert_approx_eq!(point.y(), 0.002, 1e-9);
+
+        assert_eq!(segment.point_at_distance(distances[4] + 1.0), None);
+        assert_eq!(segment.point_at_distance(-1.0), None);
+        assert_eq!(TrackSegment::<EmptyExtensions>::new().point_at_distance(0.0), None);
+    }
+
+    #[test]
+    fn closest_point_near_corner() {
+        let mut track = Track::new();
+        track.segments.push(TrackSegment {
+            points: vec![Waypoint::new(Point::new(1.0, 1.0))],
+        });
+        track.segments.push(l_shape());
+
+        let target = Point::new(0.0001, 0.0021);
+        let (segment, point, distance) = track.closest_point(target).unwrap();
+        assert_eq!((segment, point), (1, 2));
+        assert_eq!(distance, haversine_distance(Point::new(0.0, 0.002), target));
+
+        assert_eq!(Track::<EmptyExtensions>::new().closest_point(target), None);
+    }
+
+    #[test]
+    fn split_at_nearest_corner() {
+        let segment = l_shape();
+        let (before, after) = segment.split_at_nearest(&Point::new(0.0001, 0.0021));
+        assert_eq!(before.points, segment.points[..3]);
+        assert_eq!(after.points, segment.points[2..]);
+
+        // The halves reconnect at the corner, which both hold.
+        assert_eq!(before.points.last(), after.points.first());
+        let mut joined = before.points.clone();
+        joined.extend(after.points.iter().skip(1).cloned());
+        assert_eq!(joined, segment.points);
+        assert_approx_eq!(before.length_meters() + after.length_meters(), segment.length_meters());
+
+        // At either end, one half is the pivot alone.
+        let (before, after) = segment.split_at_nearest(&Point::new(-1.0, -1.0));
+        assert_eq!(before.points, segment.points[..1]);
+        assert_eq!(after.points, segment.points);
+
+        let (before, after) = TrackSegment::<EmptyExtensions>::new().split_at_nearest(&Point::new(0.0, 0.0));
+        assert!(before.points.is_empty() && after.points.is_empty());
+    }
+
+    fn time(seconds: i64) -> Time {
+        OffsetDateTime::from_unix_timestamp(1_600_000_000 + seconds)
+            .unwrap()
+            .into()
+    }
+
+    /// A track with points a minute apart, going east and climbing, and a
+    /// point without a time in between.
+    fn timed_track() -> Track<EmptyExtensions> {
+        let mut points = Vec::new();
+        for (x, elevation, seconds) in [(0.0, 100.0, Some(0)), (0.5, 0.0, None), (0.001, 110.0, Some(60))] {
+            let mut point = Waypoint::new(Point::new(x, 0.0));
+            point.elevation = Some(elevation);
+            point.time = seconds.map(time);
+            point.name = Some(String::from("recorded"));
+            points.push(point);
+        }
+        let mut last = Waypoint::new(Point::new(0.002, 0.0));
+        last.time = Some(time(120));
+        let mut track = Track::new();
+        track.segments.push(TrackSegment { points });
+        track.segments.push(TrackSegment { points: vec![last] });
+        track
+    }
+
+    #[test]
+    fn position_at_time_on_point() {
+        let track = timed_track();
+        let position = track.position_at_time(time(60)).unwrap();
+        assert_eq!(position.point(), Point::new(0.001, 0.0));
+        assert_eq!(position.elevation, Some(110.0));
+        assert_eq!(position.time, Some(time(60)));
+        assert_eq!(position.name, None);
+    }
+
+    #[test]
+    fn position_at_time_halfway() {
+        let track = timed_track();
+        let position = track.position_at_time(time(30)).unwrap();
+        assert_approx_eq!(position.point().x(), 0.0005);
+        assert_approx_eq!(position.elevation.unwrap(), 105.0);
+        assert_eq!(position.time, Some(time(30)));
+
+        // Across segments, without elevation after the gap.
+        let position = track.position_at_time(time(90)).unwrap();
+        assert_approx_eq!(position.point().x(), 0.0015);
+        assert_eq!(position.elevation, None);
+    }
+
+    #[test]
+    fn position_at_time_edges() {
+        let track = timed_track();
+        assert_eq!(track.position_at_time(time(0)).unwrap().point(), Point::new(0.0, 0.0));
+        assert_eq!(track.position_at_time(time(-1)), None);
+        assert_eq!(track.position_at_time(time(121)), None);
+
+        let snapped = track.position_at_time_with(time(125), Duration::seconds(10)).unwrap();
+        assert_eq!(snapped.point(), Point::new(0.002, 0.0));
+        assert_eq!(snapped.time, Some(time(125)));
+        assert_eq!(track.position_at_time_with(time(-11), Duration::seconds(10)), None);
+
+        assert_eq!(Track::<EmptyExtensions>::new().position_at_time(time(0)), None);
+    }
+}
@@ -1,27 +1,352 @@
 //! Reads an activity from GPX format.
 
-use std::io::Read;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::time::Duration;
 
-use crate::{Gpx, GpxVersion};
-use crate::errors::GpxResult;
-use crate::parser::{create_context, gpx};
+#[cfg(feature = "use-serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Gpx, GpxVersion, InvariantPolicy, Waypoint};
+use crate::errors::{GpxResult, GpxWarning, ReadWarning};
+use crate::parser::{
+    create_context, create_context_with_options, create_tracked_context_with_options, gpx, Context, TrackingReader,
+};
 use crate::parser::extensions::{EmptyExtensions, WaypointExtensions};
+use crate::repair::repair_ampersands;
+
+/// Options controlling how tolerant the parser is of malformed input.
+///
+/// The default is strict parsing, which fails on anything the GPX schema does
+/// not allow.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
+pub struct ParseOptions {
+    /// Recover from known producer mistakes instead of failing. Every recovery
+    /// is recorded as a [`GpxWarning`](crate::errors::GpxWarning).
+    ///
+    /// This includes control characters that XML disallows, such as NULs
+    /// left by a firmware in a name, which are stripped from the input
+    /// before it is parsed. Stripping bytes suits UTF-8 and the other
+    /// encodings that extend ASCII, but not UTF-16 or UTF-32, whose input is
+    /// read as it is.
+    pub lenient: bool,
+    /// Round coordinates to this many decimal places as they are parsed, see
+    /// [`ParseOptions::quantize_coordinates`].
+    pub coordinate_decimals: Option<u32>,
+    /// Satellite counts above this are taken for a firmware's way of saying
+    /// "unknown", such as 255, and left out with a
+    /// [`GpxWarning::UnknownSentinel`](crate::errors::GpxWarning::UnknownSentinel).
+    /// Defaults to 99, the most NMEA sentences can hold; `None` keeps every
+    /// count.
+    pub sat_sentinel: Option<u64>,
+    /// Limits on the input, see [`ParseLimits`].
+    pub limits: ParseLimits,
+    /// Gives up with a [`GpxError::DeadlineExceeded`](crate::errors::GpxError::DeadlineExceeded)
+    /// once parsing has taken longer than this, counted from when reading
+    /// starts, whatever the size of the input. This needs a clock, which
+    /// `wasm32-unknown-unknown` lacks: parsing with a timeout fails there
+    /// with a [`GpxError::UnsupportedOption`](crate::errors::GpxError::UnsupportedOption).
+    pub timeout: Option<Duration>,
+    /// Runs [`Gpx::ensure_invariants`] with this policy after parsing,
+    /// recording every fixup as a
+    /// [`GpxWarning::Normalized`](crate::errors::GpxWarning::Normalized).
+    pub post_normalize: Option<InvariantPolicy>,
+    /// Collects the comments outside of the elements of the document into
+    /// [`Gpx::comments`] instead of discarding them.
+    pub keep_comments: bool,
+    /// How the extensions of points are read, see [`ExtensionsMode`].
+    pub extensions: ExtensionsMode,
+    /// How many track and route points the document is expected to hold.
+    /// The first track segment or route reserves room for those not read yet
+    /// at once, up to about a million, instead of growing point by point,
+    /// which takes many copies of segments of hundreds of thousands of
+    /// points. The segments and routes after it grow as they would without
+    /// a hint. [`read_file`] guesses it from the size of the file.
+    pub points_capacity_hint: Option<usize>,
+    /// Shrinks the points of every track segment and route to fit once read,
+    /// giving back what [`ParseOptions::points_capacity_hint`] reserved beyond
+    /// them.
+    pub shrink_points: bool,
+    /// Escapes the ampersands that start no reference as `&amp;` before
+    /// parsing, instead of failing on them, recording each as a
+    /// [`GpxWarning::RawAmpersand`](crate::errors::GpxWarning::RawAmpersand).
+    /// Those of CDATA sections, comments and processing instructions are
+    /// left as they are, and so is input in an encoding that is not ASCII
+    /// compatible, such as UTF-16.
+    ///
+    /// The repair needs the whole input, which [`read_with_options`] and
+    /// [`read_with_warnings`] read into memory first; the readers going
+    /// through the input as it comes, such as [`GpxTrackIter`](crate::GpxTrackIter),
+    /// do not repair it. The positions of the other warnings count the
+    /// repaired input.
+    pub repair_entities: bool,
+    /// Which XML parser reads the document, see [`XmlParser`].
+    pub xml_parser: XmlParser,
+}
+
+/// How the `<extensions>` of points are read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
+pub enum ExtensionsMode {
+    /// Reads them with the extensions type of the document, the default.
+    #[default]
+    Parse,
+    /// Skips them as [`EmptyExtensions`] does, whatever the extensions type,
+    /// leaving the default value in every point. For when only the points
+    /// themselves are needed from files with large extensions, such as the
+    /// cache descriptions and logs of geocaching pocket queries.
+    Skip,
+    /// Keeps at most this many bytes of the XML of the extensions of each
+    /// point with [`RawExtensions`](crate::parser::extensions::RawExtensions),
+    /// marking the ones cut short as
+    /// [`truncated`](crate::parser::extensions::RawXml::truncated). Past the
+    /// cap the rest of the extensions is skipped without being kept, so that
+    /// large extensions take no more memory than the cap. Other extensions
+    /// types read them as with [`ExtensionsMode::Parse`].
+    RawCapped(usize),
+}
+
+/// The XML parser reading a document.
+///
+/// Both give the same documents and fail on the same mistakes, and the
+/// `quick-xml` feature only makes [`XmlParser::QuickXml`] available, so that
+/// enabling it for one crate does not change how the others parse. For the
+/// same reason the enum is non-exhaustive, so that matching on it compiles
+/// whether or not a crate in the build enables the feature. The two parsers
+/// still differ in what the tests do not hold them to:
+///
+/// - The entities declared in the document type expand as text with
+///   quick-xml, where xml-rs parses the markup in them.
+/// - Errors carry other messages and positions for some mistakes, such as
+///   malformed markup, which the two parsers find at different places.
+/// - Input in UTF-16, ISO-8859-1 or US-ASCII is transcoded to UTF-8 for
+///   quick-xml, so positions and offsets count the bytes of the UTF-8 rather
+///   than those of the input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub enum XmlParser {
+    /// xml-rs, the default, which reads no further into the input than the
+    /// events it gives.
+    #[default]
+    XmlRs,
+    /// quick-xml, with the `quick-xml` feature, which reads the input in
+    /// blocks rather than byte by byte.
+    #[cfg(feature = "quick-xml")]
+    QuickXml,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            lenient: false,
+            coordinate_decimals: None,
+            sat_sentinel: Some(99),
+            limits: Default::default(),
+            timeout: None,
+            post_normalize: None,
+            keep_comments: false,
+            extensions: ExtensionsMode::Parse,
+            points_capacity_hint: None,
+            shrink_points: false,
+            repair_entities: false,
+            xml_parser: XmlParser::XmlRs,
+        }
+    }
+}
+
+/// Limits on the input, guarding against documents crafted to exhaust memory,
+/// such as the "billion laughs" of entities expanding into other entities.
+/// Exceeding one is a [`GpxError::LimitExceeded`](crate::errors::GpxError::LimitExceeded),
+/// in lenient mode too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
+pub struct ParseLimits {
+    /// How many times entities may expand into other entities. Defaults to 8.
+    pub max_entity_expansion_depth: u8,
+    /// How many characters entities may expand to. Defaults to 100 000.
+    pub max_entity_expansion_length: usize,
+    /// How deeply elements may nest inside the elements the parser skips or
+    /// keeps as they are, such as unknown elements and extensions. Defaults
+    /// to 256.
+    pub max_element_depth: usize,
+    /// How many waypoints, route points and track points a document may
+    /// hold, or no limit, the default. A document with more is given up on
+    /// at the first point over the limit, without reading the rest.
+    pub max_points: Option<usize>,
+}
+
+impl Default for ParseLimits {
+    fn default() -> ParseLimits {
+        ParseLimits {
+            max_entity_expansion_depth: 8,
+            max_entity_expansion_length: 100_000,
+            max_element_depth: 256,
+            max_points: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Options for strict parsing, the default.
+    pub fn strict() -> ParseOptions {
+        Default::default()
+    }
+
+    /// Options for lenient parsing.
+    pub fn lenient() -> ParseOptions {
+        ParseOptions {
+            lenient: true,
+            ..Default::default()
+        }
+    }
+
+    /// Rounds every coordinate to `decimals` decimal places as it is parsed.
+    /// Five places, about a meter, is plenty for drawing tiles, and points
+    /// quantized this way make for smaller output and compress better.
+    ///
+    /// ```
+    /// use gpx::{read_with_options, Gpx, ParseOptions};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let data = "<gpx version=\"1.1\"><wpt lat=\"51.4778412\" lon=\"-0.0014863\"/></gpx>";
+    ///
+    /// let options = ParseOptions::strict().quantize_coordinates(5);
+    /// let gpx: Gpx<EmptyExtensions> = read_with_options(data.as_bytes(), options).unwrap();
+    /// assert_eq!(gpx.waypoints[0].point().y(), 51.47784);
+    /// assert_eq!(gpx.waypoints[0].point().x(), -0.00149);
+    /// ```
+    pub fn quantize_coordinates(self, decimals: u32) -> ParseOptions {
+        ParseOptions {
+            coordinate_decimals: Some(decimals),
+            ..self
+        }
+    }
+
+    /// Limits the input to `limits` instead of the default ones.
+    pub fn with_limits(self, limits: ParseLimits) -> ParseOptions {
+        ParseOptions { limits, ..self }
+    }
+
+    /// Collects the comments outside of the elements of the document into
+    /// [`Gpx::comments`].
+    ///
+    /// ```
+    /// use gpx::{read_with_options, Gpx, ParseOptions};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let data = "<!-- exported by logger v2 --><gpx version=\"1.1\"></gpx>";
+    ///
+    /// let gpx: Gpx<EmptyExtensions> =
+    ///     read_with_options(data.as_bytes(), ParseOptions::strict().with_comments()).unwrap();
+    /// assert_eq!(gpx.comments, [" exported by logger v2 "]);
+    /// ```
+    pub fn with_comments(self) -> ParseOptions {
+        ParseOptions {
+            keep_comments: true,
+            ..self
+        }
+    }
+
+    /// Reads the extensions of points as `mode` says.
+    ///
+    /// ```
+    /// use gpx::{read_with_options, ExtensionsMode, Gpx, ParseOptions};
+    /// use gpx::parser::extensions::RawExtensions;
+    ///
+    /// let data = r#"<gpx version="1.1"><wpt lat="1" lon="2"><extensions><note>long</note></extensions></wpt></gpx>"#;
+    ///
+    /// let options = ParseOptions::strict().with_extensions(ExtensionsMode::Skip);
+    /// let gpx: Gpx<RawExtensions> = read_with_options(data.as_bytes(), options).unwrap();
+    /// assert_eq!(gpx.waypoints[0].extensions.xml, "");
+    ///
+    /// let options = ParseOptions::strict().with_extensions(ExtensionsMode::RawCapped(10));
+    /// let gpx: Gpx<RawExtensions> = read_with_options(data.as_bytes(), options).unwrap();
+    /// assert_eq!(gpx.waypoints[0].extensions.xml, "<note>long");
+    /// assert!(gpx.waypoints[0].extensions.truncated);
+    /// ```
+    pub fn with_extensions(self, mode: ExtensionsMode) -> ParseOptions {
+        ParseOptions {
+            extensions: mode,
+            ..self
+        }
+    }
+
+    /// Reserves room for `points` track and route points in the first track
+    /// segment or route, shrinking the segments and routes to fit once read,
+    /// see [`ParseOptions::points_capacity_hint`].
+    pub fn with_points_capacity_hint(self, points: usize) -> ParseOptions {
+        ParseOptions {
+            points_capacity_hint: Some(points),
+            shrink_points: true,
+            ..self
+        }
+    }
+
+    /// Escapes the ampersands that start no reference before parsing, see
+    /// [`ParseOptions::repair_entities`].
+    ///
+    /// ```
+    /// use gpx::{read_with_warnings, ParseOptions};
+    /// use gpx::errors::GpxWarning;
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let data = r#"<gpx version="1.1"><wpt lat="1" lon="2"><link href="https://example.com/?a=1&b=2"/></wpt></gpx>"#;
+    ///
+    /// let options = ParseOptions::strict().with_entity_repair();
+    /// let outcome = read_with_warnings::<_, EmptyExtensions>(data.as_bytes(), options).unwrap();
+    /// assert_eq!(outcome.gpx.waypoints[0].links[0].href, "https://example.com/?a=1&b=2");
+    /// assert_eq!(outcome.warnings[0].warning, GpxWarning::RawAmpersand { offset: 76 });
+    /// ```
+    pub fn with_entity_repair(self) -> ParseOptions {
+        ParseOptions {
+            repair_entities: true,
+            ..self
+        }
+    }
+
+    /// Parses the document with `parser`.
+    pub fn with_xml_parser(self, parser: XmlParser) -> ParseOptions {
+        ParseOptions {
+            xml_parser: parser,
+            ..self
+        }
+    }
+
+    /// Gives up parsing once it has taken longer than `timeout`.
+    pub fn with_timeout(self, timeout: Duration) -> ParseOptions {
+        ParseOptions {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+}
 
 /// Reads an activity in GPX format.
 ///
 /// Takes any `std::io::Read` as its reader, and returns a
 /// `Result<Gpx>`.
 ///
+/// `read` does not panic, whatever the input: bytes that are not GPX, or not
+/// even XML, give an error. The fuzz targets in `fuzz/` check this. The
+/// guarantee is for `read` and its default options only; not every
+/// combination of [`ParseOptions`] is fuzzed. As memory use grows with the
+/// input, read input that is not trusted with [`read_with_options`] and
+/// [`ParseLimits`].
+///
 /// ```
 /// use std::io::BufReader;
 /// use gpx::read;
 /// use gpx::Gpx;
 /// use gpx::errors::GpxError;
+/// use gpx::parser::extensions::EmptyExtensions;
 ///
 /// // You can give it anything that implements `std::io::Read`.
 /// let data = BufReader::new("<gpx></gpx>".as_bytes());
 ///
-/// let res: Result<Gpx, GpxError> = read(data);
+/// let res: Result<Gpx<EmptyExtensions>, GpxError> = read(data);
 ///
 /// match res {
 ///     Ok(gpx) => {
@@ -37,7 +362,249 @@ pub fn read<R: Read>(reader: R) -> GpxResult<Gpx<EmptyExtensions>> {
     read_with_extensions::<R, EmptyExtensions>(reader)
 }
 
+/// Reads an activity in GPX format from bytes already in memory, such as a
+/// file a browser handed over. This is the way to read in WebAssembly, where
+/// there are no files to open and the bytes of a JavaScript `Uint8Array`
+/// arrive as a slice.
+///
+/// ```
+/// use gpx::read_from_slice;
+///
+/// let bytes = b"<gpx version=\"1.1\"><wpt lat=\"46.5597\" lon=\"8.6345\"/></gpx>";
+/// let gpx = read_from_slice(bytes).unwrap();
+/// assert_eq!(gpx.waypoints.len(), 1);
+/// ```
+pub fn read_from_slice(bytes: &[u8]) -> GpxResult<Gpx<EmptyExtensions>> {
+    read(bytes)
+}
+
+/// How many bytes of a file [`read_file`] counts for every point, about what
+/// a track point with an elevation, a time and a heart rate takes, indented.
+/// A point takes some 400 bytes in memory, so the points reserved take no
+/// more than twice the size of the file.
+const FILE_BYTES_PER_POINT: u64 = 256;
+
+/// Reads an activity in GPX format from the file at `path`, buffering it.
+/// Failing to open or read the file gives a
+/// [`GpxError::IoError`](crate::errors::GpxError::IoError).
+///
+/// The points of the first track segment or route are reserved at once from
+/// the size of the file, as with [`ParseOptions::with_points_capacity_hint`].
+///
+/// ```
+/// let gpx = gpx::read_file("tests/fixtures/wikipedia_example.gpx").unwrap();
+/// assert_eq!(gpx.tracks.len(), 1);
+/// ```
+pub fn read_file<P: AsRef<Path>>(path: P) -> GpxResult<Gpx<EmptyExtensions>> {
+    let file = File::open(path)?;
+    let points = file.metadata()?.len() / FILE_BYTES_PER_POINT;
+    let options = ParseOptions::strict().with_points_capacity_hint(points as usize);
+    read_with_options(BufReader::new(file), options)
+}
+
 
 pub fn read_with_extensions<R: Read, E: WaypointExtensions + Default>(reader: R) -> GpxResult<Gpx<E>> {
     gpx::consume(&mut create_context::<R, E>(reader, GpxVersion::Unknown))
 }
+
+/// Reads an activity in GPX format, using the given [`ParseOptions`].
+///
+/// ```
+/// use gpx::{read_with_options, Gpx, ParseOptions};
+/// use gpx::parser::extensions::EmptyExtensions;
+///
+/// let data = "<gpx version=\"1.1\"><metadata><time>1565653500</time></metadata></gpx>";
+///
+/// let gpx: Gpx<EmptyExtensions> = read_with_options(data.as_bytes(), ParseOptions::lenient()).unwrap();
+/// assert!(gpx.metadata.unwrap().time.is_some());
+/// ```
+pub fn read_with_options<R: Read, E: WaypointExtensions + Default>(
+    reader: R,
+    options: ParseOptions,
+) -> GpxResult<Gpx<E>> {
+    if options.repair_entities {
+        return Ok(read_repaired(reader, options)?.gpx);
+    }
+    gpx::consume(&mut create_context_with_options::<R, E>(
+        reader,
+        GpxVersion::Unknown,
+        options,
+    ))
+}
+
+/// Reads an activity in GPX format, handing every track and route point to
+/// `f` as it is parsed instead of keeping it.
+///
+/// The returned [`Gpx`] holds everything but those points, so its tracks and
+/// routes are empty. Memory use therefore stays low however many points the
+/// document has, which suits aggregating over very large files.
+///
+/// ```
+/// use gpx::{read_with_waypoint_callback, Gpx};
+/// use gpx::parser::extensions::EmptyExtensions;
+///
+/// let data = "<gpx version=\"1.1\"><trk><trkseg>
+///     <trkpt lat=\"1.0\" lon=\"2.0\"/>
+///     <trkpt lat=\"1.5\" lon=\"2.5\"/>
+/// </trkseg></trk></gpx>";
+///
+/// let mut count = 0;
+/// let gpx: Gpx<EmptyExtensions> = read_with_waypoint_callback(data.as_bytes(), |_| count += 1).unwrap();
+/// assert_eq!(count, 2);
+/// assert!(gpx.tracks[0].segments[0].points.is_empty());
+/// ```
+pub fn read_with_waypoint_callback<R, E, F>(reader: R, mut f: F) -> GpxResult<Gpx<E>>
+where
+    R: Read,
+    E: WaypointExtensions + Default,
+    F: FnMut(&Waypoint<E>),
+{
+    let mut context = create_context::<R, E>(reader, GpxVersion::Unknown);
+    context.set_on_point(&mut f);
+    gpx::consume(&mut context)
+}
+
+/// Reads an activity in GPX format, offering every child of the `gpx`
+/// element outside the GPX schema, such as the additions of formats derived
+/// from GPX, to `handler` with its local name.
+///
+/// The handler is called with the starting tag of the element peeked at, not
+/// consumed yet. It either consumes the element, up to and including its end
+/// tag, and gives `true`, or consumes nothing and gives `false`, leaving the
+/// element to be skipped in lenient mode or to fail parsing otherwise. The
+/// context reads the input through a [`TrackingReader`].
+///
+/// ```
+/// use gpx::read_with_root_handler;
+/// use gpx::parser::skip_element;
+/// use gpx::parser::extensions::EmptyExtensions;
+/// use xml::reader::XmlEvent;
+///
+/// let data = "<gpx version=\"1.1\">
+///     <route_plan><leg/><leg/></route_plan>
+///     <wpt lat=\"1.0\" lon=\"2.0\"/>
+/// </gpx>";
+///
+/// let mut legs = 0;
+/// let gpx = read_with_root_handler::<_, EmptyExtensions, _>(data.as_bytes(), |name, context| {
+///     if name != "route_plan" {
+///         return Ok(false);
+///     }
+///     context.reader().next();
+///     while let Some(Ok(XmlEvent::StartElement { .. })) = context.reader().peek() {
+///         context.reader().next();
+///         skip_element(context, "leg")?;
+///         legs += 1;
+///     }
+///     skip_element(context, "route_plan")?;
+///     Ok(true)
+/// })
+/// .unwrap();
+/// assert_eq!(legs, 2);
+/// assert_eq!(gpx.waypoints.len(), 1);
+/// ```
+pub fn read_with_root_handler<R, E, F>(reader: R, handler: F) -> GpxResult<Gpx<E>>
+where
+    R: Read,
+    E: WaypointExtensions + Default,
+    F: for<'a> FnMut(&str, &mut Context<'a, TrackingReader<R>, E>) -> GpxResult<bool>,
+{
+    read_with_root_handler_with_options(reader, Default::default(), handler)
+}
+
+/// Reads an activity in GPX format like [`read_with_root_handler`], using
+/// the given [`ParseOptions`]. As the handler reads the input as it comes,
+/// [`ParseOptions::repair_entities`] is not applied.
+///
+/// ```
+/// use gpx::{read_with_root_handler_with_options, ParseOptions};
+/// use gpx::parser::skip_element;
+/// use gpx::parser::extensions::EmptyExtensions;
+///
+/// let data = "<gpx version=\"1.1\"><route_plan/><unknown/></gpx>";
+///
+/// let mut plans = 0;
+/// let gpx = read_with_root_handler_with_options::<_, EmptyExtensions, _>(data.as_bytes(), ParseOptions::lenient(), |name, context| {
+///     if name != "route_plan" {
+///         return Ok(false);
+///     }
+///     context.reader().next();
+///     skip_element(context, "route_plan")?;
+///     plans += 1;
+///     Ok(true)
+/// })
+/// .unwrap();
+/// assert_eq!(plans, 1);
+/// ```
+pub fn read_with_root_handler_with_options<R, E, F>(reader: R, options: ParseOptions, mut handler: F) -> GpxResult<Gpx<E>>
+where
+    R: Read,
+    E: WaypointExtensions + Default,
+    F: for<'a> FnMut(&str, &mut Context<'a, TrackingReader<R>, E>) -> GpxResult<bool>,
+{
+    let mut context = create_tracked_context_with_options::<R, E>(reader, GpxVersion::Unknown, options);
+    gpx::consume_with_root_handler(&mut context, &mut handler)
+}
+
+/// A GPX document read by [`read_with_warnings`], with the issues the parser
+/// recovered from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReadOutcome<E: WaypointExtensions + Default> {
+    pub gpx: Gpx<E>,
+    /// The recovered issues, in the order they were met.
+    pub warnings: Vec<ReadWarning>,
+}
+
+/// Reads an activity in GPX format, using the given [`ParseOptions`], and
+/// gives it along with every issue recovered from in lenient mode: unknown
+/// elements skipped, coordinates out of range replaced, malformed times
+/// dropped, control characters stripped and so on, each with its position
+/// in the input.
+///
+/// ```
+/// use gpx::{read_with_warnings, ParseOptions};
+/// use gpx::errors::GpxWarning;
+/// use gpx::parser::extensions::EmptyExtensions;
+///
+/// let data = "<gpx version=\"1.1\">\n<wpt lat=\"91\" lon=\"2\"><time>noon</time></wpt></gpx>";
+///
+/// let outcome = read_with_warnings::<_, EmptyExtensions>(data.as_bytes(), ParseOptions::lenient()).unwrap();
+/// assert!(outcome.gpx.waypoints[0].time.is_none());
+/// assert!(matches!(outcome.warnings[0].warning, GpxWarning::OutOfRange { .. }));
+/// assert!(matches!(outcome.warnings[1].warning, GpxWarning::MalformedTime { .. }));
+/// assert_eq!(outcome.warnings[1].position.unwrap().line, 2);
+/// ```
+pub fn read_with_warnings<R: Read, E: WaypointExtensions + Default>(
+    reader: R,
+    options: ParseOptions,
+) -> GpxResult<ReadOutcome<E>> {
+    if options.repair_entities {
+        return read_repaired(reader, options);
+    }
+    let mut context = create_tracked_context_with_options::<R, E>(reader, GpxVersion::Unknown, options);
+    let gpx = gpx::consume(&mut context)?;
+    Ok(ReadOutcome {
+        gpx,
+        warnings: context.take_warnings(),
+    })
+}
+
+/// read_repaired reads `reader` whole, escapes its raw ampersands, and parses
+/// what comes of it, recording every repair as a warning.
+fn read_repaired<R: Read, E: WaypointExtensions + Default>(
+    mut reader: R,
+    options: ParseOptions,
+) -> GpxResult<ReadOutcome<E>> {
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input)?;
+    let (input, repairs) = repair_ampersands(&input);
+    let mut context = create_tracked_context_with_options::<&[u8], E>(&input, GpxVersion::Unknown, options);
+    for repair in repairs {
+        context.warn_at(GpxWarning::RawAmpersand { offset: repair.offset }, repair.position);
+    }
+    let gpx = gpx::consume(&mut context)?;
+    Ok(ReadOutcome {
+        gpx,
+        warnings: context.take_warnings(),
+    })
+}
@@ -0,0 +1,179 @@
+//! repair mends input that is not well-formed XML in ways common enough to be
+//! worth the trouble, before it is parsed.
+
+use std::borrow::Cow;
+
+use crate::declaration::{is_ascii_compatible, UTF8_BOM};
+use crate::errors::Position;
+
+/// The sections of a document whose ampersands are not references, with the
+/// markup opening and closing them.
+const RAW_SECTIONS: [(&[u8], &[u8]); 3] = [(b"<![CDATA[", b"]]>"), (b"<!--", b"-->"), (b"<?", b"?>")];
+
+/// A raw ampersand escaped by [`repair_ampersands`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Repair {
+    /// The offset of the ampersand in the input, in bytes.
+    pub offset: usize,
+    pub position: Position,
+}
+
+/// is_name_start tells whether `byte` can start the name of an entity. Bytes
+/// of multi-byte UTF-8 sequences are taken to, as many of those characters
+/// can.
+fn is_name_start(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || byte == b'_' || byte == b':' || !byte.is_ascii()
+}
+
+fn is_name_byte(byte: u8) -> bool {
+    is_name_start(byte) || byte.is_ascii_digit() || byte == b'-' || byte == b'.'
+}
+
+/// is_reference tells whether `rest`, which follows an ampersand, starts
+/// with what makes it a reference: a name, a decimal `#` number or a
+/// hexadecimal `#x` number, then a semicolon. Whether the entity is known is
+/// left to the parser.
+fn is_reference(rest: &[u8]) -> bool {
+    let (body, valid): (&[u8], fn(u8) -> bool) = match rest {
+        [b'#', b'x', body @ ..] => (body, |byte| byte.is_ascii_hexdigit()),
+        [b'#', body @ ..] => (body, |byte| byte.is_ascii_digit()),
+        [first, ..] if is_name_start(*first) => (rest, is_name_byte),
+        _ => return false,
+    };
+    let len = body.iter().take_while(|&&byte| valid(byte)).count();
+    len > 0 && body.get(len) == Some(&b';')
+}
+
+/// repair_ampersands escapes the ampersands of `input` that start no
+/// reference as `&amp;`, leaving those in CDATA sections, comments and
+/// processing instructions, where they are text. Gives the input as it is
+/// when there is nothing to repair, along with the repairs made.
+///
+/// Input in an encoding that is not ASCII compatible, such as UTF-16, is
+/// given as it is too, as its ampersands are not single bytes.
+///
+/// Positions count columns in bytes, as the reader does. The byte order mark
+/// the reader drops is not counted.
+pub(crate) fn repair_ampersands(input: &[u8]) -> (Cow<'_, [u8]>, Vec<Repair>) {
+    if !is_ascii_compatible(input) {
+        return (Cow::Borrowed(input), Vec::new());
+    }
+    let mut repaired = Vec::new();
+    let mut repairs = Vec::new();
+    // The bytes of input up to here are in repaired already.
+    let mut copied = 0;
+    let mut index = if input.starts_with(&UTF8_BOM) { UTF8_BOM.len() } else { 0 };
+    let (mut line, mut line_start) = (1, index);
+    while index < input.len() {
+        let rest = &input[index..];
+        if let Some((_, close)) = RAW_SECTIONS.iter().find(|(open, _)| rest.starts_with(open)) {
+            let end = find(rest, close).map_or(input.len(), |at| index + at + close.len());
+            for (at, &byte) in input[index..end].iter().enumerate() {
+                if byte == b'\n' {
+                    line += 1;
+                    line_start = index + at + 1;
+                }
+            }
+            index = end;
+            continue;
+        }
+        match rest[0] {
+            b'\n' => {
+                line += 1;
+                line_start = index + 1;
+            }
+            b'&' if !is_reference(&rest[1..]) => {
+                repaired.extend_from_slice(&input[copied..=index]);
+                repaired.extend_from_slice(b"amp;");
+                copied = index + 1;
+                repairs.push(Repair {
+                    offset: index,
+                    position: Position {
+                        line,
+                        column: (index - line_start + 1) as u64,
+                    },
+                });
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    if repairs.is_empty() {
+        return (Cow::Borrowed(input), repairs);
+    }
+    repaired.extend_from_slice(&input[copied..]);
+    (Cow::Owned(repaired), repairs)
+}
+
+/// find gives the index of the first `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::Position;
+
+    use super::{is_reference, repair_ampersands};
+
+    fn repaired(input: &str) -> String {
+        String::from_utf8(repair_ampersands(input.as_bytes()).0.into_owned()).unwrap()
+    }
+
+    #[test]
+    fn references_are_recognized() {
+        for reference in ["amp;", "lt;", "bogus;", "é;", "x-1.y;", "#38;", "#x26;", "#xaF;"] {
+            assert!(is_reference(reference.as_bytes()), "{}", reference);
+        }
+        for other in ["", ";", "amp", "c=2", "1st;", "#;", "#x;", "#12a;", "#xg;", " amp;"] {
+            assert!(!is_reference(other.as_bytes()), "{}", other);
+        }
+    }
+
+    #[test]
+    fn repair_raw_ampersands() {
+        assert_eq!(
+            repaired(r#"<link href="http://example.com/a?b=1&c=2&amp;d=3&">a & b</link>"#),
+            r#"<link href="http://example.com/a?b=1&amp;c=2&amp;d=3&amp;">a &amp; b</link>"#
+        );
+
+        let input = "<a>\n<!-- & -->&x\n<![CDATA[&]]><?pi &?>&</a>";
+        let (output, repairs) = repair_ampersands(input.as_bytes());
+        assert_eq!(
+            String::from_utf8(output.into_owned()).unwrap(),
+            "<a>\n<!-- & -->&amp;x\n<![CDATA[&]]><?pi &?>&amp;</a>"
+        );
+        let found: Vec<_> = repairs.iter().map(|repair| (repair.offset, repair.position)).collect();
+        assert_eq!(
+            found,
+            [
+                (14, Position { line: 2, column: 11 }),
+                (38, Position { line: 3, column: 22 }),
+            ]
+        );
+        assert_eq!(&input[38..39], "&");
+
+        // An unclosed section runs to the end.
+        assert_eq!(repaired("<a><![CDATA[&"), "<a><![CDATA[&");
+    }
+
+    #[test]
+    fn utf16_is_not_repaired() {
+        let mut input = vec![0xFF, 0xFE];
+        input.extend("<a>a &amp; b & c</a>".encode_utf16().flat_map(u16::to_le_bytes));
+        let (output, repairs) = repair_ampersands(&input);
+        assert!(matches!(output, std::borrow::Cow::Borrowed(_)));
+        assert!(repairs.is_empty());
+    }
+
+    #[test]
+    fn nothing_to_repair_is_borrowed() {
+        let input = "\u{feff}<a b=\"&amp;&#38;\">&lt;</a>";
+        let (output, repairs) = repair_ampersands(input.as_bytes());
+        assert!(matches!(output, std::borrow::Cow::Borrowed(_)));
+        assert!(repairs.is_empty());
+
+        let (_, repairs) = repair_ampersands("\u{feff}<a>&</a>".as_bytes());
+        assert_eq!(repairs[0].position, Position { line: 1, column: 4 });
+    }
+}
@@ -0,0 +1,287 @@
+//! resample gives track segments sampled at fixed distance or time intervals.
+
+use geo_types::Point;
+use time::{Duration, OffsetDateTime};
+
+use crate::distance::haversine_distance;
+use crate::errors::{GpxError, GpxResult};
+use crate::parser::extensions::WaypointExtensions;
+use crate::{TrackSegment, Waypoint};
+
+/// Distance in meters below which a sample counts as the end of the segment
+/// itself, so the last point is not preceded by a near duplicate.
+const END_TOLERANCE: f64 = 1e-6;
+
+/// How many points a resampled segment may have at most, some gigabytes of
+/// points, beyond which the interval is taken for a mistake.
+const MAX_SAMPLES: f64 = 10_000_000.0;
+
+/// interpolate gives a new point `fraction` of the way from `a` to `b`.
+///
+/// The elevation and time are interpolated when both points have them, while
+/// everything else, extensions included, is left at its default.
+pub(crate) fn interpolate<E: WaypointExtensions + Default>(
+    a: &Waypoint<E>,
+    b: &Waypoint<E>,
+    fraction: f64,
+) -> Waypoint<E> {
+    let (a_point, b_point) = (a.point(), b.point());
+    let mut point = Waypoint::new(Point::new(
+        a_point.x() + (b_point.x() - a_point.x()) * fraction,
+        a_point.y() + (b_point.y() - a_point.y()) * fraction,
+    ));
+    if let (Some(a), Some(b)) = (a.elevation, b.elevation) {
+        point.elevation = Some(a + (b - a) * fraction);
+    }
+    if let (Some(a), Some(b)) = (a.time, b.time) {
+        point.time = Some((OffsetDateTime::from(a) + (b - a) * fraction).into());
+    }
+    point
+}
+
+/// resample samples `points` every `interval` along their `positions`, which
+/// must not decrease, keeping the first and last points.
+///
+/// Fails with [`GpxError::InvalidInterval`] when `interval` is not positive,
+/// or would give more than [`MAX_SAMPLES`] points.
+fn resample<E: WaypointExtensions + Default + Clone>(
+    points: &[&Waypoint<E>],
+    positions: &[f64],
+    interval: f64,
+) -> GpxResult<Vec<Waypoint<E>>> {
+    // NaN and infinity are not positive intervals either.
+    if !interval.is_finite() || interval <= 0.0 {
+        return Err(GpxError::InvalidInterval(interval));
+    }
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Ok(Vec::new()),
+    };
+    let mut resampled = vec![first.clone()];
+    if points.len() == 1 {
+        return Ok(resampled);
+    }
+
+    let end = positions[positions.len() - 1];
+    if (end - positions[0]) / interval > MAX_SAMPLES {
+        return Err(GpxError::InvalidInterval(interval));
+    }
+    let mut index = 0;
+    let mut sample = 1.0;
+    while sample * interval < end - END_TOLERANCE {
+        let position = positions[0] + sample * interval;
+        while positions[index + 1] <= position {
+            index += 1;
+        }
+        let (from, to) = (positions[index], positions[index + 1]);
+        resampled.push(interpolate(
+            points[index],
+            points[index + 1],
+            (position - from) / (to - from),
+        ));
+        sample += 1.0;
+    }
+
+    resampled.push(last.clone());
+    Ok(resampled)
+}
+
+impl<E: WaypointExtensions + Default> TrackSegment<E> {
+    /// Resamples the segment to a point every `interval_m` meters along it,
+    /// interpolating between the original points.
+    ///
+    /// The first and last points are kept as they are, while the points in
+    /// between are new: their coordinates are interpolated, as are their
+    /// elevation and time when the points around them both have one, and
+    /// all else is left at its default.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`GpxError::InvalidInterval`] when `interval_m` is not a
+    /// positive number, or is so small that the segment would take more than
+    /// ten million points.
+    pub fn resample_by_distance(&self, interval_m: f64) -> GpxResult<TrackSegment<E>>
+    where
+        E: Clone,
+    {
+        let points: Vec<_> = self.points.iter().collect();
+        let mut positions = Vec::with_capacity(points.len());
+        let mut distance = 0.0;
+        for (i, point) in points.iter().enumerate() {
+            if i > 0 {
+                distance += haversine_distance(points[i - 1].point(), point.point());
+            }
+            positions.push(distance);
+        }
+        Ok(TrackSegment {
+            points: resample(&points, &positions, interval_m)?,
+        })
+    }
+
+    /// Resamples the segment to a point every `interval`, interpolating
+    /// between the original points like
+    /// [`TrackSegment::resample_by_distance`].
+    ///
+    /// Points without a time are left out, as are points whose time is not
+    /// after the time of the point before them.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`GpxError::InvalidInterval`] when `interval` is not
+    /// positive, or is so small that the segment would take more than ten
+    /// million points.
+    pub fn resample_by_time(&self, interval: Duration) -> GpxResult<TrackSegment<E>>
+    where
+        E: Clone,
+    {
+        let mut points = Vec::with_capacity(self.points.len());
+        let mut positions = Vec::with_capacity(self.points.len());
+        let mut start = None;
+        for point in &self.points {
+            let time = match point.time {
+                Some(time) => time,
+                None => continue,
+            };
+            let start = *start.get_or_insert(time);
+            let position = (time - start).as_seconds_f64();
+            if positions.last().map_or(true, |&last| position > last) {
+                points.push(point);
+                positions.push(position);
+            }
+        }
+        Ok(TrackSegment {
+            points: resample(&points, &positions, interval.as_seconds_f64())?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+    use geo_types::Point;
+    use time::Duration;
+
+    use crate::distance::haversine_distance;
+    use crate::errors::GpxError;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::testing::{self, time};
+    use crate::{TrackSegment, Waypoint};
+
+    fn waypoint(x: f64, elevation: Option<f64>, seconds: Option<i64>) -> Waypoint<EmptyExtensions> {
+        let mut point = testing::waypoint(x, seconds);
+        point.elevation = elevation;
+        point
+    }
+
+    fn two_points() -> TrackSegment<EmptyExtensions> {
+        let mut first = waypoint(0.0, Some(0.0), Some(0));
+        first.name = Some(String::from("start"));
+        TrackSegment {
+            points: vec![first, waypoint(0.003, Some(30.0), Some(30))],
+        }
+    }
+
+    #[test]
+    fn resample_by_time() {
+        let segment = two_points();
+        let resampled = segment.resample_by_time(Duration::seconds(10)).unwrap();
+
+        assert_eq!(resampled.points.len(), 4);
+        assert_eq!(resampled.points[0], segment.points[0]);
+        assert_eq!(resampled.points[3], segment.points[1]);
+        for (i, point) in resampled.points.iter().enumerate() {
+            assert_approx_eq!(point.point().x(), 0.001 * i as f64);
+            assert_eq!(point.point().y(), 0.0);
+            assert_approx_eq!(point.elevation.unwrap(), 10.0 * i as f64);
+            assert_eq!(point.time, Some(time(10 * i as i64)));
+        }
+        assert_eq!(resampled.points[1].name, None);
+    }
+
+    #[test]
+    fn resample_by_distance() {
+        let segment = two_points();
+        let length = haversine_distance(segment.points[0].point(), segment.points[1].point());
+        let resampled = segment.resample_by_distance(length / 4.0).unwrap();
+
+        assert_eq!(resampled.points.len(), 5);
+        for (i, point) in resampled.points.iter().enumerate() {
+            assert_approx_eq!(point.point().x(), 0.00075 * i as f64);
+            assert_approx_eq!(point.elevation.unwrap(), 7.5 * i as f64);
+        }
+        assert_eq!(resampled.points[2].time, Some(time(15)));
+
+        // Not a whole number of intervals: the last sample is closer.
+        let resampled = segment.resample_by_distance(length * 0.4).unwrap();
+        let xs: Vec<_> = resampled.points.iter().map(|p| p.point().x()).collect();
+        assert_eq!(xs.len(), 4);
+        assert_approx_eq!(xs[1], 0.0012);
+        assert_approx_eq!(xs[2], 0.0024);
+        assert_eq!(xs[3], 0.003);
+    }
+
+    #[test]
+    fn resample_shorter_than_interval() {
+        let segment = two_points();
+        assert_eq!(segment.resample_by_distance(1000.0).unwrap(), segment);
+        assert_eq!(segment.resample_by_time(Duration::minutes(1)).unwrap(), segment);
+
+        let single = TrackSegment {
+            points: vec![waypoint(1.0, None, Some(0))],
+        };
+        assert_eq!(single.resample_by_distance(10.0).unwrap(), single);
+        let empty: TrackSegment<EmptyExtensions> = TrackSegment::new();
+        assert_eq!(empty.resample_by_time(Duration::seconds(1)).unwrap(), empty);
+    }
+
+    #[test]
+    fn resample_duplicates_and_missing_values() {
+        let segment = TrackSegment {
+            points: vec![
+                waypoint(0.0, Some(0.0), Some(0)),
+                waypoint(0.0, Some(0.0), Some(0)),
+                waypoint(0.002, None, None),
+                waypoint(0.002, None, Some(20)),
+                waypoint(0.004, Some(40.0), Some(40)),
+            ],
+        };
+
+        let by_distance = segment
+            .resample_by_distance(haversine_distance(Point::new(0.0, 0.0), Point::new(0.001, 0.0)))
+            .unwrap();
+        let xs: Vec<_> = by_distance.points.iter().map(|p| p.point().x()).collect();
+        assert_eq!(xs.len(), 5);
+        assert_approx_eq!(xs[1], 0.001);
+        assert_approx_eq!(xs[3], 0.003);
+        // Only one of the points around the first sample has an elevation.
+        assert_eq!(by_distance.points[1].elevation, None);
+        assert_eq!(by_distance.points[1].time, None);
+
+        // The duplicate and the point without a time are left out.
+        let by_time = segment.resample_by_time(Duration::seconds(10)).unwrap();
+        let xs: Vec<_> = by_time.points.iter().map(|p| p.point().x()).collect();
+        assert_eq!(xs.len(), 5);
+        assert_approx_eq!(xs[1], 0.001);
+        assert_approx_eq!(xs[3], 0.003);
+        assert_eq!(by_time.points[3].elevation, None);
+        assert_eq!(by_time.points[3].time, Some(time(30)));
+    }
+
+    #[test]
+    fn resample_invalid_interval() {
+        let segment = two_points();
+        for interval in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e-9] {
+            assert!(matches!(
+                segment.resample_by_distance(interval),
+                Err(GpxError::InvalidInterval(got)) if got.to_bits() == interval.to_bits()
+            ));
+        }
+        for interval in [Duration::ZERO, Duration::seconds(-10), Duration::nanoseconds(1)] {
+            assert!(matches!(segment.resample_by_time(interval), Err(GpxError::InvalidInterval(_))));
+        }
+
+        // Even for segments with nothing to sample.
+        let empty: TrackSegment<EmptyExtensions> = TrackSegment::new();
+        assert!(matches!(empty.resample_by_distance(0.0), Err(GpxError::InvalidInterval(_))));
+    }
+}
@@ -0,0 +1,221 @@
+//! reverse gives tracks and routes travelled the other way round.
+
+use time::OffsetDateTime;
+
+use crate::errors::{GpxError, GpxResult};
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Route, Track, TrackSegment, Waypoint};
+
+/// What [`Track::reversed`] and [`Route::reversed`] do with the times of the
+/// points, which run backwards once the points are reversed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReverseTimes {
+    /// Removes the times.
+    Strip,
+    /// Keeps the times as they are, running backwards.
+    Keep,
+    /// Gives the first point of the reversed points the given time, and the
+    /// following ones the same durations between them as the original points
+    /// had, so the reversal is travelled like the original, starting then.
+    /// Points without a time are left without one.
+    MirrorFrom(OffsetDateTime),
+}
+
+/// latest gives the latest time of `points`.
+fn latest<'a, E, I>(points: I) -> Option<OffsetDateTime>
+where
+    E: WaypointExtensions + Default + 'a,
+    I: Iterator<Item = &'a Waypoint<E>>,
+{
+    points.filter_map(|point| point.time).map(OffsetDateTime::from).max()
+}
+
+/// reverse reverses `points`, handling their times according to `times`,
+/// with `latest` the latest time of all the points reversed together, and
+/// `first` the index of the first reversed point among them, for errors.
+fn reverse<E>(
+    points: &[Waypoint<E>],
+    times: ReverseTimes,
+    latest: Option<OffsetDateTime>,
+    first: usize,
+) -> GpxResult<Vec<Waypoint<E>>>
+where
+    E: WaypointExtensions + Default + Clone,
+{
+    points
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, point)| {
+            let mut point = point.clone();
+            point.time = match (times, point.time, latest) {
+                (ReverseTimes::Keep, time, _) => time,
+                (ReverseTimes::MirrorFrom(start), Some(time), Some(latest)) => {
+                    let time = start.checked_add(latest - OffsetDateTime::from(time));
+                    Some(time.ok_or(GpxError::TimeOutOfRange(first + index))?.into())
+                }
+                _ => None,
+            };
+            Ok(point)
+        })
+        .collect()
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Gives this track travelled the other way round: the segments in
+    /// reverse order, each with its points in reverse order, and their times
+    /// handled according to `times`. Everything else is kept as it is, name
+    /// included.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`GpxError::TimeOutOfRange`], giving the index of the point
+    /// among the reversed points of all segments, when a mirrored time would
+    /// be beyond the range of [`OffsetDateTime`].
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{ReverseTimes, Track, TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    /// use time::{Duration, OffsetDateTime};
+    ///
+    /// let start = OffsetDateTime::from_unix_timestamp(1_600_000_000).unwrap();
+    /// let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+    /// for (x, seconds) in [(0.0, 0), (0.001, 60), (0.002, 90)] {
+    ///     let mut point = Waypoint::new(Point::new(x, 0.0));
+    ///     point.time = Some((start + Duration::seconds(seconds)).into());
+    ///     segment.points.push(point);
+    /// }
+    /// let mut track = Track::new();
+    /// track.segments.push(segment);
+    ///
+    /// let back = start + Duration::hours(2);
+    /// let reversed = track.reversed(ReverseTimes::MirrorFrom(back)).unwrap();
+    /// let points = &reversed.segments[0].points;
+    /// assert_eq!(points[0].point().x(), 0.002);
+    /// assert_eq!(points[1].time, Some((back + Duration::seconds(30)).into()));
+    /// assert_eq!(points[2].time, Some((back + Duration::seconds(90)).into()));
+    /// ```
+    pub fn reversed(&self, times: ReverseTimes) -> GpxResult<Track<E>>
+    where
+        E: Clone,
+    {
+        let latest = latest(self.segments.iter().flat_map(|segment| &segment.points));
+        let mut first = 0;
+        let mut segments = Vec::with_capacity(self.segments.len());
+        for segment in self.segments.iter().rev() {
+            segments.push(TrackSegment {
+                points: reverse(&segment.points, times, latest, first)?,
+            });
+            first += segment.points.len();
+        }
+        Ok(Track {
+            segments,
+            ..self.without_segments()
+        })
+    }
+}
+
+impl<E: WaypointExtensions + Default> Route<E> {
+    /// Gives this route travelled the other way round, with its points in
+    /// reverse order and their times handled according to `times`, like
+    /// [`Track::reversed`], failing the same way.
+    pub fn reversed(&self, times: ReverseTimes) -> GpxResult<Route<E>>
+    where
+        E: Clone,
+    {
+        Ok(Route {
+            points: reverse(&self.points, times, latest(self.points.iter()), 0)?,
+            ..self.without_points()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{Date, Duration, Month, OffsetDateTime, Time};
+
+    use crate::errors::GpxError;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::testing::{start, timed_segment, xs};
+    use crate::{Route, Track};
+
+    use super::ReverseTimes;
+
+    fn track() -> Track<EmptyExtensions> {
+        let mut track = Track::new();
+        track.name = Some(String::from("Out"));
+        track.segments.push(timed_segment(&[(0.0, Some(0)), (1.0, Some(10)), (2.0, Some(40))]));
+        track.segments.push(timed_segment(&[(3.0, Some(100)), (4.0, None), (5.0, Some(160))]));
+        track
+    }
+
+    #[test]
+    fn reversed_track_mirrors_times() {
+        let track = track();
+        let back = start() + Duration::days(1);
+        let reversed = track.reversed(ReverseTimes::MirrorFrom(back)).unwrap();
+
+        assert_eq!(reversed.name.as_deref(), Some("Out"));
+        assert_eq!(xs(&reversed.segments[0]), [5.0, 4.0, 3.0]);
+        assert_eq!(xs(&reversed.segments[1]), [2.0, 1.0, 0.0]);
+
+        let times: Vec<OffsetDateTime> = reversed
+            .segments
+            .iter()
+            .flat_map(|segment| &segment.points)
+            .filter_map(|point| point.time)
+            .map(OffsetDateTime::from)
+            .collect();
+        assert_eq!(times.len(), 5);
+        assert_eq!(times[0], back);
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(times[4] - times[0], Duration::seconds(160));
+        assert_eq!(reversed.segments[0].points[1].time, None);
+    }
+
+    #[test]
+    fn reversed_track_strips_or_keeps_times() {
+        let track = track();
+        let stripped = track.reversed(ReverseTimes::Strip).unwrap();
+        assert!(stripped.segments.iter().flat_map(|segment| &segment.points).all(|point| point.time.is_none()));
+
+        let kept = track.reversed(ReverseTimes::Keep).unwrap();
+        assert_eq!(kept.segments[1].points[0].time, track.segments[0].points[2].time);
+    }
+
+    #[test]
+    fn reversed_route() {
+        let mut route: Route<EmptyExtensions> = Route::new();
+        route.points = timed_segment(&[(0.0, Some(0)), (1.0, Some(30)), (2.0, Some(45))]).points;
+
+        let reversed = route.reversed(ReverseTimes::MirrorFrom(start())).unwrap();
+        let seconds: Vec<_> = reversed
+            .points
+            .iter()
+            .map(|point| (point.time.unwrap() - start().into()).whole_seconds())
+            .collect();
+        assert_eq!(seconds, [0, 15, 45]);
+        assert_eq!(reversed.points[0].point().x(), 2.0);
+    }
+
+    #[test]
+    fn reversed_out_of_range() {
+        let track = track();
+        let date = Date::from_calendar_date(9999, Month::December, 31).unwrap();
+        let end = date.with_time(Time::from_hms(23, 59, 30).unwrap()).assume_utc();
+
+        // The first reversed point is at the start, the second has no time,
+        // and the third is a minute after, past the year 9999.
+        let err = track.reversed(ReverseTimes::MirrorFrom(end)).unwrap_err();
+        assert!(matches!(err, GpxError::TimeOutOfRange(2)));
+
+        let mut route: Route<EmptyExtensions> = Route::new();
+        route.points = track.segments[0].points.clone();
+        assert!(matches!(
+            route.reversed(ReverseTimes::MirrorFrom(end)),
+            Err(GpxError::TimeOutOfRange(1))
+        ));
+        assert!(route.reversed(ReverseTimes::Keep).is_ok());
+    }
+}
@@ -0,0 +1,386 @@
+//! sanity finds signs of bugs in the software that produced a document.
+
+use time::{Duration, OffsetDateTime};
+
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Gpx, Track};
+
+/// How far from a whole number of hours a skew may be to still look like a
+/// timezone mistake.
+const TIMEZONE_TOLERANCE: Duration = Duration::minutes(5);
+
+/// The largest UTC offset in use, beyond which a skew is not a timezone
+/// mistake alone.
+const MAX_UTC_OFFSET: Duration = Duration::hours(14);
+
+/// How far back receivers hit by the GPS week number rollover jump, the
+/// 1024 weeks the week number counts before wrapping around.
+const GPS_WEEK_ROLLOVER: Duration = Duration::weeks(1024);
+
+/// How far from [`GPS_WEEK_ROLLOVER`] a jump may be to still look like one.
+const ROLLOVER_TOLERANCE: Duration = Duration::days(1);
+
+/// Options for [`Gpx::sanity_report_with`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SanityOptions {
+    /// The largest [`Gpx::metadata_time_skew`] that is not reported. A skew
+    /// that looks like a timezone mistake, a whole number of hours no more
+    /// than any UTC offset, is reported whatever this is.
+    pub max_metadata_skew: Duration,
+}
+
+impl Default for SanityOptions {
+    fn default() -> SanityOptions {
+        SanityOptions {
+            max_metadata_skew: Duration::hours(48),
+        }
+    }
+}
+
+/// A sign of a bug in the software that produced a document.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum SanityFinding {
+    /// The metadata time is `skew` away from the earliest track point time.
+    /// When the skew is close to a whole number of hours, no more than any
+    /// UTC offset, the times were `likely_timezone` mistaken.
+    MetadataTimeSkew { skew: Duration, likely_timezone: bool },
+    /// The times of `points` points of the track at index `track` jump 1024
+    /// weeks back, as receivers hit by the GPS week number rollover wrote
+    /// them. [`Track::fix_gps_week_rollover`] puts them back.
+    GpsWeekRollover { track: usize, points: usize },
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Gives the time of the metadata minus the earliest time of the track
+    /// points, or `None` without either.
+    pub fn metadata_time_skew(&self) -> Option<Duration> {
+        let metadata_time = self.metadata.as_ref()?.time?;
+        let earliest = self
+            .tracks
+            .iter()
+            .flat_map(|track| &track.segments)
+            .flat_map(|segment| &segment.points)
+            .filter_map(|point| point.time)
+            .min()?;
+        Some(metadata_time - earliest)
+    }
+
+    /// Looks for signs of bugs in the software that produced this document,
+    /// with the default [`SanityOptions`].
+    pub fn sanity_report(&self) -> Vec<SanityFinding> {
+        self.sanity_report_with(&Default::default())
+    }
+
+    /// Looks for signs of bugs in the software that produced this document.
+    ///
+    /// ```
+    /// use gpx::{Gpx, Metadata, SanityFinding, Track, TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    /// use geo_types::Point;
+    /// use time::{Duration, OffsetDateTime};
+    ///
+    /// let start = OffsetDateTime::from_unix_timestamp(1_677_661_200).unwrap();
+    /// let mut point = Waypoint::new(Point::new(0.0, 0.0));
+    /// point.time = Some(start.into());
+    /// let mut gpx: Gpx<EmptyExtensions> = Gpx::default();
+    /// gpx.metadata = Some(Metadata {
+    ///     time: Some((start + Duration::days(5)).into()),
+    ///     ..Default::default()
+    /// });
+    /// gpx.tracks.push(Track {
+    ///     segments: vec![TrackSegment { points: vec![point] }],
+    ///     ..Default::default()
+    /// });
+    ///
+    /// assert_eq!(
+    ///     gpx.sanity_report(),
+    ///     [SanityFinding::MetadataTimeSkew { skew: Duration::days(5), likely_timezone: false }]
+    /// );
+    /// ```
+    pub fn sanity_report_with(&self, options: &SanityOptions) -> Vec<SanityFinding> {
+        let mut findings = Vec::new();
+        if let Some(skew) = self.metadata_time_skew() {
+            let likely_timezone = looks_like_timezone(skew);
+            if likely_timezone || skew.abs() > options.max_metadata_skew {
+                findings.push(SanityFinding::MetadataTimeSkew { skew, likely_timezone });
+            }
+        }
+        for (index, track) in self.tracks.iter().enumerate() {
+            let points = rolled_over(track).iter().filter(|&&rolled_over| rolled_over).count();
+            if points > 0 {
+                findings.push(SanityFinding::GpsWeekRollover { track: index, points });
+            }
+        }
+        findings
+    }
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Adds 1024 weeks to the times of points that jumped that far back
+    /// mid-track, as receivers hit by the GPS week number rollover wrote
+    /// them, giving how many points were changed.
+    ///
+    /// Only a jump back of 1024 weeks, give or take a day, starts the points
+    /// that are changed, and a jump forward as large ends them. Other jumps
+    /// back are left alone, as are points before the first jump, which have
+    /// nothing earlier to tell them wrong by, and points whose time would
+    /// be past what a date can hold.
+    ///
+    /// ```
+    /// use gpx::{Track, TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    /// use geo_types::Point;
+    /// use time::{Duration, OffsetDateTime};
+    ///
+    /// let start = OffsetDateTime::from_unix_timestamp(1_556_000_000).unwrap();
+    /// let offsets = [Duration::ZERO, Duration::minutes(1), Duration::minutes(2) - Duration::weeks(1024)];
+    /// let mut track: Track<EmptyExtensions> = Track::new();
+    /// track.segments.push(TrackSegment {
+    ///     points: offsets
+    ///         .iter()
+    ///         .map(|&offset| {
+    ///             let mut point = Waypoint::new(Point::new(0.0, 0.0));
+    ///             point.time = Some((start + offset).into());
+    ///             point
+    ///         })
+    ///         .collect(),
+    /// });
+    ///
+    /// assert_eq!(track.fix_gps_week_rollover(), 1);
+    /// assert_eq!(track.segments[0].points[2].time, Some((start + Duration::minutes(2)).into()));
+    /// ```
+    pub fn fix_gps_week_rollover(&mut self) -> usize {
+        let rolled_over = rolled_over(self);
+        let mut fixed = 0;
+        let points = self.segments.iter_mut().flat_map(|segment| &mut segment.points);
+        for (point, rolled_over) in points.zip(rolled_over) {
+            let repaired = point
+                .time
+                .filter(|_| rolled_over)
+                .and_then(|time| OffsetDateTime::from(time).checked_add(GPS_WEEK_ROLLOVER));
+            if let Some(time) = repaired {
+                point.time = Some(time.into());
+                fixed += 1;
+            }
+        }
+        fixed
+    }
+}
+
+/// rolled_over gives, for every point of `track` in order, whether its time
+/// is 1024 weeks behind, from a jump back that large to a jump forward as
+/// large. Points without a time are never behind, nor are those whose time
+/// cannot be moved 1024 weeks on.
+fn rolled_over<E: WaypointExtensions + Default>(track: &Track<E>) -> Vec<bool> {
+    let near = |jump: Duration, expected: Duration| (jump - expected).abs() <= ROLLOVER_TOLERANCE;
+    let mut behind = false;
+    let mut previous: Option<OffsetDateTime> = None;
+    let points = track.segments.iter().flat_map(|segment| &segment.points);
+    points
+        .map(|point| {
+            let time = match point.time {
+                Some(time) => OffsetDateTime::from(time),
+                None => return false,
+            };
+            if let Some(previous) = previous {
+                let jump = time - previous;
+                if !behind && near(jump, -GPS_WEEK_ROLLOVER) {
+                    behind = true;
+                } else if behind && near(jump, GPS_WEEK_ROLLOVER) {
+                    behind = false;
+                }
+            }
+            previous = Some(time);
+            behind && time.checked_add(GPS_WEEK_ROLLOVER).is_some()
+        })
+        .collect()
+}
+
+/// Whether `skew` is close to a whole, non-zero number of hours, no more
+/// than any UTC offset.
+fn looks_like_timezone(skew: Duration) -> bool {
+    let skew = skew.abs();
+    let hours = (skew.as_seconds_f64() / 3600.0).round();
+    let off = (skew - Duration::hours(hours as i64)).abs();
+    hours >= 1.0 && skew <= MAX_UTC_OFFSET + TIMEZONE_TOLERANCE && off <= TIMEZONE_TOLERANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+    use time::{Duration, OffsetDateTime};
+
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{Gpx, Metadata, Track, TrackSegment, Waypoint};
+
+    use super::{SanityFinding, SanityOptions, GPS_WEEK_ROLLOVER};
+
+    /// A document with its metadata time at `metadata` seconds and track
+    /// points at `points` seconds.
+    fn document(metadata: i64, points: &[i64]) -> Gpx<EmptyExtensions> {
+        let time = |seconds| Some((OffsetDateTime::UNIX_EPOCH + Duration::seconds(1_600_000_000 + seconds)).into());
+        let points = points
+            .iter()
+            .map(|&seconds| {
+                let mut point = Waypoint::new(Point::new(0.0, 0.0));
+                point.time = time(seconds);
+                point
+            })
+            .collect();
+        Gpx {
+            metadata: Some(Metadata {
+                time: time(metadata),
+                ..Default::default()
+            }),
+            tracks: vec![Track {
+                segments: vec![TrackSegment { points }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn metadata_time_skew_aligned() {
+        let gpx = document(-30, &[600, 0, 1200]);
+        assert_eq!(gpx.metadata_time_skew(), Some(Duration::seconds(-30)));
+        assert_eq!(gpx.sanity_report(), []);
+
+        assert_eq!(Gpx::<EmptyExtensions>::default().metadata_time_skew(), None);
+    }
+
+    #[test]
+    fn metadata_time_skew_whole_hours() {
+        // Local time written as UTC, seven hours west of Greenwich, reported
+        // whatever the largest skew not reported.
+        let gpx = document(-7 * 3600 + 40, &[0, 60]);
+        let expected = [SanityFinding::MetadataTimeSkew {
+            skew: Duration::seconds(-7 * 3600 + 40),
+            likely_timezone: true,
+        }];
+        assert_eq!(gpx.sanity_report(), expected);
+        let options = SanityOptions {
+            max_metadata_skew: Duration::hours(1),
+        };
+        assert_eq!(gpx.sanity_report_with(&options), expected);
+
+        // As far east as UTC offsets go.
+        let gpx = document(14 * 3600, &[0]);
+        assert!(matches!(
+            gpx.sanity_report()[..],
+            [SanityFinding::MetadataTimeSkew { likely_timezone: true, .. }]
+        ));
+
+        // Only whole hours are, others below the largest skew are not.
+        let gpx = document(5 * 3600 + 1800, &[0]);
+        assert_eq!(gpx.sanity_report(), []);
+    }
+
+    #[test]
+    fn metadata_time_skew_wildly_different() {
+        let skew = 9 * 86400 + 3 * 3600 + 1234;
+        let gpx = document(skew, &[0]);
+        assert_eq!(
+            gpx.sanity_report(),
+            [SanityFinding::MetadataTimeSkew {
+                skew: Duration::seconds(skew),
+                likely_timezone: false
+            }]
+        );
+
+        // Whole hours, yet too many for any timezone.
+        let gpx = document(-72 * 3600, &[0]);
+        assert_eq!(
+            gpx.sanity_report(),
+            [SanityFinding::MetadataTimeSkew {
+                skew: Duration::hours(-72),
+                likely_timezone: false
+            }]
+        );
+    }
+
+    #[test]
+    fn gps_week_rollover() {
+        let rollover = GPS_WEEK_ROLLOVER.whole_seconds();
+        assert_eq!(rollover, 619_315_200);
+        // Back a few seconds short of 1024 weeks for two points, then right
+        // again, and back once more until the end of the track.
+        let mut gpx = document(
+            0,
+            &[0, 1, 2 - rollover + 5, 3 - rollover + 5, 4, 5 - rollover, 6 - rollover],
+        );
+        gpx.tracks[0].segments[0].points.insert(3, Waypoint::new(Point::new(0.0, 0.0)));
+        // The earliest point is one of those behind, so far from the metadata.
+        let expected = [
+            SanityFinding::MetadataTimeSkew {
+                skew: Duration::seconds(rollover - 5),
+                likely_timezone: false,
+            },
+            SanityFinding::GpsWeekRollover { track: 0, points: 4 },
+        ];
+        assert_eq!(gpx.sanity_report(), expected);
+
+        assert_eq!(gpx.tracks[0].fix_gps_week_rollover(), 4);
+        let times: Vec<_> = gpx.tracks[0].segments[0]
+            .points
+            .iter()
+            .map(|point| point.time.map(|time| (OffsetDateTime::from(time) - OffsetDateTime::UNIX_EPOCH).whole_seconds() - 1_600_000_000))
+            .collect();
+        assert_eq!(times, [Some(0), Some(1), Some(7), None, Some(8), Some(4), Some(5), Some(6)]);
+        assert_eq!(gpx.sanity_report(), []);
+        assert_eq!(gpx.tracks[0].fix_gps_week_rollover(), 0);
+    }
+
+    #[test]
+    fn gps_week_rollover_across_segments() {
+        let rollover = GPS_WEEK_ROLLOVER.whole_seconds();
+        let mut gpx = document(0, &[0, 60]);
+        let later = document(0, &[120 - rollover, 180 - rollover]);
+        gpx.tracks[0].segments.extend(later.tracks[0].segments.clone());
+        assert_eq!(gpx.tracks[0].fix_gps_week_rollover(), 2);
+        assert_eq!(gpx.tracks[0].segments[1].duration(), Some(Duration::minutes(1)));
+        assert_eq!(gpx.metadata_time_skew(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn gps_week_rollover_other_jumps() {
+        // A day and a minute too far back, a year back and an hour back.
+        let rollover = GPS_WEEK_ROLLOVER.whole_seconds();
+        for jump in [rollover + 86_460, rollover - 86_460, 365 * 86_400, 3600] {
+            let mut gpx = document(0, &[0, 1, 2 - jump, 3 - jump]);
+            gpx.metadata = None;
+            assert_eq!(gpx.sanity_report(), [], "{}", jump);
+            assert_eq!(gpx.tracks[0].fix_gps_week_rollover(), 0, "{}", jump);
+        }
+
+        // Starting behind, with nothing earlier to tell.
+        let mut gpx = document(0, &[-rollover, 1 - rollover, 2]);
+        assert_eq!(gpx.tracks[0].fix_gps_week_rollover(), 0);
+    }
+
+    #[test]
+    fn gps_week_rollover_past_last_date() {
+        // Back a little less than 1024 weeks from noon of the last day a date
+        // holds, so that only the first point behind can be moved on again.
+        let last = OffsetDateTime::UNIX_EPOCH.replace_year(9999).unwrap().replace_ordinal(365).unwrap().replace_hour(12).unwrap();
+        let behind = last - GPS_WEEK_ROLLOVER;
+        let times = [last, behind - Duration::hours(1), behind + Duration::hours(23)];
+        let mut track: Track<EmptyExtensions> = Track::new();
+        track.segments.push(TrackSegment {
+            points: times
+                .iter()
+                .map(|&time| {
+                    let mut point = Waypoint::new(Point::new(0.0, 0.0));
+                    point.time = Some(time.into());
+                    point
+                })
+                .collect(),
+        });
+
+        assert_eq!(track.fix_gps_week_rollover(), 1);
+        let points = &track.segments[0].points;
+        assert_eq!(points[1].time, Some((last - Duration::hours(1)).into()));
+        assert_eq!(points[2].time, Some(times[2].into()));
+    }
+}
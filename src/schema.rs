@@ -0,0 +1,926 @@
+//! Checks GPX documents against rules derived from the GPX 1.1 XSD.
+
+use std::fmt;
+use std::io::Read;
+
+use xml::attribute::OwnedAttribute;
+use xml::name::OwnedName;
+use xml::reader::{EventReader, ParserConfig, XmlEvent};
+
+use crate::errors::GpxResult;
+
+/// The namespace of GPX 1.1 elements.
+pub(crate) const GPX11_NAMESPACE: &str = "http://www.topografix.com/GPX/1/1";
+
+/// The namespace of XML schema instance attributes, such as
+/// `xsi:schemaLocation`, which any element may carry.
+pub(crate) const XSI_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema-instance";
+
+/// A simple type of the schema, giving the lexical form of a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Simple {
+    String,
+    Uri,
+    Decimal,
+    Latitude,
+    Longitude,
+    Degrees,
+    DateTime,
+    GYear,
+    NonNegativeInteger,
+    DgpsStation,
+    Fix,
+    Version,
+}
+
+/// What an element holds.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Content {
+    Simple(Simple),
+    Complex(&'static Complex),
+    /// Anything, as `<extensions>` allow.
+    Any,
+}
+
+/// A complex type of the schema: its attributes and the sequence of its
+/// children.
+#[derive(Debug)]
+pub(crate) struct Complex {
+    pub(crate) attributes: &'static [Attribute],
+    pub(crate) children: &'static [Child],
+}
+
+#[derive(Debug)]
+pub(crate) struct Attribute {
+    pub(crate) name: &'static str,
+    pub(crate) required: bool,
+    pub(crate) value: Simple,
+}
+
+/// A child in the sequence of a complex type, occurring from `min` to `max`
+/// times, without bound when `max` is `None`.
+#[derive(Debug)]
+pub(crate) struct Child {
+    pub(crate) name: &'static str,
+    pub(crate) min: u32,
+    pub(crate) max: Option<u32>,
+    pub(crate) content: Content,
+}
+
+const fn optional(name: &'static str, content: Content) -> Child {
+    Child {
+        name,
+        min: 0,
+        max: Some(1),
+        content,
+    }
+}
+
+const fn many(name: &'static str, content: Content) -> Child {
+    Child {
+        name,
+        min: 0,
+        max: None,
+        content,
+    }
+}
+
+const fn attribute(name: &'static str, required: bool, value: Simple) -> Attribute {
+    Attribute {
+        name,
+        required,
+        value,
+    }
+}
+
+const STRING: Content = Content::Simple(Simple::String);
+const URI: Content = Content::Simple(Simple::Uri);
+const DECIMAL: Content = Content::Simple(Simple::Decimal);
+const DATE_TIME: Content = Content::Simple(Simple::DateTime);
+const NON_NEGATIVE_INTEGER: Content = Content::Simple(Simple::NonNegativeInteger);
+
+/// The root `gpxType`.
+pub(crate) static GPX: Complex = Complex {
+    attributes: &[
+        attribute("version", true, Simple::Version),
+        attribute("creator", true, Simple::String),
+    ],
+    children: &[
+        optional("metadata", Content::Complex(&METADATA)),
+        many("wpt", Content::Complex(&WAYPOINT)),
+        many("rte", Content::Complex(&ROUTE)),
+        many("trk", Content::Complex(&TRACK)),
+        optional("extensions", Content::Any),
+    ],
+};
+
+static METADATA: Complex = Complex {
+    attributes: &[],
+    children: &[
+        optional("name", STRING),
+        optional("desc", STRING),
+        optional("author", Content::Complex(&PERSON)),
+        optional("copyright", Content::Complex(&COPYRIGHT)),
+        many("link", Content::Complex(&LINK)),
+        optional("time", DATE_TIME),
+        optional("keywords", STRING),
+        optional("bounds", Content::Complex(&BOUNDS)),
+        optional("extensions", Content::Any),
+    ],
+};
+
+static WAYPOINT: Complex = Complex {
+    attributes: &[
+        attribute("lat", true, Simple::Latitude),
+        attribute("lon", true, Simple::Longitude),
+    ],
+    children: &[
+        optional("ele", DECIMAL),
+        optional("time", DATE_TIME),
+        optional("magvar", Content::Simple(Simple::Degrees)),
+        optional("geoidheight", DECIMAL),
+        optional("name", STRING),
+        optional("cmt", STRING),
+        optional("desc", STRING),
+        optional("src", STRING),
+        many("link", Content::Complex(&LINK)),
+        optional("sym", STRING),
+        optional("type", STRING),
+        optional("fix", Content::Simple(Simple::Fix)),
+        optional("sat", NON_NEGATIVE_INTEGER),
+        optional("hdop", DECIMAL),
+        optional("vdop", DECIMAL),
+        optional("pdop", DECIMAL),
+        optional("ageofdgpsdata", DECIMAL),
+        optional("dgpsid", Content::Simple(Simple::DgpsStation)),
+        optional("extensions", Content::Any),
+    ],
+};
+
+static ROUTE: Complex = Complex {
+    attributes: &[],
+    children: &[
+        optional("name", STRING),
+        optional("cmt", STRING),
+        optional("desc", STRING),
+        optional("src", STRING),
+        many("link", Content::Complex(&LINK)),
+        optional("number", NON_NEGATIVE_INTEGER),
+        optional("type", STRING),
+        optional("extensions", Content::Any),
+        many("rtept", Content::Complex(&WAYPOINT)),
+    ],
+};
+
+static TRACK: Complex = Complex {
+    attributes: &[],
+    children: &[
+        optional("name", STRING),
+        optional("cmt", STRING),
+        optional("desc", STRING),
+        optional("src", STRING),
+        many("link", Content::Complex(&LINK)),
+        optional("number", NON_NEGATIVE_INTEGER),
+        optional("type", STRING),
+        optional("extensions", Content::Any),
+        many("trkseg", Content::Complex(&TRACK_SEGMENT)),
+    ],
+};
+
+static TRACK_SEGMENT: Complex = Complex {
+    attributes: &[],
+    children: &[
+        many("trkpt", Content::Complex(&WAYPOINT)),
+        optional("extensions", Content::Any),
+    ],
+};
+
+static COPYRIGHT: Complex = Complex {
+    attributes: &[attribute("author", true, Simple::String)],
+    children: &[
+        optional("year", Content::Simple(Simple::GYear)),
+        optional("license", URI),
+    ],
+};
+
+static LINK: Complex = Complex {
+    attributes: &[attribute("href", true, Simple::Uri)],
+    children: &[optional("text", STRING), optional("type", STRING)],
+};
+
+static EMAIL: Complex = Complex {
+    attributes: &[
+        attribute("id", true, Simple::String),
+        attribute("domain", true, Simple::String),
+    ],
+    children: &[],
+};
+
+static PERSON: Complex = Complex {
+    attributes: &[],
+    children: &[
+        optional("name", STRING),
+        optional("email", Content::Complex(&EMAIL)),
+        optional("link", Content::Complex(&LINK)),
+    ],
+};
+
+static BOUNDS: Complex = Complex {
+    attributes: &[
+        attribute("minlat", true, Simple::Latitude),
+        attribute("minlon", true, Simple::Longitude),
+        attribute("maxlat", true, Simple::Latitude),
+        attribute("maxlon", true, Simple::Longitude),
+    ],
+    children: &[],
+};
+
+impl Simple {
+    /// The name of the type in the schema.
+    fn name(self) -> &'static str {
+        match self {
+            Simple::String => "xsd:string",
+            Simple::Uri => "xsd:anyURI",
+            Simple::Decimal => "xsd:decimal",
+            Simple::Latitude => "latitudeType",
+            Simple::Longitude => "longitudeType",
+            Simple::Degrees => "degreesType",
+            Simple::DateTime => "xsd:dateTime",
+            Simple::GYear => "xsd:gYear",
+            Simple::NonNegativeInteger => "xsd:nonNegativeInteger",
+            Simple::DgpsStation => "dgpsStationType",
+            Simple::Fix => "fixType",
+            Simple::Version => "version",
+        }
+    }
+
+    /// Whether `value` is of this type.
+    fn accepts(self, value: &str) -> bool {
+        // Apart from strings, values have their whitespace collapsed.
+        let collapsed = value.trim_matches(|c| matches!(c, ' ' | '\t' | '\r' | '\n'));
+        match self {
+            Simple::String | Simple::Uri => true,
+            Simple::Decimal => decimal(collapsed).is_some(),
+            Simple::Latitude => decimal(collapsed).map_or(false, |v| (-90.0..=90.0).contains(&v)),
+            Simple::Longitude => decimal(collapsed).map_or(false, |v| (-180.0..180.0).contains(&v)),
+            Simple::Degrees => decimal(collapsed).map_or(false, |v| (0.0..360.0).contains(&v)),
+            Simple::DateTime => date_time(collapsed),
+            Simple::GYear => {
+                let mut lexer = Lexer(collapsed.as_bytes());
+                lexer.year() && lexer.timezone() && lexer.0.is_empty()
+            }
+            Simple::NonNegativeInteger => {
+                let digits = collapsed.strip_prefix('+').unwrap_or(collapsed);
+                !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+            }
+            Simple::DgpsStation => {
+                let digits = collapsed.strip_prefix('+').unwrap_or(collapsed);
+                digits.bytes().all(|b| b.is_ascii_digit())
+                    && digits.parse::<u32>().map_or(false, |v| v <= 1023)
+            }
+            Simple::Fix => matches!(collapsed, "none" | "2d" | "3d" | "dgps" | "pps"),
+            Simple::Version => collapsed == "1.1",
+        }
+    }
+}
+
+/// decimal gives the value of an `xsd:decimal`, which unlike an `f64` has no
+/// exponent, infinity or NaN.
+fn decimal(value: &str) -> Option<f64> {
+    let unsigned = value.strip_prefix(|c| c == '+' || c == '-').unwrap_or(value);
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if whole.len() + fraction.len() == 0 || !digits(whole) || !digits(fraction) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// date_time checks the lexical form of an `xsd:dateTime`:
+/// `[-]CCYY-MM-DDThh:mm:ss[.s+][Z|(+|-)hh:mm]`.
+fn date_time(value: &str) -> bool {
+    let mut lexer = Lexer(value.as_bytes());
+    lexer.year()
+        && lexer.literal(b'-')
+        && lexer.number(2, 1, 12)
+        && lexer.literal(b'-')
+        && lexer.number(2, 1, 31)
+        && lexer.literal(b'T')
+        && lexer.number(2, 0, 24)
+        && lexer.literal(b':')
+        && lexer.number(2, 0, 59)
+        && lexer.literal(b':')
+        && lexer.number(2, 0, 60)
+        && (!lexer.literal(b'.') || lexer.digits() > 0)
+        && lexer.timezone()
+        && lexer.0.is_empty()
+}
+
+/// Lexer consumes the parts of dates from the front of its input.
+struct Lexer<'a>(&'a [u8]);
+
+impl Lexer<'_> {
+    /// Consumes `byte`, if it is next.
+    fn literal(&mut self, byte: u8) -> bool {
+        match self.0.split_first() {
+            Some((&first, rest)) if first == byte => {
+                self.0 = rest;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Consumes the digits that are next, giving their count.
+    fn digits(&mut self) -> usize {
+        let count = self.0.iter().take_while(|b| b.is_ascii_digit()).count();
+        self.0 = &self.0[count..];
+        count
+    }
+
+    /// Consumes a number of exactly `width` digits from `min` to `max`.
+    fn number(&mut self, width: usize, min: u32, max: u32) -> bool {
+        let digits = &self.0[..self.0.len().min(width)];
+        if digits.len() != width || !digits.iter().all(u8::is_ascii_digit) {
+            return false;
+        }
+        self.0 = &self.0[width..];
+        let value = digits.iter().fold(0, |value, b| value * 10 + u32::from(b - b'0'));
+        (min..=max).contains(&value)
+    }
+
+    /// Consumes a year of at least four digits, without leading zeros when
+    /// longer, and optionally negative.
+    fn year(&mut self) -> bool {
+        self.literal(b'-');
+        let rest = self.0;
+        let count = self.digits();
+        count == 4 || (count > 4 && rest[0] != b'0')
+    }
+
+    /// Consumes an optional timezone, `Z` or an offset.
+    fn timezone(&mut self) -> bool {
+        if self.literal(b'Z') || self.0.is_empty() {
+            return true;
+        }
+        (self.literal(b'+') || self.literal(b'-'))
+            && self.number(2, 0, 14)
+            && self.literal(b':')
+            && self.number(2, 0, 59)
+    }
+}
+
+/// A way a document breaks the schema.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ViolationKind {
+    /// The root element is not a GPX 1.1 `gpx` element.
+    NotGpx11,
+    /// An element the schema has no place for.
+    UnexpectedElement(String),
+    /// An element that belongs before its preceding siblings.
+    MisplacedElement(String),
+    /// An element occurring more often than allowed.
+    TooMany(String),
+    /// A required element is missing.
+    MissingElement(&'static str),
+    /// A required attribute is missing.
+    MissingAttribute(&'static str),
+    /// An attribute the schema has no place for.
+    UnexpectedAttribute(String),
+    /// Text within an element that holds only elements.
+    UnexpectedText(String),
+    /// A value not of the `expected` type.
+    InvalidValue {
+        expected: &'static str,
+        value: String,
+    },
+}
+
+/// A place where a document breaks the schema.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// The path to the element or attribute at fault, such as
+    /// `/gpx/trk[1]/trkseg[1]/trkpt[2]/@lat`, counting siblings of the same
+    /// name from 1.
+    pub path: String,
+    pub kind: ViolationKind,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.path)?;
+        match &self.kind {
+            ViolationKind::NotGpx11 => write!(f, "not a GPX 1.1 document"),
+            ViolationKind::UnexpectedElement(name) => write!(f, "unexpected element `{}`", name),
+            ViolationKind::MisplacedElement(name) => write!(f, "element `{}` is out of order", name),
+            ViolationKind::TooMany(name) => write!(f, "too many `{}` elements", name),
+            ViolationKind::MissingElement(name) => write!(f, "missing element `{}`", name),
+            ViolationKind::MissingAttribute(name) => write!(f, "missing attribute `{}`", name),
+            ViolationKind::UnexpectedAttribute(name) => write!(f, "unexpected attribute `{}`", name),
+            ViolationKind::UnexpectedText(text) => write!(f, "unexpected text `{}`", text),
+            ViolationKind::InvalidValue { expected, value } => {
+                write!(f, "`{}` is not a valid {}", value, expected)
+            }
+        }
+    }
+}
+
+/// An element being checked.
+struct Frame {
+    path: String,
+    content: Content,
+    /// The index of the child in the sequence last seen, and how often.
+    child: usize,
+    count: u32,
+    /// How often each child name occurred, for the paths.
+    seen: Vec<(String, usize)>,
+    text: String,
+}
+
+impl Frame {
+    fn new(path: String, content: Content) -> Frame {
+        Frame {
+            path,
+            content,
+            child: 0,
+            count: 0,
+            seen: Vec::new(),
+            text: String::new(),
+        }
+    }
+
+    fn child_path(&mut self, name: &str) -> String {
+        let index = match self.seen.iter_mut().find(|(seen, _)| seen == name) {
+            Some((_, count)) => {
+                *count += 1;
+                *count
+            }
+            None => {
+                self.seen.push((name.to_owned(), 1));
+                1
+            }
+        };
+        format!("{}/{}[{}]", self.path, name, index)
+    }
+}
+
+/// display_name gives `name` as written, with its prefix.
+fn display_name(name: &OwnedName) -> String {
+    match &name.prefix {
+        Some(prefix) => format!("{}:{}", prefix, name.local_name),
+        None => name.local_name.clone(),
+    }
+}
+
+/// Validator checks a stream of XML events against the GPX 1.1 rules.
+struct Validator {
+    stack: Vec<Frame>,
+    violations: Vec<SchemaViolation>,
+}
+
+impl Validator {
+    fn violation(&mut self, path: String, kind: ViolationKind) {
+        self.violations.push(SchemaViolation { path, kind });
+    }
+
+    fn start(&mut self, name: &OwnedName, attributes: &[OwnedAttribute]) {
+        let in_gpx = name.namespace.as_deref() == Some(GPX11_NAMESPACE);
+        let shown = display_name(name);
+        let mut violations = Vec::new();
+        let frame = match self.stack.last_mut() {
+            None => {
+                let path = format!("/{}", shown);
+                if in_gpx && name.local_name == "gpx" {
+                    Frame::new(path, Content::Complex(&GPX))
+                } else {
+                    violations.push((path.clone(), ViolationKind::NotGpx11));
+                    Frame::new(path, Content::Any)
+                }
+            }
+            Some(parent) => {
+                let path = parent.child_path(&shown);
+                match parent.content {
+                    Content::Any => Frame::new(path, Content::Any),
+                    Content::Simple(_) => {
+                        violations.push((parent.path.clone(), ViolationKind::UnexpectedElement(shown)));
+                        Frame::new(path, Content::Any)
+                    }
+                    Content::Complex(complex) => {
+                        let matches = |child: &Child| in_gpx && child.name == name.local_name;
+                        let (current, count) = (parent.child, parent.count);
+                        match complex.children[current..].iter().position(matches) {
+                            // Another occurrence of the current child.
+                            Some(0) if count > 0 => {
+                                let child = &complex.children[current];
+                                parent.count += 1;
+                                if child.max.map_or(false, |max| parent.count > max) {
+                                    violations.push((path.clone(), ViolationKind::TooMany(shown)));
+                                }
+                                Frame::new(path, child.content)
+                            }
+                            Some(offset) => {
+                                // Children skipped over may be required.
+                                let from = if count > 0 { current + 1 } else { current };
+                                for child in &complex.children[from..current + offset] {
+                                    if child.min > 0 {
+                                        violations.push((
+                                            parent.path.clone(),
+                                            ViolationKind::MissingElement(child.name),
+                                        ));
+                                    }
+                                }
+                                parent.child = current + offset;
+                                parent.count = 1;
+                                Frame::new(path, complex.children[current + offset].content)
+                            }
+                            None => {
+                                let kind = if complex.children[..current].iter().any(matches) {
+                                    ViolationKind::MisplacedElement(shown)
+                                } else {
+                                    ViolationKind::UnexpectedElement(shown)
+                                };
+                                violations.push((path.clone(), kind));
+                                Frame::new(path, Content::Any)
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        for (path, kind) in violations {
+            self.violation(path, kind);
+        }
+        match frame.content {
+            Content::Complex(complex) => self.attributes(&frame.path, complex.attributes, attributes),
+            Content::Simple(_) => self.attributes(&frame.path, &[], attributes),
+            Content::Any => {}
+        }
+        self.stack.push(frame);
+    }
+
+    fn attributes(&mut self, path: &str, rules: &[Attribute], attributes: &[OwnedAttribute]) {
+        for rule in rules {
+            let attribute = attributes
+                .iter()
+                .find(|a| a.name.namespace.is_none() && a.name.local_name == rule.name);
+            match attribute {
+                Some(attribute) if !rule.value.accepts(&attribute.value) => self.violation(
+                    format!("{}/@{}", path, rule.name),
+                    ViolationKind::InvalidValue {
+                        expected: rule.value.name(),
+                        value: attribute.value.clone(),
+                    },
+                ),
+                Some(_) => {}
+                None if rule.required => {
+                    self.violation(path.to_owned(), ViolationKind::MissingAttribute(rule.name))
+                }
+                None => {}
+            }
+        }
+        for attribute in attributes {
+            let known = match attribute.name.namespace.as_deref() {
+                None => rules.iter().any(|a| a.name == attribute.name.local_name),
+                Some(namespace) => namespace == XSI_NAMESPACE,
+            };
+            if !known {
+                let name = display_name(&attribute.name);
+                self.violation(format!("{}/@{}", path, name), ViolationKind::UnexpectedAttribute(name));
+            }
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.text.push_str(text);
+        }
+    }
+
+    fn end(&mut self) {
+        let frame = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        match frame.content {
+            Content::Simple(simple) => {
+                if !simple.accepts(&frame.text) {
+                    self.violation(
+                        frame.path,
+                        ViolationKind::InvalidValue {
+                            expected: simple.name(),
+                            value: frame.text,
+                        },
+                    );
+                }
+            }
+            Content::Complex(complex) => {
+                let text = frame.text.trim();
+                if !text.is_empty() {
+                    self.violation(frame.path.clone(), ViolationKind::UnexpectedText(text.to_owned()));
+                }
+                let from = if frame.count > 0 { frame.child + 1 } else { frame.child };
+                for child in &complex.children[from..] {
+                    if child.min > 0 {
+                        self.violation(frame.path.clone(), ViolationKind::MissingElement(child.name));
+                    }
+                }
+            }
+            Content::Any => {}
+        }
+    }
+}
+
+/// Checks a GPX document against rules derived from the GPX 1.1 XSD: the
+/// order and number of elements, required attributes, and the lexical form
+/// of values such as times, decimals, years, latitudes and longitudes. The
+/// content of `<extensions>` is not checked.
+///
+/// Gives the violations found, none for a valid document, or an error if
+/// the document is not well-formed XML.
+///
+/// ```
+/// use gpx::validate;
+///
+/// let data = r#"<gpx xmlns="http://www.topografix.com/GPX/1/1" version="1.1" creator="me">
+///     <wpt lat="95.0" lon="2.0"><name>A</name><name>B</name></wpt>
+/// </gpx>"#;
+///
+/// let violations = validate(data.as_bytes()).unwrap();
+/// assert_eq!(violations[0].to_string(), "/gpx/wpt[1]/@lat: `95.0` is not a valid latitudeType");
+/// assert_eq!(violations[1].to_string(), "/gpx/wpt[1]/name[2]: too many `name` elements");
+/// ```
+pub fn validate<R: Read>(reader: R) -> GpxResult<Vec<SchemaViolation>> {
+    let config = ParserConfig {
+        cdata_to_characters: true,
+        ..ParserConfig::new()
+    };
+    let mut validator = Validator {
+        stack: Vec::new(),
+        violations: Vec::new(),
+    };
+    for event in EventReader::new_with_config(reader, config) {
+        match event? {
+            XmlEvent::StartElement { name, attributes, .. } => validator.start(&name, &attributes),
+            XmlEvent::EndElement { .. } => validator.end(),
+            XmlEvent::Characters(text) | XmlEvent::Whitespace(text) | XmlEvent::CData(text) => {
+                validator.text(&text)
+            }
+            _ => {}
+        }
+    }
+    Ok(validator.violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{coord, Point, Rect};
+    use time::OffsetDateTime;
+    use xml::reader::{EventReader, XmlEvent};
+
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{read, write, Fix, Gpx, GpxCopyright, GpxVersion, Link, Metadata, Person, Route, Track, TrackSegment, Waypoint};
+
+    use super::{validate, Complex, SchemaViolation, Simple, ViolationKind, GPX, METADATA, ROUTE, TRACK, WAYPOINT, GPX11_NAMESPACE, XSI_NAMESPACE};
+
+    fn violations(body: &str) -> Vec<SchemaViolation> {
+        let xml = format!(
+            "<gpx xmlns=\"http://www.topografix.com/GPX/1/1\" version=\"1.1\" creator=\"test\">{}</gpx>",
+            body
+        );
+        validate(xml.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn validate_simple_types() {
+        assert!(Simple::DateTime.accepts("2001-10-26T21:32:52"));
+        assert!(Simple::DateTime.accepts("2001-10-26T21:32:52.126+02:00"));
+        assert!(Simple::DateTime.accepts("-2001-10-26T21:32:52Z"));
+        assert!(!Simple::DateTime.accepts("2001-10-26"));
+        assert!(!Simple::DateTime.accepts("2001-10-26T21:32"));
+        assert!(!Simple::DateTime.accepts("01-10-26T21:32:52"));
+        assert!(!Simple::DateTime.accepts("2001-13-26T21:32:52"));
+        assert!(!Simple::DateTime.accepts("2001-10-26T21:32:52."));
+
+        assert!(Simple::GYear.accepts("2001"));
+        assert!(Simple::GYear.accepts("12001Z"));
+        assert!(!Simple::GYear.accepts("02001"));
+        assert!(!Simple::GYear.accepts("201"));
+
+        assert!(Simple::Decimal.accepts(" -1.5 "));
+        assert!(Simple::Decimal.accepts(".5"));
+        assert!(!Simple::Decimal.accepts("1e5"));
+        assert!(!Simple::Decimal.accepts("NaN"));
+        assert!(!Simple::Decimal.accepts("."));
+
+        assert!(Simple::Longitude.accepts("-180"));
+        assert!(!Simple::Longitude.accepts("180"));
+        assert!(Simple::DgpsStation.accepts("1023"));
+        assert!(!Simple::DgpsStation.accepts("1024"));
+        assert!(!Simple::NonNegativeInteger.accepts("-1"));
+    }
+
+    #[test]
+    fn validate_valid_document() {
+        let body = "<metadata><name>n</name><time>2020-01-01T00:00:00Z</time>
+            <bounds minlat=\"1\" minlon=\"2\" maxlat=\"3\" maxlon=\"4\"/></metadata>
+            <wpt lat=\"1\" lon=\"2\"><ele>3.5</ele><fix>3d</fix><extensions><a:b xmlns:a=\"urn:a\"/></extensions></wpt>
+            <trk><name>t</name><trkseg><trkpt lat=\"1\" lon=\"2\"/></trkseg><trkseg/></trk>";
+        assert_eq!(violations(body), []);
+    }
+
+    #[test]
+    fn validate_structure() {
+        let body = "<wpt lat=\"1\"><time>noon</time><ele>1</ele></wpt>
+            <trk><trkseg/><name>late</name><color>red</color>text</trk>
+            <wpt lat=\"1\" lon=\"2\"/>";
+        let kinds: Vec<_> = violations(body).into_iter().map(|v| (v.path, v.kind)).collect();
+        assert_eq!(
+            kinds,
+            [
+                ("/gpx/wpt[1]".into(), ViolationKind::MissingAttribute("lon")),
+                (
+                    "/gpx/wpt[1]/time[1]".into(),
+                    ViolationKind::InvalidValue {
+                        expected: "xsd:dateTime",
+                        value: "noon".into()
+                    }
+                ),
+                ("/gpx/wpt[1]/ele[1]".into(), ViolationKind::MisplacedElement("ele".into())),
+                ("/gpx/trk[1]/name[1]".into(), ViolationKind::MisplacedElement("name".into())),
+                ("/gpx/trk[1]/color[1]".into(), ViolationKind::UnexpectedElement("color".into())),
+                ("/gpx/trk[1]".into(), ViolationKind::UnexpectedText("text".into())),
+                ("/gpx/wpt[2]".into(), ViolationKind::MisplacedElement("wpt".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_root() {
+        let violations = validate("<gpx version=\"1.1\"/>".as_bytes()).unwrap();
+        assert_eq!(violations[0].kind, ViolationKind::NotGpx11);
+        let violations = validate(
+            "<gpx xmlns=\"http://www.topografix.com/GPX/1/1\" version=\"1.0\"/>".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            violations,
+            [
+                SchemaViolation {
+                    path: "/gpx/@version".into(),
+                    kind: ViolationKind::InvalidValue {
+                        expected: "version",
+                        value: "1.0".into()
+                    }
+                },
+                SchemaViolation {
+                    path: "/gpx".into(),
+                    kind: ViolationKind::MissingAttribute("creator")
+                },
+            ]
+        );
+    }
+
+    /// full_document sets every field the writer writes.
+    fn full_document() -> Gpx<EmptyExtensions> {
+        let link = || Link {
+            href: String::from("https://example.com"),
+            text: Some(String::from("Example")),
+            type_: Some(String::from("text/html")),
+        };
+        let time = || Some(OffsetDateTime::from_unix_timestamp(1_000_000_000).unwrap().into());
+        let point = || {
+            let mut point: Waypoint<EmptyExtensions> = Waypoint::new(Point::new(1.5, 2.5));
+            point.elevation = Some(10.0);
+            point.time = time();
+            point.magvar = Some(3.5);
+            point.geoidheight = Some(47.0);
+            point.name = Some(String::from("Name"));
+            point.comment = Some(String::from("Comment"));
+            point.description = Some(String::from("Description"));
+            point.source = Some(String::from("Source"));
+            point.links = vec![link(), link()];
+            point.symbol = Some(String::from("Flag, Blue"));
+            point.type_ = Some(String::from("Type"));
+            point.fix = Some(Fix::DGPS);
+            point.sat = Some(8);
+            point.hdop = Some(1.5);
+            point.vdop = Some(2.5);
+            point.pdop = Some(3.0);
+            point.dgps_age = Some(4.0);
+            point.dgpsid = Some(12);
+            point
+        };
+
+        let mut route = Route::new();
+        route.name = Some(String::from("Route"));
+        route.comment = Some(String::from("Comment"));
+        route.description = Some(String::from("Description"));
+        route.source = Some(String::from("Source"));
+        route.links = vec![link()];
+        route.number = Some(1);
+        route.type_ = Some(String::from("Type"));
+        route.points = vec![point(), point()];
+
+        let mut track = Track::new();
+        track.name = Some(String::from("Track"));
+        track.comment = Some(String::from("Comment"));
+        track.description = Some(String::from("Description"));
+        track.source = Some(String::from("Source"));
+        track.links = vec![link()];
+        track.number = Some(2);
+        track.type_ = Some(String::from("Type"));
+        track.segments = vec![TrackSegment { points: vec![point()] }, TrackSegment { points: vec![point()] }];
+
+        Gpx {
+            version: GpxVersion::Gpx11,
+            creator: Some(String::from("test")),
+            metadata: Some(Metadata {
+                name: Some(String::from("Metadata")),
+                description: Some(String::from("Description")),
+                author: Some(Person {
+                    name: Some(String::from("Author")),
+                    email: Some(String::from("author@example.com")),
+                    link: Some(link()),
+                }),
+                links: vec![link()],
+                time: time(),
+                keywords: Some(String::from("Keywords")),
+                copyright: Some(GpxCopyright {
+                    author: Some(String::from("Author")),
+                    year: Some(2001),
+                    license: Some(String::from("https://example.com/license")),
+                }),
+                bounds: Some(Rect::new(coord! { x: 1.0, y: 2.0 }, coord! { x: 3.0, y: 4.0 })),
+            }),
+            waypoints: vec![point()],
+            routes: vec![route],
+            tracks: vec![track],
+            comments: Vec::new(),
+            namespaces: vec![
+                (String::new(), String::from(GPX11_NAMESPACE)),
+                (String::from("xsi"), String::from(XSI_NAMESPACE)),
+            ],
+            schema_locations: vec![(String::from(GPX11_NAMESPACE), String::from("http://www.topografix.com/GPX/1/1/gpx.xsd"))],
+        }
+    }
+
+    /// children gives the names of the children of the first `element` in
+    /// `xml`, with repetitions collapsed.
+    fn children(xml: &[u8], element: &str) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        let mut depth = None;
+        for event in EventReader::new(xml) {
+            match event.unwrap() {
+                XmlEvent::StartElement { name, .. } => match depth {
+                    None if name.local_name == element => depth = Some(0),
+                    Some(0) => {
+                        if names.last() != Some(&name.local_name) {
+                            names.push(name.local_name);
+                        }
+                        depth = Some(1);
+                    }
+                    Some(level) => depth = Some(level + 1),
+                    None => {}
+                },
+                XmlEvent::EndElement { .. } => match depth {
+                    Some(0) => return names,
+                    Some(level) => depth = Some(level - 1),
+                    None => {}
+                },
+                _ => {}
+            }
+        }
+        names
+    }
+
+    /// sequence gives the names of the children of `complex`, leaving out
+    /// the extensions the document has none of.
+    fn sequence(complex: &Complex) -> Vec<&'static str> {
+        complex
+            .children
+            .iter()
+            .map(|child| child.name)
+            .filter(|&name| name != "extensions")
+            .collect()
+    }
+
+    #[test]
+    fn writer_follows_schema_sequences() {
+        let gpx = full_document();
+        let mut xml = Vec::new();
+        write(&gpx, &mut xml).unwrap();
+
+        assert_eq!(validate(xml.as_slice()).unwrap(), []);
+        assert_eq!(children(&xml, "gpx"), sequence(&GPX));
+        assert_eq!(children(&xml, "metadata"), sequence(&METADATA));
+        assert_eq!(children(&xml, "wpt"), sequence(&WAYPOINT));
+        assert_eq!(children(&xml, "rte"), sequence(&ROUTE));
+        assert_eq!(children(&xml, "rtept"), sequence(&WAYPOINT));
+        assert_eq!(children(&xml, "trk"), sequence(&TRACK));
+        assert_eq!(children(&xml, "trkpt"), sequence(&WAYPOINT));
+
+        // Everything written is read back.
+        assert_eq!(read(xml.as_slice()).unwrap(), gpx);
+    }
+}
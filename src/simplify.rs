@@ -0,0 +1,512 @@
+//! simplify reduces the number of points of track segments.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use geo_types::Point;
+
+use crate::distance::{haversine_distance, MEAN_EARTH_RADIUS};
+use crate::parser::extensions::WaypointExtensions;
+use crate::{DeriveOptions, Gpx, Track, TrackSegment};
+
+/// Number of halvings of the tolerance searched by
+/// [`TrackSegment::simplify_to_count`], enough to get below a millimeter.
+const SEARCH_STEPS: usize = 64;
+
+/// Projects points to meters on a plane tangent at the latitude `origin`,
+/// which is accurate over the extent of a track.
+fn project(point: Point<f64>, origin: f64) -> (f64, f64) {
+    let meters_per_degree = MEAN_EARTH_RADIUS.to_radians();
+    (
+        point.x() * meters_per_degree * origin.to_radians().cos(),
+        point.y() * meters_per_degree,
+    )
+}
+
+/// Distance from `p` to the line segment from `a` to `b`.
+fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length).clamp(0.0, 1.0)
+    };
+    (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+}
+
+/// Gives which of the projected points the Ramer–Douglas–Peucker algorithm
+/// keeps with tolerance `epsilon`.
+fn ramer_douglas_peucker(points: &[(f64, f64)], epsilon: f64) -> Vec<bool> {
+    let mut keep = vec![false; points.len()];
+    if let (Some(first), Some(last)) = (keep.first_mut(), points.len().checked_sub(1)) {
+        *first = true;
+        keep[last] = true;
+    }
+
+    let mut ranges = vec![(0, points.len().saturating_sub(1))];
+    while let Some((start, end)) = ranges.pop() {
+        let farthest = (start + 1..end)
+            .map(|i| (i, segment_distance(points[i], points[start], points[end])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((index, distance)) = farthest {
+            if distance > epsilon {
+                keep[index] = true;
+                ranges.push((start, index));
+                ranges.push((index, end));
+            }
+        }
+    }
+    keep
+}
+
+/// A point that [`visvalingam_whyatt`] may remove, ordered so that the one of
+/// least effective area comes first out of a max-heap.
+#[derive(PartialEq)]
+struct Candidate {
+    area: f64,
+    index: usize,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Candidate) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Candidate) -> Ordering {
+        other
+            .area
+            .total_cmp(&self.area)
+            .then(other.index.cmp(&self.index))
+    }
+}
+
+/// Area of the triangle of the projected points `a`, `b` and `c`.
+fn triangle_area(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)).abs() / 2.0
+}
+
+/// Gives which of the projected points the Visvalingam–Whyatt algorithm
+/// keeps, removing the point of least effective area, the triangle it forms
+/// with its neighbors, until at most `max_points` remain. The ends are always
+/// kept.
+fn visvalingam_whyatt(points: &[(f64, f64)], max_points: usize) -> Vec<bool> {
+    let count = points.len();
+    let mut keep = vec![true; count];
+    if count <= max_points.max(2) {
+        return keep;
+    }
+
+    let mut previous: Vec<usize> = (0..count).map(|i| i.saturating_sub(1)).collect();
+    let mut next: Vec<usize> = (1..=count).collect();
+    let mut areas = vec![f64::INFINITY; count];
+    let mut heap = BinaryHeap::with_capacity(count);
+    for index in 1..count - 1 {
+        areas[index] = triangle_area(points[index - 1], points[index], points[index + 1]);
+        heap.push(Candidate {
+            area: areas[index],
+            index,
+        });
+    }
+
+    let mut remaining = count;
+    while remaining > max_points.max(2) {
+        let removed = match heap.pop() {
+            Some(candidate) => candidate,
+            None => break,
+        };
+        // Entries are left in the heap when the area of their point changes.
+        if !keep[removed.index] || removed.area != areas[removed.index] {
+            continue;
+        }
+        keep[removed.index] = false;
+        remaining -= 1;
+        let (before, after) = (previous[removed.index], next[removed.index]);
+        next[before] = after;
+        previous[after] = before;
+        for index in [before, after] {
+            if index == 0 || index == count - 1 {
+                continue;
+            }
+            // Never less than the area removed, so that a point does not go
+            // before the ones removed to make it a corner.
+            areas[index] =
+                triangle_area(points[previous[index]], points[index], points[next[index]])
+                    .max(removed.area);
+            heap.push(Candidate {
+                area: areas[index],
+                index,
+            });
+        }
+    }
+    keep
+}
+
+/// Shares `total` points between parts of the given sizes in proportion to
+/// them, by the largest remainder, giving no part more than it has.
+fn share(sizes: &[usize], total: usize) -> Vec<usize> {
+    let sum: usize = sizes.iter().sum();
+    if sum <= total {
+        return sizes.to_vec();
+    }
+    let quotas: Vec<f64> = sizes
+        .iter()
+        .map(|&size| size as f64 * total as f64 / sum as f64)
+        .collect();
+    let mut shares: Vec<usize> = quotas.iter().map(|quota| quota.floor() as usize).collect();
+    let mut by_remainder: Vec<usize> = (0..sizes.len()).collect();
+    by_remainder.sort_by(|&a, &b| {
+        (quotas[b] - quotas[b].floor()).total_cmp(&(quotas[a] - quotas[a].floor()))
+    });
+    let left = total - shares.iter().sum::<usize>();
+    for &index in by_remainder.iter().take(left) {
+        shares[index] += 1;
+    }
+    shares
+}
+
+impl<E: WaypointExtensions + Default> TrackSegment<E> {
+    /// Simplifies the segment with the Ramer–Douglas–Peucker algorithm,
+    /// leaving out points less than `epsilon_meters` away from the simplified
+    /// line.
+    ///
+    /// Unlike simplifying the [`linestring`](TrackSegment::linestring), the
+    /// kept points are whole copies of the original ones, with their
+    /// elevation, time and extensions.
+    pub fn simplified(&self, epsilon_meters: f64) -> TrackSegment<E>
+    where
+        E: Clone,
+    {
+        let keep = ramer_douglas_peucker(&self.projected(), epsilon_meters);
+        TrackSegment {
+            points: self
+                .points
+                .iter()
+                .zip(keep)
+                .filter(|(_, keep)| *keep)
+                .map(|(point, _)| point.clone())
+                .collect(),
+        }
+    }
+
+    /// Simplifies the segment like [`TrackSegment::simplified`], with the
+    /// smallest tolerance that leaves at most `max_points` points.
+    ///
+    /// The two end points are always kept, so that many remain when
+    /// `max_points` is smaller.
+    pub fn simplify_to_count(&self, max_points: usize) -> TrackSegment<E>
+    where
+        E: Clone,
+    {
+        if self.points.len() <= max_points {
+            return self.clone();
+        }
+
+        let projected = self.projected();
+        let count = |epsilon| {
+            ramer_douglas_peucker(&projected, epsilon)
+                .into_iter()
+                .filter(|keep| *keep)
+                .count()
+        };
+        // No point is farther from the line than the diagonal of the bounds.
+        let (min, max) = projected.iter().fold(
+            ((f64::INFINITY, f64::INFINITY), (f64::NEG_INFINITY, f64::NEG_INFINITY)),
+            |(min, max), p| ((min.0.min(p.0), min.1.min(p.1)), (max.0.max(p.0), max.1.max(p.1))),
+        );
+        let (mut low, mut high) = (0.0, (max.0 - min.0).hypot(max.1 - min.1));
+        for _ in 0..SEARCH_STEPS {
+            let middle = (low + high) / 2.0;
+            if count(middle) <= max_points {
+                high = middle;
+            } else {
+                low = middle;
+            }
+        }
+        self.simplified(high)
+    }
+
+    /// Thins the segment to at most `max_points` points with the
+    /// Visvalingam–Whyatt algorithm, which removes the points that change
+    /// the shape the least, measured by the area of the triangle they form
+    /// with their neighbors. Easier to aim than [`TrackSegment::simplified`]
+    /// when a number of points is wanted, say for a thumbnail.
+    ///
+    /// The kept points are whole copies of the original ones. The two end
+    /// points are always kept, so two remain when `max_points` is smaller.
+    pub fn thin_to(&self, max_points: usize) -> TrackSegment<E>
+    where
+        E: Clone,
+    {
+        let keep = visvalingam_whyatt(&self.projected(), max_points);
+        TrackSegment {
+            points: self
+                .points
+                .iter()
+                .zip(keep)
+                .filter(|(_, keep)| *keep)
+                .map(|(point, _)| point.clone())
+                .collect(),
+        }
+    }
+
+    /// Decimates the segment to points at least `min_meters` apart: walking
+    /// along it, a point is kept when it is that far from the last one kept,
+    /// by the haversine distance. Cheaper than [`TrackSegment::simplified`],
+    /// this thins dense logs to a predictable density, but keeps the corners
+    /// no better than straight stretches.
+    ///
+    /// The two end points are always kept. The last one takes the place of
+    /// the point kept before it when those are closer than `min_meters`.
+    pub fn simplify_by_min_distance(&self, min_meters: f64) -> TrackSegment<E>
+    where
+        E: Clone,
+    {
+        let (first, last) = match (self.points.first(), self.points.len().checked_sub(1)) {
+            (Some(first), Some(last)) if last > 0 => (first, last),
+            _ => return self.clone(),
+        };
+        let mut points = vec![first.clone()];
+        for point in &self.points[1..last] {
+            let kept = &points[points.len() - 1];
+            if haversine_distance(kept.point(), point.point()) >= min_meters {
+                points.push(point.clone());
+            }
+        }
+        let end = &self.points[last];
+        if points.len() > 1 && haversine_distance(points[points.len() - 1].point(), end.point()) < min_meters {
+            points.pop();
+        }
+        points.push(end.clone());
+        TrackSegment { points }
+    }
+
+    /// Gives the points projected to meters around their mean latitude.
+    fn projected(&self) -> Vec<(f64, f64)> {
+        let origin = self.points.iter().map(|p| p.point().y()).sum::<f64>()
+            / self.points.len().max(1) as f64;
+        self.points
+            .iter()
+            .map(|point| project(point.point(), origin))
+            .collect()
+    }
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Thins the segments of this track to at most `total` points together,
+    /// by [`TrackSegment::thin_to`], sharing them between the segments in
+    /// proportion to their numbers of points. Every segment keeps its end
+    /// points though, so more remain when `total` is too small for those.
+    pub fn thin_to(&self, total: usize) -> Track<E>
+    where
+        E: Clone,
+    {
+        let sizes: Vec<usize> = self
+            .segments
+            .iter()
+            .map(|segment| segment.points.len())
+            .collect();
+        Track {
+            segments: self
+                .segments
+                .iter()
+                .zip(share(&sizes, total))
+                .map(|(segment, max_points)| segment.thin_to(max_points))
+                .collect(),
+            ..self.without_segments()
+        }
+    }
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Gives a copy of this document with every track segment simplified by
+    /// [`TrackSegment::simplified`]. Waypoints and routes are kept as they
+    /// are.
+    pub fn simplified(&self, epsilon_meters: f64) -> Gpx<E>
+    where
+        E: Clone,
+    {
+        self.simplified_with(epsilon_meters, &Default::default())
+    }
+
+    /// Simplifies the document like [`Gpx::simplified`], using the given
+    /// [`DeriveOptions`].
+    pub fn simplified_with(&self, epsilon_meters: f64, options: &DeriveOptions) -> Gpx<E>
+    where
+        E: Clone,
+    {
+        let tracks = self
+            .tracks
+            .iter()
+            .map(|track| Track {
+                segments: track
+                    .segments
+                    .iter()
+                    .map(|segment| segment.simplified(epsilon_meters))
+                    .collect(),
+                ..track.without_segments()
+            })
+            .collect();
+        Gpx {
+            waypoints: self.waypoints.clone(),
+            tracks,
+            routes: self.routes.clone(),
+            ..self.derive_document(options, || format!("simplified with epsilon={} m", epsilon_meters))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::testing::{self, time};
+    use crate::{distance_between, Track, TrackSegment};
+
+    use super::{segment_distance, share, visvalingam_whyatt};
+
+    fn segment(coordinates: &[(f64, f64)]) -> TrackSegment<EmptyExtensions> {
+        let mut segment = testing::segment(coordinates);
+        for (i, point) in segment.points.iter_mut().enumerate() {
+            point.time = Some(time(i as i64));
+            point.elevation = Some(i as f64);
+        }
+        segment
+    }
+
+    #[test]
+    fn simplify_straight_line() {
+        let coordinates: Vec<_> = (0..100)
+            .map(|i| (10.0 + i as f64 * 0.001, 45.0 + i as f64 * 0.0005))
+            .collect();
+        let segment = segment(&coordinates);
+
+        let simplified = segment.simplified(0.5);
+        assert_eq!(simplified.points, [segment.points[0].clone(), segment.points[99].clone()]);
+    }
+
+    #[test]
+    fn simplify_keeps_points_whole() {
+        // A zigzag along the equator, its corners about 11 m off the line.
+        let coordinates: Vec<_> = (0..9)
+            .map(|i| (i as f64 * 0.001, if i % 4 == 2 { 0.0001 } else { 0.0 }))
+            .collect();
+        let segment = segment(&coordinates);
+
+        let simplified = segment.simplified(5.0);
+        let kept: Vec<_> = simplified.points.iter().map(|p| p.point().x()).collect();
+        assert_eq!(kept, [0.0, 0.001, 0.002, 0.003, 0.005, 0.006, 0.007, 0.008]);
+        for point in &simplified.points {
+            let original = segment.points.iter().find(|p| p.point() == point.point()).unwrap();
+            assert_eq!(point, original);
+        }
+
+        // A tolerance above the corners' offset leaves only the ends.
+        assert_eq!(segment.simplified(12.0).points.len(), 2);
+    }
+
+    #[test]
+    fn simplify_to_count() {
+        let coordinates: Vec<_> = (0..50)
+            .map(|i| (i as f64 * 0.001, (i as f64 * 0.7).sin() * 0.001))
+            .collect();
+        let segment = segment(&coordinates);
+
+        for &max_points in &[2, 5, 10, 30] {
+            let simplified = segment.simplify_to_count(max_points);
+            assert!(simplified.points.len() <= max_points);
+            assert_eq!(simplified.points.first(), segment.points.first());
+            assert_eq!(simplified.points.last(), segment.points.last());
+        }
+        assert_eq!(segment.simplify_to_count(0).points.len(), 2);
+        assert_eq!(segment.simplify_to_count(50), segment);
+    }
+
+    #[test]
+    fn simplify_by_min_distance() {
+        // Points about 11 m apart along the equator.
+        let coordinates: Vec<_> = (0..100).map(|i| (i as f64 * 0.0001, 0.0)).collect();
+        let segment = segment(&coordinates);
+
+        let simplified = segment.simplify_by_min_distance(50.0);
+        assert_eq!(simplified.points.first(), segment.points.first());
+        assert_eq!(simplified.points.last(), segment.points.last());
+        for pair in simplified.points.windows(2) {
+            assert!(distance_between(&pair[0], &pair[1]) >= 50.0);
+        }
+        // Every fifth point is 55.6 m on, and the last replaces the 95th.
+        let kept: Vec<_> = simplified.points.iter().map(|p| p.time).collect();
+        let expected: Vec<_> = (0..95).step_by(5).chain([99]).map(|i| segment.points[i].time).collect();
+        assert_eq!(kept, expected);
+
+        assert_eq!(segment.simplify_by_min_distance(0.0), segment);
+        assert_eq!(segment.simplify_by_min_distance(1e7).points.len(), 2);
+        let single = TrackSegment { points: vec![segment.points[0].clone()] };
+        assert_eq!(single.simplify_by_min_distance(50.0), single);
+    }
+
+    #[test]
+    fn thin_to_count() {
+        // A noisy wave of 10 000 points, about 8 m apart.
+        let coordinates: Vec<_> = (0..10_000)
+            .map(|i| {
+                let noise = ((i * 7919) % 13) as f64 * 1e-6;
+                (
+                    10.0 + i as f64 * 0.0001,
+                    45.0 + (i as f64 / 300.0).sin() * 0.01 + noise,
+                )
+            })
+            .collect();
+        let segment = segment(&coordinates);
+
+        let thinned = segment.thin_to(500);
+        assert_eq!(thinned.points.len(), 500);
+        assert_eq!(thinned.points.first(), segment.points.first());
+        assert_eq!(thinned.points.last(), segment.points.last());
+
+        // Every original point stays close to the thinned line.
+        let projected = segment.projected();
+        let kept: Vec<_> = projected
+            .iter()
+            .zip(visvalingam_whyatt(&projected, 500))
+            .filter(|(_, keep)| *keep)
+            .map(|(point, _)| *point)
+            .collect();
+        let deviation = projected
+            .iter()
+            .map(|&point| {
+                kept.windows(2)
+                    .map(|pair| segment_distance(point, pair[0], pair[1]))
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .fold(0.0, f64::max);
+        assert!(deviation < 5.0, "deviation of {} m", deviation);
+
+        assert_eq!(segment.thin_to(0).points.len(), 2);
+        assert_eq!(segment.thin_to(20_000), segment);
+    }
+
+    #[test]
+    fn thin_track_to_count() {
+        let coordinates: Vec<_> = (0..400)
+            .map(|i| (i as f64 * 0.001, (i as f64 * 0.7).sin() * 0.001))
+            .collect();
+        let mut track: Track<EmptyExtensions> = Track::new();
+        for size in [300, 100, 3] {
+            track.segments.push(segment(&coordinates[..size]));
+        }
+
+        let thinned = track.thin_to(100);
+        let sizes: Vec<_> = thinned
+            .segments
+            .iter()
+            .map(|segment| segment.points.len())
+            .collect();
+        assert_eq!(sizes, [74, 25, 2]);
+        assert_eq!(share(&[300, 100, 3], 100), [74, 25, 1]);
+        assert_eq!(share(&[3, 4], 10), [3, 4]);
+    }
+}
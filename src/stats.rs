@@ -0,0 +1,362 @@
+//! stats computes time, distance and speed statistics of recorded tracks,
+//! and of routes.
+//!
+//! Distances are in meters, computed with the haversine formula, and speeds
+//! in meters per second. Multiply a speed by 3.6 for kilometers per hour.
+
+use time::Duration;
+
+use crate::distance::haversine_distance;
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Route, Time, Track, TrackSegment, Waypoint};
+
+/// Options for computing [`TrackStats`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatsOptions {
+    /// Speed in meters per second below which the interval between two points
+    /// is considered stopped rather than moving.
+    pub stop_speed: f64,
+
+    /// Number of consecutive point-to-point intervals the maximum speed is
+    /// averaged over, to suppress single-point GPS spikes. 1 uses the speed
+    /// between every two consecutive points. Segments with fewer intervals
+    /// are averaged over all of them.
+    pub max_speed_window: usize,
+}
+
+impl Default for StatsOptions {
+    fn default() -> StatsOptions {
+        StatsOptions {
+            stop_speed: 0.5,
+            max_speed_window: 5,
+        }
+    }
+}
+
+/// Statistics of a track or track segment.
+///
+/// The time-based fields are `None` unless every point has a timestamp and
+/// the points span a positive amount of time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackStats {
+    /// Time between the first and the last timestamp.
+    pub elapsed_time: Option<Duration>,
+
+    /// Time spent moving at least at the stop speed.
+    pub moving_time: Option<Duration>,
+
+    /// Distance in meters.
+    pub distance: f64,
+
+    /// Distance divided by elapsed time, in meters per second.
+    pub average_speed: Option<f64>,
+
+    /// Distance covered while moving divided by moving time, in meters per
+    /// second.
+    pub average_moving_speed: Option<f64>,
+
+    /// Highest speed averaged over the configured window, in meters per
+    /// second.
+    pub max_speed: Option<f64>,
+}
+
+/// Statistics being accumulated over segments.
+#[derive(Default)]
+struct Accumulator {
+    untimed: bool,
+    first_time: Option<Time>,
+    last_time: Option<Time>,
+    distance: f64,
+    moving_time: Duration,
+    moving_distance: f64,
+    max_speed: Option<f64>,
+}
+
+impl Accumulator {
+    fn add_segment<E: WaypointExtensions + Default>(
+        &mut self,
+        points: &[Waypoint<E>],
+        options: &StatsOptions,
+    ) {
+        let distances: Vec<f64> = points
+            .windows(2)
+            .map(|pair| haversine_distance(pair[0].point(), pair[1].point()))
+            .collect();
+        self.distance += distances.iter().sum::<f64>();
+
+        let times: Option<Vec<Time>> = points.iter().map(|point| point.time).collect();
+        let times = match times {
+            Some(times) if !self.untimed => times,
+            _ => {
+                self.untimed = true;
+                return;
+            }
+        };
+        if let (Some(&first), Some(&last)) = (times.first(), times.last()) {
+            self.first_time.get_or_insert(first);
+            self.last_time = Some(last);
+        }
+
+        for (distance, pair) in distances.iter().zip(times.windows(2)) {
+            let seconds = (pair[1] - pair[0]).as_seconds_f64();
+            if seconds > 0.0 && distance / seconds >= options.stop_speed {
+                self.moving_time += pair[1] - pair[0];
+                self.moving_distance += distance;
+            }
+        }
+
+        let window = options.max_speed_window.max(1).min(distances.len());
+        for (index, pair) in times.windows(window + 1).enumerate() {
+            let seconds = (pair[window] - pair[0]).as_seconds_f64();
+            if seconds > 0.0 {
+                let speed = distances[index..index + window].iter().sum::<f64>() / seconds;
+                self.max_speed = Some(self.max_speed.map_or(speed, |max| max.max(speed)));
+            }
+        }
+    }
+
+    fn finish(self) -> TrackStats {
+        let elapsed_time = match (self.first_time, self.last_time) {
+            (Some(first), Some(last)) if !self.untimed && last > first => Some(last - first),
+            _ => None,
+        };
+        let moving_time = elapsed_time.map(|_| self.moving_time);
+        let speed = |distance: f64, time: Duration| {
+            Some(time)
+                .filter(|time| time.is_positive())
+                .map(|time| distance / time.as_seconds_f64())
+        };
+        TrackStats {
+            elapsed_time,
+            moving_time,
+            distance: self.distance,
+            average_speed: elapsed_time.and_then(|time| speed(self.distance, time)),
+            average_moving_speed: moving_time.and_then(|time| speed(self.moving_distance, time)),
+            max_speed: elapsed_time.and(self.max_speed),
+        }
+    }
+}
+
+/// Horizontal distance in meters below which two points are too close to tell
+/// the grade between them.
+const MIN_RUN: f64 = 0.001;
+
+impl<E: WaypointExtensions + Default> TrackSegment<E> {
+    /// Gives the speed in meters per second at every point, from the point
+    /// before it.
+    ///
+    /// The first point, and points whose time or the previous point's time is
+    /// missing or the same, get `None`.
+    pub fn speeds(&self) -> impl Iterator<Item = Option<f64>> + '_ {
+        let speeds = self.points.windows(2).map(|pair| {
+            let seconds = (pair[1].time? - pair[0].time?).as_seconds_f64();
+            Some(seconds)
+                .filter(|&seconds| seconds != 0.0)
+                .map(|seconds| haversine_distance(pair[0].point(), pair[1].point()) / seconds)
+        });
+        self.points.first().map(|_| None).into_iter().chain(speeds)
+    }
+
+    /// Gives the grade at every point, from the point before it, as the rise
+    /// over the horizontal run: 0.1 is a 10% climb.
+    ///
+    /// The first point, points whose elevation or the previous point's
+    /// elevation is missing, and points at the same place as the previous one
+    /// get `None`.
+    pub fn grades(&self) -> impl Iterator<Item = Option<f64>> + '_ {
+        let grades = self.points.windows(2).map(|pair| {
+            let rise = pair[1].elevation? - pair[0].elevation?;
+            let run = haversine_distance(pair[0].point(), pair[1].point());
+            Some(rise / run).filter(|_| run >= MIN_RUN)
+        });
+        self.points.first().map(|_| None).into_iter().chain(grades)
+    }
+
+    /// Computes the statistics of the segment with default [`StatsOptions`].
+    pub fn stats(&self) -> TrackStats {
+        self.stats_with(&Default::default())
+    }
+
+    /// Computes the statistics of the segment.
+    pub fn stats_with(&self, options: &StatsOptions) -> TrackStats {
+        let mut accumulator = Accumulator::default();
+        accumulator.add_segment(&self.points, options);
+        accumulator.finish()
+    }
+}
+
+impl<E: WaypointExtensions + Default> Route<E> {
+    /// Computes the statistics of the route with default [`StatsOptions`].
+    pub fn stats(&self) -> TrackStats {
+        self.stats_with(&Default::default())
+    }
+
+    /// Computes the statistics of the route, as those of a segment of its
+    /// points.
+    pub fn stats_with(&self, options: &StatsOptions) -> TrackStats {
+        let mut accumulator = Accumulator::default();
+        accumulator.add_segment(&self.points, options);
+        accumulator.finish()
+    }
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Computes the statistics of the track with default [`StatsOptions`].
+    pub fn stats(&self) -> TrackStats {
+        self.stats_with(&Default::default())
+    }
+
+    /// Computes the statistics of the track.
+    ///
+    /// The elapsed time runs from the first point of the first segment to the
+    /// last point of the last segment, while distances, moving time and speeds
+    /// only count the points within each segment.
+    pub fn stats_with(&self, options: &StatsOptions) -> TrackStats {
+        let mut accumulator = Accumulator::default();
+        for segment in &self.segments {
+            accumulator.add_segment(&segment.points, options);
+        }
+        accumulator.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::BufReader;
+
+    use assert_approx_eq::assert_approx_eq;
+    use geo_types::Point;
+    use time::{Duration, OffsetDateTime};
+
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{read, TrackSegment, Waypoint};
+
+    use super::StatsOptions;
+
+    #[test]
+    fn stats_garmin_activity() {
+        let file = File::open("tests/fixtures/garmin-activity.gpx").unwrap();
+        let gpx = read(BufReader::new(file)).unwrap();
+        let track = &gpx.tracks[0];
+        let stats = track.stats();
+
+        // 14:46:35 to 16:18:13
+        assert_eq!(stats.elapsed_time, Some(Duration::seconds(5498)));
+        assert_approx_eq!(stats.distance, track.length_meters());
+        let moving_time = stats.moving_time.unwrap();
+        assert!(moving_time > Duration::minutes(60) && moving_time <= Duration::seconds(5498));
+        let average_speed = stats.average_speed.unwrap();
+        assert_approx_eq!(average_speed, stats.distance / 5498.0);
+        assert!(stats.average_moving_speed.unwrap() >= average_speed);
+        let max_speed = stats.max_speed.unwrap();
+        assert!(max_speed > average_speed && max_speed < 10.0);
+
+        // A wider window smooths out peaks.
+        let smoothed = track.stats_with(&StatsOptions {
+            max_speed_window: 30,
+            ..Default::default()
+        });
+        assert!(smoothed.max_speed.unwrap() <= max_speed);
+    }
+
+    #[test]
+    fn stats_without_timestamps() {
+        let file = File::open("tests/fixtures/mousehole_to_paul.gpx").unwrap();
+        let gpx = read(BufReader::new(file)).unwrap();
+        let stats = gpx.tracks[0].stats();
+
+        assert!(stats.distance > 0.0);
+        assert_eq!(stats.elapsed_time, None);
+        assert_eq!(stats.moving_time, None);
+        assert_eq!(stats.average_speed, None);
+        assert_eq!(stats.average_moving_speed, None);
+        assert_eq!(stats.max_speed, None);
+    }
+
+    #[test]
+    fn speeds_and_grades() {
+        let start = OffsetDateTime::from_unix_timestamp(1_600_000_000).unwrap();
+        let mut first: Waypoint<EmptyExtensions> = Waypoint::new(Point::new(0.0, 0.0));
+        first.time = Some(start.into());
+        first.elevation = Some(100.0);
+        // 0.001 degrees of latitude apart: 6371008.8 m * 0.001 * pi / 180.
+        let mut second: Waypoint<EmptyExtensions> = Waypoint::new(Point::new(0.0, 0.001));
+        second.time = Some((start + Duration::seconds(10)).into());
+        second.elevation = Some(111.1195);
+        let mut segment = TrackSegment {
+            points: vec![first, second],
+        };
+
+        let speeds: Vec<_> = segment.speeds().collect();
+        assert_eq!(speeds.len(), 2);
+        assert_eq!(speeds[0], None);
+        assert_approx_eq!(speeds[1].unwrap(), 11.11950, 1e-4);
+        let grades: Vec<_> = segment.grades().collect();
+        assert_eq!(grades[0], None);
+        assert_approx_eq!(grades[1].unwrap(), 0.1, 1e-5);
+
+        // Missing or coincident times and missing elevations give no value.
+        segment.points[1].time = segment.points[0].time;
+        segment.points[1].elevation = None;
+        assert_eq!(segment.speeds().collect::<Vec<_>>(), [None, None]);
+        assert_eq!(segment.grades().collect::<Vec<_>>(), [None, None]);
+        segment.points[1].time = None;
+        assert_eq!(segment.speeds().collect::<Vec<_>>(), [None, None]);
+
+        // Points at the same place have no grade.
+        segment.points[1] = segment.points[0].clone();
+        segment.points[1].elevation = Some(101.0);
+        assert_eq!(segment.grades().collect::<Vec<_>>(), [None, None]);
+
+        let empty: TrackSegment<EmptyExtensions> = TrackSegment::new();
+        assert_eq!(empty.speeds().count(), 0);
+    }
+
+    #[test]
+    fn stats_moving_time() {
+        let start = OffsetDateTime::from_unix_timestamp(1_600_000_000).unwrap();
+        let points = [(0.0, 0), (0.001, 10), (0.001, 70), (0.002, 80)]
+            .iter()
+            .map(|&(x, seconds)| {
+                let mut point: Waypoint<EmptyExtensions> = Waypoint::new(Point::new(x, 0.0));
+                point.time = Some((start + Duration::seconds(seconds)).into());
+                point
+            })
+            .collect();
+        let segment = TrackSegment { points };
+        let stats = segment.stats_with(&StatsOptions {
+            max_speed_window: 1,
+            ..Default::default()
+        });
+
+        assert_eq!(stats.elapsed_time, Some(Duration::seconds(80)));
+        assert_eq!(stats.moving_time, Some(Duration::seconds(20)));
+        assert_approx_eq!(stats.average_moving_speed.unwrap(), stats.distance / 20.0);
+        assert_approx_eq!(stats.max_speed.unwrap(), stats.distance / 20.0);
+    }
+
+    #[test]
+    fn stats_segment_shorter_than_window() {
+        let start = OffsetDateTime::from_unix_timestamp(1_600_000_000).unwrap();
+        let points = [(0.0, 0), (0.001, 10), (0.003, 20)]
+            .iter()
+            .map(|&(x, seconds)| {
+                let mut point: Waypoint<EmptyExtensions> = Waypoint::new(Point::new(x, 0.0));
+                point.time = Some((start + Duration::seconds(seconds)).into());
+                point
+            })
+            .collect();
+        let segment = TrackSegment { points };
+        let stats = segment.stats();
+
+        // The default window of 5 intervals falls back to the whole span.
+        let average_speed = stats.average_speed.unwrap();
+        assert_approx_eq!(stats.max_speed.unwrap(), average_speed);
+
+        let single = TrackSegment {
+            points: segment.points[..1].to_vec(),
+        };
+        assert_eq!(single.stats().max_speed, None);
+    }
+}
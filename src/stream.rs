@@ -0,0 +1,733 @@
+//! stream reads the track points of a GPX document, and optionally its route
+//! points, one at a time, without keeping the document in memory.
+
+use std::cell::Cell;
+use std::io::{Chain, Cursor, Read};
+use std::rc::Rc;
+
+#[cfg(feature = "use-serde")]
+use serde::{Deserialize, Serialize};
+use xml::attribute::OwnedAttribute;
+use xml::escape::escape_str_attribute;
+use xml::name::OwnedName;
+use xml::namespace::{Namespace, NS_XMLNS_PREFIX, NS_XML_PREFIX};
+use xml::reader::XmlEvent;
+
+use crate::errors::{GpxError, GpxResult, ReadWarning};
+use crate::parser::extensions::WaypointExtensions;
+use crate::parser::{
+    create_xml_rs_context_with_options, skip_element, waypoint, Context, TrackingReader,
+};
+use crate::reader::ParseOptions;
+use crate::{GpxVersion, Waypoint};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Progress counts the bytes read from the input, and hashes the ones read
+/// since the last mark. It is shared by the reader and the iterator, which
+/// parse on one thread, so plain cells do.
+#[derive(Debug)]
+struct Progress {
+    offset: Cell<u64>,
+    hash: Cell<u64>,
+}
+
+impl Progress {
+    fn new(offset: u64) -> Progress {
+        Progress {
+            offset: Cell::new(offset),
+            hash: Cell::new(FNV_OFFSET_BASIS),
+        }
+    }
+
+    /// Restarts hashing, giving the current offset.
+    fn mark(&self) -> u64 {
+        self.hash.set(FNV_OFFSET_BASIS);
+        self.offset()
+    }
+
+    fn offset(&self) -> u64 {
+        self.offset.get()
+    }
+
+    fn hash(&self) -> u64 {
+        self.hash.get()
+    }
+}
+
+/// ProgressReader records the bytes read through it in a [`Progress`].
+struct ProgressReader<R: Read> {
+    inner: R,
+    progress: Rc<Progress>,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        let hash = buf[..read]
+            .iter()
+            .fold(self.progress.hash(), |hash, &byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+            });
+        self.progress.hash.set(hash);
+        self.progress.offset.set(self.progress.offset() + read as u64);
+        Ok(read)
+    }
+}
+
+/// The input of the parser: the elements enclosing the checkpoint, then the
+/// reader.
+type Input<R> = Chain<Cursor<Vec<u8>>, ProgressReader<R>>;
+
+/// The elements enclosing a track segment, and the namespaces in scope.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
+struct Scope {
+    open_elements: Vec<String>,
+    namespaces: Vec<(String, String)>,
+}
+
+impl Scope {
+    /// Opens the enclosing elements again, declaring the namespaces on the
+    /// outermost one.
+    fn prelude(&self) -> Vec<u8> {
+        let mut prelude = String::new();
+        for (depth, element) in self.open_elements.iter().enumerate() {
+            prelude.push('<');
+            prelude.push_str(element);
+            if depth == 0 {
+                for (prefix, uri) in &self.namespaces {
+                    prelude.push_str(" xmlns");
+                    if !prefix.is_empty() {
+                        prelude.push(':');
+                        prelude.push_str(prefix);
+                    }
+                    prelude.push_str("=\"");
+                    prelude.push_str(&escape_str_attribute(uri));
+                    prelude.push('"');
+                }
+            }
+            prelude.push('>');
+        }
+        prelude.into_bytes()
+    }
+}
+
+/// The point a [`TrackPointIter`] can be resumed from.
+///
+/// A checkpoint records the byte offset to resume reading at together with
+/// the parser state there, and a hash of the bytes of the last point read
+/// before it was taken. Resuming checks the hash, so a checkpoint is only
+/// valid for byte-identical input.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
+pub struct Checkpoint {
+    offset: u64,
+    version: GpxVersion,
+    options: ParseOptions,
+    scope: Scope,
+    routes: bool,
+    track: usize,
+    segment: usize,
+    route: usize,
+    in_route: bool,
+    length: u64,
+    hash: u64,
+}
+
+impl Checkpoint {
+    fn start(options: ParseOptions) -> Checkpoint {
+        Checkpoint {
+            offset: 0,
+            version: GpxVersion::Unknown,
+            options,
+            scope: Default::default(),
+            routes: false,
+            track: 0,
+            segment: 0,
+            route: 0,
+            in_route: false,
+            length: 0,
+            hash: FNV_OFFSET_BASIS,
+        }
+    }
+
+    /// The byte offset in the input to resume reading at.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+/// The bytes of the last point read: where it starts and how to verify it.
+#[derive(Clone, Copy, Debug)]
+struct Span {
+    offset: u64,
+    length: u64,
+    hash: u64,
+    track: usize,
+    segment: usize,
+    route: usize,
+    in_route: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Start,
+    Resume,
+    Document,
+    Track,
+    Segment,
+    Route,
+    Done,
+}
+
+/// TrackPointIter reads the track points of a GPX document one at a time, so
+/// documents too large to fit in memory can be processed.
+///
+/// Everything but the track points is skipped, and the points of routes too
+/// unless asked for with [`TrackPointIter::with_routes`]. An iterator can be
+/// stopped and later resumed from a [`Checkpoint`].
+///
+/// ```
+/// use gpx::TrackPointIter;
+/// use gpx::parser::extensions::EmptyExtensions;
+///
+/// let data = "<gpx version=\"1.1\"><trk><trkseg>
+///     <trkpt lat=\"1.0\" lon=\"2.0\"/>
+///     <trkpt lat=\"1.5\" lon=\"2.5\"/>
+/// </trkseg></trk></gpx>";
+///
+/// let mut points = TrackPointIter::<_, EmptyExtensions>::new(data.as_bytes());
+/// let first = points.next().unwrap().unwrap();
+/// assert_eq!(first.point().y(), 1.0);
+///
+/// // Later, possibly in another process, continue after the first point.
+/// let checkpoint = points.checkpoint();
+/// let rest = &data.as_bytes()[checkpoint.offset() as usize..];
+/// let points = TrackPointIter::<_, EmptyExtensions>::resume(rest, &checkpoint);
+/// assert_eq!(points.count(), 1);
+/// ```
+pub struct TrackPointIter<R: Read, E: WaypointExtensions + Default + 'static> {
+    context: Context<'static, TrackingReader<Input<R>>, E>,
+    progress: Rc<Progress>,
+    state: State,
+    resumed: Checkpoint,
+    scope: Scope,
+    last_scope: Option<Scope>,
+    routes: bool,
+    track: usize,
+    segment: usize,
+    route: usize,
+    last: Option<Span>,
+    verify: Option<(u64, u64)>,
+}
+
+impl<R: Read, E: WaypointExtensions + Default + 'static> TrackPointIter<R, E> {
+    /// Reads the track points of `reader` from its start.
+    pub fn new(reader: R) -> TrackPointIter<R, E> {
+        TrackPointIter::with_options(reader, Default::default())
+    }
+
+    /// Reads the track points of `reader` from its start, using the given
+    /// [`ParseOptions`].
+    pub fn with_options(reader: R, options: ParseOptions) -> TrackPointIter<R, E> {
+        TrackPointIter::resume(reader, &Checkpoint::start(options))
+    }
+
+    /// Continues reading from `checkpoint`, with `reader` positioned at
+    /// [`Checkpoint::offset`] of the input the checkpoint was taken from.
+    ///
+    /// The points read before the checkpoint was taken are not read again.
+    /// If the input turns out to differ, the iterator gives
+    /// [`GpxError::CheckpointMismatch`].
+    pub fn resume(reader: R, checkpoint: &Checkpoint) -> TrackPointIter<R, E> {
+        let progress = Rc::new(Progress::new(checkpoint.offset));
+        let reader = Cursor::new(checkpoint.scope.prelude()).chain(ProgressReader {
+            inner: reader,
+            progress: progress.clone(),
+        });
+        let resuming = !checkpoint.scope.open_elements.is_empty();
+        TrackPointIter {
+            // The progress is counted from the bytes the parser reads, which
+            // xml-rs reads no further than the events parsed.
+            context: create_xml_rs_context_with_options(
+                reader,
+                checkpoint.version,
+                checkpoint.options.clone(),
+            ),
+            progress,
+            state: if resuming {
+                State::Resume
+            } else {
+                State::Start
+            },
+            resumed: checkpoint.clone(),
+            scope: checkpoint.scope.clone(),
+            last_scope: None,
+            routes: checkpoint.routes,
+            track: checkpoint.track,
+            segment: checkpoint.segment,
+            route: checkpoint.route,
+            last: None,
+            verify: Some((checkpoint.length, checkpoint.hash)).filter(|_| resuming),
+        }
+    }
+
+    /// Reads the points of routes too, in document order with the track
+    /// points. Checkpoints taken then resume with route points too.
+    ///
+    /// ```
+    /// use gpx::TrackPointIter;
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let data = "<gpx version=\"1.1\">
+    ///     <rte><name>planned</name><rtept lat=\"1.0\" lon=\"2.0\"/></rte>
+    ///     <trk><trkseg><trkpt lat=\"1.5\" lon=\"2.5\"/></trkseg></trk>
+    /// </gpx>";
+    ///
+    /// let points = TrackPointIter::<_, EmptyExtensions>::new(data.as_bytes());
+    /// assert_eq!(points.count(), 1);
+    ///
+    /// let mut points = TrackPointIter::<_, EmptyExtensions>::new(data.as_bytes()).with_routes();
+    /// assert_eq!(points.next().unwrap().unwrap().point().y(), 1.0);
+    /// assert_eq!(points.route_index(), Some(0));
+    /// assert_eq!(points.next().unwrap().unwrap().point().y(), 1.5);
+    /// assert_eq!(points.route_index(), None);
+    /// ```
+    pub fn with_routes(mut self) -> TrackPointIter<R, E> {
+        self.routes = true;
+        self.resumed.routes = true;
+        self
+    }
+
+    /// Gives the checkpoint after the last point read.
+    ///
+    /// Checkpoints are taken between the points of a track segment or route,
+    /// so they can be taken at any time.
+    pub fn checkpoint(&self) -> Checkpoint {
+        match (self.last, &self.last_scope) {
+            (Some(span), Some(scope)) => Checkpoint {
+                offset: span.offset,
+                version: self.context.version(),
+                options: self.context.options().clone(),
+                scope: scope.clone(),
+                routes: self.routes,
+                track: span.track,
+                segment: span.segment,
+                route: span.route,
+                in_route: span.in_route,
+                length: span.length,
+                hash: span.hash,
+            },
+            _ => self.resumed.clone(),
+        }
+    }
+
+    /// The index of the route of the last point read, when it is a route
+    /// point.
+    pub fn route_index(&self) -> Option<usize> {
+        match self.last {
+            Some(span) => Some(span.route).filter(|_| span.in_route),
+            None => Some(self.resumed.route).filter(|_| self.resumed.in_route),
+        }
+    }
+
+    /// The index of the track of the last point read, or of the tracks read
+    /// before it for a route point.
+    pub fn track_index(&self) -> usize {
+        self.last.map_or(self.resumed.track, |span| span.track)
+    }
+
+    /// The index, within its track, of the track segment of the last point
+    /// read, which is 0 for a route point.
+    pub fn segment_index(&self) -> usize {
+        self.last.map_or(self.resumed.segment, |span| span.segment)
+    }
+
+    /// Gives the warnings recorded so far in lenient mode. After resuming,
+    /// their positions are not those in the document.
+    pub fn warnings(&self) -> &[ReadWarning] {
+        self.context.warnings()
+    }
+
+    /// Consumes the starting tag of `local_name`, giving its qualified name,
+    /// namespaces and attributes.
+    fn open(
+        &mut self,
+        local_name: &'static str,
+    ) -> GpxResult<(String, Namespace, Vec<OwnedAttribute>)> {
+        loop {
+            match self.context.reader().next() {
+                Some(Ok(XmlEvent::StartElement {
+                    name,
+                    attributes,
+                    namespace,
+                })) if name.local_name == local_name => {
+                    return Ok((qualified_name(&name), namespace, attributes));
+                }
+                Some(Ok(XmlEvent::StartElement { name, .. }))
+                | Some(Ok(XmlEvent::EndElement { name })) => {
+                    return Err(GpxError::InvalidChildElement(name.local_name, local_name));
+                }
+                Some(Ok(XmlEvent::Characters(chars))) => {
+                    return Err(GpxError::InvalidChildElement(chars, local_name));
+                }
+                Some(Err(err)) => return Err(err.into()),
+                Some(_) => {} //ignore other elements
+                None => return Err(GpxError::MissingOpeningTag(local_name)),
+            }
+        }
+    }
+
+    /// Peeks at the next event, giving the local name of a starting tag, or
+    /// `None` for an ending tag.
+    fn peek(&mut self, tagname: &'static str) -> GpxResult<Option<Option<String>>> {
+        match self.context.peek_ok(tagname)? {
+            Some(XmlEvent::StartElement { name, .. }) => {
+                Ok(Some(Some(name.local_name.clone())))
+            }
+            Some(XmlEvent::EndElement { .. }) => Ok(Some(None)),
+            Some(_) => Ok(None),
+            None => Err(GpxError::MissingClosingTag(tagname)),
+        }
+    }
+
+    /// Reads up to the next track point, giving `None` at the end of the
+    /// document.
+    fn advance(&mut self) -> GpxResult<Option<Waypoint<E>>> {
+        'states: loop {
+            match self.state {
+                State::Start => {
+                    let (name, _, attributes) = self.open("gpx")?;
+                    let version = attributes
+                        .iter()
+                        .find(|attr| attr.name.local_name == "version")
+                        .ok_or(GpxError::InvalidElementLacksAttribute("version", "gpx"))?;
+                    self.context
+                        .set_version(version.value.parse()?);
+                    self.scope.open_elements = vec![name];
+                    self.state = State::Document;
+                }
+                State::Resume if self.resumed.in_route => {
+                    self.open("gpx")?;
+                    self.open("rte")?;
+                    self.state = State::Route;
+                }
+                State::Resume => {
+                    self.open("gpx")?;
+                    self.open("trk")?;
+                    self.open("trkseg")?;
+                    self.state = State::Segment;
+                }
+                State::Document => match self.peek("gpx")? {
+                    Some(Some(child)) if child == "trk" => {
+                        let (name, _, _) = self.open("trk")?;
+                        self.scope.open_elements.truncate(1);
+                        self.scope.open_elements.push(name);
+                        self.state = State::Track;
+                    }
+                    Some(Some(child)) if child == "rte" && self.routes => {
+                        let (name, namespace, _) = self.open("rte")?;
+                        self.scope.open_elements.truncate(1);
+                        self.scope.open_elements.push(name);
+                        self.scope.namespaces = declared_namespaces(namespace);
+                        self.last_scope = None;
+                        self.state = State::Route;
+                    }
+                    Some(Some(_)) => {
+                        self.context.reader().next();
+                        skip_element(&mut self.context, "gpx")?;
+                    }
+                    Some(None) => {
+                        self.context.reader().next(); //consume the end tag
+                        self.state = State::Done;
+                    }
+                    None => {
+                        self.context.reader().next(); //consume and ignore this event
+                    }
+                },
+                State::Track => match self.peek("track")? {
+                    Some(Some(child)) if child == "trkseg" => {
+                        let (name, namespace, _) = self.open("trkseg")?;
+                        self.scope.open_elements.truncate(2);
+                        self.scope.open_elements.push(name);
+                        self.scope.namespaces = declared_namespaces(namespace);
+                        self.last_scope = None;
+                        self.state = State::Segment;
+                    }
+                    Some(Some(_)) => {
+                        self.context.reader().next();
+                        skip_element(&mut self.context, "track")?;
+                    }
+                    Some(None) => {
+                        self.context.reader().next(); //consume the end tag
+                        self.track += 1;
+                        self.segment = 0;
+                        self.state = State::Document;
+                    }
+                    None => {
+                        self.context.reader().next(); //consume and ignore this event
+                    }
+                },
+                State::Segment | State::Route => {
+                    let in_route = self.state == State::Route;
+                    let (tagname, point_tag) = match in_route {
+                        true => ("route", "rtept"),
+                        false => ("tracksegment", "trkpt"),
+                    };
+                    // Nothing past the previous child of the segment or route
+                    // has been read, making this a safe point to resume from.
+                    let offset = self.progress.mark();
+                    loop {
+                        match self.peek(tagname)? {
+                            Some(Some(child)) if child == point_tag => break,
+                            // The name, links and such of a route come before
+                            // its points.
+                            Some(Some(_)) if in_route => {
+                                self.context.reader().next();
+                                skip_element(&mut self.context, "route")?;
+                                continue 'states;
+                            }
+                            Some(Some(child)) => {
+                                return Err(GpxError::InvalidChildElement(child, "tracksegment"));
+                            }
+                            Some(None) if in_route => {
+                                self.context.reader().next(); //consume the end tag
+                                self.route += 1;
+                                self.state = State::Document;
+                                continue 'states;
+                            }
+                            Some(None) => {
+                                self.context.reader().next(); //consume the end tag
+                                self.segment += 1;
+                                self.state = State::Track;
+                                continue 'states;
+                            }
+                            None => {
+                                self.context.reader().next(); //consume and ignore this event
+                            }
+                        }
+                    }
+
+                    let point = match waypoint::consume_or_skip(&mut self.context, point_tag)? {
+                        Some(point) => point,
+                        None => continue,
+                    };
+                    self.context.count_point()?;
+                    let length = self.progress.offset() - offset;
+                    let hash = self.progress.hash();
+                    if let Some(expected) = self.verify.take() {
+                        // This point was read before the checkpoint was taken.
+                        if expected != (length, hash) {
+                            return Err(GpxError::CheckpointMismatch);
+                        }
+                        continue;
+                    }
+
+                    if self.last_scope.is_none() {
+                        self.last_scope = Some(self.scope.clone());
+                    }
+                    self.last = Some(Span {
+                        offset,
+                        length,
+                        hash,
+                        track: self.track,
+                        segment: if in_route { 0 } else { self.segment },
+                        route: self.route,
+                        in_route,
+                    });
+                    return Ok(Some(point));
+                }
+                State::Done => return Ok(None),
+            }
+        }
+    }
+}
+
+impl<R: Read, E: WaypointExtensions + Default + 'static> Iterator for TrackPointIter<R, E> {
+    type Item = GpxResult<Waypoint<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.advance() {
+            Ok(point) => point.map(Ok),
+            Err(err) => {
+                self.state = State::Done;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// declared_namespaces gives the namespaces in scope of `namespace` as prefix
+/// and URI pairs, without those every element has.
+fn declared_namespaces(namespace: Namespace) -> Vec<(String, String)> {
+    namespace
+        .0
+        .into_iter()
+        .filter(|(prefix, uri)| prefix != NS_XML_PREFIX && prefix != NS_XMLNS_PREFIX && !uri.is_empty())
+        .collect()
+}
+
+/// qualified_name gives `name` as written in the document.
+fn qualified_name(name: &OwnedName) -> String {
+    match &name.prefix {
+        Some(prefix) => format!("{}:{}", prefix, name.local_name),
+        None => name.local_name.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::errors::GpxError;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{read, Waypoint};
+
+    use super::TrackPointIter;
+
+    const SEGMENTS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<g:gpx xmlns:g="http://www.topografix.com/GPX/1/1" xmlns:x="urn:example" version="1.1">
+  <g:metadata><g:name>segments</g:name></g:metadata>
+  <g:wpt lat="1.0" lon="1.0"/>
+  <g:trk>
+    <g:name>first</g:name>
+    <g:trkseg>
+      <g:trkpt lat="1.0" lon="2.0"><g:ele>1</g:ele></g:trkpt>
+      <g:trkpt lat="1.1" lon="2.1"><g:extensions><x:hr>90</x:hr></g:extensions></g:trkpt>
+    </g:trkseg>
+    <g:trkseg>
+      <g:trkpt lat="1.2" lon="2.2"/><g:trkpt lat="1.3" lon="2.3"/>
+    </g:trkseg>
+  </g:trk>
+  <g:rte>
+    <g:name>planned</g:name>
+    <g:rtept lat="3.0" lon="4.0"><g:name>start</g:name></g:rtept>
+    <g:link href="https://example.com"/><g:rtept lat="3.1" lon="4.1"/>
+  </g:rte>
+  <g:rte/>
+  <g:trk>
+    <g:trkseg><g:trkpt lat="1.4" lon="2.4"/></g:trkseg>
+  </g:trk>
+</g:gpx>"#;
+
+    fn points(data: &[u8]) -> Vec<Waypoint<EmptyExtensions>> {
+        TrackPointIter::new(data).collect::<Result<_, _>>().unwrap()
+    }
+
+    /// Reads `take` points, then resumes from a checkpoint to read the rest.
+    fn resumed_points(data: &[u8], take: usize, routes: bool) -> Vec<Waypoint<EmptyExtensions>> {
+        let mut iter = TrackPointIter::<_, EmptyExtensions>::new(data);
+        if routes {
+            iter = iter.with_routes();
+        }
+        let mut points: Vec<_> = iter.by_ref().take(take).map(Result::unwrap).collect();
+        let checkpoint = iter.checkpoint();
+        drop(iter);
+
+        let rest = &data[checkpoint.offset() as usize..];
+        for point in TrackPointIter::resume(rest, &checkpoint) {
+            points.push(point.unwrap());
+        }
+        points
+    }
+
+    #[test]
+    fn stream_matches_read() {
+        let data = fs::read("tests/fixtures/garmin-activity.gpx").unwrap();
+        let gpx = read(data.as_slice()).unwrap();
+        let expected: Vec<_> = gpx.tracks[0].segments[0].points.clone();
+
+        assert_eq!(points(&data), expected);
+    }
+
+    #[test]
+    fn stream_resume_garmin_activity() {
+        let data = fs::read("tests/fixtures/garmin-activity.gpx").unwrap();
+        let expected = points(&data);
+        assert!(expected.len() > 100);
+
+        for &take in &[0, 1, 57, expected.len() - 1, expected.len()] {
+            assert_eq!(resumed_points(&data, take, false), expected, "take {}", take);
+        }
+    }
+
+    #[test]
+    fn stream_resume_across_segments() {
+        let data = SEGMENTS.as_bytes();
+        let expected = points(data);
+        assert_eq!(expected.len(), 5);
+
+        for take in 0..=expected.len() {
+            assert_eq!(resumed_points(data, take, false), expected, "take {}", take);
+        }
+    }
+
+    #[test]
+    fn stream_routes() {
+        let data = SEGMENTS.as_bytes();
+        let gpx = read(data).unwrap();
+        let mut iter = TrackPointIter::<_, EmptyExtensions>::new(data).with_routes();
+        let mut expected = points(data);
+        expected.splice(4..4, gpx.routes[0].points.iter().cloned());
+        let mut indices = Vec::new();
+        let mut read_points = Vec::new();
+        while let Some(point) = iter.next() {
+            read_points.push(point.unwrap());
+            indices.push((iter.route_index(), iter.track_index(), iter.segment_index()));
+        }
+        assert_eq!(read_points, expected);
+        assert_eq!(
+            indices,
+            [(None, 0, 0), (None, 0, 0), (None, 0, 1), (None, 0, 1), (Some(0), 1, 0), (Some(0), 1, 0), (None, 1, 0)]
+        );
+
+        for take in 0..=expected.len() {
+            assert_eq!(resumed_points(data, take, true), expected, "take {}", take);
+        }
+    }
+
+    #[test]
+    fn stream_indices() {
+        let mut iter = TrackPointIter::<_, EmptyExtensions>::new(SEGMENTS.as_bytes());
+        let mut indices = Vec::new();
+        while let Some(point) = iter.next() {
+            point.unwrap();
+            indices.push((iter.track_index(), iter.segment_index()));
+        }
+        assert_eq!(indices, [(0, 0), (0, 0), (0, 1), (0, 1), (1, 0)]);
+
+        // Resuming keeps counting where the checkpoint was taken.
+        let mut iter = TrackPointIter::<_, EmptyExtensions>::new(SEGMENTS.as_bytes());
+        iter.by_ref().take(3).for_each(drop);
+        let checkpoint = iter.checkpoint();
+        let mut iter = TrackPointIter::<_, EmptyExtensions>::resume(
+            &SEGMENTS.as_bytes()[checkpoint.offset() as usize..],
+            &checkpoint,
+        );
+        assert_eq!((iter.track_index(), iter.segment_index()), (0, 1));
+        iter.next().unwrap().unwrap();
+        assert_eq!((iter.track_index(), iter.segment_index()), (0, 1));
+        iter.next().unwrap().unwrap();
+        assert_eq!((iter.track_index(), iter.segment_index()), (1, 0));
+    }
+
+    #[test]
+    fn stream_resume_changed_input() {
+        let mut iter = TrackPointIter::<_, EmptyExtensions>::new(SEGMENTS.as_bytes());
+        iter.by_ref().take(3).for_each(drop);
+        let checkpoint = iter.checkpoint();
+
+        let changed = SEGMENTS.replace("lat=\"1.2\"", "lat=\"1.9\"");
+        let mut iter = TrackPointIter::<_, EmptyExtensions>::resume(
+            &changed.as_bytes()[checkpoint.offset() as usize..],
+            &checkpoint,
+        );
+        assert!(matches!(
+            iter.next(),
+            Some(Err(GpxError::CheckpointMismatch))
+        ));
+        assert!(iter.next().is_none());
+    }
+}
@@ -0,0 +1,81 @@
+//! style maps the track styles of the different GPX producers to one model.
+
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Track, TrackStyle};
+
+/// The colors a Garmin `DisplayColor` can name, with their RGB values.
+const GARMIN_COLORS: [(&str, &str); 16] = [
+    ("Black", "#000000"),
+    ("DarkRed", "#800000"),
+    ("DarkGreen", "#008000"),
+    ("DarkYellow", "#808000"),
+    ("DarkBlue", "#000080"),
+    ("DarkMagenta", "#800080"),
+    ("DarkCyan", "#008080"),
+    ("LightGray", "#C0C0C0"),
+    ("DarkGray", "#808080"),
+    ("Red", "#FF0000"),
+    ("Green", "#00FF00"),
+    ("Yellow", "#FFFF00"),
+    ("Blue", "#0000FF"),
+    ("Magenta", "#FF00FF"),
+    ("Cyan", "#00FFFF"),
+    ("White", "#FFFFFF"),
+];
+
+impl TrackStyle {
+    /// Gives the `#RRGGBB` color of a Garmin `DisplayColor` name, such as
+    /// `DarkRed`.
+    ///
+    /// ```
+    /// use gpx::TrackStyle;
+    ///
+    /// assert_eq!(TrackStyle::garmin_color("DarkRed"), Some("#800000"));
+    /// assert_eq!(TrackStyle::garmin_color("Transparent"), None);
+    /// ```
+    pub fn garmin_color(name: &str) -> Option<&'static str> {
+        GARMIN_COLORS
+            .iter()
+            .find(|(garmin, _)| *garmin == name)
+            .map(|&(_, hex)| hex)
+    }
+
+    /// Gives a gpx_style color, six hex digits, as `#RRGGBB`.
+    pub(crate) fn hex_color(color: &str) -> Option<String> {
+        let color = color.trim().trim_start_matches('#');
+        if color.len() == 6 && color.chars().all(|c| c.is_ascii_hexdigit()) {
+            Some(format!("#{}", color.to_ascii_uppercase()))
+        } else {
+            None
+        }
+    }
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Gives how the track is drawn, whichever extension it came from.
+    pub fn style(&self) -> Option<&TrackStyle> {
+        self.style.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TrackStyle;
+
+    #[test]
+    fn garmin_colors() {
+        assert_eq!(TrackStyle::garmin_color("Black"), Some("#000000"));
+        assert_eq!(TrackStyle::garmin_color("DarkYellow"), Some("#808000"));
+        assert_eq!(TrackStyle::garmin_color("Magenta"), Some("#FF00FF"));
+        assert_eq!(TrackStyle::garmin_color("magenta"), None);
+        assert_eq!(TrackStyle::garmin_color("Transparent"), None);
+    }
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(TrackStyle::hex_color("ff8000"), Some("#FF8000".into()));
+        assert_eq!(TrackStyle::hex_color(" #00ff00 "), Some("#00FF00".into()));
+        assert_eq!(TrackStyle::hex_color("red"), None);
+        assert_eq!(TrackStyle::hex_color("ff80001"), None);
+    }
+}
@@ -0,0 +1,307 @@
+//! tcx reads Garmin Training Center (TCX) activities into the GPX data model.
+//!
+//! Every `Activity` becomes a track, whose type is the sport, and every `Lap`
+//! a segment of it. The heart rate and cadence of trackpoints go into their
+//! [`GarminTrackPoint`] extensions, as Garmin writes them in GPX, and the
+//! `Id` of the first activity, its start time, becomes the time of the
+//! metadata. Trackpoints without a position, as devices write before they
+//! have a fix, are left out with a [`GpxWarning::SkippedPoint`].
+
+use std::io::Read;
+
+use geo_types::Point;
+use xml::reader::XmlEvent;
+
+use crate::errors::{GpxError, GpxResult, GpxWarning};
+use crate::parser::extensions::{GarminTrackPoint, GarminTrackPointExtensions, WaypointExtensions};
+use crate::parser::{create_tracked_context_with_options, skip_element, string, time, verify_starting_tag, Context};
+use crate::{Gpx, GpxVersion, Metadata, ParseOptions, ReadOutcome, Track, TrackSegment, Waypoint};
+
+/// Reads the activities of a TCX document.
+///
+/// ```
+/// let data = "<TrainingCenterDatabase><Activities><Activity Sport=\"Running\">
+///     <Id>2024-05-01T06:30:00Z</Id>
+///     <Lap StartTime=\"2024-05-01T06:30:00Z\"><Track><Trackpoint>
+///         <Time>2024-05-01T06:30:00Z</Time>
+///         <Position><LatitudeDegrees>47.37</LatitudeDegrees><LongitudeDegrees>8.54</LongitudeDegrees></Position>
+///         <HeartRateBpm><Value>121</Value></HeartRateBpm>
+///     </Trackpoint></Track></Lap>
+/// </Activity></Activities></TrainingCenterDatabase>";
+///
+/// let gpx = gpx::tcx::read(data.as_bytes()).unwrap();
+/// assert_eq!(gpx.tracks[0].type_.as_deref(), Some("Running"));
+/// assert_eq!(gpx.tracks[0].segments[0].points[0].extensions.heart_rate, Some(121));
+/// ```
+pub fn read<R: Read>(reader: R) -> GpxResult<Gpx<GarminTrackPointExtensions>> {
+    read_with_warnings(reader, Default::default()).map(|outcome| outcome.gpx)
+}
+
+/// Reads the activities of a TCX document like [`read`], using the given
+/// [`ParseOptions`], and gives them along with the trackpoints left out and
+/// the issues recovered from in lenient mode.
+pub fn read_with_warnings<R: Read>(
+    reader: R,
+    options: ParseOptions,
+) -> GpxResult<ReadOutcome<GarminTrackPointExtensions>> {
+    let mut context = create_tracked_context_with_options(reader, GpxVersion::Gpx11, options);
+    let gpx = consume_database(&mut context)?;
+    Ok(ReadOutcome {
+        gpx,
+        warnings: context.take_warnings(),
+    })
+}
+
+/// next_child gives the local name of the next child of `parent`, whose
+/// starting tag is peeked at, or `None` once the end of `parent` has been
+/// consumed. Other events are consumed and ignored.
+fn next_child<R: Read, E: WaypointExtensions + Default>(
+    context: &mut Context<R, E>,
+    parent: &'static str,
+) -> GpxResult<Option<String>> {
+    loop {
+        context.check_deadline()?;
+        match context.peek_ok(parent)? {
+            Some(XmlEvent::StartElement { name, .. }) => return Ok(Some(name.local_name.clone())),
+            Some(XmlEvent::EndElement { .. }) => {
+                context.reader().next(); //consume the end tag
+                return Ok(None);
+            }
+            Some(_) => {
+                context.reader().next(); //consume and ignore this event
+            }
+            None => return Err(GpxError::MissingClosingTag(parent)),
+        }
+    }
+}
+
+/// skip consumes the child of `parent` whose starting tag is peeked at.
+fn skip<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>, parent: &'static str) -> GpxResult<()> {
+    context.reader().next();
+    skip_element(context, parent)
+}
+
+fn consume_database<R: Read>(context: &mut Context<R, GarminTrackPointExtensions>) -> GpxResult<Gpx<GarminTrackPointExtensions>> {
+    verify_starting_tag(context, "TrainingCenterDatabase")?;
+    let mut gpx = Gpx {
+        version: GpxVersion::Gpx11,
+        ..Default::default()
+    };
+    while let Some(child) = next_child(context, "TrainingCenterDatabase")? {
+        match child.as_str() {
+            "Activities" => {
+                verify_starting_tag(context, "Activities")?;
+                while let Some(child) = next_child(context, "Activities")? {
+                    match child.as_str() {
+                        "Activity" => consume_activity(context, &mut gpx)?,
+                        _ => skip(context, "Activities")?,
+                    }
+                }
+            }
+            // Courses, workouts and the author are not activities
+            _ => skip(context, "TrainingCenterDatabase")?,
+        }
+    }
+    Ok(gpx)
+}
+
+/// consume_activity consumes an `Activity` into a track of `gpx`, taking its
+/// device for the creator and its start for the time of the metadata, unless
+/// an earlier activity gave them.
+fn consume_activity<R: Read>(
+    context: &mut Context<R, GarminTrackPointExtensions>,
+    gpx: &mut Gpx<GarminTrackPointExtensions>,
+) -> GpxResult<()> {
+    let mut attributes = verify_starting_tag(context, "Activity")?;
+    let mut track = Track::new();
+    track.type_ = attributes.take("Sport");
+
+    while let Some(child) = next_child(context, "Activity")? {
+        match child.as_str() {
+            "Id" => {
+                let id = string::consume(context, "Id", false)?;
+                let start = time::parse(context, &id)?;
+                let metadata = gpx.metadata.get_or_insert_with(Metadata::default);
+                metadata.time.get_or_insert(start);
+            }
+            "Lap" => track.segments.push(consume_lap(context)?),
+            "Notes" => track.description = Some(string::consume(context, "Notes", true)?),
+            "Creator" => {
+                verify_starting_tag(context, "Creator")?;
+                while let Some(child) = next_child(context, "Creator")? {
+                    match child.as_str() {
+                        "Name" if gpx.creator.is_none() => gpx.creator = Some(string::consume(context, "Name", false)?),
+                        _ => skip(context, "Creator")?,
+                    }
+                }
+            }
+            _ => skip(context, "Activity")?,
+        }
+    }
+    gpx.tracks.push(track);
+    Ok(())
+}
+
+/// consume_lap consumes a `Lap` into a segment, joining the points of its
+/// tracks, of which devices write a new one after every pause.
+fn consume_lap<R: Read>(context: &mut Context<R, GarminTrackPointExtensions>) -> GpxResult<TrackSegment<GarminTrackPointExtensions>> {
+    verify_starting_tag(context, "Lap")?;
+    let mut segment = TrackSegment::new();
+    context.reserve_points(&mut segment.points);
+    while let Some(child) = next_child(context, "Lap")? {
+        match child.as_str() {
+            "Track" => {
+                verify_starting_tag(context, "Track")?;
+                while let Some(child) = next_child(context, "Track")? {
+                    match child.as_str() {
+                        "Trackpoint" => {
+                            if let Some(point) = consume_trackpoint(context)? {
+                                context.keep_point(point, &mut segment.points)?;
+                            }
+                        }
+                        _ => skip(context, "Track")?,
+                    }
+                }
+            }
+            _ => skip(context, "Lap")?,
+        }
+    }
+    context.fit_points(&mut segment.points);
+    Ok(segment)
+}
+
+/// consume_trackpoint consumes a `Trackpoint`, giving `None` with a warning
+/// when it lacks a position.
+fn consume_trackpoint<R: Read>(
+    context: &mut Context<R, GarminTrackPointExtensions>,
+) -> GpxResult<Option<Waypoint<GarminTrackPointExtensions>>> {
+    verify_starting_tag(context, "Trackpoint")?;
+    let mut position: (Option<f64>, Option<f64>) = (None, None);
+    let mut time: Option<time::Time> = None;
+    let mut elevation: Option<f64> = None;
+    let mut speed: Option<f64> = None;
+    let mut extensions = GarminTrackPoint::default();
+    let mut run_cadence: Option<u32> = None;
+
+    while let Some(child) = next_child(context, "Trackpoint")? {
+        match child.as_str() {
+            "Time" => {
+                let value = string::consume(context, "Time", false)?;
+                time = Some(time::parse(context, &value)?);
+            }
+            "Position" => {
+                verify_starting_tag(context, "Position")?;
+                while let Some(child) = next_child(context, "Position")? {
+                    match child.as_str() {
+                        "LatitudeDegrees" => position.0 = Some(string::consume(context, "LatitudeDegrees", false)?.parse()?),
+                        "LongitudeDegrees" => position.1 = Some(string::consume(context, "LongitudeDegrees", false)?.parse()?),
+                        _ => skip(context, "Position")?,
+                    }
+                }
+            }
+            "AltitudeMeters" => elevation = Some(string::consume(context, "AltitudeMeters", false)?.parse()?),
+            "HeartRateBpm" => {
+                verify_starting_tag(context, "HeartRateBpm")?;
+                while let Some(child) = next_child(context, "HeartRateBpm")? {
+                    match child.as_str() {
+                        "Value" => extensions.heart_rate = Some(string::consume(context, "Value", false)?.parse()?),
+                        _ => skip(context, "HeartRateBpm")?,
+                    }
+                }
+            }
+            "Cadence" => extensions.cadence = Some(string::consume(context, "Cadence", false)?.parse()?),
+            // The activity extension of Garmin, holding the speed, and the
+            // cadence of runs.
+            "Extensions" => {
+                verify_starting_tag(context, "Extensions")?;
+                while let Some(child) = next_child(context, "Extensions")? {
+                    match child.as_str() {
+                        "TPX" => {
+                            verify_starting_tag(context, "TPX")?;
+                            while let Some(child) = next_child(context, "TPX")? {
+                                match child.as_str() {
+                                    "Speed" => speed = Some(string::consume(context, "Speed", false)?.parse()?),
+                                    "RunCadence" => {
+                                        run_cadence = Some(string::consume(context, "RunCadence", false)?.parse()?)
+                                    }
+                                    _ => skip(context, "TPX")?,
+                                }
+                            }
+                        }
+                        _ => skip(context, "Extensions")?,
+                    }
+                }
+            }
+            _ => skip(context, "Trackpoint")?,
+        }
+    }
+
+    let (latitude, longitude) = match position {
+        (Some(latitude), Some(longitude)) => (latitude, longitude),
+        (None, Some(_)) | (None, None) => return Ok(skipped(context, "LatitudeDegrees")),
+        (Some(_), None) => return Ok(skipped(context, "LongitudeDegrees")),
+    };
+    extensions.cadence = extensions.cadence.or(run_cadence);
+    let mut point = Waypoint::new(Point::new(longitude, latitude));
+    point.time = time;
+    point.elevation = elevation;
+    point.speed = speed;
+    point.extensions = extensions;
+    Ok(Some(point))
+}
+
+/// skipped warns that a trackpoint lacking its `missing` coordinate was left
+/// out.
+fn skipped<R: Read, T>(context: &mut Context<R, GarminTrackPointExtensions>, missing: &'static str) -> Option<T> {
+    context.warn(GpxWarning::SkippedPoint {
+        element: "Trackpoint",
+        missing,
+    });
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::{GpxError, GpxWarning};
+    use crate::{ParseLimits, ParseOptions};
+
+    use super::read_with_warnings;
+
+    #[test]
+    fn read_skips_points_without_position() {
+        let data = "<TrainingCenterDatabase><Activities><Activity Sport=\"Biking\"><Lap><Track>
+            <Trackpoint><Time>2024-05-01T06:30:00Z</Time><HeartRateBpm><Value>90</Value></HeartRateBpm></Trackpoint>
+            <Trackpoint><Position><LatitudeDegrees>1.5</LatitudeDegrees><LongitudeDegrees>2.5</LongitudeDegrees></Position><Cadence>80</Cadence></Trackpoint>
+        </Track></Lap></Activity></Activities></TrainingCenterDatabase>";
+        let outcome = read_with_warnings(data.as_bytes(), Default::default()).unwrap();
+
+        let points = &outcome.gpx.tracks[0].segments[0].points;
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].point().x(), 2.5);
+        assert_eq!(points[0].point().y(), 1.5);
+        assert_eq!(points[0].extensions.cadence, Some(80));
+        assert_eq!(outcome.warnings.len(), 1);
+        assert_eq!(
+            outcome.warnings[0].warning,
+            GpxWarning::SkippedPoint {
+                element: "Trackpoint",
+                missing: "LatitudeDegrees",
+            }
+        );
+    }
+
+    #[test]
+    fn read_limits_points() {
+        let data = include_str!("../tests/fixtures/two_laps.tcx");
+        let limited = |max_points| {
+            ParseOptions::strict().with_limits(ParseLimits {
+                max_points: Some(max_points),
+                ..Default::default()
+            })
+        };
+        let outcome = read_with_warnings(data.as_bytes(), limited(4)).unwrap();
+        assert_eq!(outcome.gpx.tracks[0].segments.iter().map(|segment| segment.points.len()).sum::<usize>(), 4);
+        let result = read_with_warnings(data.as_bytes(), limited(3));
+        assert!(matches!(result, Err(GpxError::LimitExceeded("points"))));
+    }
+}
@@ -0,0 +1,45 @@
+//! testing builds the points and segments the unit tests work on.
+
+use geo_types::Point;
+use time::{Duration, OffsetDateTime};
+
+use crate::parser::extensions::EmptyExtensions;
+use crate::{Time, TrackSegment, Waypoint};
+
+/// start gives the time the timed points of the tests count from.
+pub(crate) fn start() -> OffsetDateTime {
+    OffsetDateTime::from_unix_timestamp(1_600_000_000).unwrap()
+}
+
+/// time gives the time `seconds` after [`start`].
+pub(crate) fn time(seconds: i64) -> Time {
+    (start() + Duration::seconds(seconds)).into()
+}
+
+/// waypoint gives a point at longitude `x` on the equator, timed `seconds`
+/// after [`start`] if given.
+pub(crate) fn waypoint(x: f64, seconds: Option<i64>) -> Waypoint<EmptyExtensions> {
+    let mut point = Waypoint::new(Point::new(x, 0.0));
+    point.time = seconds.map(time);
+    point
+}
+
+/// segment gives a segment of untimed points at the given longitude and
+/// latitude pairs.
+pub(crate) fn segment(coordinates: &[(f64, f64)]) -> TrackSegment<EmptyExtensions> {
+    TrackSegment {
+        points: coordinates.iter().map(|&(x, y)| Waypoint::new(Point::new(x, y))).collect(),
+    }
+}
+
+/// timed_segment gives a segment of [`waypoint`]s.
+pub(crate) fn timed_segment(points: &[(f64, Option<i64>)]) -> TrackSegment<EmptyExtensions> {
+    TrackSegment {
+        points: points.iter().map(|&(x, seconds)| waypoint(x, seconds)).collect(),
+    }
+}
+
+/// xs gives the longitudes of the points of `segment`.
+pub(crate) fn xs(segment: &TrackSegment<EmptyExtensions>) -> Vec<f64> {
+    segment.points.iter().map(|point| point.point().x()).collect()
+}
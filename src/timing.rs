@@ -0,0 +1,207 @@
+//! timing assigns timestamps to points that lack them, and gives the time
+//! spanned by points that have them.
+
+use std::convert::TryFrom;
+
+use time::{Duration, OffsetDateTime};
+
+use crate::distance::haversine_distance;
+use crate::errors::{GpxError, GpxResult};
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Track, TrackSegment, Waypoint};
+
+/// How [`TrackSegment::assign_times`] spaces the timestamps it assigns.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimingStrategy {
+    /// Every point is the given duration after the previous one.
+    FixedInterval(Duration),
+    /// Points are timed as if travelled at the given speed, in meters per
+    /// second, along the haversine distance between them. Must be positive.
+    ConstantSpeed(f64),
+}
+
+/// Assigns times to `points` as one continuous sequence starting at `start`.
+///
+/// All times are worked out before any is assigned, so the points are left
+/// unchanged when one is out of range.
+fn assign_times<'a, E, I>(points: I, start: OffsetDateTime, strategy: TimingStrategy, overwrite: bool) -> GpxResult<()>
+where
+    E: WaypointExtensions + Default + 'a,
+    I: Iterator<Item = &'a mut Waypoint<E>>,
+{
+    if let TimingStrategy::ConstantSpeed(mps) = strategy {
+        if mps.is_nan() || mps <= 0.0 {
+            return Err(GpxError::InvalidSpeed(mps));
+        }
+    }
+    let mut points: Vec<_> = points.collect();
+    let mut times = Vec::with_capacity(points.len());
+    let mut distance = 0.0;
+    for (index, point) in points.iter().enumerate() {
+        let elapsed = match strategy {
+            TimingStrategy::FixedInterval(interval) => times_interval(interval, index),
+            TimingStrategy::ConstantSpeed(mps) => {
+                if index > 0 {
+                    distance += haversine_distance(points[index - 1].point(), point.point());
+                }
+                Duration::checked_seconds_f64(distance / mps)
+            }
+        };
+        let time = elapsed.and_then(|elapsed| start.checked_add(elapsed));
+        times.push(time.ok_or(GpxError::TimeOutOfRange(index))?);
+    }
+    for (point, time) in points.iter_mut().zip(times) {
+        if overwrite || point.time.is_none() {
+            point.time = Some(time.into());
+        }
+    }
+    Ok(())
+}
+
+/// times_interval gives `interval` taken `count` times, or `None` when that is
+/// beyond what a [`Duration`] holds.
+fn times_interval(interval: Duration, count: usize) -> Option<Duration> {
+    let count = i64::try_from(count).ok()?;
+    let seconds = interval.whole_seconds().checked_mul(count)?;
+    let nanoseconds = i64::from(interval.subsec_nanoseconds()).checked_mul(count)?;
+    Duration::seconds(seconds).checked_add(Duration::nanoseconds(nanoseconds))
+}
+
+impl<E: WaypointExtensions + Default> TrackSegment<E> {
+    /// Assigns a timestamp to every point, the first one at `start` and the
+    /// following ones according to `strategy`.
+    ///
+    /// Points that already have a time keep it unless `overwrite` is set.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`GpxError::TimeOutOfRange`], leaving the points unchanged,
+    /// when a time would be beyond the range of [`OffsetDateTime`], and with
+    /// [`GpxError::InvalidSpeed`] when the strategy is
+    /// [`TimingStrategy::ConstantSpeed`] with a speed that is not positive.
+    pub fn assign_times(&mut self, start: OffsetDateTime, strategy: TimingStrategy, overwrite: bool) -> GpxResult<()> {
+        assign_times(self.points.iter_mut(), start, strategy, overwrite)
+    }
+
+    /// Gives the earliest and latest times of the points, or `None` when no
+    /// point has a time.
+    pub fn time_span(&self) -> Option<(OffsetDateTime, OffsetDateTime)> {
+        let mut times = self.points.iter().filter_map(|point| point.time).map(OffsetDateTime::from);
+        let first = times.next()?;
+        Some(times.fold((first, first), |(earliest, latest), time| {
+            (earliest.min(time), latest.max(time))
+        }))
+    }
+
+    /// Gives the time between the earliest and latest times of the points,
+    /// or `None` when no point has a time.
+    pub fn duration(&self) -> Option<Duration> {
+        self.time_span().map(|(earliest, latest)| latest - earliest)
+    }
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Assigns timestamps to the points of all segments like
+    /// [`TrackSegment::assign_times`], continuing from one segment to the next
+    /// as if they formed a single segment.
+    pub fn assign_times(&mut self, start: OffsetDateTime, strategy: TimingStrategy, overwrite: bool) -> GpxResult<()> {
+        let points = self.segments.iter_mut().flat_map(|segment| segment.points.iter_mut());
+        assign_times(points, start, strategy, overwrite)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+    use time::Duration;
+
+    use crate::distance::haversine_distance;
+    use crate::errors::GpxError;
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::testing::{segment, start};
+    use crate::Track;
+
+    use super::TimingStrategy;
+
+    #[test]
+    fn assign_times_fixed_interval() {
+        let mut segment = segment(&[(0.0, 0.0), (0.1, 0.0), (0.5, 0.2), (0.6, 0.2)]);
+        segment.assign_times(start(), TimingStrategy::FixedInterval(Duration::seconds(5)), false).unwrap();
+
+        for (index, point) in segment.points.iter().enumerate() {
+            let expect = start() + Duration::seconds(5 * index as i64);
+            assert_eq!(point.time, Some(expect.into()));
+        }
+    }
+
+    #[test]
+    fn assign_times_constant_speed() {
+        let mut segment = segment(&[(0.0, 0.0), (0.001, 0.0), (0.003, 0.002), (0.004, 0.002)]);
+        segment.assign_times(start(), TimingStrategy::ConstantSpeed(4.0), false).unwrap();
+
+        assert_eq!(segment.points[0].time, Some(start().into()));
+        for pair in segment.points.windows(2) {
+            let distance = haversine_distance(pair[0].point(), pair[1].point());
+            let seconds = (pair[1].time.unwrap() - pair[0].time.unwrap()).as_seconds_f64();
+            assert_approx_eq!(distance / seconds, 4.0, 1e-6);
+        }
+    }
+
+    #[test]
+    fn assign_times_keeps_existing_times() {
+        let mut segment = segment(&[(0.0, 0.0), (0.1, 0.0)]);
+        let existing = (start() - Duration::days(1)).into();
+        segment.points[1].time = Some(existing);
+
+        let strategy = TimingStrategy::FixedInterval(Duration::seconds(1));
+        segment.assign_times(start(), strategy, false).unwrap();
+        assert_eq!(segment.points[1].time, Some(existing));
+
+        segment.assign_times(start(), strategy, true).unwrap();
+        assert_eq!(segment.points[1].time, Some((start() + Duration::seconds(1)).into()));
+    }
+
+    #[test]
+    fn track_assign_times_continues_across_segments() {
+        let mut track: Track<EmptyExtensions> = Track::new();
+        track.segments.push(segment(&[(0.0, 0.0), (0.1, 0.0)]));
+        track.segments.push(segment(&[(0.2, 0.0), (0.3, 0.0)]));
+        track.assign_times(start(), TimingStrategy::FixedInterval(Duration::seconds(2)), false).unwrap();
+
+        let last = track.segments[1].points[1].time.unwrap();
+        assert_eq!(last, (start() + Duration::seconds(6)).into());
+    }
+
+    #[test]
+    fn assign_times_out_of_range() {
+        let mut segment = segment(&[(0.0, 0.0), (0.1, 0.0), (0.2, 0.0)]);
+        let interval = TimingStrategy::FixedInterval(Duration::MAX);
+        assert!(matches!(segment.assign_times(start(), interval, false), Err(GpxError::TimeOutOfRange(1))));
+        assert!(segment.points.iter().all(|point| point.time.is_none()));
+
+        // Past the year 9999 at the third point only.
+        let interval = TimingStrategy::FixedInterval(Duration::days(2_000_000));
+        assert!(matches!(segment.assign_times(start(), interval, false), Err(GpxError::TimeOutOfRange(2))));
+        assert!(segment.points.iter().all(|point| point.time.is_none()));
+
+        let speed = TimingStrategy::ConstantSpeed(f64::MIN_POSITIVE);
+        assert!(matches!(segment.assign_times(start(), speed, false), Err(GpxError::TimeOutOfRange(1))));
+    }
+
+    #[test]
+    fn assign_times_invalid_speed() {
+        let mut segment = segment(&[(0.0, 0.0), (0.1, 0.0)]);
+        for mps in [0.0, -1.0] {
+            let speed = TimingStrategy::ConstantSpeed(mps);
+            assert!(matches!(segment.assign_times(start(), speed, false), Err(GpxError::InvalidSpeed(got)) if got == mps));
+        }
+        let speed = TimingStrategy::ConstantSpeed(f64::NAN);
+        assert!(matches!(segment.assign_times(start(), speed, false), Err(GpxError::InvalidSpeed(got)) if got.is_nan()));
+        assert!(segment.points.iter().all(|point| point.time.is_none()));
+
+        let mut track: Track<EmptyExtensions> = Track::new();
+        track.segments.push(segment);
+        let speed = TimingStrategy::ConstantSpeed(0.0);
+        assert!(matches!(track.assign_times(start(), speed, false), Err(GpxError::InvalidSpeed(_))));
+    }
+}
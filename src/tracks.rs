@@ -0,0 +1,372 @@
+//! tracks reads the tracks of a GPX document one at a time, without keeping
+//! the others in memory.
+
+
+use std::io::Read;
+
+use xml::reader::XmlEvent;
+
+use crate::errors::{GpxError, GpxResult, ReadWarning};
+use crate::parser::extensions::{EmptyExtensions, WaypointExtensions};
+use crate::parser::gpx::{consume_start, Gpx10Metadata};
+use crate::parser::{
+    create_tracked_context_with_options, metadata, route, track, unknown_element, waypoint, Context, TrackingReader,
+};
+use crate::reader::ParseOptions;
+use crate::{GpxVersion, Metadata, Route, Track, Waypoint};
+
+/// An item of a GPX document, as read one at a time by
+/// [`pipeline::spawn_reader`](crate::pipeline::spawn_reader).
+#[derive(Clone, Debug, PartialEq)]
+pub enum GpxItem<E: WaypointExtensions + Default> {
+    /// What comes first: the version and creator of the document, the
+    /// namespaces and schema locations of its `gpx` element, and its
+    /// metadata.
+    Header {
+        version: GpxVersion,
+        creator: Option<String>,
+        metadata: Option<Metadata>,
+        namespaces: Vec<(String, String)>,
+        schema_locations: Vec<(String, String)>,
+    },
+    Waypoint(Waypoint<E>),
+    Route(Route<E>),
+    Track(Track<E>),
+}
+
+/// Reads the tracks of `reader` one at a time, parsing what comes before the
+/// first of them up front.
+///
+/// See [`GpxTrackIter`].
+pub fn tracks<R: Read, E: WaypointExtensions + Default + 'static>(reader: R) -> GpxResult<GpxTrackIter<R, E>> {
+    GpxTrackIter::with_options(reader, Default::default())
+}
+
+/// GpxTrackIter reads the tracks of a GPX document one at a time, as fully
+/// parsed [`Track`] values, so the tracks of a bulk export need not all be in
+/// memory at once.
+///
+/// The metadata is parsed when the iterator is made. The waypoints and routes
+/// are kept as they are read, so those between tracks are only there once the
+/// tracks before them were. Dropping the iterator stops reading.
+///
+/// As there is never a whole document, [`ParseOptions::post_normalize`] is
+/// not applied, and comments are left out.
+///
+/// ```
+/// use gpx::parser::extensions::EmptyExtensions;
+/// use gpx::GpxTrackIter;
+///
+/// let data = "<gpx version=\"1.1\">
+///     <metadata><name>rides</name></metadata>
+///     <trk><trkseg><trkpt lat=\"1.0\" lon=\"2.0\"/></trkseg></trk>
+///     <wpt lat=\"1.5\" lon=\"2.5\"/>
+///     <trk><trkseg><trkpt lat=\"1.0\" lon=\"2.0\"/><trkpt lat=\"1.5\" lon=\"2.5\"/></trkseg></trk>
+/// </gpx>";
+///
+/// let mut tracks: GpxTrackIter<_, EmptyExtensions> = gpx::tracks(data.as_bytes()).unwrap();
+/// assert_eq!(tracks.metadata().unwrap().name.as_deref(), Some("rides"));
+///
+/// let points: usize = tracks.by_ref().map(|track| track.unwrap().points_count()).sum();
+/// assert_eq!(points, 3);
+/// assert_eq!(tracks.waypoints().len(), 1);
+/// ```
+pub struct GpxTrackIter<R: Read, E: WaypointExtensions + Default + 'static> {
+    context: Context<'static, TrackingReader<R>, E>,
+    creator: Option<String>,
+    metadata: Option<Metadata>,
+    namespaces: Vec<(String, String)>,
+    schema_locations: Vec<(String, String)>,
+    waypoints: Vec<Waypoint<E>>,
+    routes: Vec<Route<E>>,
+    done: bool,
+}
+
+impl<R: Read, E: WaypointExtensions + Default + 'static> GpxTrackIter<R, E> {
+    /// Reads the tracks of `reader` using the given [`ParseOptions`], parsing
+    /// what comes before the first of them up front.
+    pub fn with_options(reader: R, options: ParseOptions) -> GpxResult<GpxTrackIter<R, E>> {
+        let mut context = create_tracked_context_with_options(reader, GpxVersion::Unknown, options);
+        let start = consume_start(&mut context, &mut Vec::new())?;
+        let mut tracks = GpxTrackIter {
+            context,
+            creator: start.creator,
+            metadata: None,
+            namespaces: start.namespaces,
+            schema_locations: start.schema_locations,
+            waypoints: Vec::new(),
+            routes: Vec::new(),
+            done: false,
+        };
+        tracks.start()?;
+        Ok(tracks)
+    }
+
+    /// The version of the document.
+    pub fn version(&self) -> GpxVersion {
+        self.context.version()
+    }
+
+    /// The creator of the document.
+    pub fn creator(&self) -> Option<&str> {
+        self.creator.as_deref()
+    }
+
+    /// The metadata of the document.
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// The namespaces declared on the `gpx` element, as in
+    /// [`Gpx::namespaces`](crate::Gpx::namespaces).
+    pub fn namespaces(&self) -> &[(String, String)] {
+        &self.namespaces
+    }
+
+    /// The schema locations of the `gpx` element, as in
+    /// [`Gpx::schema_locations`](crate::Gpx::schema_locations).
+    pub fn schema_locations(&self) -> &[(String, String)] {
+        &self.schema_locations
+    }
+
+    /// The waypoints read so far.
+    pub fn waypoints(&self) -> &[Waypoint<E>] {
+        &self.waypoints
+    }
+
+    /// The routes read so far.
+    pub fn routes(&self) -> &[Route<E>] {
+        &self.routes
+    }
+
+    /// Gives the warnings recorded so far in lenient mode.
+    pub fn warnings(&self) -> &[ReadWarning] {
+        self.context.warnings()
+    }
+
+    /// start reads up to the first track, which is where the metadata of a
+    /// GPX 1.0 document ends.
+    fn start(&mut self) -> GpxResult<()> {
+        let mut gpx10_metadata = Gpx10Metadata::default();
+        loop {
+            match self.peek()? {
+                Some(Some(child)) if child == "trk" => break,
+                Some(Some(child)) if child == "metadata" && self.version() != GpxVersion::Gpx10 => {
+                    self.metadata = Some(metadata::consume(&mut self.context)?);
+                }
+                Some(Some(child)) => {
+                    if !gpx10_metadata.consume(&mut self.context, &child)? {
+                        if let Some(item) = self.consume_other(child)? {
+                            self.keep(item);
+                        }
+                    }
+                }
+                Some(None) => break,
+                None => {
+                    self.context.reader().next(); //consume and ignore this event
+                }
+            }
+        }
+        if self.version() == GpxVersion::Gpx10 {
+            self.metadata = gpx10_metadata.into_metadata();
+        }
+        Ok(())
+    }
+
+    /// Peeks at the next child of the `gpx` element, giving the local name
+    /// of a starting tag, or `None` for an ending tag.
+    fn peek(&mut self) -> GpxResult<Option<Option<String>>> {
+        self.context.check_deadline()?;
+        match self.context.peek_ok("gpx")? {
+            Some(XmlEvent::StartElement { name, .. }) => Ok(Some(Some(name.local_name.clone()))),
+            Some(XmlEvent::EndElement { .. }) => Ok(Some(None)),
+            Some(_) => Ok(None),
+            None => Err(GpxError::MissingClosingTag("gpx")),
+        }
+    }
+
+    /// consume_other consumes a child of the `gpx` element other than a
+    /// track or the metadata, giving it when it is a waypoint or a route.
+    fn consume_other(&mut self, child: String) -> GpxResult<Option<GpxItem<E>>> {
+        match child.as_str() {
+            "rte" => return Ok(Some(GpxItem::Route(route::consume(&mut self.context)?))),
+            "wpt" => {
+                let point = waypoint::consume_or_skip(&mut self.context, "wpt")?;
+                if point.is_some() {
+                    self.context.count_point()?;
+                }
+                return Ok(point.map(GpxItem::Waypoint));
+            }
+            "extensions" => EmptyExtensions::consume(&mut self.context)?,
+            _ => unknown_element(&mut self.context, child, "gpx")?,
+        }
+        Ok(None)
+    }
+
+    /// keep keeps a waypoint or route read on the way to a track.
+    fn keep(&mut self, item: GpxItem<E>) {
+        match item {
+            GpxItem::Waypoint(point) => self.waypoints.push(point),
+            GpxItem::Route(route) => self.routes.push(route),
+            GpxItem::Header { .. } | GpxItem::Track(_) => {}
+        }
+    }
+
+    /// take_start takes what was read up front as items, the header first,
+    /// leaving the iterator without them.
+    pub(crate) fn take_start(&mut self) -> Vec<GpxItem<E>> {
+        let header = GpxItem::Header {
+            version: self.version(),
+            creator: self.creator.take(),
+            metadata: self.metadata.take(),
+            namespaces: std::mem::take(&mut self.namespaces),
+            schema_locations: std::mem::take(&mut self.schema_locations),
+        };
+        let waypoints = self.waypoints.drain(..).map(GpxItem::Waypoint);
+        let routes = self.routes.drain(..).map(GpxItem::Route);
+        std::iter::once(header).chain(waypoints).chain(routes).collect()
+    }
+
+    /// Reads the next waypoint, route or track, giving `None` at the end of
+    /// the document.
+    pub(crate) fn next_item(&mut self) -> GpxResult<Option<GpxItem<E>>> {
+        loop {
+            match self.peek()? {
+                Some(Some(child)) if child == "trk" => {
+                    return Ok(Some(GpxItem::Track(track::consume(&mut self.context)?)));
+                }
+                Some(Some(child)) => {
+                    if let Some(item) = self.consume_other(child)? {
+                        return Ok(Some(item));
+                    }
+                }
+                Some(None) => {
+                    if let Some(Ok(XmlEvent::EndElement { name })) = self.context.reader().next() {
+                        if name.local_name != "gpx" {
+                            return Err(GpxError::InvalidClosingTag(name.local_name, "gpx"));
+                        }
+                    }
+                    return Ok(None);
+                }
+                None => {
+                    self.context.reader().next(); //consume and ignore this event
+                }
+            }
+        }
+    }
+
+    /// Reads up to the next track, giving `None` at the end of the document.
+    fn advance(&mut self) -> GpxResult<Option<Track<E>>> {
+        loop {
+            match self.next_item()? {
+                Some(GpxItem::Track(track)) => return Ok(Some(track)),
+                Some(item) => self.keep(item),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+impl<R: Read, E: WaypointExtensions + Default + 'static> Iterator for GpxTrackIter<R, E> {
+    type Item = GpxResult<Track<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.advance() {
+            Ok(Some(track)) => Some(Ok(track)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::io::Read;
+    use std::rc::Rc;
+
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{read, GpxVersion};
+
+    use super::{tracks, GpxTrackIter};
+
+    /// CountingReader counts the bytes read through it.
+    struct CountingReader<'a> {
+        inner: &'a [u8],
+        read: Rc<Cell<usize>>,
+    }
+
+    impl Read for CountingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let read = self.inner.read(buf)?;
+            self.read.set(self.read.get() + read);
+            Ok(read)
+        }
+    }
+
+    #[test]
+    fn tracks_as_read() {
+        let data = r#"<gpx version="1.0" creator="test" xmlns="http://www.topografix.com/GPX/1/0"
+                xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+                xsi:schemaLocation="http://www.topografix.com/GPX/1/0 http://www.topografix.com/GPX/1/0/gpx.xsd">
+            <name>old</name>
+            <time>2020-01-01T00:00:00Z</time>
+            <wpt lat="1" lon="2"/>
+            <trk><trkseg><trkpt lat="1" lon="2"/></trkseg></trk>
+            <rte><rtept lat="1" lon="2"/></rte>
+            <trk><name>second</name></trk>
+            <wpt lat="3" lon="4"/>
+        </gpx>"#;
+        let gpx = read(data.as_bytes()).unwrap();
+
+        let mut iter: GpxTrackIter<_, EmptyExtensions> = tracks(data.as_bytes()).unwrap();
+        assert_eq!(iter.version(), GpxVersion::Gpx10);
+        assert_eq!(iter.creator(), Some("test"));
+        assert_eq!(iter.metadata(), gpx.metadata.as_ref());
+        assert_eq!(iter.namespaces(), gpx.namespaces.as_slice());
+        assert_eq!(iter.schema_locations(), gpx.schema_locations.as_slice());
+        assert_eq!(iter.schema_locations().len(), 1);
+        assert_eq!(iter.waypoints(), &gpx.waypoints[..1]);
+
+        let read_tracks: Vec<_> = iter.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(read_tracks, gpx.tracks);
+        assert_eq!(iter.waypoints(), gpx.waypoints.as_slice());
+        assert_eq!(iter.routes(), gpx.routes.as_slice());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn tracks_fail_once() {
+        let data = "<gpx version=\"1.1\"><trk><trkseg></trk></gpx>";
+        let mut iter = tracks::<_, EmptyExtensions>(data.as_bytes()).unwrap();
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+
+        assert!(tracks::<_, EmptyExtensions>("<gpx></gpx>".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn tracks_read_no_further_than_asked() {
+        let track = "<trk><trkseg><trkpt lat=\"1\" lon=\"2\"/><trkpt lat=\"3\" lon=\"4\"/></trkseg></trk>";
+        let data = format!("<gpx version=\"1.1\">{}</gpx>", track.repeat(2000));
+        let read = Rc::new(Cell::new(0));
+        let reader = CountingReader {
+            inner: data.as_bytes(),
+            read: read.clone(),
+        };
+
+        let mut iter = tracks::<_, EmptyExtensions>(reader).unwrap();
+        assert_eq!(iter.next().unwrap().unwrap().points_count(), 2);
+        drop(iter);
+        assert!(read.get() < data.len() / 10, "read {} of {}", read.get(), data.len());
+    }
+}
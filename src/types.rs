@@ -1,9 +1,11 @@
 //! generic types for GPX
 
-use geo_types::{Geometry, LineString, MultiLineString, Point, Rect};
+use geo_types::{Geometry, GeometryCollection, LineString, MultiLineString, Point, Rect};
 #[cfg(feature = "use-serde")]
 use serde::{Deserialize, Serialize};
+use time::Duration;
 
+use crate::errors::{GpxError, GpxResult};
 use crate::parser::extensions::WaypointExtensions;
 pub use crate::parser::time::Time;
 
@@ -18,6 +20,39 @@ pub enum GpxVersion {
     Gpx11,
 }
 
+impl GpxVersion {
+    /// Gives the version as written in the `version` attribute of documents,
+    /// "1.0" or "1.1", or `None` for an unknown version.
+    ///
+    /// ```
+    /// use gpx::GpxVersion;
+    ///
+    /// assert_eq!(GpxVersion::Gpx11.as_str(), Some("1.1"));
+    /// assert_eq!("1.0".parse::<GpxVersion>().unwrap(), GpxVersion::Gpx10);
+    /// ```
+    pub fn as_str(&self) -> Option<&'static str> {
+        match self {
+            GpxVersion::Gpx10 => Some("1.0"),
+            GpxVersion::Gpx11 => Some("1.1"),
+            GpxVersion::Unknown => None,
+        }
+    }
+}
+
+/// Parses the `version` attribute of documents, failing with a
+/// [`GpxError::UnknownVersionError`] for versions other than "1.0" and "1.1".
+impl std::str::FromStr for GpxVersion {
+    type Err = GpxError;
+
+    fn from_str(version: &str) -> Result<GpxVersion, GpxError> {
+        match version {
+            "1.0" => Ok(GpxVersion::Gpx10),
+            "1.1" => Ok(GpxVersion::Gpx11),
+            _ => Err(GpxError::UnknownVersionError(GpxVersion::Unknown)),
+        }
+    }
+}
+
 impl std::fmt::Display for GpxVersion {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self)
@@ -45,6 +80,128 @@ pub struct Gpx<E: WaypointExtensions + Default> {
 
     /// A list of routes with a list of point-by-point directions
     pub routes: Vec<Route<E>>,
+
+    /// Comments outside of the elements of the file, before the gpx element
+    /// and between its children, where some tools leave notes on where the
+    /// file came from. Only read with
+    /// [`ParseOptions::keep_comments`](crate::ParseOptions::keep_comments),
+    /// and never written.
+    pub comments: Vec<String>,
+
+    /// The namespaces declared on the gpx element, as prefix and URI pairs
+    /// ordered by prefix, the default namespace having an empty prefix.
+    /// Tells which extension schemas the document uses. All but the default
+    /// namespace, which is the one of the version, are written back.
+    pub namespaces: Vec<(String, String)>,
+
+    /// The `xsi:schemaLocation` of the gpx element, as namespace and schema
+    /// location pairs in document order.
+    pub schema_locations: Vec<(String, String)>,
+}
+
+impl<E: WaypointExtensions + Default> Gpx<E> {
+    /// Gives how many points the document has: its waypoints, the points of
+    /// its routes and the points of its tracks.
+    pub fn points_count(&self) -> usize {
+        self.waypoints.len()
+            + self.routes.iter().map(Route::points_count).sum::<usize>()
+            + self.tracks.iter().map(Track::points_count).sum::<usize>()
+    }
+
+    /// Gives every point of the document, the ones [`Gpx::points_count`]
+    /// counts: its waypoints, then the points of its routes, then the points
+    /// of its tracks.
+    pub fn iter_points(&self) -> impl Iterator<Item = &Waypoint<E>> {
+        let routes = self.routes.iter().flat_map(|route| &route.points);
+        let tracks = self.tracks.iter().flat_map(|track| &track.segments).flat_map(|segment| &segment.points);
+        self.waypoints.iter().chain(routes).chain(tracks)
+    }
+
+    /// Computes the bounds of every point of the document, see
+    /// [`Gpx::iter_points`], or `None` without points. Unlike
+    /// [`Metadata::bounds`], which is as the document gives it, these hold
+    /// what it holds.
+    ///
+    /// ```
+    /// use geo_types::coord;
+    /// use gpx::{Gpx, Route, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut gpx: Gpx<EmptyExtensions> = Gpx::default();
+    /// assert_eq!(gpx.compute_bounds(), None);
+    ///
+    /// let mut route = Route::new();
+    /// route.points.push(Waypoint::from_lat_lon(47.37, 8.54));
+    /// route.points.push(Waypoint::from_lat_lon(46.95, 7.45));
+    /// gpx.routes.push(route);
+    /// let bounds = gpx.compute_bounds().unwrap();
+    /// assert_eq!(bounds.min(), coord! { x: 7.45, y: 46.95 });
+    /// assert_eq!(bounds.max(), coord! { x: 8.54, y: 47.37 });
+    /// ```
+    pub fn compute_bounds(&self) -> Option<Rect<f64>> {
+        let mut points = self.iter_points().map(Waypoint::point);
+        let first = points.next()?;
+        let (min, max) = points.fold((first, first), |(min, max), point| {
+            (
+                Point::new(min.x().min(point.x()), min.y().min(point.y())),
+                Point::new(max.x().max(point.x()), max.y().max(point.y())),
+            )
+        });
+        Some(Rect::new(min.0, max.0))
+    }
+
+    /// Gives the tracks of the document, to edit in place.
+    pub fn tracks_mut(&mut self) -> impl Iterator<Item = &mut Track<E>> {
+        self.tracks.iter_mut()
+    }
+
+    /// Gives the segments of every track of the document in order, to edit
+    /// in place.
+    pub fn segments_mut(&mut self) -> impl Iterator<Item = &mut TrackSegment<E>> {
+        self.tracks.iter_mut().flat_map(|track| &mut track.segments)
+    }
+
+    /// Calls `f` with every segment of every track of the document in
+    /// order, such as to smooth them in place.
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{Gpx, Track, TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut segment = TrackSegment::new();
+    /// segment.points.push(Waypoint::new(Point::new(8.54, 47.37)));
+    /// let mut track = Track::new();
+    /// track.segments.push(segment);
+    /// let mut gpx: Gpx<EmptyExtensions> = Gpx::default();
+    /// gpx.tracks.push(track);
+    ///
+    /// gpx.for_each_segment_mut(|segment| segment.points.clear());
+    /// assert_eq!(gpx.points_count(), 0);
+    /// ```
+    pub fn for_each_segment_mut<F: FnMut(&mut TrackSegment<E>)>(&mut self, f: F) {
+        self.segments_mut().for_each(f)
+    }
+}
+
+/// Collects the whole document into one collection: a multi-linestring per
+/// track, then a linestring per route, then a point per waypoint.
+impl<E: WaypointExtensions + Default> From<&Gpx<E>> for GeometryCollection<f64> {
+    fn from(gpx: &Gpx<E>) -> GeometryCollection<f64> {
+        let tracks = gpx
+            .tracks
+            .iter()
+            .map(|track| Geometry::MultiLineString(track.multilinestring()));
+        let routes = gpx
+            .routes
+            .iter()
+            .map(|route| Geometry::LineString(route.linestring()));
+        let waypoints = gpx
+            .waypoints
+            .iter()
+            .map(|waypoint| Geometry::Point(waypoint.point()));
+        tracks.chain(routes).chain(waypoints).collect()
+    }
 }
 
 /// Information about the copyright holder and any license governing use of this file.
@@ -93,6 +250,27 @@ pub struct Metadata {
     /*extensions: GpxExtensionsType,*/
 }
 
+impl Metadata {
+    /// Gives the creation time of the file in seconds since the unix epoch,
+    /// see [`Time::unix`].
+    pub fn unix_time(&self) -> Option<i64> {
+        self.time.map(|time| time.unix())
+    }
+
+    /// Gives the creation time of the file in milliseconds since the unix
+    /// epoch, see [`Time::unix_millis`].
+    pub fn unix_time_millis(&self) -> Option<i64> {
+        self.time.map(|time| time.unix_millis())
+    }
+
+    /// Sets the creation time of the file to `seconds` since the unix epoch,
+    /// failing like [`Time::from_unix`] and leaving the time as it was.
+    pub fn set_unix_time(&mut self, seconds: i64) -> GpxResult<()> {
+        self.time = Some(Time::from_unix(seconds)?);
+        Ok(())
+    }
+}
+
 /// Route represents an ordered list of waypoints representing a series of turn points leading to a destination.
 #[derive(Clone, Default, Debug, PartialEq)]
 #[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
@@ -125,12 +303,42 @@ pub struct Route<E: WaypointExtensions + Default> {
 }
 
 impl<E: WaypointExtensions + Default> Route<E> {
+    /// Gives how many points the route has.
+    pub fn points_count(&self) -> usize {
+        self.points.len()
+    }
+
     /// Gives the linestring of the segment's points, the sequence of points that
     /// comprises the track segment.
+    ///
+    /// The linestring is 2D, as the coordinates of `geo-types` have no
+    /// elevation. [`coords_3d`](Self::coords_3d) gives the points with their
+    /// elevation, and [`length_3d_meters`](Self::length_3d_meters) the length
+    /// including it.
     pub fn linestring(&self) -> LineString<f64> {
         self.points.iter().map(|wpt| wpt.point()).collect()
     }
 
+    /// Gives the coordinates of the route's points with their elevation, as
+    /// `[longitude, latitude, elevation]`, see [`Waypoint::coord_3d`].
+    pub fn coords_3d(&self, missing_elevation: f64) -> Vec<[f64; 3]> {
+        self.points.iter().map(|wpt| wpt.coord_3d(missing_elevation)).collect()
+    }
+
+    /// Gives a copy of the route's own fields, without any points.
+    pub(crate) fn without_points(&self) -> Route<E> {
+        Route {
+            name: self.name.clone(),
+            comment: self.comment.clone(),
+            description: self.description.clone(),
+            source: self.source.clone(),
+            links: self.links.clone(),
+            number: self.number,
+            type_: self.type_.clone(),
+            points: Vec::new(),
+        }
+    }
+
     /// Creates a new Route with default values.
     ///
     /// ```
@@ -184,6 +392,9 @@ pub struct Track<E: WaypointExtensions + Default> {
     /// GPS number of track
     pub number: Option<u32>,
 
+    /// How the track is drawn, from its `gpx_style` or Garmin extensions.
+    pub style: Option<TrackStyle>,
+
     /// A Track Segment holds a list of Track Points which are logically
     /// connected in order. To represent a single GPS track where GPS reception
     /// was lost, or the GPS receiver was turned off, start a new Track Segment
@@ -194,12 +405,55 @@ pub struct Track<E: WaypointExtensions + Default> {
 }
 
 impl<E: WaypointExtensions + Default> Track<E> {
+    /// Gives how many points the track has, in all of its segments.
+    pub fn points_count(&self) -> usize {
+        self.segments.iter().map(|seg| seg.points.len()).sum()
+    }
+
     /// Gives the multi-linestring that this track represents, which is multiple
     /// linestrings.
     pub fn multilinestring(&self) -> MultiLineString<f64> {
         self.segments.iter().map(|seg| seg.linestring()).collect()
     }
 
+    /// Gives the coordinates of every segment's points with their elevation,
+    /// as `[longitude, latitude, elevation]`, see [`Waypoint::coord_3d`].
+    pub fn coords_3d(&self, missing_elevation: f64) -> Vec<Vec<[f64; 3]>> {
+        self.segments.iter().map(|seg| seg.coords_3d(missing_elevation)).collect()
+    }
+
+    /// Splits every segment of the track with
+    /// [`TrackSegment::split_by_time_gap`], giving a copy of this track
+    /// (name, links, etc.) holding the resulting segments.
+    pub fn split_segments_by_time_gap(&self, gap: Duration) -> Track<E>
+    where
+        E: Clone,
+    {
+        Track {
+            segments: self
+                .segments
+                .iter()
+                .flat_map(|segment| segment.split_by_time_gap(gap))
+                .collect(),
+            ..self.without_segments()
+        }
+    }
+
+    /// Gives a copy of the track's own fields, without any segments.
+    pub(crate) fn without_segments(&self) -> Track<E> {
+        Track {
+            name: self.name.clone(),
+            comment: self.comment.clone(),
+            description: self.description.clone(),
+            source: self.source.clone(),
+            links: self.links.clone(),
+            type_: self.type_.clone(),
+            number: self.number,
+            style: self.style.clone(),
+            segments: Vec::new(),
+        }
+    }
+
     /// Creates a new Track with default values.
     ///
     /// ```
@@ -239,10 +493,84 @@ pub struct TrackSegment<E: WaypointExtensions + Default> {
 impl<E: WaypointExtensions + Default> TrackSegment<E> {
     /// Gives the linestring of the segment's points, the sequence of points that
     /// comprises the track segment.
+    ///
+    /// The linestring is 2D, as the coordinates of `geo-types` have no
+    /// elevation. [`coords_3d`](Self::coords_3d) gives the points with their
+    /// elevation, and [`length_3d_meters`](Self::length_3d_meters) the length
+    /// including it.
     pub fn linestring(&self) -> LineString<f64> {
         self.points.iter().map(|wpt| wpt.point()).collect()
     }
 
+    /// Gives the coordinates of the segment's points with their elevation, as
+    /// `[longitude, latitude, elevation]`, see [`Waypoint::coord_3d`].
+    pub fn coords_3d(&self, missing_elevation: f64) -> Vec<[f64; 3]> {
+        self.points.iter().map(|wpt| wpt.coord_3d(missing_elevation)).collect()
+    }
+
+    /// Splits the segment wherever two consecutive timestamped points are
+    /// more than `gap` apart.
+    ///
+    /// Points without a timestamp never start a new segment; the gap is
+    /// measured from the last point that had one. An empty segment gives no
+    /// segments.
+    pub fn split_by_time_gap(&self, gap: Duration) -> Vec<TrackSegment<E>>
+    where
+        E: Clone,
+    {
+        let mut segments: Vec<TrackSegment<E>> = Vec::new();
+        let mut current = TrackSegment::new();
+        let mut last_time: Option<Time> = None;
+        for point in &self.points {
+            if let Some(time) = point.time {
+                if let Some(last_time) = last_time {
+                    if time - last_time > gap {
+                        segments.push(std::mem::take(&mut current));
+                    }
+                }
+                last_time = Some(time);
+            }
+            current.points.push(point.clone());
+        }
+        if !current.points.is_empty() {
+            segments.push(current);
+        }
+        segments
+    }
+
+    /// Splits the segment in two, the first holding the points before `index`
+    /// and the second the points from `index` on.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is larger than the number of points.
+    pub fn split_at(&self, index: usize) -> (TrackSegment<E>, TrackSegment<E>)
+    where
+        E: Clone,
+    {
+        let (first, second) = self.points.split_at(index);
+        (
+            TrackSegment { points: first.to_vec() },
+            TrackSegment { points: second.to_vec() },
+        )
+    }
+
+    /// Splits the segment into consecutive segments of at most `max_points`
+    /// points each.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_points` is 0.
+    pub fn split_into_chunks(&self, max_points: usize) -> Vec<TrackSegment<E>>
+    where
+        E: Clone,
+    {
+        self.points
+            .chunks(max_points)
+            .map(|points| TrackSegment { points: points.to_vec() })
+            .collect()
+    }
+
     /// Creates a new TrackSegment with default values.
     ///
     /// ```
@@ -274,7 +602,7 @@ impl<E: WaypointExtensions + Default> From<TrackSegment<E>> for Geometry<f64> {
 // in the Waypoint::new function below
 #[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
-struct GpxPoint(Point<f64>);
+pub(crate) struct GpxPoint(Point<f64>);
 
 impl Default for GpxPoint {
     fn default() -> GpxPoint {
@@ -288,7 +616,7 @@ impl Default for GpxPoint {
 #[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
 pub struct Waypoint<E: WaypointExtensions + Default> {
     /// The geographical point.
-    point: GpxPoint,
+    pub(crate) point: GpxPoint,
 
     /// Elevation (in meters) of the point.
     pub elevation: Option<f64>,
@@ -296,6 +624,9 @@ pub struct Waypoint<E: WaypointExtensions + Default> {
     /// Speed (in meters per second) (only in GPX 1.0)
     pub speed: Option<f64>,
 
+    /// Course over ground (in degrees, relative to true north) (only in GPX 1.0)
+    pub course: Option<f64>,
+
     /// Creation/modification timestamp for element. Date and time in are in
     /// Univeral Coordinated Time (UTC), not local time! Conforms to ISO 8601
     /// specification for date/time representation. Fractional seconds are
@@ -319,7 +650,8 @@ pub struct Waypoint<E: WaypointExtensions + Default> {
     /// accuracy of data. "Garmin eTrex", "USGS quad Boston North", e.g.
     pub source: Option<String>,
 
-    /// Links to additional information about the waypoint.
+    /// Links to additional information about the waypoint. Most points have
+    /// none, for which the vector allocates nothing.
     pub links: Vec<Link>,
 
     /// Text of GPS symbol name. For interchange with other programs, use the
@@ -330,7 +662,9 @@ pub struct Waypoint<E: WaypointExtensions + Default> {
     /// Type (classification) of the waypoint.
     pub type_: Option<String>,
 
-    // <magvar> degreesType </magvar> [0..1] ?
+    /// Magnetic variation (in degrees) at the point.
+    pub magvar: Option<f64>,
+
     /// Height of geoid in meters above WGS 84. This correspond to the sea level.
     pub geoidheight: Option<f64>,
 
@@ -368,47 +702,164 @@ pub struct Waypoint<E: WaypointExtensions + Default> {
 impl<E: WaypointExtensions + Default> Waypoint<E> {
     /// Gives the geographical point of the waypoint.
     ///
+    /// **x is the longitude and y the latitude**, as geo-types has them, the
+    /// other way around from GPX attributes. [`Waypoint::lat`] and
+    /// [`Waypoint::lon`] give them by name.
+    ///
     /// ```
     /// extern crate geo_types;
     /// extern crate gpx;
     ///
     /// use gpx::Waypoint;
     /// use geo_types::Point;
+    /// use gpx::parser::extensions::EmptyExtensions;
     ///
     /// fn main() {
     ///     // Kind of useless, but it shows the point.
-    ///     let wpt = Waypoint::new(Point::new(-121.97, 37.24));
+    ///     let wpt: Waypoint<EmptyExtensions> = Waypoint::new(Point::new(-121.97, 37.24));
     ///     let point = wpt.point();
     ///
-    ///     println!("waypoint latitude: {}, longitude: {}", point.x(), point.y());
+    ///     println!("waypoint latitude: {}, longitude: {}", point.y(), point.x());
     /// }
     /// ```
     pub fn point(&self) -> Point<f64> {
         self.point.0 //.0 to extract the geo_types::Point from the tuple struct GpxPoint
     }
 
+    /// Gives the coordinate of this point with its elevation, for consumers
+    /// wanting xyz triples, as geo-types points have no elevation.
+    ///
+    /// The order is `[longitude, latitude, elevation]`, that is x, y, z as in
+    /// GeoJSON, and not latitude first as GPX attributes are written.
+    /// Longitude and latitude are in degrees, elevation in meters, and
+    /// `missing_elevation`, such as `0.0` or `f64::NAN`, when there is none.
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::Waypoint;
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut wpt: Waypoint<EmptyExtensions> = Waypoint::new(Point::new(-121.97, 37.24));
+    /// assert_eq!(wpt.coord_3d(0.0), [-121.97, 37.24, 0.0]);
+    /// assert!(wpt.coord_3d(f64::NAN)[2].is_nan());
+    ///
+    /// wpt.elevation = Some(553.21);
+    /// assert_eq!(wpt.coord_3d(0.0), [-121.97, 37.24, 553.21]);
+    /// ```
+    pub fn coord_3d(&self, missing_elevation: f64) -> [f64; 3] {
+        let point = self.point();
+        [point.x(), point.y(), self.elevation.unwrap_or(missing_elevation)]
+    }
+
+    /// Gives the coordinate of this point with its elevation as
+    /// `(longitude, latitude, elevation)`, or `None` without an elevation.
+    /// Unlike [`Waypoint::coord_3d`], nothing stands in for a missing
+    /// elevation.
+    pub fn point_with_elevation(&self) -> Option<(f64, f64, f64)> {
+        let point = self.point();
+        self.elevation.map(|elevation| (point.x(), point.y(), elevation))
+    }
+
+    /// Gives the latitude of the waypoint in degrees, the y of
+    /// [`Waypoint::point`].
+    pub fn lat(&self) -> f64 {
+        self.point().y()
+    }
+
+    /// Gives the longitude of the waypoint in degrees, the x of
+    /// [`Waypoint::point`].
+    pub fn lon(&self) -> f64 {
+        self.point().x()
+    }
+
+    /// Gives the time of the waypoint in seconds since the unix epoch, see
+    /// [`Time::unix`].
+    ///
+    /// ```
+    /// use gpx::Waypoint;
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut wpt: Waypoint<EmptyExtensions> = Waypoint::from_lat_lon(37.24, -121.97);
+    /// assert_eq!(wpt.unix_time(), None);
+    ///
+    /// wpt.set_unix_time(1_255_804_646).unwrap();
+    /// assert_eq!(wpt.unix_time(), Some(1_255_804_646));
+    /// assert_eq!(wpt.unix_time_millis(), Some(1_255_804_646_000));
+    /// assert_eq!(wpt.time.unwrap().format().unwrap(), "2009-10-17T18:37:26.000000000Z");
+    /// ```
+    pub fn unix_time(&self) -> Option<i64> {
+        self.time.map(|time| time.unix())
+    }
+
+    /// Gives the time of the waypoint in milliseconds since the unix epoch,
+    /// see [`Time::unix_millis`].
+    pub fn unix_time_millis(&self) -> Option<i64> {
+        self.time.map(|time| time.unix_millis())
+    }
+
+    /// Sets the time of the waypoint to `seconds` since the unix epoch,
+    /// failing like [`Time::from_unix`] and leaving the time as it was.
+    pub fn set_unix_time(&mut self, seconds: i64) -> GpxResult<()> {
+        self.time = Some(Time::from_unix(seconds)?);
+        Ok(())
+    }
+
     /// Creates a new Waypoint from a given geographical point.
     ///
+    /// **The point is longitude first**, `Point::new(lon, lat)`, the other way
+    /// around from GPX attributes; [`Waypoint::from_lat_lon`] and
+    /// [`Waypoint::from_lon_lat`] say which in their name. In debug builds
+    /// with the `tracing` feature, a point whose y is beyond ±90 while its x
+    /// is not logs a warning, as its axes look swapped.
+    ///
     /// ```
     /// extern crate geo_types;
     /// extern crate gpx;
     ///
     /// use gpx::Waypoint;
     /// use geo_types::Point;
+    /// use gpx::parser::extensions::EmptyExtensions;
     ///
     /// fn main() {
     ///     let point = Point::new(-121.97, 37.24);
     ///
-    ///     let mut wpt = Waypoint::new(point);
+    ///     let mut wpt: Waypoint<EmptyExtensions> = Waypoint::new(point);
     ///     wpt.elevation = Some(553.21);
     /// }
     /// ```
     pub fn new(point: Point<f64>) -> Waypoint<E> {
+        #[cfg(all(debug_assertions, feature = "tracing"))]
+        if point.y().abs() > 90.0 && point.x().abs() <= 90.0 {
+            tracing::warn!(
+                x = point.x(),
+                y = point.y(),
+                "waypoint latitude is beyond ±90 while its longitude is not, are the axes swapped? Points are Point::new(lon, lat)"
+            );
+        }
         Waypoint {
             point: GpxPoint(point),
             ..Default::default()
         }
     }
+    /// Creates a new Waypoint at `latitude` and `longitude`, in degrees, in
+    /// the order GPX attributes have them.
+    ///
+    /// ```
+    /// use gpx::Waypoint;
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let wpt: Waypoint<EmptyExtensions> = Waypoint::from_lat_lon(37.24, -121.97);
+    /// assert_eq!((wpt.lat(), wpt.lon()), (37.24, -121.97));
+    /// ```
+    pub fn from_lat_lon(latitude: f64, longitude: f64) -> Waypoint<E> {
+        Waypoint::new(Point::new(longitude, latitude))
+    }
+
+    /// Creates a new Waypoint at `longitude` and `latitude`, in degrees, in
+    /// the order of geo-types and GeoJSON.
+    pub fn from_lon_lat(longitude: f64, latitude: f64) -> Waypoint<E> {
+        Waypoint::new(Point::new(longitude, latitude))
+    }
 }
 
 impl<E: WaypointExtensions + Default> From<Waypoint<E>> for Geometry<f64> {
@@ -431,6 +882,24 @@ pub struct Person {
     pub link: Option<Link>,
 }
 
+/// TrackStyle describes how a track is drawn on a map.
+///
+/// It is read from the `line` element of the
+/// [gpx_style](http://www.topografix.com/GPX/gpx_style/0/2) extension, or from
+/// the `DisplayColor` of Garmin's `TrackExtension`.
+#[derive(Clone, Default, Debug, PartialEq)]
+#[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
+pub struct TrackStyle {
+    /// Line color as `#RRGGBB`.
+    pub color: Option<String>,
+
+    /// Line opacity, from 0.0 (transparent) to 1.0 (opaque).
+    pub opacity: Option<f64>,
+
+    /// Line width, in the unit of its source: gpx_style uses millimeters.
+    pub width: Option<f64>,
+}
+
 /// Link represents a link to an external resource.
 ///
 /// An external resource could be a web page, digital photo,
@@ -465,3 +934,198 @@ pub enum Fix {
     /// Other values that are not in the specification.
     Other(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+    use time::Duration;
+
+    use crate::parser::extensions::EmptyExtensions;
+
+    use geo_types::{Geometry, GeometryCollection};
+
+    use crate::errors::GpxError;
+    use crate::testing::{timed_segment, waypoint, xs};
+
+    use super::{Gpx, GpxVersion, Link, Route, Track, TrackSegment, Waypoint};
+
+    #[test]
+    fn gpx_version_strings() {
+        for version in [GpxVersion::Gpx10, GpxVersion::Gpx11] {
+            assert_eq!(version.as_str().unwrap().parse::<GpxVersion>().unwrap(), version);
+        }
+        assert_eq!(GpxVersion::Gpx10.as_str(), Some("1.0"));
+        assert_eq!(GpxVersion::Unknown.as_str(), None);
+
+        for unknown in ["1.2", "1", " 1.1", ""] {
+            assert!(matches!(
+                unknown.parse::<GpxVersion>(),
+                Err(GpxError::UnknownVersionError(GpxVersion::Unknown))
+            ));
+        }
+    }
+
+    #[test]
+    fn coords_3d() {
+        let mut first = timed_segment(&[(1.0, None), (2.0, None)]);
+        first.points[1].elevation = Some(12.5);
+        let mut track = Track::new();
+        track.segments.push(first);
+        track.segments.push(timed_segment(&[(3.0, None)]));
+
+        assert_eq!(
+            track.coords_3d(-1.0),
+            [vec![[1.0, 0.0, -1.0], [2.0, 0.0, 12.5]], vec![[3.0, 0.0, -1.0]]]
+        );
+
+        let mut route: Route<EmptyExtensions> = Route::new();
+        route.points = track.segments[0].points.clone();
+        assert_eq!(route.coords_3d(0.0), [[1.0, 0.0, 0.0], [2.0, 0.0, 12.5]]);
+    }
+
+    #[test]
+    fn for_each_segment_mut() {
+        let mut gpx: Gpx<EmptyExtensions> = Gpx::default();
+        for elevations in [[Some(12.5), None], [Some(-3.0), Some(480.25)]] {
+            let mut track = Track::new();
+            for elevation in elevations.iter() {
+                let mut segment = timed_segment(&[(1.0, None), (2.0, None)]);
+                segment.points[1].elevation = *elevation;
+                track.segments.push(segment);
+            }
+            gpx.tracks.push(track);
+        }
+
+        gpx.for_each_segment_mut(|segment| {
+            for point in &mut segment.points {
+                point.elevation = point.elevation.map(|_| 0.0);
+            }
+        });
+        let elevations: Vec<_> = gpx
+            .segments_mut()
+            .map(|segment| segment.points[1].elevation)
+            .collect();
+        assert_eq!(elevations, [Some(0.0), None, Some(0.0), Some(0.0)]);
+        assert_eq!(gpx.tracks_mut().count(), 2);
+    }
+
+    #[test]
+    fn iter_points_and_bounds() {
+        let mut gpx: Gpx<EmptyExtensions> = Gpx::default();
+        assert_eq!(gpx.iter_points().count(), 0);
+        assert_eq!(gpx.compute_bounds(), None);
+
+        let mut track = Track::new();
+        track.segments.push(timed_segment(&[(1.0, None), (2.0, None)]));
+        track.segments.push(timed_segment(&[(3.0, None)]));
+        gpx.tracks.push(track);
+        let mut route = Route::new();
+        route.points = timed_segment(&[(-4.0, None)]).points;
+        gpx.routes.push(route);
+        gpx.waypoints.push(Waypoint::new(Point::new(0.5, -2.0)));
+
+        // Waypoints, then routes, then tracks.
+        let xs: Vec<_> = gpx.iter_points().map(Waypoint::lon).collect();
+        assert_eq!(xs, [0.5, -4.0, 1.0, 2.0, 3.0]);
+        assert_eq!(xs.len(), gpx.points_count());
+        let bounds = gpx.compute_bounds().unwrap();
+        assert_eq!((bounds.min().x, bounds.min().y), (-4.0, -2.0));
+        assert_eq!((bounds.max().x, bounds.max().y), (3.0, 0.0));
+    }
+
+    #[test]
+    fn point_with_elevation() {
+        let mut point = waypoint(12.5, None);
+        assert_eq!(point.point_with_elevation(), None);
+
+        point.elevation = Some(-3.25);
+        assert_eq!(point.point_with_elevation(), Some((12.5, 0.0, -3.25)));
+        assert_eq!(point.point(), Point::new(12.5, 0.0));
+    }
+
+    #[test]
+    fn split_by_time_gap() {
+        let segment = timed_segment(&[
+            (0.0, None),
+            (1.0, Some(0)),
+            (2.0, Some(10)),
+            (3.0, None),
+            (4.0, Some(1000)),
+            (5.0, Some(1010)),
+            (6.0, None),
+        ]);
+
+        let split = segment.split_by_time_gap(Duration::seconds(60));
+        assert_eq!(split.len(), 2);
+        assert_eq!(xs(&split[0]), vec![0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(xs(&split[1]), vec![4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn split_by_time_gap_without_times() {
+        let segment = timed_segment(&[(0.0, None), (1.0, None)]);
+        let split = segment.split_by_time_gap(Duration::seconds(1));
+        assert_eq!(split, vec![segment]);
+
+        let empty: TrackSegment<EmptyExtensions> = TrackSegment::new();
+        assert!(empty.split_by_time_gap(Duration::seconds(1)).is_empty());
+    }
+
+    #[test]
+    fn split_segments_by_time_gap() {
+        let mut track: Track<EmptyExtensions> = Track::new();
+        track.name = Some("two days".into());
+        track.links.push(Link {
+            href: "http://example.com".into(),
+            ..Default::default()
+        });
+        track.segments.push(timed_segment(&[(0.0, Some(0)), (1.0, Some(90_000))]));
+        track.segments.push(timed_segment(&[(2.0, Some(90_010))]));
+
+        let split = track.split_segments_by_time_gap(Duration::hours(1));
+        assert_eq!(split.name, track.name);
+        assert_eq!(split.links, track.links);
+        assert_eq!(split.segments.len(), 3);
+        assert_eq!(xs(&split.segments[0]), vec![0.0]);
+        assert_eq!(xs(&split.segments[1]), vec![1.0]);
+        assert_eq!(xs(&split.segments[2]), vec![2.0]);
+    }
+
+    #[test]
+    fn split_at_and_into_chunks() {
+        let segment = timed_segment(&[(0.0, None), (1.0, None), (2.0, None), (3.0, None), (4.0, None)]);
+
+        let (first, second) = segment.split_at(2);
+        assert_eq!(xs(&first), vec![0.0, 1.0]);
+        assert_eq!(xs(&second), vec![2.0, 3.0, 4.0]);
+
+        let chunks = segment.split_into_chunks(2);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(xs(&chunks[2]), vec![4.0]);
+    }
+
+    #[test]
+    fn gpx_into_geometry_collection() {
+        let mut track = Track::new();
+        track.segments.push(timed_segment(&[(0.0, None), (1.0, None)]));
+        track.segments.push(timed_segment(&[(2.0, None)]));
+        let mut route = Route::new();
+        route.points.push(waypoint(3.0, None));
+        let gpx: Gpx<EmptyExtensions> = Gpx {
+            tracks: vec![track, Track::new()],
+            routes: vec![route],
+            waypoints: vec![waypoint(4.0, None), waypoint(5.0, None), waypoint(6.0, None)],
+            ..Default::default()
+        };
+
+        let collection = GeometryCollection::from(&gpx);
+        assert_eq!(collection.len(), 6);
+        match &collection[0] {
+            Geometry::MultiLineString(lines) => assert_eq!(lines.0.len(), 2),
+            other => panic!("expected the first track, got {:?}", other),
+        }
+        assert!(matches!(collection[1], Geometry::MultiLineString(_)));
+        assert!(matches!(collection[2], Geometry::LineString(_)));
+        assert_eq!(collection[5], Geometry::Point(Point::new(6.0, 0.0)));
+    }
+}
@@ -0,0 +1,125 @@
+//! wasm exposes reading to JavaScript, for previewing GPX in a browser.
+//!
+//! Built with the `wasm` feature, for `wasm32-unknown-unknown` with
+//! wasm-bindgen, this gives JavaScript a `parse_gpx` function:
+//!
+//! ```js
+//! import init, { parse_gpx } from "./pkg/gpx.js";
+//!
+//! await init();
+//! const bytes = new Uint8Array(await file.arrayBuffer());
+//! const manifest = parse_gpx(bytes);
+//! console.log(manifest.tracks.map((track) => track.name));
+//! ```
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::parser::extensions::WaypointExtensions;
+use crate::{read_from_slice, Gpx, Waypoint};
+
+/// What a preview needs of a document: names and coordinates, as
+/// `[longitude, latitude]` pairs.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Manifest {
+    /// The GPX version, "1.0" or "1.1", `None` when it is unknown.
+    pub version: Option<String>,
+    pub creator: Option<String>,
+    pub name: Option<String>,
+    pub waypoints: Vec<PlaceManifest>,
+    pub routes: Vec<LineManifest>,
+    pub tracks: Vec<LineManifest>,
+}
+
+/// A waypoint of a [`Manifest`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PlaceManifest {
+    pub name: Option<String>,
+    pub coordinates: [f64; 2],
+}
+
+/// A route or a track of a [`Manifest`], with a line per track segment.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LineManifest {
+    pub name: Option<String>,
+    pub lines: Vec<Vec<[f64; 2]>>,
+}
+
+/// coordinates gives the longitude and latitude of `points`.
+fn coordinates<E: WaypointExtensions + Default>(points: &[Waypoint<E>]) -> Vec<[f64; 2]> {
+    points.iter().map(|point| [point.point().x(), point.point().y()]).collect()
+}
+
+impl<E: WaypointExtensions + Default> From<&Gpx<E>> for Manifest {
+    fn from(gpx: &Gpx<E>) -> Manifest {
+        Manifest {
+            version: gpx.version.as_str().map(String::from),
+            creator: gpx.creator.clone(),
+            name: gpx.metadata.as_ref().and_then(|metadata| metadata.name.clone()),
+            waypoints: gpx
+                .waypoints
+                .iter()
+                .map(|waypoint| PlaceManifest {
+                    name: waypoint.name.clone(),
+                    coordinates: [waypoint.point().x(), waypoint.point().y()],
+                })
+                .collect(),
+            routes: gpx
+                .routes
+                .iter()
+                .map(|route| LineManifest {
+                    name: route.name.clone(),
+                    lines: vec![coordinates(&route.points)],
+                })
+                .collect(),
+            tracks: gpx
+                .tracks
+                .iter()
+                .map(|track| LineManifest {
+                    name: track.name.clone(),
+                    lines: track.segments.iter().map(|segment| coordinates(&segment.points)).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Reads the GPX document in `bytes` with [`read_from_slice`], giving its
+/// [`Manifest`] as a JavaScript object, or the error as a string.
+#[wasm_bindgen]
+pub fn parse_gpx(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    let gpx = read_from_slice(bytes).map_err(|err| JsValue::from_str(&err.to_string()))?;
+    Ok(serde_wasm_bindgen::to_value(&Manifest::from(&gpx))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen::JsValue;
+
+    use crate::read_from_slice;
+
+    use super::{parse_gpx, Manifest};
+
+    #[test]
+    fn manifest_from_gpx() {
+        let bytes = std::fs::read("tests/fixtures/wikipedia_example.gpx").unwrap();
+        let gpx = read_from_slice(&bytes).unwrap();
+        let manifest = Manifest::from(&gpx);
+
+        assert_eq!(manifest.version.as_deref(), Some("1.1"));
+        assert_eq!(manifest.tracks.len(), 1);
+        assert_eq!(manifest.tracks[0].name.as_deref(), Some("Example GPX Document"));
+        assert_eq!(manifest.tracks[0].lines[0].len(), 3);
+        assert_eq!(
+            manifest.tracks[0].lines[0][0],
+            [gpx.tracks[0].segments[0].points[0].point().x(), gpx.tracks[0].segments[0].points[0].point().y()]
+        );
+    }
+
+    // JavaScript values only exist in WebAssembly, so off it the entry point
+    // can only be type-checked.
+    #[test]
+    fn parse_gpx_signature() {
+        let _: fn(&[u8]) -> Result<JsValue, JsValue> = parse_gpx;
+    }
+}
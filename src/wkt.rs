@@ -0,0 +1,262 @@
+//! wkt converts points, routes and tracks to Well-Known Text, for loading
+//! into spatial databases such as PostGIS.
+//!
+//! **Coordinates are written longitude first, then latitude**, as WKT has
+//! them, the other way around from GPX attributes and from how coordinates
+//! are usually said.
+
+use crate::parser::extensions::WaypointExtensions;
+use crate::{Route, Track, TrackSegment, Waypoint};
+
+/// The most decimal places coordinates are rounded to, as many as an `f64`
+/// carries.
+const MAX_DECIMALS: u32 = 17;
+
+/// Options for the `to_wkt_with` methods, such as
+/// [`TrackSegment::to_wkt_with`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WktOptions {
+    /// Writes the elevation as a third coordinate, as `LINESTRING Z`, when
+    /// every point has one. Geometries with a point lacking it stay 2D.
+    pub z: bool,
+    /// Rounds coordinates to this many decimal places, leaving out trailing
+    /// zeros, like [`ParseOptions::coordinate_decimals`] does when reading.
+    /// By default they are written with full precision. At most 17 places
+    /// are written, as many as an `f64` carries, whatever is asked for.
+    ///
+    /// [`ParseOptions::coordinate_decimals`]: crate::ParseOptions::coordinate_decimals
+    pub decimals: Option<u32>,
+}
+
+/// number formats `value` with the decimals of `options`, at most
+/// [`MAX_DECIMALS`].
+fn number(value: f64, options: &WktOptions) -> String {
+    match options.decimals {
+        Some(decimals) => {
+            let rounded = format!("{:.*}", decimals.min(MAX_DECIMALS) as usize, value);
+            if rounded.contains('.') {
+                rounded.trim_end_matches('0').trim_end_matches('.').to_string()
+            } else {
+                rounded
+            }
+        }
+        None => value.to_string(),
+    }
+}
+
+/// has_z tells whether the elevation of `points` is written.
+fn has_z<'a, E: WaypointExtensions + Default + 'a>(
+    mut points: impl Iterator<Item = &'a Waypoint<E>>,
+    options: &WktOptions,
+) -> bool {
+    options.z && points.all(|point| point.elevation.is_some())
+}
+
+/// tagged gives the geometry `tag`, with ` Z ` when it has elevations.
+fn tagged(tag: &str, z: bool) -> String {
+    if z {
+        format!("{} Z ", tag)
+    } else {
+        tag.to_string()
+    }
+}
+
+/// coordinates gives the WKT coordinates of `points` in parentheses,
+/// longitude first.
+fn coordinates<E: WaypointExtensions + Default>(points: &[Waypoint<E>], z: bool, options: &WktOptions) -> String {
+    let coordinates: Vec<_> = points
+        .iter()
+        .map(|point| {
+            let mut coordinate = format!(
+                "{} {}",
+                number(point.point().x(), options),
+                number(point.point().y(), options)
+            );
+            if let (true, Some(elevation)) = (z, point.elevation) {
+                coordinate.push(' ');
+                coordinate.push_str(&number(elevation, options));
+            }
+            coordinate
+        })
+        .collect();
+    format!("({})", coordinates.join(","))
+}
+
+/// line_string gives `points` as a WKT `LINESTRING`.
+fn line_string<E: WaypointExtensions + Default>(points: &[Waypoint<E>], options: &WktOptions) -> String {
+    if points.is_empty() {
+        return String::from("LINESTRING EMPTY");
+    }
+    let z = has_z(points.iter(), options);
+    tagged("LINESTRING", z) + &coordinates(points, z, options)
+}
+
+impl<E: WaypointExtensions + Default> Waypoint<E> {
+    /// Gives this waypoint as a WKT `POINT`, **longitude first**, as in
+    /// `POINT(8.6345 46.5597)`.
+    pub fn to_wkt(&self) -> String {
+        self.to_wkt_with(&Default::default())
+    }
+
+    /// Gives this waypoint as a WKT `POINT` like [`Waypoint::to_wkt`], as a
+    /// `POINT Z` with its elevation and rounded according to `options`.
+    pub fn to_wkt_with(&self, options: &WktOptions) -> String {
+        let z = has_z(std::iter::once(self), options);
+        tagged("POINT", z) + &coordinates(std::slice::from_ref(self), z, options)
+    }
+}
+
+impl<E: WaypointExtensions + Default> TrackSegment<E> {
+    /// Gives this segment as a WKT `LINESTRING`, **longitude first**, written
+    /// as PostGIS writes it.
+    ///
+    /// ```
+    /// use geo_types::Point;
+    /// use gpx::{TrackSegment, Waypoint};
+    /// use gpx::parser::extensions::EmptyExtensions;
+    ///
+    /// let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+    /// segment.points.push(Waypoint::new(Point::new(8.54, 47.37)));
+    /// segment.points.push(Waypoint::new(Point::new(8.55, 47.38)));
+    ///
+    /// assert_eq!(segment.to_wkt(), "LINESTRING(8.54 47.37,8.55 47.38)");
+    /// ```
+    pub fn to_wkt(&self) -> String {
+        self.to_wkt_with(&Default::default())
+    }
+
+    /// Gives this segment as a WKT `LINESTRING` like [`TrackSegment::to_wkt`],
+    /// as a `LINESTRING Z` and rounded according to `options`.
+    pub fn to_wkt_with(&self, options: &WktOptions) -> String {
+        line_string(&self.points, options)
+    }
+}
+
+impl<E: WaypointExtensions + Default> Route<E> {
+    /// Gives this route as a WKT `LINESTRING`, **longitude first**.
+    pub fn to_wkt(&self) -> String {
+        self.to_wkt_with(&Default::default())
+    }
+
+    /// Gives this route as a WKT `LINESTRING` like [`Route::to_wkt`], as a
+    /// `LINESTRING Z` and rounded according to `options`.
+    pub fn to_wkt_with(&self, options: &WktOptions) -> String {
+        line_string(&self.points, options)
+    }
+}
+
+impl<E: WaypointExtensions + Default> Track<E> {
+    /// Gives this track as a WKT `MULTILINESTRING` of its segments,
+    /// **longitude first**. Empty segments are left out.
+    pub fn to_wkt(&self) -> String {
+        self.to_wkt_with(&Default::default())
+    }
+
+    /// Gives this track as a WKT `MULTILINESTRING` like [`Track::to_wkt`], as
+    /// a `MULTILINESTRING Z` when every point of every segment has an
+    /// elevation, and rounded according to `options`.
+    pub fn to_wkt_with(&self, options: &WktOptions) -> String {
+        let segments: Vec<_> = self.segments.iter().filter(|segment| !segment.points.is_empty()).collect();
+        if segments.is_empty() {
+            return String::from("MULTILINESTRING EMPTY");
+        }
+        let z = has_z(segments.iter().flat_map(|segment| &segment.points), options);
+        let lines: Vec<_> = segments
+            .iter()
+            .map(|segment| coordinates(&segment.points, z, options))
+            .collect();
+        format!("{}({})", tagged("MULTILINESTRING", z), lines.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+
+    use crate::parser::extensions::EmptyExtensions;
+    use crate::{Route, Track, TrackSegment, Waypoint};
+
+    use super::WktOptions;
+
+    fn segment(elevations: [Option<f64>; 3]) -> TrackSegment<EmptyExtensions> {
+        let coordinates = [(-122.4194, 37.7749), (-122.41, 37.78), (-122.405123456789, 37.7851)];
+        let mut segment = TrackSegment::new();
+        for (&(x, y), &elevation) in coordinates.iter().zip(&elevations) {
+            let mut point = Waypoint::new(Point::new(x, y));
+            point.elevation = elevation;
+            segment.points.push(point);
+        }
+        segment
+    }
+
+    #[test]
+    fn segment_to_wkt() {
+        let segment = segment([Some(16.0), Some(20.5), Some(31.25)]);
+        let z = WktOptions {
+            z: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            segment.to_wkt(),
+            "LINESTRING(-122.4194 37.7749,-122.41 37.78,-122.405123456789 37.7851)"
+        );
+        assert_eq!(
+            segment.to_wkt_with(&z),
+            "LINESTRING Z (-122.4194 37.7749 16,-122.41 37.78 20.5,-122.405123456789 37.7851 31.25)"
+        );
+        assert_eq!(
+            segment.to_wkt_with(&WktOptions {
+                z: true,
+                decimals: Some(3),
+            }),
+            "LINESTRING Z (-122.419 37.775 16,-122.41 37.78 20.5,-122.405 37.785 31.25)"
+        );
+
+        // Without an elevation for every point, the line stays 2D.
+        let partial = self::segment([Some(16.0), None, Some(31.25)]);
+        assert_eq!(partial.to_wkt_with(&z), segment.to_wkt());
+    }
+
+    #[test]
+    fn track_route_and_waypoint_to_wkt() {
+        let mut track: Track<EmptyExtensions> = Track::new();
+        track.segments.push(segment([Some(1.0), Some(2.0), Some(3.0)]));
+        track.segments.push(TrackSegment::new());
+        let mut second = segment([Some(4.0), Some(5.0), Some(6.0)]);
+        second.points.truncate(2);
+        track.segments.push(second);
+        let z = WktOptions {
+            z: true,
+            decimals: Some(2),
+        };
+
+        assert_eq!(
+            track.to_wkt_with(&z),
+            "MULTILINESTRING Z ((-122.42 37.77 1,-122.41 37.78 2,-122.41 37.79 3),(-122.42 37.77 4,-122.41 37.78 5))"
+        );
+        assert_eq!(Track::<EmptyExtensions>::new().to_wkt(), "MULTILINESTRING EMPTY");
+
+        let mut route: Route<EmptyExtensions> = Route::new();
+        assert_eq!(route.to_wkt(), "LINESTRING EMPTY");
+        route.points = segment([None; 3]).points;
+        assert_eq!(route.to_wkt(), segment([None; 3]).to_wkt());
+
+        let point = &track.segments[0].points[0];
+        assert_eq!(point.to_wkt(), "POINT(-122.4194 37.7749)");
+        assert_eq!(point.to_wkt_with(&z), "POINT Z (-122.42 37.77 1)");
+    }
+
+    #[test]
+    fn decimals_capped() {
+        let point: Waypoint<EmptyExtensions> = Waypoint::new(Point::new(-122.4194, 37.75));
+        let capped = |decimals| {
+            point.to_wkt_with(&WktOptions {
+                z: false,
+                decimals: Some(decimals),
+            })
+        };
+        assert_eq!(capped(u32::MAX), capped(17));
+        assert_eq!(capped(u32::MAX), "POINT(-122.419399999999996 37.75)");
+    }
+}
@@ -3,12 +3,14 @@
 use std::io::Write;
 
 use geo_types::Rect;
+use time::{Duration, OffsetDateTime};
 use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
 
 use crate::{Gpx, GpxVersion};
 use crate::errors::{GpxError, GpxResult};
 use crate::parser::extensions::WaypointExtensions;
 use crate::parser::time::Time;
+use crate::schema::XSI_NAMESPACE;
 use crate::types::*;
 
 /// Writes an activity to GPX format.
@@ -16,6 +18,11 @@ use crate::types::*;
 /// Takes any `std::io::Write` as its writer, and returns a
 /// [`Result<(), GpxError>`].
 ///
+/// The document is written to `writer` element by element as it goes, never
+/// held whole, so writing takes the same memory however many points there
+/// are. That makes for many small writes: give it a
+/// [`BufWriter`](std::io::BufWriter) around a file or socket.
+///
 /// [`Result<(), GpxError>`]: std::result::Result<T>
 ///
 /// ```
@@ -31,10 +38,87 @@ use crate::types::*;
 /// write(&data, std::io::stdout()).unwrap();
 /// ```
 pub fn write<W: Write, E: WaypointExtensions + Default>(gpx: &Gpx<E>, writer: W) -> GpxResult<()> {
-    let mut writer = EmitterConfig::new()
-        .perform_indent(true)
-        .create_writer(writer);
-    write_with_event_writer(gpx, &mut writer)
+    write_with_config(gpx, writer, &Default::default())
+}
+
+/// Options for writing GPX.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WriterConfig {
+    /// GPX version to emit. With [`GpxVersion::Unknown`], the default, the
+    /// version of the written [`Gpx`] is used.
+    pub version: GpxVersion,
+
+    /// Check the output against the GPX 1.1 schema rules of
+    /// [`validate`](crate::validate) before writing anything, failing with
+    /// [`GpxError::SchemaViolations`] instead of writing an invalid document.
+    /// There are no rules for GPX 1.0. The whole document is then held in
+    /// memory until checked, unlike when writing without checking.
+    pub validate_output: bool,
+
+    /// How many digits of fractional seconds times are written with, see
+    /// [`TimePrecision`].
+    pub time_precision: TimePrecision,
+}
+
+/// How many digits of fractional seconds times are written with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimePrecision {
+    /// As many as the time needs and no more, up to nanoseconds, leaving
+    /// them out for whole seconds. Every time reads back as it was, and is
+    /// written the same way every time, the default.
+    #[default]
+    Auto,
+    /// Always this many digits, at most 9, rounding the time to them. Times
+    /// then line up in columns, as some tools expect.
+    Fixed(u8),
+}
+
+/// Writes an activity to GPX format, using the given [`WriterConfig`].
+///
+/// ```
+/// use gpx::{write_with_config, Gpx, GpxVersion, WriterConfig};
+/// use gpx::parser::extensions::EmptyExtensions;
+///
+/// let mut data : Gpx<EmptyExtensions> = Default::default();
+/// data.version = GpxVersion::Gpx11;
+///
+/// // Legacy devices only understand GPX 1.0.
+/// let config = WriterConfig { version: GpxVersion::Gpx10, ..Default::default() };
+/// write_with_config(&data, std::io::stdout(), &config).unwrap();
+/// ```
+pub fn write_with_config<W: Write, E: WaypointExtensions + Default>(
+    gpx: &Gpx<E>,
+    writer: W,
+    config: &WriterConfig,
+) -> GpxResult<()> {
+    let emitter = EmitterConfig::new().perform_indent(true);
+    if !config.validate_output {
+        return write_gpx(gpx, &mut emitter.create_writer(writer), config);
+    }
+
+    let version = output_version(gpx, config);
+    if version == GpxVersion::Gpx10 {
+        return Err(GpxError::NoSchemaRules(version));
+    }
+    let mut output = Vec::new();
+    write_gpx(gpx, &mut emitter.create_writer(&mut output), config)?;
+    let violations = crate::schema::validate(output.as_slice())?;
+    if !violations.is_empty() {
+        return Err(GpxError::SchemaViolations(violations));
+    }
+    let mut writer = writer;
+    writer
+        .write_all(&output)
+        .map_err(xml::writer::Error::from)?;
+    Ok(())
+}
+
+/// The version `gpx` is written in, given `config`.
+fn output_version<E: WaypointExtensions + Default>(gpx: &Gpx<E>, config: &WriterConfig) -> GpxVersion {
+    match config.version {
+        GpxVersion::Unknown => gpx.version,
+        version => version,
+    }
 }
 
 /// Writes an activity to GPX format.
@@ -60,32 +144,56 @@ pub fn write<W: Write, E: WaypointExtensions + Default>(gpx: &Gpx<E>, writer: W)
 /// write_with_event_writer(&data, &mut writer).unwrap();
 /// ```
 pub fn write_with_event_writer<W: Write, E: WaypointExtensions + Default>(gpx: &Gpx<E>, writer: &mut EventWriter<W>) -> GpxResult<()> {
+    write_gpx(gpx, writer, &Default::default())
+}
+
+fn write_gpx<W: Write, E: WaypointExtensions + Default>(
+    gpx: &Gpx<E>,
+    writer: &mut EventWriter<W>,
+    config: &WriterConfig,
+) -> GpxResult<()> {
+    let version = output_version(gpx, config);
     let creator: &str = gpx
         .creator
         .as_deref()
         .unwrap_or("https://github.com/georust/gpx");
-    write_xml_event(
-        XmlEvent::start_element("gpx")
-            .attr("version", version_to_version_string(gpx.version)?)
-            .attr("xmlns", version_to_xml_url(gpx.version)?)
-            .attr("creator", creator),
-        writer,
-    )?;
-    write_metadata(gpx, writer)?;
-    for point in &gpx.waypoints {
-        write_waypoint("wpt", point, writer)?;
+    let schema_locations = gpx
+        .schema_locations
+        .iter()
+        .map(|(namespace, location)| format!("{} {}", namespace, location))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut start = XmlEvent::start_element("gpx")
+        .attr("version", version.as_str().ok_or(GpxError::UnknownVersionError(version))?)
+        .attr("xmlns", version_to_xml_url(version)?)
+        .attr("creator", creator);
+    // The default namespace is the one of the version written.
+    for (prefix, uri) in gpx.namespaces.iter().filter(|(prefix, _)| !prefix.is_empty()) {
+        start = start.ns(prefix.as_str(), uri.as_str());
     }
-    for track in &gpx.tracks {
-        write_track(track, writer)?;
+    if !schema_locations.is_empty() {
+        if !gpx.namespaces.iter().any(|(prefix, _)| prefix == "xsi") {
+            start = start.ns("xsi", XSI_NAMESPACE);
+        }
+        start = start.attr("xsi:schemaLocation", &schema_locations);
+    }
+    write_xml_event(start, writer)?;
+    let precision = config.time_precision;
+    write_metadata(gpx, version, precision, writer)?;
+    for point in &gpx.waypoints {
+        write_waypoint("wpt", point, version, precision, writer)?;
     }
     for route in &gpx.routes {
-        write_route(route, writer)?;
+        write_route(route, version, precision, writer)?;
+    }
+    for track in &gpx.tracks {
+        write_track(track, version, precision, writer)?;
     }
     write_xml_event(XmlEvent::end_element(), writer)?;
     Ok(())
 }
 
-fn write_xml_event<'a, W, E>(event: E, writer: &mut EventWriter<W>) -> GpxResult<()>
+pub(crate) fn write_xml_event<'a, W, E>(event: E, writer: &mut EventWriter<W>) -> GpxResult<()>
     where
         W: Write,
         E: Into<XmlEvent<'a>>,
@@ -93,14 +201,6 @@ fn write_xml_event<'a, W, E>(event: E, writer: &mut EventWriter<W>) -> GpxResult
     Ok(writer.write(event)?)
 }
 
-fn version_to_version_string(version: GpxVersion) -> GpxResult<&'static str> {
-    match version {
-        GpxVersion::Gpx10 => Ok("1.0"),
-        GpxVersion::Gpx11 => Ok("1.1"),
-        version => Err(GpxError::UnknownVersionError(version)),
-    }
-}
-
 fn version_to_xml_url(version: GpxVersion) -> GpxResult<&'static str> {
     match version {
         GpxVersion::Gpx10 => Ok("http://www.topografix.com/GPX/1/0"),
@@ -109,15 +209,24 @@ fn version_to_xml_url(version: GpxVersion) -> GpxResult<&'static str> {
     }
 }
 
-fn write_metadata<W: Write, E: WaypointExtensions + Default>(gpx: &Gpx<E>, writer: &mut EventWriter<W>) -> GpxResult<()> {
-    match gpx.version {
-        GpxVersion::Gpx10 => write_gpx10_metadata(gpx, writer),
-        GpxVersion::Gpx11 => write_gpx11_metadata(gpx, writer),
+fn write_metadata<W: Write, E: WaypointExtensions + Default>(
+    gpx: &Gpx<E>,
+    version: GpxVersion,
+    precision: TimePrecision,
+    writer: &mut EventWriter<W>,
+) -> GpxResult<()> {
+    match version {
+        GpxVersion::Gpx10 => write_gpx10_metadata(gpx, precision, writer),
+        GpxVersion::Gpx11 => write_gpx11_metadata(gpx, precision, writer),
         version => Err(GpxError::UnknownVersionError(version)),
     }
 }
 
-fn write_gpx10_metadata<W: Write, E: WaypointExtensions + Default>(gpx: &Gpx<E>, writer: &mut EventWriter<W>) -> GpxResult<()> {
+fn write_gpx10_metadata<W: Write, E: WaypointExtensions + Default>(
+    gpx: &Gpx<E>,
+    precision: TimePrecision,
+    writer: &mut EventWriter<W>,
+) -> GpxResult<()> {
     if gpx.metadata.is_none() {
         return Ok(());
     }
@@ -126,19 +235,24 @@ fn write_gpx10_metadata<W: Write, E: WaypointExtensions + Default>(gpx: &Gpx<E>,
     write_string_if_exists("desc", &metadata.description, writer)?;
     if let Some(author) = metadata.author.as_ref() {
         write_string_if_exists("author", &author.name, writer)?;
-        write_email_if_exists(&author.email, writer)?;
+        // GPX 1.0 has the email address as plain text
+        write_string_if_exists("email", &author.email, writer)?;
         if let Some(link) = author.link.as_ref() {
             write_string("url", &link.href, writer)?;
             write_string_if_exists("urlname", &link.text, writer)?;
         }
     }
+    write_time_if_exists(&metadata.time, precision, writer)?;
     write_string_if_exists("keywords", &metadata.keywords, writer)?;
-    write_time_if_exists(&metadata.time, writer)?;
     write_bounds_if_exists(&metadata.bounds, writer)?;
     Ok(())
 }
 
-fn write_gpx11_metadata<W: Write, E: WaypointExtensions + Default>(gpx: &Gpx<E>, writer: &mut EventWriter<W>) -> GpxResult<()> {
+fn write_gpx11_metadata<W: Write, E: WaypointExtensions + Default>(
+    gpx: &Gpx<E>,
+    precision: TimePrecision,
+    writer: &mut EventWriter<W>,
+) -> GpxResult<()> {
     if gpx.metadata.is_none() {
         return Ok(());
     }
@@ -147,24 +261,25 @@ fn write_gpx11_metadata<W: Write, E: WaypointExtensions + Default>(gpx: &Gpx<E>,
     write_string_if_exists("name", &metadata.name, writer)?;
     write_string_if_exists("desc", &metadata.description, writer)?;
     write_person_if_exists("author", &metadata.author, writer)?;
-    write_string_if_exists("keywords", &metadata.keywords, writer)?;
-    write_time_if_exists(&metadata.time, writer)?;
+    write_copyright_if_exists(&metadata.copyright, writer)?;
     for link in &metadata.links {
         write_link(link, writer)?;
     }
+    write_time_if_exists(&metadata.time, precision, writer)?;
+    write_string_if_exists("keywords", &metadata.keywords, writer)?;
     write_bounds_if_exists(&metadata.bounds, writer)?;
     write_xml_event(XmlEvent::end_element(), writer)?;
     Ok(())
 }
 
-fn write_string<W: Write>(key: &str, value: &str, writer: &mut EventWriter<W>) -> GpxResult<()> {
+pub(crate) fn write_string<W: Write>(key: &str, value: &str, writer: &mut EventWriter<W>) -> GpxResult<()> {
     write_xml_event(XmlEvent::start_element(key), writer)?;
     write_xml_event(XmlEvent::characters(value), writer)?;
     write_xml_event(XmlEvent::end_element(), writer)?;
     Ok(())
 }
 
-fn write_string_if_exists<W: Write>(
+pub(crate) fn write_string_if_exists<W: Write>(
     key: &str,
     value: &Option<String>,
     writer: &mut EventWriter<W>,
@@ -224,6 +339,22 @@ fn write_link<W: Write>(link: &Link, writer: &mut EventWriter<W>) -> GpxResult<(
     Ok(())
 }
 
+/// Writes links as `<link>` elements, or for GPX 1.0, which allows a single
+/// link only, the first one as `<url>` and `<urlname>`.
+fn write_links<W: Write>(links: &[Link], version: GpxVersion, writer: &mut EventWriter<W>) -> GpxResult<()> {
+    if version == GpxVersion::Gpx10 {
+        if let Some(link) = links.first() {
+            write_string("url", &link.href, writer)?;
+            write_string_if_exists("urlname", &link.text, writer)?;
+        }
+        return Ok(());
+    }
+    for link in links {
+        write_link(link, writer)?;
+    }
+    Ok(())
+}
+
 fn write_link_if_exists<W: Write>(
     link: &Option<Link>,
     writer: &mut EventWriter<W>,
@@ -249,13 +380,66 @@ fn write_person_if_exists<W: Write>(
     Ok(())
 }
 
+fn write_copyright_if_exists<W: Write>(
+    value: &Option<GpxCopyright>,
+    writer: &mut EventWriter<W>,
+) -> GpxResult<()> {
+    if let Some(ref value) = value {
+        let mut element = XmlEvent::start_element("copyright");
+        if let Some(ref author) = value.author {
+            element = element.attr("author", author);
+        }
+        write_xml_event(element, writer)?;
+        write_value_if_exists("year", &value.year, writer)?;
+        write_string_if_exists("license", &value.license, writer)?;
+        write_xml_event(XmlEvent::end_element(), writer)?;
+    }
+    Ok(())
+}
+
+/// format_time formats `time` with the fractional seconds `precision` gives.
+fn format_time(time: &Time, precision: TimePrecision) -> GpxResult<String> {
+    let digits = match precision {
+        TimePrecision::Auto => None,
+        TimePrecision::Fixed(digits) => Some(usize::from(digits.min(9))),
+    };
+    let mut time = OffsetDateTime::from(*time);
+    if let Some(digits) = digits {
+        let unit = 10u32.pow(9 - digits as u32);
+        let nanoseconds = (time.nanosecond() + unit / 2) / unit * unit;
+        let rounded = time
+            .replace_nanosecond(0)
+            .ok()
+            .and_then(|whole| whole.checked_add(Duration::nanoseconds(i64::from(nanoseconds))));
+        time = rounded.unwrap_or(time);
+    }
+
+    // The default format always has nine digits of fractional seconds.
+    let formatted = Time::from(time).format()?;
+    let (whole, rest) = match formatted.split_once('.') {
+        Some(split) => split,
+        None => return Ok(formatted),
+    };
+    let (fraction, offset) = rest.split_at(rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len()));
+    let fraction = match digits {
+        Some(digits) => &fraction[..digits.min(fraction.len())],
+        None => fraction.trim_end_matches('0'),
+    };
+    if fraction.is_empty() {
+        Ok(format!("{}{}", whole, offset))
+    } else {
+        Ok(format!("{}.{}{}", whole, fraction, offset))
+    }
+}
+
 fn write_time_if_exists<W: Write>(
     time: &Option<Time>,
+    precision: TimePrecision,
     writer: &mut EventWriter<W>,
 ) -> GpxResult<()> {
     if let Some(ref time) = time {
         write_xml_event(XmlEvent::start_element("time"), writer)?;
-        write_xml_event(XmlEvent::characters(&time.format()?), writer)?;
+        write_xml_event(XmlEvent::characters(&format_time(time, precision)?), writer)?;
         write_xml_event(XmlEvent::end_element(), writer)?;
     }
     Ok(())
@@ -296,36 +480,47 @@ fn write_fix_if_exists<W: Write>(fix: &Option<Fix>, writer: &mut EventWriter<W>)
     Ok(())
 }
 
-fn write_track<W: Write, E: WaypointExtensions + Default>(track: &Track<E>, writer: &mut EventWriter<W>) -> GpxResult<()> {
+fn write_track<W: Write, E: WaypointExtensions + Default>(
+    track: &Track<E>,
+    version: GpxVersion,
+    precision: TimePrecision,
+    writer: &mut EventWriter<W>,
+) -> GpxResult<()> {
     write_xml_event(XmlEvent::start_element("trk"), writer)?;
     write_string_if_exists("name", &track.name, writer)?;
     write_string_if_exists("cmt", &track.comment, writer)?;
     write_string_if_exists("desc", &track.description, writer)?;
     write_string_if_exists("src", &track.source, writer)?;
-    for link in &track.links {
-        write_link(link, writer)?;
+    write_links(&track.links, version, writer)?;
+    write_value_if_exists("number", &track.number, writer)?;
+    if version != GpxVersion::Gpx10 {
+        write_string_if_exists("type", &track.type_, writer)?;
     }
-    write_string_if_exists("type", &track.type_, writer)?;
     for segment in &track.segments {
-        write_track_segment(segment, writer)?;
+        write_track_segment(segment, version, precision, writer)?;
     }
     write_xml_event(XmlEvent::end_element(), writer)?;
     Ok(())
 }
 
-fn write_route<W: Write, E: WaypointExtensions + Default>(route: &Route<E>, writer: &mut EventWriter<W>) -> GpxResult<()> {
+fn write_route<W: Write, E: WaypointExtensions + Default>(
+    route: &Route<E>,
+    version: GpxVersion,
+    precision: TimePrecision,
+    writer: &mut EventWriter<W>,
+) -> GpxResult<()> {
     write_xml_event(XmlEvent::start_element("rte"), writer)?;
     write_string_if_exists("name", &route.name, writer)?;
     write_string_if_exists("cmt", &route.comment, writer)?;
     write_string_if_exists("desc", &route.description, writer)?;
     write_string_if_exists("src", &route.source, writer)?;
-    for link in &route.links {
-        write_link(link, writer)?;
-    }
+    write_links(&route.links, version, writer)?;
     write_value_if_exists("number", &route.number, writer)?;
-    write_string_if_exists("type", &route.type_, writer)?;
+    if version != GpxVersion::Gpx10 {
+        write_string_if_exists("type", &route.type_, writer)?;
+    }
     for point in &route.points {
-        write_waypoint("rtept", point, writer)?;
+        write_waypoint("rtept", point, version, precision, writer)?;
     }
     write_xml_event(XmlEvent::end_element(), writer)?;
     Ok(())
@@ -333,11 +528,13 @@ fn write_route<W: Write, E: WaypointExtensions + Default>(route: &Route<E>, writ
 
 fn write_track_segment<W: Write, E: WaypointExtensions + Default>(
     segment: &TrackSegment<E>,
+    version: GpxVersion,
+    precision: TimePrecision,
     writer: &mut EventWriter<W>,
 ) -> GpxResult<()> {
     write_xml_event(XmlEvent::start_element("trkseg"), writer)?;
     for point in &segment.points {
-        write_waypoint("trkpt", point, writer)?;
+        write_waypoint("trkpt", point, version, precision, writer)?;
     }
     write_xml_event(XmlEvent::end_element(), writer)?;
     Ok(())
@@ -346,6 +543,8 @@ fn write_track_segment<W: Write, E: WaypointExtensions + Default>(
 fn write_waypoint<W: Write, E: WaypointExtensions + Default>(
     tagname: &str,
     waypoint: &Waypoint<E>,
+    version: GpxVersion,
+    precision: TimePrecision,
     writer: &mut EventWriter<W>,
 ) -> GpxResult<()> {
     write_xml_event(
@@ -355,16 +554,18 @@ fn write_waypoint<W: Write, E: WaypointExtensions + Default>(
         writer,
     )?;
     write_value_if_exists("ele", &waypoint.elevation, writer)?;
-    // TODO: write speed if GPX version == 1.0
-    write_time_if_exists(&waypoint.time, writer)?;
+    write_time_if_exists(&waypoint.time, precision, writer)?;
+    if version == GpxVersion::Gpx10 {
+        write_value_if_exists("course", &waypoint.course, writer)?;
+        write_value_if_exists("speed", &waypoint.speed, writer)?;
+    }
+    write_value_if_exists("magvar", &waypoint.magvar, writer)?;
     write_value_if_exists("geoidheight", &waypoint.geoidheight, writer)?;
     write_string_if_exists("name", &waypoint.name, writer)?;
     write_string_if_exists("cmt", &waypoint.comment, writer)?;
     write_string_if_exists("desc", &waypoint.description, writer)?;
     write_string_if_exists("src", &waypoint.source, writer)?;
-    for link in &waypoint.links {
-        write_link(link, writer)?;
-    }
+    write_links(&waypoint.links, version, writer)?;
     write_string_if_exists("sym", &waypoint.symbol, writer)?;
     write_string_if_exists("type", &waypoint.type_, writer)?;
     write_fix_if_exists(&waypoint.fix, writer)?;
@@ -374,6 +575,9 @@ fn write_waypoint<W: Write, E: WaypointExtensions + Default>(
     write_value_if_exists("pdop", &waypoint.pdop, writer)?;
     write_value_if_exists("ageofdgpsdata", &waypoint.dgps_age, writer)?;
     write_value_if_exists("dgpsid", &waypoint.dgpsid, writer)?;
+    if version != GpxVersion::Gpx10 {
+        E::write(&waypoint.extensions, writer)?;
+    }
     write_xml_event(XmlEvent::end_element(), writer)?;
     Ok(())
 }
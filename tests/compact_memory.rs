@@ -0,0 +1,138 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use geo_types::Point;
+
+use gpx::parser::extensions::EmptyExtensions;
+use gpx::{Link, TrackSegment, Waypoint};
+
+/// Counts the bytes allocated and freed by every thread, so the memory taken
+/// by a value is the count after making it minus the count before, whatever
+/// the tests running alongside it allocate.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+    /// The bytes allocated minus those freed, which is negative once the
+    /// thread frees what another allocated.
+    static HELD: Cell<i64> = const { Cell::new(0) };
+}
+
+/// count counts `bytes` allocated, or freed when negative.
+fn count(bytes: i64) {
+    // The counts are gone while the thread is torn down.
+    if bytes > 0 {
+        let _ = ALLOCATED.try_with(|allocated| allocated.set(allocated.get() + bytes as usize));
+    }
+    let _ = HELD.try_with(|held| held.set(held.get() + bytes));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(layout.size() as i64);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        count(-(layout.size() as i64));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Gives the value made by `f` along with the bytes allocated making it.
+fn allocated<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.with(Cell::get);
+    let value = f();
+    (value, ALLOCATED.with(Cell::get) - before)
+}
+
+/// Gives the value made by `f` along with the bytes it still holds, those
+/// allocated making it that were not freed.
+fn retained<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = HELD.with(Cell::get);
+    let value = f();
+    let retained = HELD.with(Cell::get) - before;
+    (value, retained.max(0) as usize)
+}
+
+#[test]
+fn compact_track_segment_halves_memory() {
+    let (segment, segment_bytes) = allocated(|| {
+        let mut segment: TrackSegment<EmptyExtensions> = TrackSegment::new();
+        segment.points.reserve_exact(100_000);
+        for i in 0..100_000 {
+            let i = f64::from(i);
+            segment.points.push(Waypoint::new(Point::new(i * 1e-5, 45.0 + i * 1e-6)));
+        }
+        segment
+    });
+    let (linestring, linestring_bytes) = allocated(|| segment.linestring());
+    let (compact, compact_bytes) = allocated(|| segment.compact());
+
+    assert_eq!(compact.len(), segment.points.len());
+    assert_eq!(compact.len(), linestring.0.len());
+    assert!(segment_bytes >= std::mem::size_of::<Waypoint<EmptyExtensions>>() * 100_000);
+    assert!(
+        compact_bytes * 2 <= segment_bytes,
+        "compact took {} bytes, against {} bytes as a track segment",
+        compact_bytes,
+        segment_bytes
+    );
+    // The coordinates alone, as f64, take twice as much again.
+    assert!(
+        compact_bytes <= linestring_bytes * 55 / 100,
+        "compact took {} bytes, against {} bytes as f64",
+        compact_bytes,
+        linestring_bytes
+    );
+}
+
+#[test]
+fn points_without_links_allocate_nothing_for_them() {
+    // An empty Vec takes its 24 bytes in the point and nothing on the heap,
+    // which a SmallVec<[Link; 1]> would only make larger, holding a whole
+    // Link in every point.
+    let data = r#"<gpx version="1.1"><trk><trkseg><trkpt lat="1" lon="2"/></trkseg></trk></gpx>"#;
+    let (gpx, _) = allocated(|| gpx::read(data.as_bytes()).unwrap());
+    let point = &gpx.tracks[0].segments[0].points[0];
+    assert!(point.links.is_empty());
+    assert_eq!(point.links.capacity(), 0);
+    assert!(std::mem::size_of::<Vec<Link>>() < std::mem::size_of::<Link>());
+
+    let (point, bytes) = allocated(|| Waypoint::<EmptyExtensions>::new(Point::new(2.0, 1.0)));
+    assert_eq!(bytes, 0);
+    assert!(point.links.is_empty());
+}
+
+#[test]
+fn repeated_strings_take_little_of_a_point() {
+    let (sym, src, type_) = ("Flag, Blue", "Garmin eTrex", "Summit");
+    let document = |strings: bool| {
+        let mut gpx = String::from("<gpx version=\"1.1\"><trk><trkseg>");
+        for i in 0..20_000 {
+            gpx.push_str(&format!("<trkpt lat=\"{}\" lon=\"0\">", i % 90));
+            if strings {
+                gpx.push_str(&format!("<src>{}</src><sym>{}</sym><type>{}</type>", src, sym, type_));
+            }
+            gpx.push_str("</trkpt>");
+        }
+        gpx.push_str("</trkseg></trk></gpx>");
+        gpx
+    };
+    let (bare, bare_bytes) = retained(|| gpx::read::<&[u8]>(document(false).as_bytes()).unwrap());
+    let (named, named_bytes) = retained(|| gpx::read::<&[u8]>(document(true).as_bytes()).unwrap());
+    let point = &named.tracks[0].segments[0].points[0];
+    assert_eq!(point.symbol.as_deref(), Some(sym));
+    assert_eq!(bare.points_count(), named.points_count());
+
+    // Each point takes its copies of the strings, which sharing them would
+    // save, but far more for the point itself.
+    let strings = named_bytes - bare_bytes;
+    assert!(strings >= 20_000 * (sym.len() + src.len() + type_.len()));
+    let point_bytes = std::mem::size_of::<Waypoint<EmptyExtensions>>();
+    assert!(strings * 10 < bare_bytes, "{} bytes of strings, {} of points", strings, bare_bytes);
+    assert!(bare_bytes >= 20_000 * point_bytes);
+}
@@ -4,15 +4,24 @@
 
 use std::error::Error;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Read};
+use std::time::{Duration, Instant};
 
 use assert_approx_eq::assert_approx_eq;
 use geo::algorithm::haversine_distance::HaversineDistance;
 use geo::euclidean_length::EuclideanLength;
-use geo_types::{Geometry, Point};
+use geo::HaversineLength;
+use geo_types::{coord, Geometry, Point, Rect};
 use time::{Date, Month, PrimitiveDateTime, Time};
+use xml::reader::XmlEvent;
 
-use gpx::{Fix, read};
+use gpx::{Activity, DeriveOptions, ExtensionsMode, Fix, FixupAction, Invariant, InvariantPolicy, read, read_file, read_with_extensions, read_with_options, read_with_root_handler, read_with_root_handler_with_options, read_with_warnings, read_with_waypoint_callback, Gpx, GpxTrackIter, ParseLimits, ParseOptions, TrackSegment, TrackStyle, Waypoint, XmlParser};
+use gpx::errors::{ErrorKind, GpxError, GpxResult, GpxWarning};
+use gpx::parser::extensions::{
+    DetectedExtensions, EmptyExtensions, GarminExtensions, GarminTrackPoint, GarminTrackPointExtensions, GarminWaypoint,
+    RawExtensions, WaypointExtensions,
+};
+use gpx::parser::{skip_element, verify_starting_tag, Context, TrackingReader};
 
 #[test]
 fn gpx_reader_read_test_badxml() {
@@ -25,6 +34,154 @@ fn gpx_reader_read_test_badxml() {
     assert!(result.is_err());
 }
 
+#[test]
+fn gpx_reader_read_file() {
+    let gpx = read_file("tests/fixtures/wikipedia_example.gpx").unwrap();
+    let file = File::open("tests/fixtures/wikipedia_example.gpx").unwrap();
+    assert_eq!(gpx, read(BufReader::new(file)).unwrap());
+
+    let result = read_file("tests/fixtures/missing.gpx");
+    assert!(matches!(result, Err(GpxError::IoError(err)) if err.kind() == std::io::ErrorKind::NotFound));
+}
+
+#[test]
+fn gpx_reader_read_test_lat_lon() {
+    let gpx = read_file("tests/fixtures/wikipedia_example.gpx").unwrap();
+    let point = &gpx.tracks[0].segments[0].points[0];
+    assert_eq!((point.lat(), point.lon()), (47.644548, -122.326897));
+    assert_eq!(point.point(), Point::new(-122.326897, 47.644548));
+
+    let from_lat_lon: Waypoint<EmptyExtensions> = Waypoint::from_lat_lon(47.644548, -122.326897);
+    let from_lon_lat: Waypoint<EmptyExtensions> = Waypoint::from_lon_lat(-122.326897, 47.644548);
+    assert_eq!(from_lat_lon.point(), point.point());
+    assert_eq!(from_lon_lat.point(), point.point());
+}
+
+/// Extensions counting the elements in them, merged as by default.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ChildCount;
+
+impl WaypointExtensions for ChildCount {
+    type ExtensionsValue = usize;
+
+    fn consume<R: Read, E: WaypointExtensions + Default>(context: &mut Context<R, E>) -> GpxResult<usize> {
+        verify_starting_tag(context, "extensions")?;
+        let mut children = 0;
+        loop {
+            match context.reader().next() {
+                Some(Ok(XmlEvent::StartElement { .. })) => {
+                    children += 1;
+                    skip_element(context, "extensions")?;
+                }
+                Some(Ok(XmlEvent::EndElement { .. })) => return Ok(children),
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(err.into()),
+                None => return Err(GpxError::MissingClosingTag("extensions")),
+            }
+        }
+    }
+}
+
+#[test]
+fn gpx_reader_read_test_repeated_extensions() {
+    let first = "<gpxtpx:TrackPointExtension><gpxtpx:hr>140</gpxtpx:hr></gpxtpx:TrackPointExtension>";
+    let second = "<gpxtpx:TrackPointExtension><gpxtpx:cad>85</gpxtpx:cad></gpxtpx:TrackPointExtension><gpxx:WaypointExtension><gpxx:Depth>3.5</gpxx:Depth></gpxx:WaypointExtension>";
+    let data = format!(
+        r#"<gpx version="1.1" xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1" xmlns:gpxx="http://www.garmin.com/xmlschemas/GpxExtensions/v3"><trk><trkseg><trkpt lat="1" lon="2"><extensions>{}</extensions><extensions>{}</extensions></trkpt></trkseg></trk></gpx>"#,
+        first, second
+    );
+
+    // The schema allows one.
+    let result: Result<Gpx<GarminTrackPointExtensions>, _> = read_with_extensions(data.as_bytes());
+    assert!(matches!(result, Err(GpxError::DuplicateElement("extensions", "trkpt"))));
+
+    let lenient = |data: &str| {
+        let outcome = read_with_warnings::<_, GarminTrackPointExtensions>(data.as_bytes(), ParseOptions::lenient()).unwrap();
+        let warnings: Vec<_> = outcome.warnings.into_iter().map(|warning| warning.warning).collect();
+        assert_eq!(
+            warnings,
+            [GpxWarning::DuplicateElement {
+                element: "extensions",
+                parent: "trkpt",
+            }]
+        );
+        outcome.gpx.tracks[0].segments[0].points[0].extensions.clone()
+    };
+    let track_point = GarminTrackPoint {
+        heart_rate: Some(140),
+        cadence: Some(85),
+        ..Default::default()
+    };
+    assert_eq!(lenient(&data), track_point);
+
+    let gpx: Gpx<DetectedExtensions> = read_with_options(data.as_bytes(), ParseOptions::lenient()).unwrap();
+    let waypoint = GarminWaypoint {
+        depth: Some(3.5),
+        ..Default::default()
+    };
+    assert_eq!(
+        gpx.tracks[0].segments[0].points[0].extensions,
+        DetectedExtensions::Composite(waypoint, track_point)
+    );
+
+    let gpx: Gpx<RawExtensions> = read_with_options(data.as_bytes(), ParseOptions::lenient()).unwrap();
+    let raw = &gpx.tracks[0].segments[0].points[0].extensions;
+    assert_eq!(raw.xml, format!("{}{}", first, second));
+    assert_eq!(raw.namespaces.len(), 2);
+
+    // Without a merge of its own, a consumer keeps the first.
+    let gpx: Gpx<ChildCount> = read_with_options(data.as_bytes(), ParseOptions::lenient()).unwrap();
+    assert_eq!(gpx.tracks[0].segments[0].points[0].extensions, 1);
+}
+
+#[test]
+fn gpx_reader_read_test_byte_order_mark() {
+    let data = std::fs::read("tests/fixtures/wikipedia_example.gpx").unwrap();
+    let mut marked = vec![0xEF, 0xBB, 0xBF];
+    marked.extend_from_slice(&data);
+
+    let gpx = read(data.as_slice()).unwrap();
+    assert_eq!(read(marked.as_slice()).unwrap(), gpx);
+
+    // The mark takes no column.
+    let data = "\u{FEFF}<gpx version=\"1.1\"><wpt lat=\"1\" lon=\"2\"><name>A\u{1}</name></wpt></gpx>";
+    let outcome = read_with_warnings::<_, EmptyExtensions>(data.as_bytes(), ParseOptions::lenient()).unwrap();
+    assert_eq!(outcome.warnings[0].position.unwrap().column, 48);
+}
+
+#[test]
+fn gpx_reader_read_test_control_characters() {
+    let data = "<gpx version=\"1.1\">\n  <wpt lat=\"1\" lon=\"2\"><name>Camp\u{1}site\u{0}</name><desc>Tab\there</desc></wpt>\n</gpx>";
+    assert!(read(data.as_bytes()).is_err());
+
+    let outcome = read_with_warnings::<_, EmptyExtensions>(data.as_bytes(), ParseOptions::lenient()).unwrap();
+    assert_eq!(outcome.gpx.waypoints[0].name.as_deref(), Some("Campsite"));
+    assert_eq!(outcome.gpx.waypoints[0].description.as_deref(), Some("Tab\there"));
+    let warnings: Vec<_> = outcome
+        .warnings
+        .iter()
+        .map(|warning| (warning.warning.clone(), warning.position.unwrap().column))
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            (GpxWarning::ControlCharacter { byte: 1 }, 34),
+            (GpxWarning::ControlCharacter { byte: 0 }, 39),
+        ]
+    );
+}
+
+#[test]
+fn gpx_reader_lenient_utf16() {
+    // The zero bytes of UTF-16 are not control characters to strip.
+    let data = utf16le("<gpx version=\"1.1\"><wpt lat=\"1\" lon=\"2\"><name>Zürich</name></wpt></gpx>");
+    for options in with_xml_parsers(&[ParseOptions::strict(), ParseOptions::lenient()]) {
+        let outcome = read_with_warnings::<_, EmptyExtensions>(data.as_slice(), options).unwrap();
+        assert_eq!(outcome.gpx.waypoints[0].name.as_deref(), Some("Zürich"));
+        assert!(outcome.warnings.is_empty());
+    }
+}
+
 #[test]
 fn gpx_reader_read_test_wikipedia() {
     // Should not give an error, and should have all the correct data.
@@ -68,6 +225,35 @@ fn gpx_reader_read_test_wikipedia() {
     assert_eq!(points[2].elevation, Some(6.87));
 }
 
+#[test]
+fn gpx_reader_unix_time() {
+    let mut gpx = read(BufReader::new(File::open("tests/fixtures/wikipedia_example.gpx").unwrap())).unwrap();
+
+    // 2009-10-17T22:58:43Z and 2009-10-17T18:37:26Z
+    let metadata = gpx.metadata.as_mut().unwrap();
+    assert_eq!(metadata.unix_time(), Some(1_255_820_323));
+    assert_eq!(metadata.unix_time_millis(), Some(1_255_820_323_000));
+    let point = &mut gpx.tracks[0].segments[0].points[0];
+    assert_eq!(point.unix_time(), Some(1_255_804_646));
+    assert_eq!(point.unix_time_millis(), Some(1_255_804_646_000));
+
+    // Setting them back gives the times read.
+    let read_time = point.time;
+    point.set_unix_time(1_255_804_646).unwrap();
+    assert_eq!(point.time, read_time);
+    let read_time = metadata.time;
+    metadata.set_unix_time(1_255_820_323).unwrap();
+    assert_eq!(metadata.time, read_time);
+
+    // A time out of range leaves the one there.
+    metadata.set_unix_time(1_255_820_323_000).unwrap_err();
+    assert_eq!(metadata.time, read_time);
+    let time = gpx::Time::from_unix(-3_600).unwrap();
+    metadata.set_unix_time(-3_600).unwrap();
+    assert_eq!(metadata.time, Some(time));
+    assert_eq!(metadata.unix_time(), Some(-3_600));
+}
+
 #[test]
 fn gpx_reader_read_test_gpsies() {
     // Should not give an error, and should have all the correct data.
@@ -121,6 +307,828 @@ fn gpx_reader_read_test_gpsies() {
     assert_eq!(points[2].elevation, Some(305.0));
 }
 
+#[test]
+fn gpx_reader_length_gpsies() {
+    let file = File::open("tests/fixtures/gpsies_example.gpx").unwrap();
+    let gpx = read(BufReader::new(file)).unwrap();
+
+    // The file records its own length as 6998.29 m in a gpsies extension.
+    assert_approx_eq!(gpx.total_length_meters(), 6998.29, 0.1);
+    #[cfg(feature = "geodesic")]
+    assert_approx_eq!(gpx.total_length_geodesic_meters(), 6998.29, 0.005 * 6998.29);
+}
+
+#[test]
+fn gpx_reader_waypoint_callback() {
+    for fixture in &[
+        "tests/fixtures/garmin-activity.gpx",
+        "tests/fixtures/strava_route_example.gpx",
+        "tests/fixtures/viking_with_route_extensions.gpx",
+    ] {
+        let gpx = read(BufReader::new(File::open(fixture).unwrap())).unwrap();
+        let track_points = gpx.tracks.iter().flat_map(|track| &track.segments);
+        let expected: Vec<Point<f64>> = track_points
+            .flat_map(|segment| &segment.points)
+            .chain(gpx.routes.iter().flat_map(|route| &route.points))
+            .map(|point| point.point())
+            .collect();
+
+        let mut points = Vec::new();
+        let streamed: Gpx<EmptyExtensions> = read_with_waypoint_callback(
+            BufReader::new(File::open(fixture).unwrap()),
+            |point| points.push(point.point()),
+        )
+        .unwrap();
+
+        assert!(!points.is_empty());
+        // The tracks of these documents come before their routes.
+        assert_eq!(points, expected, "{}", fixture);
+
+        // Everything but the points is still read.
+        assert_eq!(streamed.tracks.len(), gpx.tracks.len());
+        assert_eq!(streamed.routes.len(), gpx.routes.len());
+        assert_eq!(streamed.waypoints, gpx.waypoints);
+        assert!(streamed.tracks.iter().all(|track| track
+            .segments
+            .iter()
+            .all(|segment| segment.points.is_empty())));
+        assert!(streamed.routes.iter().all(|route| route.points.is_empty()));
+    }
+}
+
+#[test]
+fn gpx_reader_waypoint_callback_document_order() {
+    let data = r#"<gpx version="1.1">
+        <rte><rtept lat="1.0" lon="1.0"/><rtept lat="2.0" lon="2.0"/></rte>
+        <trk><trkseg><trkpt lat="3.0" lon="3.0"/></trkseg><trkseg><trkpt lat="4.0" lon="4.0"/></trkseg></trk>
+        <rte><rtept lat="5.0" lon="5.0"/></rte>
+        <trk><trkseg><trkpt lat="0.5" lon="0.5"/></trkseg></trk>
+    </gpx>"#;
+    let mut points = Vec::new();
+    let _: Gpx<EmptyExtensions> = read_with_waypoint_callback(data.as_bytes(), |point| points.push(point.point().y())).unwrap();
+    assert_eq!(points, [1.0, 2.0, 3.0, 4.0, 5.0, 0.5]);
+}
+
+#[test]
+fn gpx_reader_read_test_styled_tracks() {
+    let file = File::open("tests/fixtures/styled_tracks.gpx").unwrap();
+    let gpx = read(BufReader::new(file)).unwrap();
+    assert_eq!(gpx.tracks.len(), 3);
+
+    assert_eq!(
+        gpx.tracks[0].style(),
+        Some(&TrackStyle {
+            color: Some("#1E90FF".into()),
+            opacity: Some(0.75),
+            width: Some(3.0),
+        })
+    );
+    assert_eq!(
+        gpx.tracks[1].style(),
+        Some(&TrackStyle {
+            color: Some("#800000".into()),
+            ..Default::default()
+        })
+    );
+    assert_eq!(gpx.tracks[2].style(), None);
+    assert_eq!(gpx.tracks[1].segments[0].points.len(), 2);
+}
+
+#[test]
+fn gpx_reader_read_test_messy_warnings() {
+    let file = File::open("tests/fixtures/messy.gpx").unwrap();
+    assert!(read(BufReader::new(File::open("tests/fixtures/messy.gpx").unwrap())).is_err());
+
+    let outcome =
+        read_with_warnings::<_, EmptyExtensions>(BufReader::new(file), ParseOptions::lenient()).unwrap();
+    let gpx = outcome.gpx;
+    assert!(gpx.metadata.unwrap().time.is_none());
+    assert_eq!(gpx.waypoints[0].point(), Point::new(-170.0, 90.0));
+    let points = &gpx.tracks[0].segments[0].points;
+    assert_eq!(points.len(), 2);
+    assert!(points[0].time.is_some());
+    assert!(points[1].time.is_none());
+
+    let warnings: Vec<_> = outcome
+        .warnings
+        .iter()
+        .map(|w| (w.position.unwrap().line, w.warning.clone()))
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            (5, GpxWarning::MalformedTime { value: "last tuesday".into() }),
+            (
+                7,
+                GpxWarning::OutOfRange {
+                    element: "wpt",
+                    coordinate: "latitude",
+                    value: 95.5,
+                    replacement: 90.0,
+                }
+            ),
+            (
+                7,
+                GpxWarning::OutOfRange {
+                    element: "wpt",
+                    coordinate: "longitude",
+                    value: 190.0,
+                    replacement: -170.0,
+                }
+            ),
+            (
+                12,
+                GpxWarning::UnknownElement {
+                    element: "color".into(),
+                    parent: "track",
+                }
+            ),
+            (
+                16,
+                GpxWarning::UnknownElement {
+                    element: "heartrate".into(),
+                    parent: "waypoint",
+                }
+            ),
+            (
+                18,
+                GpxWarning::SkippedPoint {
+                    element: "trkpt",
+                    missing: "latitude",
+                }
+            ),
+            (22, GpxWarning::MalformedTime { value: "2020-13-45T99:00:00Z".into() }),
+        ]
+    );
+}
+
+#[test]
+fn gpx_derived_documents_keep_metadata() {
+    let file = File::open("tests/fixtures/with_personal_data.gpx").unwrap();
+    let gpx = read(BufReader::new(file)).unwrap();
+    let bounds = Rect::new(coord! { x: -1.0, y: 51.0 }, coord! { x: 0.0, y: 52.0 });
+
+    let derived = [gpx.crop_by_bounds(&bounds), gpx.simplified(5.0)];
+    for derived in &derived {
+        assert_eq!(derived.creator, gpx.creator);
+        assert_eq!(derived.metadata, gpx.metadata);
+        let metadata = derived.metadata.as_ref().unwrap();
+        assert!(metadata.copyright.is_some());
+        assert!(!metadata.links.is_empty());
+        assert!(!derived.tracks[0].links.is_empty());
+    }
+
+    let options = DeriveOptions { provenance: true };
+    let simplified = gpx.simplified_with(5.0, &options);
+    let description = simplified.metadata.unwrap().description.unwrap();
+    assert!(description.ends_with("simplified with epsilon=5 m"));
+    let cropped = gpx.crop_by_bounds_with(&bounds, &options);
+    let description = cropped.metadata.unwrap().description.unwrap();
+    assert!(description.ends_with("cropped to bounds (-1, 51) - (0, 52)"));
+}
+
+#[test]
+fn gpx_reader_read_test_marine_waypoints() {
+    let file = File::open("tests/fixtures/marine_waypoint.gpx").unwrap();
+    let gpx: Gpx<GarminExtensions> = read_with_extensions(BufReader::new(file)).unwrap();
+    assert_eq!(gpx.waypoints.len(), 3);
+
+    assert_eq!(
+        gpx.waypoints[0].extensions,
+        GarminWaypoint {
+            proximity: Some(30.0),
+            depth: Some(8.4),
+            display_mode: Some("SymbolAndName".into()),
+        }
+    );
+    assert_eq!(gpx.waypoints[1].extensions.depth, Some(12.0));
+    assert_eq!(gpx.waypoints[2].extensions, GarminWaypoint::default());
+
+    let mut written = Vec::new();
+    gpx::write(&gpx, &mut written).unwrap();
+    let reread: Gpx<GarminExtensions> = read_with_extensions(written.as_slice()).unwrap();
+    assert_eq!(reread.waypoints, gpx.waypoints);
+}
+
+#[test]
+fn gpx_reader_read_test_skipped_extensions() {
+    let file = File::open("tests/fixtures/marine_waypoint.gpx").unwrap();
+    let options = ParseOptions::strict().with_extensions(ExtensionsMode::Skip);
+    let gpx: Gpx<GarminExtensions> = read_with_options(BufReader::new(file), options).unwrap();
+
+    let file = File::open("tests/fixtures/marine_waypoint.gpx").unwrap();
+    let mut parsed: Gpx<GarminExtensions> = read_with_extensions(BufReader::new(file)).unwrap();
+    for waypoint in &mut parsed.waypoints {
+        waypoint.extensions = GarminWaypoint::default();
+    }
+    assert_eq!(gpx, parsed);
+}
+
+#[test]
+fn gpx_reader_read_test_detected_extensions() {
+    use gpx::extensions::{detect, read_with_detected_extensions, DetectedExtensions, KnownSchema};
+    use gpx::parser::extensions::GarminTrackPoint;
+
+    let detected = |fixture: &str| detect(File::open(fixture).unwrap()).unwrap();
+    assert_eq!(
+        detected("tests/fixtures/garmin_with_extensions.gpx"),
+        [KnownSchema::GarminTrackPointV1, KnownSchema::GarminGpxx]
+    );
+    assert_eq!(
+        detected("tests/fixtures/viking_with_route_extensions.gpx"),
+        [KnownSchema::GarminTrackPointV2, KnownSchema::GarminGpxx]
+    );
+    assert_eq!(detected("tests/fixtures/marine_waypoint.gpx"), [KnownSchema::GarminGpxx]);
+    assert_eq!(detected("tests/fixtures/raw_extensions.gpx"), [KnownSchema::GarminTrackPointV1]);
+    assert!(detected("tests/fixtures/gpsies_example.gpx").is_empty());
+
+    let file = File::open("tests/fixtures/marine_waypoint.gpx").unwrap();
+    let gpx = read_with_detected_extensions(BufReader::new(file)).unwrap();
+    let file = File::open("tests/fixtures/marine_waypoint.gpx").unwrap();
+    let garmin: Gpx<GarminExtensions> = read_with_extensions(BufReader::new(file)).unwrap();
+    assert_eq!(
+        gpx.waypoints[0].extensions,
+        DetectedExtensions::GarminWaypoint(garmin.waypoints[0].extensions.clone())
+    );
+    assert_eq!(gpx.waypoints[2].extensions, DetectedExtensions::None);
+
+    let file = File::open("tests/fixtures/raw_extensions.gpx").unwrap();
+    let gpx = read_with_detected_extensions(BufReader::new(file)).unwrap();
+    let points = &gpx.tracks[0].segments[0].points;
+    assert_eq!(
+        points[0].extensions,
+        DetectedExtensions::GarminTrackPoint(GarminTrackPoint {
+            heart_rate: Some(142),
+            ..Default::default()
+        })
+    );
+    assert_eq!(points[1].extensions, DetectedExtensions::None);
+
+    let mut written = Vec::new();
+    gpx::write(&gpx, &mut written).unwrap();
+    let reread = read_with_detected_extensions(written.as_slice()).unwrap();
+    assert_eq!(reread.tracks, gpx.tracks);
+}
+
+#[cfg(feature = "encoding")]
+#[test]
+fn gpx_reader_read_test_windows1252() {
+    let file = File::open("tests/fixtures/windows1252.gpx").unwrap();
+    let gpx = read(gpx::TranscodingReader::new(BufReader::new(file))).unwrap();
+    assert_eq!(gpx.creator.as_deref(), Some("Carto Export 2.3"));
+    assert_eq!(gpx.waypoints.len(), 2);
+
+    assert_eq!(gpx.waypoints[0].name.as_deref(), Some("Cathédrale Notre-Dame"));
+    assert_eq!(
+        gpx.waypoints[0].description.as_deref(),
+        Some("Parvis, côté île de la Cité – rendez-vous")
+    );
+    assert_eq!(gpx.waypoints[1].name.as_deref(), Some("Zürich Hauptbahnhof"));
+}
+
+#[cfg(feature = "nmea")]
+#[test]
+fn nmea_reader_read_test_logger() {
+    let file = File::open("tests/fixtures/logger.nmea").unwrap();
+    let outcome = gpx::nmea::read_with_warnings(BufReader::new(file), ParseOptions::default()).unwrap();
+
+    // The logger had no fix yet at the first second.
+    assert_eq!(outcome.warnings.len(), 1);
+    assert_eq!(outcome.warnings[0].position.unwrap().line, 2);
+    assert_eq!(
+        outcome.warnings[0].warning,
+        GpxWarning::SkippedPoint {
+            element: "RMC",
+            missing: "latitude",
+        }
+    );
+
+    let gpx = outcome.gpx;
+    assert_eq!(gpx.tracks.len(), 1);
+    assert_eq!(gpx.tracks[0].segments.len(), 1);
+    let points = &gpx.tracks[0].segments[0].points;
+    assert_eq!(points.len(), 8);
+
+    let point = &points[0];
+    assert_approx_eq!(point.point().y(), 47.3702);
+    assert_approx_eq!(point.point().x(), 8.54015);
+    assert_eq!(point.elevation, Some(410.9));
+    assert_eq!(point.geoidheight, Some(48.0));
+    assert_eq!(point.fix, Some(Fix::ThreeDimensional));
+    assert_eq!(point.sat, Some(7));
+    assert_eq!(point.hdop, Some(0.9));
+    assert_approx_eq!(point.speed.unwrap(), 2.675, 1e-3);
+    assert_eq!(point.course, Some(32.4));
+    assert_eq!(point.time.unwrap().format().unwrap(), "2024-05-01T06:30:01.000000000Z");
+
+    let last = &points[7];
+    assert_approx_eq!(last.point().y(), 47.0 + 22.296 / 60.0);
+    assert_approx_eq!(last.point().x(), 8.0 + 32.472 / 60.0);
+    assert_eq!(last.elevation, Some(413.7));
+    assert_eq!(last.time.unwrap().format().unwrap(), "2024-05-01T06:30:08.000000000Z");
+}
+
+#[cfg(feature = "tcx")]
+#[test]
+fn tcx_reader_read_test_two_laps() {
+    use gpx::parser::extensions::GarminTrackPointExtensions;
+
+    let file = File::open("tests/fixtures/two_laps.tcx").unwrap();
+    let outcome = gpx::tcx::read_with_warnings(BufReader::new(file), ParseOptions::default()).unwrap();
+    let gpx = outcome.gpx;
+
+    assert_eq!(gpx.creator.as_deref(), Some("Forerunner 255"));
+    let start = gpx.metadata.as_ref().and_then(|metadata| metadata.time).unwrap();
+    assert_eq!(start.format().unwrap(), "2024-05-01T06:30:00.000000000Z");
+
+    assert_eq!(gpx.tracks.len(), 1);
+    let track = &gpx.tracks[0];
+    assert_eq!(track.activity(), Some(Activity::Running));
+    assert_eq!(track.segments.len(), 2);
+
+    // The first point of the first lap has no position yet.
+    assert_eq!(outcome.warnings.len(), 1);
+    assert_eq!(
+        outcome.warnings[0].warning,
+        GpxWarning::SkippedPoint {
+            element: "Trackpoint",
+            missing: "LatitudeDegrees",
+        }
+    );
+    let heart_rates = |segment: &TrackSegment<GarminTrackPointExtensions>| -> Vec<Option<u32>> {
+        segment.points.iter().map(|point| point.extensions.heart_rate).collect()
+    };
+    assert_eq!(heart_rates(&track.segments[0]), [Some(101), Some(118)]);
+    // The tracks of a lap, split by a pause, are joined.
+    assert_eq!(heart_rates(&track.segments[1]), [Some(131), Some(137)]);
+
+    let point = &track.segments[0].points[0];
+    assert_eq!(point.point().y(), 47.3769);
+    assert_eq!(point.point().x(), 8.5417);
+    assert_eq!(point.elevation, Some(408.2));
+    assert_eq!(point.speed, Some(2.6));
+    assert_eq!(point.extensions.cadence, Some(82));
+    assert_eq!(track.segments[1].points[1].extensions.cadence, Some(85));
+
+    // Written as GPX, the heart rates are kept in Garmin's extension.
+    let mut written: Vec<u8> = Vec::new();
+    gpx::write(&gpx, &mut written).unwrap();
+    let reread: Gpx<GarminTrackPointExtensions> = read_with_extensions(written.as_slice()).unwrap();
+    assert_eq!(heart_rates(&reread.tracks[0].segments[0]), [Some(101), Some(118)]);
+    assert_eq!(reread.tracks[0].segments[0].points[0].extensions, point.extensions);
+}
+
+#[test]
+fn gpx_reader_root_handler() {
+    let data = "<gpx version=\"1.1\" creator=\"chartplotter\">
+        <wpt lat=\"50.1\" lon=\"-4.2\"><name>Harbour</name></wpt>
+        <route_plan speed=\"6\">
+            <leg to=\"Harbour\"/>
+            <leg to=\"Point\"><note>tide</note></leg>
+            <leg to=\"Bay\"/>
+        </route_plan>
+        <trk><trkseg><trkpt lat=\"50.1\" lon=\"-4.2\"/></trkseg></trk>
+        <tides/>
+    </gpx>";
+
+    let mut plans = 0;
+    let mut legs = 0;
+    let gpx = read_with_root_handler::<_, EmptyExtensions, _>(data.as_bytes(), |name, context| {
+        if name != "route_plan" {
+            return Ok(false);
+        }
+        plans += 1;
+        context.reader().next();
+        loop {
+            match context.reader().next() {
+                Some(Ok(XmlEvent::StartElement { .. })) => {
+                    legs += 1;
+                    skip_element(context, "leg")?;
+                }
+                Some(Ok(XmlEvent::EndElement { .. })) => return Ok(true),
+                Some(Ok(_)) => {}
+                _ => return Err(GpxError::MissingClosingTag("route_plan")),
+            }
+        }
+    });
+
+    // The handler declines the unknown <tides/>, which strict parsing refuses.
+    assert!(matches!(gpx, Err(GpxError::InvalidChildElement(ref child, "gpx")) if child == "tides"));
+    assert_eq!((plans, legs), (1, 3));
+
+    let data = data.replace("<tides/>", "");
+    let gpx = read_with_root_handler::<_, EmptyExtensions, _>(data.as_bytes(), |name, context| {
+        assert_eq!(name, "route_plan");
+        context.reader().next();
+        skip_element(context, "route_plan")?;
+        Ok(true)
+    })
+    .unwrap();
+    assert_eq!(gpx.creator.as_deref(), Some("chartplotter"));
+    assert_eq!(gpx.waypoints[0].name.as_deref(), Some("Harbour"));
+    assert_eq!(gpx.tracks[0].segments[0].points.len(), 1);
+}
+
+/// skip_route_plan is a root handler taking the `route_plan` elements, named
+/// as a function rather than a closure.
+fn skip_route_plan(name: &str, context: &mut Context<TrackingReader<&[u8]>, EmptyExtensions>) -> GpxResult<bool> {
+    if name != "route_plan" {
+        return Ok(false);
+    }
+    context.reader().next();
+    skip_element(context, "route_plan")?;
+    Ok(true)
+}
+
+#[test]
+fn gpx_reader_root_handler_with_options() {
+    let data = "<gpx version=\"1.1\"><route_plan/><tides/><wpt lat=\"1\" lon=\"2\"/></gpx>";
+
+    let result = read_with_root_handler(data.as_bytes(), skip_route_plan);
+    assert!(matches!(result, Err(GpxError::InvalidChildElement(ref child, "gpx")) if child == "tides"));
+
+    // Leniently, the element the handler declines is skipped.
+    let gpx = read_with_root_handler_with_options(data.as_bytes(), ParseOptions::lenient(), skip_route_plan).unwrap();
+    assert_eq!(gpx.waypoints.len(), 1);
+}
+
+#[test]
+fn gpx_reader_read_from_memory() {
+    // Without a filesystem, the bytes of a document are a reader themselves.
+    let bytes = std::fs::read("tests/fixtures/wikipedia_example.gpx").unwrap();
+    let gpx = read(&bytes[..]).unwrap();
+    assert_eq!(gpx.tracks[0].name.as_deref(), Some("Example GPX Document"));
+    assert_eq!(gpx.tracks[0].segments[0].points.len(), 3);
+}
+
+#[test]
+fn gpx_reader_read_test_comments() {
+    let file = File::open("tests/fixtures/with_comments.gpx").unwrap();
+    let gpx: Gpx<EmptyExtensions> = read_with_options(BufReader::new(file), ParseOptions::strict().with_comments()).unwrap();
+
+    // Comments inside the elements of the document are not collected, nor do
+    // they end up in the text of those elements.
+    assert_eq!(gpx.comments, [" generated by X ", " source: logger serial 0042 ", " recorded on a ferry "]);
+    assert_eq!(gpx.tracks[0].name.as_deref(), Some("Crossing"));
+    assert_eq!(gpx.tracks[0].segments[0].points.len(), 2);
+    assert_eq!(gpx.tracks[0].segments[0].points[0].elevation, Some(2.0));
+
+    // By default they are discarded.
+    let file = File::open("tests/fixtures/with_comments.gpx").unwrap();
+    let without: Gpx<EmptyExtensions> = read(BufReader::new(file)).unwrap();
+    assert!(without.comments.is_empty());
+    assert_eq!(without.tracks, gpx.tracks);
+}
+
+#[test]
+fn gpx_reader_limits() {
+    // Entities expanding into other entities, a billion times "lol" in all,
+    // must be rejected rather than exhaust memory.
+    let file = File::open("tests/fixtures/billion-laughs.gpx").unwrap();
+    let result: Result<Gpx<EmptyExtensions>, GpxError> = read_with_options(BufReader::new(file), ParseOptions::lenient());
+    assert!(matches!(result, Err(GpxError::LimitExceeded("entity expansion"))));
+
+    // So must elements nested too deeply in content that is skipped.
+    let nested = format!(
+        "<gpx version=\"1.1\"><wpt lat=\"0\" lon=\"0\"><extensions>{}{}</extensions></wpt></gpx>",
+        "<a>".repeat(300),
+        "</a>".repeat(300)
+    );
+    let result: Result<Gpx<EmptyExtensions>, GpxError> = read(nested.as_bytes());
+    assert!(matches!(result, Err(GpxError::LimitExceeded("element depth"))));
+    let result: Result<Gpx<RawExtensions>, GpxError> = read_with_extensions(nested.as_bytes());
+    assert!(matches!(result, Err(GpxError::LimitExceeded("element depth"))));
+
+    let limits = ParseLimits {
+        max_element_depth: 1000,
+        ..Default::default()
+    };
+    let result: Result<Gpx<EmptyExtensions>, GpxError> = read_with_options(nested.as_bytes(), ParseOptions::strict().with_limits(limits));
+    assert!(result.is_ok());
+}
+
+#[test]
+fn gpx_reader_namespaces() {
+    let file = File::open("tests/fixtures/garmin_with_extensions.gpx").unwrap();
+    let gpx: Gpx<EmptyExtensions> = read(BufReader::new(file)).unwrap();
+
+    let namespace = |prefix: &str| {
+        gpx.namespaces
+            .iter()
+            .find(|(other, _)| other == prefix)
+            .map(|(_, uri)| uri.as_str())
+    };
+    assert_eq!(namespace(""), Some("http://www.topografix.com/GPX/1/1"));
+    assert_eq!(namespace("gpxtpx"), Some("http://www.garmin.com/xmlschemas/TrackPointExtension/v1"));
+    assert_eq!(namespace("xml"), None);
+    assert_eq!(gpx.namespaces.len(), 6);
+
+    assert_eq!(gpx.schema_locations.len(), 5);
+    assert_eq!(
+        gpx.schema_locations[0],
+        (
+            String::from("http://www.topografix.com/GPX/1/1"),
+            String::from("http://www.topografix.com/GPX/1/1/gpx.xsd")
+        )
+    );
+
+    // Written back, they are read the same.
+    let mut written = Vec::new();
+    gpx::write(&gpx, &mut written).unwrap();
+    let again: Gpx<EmptyExtensions> = read(written.as_slice()).unwrap();
+    assert_eq!(again.namespaces, gpx.namespaces);
+    assert_eq!(again.schema_locations, gpx.schema_locations);
+}
+
+#[test]
+fn gpx_reader_error_kinds() {
+    let kind = |data: &str| read(data.as_bytes()).unwrap_err().kind();
+    // Not well-formed XML: an unclosed attribute, a missing closing tag and
+    // a document cut off before its closing tags.
+    assert_eq!(kind("<gpx version=\"1.1\"><wpt lat=\"1 lon=\"2\"/></gpx>"), ErrorKind::Xml);
+    assert_eq!(kind("<gpx version=\"1.1\"><wpt lat=\"1\" lon=\"2\"></gpx>"), ErrorKind::Xml);
+    assert_eq!(kind("<gpx version=\"1.1\"><trk><trkseg>"), ErrorKind::Xml);
+    assert_eq!(kind("<gpx version=\"1.1\"><wpt lon=\"2\"/></gpx>"), ErrorKind::InvalidStructure);
+    assert_eq!(kind("<gpx version=\"1.1\"><wpt lat=\"1\" lon=\"2\"><trk/></wpt></gpx>"), ErrorKind::InvalidStructure);
+    assert_eq!(kind("<gpx version=\"1.1\"><wpt lat=\"91\" lon=\"2\"/></gpx>"), ErrorKind::InvalidValue);
+    assert_eq!(kind("<gpx version=\"1.1\"><wpt lat=\"1\" lon=\"2\"><ele>high</ele></wpt></gpx>"), ErrorKind::InvalidValue);
+    assert_eq!(kind("<gpx version=\"2.0\"></gpx>"), ErrorKind::Unsupported);
+
+    // Errors of the XML parser are kept as the source, from whichever
+    // element met them.
+    let err = read("<gpx version=\"1.1\"><trk><trkseg></trk></gpx>".as_bytes()).unwrap_err();
+    assert!(err.source().unwrap().to_string().contains("Unexpected closing tag"), "{}", err);
+
+    /// Fails every read.
+    struct Failing;
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(std::io::ErrorKind::Other, "disk on fire"))
+        }
+    }
+    let err = read(Failing).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Io);
+    assert!(err.source().unwrap().to_string().contains("disk on fire"), "{}", err);
+}
+
+#[test]
+fn gpx_reader_malformed_entity() {
+    // The message tells both the element and what the XML parser found.
+    let data = "<gpx version=\"1.1\"><metadata><copyright author=\"me\">&bogus;</copyright></metadata></gpx>";
+    let err = read(data.as_bytes()).unwrap_err();
+    assert!(matches!(err, GpxError::EventParsingError("copyright", _)), "{:?}", err);
+    assert_eq!(err.kind(), ErrorKind::Xml);
+    let message = err.to_string();
+    assert!(message.contains("`copyright`") && message.contains("bogus"), "{}", message);
+
+    let data = "<gpx version=\"1.1\"><wpt lat=\"1\" lon=\"2\"><name>&bogus;</name></wpt></gpx>";
+    let err = read(data.as_bytes()).unwrap_err();
+    assert!(matches!(err, GpxError::EventParsingError("name", _)), "{:?}", err);
+    assert!(err.source().unwrap().to_string().contains("bogus"), "{}", err);
+}
+
+#[test]
+fn gpx_reader_not_a_number() {
+    let point = |children: &str| format!("<gpx version=\"1.1\"><wpt lat=\"1\" lon=\"2\">{}</wpt></gpx>", children);
+    let cases = [
+        (point("<ele>12O.5</ele>"), "ele", "12O.5"),
+        (point("<magvar>east</magvar>"), "magvar", "east"),
+        (point("<geoidheight>4O</geoidheight>"), "geoidheight", "4O"),
+        (point("<sat>seven</sat>"), "sat", "seven"),
+        (point("<hdop>1.2.3</hdop>"), "hdop", "1.2.3"),
+        (point("<vdop>-</vdop>"), "vdop", "-"),
+        (point("<pdop>high</pdop>"), "pdop", "high"),
+        (point("<ageofdgpsdata>1s</ageofdgpsdata>"), "ageofdgpsdata", "1s"),
+        (point("<dgpsid>x12</dgpsid>"), "dgpsid", "x12"),
+        (
+            "<gpx version=\"1.0\"><wpt lat=\"1\" lon=\"2\"><speed>fast</speed></wpt></gpx>".to_string(),
+            "speed",
+            "fast",
+        ),
+        (
+            "<gpx version=\"1.0\"><wpt lat=\"1\" lon=\"2\"><course>N</course></wpt></gpx>".to_string(),
+            "course",
+            "N",
+        ),
+        ("<gpx version=\"1.1\"><wpt lat=\"1O\" lon=\"2\"/></gpx>".to_string(), "lat", "1O"),
+        ("<gpx version=\"1.1\"><wpt lat=\"1\" lon=\"2,5\"/></gpx>".to_string(), "lon", "2,5"),
+        (
+            "<gpx version=\"1.1\"><metadata><bounds minlat=\"1\" maxlat=\"2\" minlon=\"O\" maxlon=\"4\"/></metadata></gpx>".to_string(),
+            "minlon",
+            "O",
+        ),
+        (
+            "<gpx version=\"1.1\"><metadata><copyright author=\"me\"><year>MMXX</year></copyright></metadata></gpx>".to_string(),
+            "year",
+            "MMXX",
+        ),
+    ];
+    for (data, element, value) in &cases {
+        let err = read(data.as_bytes()).unwrap_err();
+        assert!(matches!(&err, GpxError::NotANumber(v, e) if v == value && e == element), "{:?}", err);
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+        let message = err.to_string();
+        assert!(message.contains(element) && message.contains(value), "{}", message);
+    }
+
+    // A year that is not a number used to be dropped silently; now only in
+    // lenient mode, with a warning.
+    let (data, _, _) = &cases[cases.len() - 1];
+    let outcome = read_with_warnings::<_, EmptyExtensions>(data.as_bytes(), ParseOptions::lenient()).unwrap();
+    assert_eq!(outcome.gpx.metadata.unwrap().copyright.unwrap().year, None);
+    assert_eq!(
+        outcome.warnings[0].warning,
+        GpxWarning::InvalidNumber {
+            element: "year",
+            value: "MMXX".into()
+        }
+    );
+}
+
+#[test]
+fn gpx_reader_repair_entities() {
+    let data = "<gpx version=\"1.1\">
+<wpt lat=\"1\" lon=\"2\">
+  <desc><![CDATA[fish & chips]]></desc>
+  <link href=\"http://example.com/a?b=1&c=2&amp;d=3\"><text>Tom &amp; Jerry</text></link>
+</wpt>
+</gpx>";
+    assert!(read(data.as_bytes()).is_err());
+
+    let outcome = read_with_warnings::<_, EmptyExtensions>(data.as_bytes(), ParseOptions::strict().with_entity_repair()).unwrap();
+    let waypoint = &outcome.gpx.waypoints[0];
+    assert_eq!(waypoint.links[0].href, "http://example.com/a?b=1&c=2&d=3");
+    assert_eq!(waypoint.links[0].text.as_deref(), Some("Tom & Jerry"));
+    assert_eq!(waypoint.description.as_deref(), Some("fish & chips"));
+
+    // Only the raw ampersand of the href is repaired.
+    let offset = data.find("&c=2").unwrap();
+    assert_eq!(outcome.warnings.len(), 1);
+    assert_eq!(outcome.warnings[0].warning, GpxWarning::RawAmpersand { offset });
+    let position = outcome.warnings[0].position.unwrap();
+    assert_eq!((position.line, position.column), (4, 39));
+
+    let gpx: Gpx<EmptyExtensions> = read_with_options(data.as_bytes(), ParseOptions::lenient().with_entity_repair()).unwrap();
+    assert_eq!(gpx, outcome.gpx);
+}
+
+/// utf16le encodes `text` in UTF-16LE, with a byte order mark.
+fn utf16le(text: &str) -> Vec<u8> {
+    let mut bytes = vec![0xFF, 0xFE];
+    bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+    bytes
+}
+
+/// with_xml_parsers gives each of `options` with each XML parser the crate
+/// is built with.
+fn with_xml_parsers(options: &[ParseOptions]) -> Vec<ParseOptions> {
+    let parsers = [
+        XmlParser::XmlRs,
+        #[cfg(feature = "quick-xml")]
+        XmlParser::QuickXml,
+    ];
+    options
+        .iter()
+        .flat_map(|options| parsers.iter().map(move |&parser| options.clone().with_xml_parser(parser)))
+        .collect()
+}
+
+#[test]
+fn gpx_reader_repair_entities_utf16() {
+    // Ampersands in UTF-16 are not single bytes, so the input is left alone.
+    let data = utf16le("<gpx version=\"1.1\"><wpt lat=\"1\" lon=\"2\"><name>a &amp; b</name></wpt></gpx>");
+    for options in with_xml_parsers(&[ParseOptions::strict(), ParseOptions::strict().with_entity_repair()]) {
+        let outcome = read_with_warnings::<_, EmptyExtensions>(data.as_slice(), options).unwrap();
+        assert_eq!(outcome.gpx.waypoints[0].name.as_deref(), Some("a & b"));
+        assert!(outcome.warnings.is_empty());
+    }
+}
+
+#[test]
+fn gpx_reader_max_points() {
+    let mut oversize = String::from("<gpx version=\"1.1\"><wpt lat=\"0\" lon=\"0\"/><rte><rtept lat=\"0\" lon=\"0\"/></rte>");
+    for _ in 0..100 {
+        oversize.push_str("<trk><trkseg><trkpt lat=\"1\" lon=\"2\"/><trkpt lat=\"3\" lon=\"4\"/></trkseg></trk>");
+    }
+    oversize.push_str("</gpx>");
+
+    let limits = |max_points| ParseLimits {
+        max_points: Some(max_points),
+        ..Default::default()
+    };
+    let limited = |max_points| ParseOptions::strict().with_limits(limits(max_points));
+    let result: Result<Gpx<EmptyExtensions>, GpxError> = read_with_options(oversize.as_bytes(), limited(201));
+    assert!(matches!(result, Err(GpxError::LimitExceeded("points"))));
+    let result: Result<Gpx<EmptyExtensions>, GpxError> = read_with_options(oversize.as_bytes(), ParseOptions::lenient().with_limits(limits(201)));
+    assert!(matches!(result, Err(GpxError::LimitExceeded("points"))));
+
+    let gpx: Gpx<EmptyExtensions> = read_with_options(oversize.as_bytes(), limited(202)).unwrap();
+    assert_eq!(gpx.tracks.len(), 100);
+    let gpx: Gpx<EmptyExtensions> = read(oversize.as_bytes()).unwrap();
+    assert_eq!(gpx.tracks.len(), 100);
+}
+
+#[test]
+fn gpx_reader_timeout() {
+    let points = 200_000;
+    let mut huge = String::from("<gpx version=\"1.1\"><trk><trkseg>");
+    for i in 0..points {
+        huge.push_str(&format!("<trkpt lat=\"{}\" lon=\"0\"><ele>1</ele></trkpt>", i % 90));
+    }
+    huge.push_str("</trkseg></trk></gpx>");
+
+    let options = ParseOptions::strict().with_timeout(Duration::from_nanos(1));
+    let started = Instant::now();
+    let result: Result<Gpx<EmptyExtensions>, GpxError> = read_with_options(huge.as_bytes(), options.clone());
+    assert!(started.elapsed() < Duration::from_secs(1));
+    match result {
+        Err(GpxError::DeadlineExceeded { bytes, points: parsed }) => {
+            assert!(parsed > 0 && parsed < points);
+            let bytes = bytes.unwrap();
+            assert!(bytes > 0 && bytes < huge.len() as u64);
+        }
+        other => panic!("expected the deadline to pass, got {:?}", other),
+    }
+
+    // Skipped extensions are checked too.
+    let mut skipped = String::from("<gpx version=\"1.1\"><wpt lat=\"0\" lon=\"0\"><extensions>");
+    skipped.push_str(&"<a/>".repeat(points as usize));
+    skipped.push_str("</extensions></wpt></gpx>");
+    let result: Result<Gpx<EmptyExtensions>, GpxError> = read_with_options(skipped.as_bytes(), options.clone());
+    assert!(matches!(result, Err(GpxError::DeadlineExceeded { points: 0, .. })));
+    let result: Result<Gpx<RawExtensions>, GpxError> = read_with_options(skipped.as_bytes(), options);
+    assert!(matches!(result, Err(GpxError::DeadlineExceeded { points: 0, .. })));
+
+    let options = ParseOptions::strict().with_timeout(Duration::from_secs(60));
+    let result: Gpx<EmptyExtensions> = read_with_options(huge.as_bytes(), options).unwrap();
+    assert_eq!(result.tracks[0].segments[0].points.len(), points as usize);
+}
+
+#[test]
+fn gpx_reader_read_test_garmin_symbols() {
+    let file = File::open("tests/fixtures/garmin_symbols.gpx").unwrap();
+    let gpx = read(BufReader::new(file)).unwrap();
+
+    // Symbols are kept verbatim, commas and spaces included.
+    let symbols: Vec<_> = gpx.waypoints.iter().map(|waypoint| waypoint.symbol.as_deref()).collect();
+    assert_eq!(
+        symbols,
+        [Some("Flag, Blue"), Some("Fishing Hot Spot Facility"), Some("Navaid, White/Red")]
+    );
+    let symbols: Vec<_> = gpx.routes[0].points.iter().map(|point| point.symbol.as_deref()).collect();
+    assert_eq!(symbols, [Some("Flag, Blue"), Some("Pin,  Green")]);
+}
+
+#[test]
+fn gpx_reader_post_normalize() {
+    let data = r#"<gpx version="1.1">
+        <rte><name>Empty</name></rte>
+        <trk>
+            <trkseg></trkseg>
+            <trkseg><trkpt lat="1" lon="2"><ele>NaN</ele><link href=""/></trkpt></trkseg>
+        </trk>
+    </gpx>"#;
+
+    let options = ParseOptions {
+        post_normalize: Some(InvariantPolicy::DropEmpty),
+        ..Default::default()
+    };
+    let outcome = read_with_warnings::<_, EmptyExtensions>(data.as_bytes(), options).unwrap();
+    let fixups: Vec<_> = outcome
+        .warnings
+        .iter()
+        .map(|warning| match &warning.warning {
+            GpxWarning::Normalized(fixup) => (fixup.path.as_str(), fixup.invariant, fixup.action),
+            other => panic!("unexpected warning {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        fixups,
+        [
+            ("rte[0]", Invariant::NonEmptyRoute, FixupAction::Removed),
+            ("trk[0]/trkseg[0]", Invariant::NonEmptySegment, FixupAction::Removed),
+            ("trk[0]/trkseg[1]/trkpt[0]/link[0]", Invariant::LinkHref, FixupAction::Removed),
+            ("trk[0]/trkseg[1]/trkpt[0]", Invariant::FiniteElevation, FixupAction::Repaired),
+        ]
+    );
+    assert!(outcome.gpx.routes.is_empty());
+    let segments = &outcome.gpx.tracks[0].segments;
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].points[0].elevation, None);
+
+    let options = ParseOptions {
+        post_normalize: Some(InvariantPolicy::Error),
+        ..Default::default()
+    };
+    let result: Result<Gpx<EmptyExtensions>, GpxError> = read_with_options(data.as_bytes(), options);
+    assert!(matches!(result, Err(GpxError::InvariantViolation(_, Invariant::NonEmptyRoute))));
+
+    // Without the option, the document is kept as it was written.
+    let gpx: Gpx<EmptyExtensions> = read(data.as_bytes()).unwrap();
+    assert_eq!(gpx.tracks[0].segments.len(), 2);
+    assert!(gpx.tracks[0].segments[1].points[0].elevation.unwrap().is_nan());
+}
+
 #[test]
 fn gpx_reader_read_test_empty_elevation() {
     let file = File::open("tests/fixtures/wahoo_example.gpx").unwrap();
@@ -130,6 +1138,8 @@ fn gpx_reader_read_test_empty_elevation() {
     assert!(result.is_ok());
     let res = result.unwrap();
 
+    assert_eq!(res.tracks[0].coords_3d(0.0).concat().len(), 3);
+
     // Test for every single point in the file.
     for track in &res.tracks {
         for segment in &track.segments {
@@ -137,6 +1147,18 @@ fn gpx_reader_read_test_empty_elevation() {
                 let elevation = point.elevation.is_none();
                 assert!(elevation);
             }
+
+            // Missing elevations are filled in for xyz coordinates.
+            let coords = segment.coords_3d(0.0);
+            assert_eq!(coords.len(), segment.points.len());
+            assert!(coords.iter().all(|&coord| coord == [-121.933136, 45.454350, 0.0]));
+            assert!(segment.coords_3d(f64::NAN).iter().all(|coord| coord[2].is_nan()));
+
+            // Without any elevation, there is nothing to smooth or fill.
+            let mut cleaned = segment.clone();
+            cleaned.smooth_elevation(5);
+            assert_eq!(cleaned.fill_elevation_gaps(), 0);
+            assert_eq!(&cleaned, segment);
         }
     }
 }
@@ -173,11 +1195,18 @@ fn gpx_reader_read_test_garmin_activity() {
 
     assert_eq!(track.name, Some(String::from("casual stroll")));
     assert_eq!(track.type_, Some(String::from("running")));
+    assert_eq!(track.activity(), Some(Activity::Running));
 
     // Check some Geo operations on the track.
     let mls = track.multilinestring();
     assert_approx_eq!(mls.euclidean_length(), 0.12704048);
 
+    // The length helpers agree with geo's haversine length.
+    assert_approx_eq!(track.length_meters(), mls.haversine_length(), 1e-6);
+    assert!(track.length_meters() > 12_000. && track.length_meters() < 13_000.);
+    #[cfg(feature = "geodesic")]
+    assert_approx_eq!(track.length_geodesic_meters(), track.length_meters(), 0.005 * track.length_meters());
+
     // Get the first track segment.
     assert_eq!(track.segments.len(), 1);
     let segment = &track.segments[0];
@@ -259,6 +1288,7 @@ fn gpx_reader_read_test_lovers_lane() {
         Some(String::from("Trail Planner Map on AllTrails"))
     );
     assert_eq!(routes[0].points.len(), 139);
+    assert_eq!(routes[0].points_count(), 139);
 
     // Test for every single point in the file.
     for point in routes[0].points.iter() {
@@ -284,6 +1314,27 @@ fn gpx_reader_read_test_lovers_lane() {
     }
 }
 
+#[test]
+fn gpx_reader_lovers_lane_route_as_path() {
+    let gpx = read(BufReader::new(File::open("tests/fixtures/ecology-trail-and-lovers-lane-loop.gpx").unwrap())).unwrap();
+    assert!(gpx.tracks.is_empty() && gpx.waypoints.is_empty());
+    let route = &gpx.routes[0];
+
+    assert_approx_eq!(route.length_meters(), 3210.13, 0.01);
+    // All of its points have an elevation.
+    assert!(route.length_3d_meters() > route.length_meters());
+    let stats = route.stats();
+    assert_eq!(stats.distance, route.length_meters());
+    assert_eq!(stats.elapsed_time, None);
+
+    // The route is all the document holds.
+    assert_eq!(gpx.iter_points().count(), 139);
+    assert!(gpx.iter_points().eq(&route.points));
+    let bounds = gpx.compute_bounds().unwrap();
+    assert_eq!(bounds.min(), coord! { x: -122.4607, y: 37.79147 });
+    assert_eq!(bounds.max(), coord! { x: -122.4485, y: 37.79807 });
+}
+
 #[test]
 fn gpx_reader_read_test_with_accuracy() {
     let file = File::open("tests/fixtures/with_accuracy.gpx").unwrap();
@@ -391,6 +1442,9 @@ fn gpx_reader_read_test_caltopo_export() -> Result<(), Box<dyn Error>> {
     let reader = BufReader::new(file);
     let res = read(reader)?;
     assert_eq!(res.tracks.len(), 2);
+    assert_eq!(res.tracks[0].points_count(), 3);
+    assert_eq!(res.tracks[1].points_count(), 3);
+    assert_eq!(res.points_count(), 6);
 
     // ensure day 1 tracks are parsed
     let track = &res.tracks[0];
@@ -443,6 +1497,47 @@ fn gpx_reader_read_test_caltopo_export() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[test]
+fn gpx_reader_tracks_caltopo() {
+    let gpx = read(BufReader::new(File::open("tests/fixtures/caltopo-export.gpx").unwrap())).unwrap();
+
+    let file = File::open("tests/fixtures/caltopo-export.gpx").unwrap();
+    let mut tracks: GpxTrackIter<_, EmptyExtensions> = gpx::tracks(BufReader::new(file)).unwrap();
+    assert_eq!(tracks.metadata(), gpx.metadata.as_ref());
+    let mut count = 0;
+    let points: usize = tracks
+        .by_ref()
+        .map(|track| {
+            count += 1;
+            track.unwrap().points_count()
+        })
+        .sum();
+    assert_eq!(count, gpx.tracks.len());
+    assert_eq!(points, gpx.tracks.iter().map(|track| track.points_count()).sum::<usize>());
+    assert_eq!(tracks.waypoints(), gpx.waypoints.as_slice());
+}
+
+#[test]
+fn gpx_reader_time_span_caltopo() {
+    let file = File::open("tests/fixtures/caltopo-export.gpx").unwrap();
+    let gpx = read(BufReader::new(file)).unwrap();
+
+    for track in &gpx.tracks {
+        let segment = &track.segments[0];
+        let first = segment.points.first().unwrap().time.unwrap().into();
+        let last = segment.points.last().unwrap().time.unwrap().into();
+        assert_eq!(segment.time_span(), Some((first, last)));
+        assert_eq!(segment.duration(), Some(last - first));
+    }
+
+    // Every point of the first day has the same time.
+    assert_eq!(gpx.tracks[0].segments[0].duration(), Some(time::Duration::ZERO));
+    let duration = time::Duration::hours(4) + time::Duration::minutes(55);
+    assert_eq!(gpx.tracks[1].segments[0].duration(), Some(duration));
+
+    assert_eq!(TrackSegment::<EmptyExtensions>::new().time_span(), None);
+}
+
 #[test]
 fn garmin_with_extensions() {
     // Should not give an error, and should have all the correct data.
@@ -526,6 +1621,7 @@ fn outdooractive_export() {
     let track = &result.tracks[0];
 
     assert_eq!(track.name, Some("Kilimanjaro - Machame Route".to_owned()));
+    assert_eq!(track.activity(), Some(Activity::Mountaineering));
 
     // Each point has its own information; test elevation.
     assert_eq!(track.segments.len(), 1);
@@ -534,3 +1630,121 @@ fn outdooractive_export() {
     assert_eq!(points.len(), 9);
     assert_eq!(points[0].point().y(), -3.173433);
 }
+
+#[test]
+fn gpx_canonical_order() {
+    let mut gpx: Gpx<EmptyExtensions> = Gpx::default();
+    for name in [
+        "caltopo-export",
+        "styled_tracks",
+        "marine_waypoint",
+        "mousehole_to_paul",
+        "viking_with_route_extensions",
+        "ecology-trail-and-lovers-lane-loop",
+        "garmin-activity",
+    ] {
+        let file = File::open(format!("tests/fixtures/{}.gpx", name)).unwrap();
+        let fixture = read(BufReader::new(file)).unwrap();
+        gpx.waypoints.extend(fixture.waypoints);
+        gpx.routes.extend(fixture.routes);
+        gpx.tracks.extend(fixture.tracks);
+    }
+    let original = gpx.clone();
+
+    // Shuffle the collections.
+    gpx.waypoints.reverse();
+    gpx.waypoints.rotate_left(2);
+    gpx.routes.rotate_left(1);
+    gpx.tracks.reverse();
+    gpx.tracks.rotate_left(3);
+
+    gpx.canonical_order();
+    let waypoints: Vec<_> = gpx.waypoints.iter().map(|waypoint| waypoint.name.as_deref().unwrap()).collect();
+    assert_eq!(
+        waypoints,
+        ["001", "Anchorage", "Ferry Building", "Fuel dock", "Harbour entrance", "N Cliff", "The Church Of Paul"]
+    );
+    let routes: Vec<_> = gpx.routes.iter().map(|route| route.name.as_deref()).collect();
+    assert_eq!(routes, [Some("Route"), Some("Trail Planner Map on AllTrails"), None]);
+    // Timed tracks by start, then the others in the order they were in.
+    let tracks: Vec<_> = gpx.tracks.iter().map(|track| track.name.as_deref().unwrap()).collect();
+    assert_eq!(
+        tracks,
+        [
+            "casual stroll",
+            "Day 01",
+            "Day 02",
+            "Plain walk",
+            "Morning run",
+            "Evening ride",
+            "Trace",
+            "N Cliff to The Church Of Paul"
+        ]
+    );
+
+    // Points keep their order.
+    for track in &gpx.tracks {
+        assert!(original.tracks.contains(track));
+    }
+
+    let canonical = gpx.clone();
+    gpx.canonical_order();
+    assert_eq!(gpx, canonical);
+}
+
+#[test]
+fn gpx_reader_fuzz_fixtures() {
+    // Inputs the fuzz targets found crashing the reader, which must now give
+    // a document or an error, in both modes and with any extensions.
+    let mut fixtures: Vec<_> = std::fs::read_dir("tests/fixtures/fuzz")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty());
+    for path in fixtures {
+        let mut data = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut data).unwrap();
+        let _ = read(data.as_slice());
+        for lenient in with_xml_parsers(&[ParseOptions::lenient().with_entity_repair()]) {
+            let _ = read_with_options::<_, GarminExtensions>(data.as_slice(), lenient.clone());
+            let _ = read_with_options::<_, RawExtensions>(data.as_slice(), lenient);
+        }
+    }
+
+    // The UTC time of a time in range at its own offset can be out of range.
+    let path = "tests/fixtures/fuzz/time_out_of_range_in_utc.gpx";
+    let result = read(BufReader::new(File::open(path).unwrap()));
+    assert!(matches!(result, Err(GpxError::Iso8601Error(_))), "{:?}", result);
+    let gpx: Gpx<EmptyExtensions> =
+        read_with_options(BufReader::new(File::open(path).unwrap()), ParseOptions::lenient()).unwrap();
+    assert_eq!(gpx.metadata.unwrap().time, None);
+    assert!(gpx.waypoints[0].time.is_some());
+}
+
+#[cfg(feature = "quick-xml")]
+#[test]
+fn gpx_reader_quick_xml_reads_fixtures() {
+    // Both XML parsers give the same documents, warnings and kinds of error.
+    let mut fixtures: Vec<_> = std::fs::read_dir("tests/fixtures")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |extension| extension == "gpx" || extension == "xml"))
+        .collect();
+    fixtures.sort();
+    for path in fixtures {
+        let mut data = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut data).unwrap();
+        for options in [ParseOptions::strict(), ParseOptions::lenient().with_comments()] {
+            let read = |parser| {
+                read_with_warnings::<_, RawExtensions>(data.as_slice(), options.clone().with_xml_parser(parser))
+                    .map(|outcome| {
+                        let warnings: Vec<_> = outcome.warnings.into_iter().map(|warning| warning.warning).collect();
+                        (outcome.gpx, warnings)
+                    })
+                    .map_err(|error| error.kind())
+            };
+            assert_eq!(read(XmlParser::QuickXml), read(XmlParser::XmlRs), "in {}", path.display());
+        }
+    }
+}
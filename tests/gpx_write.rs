@@ -1,9 +1,15 @@
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Read, Write};
 
-use gpx::{read, write};
-use gpx::{Gpx, Link, Waypoint};
-use gpx::parser::extensions::EmptyExtensions;
+use geo_types::Point;
+use time::{Duration, OffsetDateTime};
+use xml::writer::{EventWriter, XmlEvent};
+
+use gpx::{read, read_with_extensions, write, write_with_config};
+use gpx::{Gpx, GpxVersion, Link, SchemaViolation, TimePrecision, Track, TrackSegment, ViolationKind, Waypoint, WriterConfig};
+use gpx::errors::{GpxError, GpxResult};
+use gpx::parser::Context;
+use gpx::parser::extensions::{EmptyExtensions, GarminTrackPoint, GarminTrackPointExtensions, RawExtensions, WaypointExtensions};
 
 #[test]
 fn gpx_writer_write_unknown_gpx_version() {
@@ -36,6 +42,332 @@ fn gpx_writer_write_test_outdooractive_export() {
     check_write_for_example_file("tests/fixtures/outdooractive-export.gpx");
 }
 
+/// The text between the first `start` tag and the following `</extensions>`.
+fn extensions_content<'a>(xml: &'a str, start: &str) -> &'a str {
+    let open = xml.find(start).unwrap();
+    let content = open + xml[open..].find('>').unwrap() + 1;
+    let close = content + xml[content..].find("</extensions>").unwrap();
+    &xml[content..close]
+}
+
+#[test]
+fn gpx_writer_raw_extensions_round_trip() {
+    let original = std::fs::read_to_string("tests/fixtures/raw_extensions.gpx").unwrap();
+    let expected = extensions_content(&original, "<extensions>");
+    assert!(expected.contains("\r\n") && expected.contains("<![CDATA["));
+
+    let gpx: Gpx<RawExtensions> = read_with_extensions(original.as_bytes()).unwrap();
+    let points = &gpx.tracks[0].segments[0].points;
+    assert_eq!(points[0].extensions.xml, expected);
+    // `cad` is declared within the extensions themselves.
+    assert_eq!(
+        points[0].extensions.namespaces.keys().collect::<Vec<_>>(),
+        ["gpxtpx"]
+    );
+    assert_eq!(points[1].extensions.xml, "");
+
+    let mut written = Vec::new();
+    write(&gpx, &mut written).unwrap();
+    let written = String::from_utf8(written).unwrap();
+    assert_eq!(extensions_content(&written, "<extensions"), expected);
+
+    let reread: Gpx<RawExtensions> = read_with_extensions(written.as_bytes()).unwrap();
+    assert_eq!(reread.tracks[0].segments[0].points, *points);
+}
+
+#[test]
+fn gpx_writer_garmin_heart_rate_round_trip() {
+    let mut point: Waypoint<GarminTrackPointExtensions> = Waypoint::new(Point::new(8.5, 47.3));
+    point.extensions = GarminTrackPoint {
+        heart_rate: Some(152),
+        ..Default::default()
+    };
+    let mut segment = TrackSegment::new();
+    segment.points.push(point);
+    // A point without extensions gets no element.
+    segment.points.push(Waypoint::new(Point::new(8.6, 47.4)));
+    let mut track = Track::new();
+    track.segments.push(segment);
+    let gpx = Gpx {
+        version: GpxVersion::Gpx11,
+        tracks: vec![track],
+        ..Default::default()
+    };
+
+    let mut written = Vec::new();
+    write(&gpx, &mut written).unwrap();
+    let written = String::from_utf8(written).unwrap();
+    assert!(written.contains("<gpxtpx:hr>152</gpxtpx:hr>"), "{}", written);
+    assert_eq!(written.matches("<extensions>").count(), 1);
+
+    let reread: Gpx<GarminTrackPointExtensions> = read_with_extensions(written.as_bytes()).unwrap();
+    assert_eq!(reread.tracks[0].segments[0].points, gpx.tracks[0].segments[0].points);
+}
+
+#[test]
+fn gpx_writer_write_gpx10_round_trip() {
+    let reference_gpx = read_test_gpx_file("tests/fixtures/gpx10_example.gpx");
+    assert_eq!(reference_gpx.version, GpxVersion::Gpx10);
+
+    let written_gpx = write_and_reread_gpx_with_config(&reference_gpx, GpxVersion::Gpx10);
+
+    assert_eq!(written_gpx.version, GpxVersion::Gpx10);
+    assert_eq!(reference_gpx.metadata, written_gpx.metadata);
+    check_points_equal(&reference_gpx, &written_gpx);
+    assert_eq!(written_gpx.waypoints[0].course, Some(182.5));
+    assert_eq!(written_gpx.waypoints[0].speed, Some(1.4));
+    assert_eq!(written_gpx.waypoints[0].links[0].text.as_deref(), Some("The pier"));
+    assert_eq!(written_gpx.tracks[0].number, Some(1));
+}
+
+#[test]
+fn gpx_writer_write_gpx11_as_gpx10() {
+    let reference_gpx = read_test_gpx_file("tests/fixtures/wikipedia_example.gpx");
+    assert_eq!(reference_gpx.version, GpxVersion::Gpx11);
+
+    let written_gpx = write_and_reread_gpx_with_config(&reference_gpx, GpxVersion::Gpx10);
+
+    assert_eq!(written_gpx.version, GpxVersion::Gpx10);
+    check_points_equal(&reference_gpx, &written_gpx);
+    assert_eq!(reference_gpx.metadata.unwrap().time, written_gpx.metadata.unwrap().time);
+}
+
+#[test]
+fn gpx_writer_validate_output_fixtures() {
+    let config = WriterConfig {
+        version: GpxVersion::Gpx11,
+        validate_output: true,
+        ..Default::default()
+    };
+    let mut refused = Vec::new();
+    for entry in std::fs::read_dir("tests/fixtures").unwrap() {
+        let path = entry.unwrap().path();
+        let gpx: Gpx<RawExtensions> = match read_with_extensions(BufReader::new(File::open(&path).unwrap())) {
+            Ok(gpx) => gpx,
+            // Some fixtures are deliberately broken.
+            Err(_) => continue,
+        };
+        let mut buffer = Vec::new();
+        match write_with_config(&gpx, &mut buffer, &config) {
+            Ok(()) => assert!(!buffer.is_empty()),
+            Err(GpxError::SchemaViolations(violations)) => refused.push((path, violations)),
+            Err(err) => panic!("{}: {:?}", path.display(), err),
+        }
+    }
+
+    // Only a fix type the schema does not know is refused.
+    assert_eq!(refused.len(), 1);
+    assert!(refused[0].0.ends_with("with_accuracy.gpx"));
+    assert_eq!(
+        refused[0].1,
+        [SchemaViolation {
+            path: "/gpx/trk[1]/trkseg[1]/trkpt[3]/fix[1]".into(),
+            kind: ViolationKind::InvalidValue {
+                expected: "fixType",
+                value: "something_not_in_the_spec".into(),
+            },
+        }]
+    );
+}
+
+/// Extensions that write a second `<name>`, as a buggy extension might.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct DuplicateName;
+
+impl WaypointExtensions for DuplicateName {
+    type ExtensionsValue = ();
+
+    fn consume<R: Read, E: WaypointExtensions + Default>(_context: &mut Context<R, E>) -> GpxResult<()> {
+        Ok(())
+    }
+
+    fn write<W: Write>(_value: &(), writer: &mut EventWriter<W>) -> GpxResult<()> {
+        writer.write(XmlEvent::start_element("name"))?;
+        writer.write(XmlEvent::characters("again"))?;
+        writer.write(XmlEvent::end_element())?;
+        Ok(())
+    }
+}
+
+#[test]
+fn gpx_writer_validate_output_refuses_invalid() {
+    let mut gpx: Gpx<DuplicateName> = Gpx {
+        version: GpxVersion::Gpx11,
+        ..Default::default()
+    };
+    let mut waypoint = Waypoint::new(Point::new(2.0, 95.0));
+    waypoint.name = Some("once".into());
+    gpx.waypoints.push(waypoint);
+
+    // Without validation the document is written regardless.
+    let mut buffer = Vec::new();
+    write(&gpx, &mut buffer).unwrap();
+
+    let config = WriterConfig {
+        validate_output: true,
+        ..Default::default()
+    };
+    let mut buffer = Vec::new();
+    let violations = match write_with_config(&gpx, &mut buffer, &config) {
+        Err(GpxError::SchemaViolations(violations)) => violations,
+        other => panic!("expected violations, got {:?}", other),
+    };
+    assert!(buffer.is_empty());
+    assert_eq!(
+        violations,
+        [
+            SchemaViolation {
+                path: "/gpx/wpt[1]/@lat".into(),
+                kind: ViolationKind::InvalidValue {
+                    expected: "latitudeType",
+                    value: "95".into(),
+                },
+            },
+            SchemaViolation {
+                path: "/gpx/wpt[1]/name[2]".into(),
+                kind: ViolationKind::TooMany("name".into()),
+            },
+        ]
+    );
+
+    let config = WriterConfig {
+        version: GpxVersion::Gpx10,
+        validate_output: true,
+        ..Default::default()
+    };
+    assert!(matches!(
+        write_with_config(&gpx, &mut buffer, &config),
+        Err(GpxError::NoSchemaRules(GpxVersion::Gpx10))
+    ));
+}
+
+/// ten_hertz gives a track of points recorded ten times a second, with the
+/// last at a time that needs all nine digits.
+fn ten_hertz() -> Gpx<EmptyExtensions> {
+    let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    let mut segment = TrackSegment::new();
+    for i in 0..25 {
+        let mut point = Waypoint::new(Point::new(8.5 + f64::from(i) * 1e-5, 47.3));
+        point.time = Some((start + Duration::milliseconds(100 * i64::from(i))).into());
+        segment.points.push(point);
+    }
+    let mut point = Waypoint::new(Point::new(8.51, 47.3));
+    point.time = Some((start + Duration::nanoseconds(3_123_456_789)).into());
+    segment.points.push(point);
+    let mut track = Track::new();
+    track.segments.push(segment);
+    Gpx {
+        version: GpxVersion::Gpx11,
+        tracks: vec![track],
+        ..Default::default()
+    }
+}
+
+/// written_times writes `gpx` with `config`, giving the content of every
+/// `<time>`.
+fn written_times(gpx: &Gpx<EmptyExtensions>, config: &WriterConfig) -> Vec<String> {
+    let mut buffer = Vec::new();
+    write_with_config(gpx, &mut buffer, config).unwrap();
+    let xml = String::from_utf8(buffer).unwrap();
+    xml.split("<time>")
+        .skip(1)
+        .map(|rest| rest[..rest.find("</time>").unwrap()].to_string())
+        .collect()
+}
+
+#[test]
+fn gpx_writer_time_precision_auto() {
+    let gpx = ten_hertz();
+    let times = written_times(&gpx, &Default::default());
+
+    assert_eq!(times[0], "2023-11-14T22:13:20Z");
+    assert_eq!(times[1], "2023-11-14T22:13:20.1Z");
+    assert_eq!(times[25], "2023-11-14T22:13:23.123456789Z");
+
+    let mut buffer = Vec::new();
+    write(&gpx, &mut buffer).unwrap();
+    let reread: Gpx<EmptyExtensions> = read(buffer.as_slice()).unwrap();
+    assert_eq!(reread.tracks[0].segments[0].points, gpx.tracks[0].segments[0].points);
+}
+
+#[test]
+fn gpx_writer_time_precision_fixed() {
+    let gpx = ten_hertz();
+    let config = WriterConfig {
+        time_precision: TimePrecision::Fixed(3),
+        ..Default::default()
+    };
+    let times = written_times(&gpx, &config);
+
+    assert_eq!(times[0], "2023-11-14T22:13:20.000Z");
+    assert_eq!(times[1], "2023-11-14T22:13:20.100Z");
+    assert_eq!(times[25], "2023-11-14T22:13:23.123Z");
+    assert!(times.iter().all(|time| time.len() == "2023-11-14T22:13:20.000Z".len()));
+
+    // Rounding carries into the seconds.
+    let mut gpx = ten_hertz();
+    let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    gpx.tracks[0].segments[0].points[0].time = Some((start + Duration::nanoseconds(999_600_000)).into());
+    assert_eq!(written_times(&gpx, &config)[0], "2023-11-14T22:13:21.000Z");
+
+    let config = WriterConfig {
+        time_precision: TimePrecision::Fixed(0),
+        ..Default::default()
+    };
+    assert_eq!(written_times(&ten_hertz(), &config)[25], "2023-11-14T22:13:23Z");
+}
+
+/// Collects what is written to it, remembering the largest single write.
+#[derive(Default)]
+struct RecordingSink {
+    output: Vec<u8>,
+    writes: usize,
+    largest_write: usize,
+}
+
+impl Write for RecordingSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writes += 1;
+        self.largest_write = self.largest_write.max(buf.len());
+        self.output.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn gpx_writer_streams_large_documents() {
+    let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    let mut segment = TrackSegment::new();
+    for i in 0..100_000 {
+        let mut point = Waypoint::new(Point::new(8.5 + f64::from(i) * 1e-6, 47.3 - f64::from(i) * 1e-6));
+        point.elevation = Some(f64::from(i % 1000) / 4.0);
+        point.time = Some((start + Duration::seconds(i64::from(i))).into());
+        segment.points.push(point);
+    }
+    let mut track = Track::new();
+    track.segments.push(segment);
+    let gpx: Gpx<EmptyExtensions> = Gpx {
+        version: GpxVersion::Gpx11,
+        tracks: vec![track],
+        ..Default::default()
+    };
+
+    // The document goes out in pieces the size of an element at most, not
+    // serialized whole first.
+    let mut sink = RecordingSink::default();
+    write(&gpx, &mut sink).unwrap();
+    assert!(sink.output.len() > 10_000_000, "{} bytes", sink.output.len());
+    assert!(sink.writes > 100_000);
+    assert!(sink.largest_write < 1024, "largest write of {} bytes", sink.largest_write);
+
+    let reread: Gpx<EmptyExtensions> = read(sink.output.as_slice()).unwrap();
+    assert_eq!(reread.tracks[0].segments[0].points, gpx.tracks[0].segments[0].points);
+}
+
 fn check_write_for_example_file(filename: &str) {
     let reference_gpx = read_test_gpx_file(filename);
     let written_gpx = write_and_reread_gpx(&reference_gpx);
@@ -63,6 +395,17 @@ fn write_and_reread_gpx(reference_gpx: &Gpx<EmptyExtensions>) -> Gpx<EmptyExtens
     written_gpx
 }
 
+fn write_and_reread_gpx_with_config(reference_gpx: &Gpx<EmptyExtensions>, version: GpxVersion) -> Gpx<EmptyExtensions> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let config = WriterConfig {
+        version,
+        ..Default::default()
+    };
+    write_with_config(reference_gpx, &mut buffer, &config).unwrap();
+
+    read(buffer.as_slice()).unwrap()
+}
+
 fn check_metadata_equal(reference_gpx: &Gpx<EmptyExtensions>, written_gpx: &Gpx<EmptyExtensions>) {
     let reference = &reference_gpx.metadata;
     let written = &written_gpx.metadata;
@@ -79,7 +422,7 @@ fn check_metadata_equal(reference_gpx: &Gpx<EmptyExtensions>, written_gpx: &Gpx<
     check_links_equal(&reference.links, &written.links);
 }
 
-fn check_links_equal(reference: &Vec<Link>, written: &Vec<Link>) {
+fn check_links_equal(reference: &[Link], written: &[Link]) {
     assert_eq!(reference.len(), written.len());
     for (r, w) in reference.iter().zip(written) {
         assert_eq!(r.href, w.href);
@@ -99,7 +442,7 @@ fn check_points_equal(reference: &Gpx<EmptyExtensions>, written: &Gpx<EmptyExten
     }
 }
 
-fn check_waypoints_equal(reference: &Vec<Waypoint<EmptyExtensions>>, written: &Vec<Waypoint<EmptyExtensions>>) {
+fn check_waypoints_equal(reference: &[Waypoint<EmptyExtensions>], written: &[Waypoint<EmptyExtensions>]) {
     assert_eq!(reference.len(), written.len());
     for (r_wp, w_wp) in reference.iter().zip(written) {
         assert_eq!(r_wp.point(), w_wp.point());
@@ -0,0 +1,142 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use xml::{EventReader, ParserConfig};
+
+use gpx::parser::extensions::EmptyExtensions;
+use gpx::{read_with_options, Gpx, ParseOptions};
+
+/// Counts the allocations made by every thread, so the allocations of a call
+/// are the count after it minus the count before, whatever the tests running
+/// alongside it allocate.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count() {
+    // The count is gone while the thread is torn down.
+    let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Gives the allocations made by `f`.
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// track gives a document of `points` track points with the usual leaf
+/// elements.
+fn track(points: usize) -> String {
+    let mut gpx = String::from(r#"<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1"><trk><trkseg>"#);
+    for point in 0..points {
+        gpx.push_str(&format!(
+            r#"<trkpt lat="47.{0:04}" lon="8.{0:04}"><ele>{0}.5</ele><time>2020-01-01T00:00:00Z</time><hdop>1.2</hdop><sat>7</sat></trkpt>"#,
+            point
+        ));
+    }
+    gpx.push_str("</trkseg></trk></gpx>");
+    gpx
+}
+
+/// per_point gives the allocations `read` makes for every point, from the
+/// difference between reading documents of 1000 and 2000 points, which
+/// leaves out those made once per document.
+fn per_point(read: impl Fn(&[u8])) -> f64 {
+    let (short, long) = (track(1000), track(2000));
+    let short = allocations(|| read(short.as_bytes()));
+    let long = allocations(|| read(long.as_bytes()));
+    (long as f64 - short as f64) / 1000.0
+}
+
+#[test]
+fn parse_allocates_only_the_events() {
+    // The XML parser allocates the names and character data of every event,
+    // parsing should not add to them.
+    let events = per_point(|data| {
+        let config = ParserConfig {
+            whitespace_to_characters: true,
+            cdata_to_characters: true,
+            ignore_comments: true,
+            ..ParserConfig::new()
+        };
+        for event in EventReader::new_with_config(data, config) {
+            event.unwrap();
+        }
+    });
+    for options in [ParseOptions::strict(), ParseOptions::lenient()] {
+        let parse = per_point(|data| {
+            let _: Gpx<EmptyExtensions> = read_with_options(data, options.clone()).unwrap();
+        });
+        assert!(
+            parse < events + 0.1,
+            "parsing made {} allocations per point, against {} for the events",
+            parse,
+            events
+        );
+    }
+}
+
+#[test]
+fn capacity_hint_reserves_points_at_once() {
+    let data = track(2000);
+    let read = |options: ParseOptions| -> Gpx<EmptyExtensions> { read_with_options(data.as_bytes(), options).unwrap() };
+
+    let mut grown = None;
+    let growing = allocations(|| grown = Some(read(ParseOptions::strict())));
+    for hint in [2000, 5000] {
+        let mut reserved = None;
+        let reserving = allocations(|| reserved = Some(read(ParseOptions::strict().with_points_capacity_hint(hint))));
+        let reserved = reserved.unwrap();
+        assert_eq!(Some(&reserved), grown.as_ref());
+        assert_eq!(reserved.tracks[0].segments[0].points.capacity(), 2000);
+        // Growing point by point to 2000 takes 10 allocations, from 4 to
+        // 2048, reserving takes one, and shrinking to fit one more.
+        assert!(
+            reserving + 8 <= growing,
+            "reserving {} points made {} allocations, against {} growing",
+            hint,
+            reserving,
+            growing
+        );
+    }
+}
+
+#[test]
+fn capacity_hint_reserves_once() {
+    let data = track(10).replace("</trkseg>", "</trkseg><trkseg><trkpt lat=\"1\" lon=\"2\"/></trkseg>");
+    let options = ParseOptions {
+        points_capacity_hint: Some(5000),
+        ..ParseOptions::strict()
+    };
+    let gpx: Gpx<EmptyExtensions> = read_with_options(data.as_bytes(), options).unwrap();
+    let segments = &gpx.tracks[0].segments;
+    assert_eq!(segments[0].points.capacity(), 5000);
+    assert!(segments[1].points.capacity() < 10);
+
+    // A hint beyond what memory holds reserves what it can, if anything.
+    let options = ParseOptions::strict().with_points_capacity_hint(usize::MAX);
+    let gpx: Gpx<EmptyExtensions> = read_with_options(data.as_bytes(), options).unwrap();
+    assert_eq!(gpx.tracks[0].segments[0].points.len(), 10);
+}